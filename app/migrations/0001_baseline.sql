@@ -0,0 +1,308 @@
+-- Baseline: the schema as it stood when versioned migrations were introduced.
+-- Databases created before then already have some of these tables, so every
+-- statement here is IF NOT EXISTS. Later migrations don't need to be.
+
+CREATE TABLE IF NOT EXISTS movies (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    tmdb_id INTEGER UNIQUE NOT NULL,
+    title TEXT NOT NULL,
+    overview TEXT,
+    poster_path TEXT,
+    backdrop_path TEXT,
+    release_date TEXT,
+    vote_average REAL DEFAULT 0,
+    vote_count INTEGER DEFAULT 0,
+    genres TEXT DEFAULT '[]',
+    runtime INTEGER,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    updated_at DATETIME DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE IF NOT EXISTS tv_shows (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    tmdb_id INTEGER UNIQUE NOT NULL,
+    name TEXT NOT NULL,
+    overview TEXT,
+    poster_path TEXT,
+    backdrop_path TEXT,
+    first_air_date TEXT,
+    vote_average REAL DEFAULT 0,
+    vote_count INTEGER DEFAULT 0,
+    genres TEXT DEFAULT '[]',
+    number_of_seasons INTEGER,
+    number_of_episodes INTEGER,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    updated_at DATETIME DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE IF NOT EXISTS seasons (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    tmdb_id INTEGER UNIQUE NOT NULL,
+    show_id INTEGER NOT NULL,
+    season_number INTEGER NOT NULL,
+    name TEXT,
+    overview TEXT,
+    poster_path TEXT,
+    air_date TEXT,
+    episode_count INTEGER DEFAULT 0,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (show_id) REFERENCES tv_shows(id) ON DELETE CASCADE
+);
+
+CREATE TABLE IF NOT EXISTS episodes (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    tmdb_id INTEGER UNIQUE NOT NULL,
+    season_id INTEGER NOT NULL,
+    episode_number INTEGER NOT NULL,
+    name TEXT,
+    overview TEXT,
+    still_path TEXT,
+    air_date TEXT,
+    runtime INTEGER,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (season_id) REFERENCES seasons(id) ON DELETE CASCADE
+);
+
+-- Movies use season and episode 0. `options` is the embed query string,
+-- which changes the resolved URLs.
+CREATE TABLE IF NOT EXISTS streaming_cache (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    provider TEXT NOT NULL,
+    tmdb_id INTEGER NOT NULL,
+    media_type TEXT NOT NULL,
+    season_number INTEGER NOT NULL DEFAULT 0,
+    episode_number INTEGER NOT NULL DEFAULT 0,
+    options TEXT NOT NULL DEFAULT '',
+    streams TEXT NOT NULL,
+    expires_at DATETIME NOT NULL,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE(provider, tmdb_id, media_type, season_number, episode_number, options)
+);
+
+CREATE TABLE IF NOT EXISTS users (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    username TEXT UNIQUE NOT NULL,
+    password_hash TEXT NOT NULL,
+    is_admin BOOLEAN DEFAULT 0,
+    role TEXT NOT NULL DEFAULT 'member',
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE IF NOT EXISTS sessions (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    session_id TEXT UNIQUE NOT NULL,
+    user_id INTEGER NOT NULL,
+    username TEXT NOT NULL,
+    is_admin BOOLEAN DEFAULT 0,
+    role TEXT NOT NULL DEFAULT 'member',
+    expires_at INTEGER NOT NULL,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    last_seen_at INTEGER,
+    user_agent TEXT,
+    ip TEXT
+);
+
+CREATE TABLE IF NOT EXISTS watch_history (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id INTEGER NOT NULL,
+    tmdb_id INTEGER NOT NULL,
+    media_type TEXT NOT NULL,
+    title TEXT NOT NULL,
+    poster_path TEXT,
+    season_number INTEGER DEFAULT -1,
+    episode_number INTEGER DEFAULT -1,
+    episode_title TEXT,
+    progress_seconds INTEGER DEFAULT 0,
+    completed BOOLEAN DEFAULT 0,
+    watched_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE(user_id, tmdb_id, media_type, season_number, episode_number)
+);
+
+CREATE TABLE IF NOT EXISTS user_settings (
+    user_id INTEGER NOT NULL,
+    key TEXT NOT NULL,
+    value TEXT NOT NULL,
+    updated_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (user_id, key)
+);
+
+CREATE TABLE IF NOT EXISTS playback_events (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id INTEGER,
+    playback_id TEXT NOT NULL,
+    tmdb_id INTEGER NOT NULL,
+    media_type TEXT NOT NULL,
+    season_number INTEGER,
+    episode_number INTEGER,
+    provider TEXT,
+    kind TEXT NOT NULL,
+    position_seconds INTEGER DEFAULT 0,
+    detail TEXT,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE IF NOT EXISTS title_orphans (
+    tmdb_id INTEGER NOT NULL,
+    media_type TEXT NOT NULL,
+    title TEXT NOT NULL,
+    detected_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (tmdb_id, media_type)
+);
+
+CREATE TABLE IF NOT EXISTS tmdb_accounts (
+    user_id INTEGER PRIMARY KEY,
+    account_id TEXT NOT NULL,
+    access_token TEXT NOT NULL,
+    session_id TEXT NOT NULL,
+    v3_account_id INTEGER NOT NULL,
+    sync_enabled BOOLEAN DEFAULT 0,
+    last_synced_at DATETIME,
+    connected_at DATETIME DEFAULT CURRENT_TIMESTAMP
+);
+
+-- `synced` marks rows TMDB already knows about; unsynced rows are
+-- pushed on the next account sync.
+CREATE TABLE IF NOT EXISTS watchlist (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id INTEGER NOT NULL,
+    tmdb_id INTEGER NOT NULL,
+    media_type TEXT NOT NULL,
+    title TEXT NOT NULL,
+    poster_path TEXT,
+    synced BOOLEAN DEFAULT 0,
+    added_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE(user_id, tmdb_id, media_type)
+);
+
+CREATE TABLE IF NOT EXISTS ratings (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id INTEGER NOT NULL,
+    tmdb_id INTEGER NOT NULL,
+    media_type TEXT NOT NULL,
+    title TEXT NOT NULL,
+    rating REAL NOT NULL,
+    synced BOOLEAN DEFAULT 0,
+    rated_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE(user_id, tmdb_id, media_type)
+);
+
+-- Names from `actor:`/`director:` searches, lowercased, to TMDB person ids.
+CREATE TABLE IF NOT EXISTS person_ids (
+    name TEXT PRIMARY KEY,
+    tmdb_id INTEGER NOT NULL,
+    resolved_at DATETIME DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE IF NOT EXISTS provider_health (
+    name TEXT PRIMARY KEY,
+    healthy BOOLEAN NOT NULL,
+    consecutive_failures INTEGER DEFAULT 0,
+    last_checked_at DATETIME,
+    last_healthy_at DATETIME
+);
+
+-- Movies use season and episode 0, like `streaming_cache`.
+CREATE TABLE IF NOT EXISTS stream_reports (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id INTEGER,
+    provider TEXT NOT NULL,
+    tmdb_id INTEGER NOT NULL,
+    media_type TEXT NOT NULL,
+    season_number INTEGER NOT NULL DEFAULT 0,
+    episode_number INTEGER NOT NULL DEFAULT 0,
+    reason TEXT,
+    reported_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE SET NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_stream_reports_title ON stream_reports(tmdb_id, media_type, season_number, episode_number);
+
+-- Exactly one of `provider` and `url` is set. Movies use season and episode 0.
+CREATE TABLE IF NOT EXISTS stream_overrides (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    tmdb_id INTEGER NOT NULL,
+    media_type TEXT NOT NULL,
+    season_number INTEGER NOT NULL DEFAULT 0,
+    episode_number INTEGER NOT NULL DEFAULT 0,
+    provider TEXT,
+    url TEXT,
+    created_by INTEGER,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE(tmdb_id, media_type, season_number, episode_number),
+    FOREIGN KEY (created_by) REFERENCES users(id) ON DELETE SET NULL
+);
+
+CREATE TABLE IF NOT EXISTS provider_stats (
+    name TEXT PRIMARY KEY,
+    attempts INTEGER NOT NULL DEFAULT 0,
+    successes INTEGER NOT NULL DEFAULT 0,
+    total_latency_ms INTEGER NOT NULL DEFAULT 0,
+    last_latency_ms INTEGER NOT NULL DEFAULT 0,
+    updated_at DATETIME DEFAULT CURRENT_TIMESTAMP
+);
+
+-- `path` is relative to MEDIA_DIR. Movies use season and episode 0.
+CREATE TABLE IF NOT EXISTS local_media (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    tmdb_id INTEGER NOT NULL,
+    media_type TEXT NOT NULL,
+    season_number INTEGER NOT NULL DEFAULT 0,
+    episode_number INTEGER NOT NULL DEFAULT 0,
+    path TEXT NOT NULL,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE(tmdb_id, media_type, season_number, episode_number)
+);
+
+-- Runtime provider configuration from the admin area. `priority` NULL
+-- keeps the provider's own; `options` is a JSON object.
+CREATE TABLE IF NOT EXISTS provider_settings (
+    name TEXT PRIMARY KEY,
+    enabled BOOLEAN NOT NULL DEFAULT 1,
+    priority INTEGER,
+    options TEXT NOT NULL DEFAULT '{}',
+    updated_at DATETIME DEFAULT CURRENT_TIMESTAMP
+);
+
+-- Single-use registration codes. `expires_at` is a Unix timestamp; NULL never expires.
+CREATE TABLE IF NOT EXISTS invite_codes (
+    code TEXT PRIMARY KEY,
+    role TEXT NOT NULL DEFAULT 'member',
+    created_by INTEGER NOT NULL,
+    expires_at INTEGER,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    used_by INTEGER,
+    used_at DATETIME
+);
+
+-- Generated keys, such as the session secret when SESSION_SECRET is unset.
+CREATE TABLE IF NOT EXISTS app_secrets (
+    name TEXT PRIMARY KEY,
+    value TEXT NOT NULL,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP
+);
+
+-- Roles whose default permissions have been seeded; an admin may have
+-- changed them since, so seeding happens once per role.
+CREATE TABLE IF NOT EXISTS roles (
+    name TEXT PRIMARY KEY,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE IF NOT EXISTS role_permissions (
+    role TEXT NOT NULL,
+    permission TEXT NOT NULL,
+    PRIMARY KEY (role, permission)
+);
+
+-- One row per sign-in attempt: the audit trail and the lockout counter.
+CREATE TABLE IF NOT EXISTS login_attempts (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    username TEXT NOT NULL,
+    ip TEXT,
+    outcome TEXT NOT NULL,
+    attempted_at INTEGER NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_login_attempts_username ON login_attempts(username, attempted_at);
+
+CREATE INDEX IF NOT EXISTS idx_login_attempts_ip ON login_attempts(ip, attempted_at);
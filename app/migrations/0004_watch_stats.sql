@@ -0,0 +1,18 @@
+-- Watch time rolled up from `watch_history` by the stats job. Each history
+-- entry counts toward the UTC day it was last watched. Genres are kept
+-- apart from the totals because a title counts toward each of its genres.
+CREATE TABLE watch_stats_daily (
+    user_id INTEGER NOT NULL,
+    day TEXT NOT NULL,
+    seconds INTEGER NOT NULL,
+    titles INTEGER NOT NULL,
+    PRIMARY KEY (user_id, day)
+);
+
+CREATE TABLE watch_stats_genres (
+    user_id INTEGER NOT NULL,
+    day TEXT NOT NULL,
+    genre TEXT NOT NULL,
+    seconds INTEGER NOT NULL,
+    PRIMARY KEY (user_id, day, genre)
+);
@@ -0,0 +1,12 @@
+-- One row per distinct query a user has searched for. Queries differing only
+-- in case share a row, which keeps the most recent spelling.
+CREATE TABLE search_history (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id INTEGER NOT NULL,
+    query TEXT NOT NULL COLLATE NOCASE,
+    search_count INTEGER NOT NULL DEFAULT 1,
+    last_searched_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE(user_id, query)
+);
+
+CREATE INDEX idx_search_history_user_recent ON search_history(user_id, last_searched_at);
@@ -0,0 +1,16 @@
+-- In-app notifications, kept so browsers that reconnect or open later can
+-- catch up. `user_id` is NULL for notifications meant for every admin.
+-- Ids are millisecond timestamps, so they keep increasing across restarts.
+CREATE TABLE notifications (
+    id INTEGER PRIMARY KEY,
+    user_id INTEGER,
+    for_admins BOOLEAN NOT NULL DEFAULT 0,
+    kind TEXT NOT NULL,
+    title TEXT NOT NULL,
+    message TEXT NOT NULL,
+    url TEXT,
+    created_at TEXT NOT NULL,
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+);
+
+CREATE INDEX idx_notifications_user ON notifications(user_id, id);
@@ -0,0 +1,18 @@
+-- Per-day provider counters, so ranking and the admin stats cover recent
+-- days rather than everything since install. `provider_stats` keeps the
+-- all-time totals and the last latency.
+CREATE TABLE IF NOT EXISTS provider_stats_daily (
+    name TEXT NOT NULL,
+    day TEXT NOT NULL,
+    attempts INTEGER NOT NULL DEFAULT 0,
+    successes INTEGER NOT NULL DEFAULT 0,
+    total_latency_ms INTEGER NOT NULL DEFAULT 0,
+    playbacks INTEGER NOT NULL DEFAULT 0,
+    troubled_playbacks INTEGER NOT NULL DEFAULT 0,
+    PRIMARY KEY (name, day)
+);
+
+-- Existing totals count as today's, so they age out of the window.
+INSERT INTO provider_stats_daily (name, day, attempts, successes, total_latency_ms, playbacks, troubled_playbacks)
+SELECT name, date('now'), attempts, successes, total_latency_ms, playbacks, troubled_playbacks
+FROM provider_stats;
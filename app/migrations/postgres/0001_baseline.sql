@@ -0,0 +1,340 @@
+-- PostgreSQL counterpart of ../0001_baseline.sql.
+--
+-- Timestamps are TEXT in SQLite's `YYYY-MM-DD HH:MM:SS` UTC format, so they
+-- sort, compare, and decode the same as on SQLite. `datetime`, `date`, and
+-- the other functions below behave like SQLite's for the forms RustStream's
+-- queries use, so those queries run unchanged on both databases.
+
+-- datetime('now'), datetime(value), or either with a modifier such as
+-- '-7 days' or '+30 seconds'.
+CREATE FUNCTION datetime(value TEXT, modifier TEXT DEFAULT '0 seconds') RETURNS TEXT AS $$
+    SELECT to_char(
+        CASE WHEN value = 'now' THEN now() AT TIME ZONE 'UTC' ELSE value::timestamp END + modifier::interval,
+        'YYYY-MM-DD HH24:MI:SS'
+    )
+$$ LANGUAGE SQL STABLE;
+
+-- date('now'), date(value), or either with a modifier, as `YYYY-MM-DD`.
+CREATE FUNCTION date(value TEXT, modifier TEXT DEFAULT '0 seconds') RETURNS TEXT AS $$
+    SELECT left(datetime(value, modifier), 10)
+$$ LANGUAGE SQL STABLE;
+
+-- 1-based position of `needle` in `haystack`, or 0.
+CREATE FUNCTION instr(haystack TEXT, needle TEXT) RETURNS INTEGER AS $$
+    SELECT strpos(haystack, needle)
+$$ LANGUAGE SQL IMMUTABLE;
+
+-- The elements of a JSON array, as json_each(array) yields them.
+CREATE FUNCTION json_each(document TEXT) RETURNS TABLE (value JSON) AS $$
+    SELECT json_array_elements(document::json)
+$$ LANGUAGE SQL IMMUTABLE;
+
+-- json_extract(value, '$.key'): a top-level field of an object, as text.
+CREATE FUNCTION json_extract(document JSON, path TEXT) RETURNS TEXT AS $$
+    SELECT document ->> substr(path, 3)
+$$ LANGUAGE SQL IMMUTABLE;
+
+CREATE TABLE movies (
+    id BIGSERIAL PRIMARY KEY,
+    tmdb_id BIGINT UNIQUE NOT NULL,
+    title TEXT NOT NULL,
+    overview TEXT,
+    poster_path TEXT,
+    backdrop_path TEXT,
+    release_date TEXT,
+    vote_average DOUBLE PRECISION DEFAULT 0,
+    vote_count BIGINT DEFAULT 0,
+    genres TEXT DEFAULT '[]',
+    runtime BIGINT,
+    created_at TEXT DEFAULT datetime('now'),
+    updated_at TEXT DEFAULT datetime('now')
+);
+
+CREATE TABLE tv_shows (
+    id BIGSERIAL PRIMARY KEY,
+    tmdb_id BIGINT UNIQUE NOT NULL,
+    name TEXT NOT NULL,
+    overview TEXT,
+    poster_path TEXT,
+    backdrop_path TEXT,
+    first_air_date TEXT,
+    vote_average DOUBLE PRECISION DEFAULT 0,
+    vote_count BIGINT DEFAULT 0,
+    genres TEXT DEFAULT '[]',
+    number_of_seasons BIGINT,
+    number_of_episodes BIGINT,
+    created_at TEXT DEFAULT datetime('now'),
+    updated_at TEXT DEFAULT datetime('now')
+);
+
+CREATE TABLE seasons (
+    id BIGSERIAL PRIMARY KEY,
+    tmdb_id BIGINT UNIQUE NOT NULL,
+    show_id BIGINT NOT NULL,
+    season_number BIGINT NOT NULL,
+    name TEXT,
+    overview TEXT,
+    poster_path TEXT,
+    air_date TEXT,
+    episode_count BIGINT DEFAULT 0,
+    created_at TEXT DEFAULT datetime('now'),
+    FOREIGN KEY (show_id) REFERENCES tv_shows(id) ON DELETE CASCADE
+);
+
+CREATE TABLE episodes (
+    id BIGSERIAL PRIMARY KEY,
+    tmdb_id BIGINT UNIQUE NOT NULL,
+    season_id BIGINT NOT NULL,
+    episode_number BIGINT NOT NULL,
+    name TEXT,
+    overview TEXT,
+    still_path TEXT,
+    air_date TEXT,
+    runtime BIGINT,
+    created_at TEXT DEFAULT datetime('now'),
+    FOREIGN KEY (season_id) REFERENCES seasons(id) ON DELETE CASCADE
+);
+
+-- Movies use season and episode 0. `options` is the embed query string,
+-- which changes the resolved URLs.
+CREATE TABLE streaming_cache (
+    id BIGSERIAL PRIMARY KEY,
+    provider TEXT NOT NULL,
+    tmdb_id BIGINT NOT NULL,
+    media_type TEXT NOT NULL,
+    season_number BIGINT NOT NULL DEFAULT 0,
+    episode_number BIGINT NOT NULL DEFAULT 0,
+    options TEXT NOT NULL DEFAULT '',
+    streams TEXT NOT NULL,
+    expires_at TEXT NOT NULL,
+    created_at TEXT DEFAULT datetime('now'),
+    UNIQUE(provider, tmdb_id, media_type, season_number, episode_number, options)
+);
+
+CREATE TABLE users (
+    id BIGSERIAL PRIMARY KEY,
+    username TEXT UNIQUE NOT NULL,
+    password_hash TEXT NOT NULL,
+    is_admin BOOLEAN DEFAULT FALSE,
+    role TEXT NOT NULL DEFAULT 'member',
+    created_at TEXT DEFAULT datetime('now')
+);
+
+CREATE TABLE sessions (
+    id BIGSERIAL PRIMARY KEY,
+    session_id TEXT UNIQUE NOT NULL,
+    user_id BIGINT NOT NULL,
+    username TEXT NOT NULL,
+    is_admin BOOLEAN DEFAULT FALSE,
+    role TEXT NOT NULL DEFAULT 'member',
+    expires_at BIGINT NOT NULL,
+    created_at TEXT DEFAULT datetime('now'),
+    last_seen_at BIGINT,
+    user_agent TEXT,
+    ip TEXT
+);
+
+CREATE TABLE watch_history (
+    id BIGSERIAL PRIMARY KEY,
+    user_id BIGINT NOT NULL,
+    tmdb_id BIGINT NOT NULL,
+    media_type TEXT NOT NULL,
+    title TEXT NOT NULL,
+    poster_path TEXT,
+    season_number BIGINT DEFAULT -1,
+    episode_number BIGINT DEFAULT -1,
+    episode_title TEXT,
+    progress_seconds BIGINT DEFAULT 0,
+    completed BOOLEAN DEFAULT FALSE,
+    watched_at TEXT DEFAULT datetime('now'),
+    UNIQUE(user_id, tmdb_id, media_type, season_number, episode_number)
+);
+
+CREATE TABLE user_settings (
+    user_id BIGINT NOT NULL,
+    key TEXT NOT NULL,
+    value TEXT NOT NULL,
+    updated_at TEXT DEFAULT datetime('now'),
+    PRIMARY KEY (user_id, key)
+);
+
+CREATE TABLE playback_events (
+    id BIGSERIAL PRIMARY KEY,
+    user_id BIGINT,
+    playback_id TEXT NOT NULL,
+    tmdb_id BIGINT NOT NULL,
+    media_type TEXT NOT NULL,
+    season_number BIGINT,
+    episode_number BIGINT,
+    provider TEXT,
+    kind TEXT NOT NULL,
+    position_seconds BIGINT DEFAULT 0,
+    detail TEXT,
+    created_at TEXT DEFAULT datetime('now')
+);
+
+CREATE TABLE title_orphans (
+    tmdb_id BIGINT NOT NULL,
+    media_type TEXT NOT NULL,
+    title TEXT NOT NULL,
+    detected_at TEXT DEFAULT datetime('now'),
+    PRIMARY KEY (tmdb_id, media_type)
+);
+
+CREATE TABLE tmdb_accounts (
+    user_id BIGINT PRIMARY KEY,
+    account_id TEXT NOT NULL,
+    access_token TEXT NOT NULL,
+    session_id TEXT NOT NULL,
+    v3_account_id BIGINT NOT NULL,
+    sync_enabled BOOLEAN DEFAULT FALSE,
+    last_synced_at TEXT,
+    connected_at TEXT DEFAULT datetime('now')
+);
+
+-- `synced` marks rows TMDB already knows about; unsynced rows are
+-- pushed on the next account sync.
+CREATE TABLE watchlist (
+    id BIGSERIAL PRIMARY KEY,
+    user_id BIGINT NOT NULL,
+    tmdb_id BIGINT NOT NULL,
+    media_type TEXT NOT NULL,
+    title TEXT NOT NULL,
+    poster_path TEXT,
+    synced BOOLEAN DEFAULT FALSE,
+    added_at TEXT DEFAULT datetime('now'),
+    UNIQUE(user_id, tmdb_id, media_type)
+);
+
+CREATE TABLE ratings (
+    id BIGSERIAL PRIMARY KEY,
+    user_id BIGINT NOT NULL,
+    tmdb_id BIGINT NOT NULL,
+    media_type TEXT NOT NULL,
+    title TEXT NOT NULL,
+    rating DOUBLE PRECISION NOT NULL,
+    synced BOOLEAN DEFAULT FALSE,
+    rated_at TEXT DEFAULT datetime('now'),
+    UNIQUE(user_id, tmdb_id, media_type)
+);
+
+-- Names from `actor:`/`director:` searches, lowercased, to TMDB person ids.
+CREATE TABLE person_ids (
+    name TEXT PRIMARY KEY,
+    tmdb_id BIGINT NOT NULL,
+    resolved_at TEXT DEFAULT datetime('now')
+);
+
+CREATE TABLE provider_health (
+    name TEXT PRIMARY KEY,
+    healthy BOOLEAN NOT NULL,
+    consecutive_failures BIGINT DEFAULT 0,
+    last_checked_at TEXT,
+    last_healthy_at TEXT
+);
+
+-- Movies use season and episode 0, like `streaming_cache`.
+CREATE TABLE stream_reports (
+    id BIGSERIAL PRIMARY KEY,
+    user_id BIGINT,
+    provider TEXT NOT NULL,
+    tmdb_id BIGINT NOT NULL,
+    media_type TEXT NOT NULL,
+    season_number BIGINT NOT NULL DEFAULT 0,
+    episode_number BIGINT NOT NULL DEFAULT 0,
+    reason TEXT,
+    reported_at TEXT DEFAULT datetime('now'),
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE SET NULL
+);
+
+CREATE INDEX idx_stream_reports_title ON stream_reports(tmdb_id, media_type, season_number, episode_number);
+
+-- Exactly one of `provider` and `url` is set. Movies use season and episode 0.
+CREATE TABLE stream_overrides (
+    id BIGSERIAL PRIMARY KEY,
+    tmdb_id BIGINT NOT NULL,
+    media_type TEXT NOT NULL,
+    season_number BIGINT NOT NULL DEFAULT 0,
+    episode_number BIGINT NOT NULL DEFAULT 0,
+    provider TEXT,
+    url TEXT,
+    created_by BIGINT,
+    created_at TEXT DEFAULT datetime('now'),
+    UNIQUE(tmdb_id, media_type, season_number, episode_number),
+    FOREIGN KEY (created_by) REFERENCES users(id) ON DELETE SET NULL
+);
+
+CREATE TABLE provider_stats (
+    name TEXT PRIMARY KEY,
+    attempts BIGINT NOT NULL DEFAULT 0,
+    successes BIGINT NOT NULL DEFAULT 0,
+    total_latency_ms BIGINT NOT NULL DEFAULT 0,
+    last_latency_ms BIGINT NOT NULL DEFAULT 0,
+    updated_at TEXT DEFAULT datetime('now')
+);
+
+-- `path` is relative to MEDIA_DIR. Movies use season and episode 0.
+CREATE TABLE local_media (
+    id BIGSERIAL PRIMARY KEY,
+    tmdb_id BIGINT NOT NULL,
+    media_type TEXT NOT NULL,
+    season_number BIGINT NOT NULL DEFAULT 0,
+    episode_number BIGINT NOT NULL DEFAULT 0,
+    path TEXT NOT NULL,
+    created_at TEXT DEFAULT datetime('now'),
+    UNIQUE(tmdb_id, media_type, season_number, episode_number)
+);
+
+-- Runtime provider configuration from the admin area. `priority` NULL
+-- keeps the provider's own; `options` is a JSON object.
+CREATE TABLE provider_settings (
+    name TEXT PRIMARY KEY,
+    enabled BOOLEAN NOT NULL DEFAULT TRUE,
+    priority INTEGER,
+    options TEXT NOT NULL DEFAULT '{}',
+    updated_at TEXT DEFAULT datetime('now')
+);
+
+-- Single-use registration codes. `expires_at` is a Unix timestamp; NULL never expires.
+CREATE TABLE invite_codes (
+    code TEXT PRIMARY KEY,
+    role TEXT NOT NULL DEFAULT 'member',
+    created_by BIGINT NOT NULL,
+    expires_at BIGINT,
+    created_at TEXT DEFAULT datetime('now'),
+    used_by BIGINT,
+    used_at TEXT
+);
+
+-- Generated keys, such as the session secret when SESSION_SECRET is unset.
+CREATE TABLE app_secrets (
+    name TEXT PRIMARY KEY,
+    value TEXT NOT NULL,
+    created_at TEXT DEFAULT datetime('now')
+);
+
+-- Roles whose default permissions have been seeded; an admin may have
+-- changed them since, so seeding happens once per role.
+CREATE TABLE roles (
+    name TEXT PRIMARY KEY,
+    created_at TEXT DEFAULT datetime('now')
+);
+
+CREATE TABLE role_permissions (
+    role TEXT NOT NULL,
+    permission TEXT NOT NULL,
+    PRIMARY KEY (role, permission)
+);
+
+-- One row per sign-in attempt: the audit trail and the lockout counter.
+CREATE TABLE login_attempts (
+    id BIGSERIAL PRIMARY KEY,
+    username TEXT NOT NULL,
+    ip TEXT,
+    outcome TEXT NOT NULL,
+    attempted_at BIGINT NOT NULL
+);
+
+CREATE INDEX idx_login_attempts_username ON login_attempts(username, attempted_at);
+
+CREATE INDEX idx_login_attempts_ip ON login_attempts(ip, attempted_at);
@@ -0,0 +1,13 @@
+-- A user's history, newest first, straight from the index instead of
+-- sorting every row they have.
+CREATE INDEX idx_watch_history_user_watched ON watch_history(user_id, watched_at);
+
+-- Title-wide lookups, such as re-pointing history at a merged TMDB id.
+CREATE INDEX idx_watch_history_title ON watch_history(tmdb_id, media_type);
+
+-- `sessions.session_id` is already indexed by its UNIQUE constraint.
+CREATE INDEX idx_sessions_user ON sessions(user_id);
+CREATE INDEX idx_sessions_expires ON sessions(expires_at);
+
+CREATE INDEX idx_streaming_cache_title ON streaming_cache(tmdb_id, media_type);
+CREATE INDEX idx_streaming_cache_expires ON streaming_cache(expires_at);
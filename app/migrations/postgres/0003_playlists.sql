@@ -0,0 +1,26 @@
+-- Named lists of titles a user puts together, optionally shared read-only
+-- through `share_token`.
+CREATE TABLE playlists (
+    id BIGSERIAL PRIMARY KEY,
+    user_id BIGINT NOT NULL,
+    name TEXT NOT NULL,
+    share_token TEXT UNIQUE,
+    created_at TEXT DEFAULT datetime('now'),
+    updated_at TEXT DEFAULT datetime('now')
+);
+
+CREATE INDEX idx_playlists_user ON playlists(user_id);
+
+CREATE TABLE playlist_items (
+    id BIGSERIAL PRIMARY KEY,
+    playlist_id BIGINT NOT NULL,
+    tmdb_id BIGINT NOT NULL,
+    media_type TEXT NOT NULL,
+    title TEXT NOT NULL,
+    poster_path TEXT,
+    position BIGINT NOT NULL,
+    added_at TEXT DEFAULT datetime('now'),
+    UNIQUE(playlist_id, tmdb_id, media_type)
+);
+
+CREATE INDEX idx_playlist_items_order ON playlist_items(playlist_id, position);
@@ -0,0 +1,15 @@
+-- One row per distinct query a user has searched for. Queries differing only
+-- in case share a row, which keeps the most recent spelling; `citext` gives
+-- the column SQLite's NOCASE comparisons, LIKE included.
+CREATE EXTENSION IF NOT EXISTS citext;
+
+CREATE TABLE search_history (
+    id BIGSERIAL PRIMARY KEY,
+    user_id BIGINT NOT NULL,
+    query CITEXT NOT NULL,
+    search_count BIGINT NOT NULL DEFAULT 1,
+    last_searched_at TEXT NOT NULL DEFAULT datetime('now'),
+    UNIQUE(user_id, query)
+);
+
+CREATE INDEX idx_search_history_user_recent ON search_history(user_id, last_searched_at);
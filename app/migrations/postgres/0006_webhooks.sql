@@ -0,0 +1,32 @@
+-- Admin-registered URLs that receive a signed POST when watch events happen.
+-- `events` is a comma-separated list of event names.
+CREATE TABLE webhooks (
+    id BIGSERIAL PRIMARY KEY,
+    url TEXT NOT NULL,
+    secret TEXT NOT NULL,
+    events TEXT NOT NULL,
+    enabled BOOLEAN NOT NULL DEFAULT TRUE,
+    created_by BIGINT,
+    created_at TEXT NOT NULL DEFAULT datetime('now'),
+    FOREIGN KEY (created_by) REFERENCES users(id) ON DELETE SET NULL
+);
+
+-- One row per event per webhook. Pending rows are retried with backoff
+-- until they are delivered or run out of attempts.
+CREATE TABLE webhook_deliveries (
+    id BIGSERIAL PRIMARY KEY,
+    webhook_id BIGINT NOT NULL,
+    event TEXT NOT NULL,
+    payload TEXT NOT NULL,
+    status TEXT NOT NULL DEFAULT 'pending',
+    attempts BIGINT NOT NULL DEFAULT 0,
+    response_status BIGINT,
+    last_error TEXT,
+    next_attempt_at TEXT NOT NULL DEFAULT datetime('now'),
+    created_at TEXT NOT NULL DEFAULT datetime('now'),
+    delivered_at TEXT,
+    FOREIGN KEY (webhook_id) REFERENCES webhooks(id) ON DELETE CASCADE
+);
+
+CREATE INDEX idx_webhook_deliveries_due ON webhook_deliveries(status, next_attempt_at);
+CREATE INDEX idx_webhook_deliveries_webhook ON webhook_deliveries(webhook_id, id);
@@ -0,0 +1,6 @@
+-- Where each stream report came from, so reports can be rate-limited per IP
+-- as well as per user.
+ALTER TABLE stream_reports ADD COLUMN ip TEXT;
+
+CREATE INDEX idx_stream_reports_user_recent ON stream_reports(user_id, reported_at);
+CREATE INDEX idx_stream_reports_ip_recent ON stream_reports(ip, reported_at);
@@ -0,0 +1,5 @@
+-- Playbacks started per provider, and how many of them stalled, errored, or
+-- went silent, so ranking weighs how sources play and not just whether they
+-- resolve.
+ALTER TABLE provider_stats ADD COLUMN playbacks BIGINT NOT NULL DEFAULT 0;
+ALTER TABLE provider_stats ADD COLUMN troubled_playbacks BIGINT NOT NULL DEFAULT 0;
@@ -0,0 +1,300 @@
+use std::collections::BTreeMap;
+
+use chrono::Utc;
+use serde::Serialize;
+use tracing::info;
+
+use crate::auth::ActiveSession;
+use crate::db::{self, Db};
+use crate::playlists::{PlaylistWithItems, Playlists};
+use crate::search_history::{SearchHistory, SearchHistoryEntry};
+
+/// Everything stored about one user, as handed out by the data export.
+/// Password and PIN hashes and TMDB tokens are left out.
+#[derive(Debug, Serialize)]
+pub struct AccountExport {
+    pub exported_at: String,
+    pub user: ExportedUser,
+    pub settings: BTreeMap<String, String>,
+    pub watch_history: Vec<ExportedHistoryEntry>,
+    pub watchlist: Vec<ExportedWatchlistEntry>,
+    pub ratings: Vec<ExportedRating>,
+    pub playlists: Vec<PlaylistWithItems>,
+    pub search_history: Vec<SearchHistoryEntry>,
+    pub tmdb_account: Option<ExportedTmdbAccount>,
+    pub sessions: Vec<ActiveSession>,
+}
+
+/// The parts of an account other tools can use, for backups and moving to
+/// another service.
+#[derive(Debug, Serialize)]
+pub struct DataExport {
+    pub exported_at: String,
+    pub settings: BTreeMap<String, String>,
+    pub watch_history: Vec<ExportedHistoryEntry>,
+    pub watchlist: Vec<ExportedWatchlistEntry>,
+    pub ratings: Vec<ExportedRating>,
+}
+
+/// Column names of the watch history CSV, in order.
+const HISTORY_CSV_HEADER: &[&str] = &[
+    "tmdb_id",
+    "media_type",
+    "title",
+    "season_number",
+    "episode_number",
+    "episode_title",
+    "progress_seconds",
+    "completed",
+    "watched_at",
+];
+
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct ExportedUser {
+    pub id: i64,
+    pub username: String,
+    pub role: String,
+    pub created_at: String,
+}
+
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct ExportedHistoryEntry {
+    pub tmdb_id: i64,
+    pub media_type: String,
+    pub title: String,
+    pub season_number: Option<i64>,
+    pub episode_number: Option<i64>,
+    pub episode_title: Option<String>,
+    pub progress_seconds: i64,
+    pub completed: bool,
+    pub watched_at: String,
+}
+
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct ExportedWatchlistEntry {
+    pub tmdb_id: i64,
+    pub media_type: String,
+    pub title: String,
+    pub added_at: String,
+}
+
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct ExportedRating {
+    pub tmdb_id: i64,
+    pub media_type: String,
+    pub title: String,
+    pub rating: f64,
+    pub rated_at: String,
+}
+
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct ExportedTmdbAccount {
+    pub account_id: String,
+    pub sync_enabled: bool,
+    pub last_synced_at: Option<String>,
+    pub connected_at: String,
+}
+
+/// Per-user rows, for deleting an account. Rows other users rely on, such as
+/// stream reports and overrides, are kept but no longer point at the user.
+const USER_TABLES: &[&str] = &[
+    "sessions",
+    "watch_history",
+    "user_settings",
+    "playback_events",
+    "tmdb_accounts",
+    "watchlist",
+    "ratings",
+    "playlists",
+    "watch_stats_daily",
+    "watch_stats_genres",
+    "search_history",
+];
+
+/// Data export and deletion of a user's own account.
+#[derive(Debug, Clone)]
+pub struct Accounts {
+    db: Db,
+}
+
+impl Accounts {
+    pub fn new(db: Db) -> Self {
+        Self { db }
+    }
+
+    pub async fn export(&self, user_id: i64) -> anyhow::Result<AccountExport> {
+        let user: ExportedUser =
+            db::query_as("SELECT id, username, role, created_at FROM users WHERE id = ?")
+                .bind(user_id)
+                .fetch_one(&self.db)
+                .await?;
+
+        let settings = self.settings(user_id).await?;
+        let watch_history = self.watch_history(user_id).await?;
+        let watchlist = self.watchlist(user_id).await?;
+        let ratings = self.ratings(user_id).await?;
+
+        let store = Playlists::new(self.db.clone());
+        let mut playlists = Vec::new();
+        for playlist in store.list(user_id).await? {
+            playlists.push(store.get_with_items(user_id, playlist.id).await?);
+        }
+
+        let search_history = SearchHistory::new(self.db.clone()).recent(user_id, i64::MAX).await?;
+
+        let tmdb_account = db::query_as(
+            "SELECT account_id, sync_enabled, last_synced_at, connected_at FROM tmdb_accounts WHERE user_id = ?"
+        )
+        .bind(user_id)
+        .fetch_optional(&self.db)
+        .await?;
+
+        let sessions = db::query_as(
+            r#"
+            SELECT session_id AS id, created_at, last_seen_at, user_agent, ip, expires_at
+            FROM sessions
+            WHERE user_id = ?
+            ORDER BY created_at DESC
+            "#
+        )
+        .bind(user_id)
+        .fetch_all(&self.db)
+        .await?;
+
+        Ok(AccountExport {
+            exported_at: Utc::now().to_rfc3339(),
+            user,
+            settings,
+            watch_history,
+            watchlist,
+            ratings,
+            playlists,
+            search_history,
+            tmdb_account,
+            sessions,
+        })
+    }
+
+    pub async fn export_data(&self, user_id: i64) -> anyhow::Result<DataExport> {
+        Ok(DataExport {
+            exported_at: Utc::now().to_rfc3339(),
+            settings: self.settings(user_id).await?,
+            watch_history: self.watch_history(user_id).await?,
+            watchlist: self.watchlist(user_id).await?,
+            ratings: self.ratings(user_id).await?,
+        })
+    }
+
+    /// The user's watch history as CSV with a header row, newest first.
+    pub async fn watch_history_csv(&self, user_id: i64) -> anyhow::Result<String> {
+        let mut out = String::new();
+        crate::csv::write_record(&mut out, HISTORY_CSV_HEADER);
+        for entry in self.watch_history(user_id).await? {
+            let optional = |value: Option<i64>| value.map(|v| v.to_string()).unwrap_or_default();
+            crate::csv::write_record(
+                &mut out,
+                &[
+                    entry.tmdb_id.to_string(),
+                    entry.media_type,
+                    entry.title,
+                    optional(entry.season_number),
+                    optional(entry.episode_number),
+                    entry.episode_title.unwrap_or_default(),
+                    entry.progress_seconds.to_string(),
+                    entry.completed.to_string(),
+                    entry.watched_at,
+                ],
+            );
+        }
+        Ok(out)
+    }
+
+    /// Settings other than the parental PIN hash.
+    async fn settings(&self, user_id: i64) -> anyhow::Result<BTreeMap<String, String>> {
+        let settings: Vec<(String, String)> =
+            db::query_as("SELECT key, value FROM user_settings WHERE user_id = ? AND key != ?")
+                .bind(user_id)
+                .bind(crate::parental::PIN_KEY)
+                .fetch_all(&self.db)
+                .await?;
+        Ok(settings.into_iter().collect())
+    }
+
+    async fn watch_history(&self, user_id: i64) -> anyhow::Result<Vec<ExportedHistoryEntry>> {
+        Ok(db::query_as(
+            r#"
+            SELECT tmdb_id, media_type, title, NULLIF(season_number, -1) AS season_number,
+                   NULLIF(episode_number, -1) AS episode_number, episode_title, progress_seconds, completed, watched_at
+            FROM watch_history
+            WHERE user_id = ?
+            ORDER BY watched_at DESC, id DESC
+            "#
+        )
+        .bind(user_id)
+        .fetch_all(&self.db)
+        .await?)
+    }
+
+    async fn watchlist(&self, user_id: i64) -> anyhow::Result<Vec<ExportedWatchlistEntry>> {
+        Ok(db::query_as(
+            "SELECT tmdb_id, media_type, title, added_at FROM watchlist WHERE user_id = ? ORDER BY added_at DESC"
+        )
+        .bind(user_id)
+        .fetch_all(&self.db)
+        .await?)
+    }
+
+    async fn ratings(&self, user_id: i64) -> anyhow::Result<Vec<ExportedRating>> {
+        Ok(db::query_as(
+            "SELECT tmdb_id, media_type, title, rating, rated_at FROM ratings WHERE user_id = ? ORDER BY rated_at DESC"
+        )
+        .bind(user_id)
+        .fetch_all(&self.db)
+        .await?)
+    }
+
+    /// Removes the user and everything stored for them in one transaction.
+    pub async fn delete(&self, user_id: i64) -> anyhow::Result<()> {
+        let mut tx = self.db.begin().await?;
+
+        let username: String = db::query_scalar("SELECT username FROM users WHERE id = ?")
+            .bind(user_id)
+            .fetch_one(&mut tx)
+            .await?;
+
+        db::query("DELETE FROM playlist_items WHERE playlist_id IN (SELECT id FROM playlists WHERE user_id = ?)")
+            .bind(user_id)
+            .execute(&mut tx)
+            .await?;
+        for table in USER_TABLES {
+            db::query(&format!("DELETE FROM {table} WHERE user_id = ?"))
+                .bind(user_id)
+                .execute(&mut tx)
+                .await?;
+        }
+        db::query("DELETE FROM login_attempts WHERE username = ?")
+            .bind(&username)
+            .execute(&mut tx)
+            .await?;
+        db::query("DELETE FROM invite_codes WHERE created_by = ? AND used_by IS NULL")
+            .bind(user_id)
+            .execute(&mut tx)
+            .await?;
+        db::query("UPDATE stream_reports SET user_id = NULL WHERE user_id = ?")
+            .bind(user_id)
+            .execute(&mut tx)
+            .await?;
+        db::query("UPDATE stream_overrides SET created_by = NULL WHERE created_by = ?")
+            .bind(user_id)
+            .execute(&mut tx)
+            .await?;
+        db::query("DELETE FROM users WHERE id = ?")
+            .bind(user_id)
+            .execute(&mut tx)
+            .await?;
+
+        tx.commit().await?;
+        info!("Deleted account {} and its data", username);
+        Ok(())
+    }
+}
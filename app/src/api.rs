@@ -1,85 +1,584 @@
 use axum::{
-    extract::{Path, Query, State},
-    routing::get,
+    body::{Body, HttpBody},
+    extract::{DefaultBodyLimit, Path, Query, Request, State},
+    http::{self, HeaderMap, StatusCode},
+    middleware::{self, Next},
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        IntoResponse, Response,
+    },
+    routing::{delete, get, patch, post, put},
     Json, Router,
 };
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
+use std::convert::Infallible;
+use tokio_stream::{Stream, StreamExt};
 
 use crate::{
-    error::AppError,
+    auth::{
+        self, clear_session_cookie, ActiveSession, ClientIp, ContinueWatchingItem, CurrentUser,
+        ImageQuality, MaybeSession, Permission, PlayerPreferences, RequireAdmin, RequireSession,
+        Role, SessionClient, UserPreferences, WatchHistoryItem, WatchlistItem,
+    },
+    error::{self, AppError},
+    history_import::{self, ImportFormat, ImportJob},
+    invites::{self, Invite, Registration},
+    local_media::{LocalMedia, LocalMediaProvider},
+    login::Credentials,
+    provider_health::{self, ProviderStatus},
+    provider_settings::ProviderSetting,
+    provider_stats::ProviderStat,
+    stream_overrides::StreamOverride,
+    metrics::MetricsSnapshot,
+    parental::Restriction,
+    playlists::{Playlist, PlaylistWithItems, TitleRef},
+    search_history::{self, SearchHistoryEntry},
+    stats::StatsSummary,
+    tmdb_keys::KeyStatus,
+    webhooks::{Webhook, WebhookDelivery, WebhookEvent},
+    providers::{EmbedOptions, ProviderConfig, StreamResolution, StreamStatus},
     AppState,
 };
 
 pub fn routes(state: AppState) -> Router<AppState> {
     Router::new()
         .route("/movies/popular", get(get_popular_movies))
+        .route("/movies/now_playing", get(get_now_playing))
+        .route("/movies/upcoming", get(get_upcoming))
+        .route("/movies/top_rated", get(get_top_rated_movies))
         .route("/tv/popular", get(get_popular_tv))
+        .route("/tv/top_rated", get(get_top_rated_tv))
         .route("/trending/:media_type/:time_window", get(get_trending))
+        .route("/people/trending", get(get_trending_people))
         .route("/search", get(search))
+        .route("/search/suggest", get(suggest))
+        .route("/search/history", get(get_search_history).delete(clear_search_history))
+        .route("/search/history/:id", delete(delete_search_history_entry))
+        .route("/discover", get(discover))
+        .route("/find/:imdb_id", get(find_by_imdb_id))
+        .route("/genres", get(get_genres))
         .route("/movie/:id", get(get_movie_detail))
         .route("/tv/:id", get(get_tv_detail))
+        .route("/tv/:id/season/:season_number", get(get_tv_season))
         .route("/movie/:id/streams", get(get_movie_streams))
         .route("/tv/:id/streams", get(get_tv_streams))
+        .route("/auth/register", post(register))
+        .route("/auth/login", post(login))
+        .route("/auth/sessions", get(list_sessions).delete(revoke_all_sessions))
+        .route("/auth/sessions/:id", delete(revoke_session))
+        .route("/settings", get(get_settings).put(update_settings))
+        .route("/playlists", get(list_playlists).post(create_playlist))
+        .route(
+            "/playlists/:id",
+            get(get_playlist).patch(update_playlist).delete(delete_playlist),
+        )
+        .route("/playlists/:id/items", post(add_playlist_item).put(reorder_playlist))
+        .route(
+            "/playlists/:id/items/:media_type/:tmdb_id",
+            delete(remove_playlist_item),
+        )
+        .route("/shared/playlists/:token", get(get_shared_playlist))
+        .route("/stats", get(get_stats))
+        .route("/continue-watching", get(get_continue_watching))
+        .route("/history", get(get_history).delete(clear_history))
+        .route("/history/:id", delete(delete_history_entry))
+        .route("/account", delete(delete_account))
+        .route("/account/export", get(export_account))
+        .route("/export", get(export_data))
+        .route("/events", get(notification_events))
+        .route("/providers", get(list_providers))
+        .route(
+            "/import",
+            post(import_history).layer(DefaultBodyLimit::max(history_import::MAX_IMPORT_BYTES)),
+        )
+        .route("/import/status", get(import_status))
+        .route("/watchlist", get(get_watchlist).post(add_to_watchlist))
+        .route(
+            "/watchlist/:media_type/:id",
+            get(get_watchlist_status).delete(remove_from_watchlist),
+        )
+        .merge(user_admin_routes(state.clone()))
+        .merge(settings_admin_routes(state.clone()))
+        .route("/:media_type/:id/providers", get(get_watch_providers))
+        .route("/:media_type/:id/recommendations", get(get_recommendations))
+        .route("/metrics", get(get_metrics))
+        .route("/version", get(get_version))
+        .route("/openapi.json", get(get_openapi))
+        .route("/docs", get(api_docs))
+        .fallback(|| async { AppError::NotFound })
         .with_state(state)
 }
 
+/// Where the current API version is mounted. `/api` serves the same routes
+/// with their original response shapes, for scripts written against it.
+pub const V1_PREFIX: &str = "/api/v1";
+
+/// Larger `/api/v1` responses, and ones whose size isn't known up front,
+/// pass through as they are, without being rewrapped.
+const MAX_ENVELOPE_BYTES: u64 = 64 * 1024 * 1024;
+
+/// Keeps `/api/v1` responses open to new fields: lists come back as
+/// `{"results": [...]}` instead of a bare array and `null` as `{}`. Errors
+/// axum produces itself, such as unparseable query strings, get the same
+/// problem details body as the app's own.
+pub async fn v1_envelope(request: Request, next: Next) -> Response {
+    let response = next.run(request).await;
+    let status = response.status();
+    let is_json = response
+        .headers()
+        .get(http::header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.starts_with("application/json") || v.starts_with(error::PROBLEM_CONTENT_TYPE));
+    let is_error = status.is_client_error() || status.is_server_error();
+    let fits = response
+        .body()
+        .size_hint()
+        .upper()
+        .is_some_and(|size| size <= MAX_ENVELOPE_BYTES);
+    if is_json == is_error || !fits {
+        // Non-JSON successes (pages, streams) and JSON errors pass through.
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+    // Only a body that fails while being read ends up here.
+    let Ok(bytes) = axum::body::to_bytes(body, usize::MAX).await else {
+        return AppError::Internal.into_response();
+    };
+    parts.headers.remove(http::header::CONTENT_LENGTH);
+
+    if is_error {
+        let message = String::from_utf8_lossy(&bytes);
+        let message = match message.trim() {
+            "" => status.canonical_reason().unwrap_or("Error"),
+            message => message,
+        };
+        parts.headers.insert(
+            http::header::CONTENT_TYPE,
+            http::HeaderValue::from_static(error::PROBLEM_CONTENT_TYPE),
+        );
+        let body = error::problem_body(status, error::error_code(status), message);
+        return Response::from_parts(parts, Body::from(body.to_string()));
+    }
+
+    if bytes.as_ref() == b"null" {
+        return Response::from_parts(parts, Body::from("{}"));
+    }
+    if bytes.iter().find(|b| !b.is_ascii_whitespace()) != Some(&b'[') {
+        return Response::from_parts(parts, Body::from(bytes));
+    }
+    let mut wrapped = Vec::with_capacity(bytes.len() + 12);
+    wrapped.extend_from_slice(b"{\"results\":");
+    wrapped.extend_from_slice(&bytes);
+    wrapped.push(b'}');
+    Response::from_parts(parts, Body::from(wrapped))
+}
+
+/// Accounts, invites, and what each role may do. Needs `manage_users`.
+fn user_admin_routes(state: AppState) -> Router<AppState> {
+    Router::new()
+        .route("/admin/invites", get(list_invites).post(create_invite))
+        .route("/admin/invites/:code", delete(revoke_invite))
+        .route("/admin/roles", get(list_roles))
+        .route("/admin/roles/:role", put(set_role_permissions))
+        .route_layer(middleware::from_fn_with_state(state, auth::require_manage_users))
+}
+
+/// Instance-wide stream and library configuration. Needs `manage_settings`.
+fn settings_admin_routes(state: AppState) -> Router<AppState> {
+    Router::new()
+        .route("/admin/providers", get(list_provider_configs))
+        .route("/admin/providers/stats", get(get_provider_stats))
+        .route(
+            "/admin/providers/:name",
+            put(configure_provider).delete(reset_provider),
+        )
+        .route("/admin/local-media", get(list_local_media).put(set_local_media))
+        .route("/admin/local-media/:id", delete(remove_local_media))
+        .route(
+            "/admin/stream-overrides",
+            get(list_stream_overrides)
+                .put(set_stream_override)
+                .delete(remove_stream_override),
+        )
+        .route("/admin/webhooks", get(list_webhooks).post(create_webhook))
+        .route("/admin/webhooks/:id", patch(update_webhook).delete(delete_webhook))
+        .route("/admin/webhooks/:id/deliveries", get(list_webhook_deliveries))
+        .route_layer(middleware::from_fn_with_state(state, auth::require_manage_settings))
+}
+
 #[derive(Deserialize)]
 struct SearchQuery {
+    #[serde(default)]
     q: String,
     #[serde(default = "default_page")]
     page: i32,
+    /// Collect this many results across consecutive pages instead of one page.
+    #[serde(default)]
+    limit: Option<usize>,
+    /// `person` searches people only and `movie` or `tv` titles of that
+    /// type; otherwise titles and people are mixed.
+    #[serde(default)]
+    media_type: Option<String>,
+    #[serde(default)]
+    year: Option<i32>,
+    /// Genre id or name.
+    #[serde(default)]
+    genre: Option<String>,
+    #[serde(default)]
+    min_rating: Option<f64>,
+    #[serde(default)]
+    sort_by: Option<String>,
 }
 
+impl SearchQuery {
+    /// The search page's filters when any are set or `q` has a filter
+    /// prefix, in which case results come from discover as they do there.
+    fn discover_filters(&self) -> Result<Option<crate::tmdb::DiscoverFilters>, AppError> {
+        let media_type = match self.media_type.as_deref().filter(|t| !t.is_empty()) {
+            None | Some("person") => None,
+            Some(t @ ("movie" | "tv")) => Some(t.to_string()),
+            Some(_) => return Err(AppError::invalid("invalid_media_type", "media_type must be movie, tv, or person")),
+        };
+        let genre = self.genre.clone().filter(|g| !g.trim().is_empty());
+        let sort_by = self.sort_by.clone().filter(|s| !s.is_empty());
+        if let Some(sort_by) = sort_by.as_deref().filter(|s| !SEARCH_SORTS.contains(s)) {
+            return Err(AppError::invalid("invalid_sort", format!(
+                "Unknown sort_by \"{}\"; use one of {}",
+                sort_by,
+                SEARCH_SORTS.join(", ")
+            )));
+        }
+
+        let has_filters = crate::tmdb::is_filter_query(&self.q)
+            || media_type.is_some()
+            || genre.is_some()
+            || self.year.is_some()
+            || self.min_rating.is_some()
+            || sort_by.is_some();
+        if !has_filters {
+            return Ok(None);
+        }
+        if self.media_type.as_deref() == Some("person") {
+            return Err(AppError::BadRequest("People searches take no filters".to_string()));
+        }
+        Ok(Some(crate::tmdb::DiscoverFilters {
+            q: self.q.clone(),
+            media_type,
+            genre,
+            year: self.year,
+            min_rating: self.min_rating,
+            sort_by,
+            ..Default::default()
+        }))
+    }
+}
+
+const MAX_SEARCH_LIMIT: usize = 200;
+
+/// The orders the search page offers.
+const SEARCH_SORTS: [&str; 4] = ["popularity.desc", "vote_average.desc", "release_date.desc", "revenue.desc"];
+
 fn default_page() -> i32 {
     1
 }
 
+/// `?page=` for endpoints that pass through one page of a TMDB listing.
+#[derive(Deserialize)]
+struct PageQuery {
+    #[serde(default = "default_page")]
+    page: i32,
+}
+
+impl PageQuery {
+    fn page(&self) -> Result<i32, AppError> {
+        checked_page(self.page)
+    }
+}
+
+/// TMDB only serves pages 1 through 500.
+fn checked_page(page: i32) -> Result<i32, AppError> {
+    if (1..=crate::tmdb::TMDB_MAX_PAGE).contains(&page) {
+        Ok(page)
+    } else {
+        Err(AppError::invalid("invalid_page", format!(
+            "page must be between 1 and {}",
+            crate::tmdb::TMDB_MAX_PAGE
+        )))
+    }
+}
+
 async fn search(
     State(state): State<AppState>,
+    restriction: Restriction,
     Query(params): Query<SearchQuery>,
 ) -> Result<Json<crate::tmdb::SearchResponse>, AppError> {
-    let results = state.tmdb.search(&params.q, params.page).await?;
+    let page = checked_page(params.page)?;
+    let mut filters = params.discover_filters()?;
+    if filters.is_none() && params.q.trim().is_empty() {
+        return Err(AppError::invalid("query_required", "q is required"));
+    }
+    if let Some(filters) = filters.as_mut() {
+        crate::people::resolve_filters(&state.db, &state.tmdb, filters).await?;
+    }
+
+    let people_only = params.media_type.as_deref() == Some("person");
+    let tmdb = &state.tmdb;
+    let query = params.q.as_str();
+    let filters = filters.as_ref();
+    let fetch_page = |page| async move {
+        match filters {
+            Some(filters) => tmdb.discover_page(filters, page).await,
+            None if people_only => tmdb.search_people(query, page).await,
+            None => tmdb.search(query, page).await,
+        }
+    };
+    let mut results = match params.limit {
+        Some(limit) => {
+            crate::tmdb::fetch_pages(page, limit.min(MAX_SEARCH_LIMIT), fetch_page).await?
+        }
+        None => fetch_page(page).await?,
+    };
+    results.results = restriction.filter_results(&state.tmdb, results.results).await;
     Ok(Json(results))
 }
 
+#[derive(Deserialize)]
+struct SuggestQuery {
+    #[serde(default)]
+    q: String,
+}
+
+/// Titles the viewer has searched for before come first.
+async fn suggest(
+    State(state): State<AppState>,
+    MaybeSession(session): MaybeSession,
+    Query(params): Query<SuggestQuery>,
+) -> Result<Json<Vec<crate::tmdb::Suggestion>>, AppError> {
+    let mut suggestions = state.tmdb.suggest(&params.q).await?.as_ref().clone();
+    if let Some(session) = session {
+        let past = state.search_history.frequent(session.user_id, &params.q).await?;
+        search_history::rank_suggestions(&mut suggestions, &past);
+    }
+    Ok(Json(suggestions))
+}
+
+async fn get_search_history(
+    State(state): State<AppState>,
+    RequireSession(session): RequireSession,
+) -> Result<Json<Vec<SearchHistoryEntry>>, AppError> {
+    Ok(Json(
+        state
+            .search_history
+            .recent(session.user_id, search_history::RECENT_LIMIT)
+            .await?,
+    ))
+}
+
+async fn clear_search_history(
+    State(state): State<AppState>,
+    RequireSession(session): RequireSession,
+) -> Result<StatusCode, AppError> {
+    state.search_history.clear(session.user_id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn delete_search_history_entry(
+    State(state): State<AppState>,
+    RequireSession(session): RequireSession,
+    Path(id): Path<i64>,
+) -> Result<StatusCode, AppError> {
+    if !state.search_history.delete(session.user_id, id).await? {
+        return Err(AppError::NotFound);
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[derive(Deserialize)]
+struct CursorQuery {
+    #[serde(default)]
+    cursor: Option<String>,
+    /// Full credits for an `actor:` or `director:` query, newest first.
+    #[serde(default)]
+    filmography: bool,
+}
+
+async fn discover(
+    State(state): State<AppState>,
+    restriction: Restriction,
+    Query(mut filters): Query<crate::tmdb::DiscoverFilters>,
+    Query(params): Query<CursorQuery>,
+) -> Result<Json<crate::tmdb::DiscoverPage>, AppError> {
+    let cursor = params
+        .cursor
+        .as_deref()
+        .map(|c| {
+            crate::tmdb::DiscoverCursor::parse(c)
+                .ok_or_else(|| AppError::invalid("invalid_cursor", "Invalid cursor"))
+        })
+        .transpose()?;
+    let person = crate::people::resolve_filters(&state.db, &state.tmdb, &mut filters).await?;
+    if params.filmography {
+        let (role, id) = person
+            .filter(|(_, id)| *id > 0)
+            .ok_or_else(|| AppError::BadRequest("filmography needs an actor: or director: query naming a known person".to_string()))?;
+        let results = state
+            .tmdb
+            .get_filmography(id, role == crate::people::PersonRole::Director)
+            .await?;
+        let results = restriction.filter_results(&state.tmdb, results).await;
+        return Ok(Json(crate::tmdb::DiscoverPage {
+            total_results: results.len() as i64,
+            total_pages: 1,
+            results,
+            next_cursor: None,
+        }));
+    }
+    let mut page = state.tmdb.discover_combined(&filters, cursor).await?;
+    page.results = restriction.filter_results(&state.tmdb, page.results).await;
+    Ok(Json(page))
+}
+
+#[derive(Deserialize)]
+struct GenresQuery {
+    /// Only genres that apply to `movie` or `tv`.
+    #[serde(default)]
+    media_type: Option<String>,
+}
+
+/// Movie and TV genres merged by id, for building filter menus.
+async fn get_genres(
+    State(state): State<AppState>,
+    Query(params): Query<GenresQuery>,
+) -> Result<Json<Vec<crate::tmdb::MediaGenre>>, AppError> {
+    let media_type = params
+        .media_type
+        .as_deref()
+        .map(crate::list_media_type)
+        .transpose()?;
+    let mut genres = state.tmdb.get_genres().await?;
+    if let Some(media_type) = media_type {
+        genres.retain(|g| g.media_types.iter().any(|t| t == media_type));
+    }
+    Ok(Json(genres))
+}
+
+async fn find_by_imdb_id(
+    State(state): State<AppState>,
+    Path(imdb_id): Path<String>,
+) -> Result<Json<crate::tmdb::FindResponse>, AppError> {
+    let imdb_id = crate::tmdb::parse_imdb_id(&imdb_id)
+        .ok_or_else(|| AppError::invalid("invalid_imdb_id", "Invalid IMDb id"))?;
+    let found = state.tmdb.find_by_external_id(&imdb_id).await?;
+    Ok(Json(found))
+}
+
 async fn get_popular_movies(
     State(state): State<AppState>,
+    Query(params): Query<PageQuery>,
+) -> Result<Json<crate::tmdb::MovieListResponse>, AppError> {
+    let movies = state.tmdb.get_popular_movies(params.page()?).await?;
+    Ok(Json(movies))
+}
+
+async fn get_now_playing(
+    State(state): State<AppState>,
+    Query(params): Query<PageQuery>,
 ) -> Result<Json<crate::tmdb::MovieListResponse>, AppError> {
-    let movies = state.tmdb.get_popular_movies(1).await?;
+    let movies = state.tmdb.get_now_playing(params.page()?).await?;
+    Ok(Json(movies))
+}
+
+async fn get_upcoming(
+    State(state): State<AppState>,
+    Query(params): Query<PageQuery>,
+) -> Result<Json<crate::tmdb::MovieListResponse>, AppError> {
+    let movies = state.tmdb.get_upcoming(params.page()?).await?;
     Ok(Json(movies))
 }
 
 async fn get_popular_tv(
     State(state): State<AppState>,
+    Query(params): Query<PageQuery>,
 ) -> Result<Json<crate::tmdb::TvListResponse>, AppError> {
-    let shows = state.tmdb.get_popular_tv(1).await?;
+    let shows = state.tmdb.get_popular_tv(params.page()?).await?;
+    Ok(Json(shows))
+}
+
+async fn get_top_rated_movies(
+    State(state): State<AppState>,
+    Query(params): Query<PageQuery>,
+) -> Result<Json<crate::tmdb::MovieListResponse>, AppError> {
+    let movies = state.tmdb.get_top_rated_movies(params.page()?).await?;
+    Ok(Json(movies))
+}
+
+async fn get_top_rated_tv(
+    State(state): State<AppState>,
+    Query(params): Query<PageQuery>,
+) -> Result<Json<crate::tmdb::TvListResponse>, AppError> {
+    let shows = state.tmdb.get_top_rated_tv(params.page()?).await?;
     Ok(Json(shows))
 }
 
 async fn get_trending(
     State(state): State<AppState>,
     Path((media_type, time_window)): Path<(String, String)>,
+    Query(params): Query<PageQuery>,
 ) -> Result<Json<crate::tmdb::SearchResponse>, AppError> {
-    let trending = state.tmdb.get_trending(&media_type, &time_window).await?;
+    let trending = state.tmdb.get_trending(&media_type, &time_window, params.page()?).await?;
     Ok(Json(trending))
 }
 
+async fn get_trending_people(
+    State(state): State<AppState>,
+    Query(params): Query<PageQuery>,
+) -> Result<Json<crate::tmdb::SearchResponse>, AppError> {
+    let people = state.tmdb.get_trending_people("week", params.page()?).await?;
+    Ok(Json(people))
+}
+
 async fn get_movie_detail(
     State(state): State<AppState>,
+    restriction: Restriction,
     Path(id): Path<i64>,
 ) -> Result<Json<crate::tmdb::MovieDetail>, AppError> {
-    let movie = state.tmdb.get_movie(id).await?;
+    let mut movie = state.tmdb.get_movie(id).await?;
+    restriction.check(movie.certification.as_ref())?;
+    if let Some(omdb) = &state.omdb {
+        omdb.enrich(&mut movie).await;
+    }
     Ok(Json(movie))
 }
 
 async fn get_tv_detail(
     State(state): State<AppState>,
+    restriction: Restriction,
     Path(id): Path<i64>,
 ) -> Result<Json<crate::tmdb::TvShowDetail>, AppError> {
     let show = state.tmdb.get_tv_show(id).await?;
+    restriction.check(show.certification.as_ref())?;
     Ok(Json(show))
 }
 
+/// A season with every episode's name, overview, still, air date, and runtime.
+async fn get_tv_season(
+    State(state): State<AppState>,
+    restriction: Restriction,
+    Path((id, season_number)): Path<(i64, i64)>,
+) -> Result<Json<crate::tmdb::SeasonDetail>, AppError> {
+    if season_number < 0 {
+        return Err(AppError::BadRequest("season_number can't be negative".to_string()));
+    }
+    restriction.check_title(&state.tmdb, "tv", id).await?;
+    let season = state
+        .tmdb
+        .get_season(id, season_number)
+        .await?
+        .ok_or(AppError::NotFound)?;
+    Ok(Json(season.as_ref().clone()))
+}
+
 #[derive(Deserialize)]
 struct StreamQuery {
     #[serde(default)]
@@ -88,22 +587,1147 @@ struct StreamQuery {
     episode: Option<i64>,
 }
 
+/// 200 with streams, 404 when no provider has the title, or 503 when none
+/// was found but some provider failed. The body always lists each provider's outcome.
+fn stream_response(resolution: StreamResolution) -> (StatusCode, Json<StreamResolution>) {
+    let status = match resolution.status {
+        StreamStatus::Available => StatusCode::OK,
+        StreamStatus::NotFound => StatusCode::NOT_FOUND,
+        StreamStatus::ProviderDown => StatusCode::SERVICE_UNAVAILABLE,
+    };
+    (status, Json(resolution))
+}
+
 async fn get_movie_streams(
     State(state): State<AppState>,
+    restriction: Restriction,
+    MaybeSession(session): MaybeSession,
     Path(id): Path<i64>,
-) -> Result<Json<Vec<crate::vidking::StreamSource>>, AppError> {
-    let streams = state.vidking.get_movie_streams(id).await?;
-    Ok(Json(streams))
+) -> Result<(StatusCode, Json<StreamResolution>), AppError> {
+    restriction.check_title(&state.tmdb, "movie", id).await?;
+    let options = stream_options(session.as_ref());
+    Ok(stream_response(state.providers.get_movie_streams(id, &options).await))
 }
 
 async fn get_tv_streams(
     State(state): State<AppState>,
+    restriction: Restriction,
+    MaybeSession(session): MaybeSession,
     Path(id): Path<i64>,
     Query(params): Query<StreamQuery>,
-) -> Result<Json<Vec<crate::vidking::StreamSource>>, AppError> {
-    let season = params.season.ok_or_else(|| AppError::BadRequest("Season required".to_string()))?;
-    let episode = params.episode.ok_or_else(|| AppError::BadRequest("Episode required".to_string()))?;
-    
-    let streams = state.vidking.get_tv_streams(id, season, episode).await?;
-    Ok(Json(streams))
-}
\ No newline at end of file
+) -> Result<(StatusCode, Json<StreamResolution>), AppError> {
+    let season = params.season.ok_or_else(|| AppError::invalid("season_required", "Season required"))?;
+    let episode = params.episode.ok_or_else(|| AppError::invalid("episode_required", "Episode required"))?;
+    restriction.check_title(&state.tmdb, "tv", id).await?;
+
+    let options = stream_options(session.as_ref());
+    let resolution = state.providers.get_tv_streams(id, season, episode, &options).await;
+    Ok(stream_response(resolution))
+}
+
+/// Default embed options, with relayed HLS links signed for the caller.
+fn stream_options(session: Option<&crate::Session>) -> EmbedOptions {
+    EmbedOptions {
+        viewer: session.map(|s| s.user_id),
+        ..Default::default()
+    }
+}
+
+fn local_library(state: &AppState) -> Result<&LocalMediaProvider, AppError> {
+    state
+        .local_media
+        .as_deref()
+        .ok_or_else(|| AppError::BadRequest("MEDIA_DIR is not configured".to_string()))
+}
+
+async fn list_local_media(
+    State(state): State<AppState>,
+) -> Result<Json<Vec<LocalMedia>>, AppError> {
+    Ok(Json(local_library(&state)?.list().await?))
+}
+
+/// Maps a file under `MEDIA_DIR` to a title or episode.
+async fn set_local_media(
+    State(state): State<AppState>,
+    Json(mut entry): Json<LocalMedia>,
+) -> Result<Json<LocalMedia>, AppError> {
+    let library = local_library(&state)?;
+
+    match entry.media_type.as_str() {
+        "movie" => {
+            entry.season_number = 0;
+            entry.episode_number = 0;
+        }
+        "tv" if entry.season_number >= 0 && entry.episode_number > 0 => {}
+        "tv" => return Err(AppError::invalid("season_required", "season_number and episode_number are required for tv")),
+        _ => return Err(AppError::invalid("invalid_media_type", "media_type must be movie or tv")),
+    }
+    if library.resolve(&entry.path).is_none() {
+        return Err(AppError::BadRequest(format!("No file at {} inside MEDIA_DIR", entry.path)));
+    }
+
+    Ok(Json(library.set(&entry).await?))
+}
+
+async fn remove_local_media(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+) -> Result<Json<()>, AppError> {
+    if !local_library(&state)?.remove(id).await? {
+        return Err(AppError::NotFound);
+    }
+    Ok(Json(()))
+}
+
+#[derive(Serialize)]
+struct ProviderStatsResponse {
+    #[serde(flatten)]
+    stat: ProviderStat,
+    success_rate: f64,
+    average_latency_ms: f64,
+    /// `null` until the provider has enough resolutions to be ranked.
+    score: Option<f64>,
+}
+
+#[derive(Serialize)]
+struct SignedInResponse {
+    user_id: i64,
+    username: String,
+    role: Role,
+    /// Also set as the session cookie; send it back as that cookie.
+    token: String,
+    /// Older sessions signed out to stay within `MAX_SESSIONS_PER_USER`.
+    evicted_sessions: u64,
+}
+
+/// Signs in with a username and password. Repeated failures lock out the
+/// username or IP for a while and return 429.
+async fn login(
+    State(state): State<AppState>,
+    ClientIp(ip): ClientIp,
+    headers: HeaderMap,
+    Json(credentials): Json<Credentials>,
+) -> Result<impl IntoResponse, AppError> {
+    let client = SessionClient::new(&headers, ip);
+    let (user_id, role, session) = crate::sign_in(&state, &credentials, &client).await?;
+    Ok((
+        [(http::header::SET_COOKIE, session.cookie())],
+        Json(SignedInResponse {
+            user_id,
+            username: credentials.username.trim().to_string(),
+            role,
+            evicted_sessions: session.evicted,
+            token: session.token,
+        }),
+    ))
+}
+
+/// Creates an account from an invite code and signs it in.
+async fn register(
+    State(state): State<AppState>,
+    ClientIp(ip): ClientIp,
+    headers: HeaderMap,
+    Json(registration): Json<Registration>,
+) -> Result<impl IntoResponse, AppError> {
+    let client = SessionClient::new(&headers, ip);
+    let (user, session) = crate::register_account(&state, &registration, &client).await?;
+    Ok((
+        [(http::header::SET_COOKIE, session.cookie())],
+        Json(SignedInResponse {
+            user_id: user.id,
+            username: user.username,
+            role: user.role,
+            evicted_sessions: session.evicted,
+            token: session.token,
+        }),
+    ))
+}
+
+#[derive(Serialize)]
+struct SessionResponse {
+    #[serde(flatten)]
+    session: ActiveSession,
+    /// Whether this is the session making the request.
+    current: bool,
+}
+
+async fn list_sessions(
+    State(state): State<AppState>,
+    RequireSession(current): RequireSession,
+) -> Result<Json<Vec<SessionResponse>>, AppError> {
+    let sessions = state.sessions.list_sessions(current.user_id).await?;
+    Ok(Json(
+        sessions
+            .into_iter()
+            .map(|session| SessionResponse {
+                current: session.id == current.id,
+                session,
+            })
+            .collect(),
+    ))
+}
+
+/// Signs out one of the caller's sessions, possibly the current one.
+async fn revoke_session(
+    State(state): State<AppState>,
+    RequireSession(session): RequireSession,
+    Path(id): Path<String>,
+) -> Result<Json<()>, AppError> {
+    if !state.sessions.revoke_session(session.user_id, &id).await? {
+        return Err(AppError::NotFound);
+    }
+    Ok(Json(()))
+}
+
+#[derive(Serialize)]
+struct RevokeAllResponse {
+    revoked: u64,
+}
+
+/// Signs the caller out everywhere, including this session.
+async fn revoke_all_sessions(
+    State(state): State<AppState>,
+    RequireSession(session): RequireSession,
+) -> Result<impl IntoResponse, AppError> {
+    let revoked = state.sessions.revoke_all_sessions(session.user_id).await?;
+    Ok((
+        [(http::header::SET_COOKIE, clear_session_cookie())],
+        Json(RevokeAllResponse { revoked }),
+    ))
+}
+
+/// Everything stored about the caller, as a JSON download.
+async fn export_account(
+    State(state): State<AppState>,
+    RequireSession(session): RequireSession,
+) -> Result<impl IntoResponse, AppError> {
+    let export = state.accounts.export(session.user_id).await?;
+    Ok((
+        [(
+            http::header::CONTENT_DISPOSITION,
+            "attachment; filename=\"ruststream-export.json\"",
+        )],
+        Json(export),
+    ))
+}
+
+#[derive(Deserialize)]
+struct ExportQuery {
+    #[serde(default)]
+    format: Option<String>,
+}
+
+/// The caller's history, watchlist, ratings, and settings as JSON, or just
+/// the history as CSV with `?format=csv`.
+async fn export_data(
+    State(state): State<AppState>,
+    RequireSession(session): RequireSession,
+    Query(params): Query<ExportQuery>,
+) -> Result<Response, AppError> {
+    match params.format.as_deref().unwrap_or("json") {
+        "json" => {
+            let export = state.accounts.export_data(session.user_id).await?;
+            Ok((
+                [(
+                    http::header::CONTENT_DISPOSITION,
+                    "attachment; filename=\"ruststream-data.json\"",
+                )],
+                Json(export),
+            )
+                .into_response())
+        }
+        "csv" => {
+            let csv = state.accounts.watch_history_csv(session.user_id).await?;
+            Ok((
+                [
+                    (http::header::CONTENT_TYPE, "text/csv; charset=utf-8"),
+                    (
+                        http::header::CONTENT_DISPOSITION,
+                        "attachment; filename=\"ruststream-history.csv\"",
+                    ),
+                ],
+                csv,
+            )
+                .into_response())
+        }
+        _ => Err(AppError::invalid("invalid_format", "format must be json or csv")),
+    }
+}
+
+#[derive(Deserialize)]
+struct ImportQuery {
+    #[serde(default)]
+    format: Option<ImportFormat>,
+    #[serde(default)]
+    dry_run: bool,
+    /// Read Netflix's slashed dates day first; guessed from the file when left out.
+    #[serde(default)]
+    day_first: Option<bool>,
+}
+
+/// Adds a Trakt JSON or Netflix CSV export to the caller's watch history.
+/// The body is the file as downloaded; `?dry_run=true` reports what would
+/// be imported without saving it. Files over [`history_import::MAX_INLINE_ROWS`]
+/// rows are imported in the background: the answer is `202` with the job,
+/// which `GET /api/import/status` follows.
+async fn import_history(
+    State(state): State<AppState>,
+    RequireSession(session): RequireSession,
+    Query(params): Query<ImportQuery>,
+    body: String,
+) -> Result<Response, AppError> {
+    let format = params.format.unwrap_or_else(|| ImportFormat::detect(&body));
+    let entries = history_import::parse(format, &body, params.day_first)
+        .map_err(|message| AppError::invalid("invalid_import", message))?;
+
+    if entries.len() > history_import::MAX_INLINE_ROWS {
+        let job = state
+            .imports
+            .start(state.clone(), session.user_id, format, entries, params.dry_run)
+            .ok_or_else(|| AppError::TooManyRequests("An import is already running".to_string()))?;
+        return Ok((StatusCode::ACCEPTED, Json(job)).into_response());
+    }
+    let report = history_import::import(
+        &state.db,
+        &state.tmdb,
+        session.user_id,
+        format,
+        entries,
+        params.dry_run,
+    )
+    .await?;
+    Ok(Json(report).into_response())
+}
+
+/// The caller's latest background import, running or finished.
+async fn import_status(
+    State(state): State<AppState>,
+    RequireSession(session): RequireSession,
+) -> Result<Json<ImportJob>, AppError> {
+    state.imports.latest(session.user_id).map(Json).ok_or(AppError::NotFound)
+}
+
+/// The caller's notifications as server-sent `notification` events, each
+/// with its id so a reconnecting browser gets only what it missed. 429 when
+/// the caller already has too many streams open.
+async fn notification_events(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    headers: HeaderMap,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, AppError> {
+    let after = headers
+        .get("last-event-id")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<u64>().ok());
+    let admin = user.require(Permission::ManageSettings).is_ok();
+    let events = state
+        .notifications
+        .stream(user.session.user_id, admin, after)
+        .await?
+        .ok_or_else(|| AppError::TooManyRequests("Too many notification streams open".to_string()))?
+        .map(|notification| {
+            Ok(Event::default()
+                .id(notification.id.to_string())
+                .event("notification")
+                .data(serde_json::to_string(&notification).unwrap_or_default()))
+        });
+    Ok(Sse::new(events).keep_alive(KeepAlive::default()))
+}
+
+#[derive(Deserialize)]
+struct DeleteAccountRequest {
+    password: String,
+}
+
+/// Deletes the caller's account with their history, settings, and sessions.
+async fn delete_account(
+    State(state): State<AppState>,
+    RequireSession(session): RequireSession,
+    Json(request): Json<DeleteAccountRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    crate::delete_own_account(&state, &session, &request.password).await?;
+    Ok(([(http::header::SET_COOKIE, clear_session_cookie())], Json(())))
+}
+
+async fn get_watchlist(
+    State(state): State<AppState>,
+    RequireSession(session): RequireSession,
+) -> Result<Json<Vec<WatchlistItem>>, AppError> {
+    Ok(Json(state.auth.get_watchlist(session.user_id).await?))
+}
+
+#[derive(Deserialize)]
+struct AddToWatchlistRequest {
+    tmdb_id: i64,
+    media_type: String,
+    title: String,
+    #[serde(default)]
+    poster_path: Option<String>,
+}
+
+#[derive(Serialize)]
+struct AddToWatchlistResponse {
+    /// `false` if the title was already on the list.
+    added: bool,
+}
+
+async fn add_to_watchlist(
+    State(state): State<AppState>,
+    RequireSession(session): RequireSession,
+    Json(request): Json<AddToWatchlistRequest>,
+) -> Result<Json<AddToWatchlistResponse>, AppError> {
+    let media_type = crate::list_media_type(&request.media_type)?;
+    let title = request.title.trim();
+    if title.is_empty() {
+        return Err(AppError::invalid("title_required", "title is required"));
+    }
+    let added = crate::add_to_watchlist(
+        &state,
+        &session,
+        media_type,
+        request.tmdb_id,
+        title,
+        request.poster_path.as_deref().filter(|p| !p.is_empty()),
+    )
+    .await?;
+    Ok(Json(AddToWatchlistResponse { added }))
+}
+
+#[derive(Serialize)]
+struct WatchlistStatusResponse {
+    on_watchlist: bool,
+}
+
+async fn get_watchlist_status(
+    State(state): State<AppState>,
+    RequireSession(session): RequireSession,
+    Path((media_type, id)): Path<(String, i64)>,
+) -> Result<Json<WatchlistStatusResponse>, AppError> {
+    let media_type = crate::list_media_type(&media_type)?;
+    let on_watchlist = state.auth.is_on_watchlist(session.user_id, id, media_type).await?;
+    Ok(Json(WatchlistStatusResponse { on_watchlist }))
+}
+
+/// Also removes the title from a connected TMDB account's watchlist.
+async fn remove_from_watchlist(
+    State(state): State<AppState>,
+    RequireSession(session): RequireSession,
+    Path((media_type, id)): Path<(String, i64)>,
+) -> Result<Json<()>, AppError> {
+    let media_type = crate::list_media_type(&media_type)?;
+    if !crate::remove_from_watchlist(&state, session.user_id, media_type, id).await? {
+        return Err(AppError::NotFound);
+    }
+    Ok(Json(()))
+}
+
+/// What the home page's Continue Watching row shows. Each item's `player_url`
+/// resumes from the saved position.
+async fn get_continue_watching(
+    State(state): State<AppState>,
+    RequireSession(session): RequireSession,
+) -> Result<Json<Vec<ContinueWatchingResponse>>, AppError> {
+    let items = state
+        .auth
+        .get_continue_watching(session.user_id, crate::CONTINUE_WATCHING_LIMIT)
+        .await?;
+    Ok(Json(
+        items
+            .into_iter()
+            .map(|item| ContinueWatchingResponse {
+                player_url: item.player_url(),
+                item,
+            })
+            .collect(),
+    ))
+}
+
+#[derive(Serialize)]
+struct ContinueWatchingResponse {
+    #[serde(flatten)]
+    item: ContinueWatchingItem,
+    player_url: String,
+}
+
+#[derive(Deserialize)]
+struct HistoryQuery {
+    #[serde(default = "default_page")]
+    page: i32,
+    #[serde(default = "default_history_per_page")]
+    per_page: i64,
+}
+
+const MAX_HISTORY_PER_PAGE: i64 = 200;
+
+fn default_history_per_page() -> i64 {
+    50
+}
+
+#[derive(Serialize)]
+struct HistoryPage {
+    page: i32,
+    per_page: i64,
+    total_pages: i64,
+    total_results: i64,
+    results: Vec<WatchHistoryItem>,
+}
+
+/// The caller's watch history, most recently watched first.
+async fn get_history(
+    State(state): State<AppState>,
+    RequireSession(session): RequireSession,
+    Query(params): Query<HistoryQuery>,
+) -> Result<Json<HistoryPage>, AppError> {
+    if params.page < 1 {
+        return Err(AppError::invalid("invalid_page", "page must be at least 1"));
+    }
+    if !(1..=MAX_HISTORY_PER_PAGE).contains(&params.per_page) {
+        return Err(AppError::invalid("invalid_page", format!(
+            "per_page must be between 1 and {}",
+            MAX_HISTORY_PER_PAGE
+        )));
+    }
+    let offset = (i64::from(params.page) - 1) * params.per_page;
+    let (results, total_results) = state
+        .auth
+        .get_watch_history_page(session.user_id, params.per_page, offset)
+        .await?;
+    Ok(Json(HistoryPage {
+        page: params.page,
+        per_page: params.per_page,
+        total_pages: (total_results + params.per_page - 1) / params.per_page,
+        total_results,
+        results,
+    }))
+}
+
+async fn delete_history_entry(
+    State(state): State<AppState>,
+    RequireSession(session): RequireSession,
+    Path(id): Path<i64>,
+) -> Result<StatusCode, AppError> {
+    if !state.auth.remove_from_watch_history(session.user_id, id).await? {
+        return Err(AppError::NotFound);
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[derive(Serialize)]
+struct ClearHistoryResponse {
+    removed: u64,
+}
+
+async fn clear_history(
+    State(state): State<AppState>,
+    RequireSession(session): RequireSession,
+) -> Result<Json<ClearHistoryResponse>, AppError> {
+    let removed = state.auth.clear_watch_history(session.user_id).await?;
+    Ok(Json(ClearHistoryResponse { removed }))
+}
+
+#[derive(Deserialize)]
+struct StatsQuery {
+    /// Only the last this many days; all time when unset.
+    #[serde(default)]
+    days: Option<u32>,
+}
+
+/// Watch time from the hourly rollup. `?days=30` limits it to recent days.
+async fn get_stats(
+    State(state): State<AppState>,
+    RequireSession(session): RequireSession,
+    Query(params): Query<StatsQuery>,
+) -> Result<Json<StatsSummary>, AppError> {
+    let days = params.days.filter(|d| *d > 0);
+    Ok(Json(state.stats.summary(session.user_id, days).await?))
+}
+
+async fn list_playlists(
+    State(state): State<AppState>,
+    RequireSession(session): RequireSession,
+) -> Result<Json<Vec<Playlist>>, AppError> {
+    Ok(Json(state.playlists.list(session.user_id).await?))
+}
+
+#[derive(Deserialize)]
+struct CreatePlaylistRequest {
+    name: String,
+}
+
+async fn create_playlist(
+    State(state): State<AppState>,
+    RequireSession(session): RequireSession,
+    Json(request): Json<CreatePlaylistRequest>,
+) -> Result<(StatusCode, Json<Playlist>), AppError> {
+    let playlist = state.playlists.create(session.user_id, &request.name).await?;
+    Ok((StatusCode::CREATED, Json(playlist)))
+}
+
+async fn get_playlist(
+    State(state): State<AppState>,
+    RequireSession(session): RequireSession,
+    Path(id): Path<i64>,
+) -> Result<Json<PlaylistWithItems>, AppError> {
+    Ok(Json(state.playlists.get_with_items(session.user_id, id).await?))
+}
+
+/// Fields left out are unchanged.
+#[derive(Deserialize)]
+struct UpdatePlaylistRequest {
+    #[serde(default)]
+    name: Option<String>,
+    /// `true` creates a share link, `false` removes it.
+    #[serde(default)]
+    shared: Option<bool>,
+}
+
+async fn update_playlist(
+    State(state): State<AppState>,
+    RequireSession(session): RequireSession,
+    Path(id): Path<i64>,
+    Json(request): Json<UpdatePlaylistRequest>,
+) -> Result<Json<Playlist>, AppError> {
+    let playlists = &state.playlists;
+    if let Some(name) = &request.name {
+        playlists.rename(session.user_id, id, name).await?;
+    }
+    let playlist = match request.shared {
+        Some(shared) => playlists.set_shared(session.user_id, id, shared).await?,
+        None => playlists.get(session.user_id, id).await?,
+    };
+    Ok(Json(playlist))
+}
+
+async fn delete_playlist(
+    State(state): State<AppState>,
+    RequireSession(session): RequireSession,
+    Path(id): Path<i64>,
+) -> Result<Json<()>, AppError> {
+    state.playlists.delete(session.user_id, id).await?;
+    Ok(Json(()))
+}
+
+#[derive(Deserialize)]
+struct AddPlaylistItemRequest {
+    tmdb_id: i64,
+    media_type: String,
+    title: String,
+    #[serde(default)]
+    poster_path: Option<String>,
+}
+
+async fn add_playlist_item(
+    State(state): State<AppState>,
+    RequireSession(session): RequireSession,
+    Path(id): Path<i64>,
+    Json(request): Json<AddPlaylistItemRequest>,
+) -> Result<Json<AddToWatchlistResponse>, AppError> {
+    let title = TitleRef {
+        tmdb_id: request.tmdb_id,
+        media_type: crate::list_media_type(&request.media_type)?.to_string(),
+    };
+    let name = request.title.trim();
+    if name.is_empty() {
+        return Err(AppError::invalid("title_required", "title is required"));
+    }
+    let added = state
+        .playlists
+        .add_item(
+            session.user_id,
+            id,
+            &title,
+            name,
+            request.poster_path.as_deref().filter(|p| !p.is_empty()),
+        )
+        .await?;
+    Ok(Json(AddToWatchlistResponse { added }))
+}
+
+async fn remove_playlist_item(
+    State(state): State<AppState>,
+    RequireSession(session): RequireSession,
+    Path((id, media_type, tmdb_id)): Path<(i64, String, i64)>,
+) -> Result<Json<()>, AppError> {
+    let title = TitleRef {
+        tmdb_id,
+        media_type: crate::list_media_type(&media_type)?.to_string(),
+    };
+    if !state.playlists.remove_item(session.user_id, id, &title).await? {
+        return Err(AppError::NotFound);
+    }
+    Ok(Json(()))
+}
+
+#[derive(Deserialize)]
+struct ReorderPlaylistRequest {
+    items: Vec<TitleRef>,
+}
+
+/// Replaces the playlist's order. Every title must be listed once.
+async fn reorder_playlist(
+    State(state): State<AppState>,
+    RequireSession(session): RequireSession,
+    Path(id): Path<i64>,
+    Json(request): Json<ReorderPlaylistRequest>,
+) -> Result<Json<PlaylistWithItems>, AppError> {
+    state.playlists.reorder(session.user_id, id, &request.items).await?;
+    Ok(Json(state.playlists.get_with_items(session.user_id, id).await?))
+}
+
+/// A playlist shared by link. Needs no session.
+async fn get_shared_playlist(
+    State(state): State<AppState>,
+    Path(token): Path<String>,
+) -> Result<Json<PlaylistWithItems>, AppError> {
+    Ok(Json(state.playlists.get_shared(&token).await?))
+}
+
+#[derive(Serialize)]
+struct SettingsResponse {
+    #[serde(flatten)]
+    preferences: UserPreferences,
+    #[serde(flatten)]
+    player: PlayerPreferences,
+}
+
+async fn get_settings(
+    State(state): State<AppState>,
+    RequireSession(session): RequireSession,
+) -> Result<Json<SettingsResponse>, AppError> {
+    Ok(Json(SettingsResponse {
+        preferences: state.auth.get_preferences(session.user_id).await?,
+        player: state.auth.get_player_preferences(session.user_id).await?,
+    }))
+}
+
+/// Fields left out are unchanged; `null` clears the nullable ones.
+#[derive(Deserialize)]
+struct SettingsUpdate {
+    #[serde(default)]
+    image_quality: Option<ImageQuality>,
+    #[serde(default)]
+    data_saver: Option<bool>,
+    #[serde(default)]
+    pause_history: Option<bool>,
+    #[serde(default, deserialize_with = "nullable")]
+    accent_color: Option<Option<String>>,
+    #[serde(default)]
+    autoplay: Option<bool>,
+    #[serde(default)]
+    next_episode: Option<bool>,
+    #[serde(default)]
+    episode_selector: Option<bool>,
+    #[serde(default, deserialize_with = "nullable")]
+    audio_language: Option<Option<String>>,
+    #[serde(default, deserialize_with = "nullable")]
+    max_quality: Option<Option<u32>>,
+}
+
+/// Tells a `null` field (`Some(None)`) apart from a missing one (`None`).
+fn nullable<'de, D, T>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    Option::<T>::deserialize(deserializer).map(Some)
+}
+
+async fn update_settings(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    Json(update): Json<SettingsUpdate>,
+) -> Result<Json<SettingsResponse>, AppError> {
+    let session = user.require(Permission::EditPreferences)?;
+    let mut preferences = state.auth.get_preferences(session.user_id).await?;
+    let mut player = state.auth.get_player_preferences(session.user_id).await?;
+
+    preferences.image_quality = update.image_quality.unwrap_or(preferences.image_quality);
+    preferences.data_saver = update.data_saver.unwrap_or(preferences.data_saver);
+    preferences.pause_history = update.pause_history.unwrap_or(preferences.pause_history);
+    if let Some(color) = update.accent_color {
+        player.accent_color = match color {
+            Some(color) => Some(PlayerPreferences::parse_color(&color).ok_or_else(|| {
+                AppError::BadRequest("accent_color must be a hex color like #e50914".to_string())
+            })?),
+            None => None,
+        };
+    }
+    if let Some(language) = update.audio_language {
+        player.audio_language = match language {
+            Some(language) => Some(PlayerPreferences::parse_language(&language).ok_or_else(|| {
+                AppError::BadRequest("audio_language must be a two-letter language code".to_string())
+            })?),
+            None => None,
+        };
+    }
+    if let Some(quality) = update.max_quality {
+        player.max_quality = match quality {
+            Some(height) => Some(PlayerPreferences::parse_quality(&height.to_string()).ok_or_else(|| {
+                AppError::BadRequest(format!("max_quality must be one of {:?}", crate::auth::QUALITY_CAPS))
+            })?),
+            None => None,
+        };
+    }
+    player.autoplay = update.autoplay.unwrap_or(player.autoplay);
+    player.next_episode = update.next_episode.unwrap_or(player.next_episode);
+    player.episode_selector = update.episode_selector.unwrap_or(player.episode_selector);
+
+    state.auth.save_preferences(session.user_id, &preferences).await?;
+    state.auth.save_player_preferences(session.user_id, &player).await?;
+    Ok(Json(SettingsResponse { preferences, player }))
+}
+
+async fn list_invites(
+    State(state): State<AppState>,
+) -> Result<Json<Vec<Invite>>, AppError> {
+    Ok(Json(state.invites.list().await?))
+}
+
+#[derive(Deserialize)]
+struct CreateInviteRequest {
+    #[serde(default)]
+    role: Role,
+    /// Omit for an invite that never expires.
+    #[serde(default)]
+    expires_in_days: Option<i64>,
+}
+
+async fn create_invite(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    Json(request): Json<CreateInviteRequest>,
+) -> Result<Json<Invite>, AppError> {
+    let session = &user.session;
+    if request.expires_in_days.is_some_and(|days| days <= 0) {
+        return Err(AppError::BadRequest("expires_in_days must be positive".to_string()));
+    }
+    invites::check_invite_role(session.role, request.role)?;
+    let invite = state
+        .invites
+        .create(session.user_id, request.role, request.expires_in_days)
+        .await?;
+    Ok(Json(invite))
+}
+
+/// Removes an invite that hasn't been used yet.
+async fn revoke_invite(
+    State(state): State<AppState>,
+    Path(code): Path<String>,
+) -> Result<Json<()>, AppError> {
+    if !state.invites.revoke(&code).await? {
+        return Err(AppError::NotFound);
+    }
+    Ok(Json(()))
+}
+
+#[derive(Serialize)]
+struct RolePermissionsResponse {
+    role: Role,
+    permissions: Vec<Permission>,
+}
+
+async fn list_roles(State(state): State<AppState>) -> Json<Vec<RolePermissionsResponse>> {
+    Json(
+        state
+            .roles
+            .all()
+            .into_iter()
+            .map(|(role, permissions)| RolePermissionsResponse { role, permissions })
+            .collect(),
+    )
+}
+
+#[derive(Deserialize)]
+struct SetRolePermissionsRequest {
+    permissions: Vec<Permission>,
+}
+
+/// Replaces everything a role may do. The admin role always keeps
+/// `manage_users` and `manage_settings`. Admins only, so a `manage_users`
+/// grant can't be used to hand out more permissions.
+async fn set_role_permissions(
+    State(state): State<AppState>,
+    RequireAdmin(admin): RequireAdmin,
+    Path(role): Path<String>,
+    Json(request): Json<SetRolePermissionsRequest>,
+) -> Result<Json<RolePermissionsResponse>, AppError> {
+    let role = Role::parse(&role).ok_or(AppError::NotFound)?;
+    state.roles.set(role, &request.permissions).await?;
+    tracing::info!("{} changed the permissions of the {} role", admin.username, role.as_str());
+    let mut permissions: Vec<Permission> = state.roles.permissions(role).into_iter().collect();
+    permissions.sort_by_key(|p| p.as_str());
+    Ok(Json(RolePermissionsResponse { role, permissions }))
+}
+
+/// The stream providers a player can offer, so clients needn't hardcode them.
+async fn list_providers(State(state): State<AppState>) -> Result<Json<Vec<ProviderStatus>>, AppError> {
+    Ok(Json(provider_health::statuses(&state.db, &state.providers).await?))
+}
+
+async fn list_provider_configs(
+    State(state): State<AppState>,
+) -> Result<Json<Vec<ProviderConfig>>, AppError> {
+    Ok(Json(state.providers.configs()))
+}
+
+/// Enables or disables a provider, overrides its priority, or sets the
+/// extra query parameters added to its embed URLs.
+async fn configure_provider(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    Json(mut setting): Json<ProviderSetting>,
+) -> Result<Json<Vec<ProviderConfig>>, AppError> {
+    if state.providers.get(&name).is_none() {
+        return Err(AppError::NotFound);
+    }
+    if setting.options.keys().any(|key| key.trim().is_empty()) {
+        return Err(AppError::BadRequest("Option names can't be empty".to_string()));
+    }
+
+    setting.name = name;
+    state.providers.configure(setting).await?;
+    Ok(Json(state.providers.configs()))
+}
+
+/// Drops a provider's saved settings, back to `STREAM_PROVIDERS` and its own priority.
+async fn reset_provider(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+) -> Result<Json<Vec<ProviderConfig>>, AppError> {
+    if !state.providers.reset(&name).await? {
+        return Err(AppError::NotFound);
+    }
+    Ok(Json(state.providers.configs()))
+}
+
+async fn get_provider_stats(
+    State(state): State<AppState>,
+) -> Result<Json<Vec<ProviderStatsResponse>>, AppError> {
+    let stats = state.providers.stats().all().await?;
+    Ok(Json(
+        stats
+            .into_iter()
+            .map(|stat| ProviderStatsResponse {
+                success_rate: stat.success_rate(),
+                average_latency_ms: stat.average_latency_ms(),
+                score: stat.score(),
+                stat,
+            })
+            .collect(),
+    ))
+}
+
+async fn list_stream_overrides(
+    State(state): State<AppState>,
+) -> Result<Json<Vec<StreamOverride>>, AppError> {
+    Ok(Json(state.providers.overrides().list().await?))
+}
+
+/// Pins a provider or a fixed embed URL for a title or episode.
+async fn set_stream_override(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    Json(mut pin): Json<StreamOverride>,
+) -> Result<Json<StreamOverride>, AppError> {
+    let session = &user.session;
+
+    match pin.media_type.as_str() {
+        "movie" => {
+            pin.season_number = 0;
+            pin.episode_number = 0;
+        }
+        "tv" if pin.season_number >= 0 && pin.episode_number > 0 => {}
+        "tv" => return Err(AppError::invalid("season_required", "season_number and episode_number are required for tv")),
+        _ => return Err(AppError::invalid("invalid_media_type", "media_type must be movie or tv")),
+    }
+    match (&pin.provider, &pin.url) {
+        (Some(provider), None) => {
+            if state.providers.get(provider).is_none() {
+                return Err(AppError::invalid("unknown_provider", format!("Unknown stream provider \"{}\"", provider)));
+            }
+        }
+        (None, Some(url)) => {
+            let scheme = reqwest::Url::parse(url)
+                .map_err(|e| AppError::invalid("invalid_url", format!("Invalid url: {}", e)))?
+                .scheme()
+                .to_string();
+            if scheme != "https" && scheme != "http" {
+                return Err(AppError::invalid("invalid_url", "url must be http or https"));
+            }
+        }
+        _ => return Err(AppError::BadRequest("Set exactly one of provider and url".to_string())),
+    }
+
+    state.providers.overrides().set(&pin, session.user_id).await?;
+    Ok(Json(pin))
+}
+
+#[derive(Deserialize)]
+struct OverrideQuery {
+    tmdb_id: i64,
+    media_type: String,
+    #[serde(default)]
+    season: Option<i64>,
+    #[serde(default)]
+    episode: Option<i64>,
+}
+
+async fn remove_stream_override(
+    State(state): State<AppState>,
+    Query(params): Query<OverrideQuery>,
+) -> Result<Json<()>, AppError> {
+    let removed = state
+        .providers
+        .overrides()
+        .remove(
+            params.tmdb_id,
+            &params.media_type,
+            params.season.unwrap_or(0),
+            params.episode.unwrap_or(0),
+        )
+        .await?;
+    if !removed {
+        return Err(AppError::NotFound);
+    }
+    Ok(Json(()))
+}
+
+async fn list_webhooks(State(state): State<AppState>) -> Result<Json<Vec<Webhook>>, AppError> {
+    Ok(Json(state.webhooks.list().await?))
+}
+
+#[derive(Deserialize)]
+struct CreateWebhookRequest {
+    url: String,
+    events: Vec<WebhookEvent>,
+}
+
+#[derive(Serialize)]
+struct CreatedWebhook {
+    #[serde(flatten)]
+    webhook: Webhook,
+    /// Signs every delivery. It is not shown again.
+    secret: String,
+}
+
+/// Registers a URL to be sent the chosen events.
+async fn create_webhook(
+    State(state): State<AppState>,
+    RequireSession(session): RequireSession,
+    Json(request): Json<CreateWebhookRequest>,
+) -> Result<(StatusCode, Json<CreatedWebhook>), AppError> {
+    let url = reqwest::Url::parse(request.url.trim())
+        .map_err(|e| AppError::invalid("invalid_url", format!("Invalid url: {}", e)))?;
+    crate::outbound::check_url_resolves(&url)
+        .await
+        .map_err(|message| AppError::invalid("invalid_url", message))?;
+    if request.events.is_empty() {
+        return Err(AppError::BadRequest("Choose at least one event".to_string()));
+    }
+
+    let (webhook, secret) = state
+        .webhooks
+        .create(url.as_str(), &request.events, session.user_id)
+        .await?;
+    tracing::info!("{} added a webhook for {}", session.username, webhook.url);
+    Ok((StatusCode::CREATED, Json(CreatedWebhook { webhook, secret })))
+}
+
+#[derive(Deserialize)]
+struct UpdateWebhookRequest {
+    enabled: bool,
+}
+
+/// Pauses or resumes a webhook. Events while it is paused are not queued.
+async fn update_webhook(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+    Json(request): Json<UpdateWebhookRequest>,
+) -> Result<Json<Webhook>, AppError> {
+    state
+        .webhooks
+        .set_enabled(id, request.enabled)
+        .await?
+        .map(Json)
+        .ok_or(AppError::NotFound)
+}
+
+async fn delete_webhook(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+) -> Result<StatusCode, AppError> {
+    if !state.webhooks.remove(id).await? {
+        return Err(AppError::NotFound);
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+const WEBHOOK_DELIVERIES_LIMIT: i64 = 50;
+
+/// The webhook's latest deliveries, for checking that a receiver works.
+async fn list_webhook_deliveries(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+) -> Result<Json<Vec<WebhookDelivery>>, AppError> {
+    Ok(Json(state.webhooks.deliveries(id, WEBHOOK_DELIVERIES_LIMIT).await?))
+}
+
+#[derive(Deserialize)]
+struct ProvidersQuery {
+    #[serde(default)]
+    region: Option<String>,
+}
+
+async fn get_watch_providers(
+    State(state): State<AppState>,
+    Path((media_type, id)): Path<(String, i64)>,
+    Query(params): Query<ProvidersQuery>,
+) -> Result<Json<crate::tmdb::WatchProviders>, AppError> {
+    if media_type != "movie" && media_type != "tv" {
+        return Err(AppError::invalid("invalid_media_type", "Media type must be movie or tv"));
+    }
+
+    let region = params.region.as_deref().unwrap_or(state.tmdb.region());
+    let providers = state.tmdb.get_watch_providers(&media_type, id, region).await?;
+    Ok(Json(providers.unwrap_or_default()))
+}
+
+async fn get_recommendations(
+    State(state): State<AppState>,
+    Path((media_type, id)): Path<(String, i64)>,
+    Query(params): Query<PageQuery>,
+) -> Result<Json<crate::tmdb::SearchResponse>, AppError> {
+    if media_type != "movie" && media_type != "tv" {
+        return Err(AppError::invalid("invalid_media_type", "Media type must be movie or tv"));
+    }
+
+    let recommendations = state.tmdb.get_recommendations(&media_type, id, params.page()?).await?;
+    Ok(Json(recommendations))
+}
+
+#[derive(Serialize)]
+struct MetricsResponse {
+    #[serde(flatten)]
+    metrics: MetricsSnapshot,
+    tmdb_keys: Vec<KeyStatus>,
+}
+
+async fn get_metrics(State(state): State<AppState>, _reader: crate::auth::MetricsReader) -> Json<MetricsResponse> {
+    Json(MetricsResponse {
+        metrics: state.metrics.snapshot(),
+        tmdb_keys: state.tmdb.key_status(),
+    })
+}
+
+async fn get_version() -> Json<crate::changelog::VersionInfo> {
+    Json(crate::changelog::version_info())
+}
+
+async fn get_openapi() -> Json<&'static serde_json::Value> {
+    Json(crate::openapi::document())
+}
+
+/// Swagger UI over `/api/openapi.json`.
+async fn api_docs() -> axum::response::Html<String> {
+    axum::response::Html(crate::templates::render_api_docs(&format!("{}/openapi.json", V1_PREFIX)))
+}
@@ -1,165 +1,830 @@
+use axum::{
+    async_trait,
+    extract::{ConnectInfo, FromRequestParts, Request, State},
+    http::{header, request::Parts, HeaderMap},
+    middleware::Next,
+    response::{IntoResponse, Redirect, Response},
+};
 use bcrypt::{hash, DEFAULT_COST};
+use chacha20poly1305::aead::{Aead, AeadCore, OsRng};
+use chacha20poly1305::{XChaCha20Poly1305, XNonce};
 use chrono::{Duration, Utc};
+use dashmap::DashMap;
 use hmac::{Hmac, Mac};
+use ipnet::IpNet;
 use serde::{Deserialize, Serialize};
-use sha2::Sha256;
-use sqlx::{Pool, Sqlite};
-use tracing::info;
+use sha2::{Digest, Sha256};
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::convert::Infallible;
+use std::net::{IpAddr, SocketAddr};
+use std::str::FromStr;
+use std::sync::{Arc, OnceLock};
+use std::time::Instant;
+use tracing::{info, warn};
 
-pub const SESSION_SECRET: &[u8] = b"your-32-byte-secret-key-change-me-in-prod!";
+use crate::db::{self, Db};
+use crate::error::AppError;
+use crate::metrics::AppMetrics;
+use crate::tmdb::image_config;
+use crate::AppState;
+
+/// Shortest `SESSION_SECRET` accepted.
+const MIN_SECRET_LENGTH: usize = 32;
+
+/// `app_secrets` row holding the generated secret.
+const GENERATED_SECRET_NAME: &str = "session_secret";
+
+/// Keys for signing session tokens and stream links. New signatures use
+/// `current`; `previous` is still accepted, so rotating the secret doesn't
+/// sign everyone out at once.
+#[derive(Clone)]
+pub struct SessionSecrets {
+    current: Vec<u8>,
+    previous: Option<Vec<u8>>,
+}
+
+impl std::fmt::Debug for SessionSecrets {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SessionSecrets")
+            .field("previous", &self.previous.is_some())
+            .finish_non_exhaustive()
+    }
+}
+
+impl SessionSecrets {
+    /// Uses the configured secret, or else the one generated on first run.
+    pub async fn load(db: &Db, configured: Option<&str>, previous: Option<&str>) -> anyhow::Result<Self> {
+        for secret in [configured, previous].into_iter().flatten() {
+            if secret.len() < MIN_SECRET_LENGTH {
+                return Err(anyhow::anyhow!(
+                    "SESSION_SECRET and SESSION_SECRET_PREVIOUS must be at least {} characters",
+                    MIN_SECRET_LENGTH
+                ));
+            }
+        }
+
+        let current = match configured {
+            Some(secret) => secret.to_string(),
+            None => Self::generated(db).await?,
+        };
+        Ok(Self {
+            current: current.into_bytes(),
+            previous: previous.map(|s| s.as_bytes().to_vec()),
+        })
+    }
+
+    async fn generated(db: &Db) -> anyhow::Result<String> {
+        let candidate = format!(
+            "{}{}",
+            uuid::Uuid::new_v4().simple(),
+            uuid::Uuid::new_v4().simple()
+        );
+        db::query("INSERT INTO app_secrets (name, value) VALUES (?, ?) ON CONFLICT DO NOTHING")
+            .bind(GENERATED_SECRET_NAME)
+            .bind(&candidate)
+            .execute(db)
+            .await?;
+        let secret: String = db::query_scalar("SELECT value FROM app_secrets WHERE name = ?")
+            .bind(GENERATED_SECRET_NAME)
+            .fetch_one(db)
+            .await?;
+        if secret == candidate {
+            info!("Generated a session secret; set SESSION_SECRET to manage it yourself");
+        }
+        Ok(secret)
+    }
+
+    /// Hex HMAC-SHA256 of `message` under the current secret.
+    pub fn sign(&self, message: &str) -> String {
+        hex::encode(mac(&self.current, message).finalize().into_bytes())
+    }
+
+    /// Whether `signature` is a valid hex signature of `message` under the
+    /// current or previous secret. The comparison is constant-time.
+    pub fn verify(&self, message: &str, signature: &str) -> bool {
+        let Ok(signature) = hex::decode(signature) else {
+            return false;
+        };
+        std::iter::once(&self.current)
+            .chain(self.previous.as_ref())
+            .any(|secret| mac(secret, message).verify_slice(&signature).is_ok())
+    }
+}
+
+/// Marks a value sealed by [`SessionSecrets::seal`].
+const SEALED_PREFIX: &str = "sealed:v1:";
+
+impl SessionSecrets {
+    /// Encrypts `plaintext` for storage with XChaCha20-Poly1305 under a key
+    /// derived from the current secret, so credentials such as TMDB tokens
+    /// aren't stored in the clear. This only keeps them from someone holding
+    /// the database when `SESSION_SECRET` is set: a generated secret lives
+    /// in `app_secrets` in the same database.
+    pub fn seal(&self, plaintext: &str) -> String {
+        let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let ciphertext = seal_cipher(&self.current)
+            .encrypt(&nonce, plaintext.as_bytes())
+            .expect("sealing a short string can't fail");
+        format!("{}{}.{}", SEALED_PREFIX, hex::encode(nonce), hex::encode(ciphertext))
+    }
+
+    /// Decrypts a value from [`Self::seal`] under the current or previous
+    /// secret. Values stored before sealing existed come back as they are;
+    /// `None` means it was sealed under a secret no longer configured, or
+    /// was tampered with.
+    pub fn open(&self, stored: &str) -> Option<String> {
+        self.open_with_key(stored).map(|(plaintext, _)| plaintext)
+    }
+
+    /// `stored` sealed again under the current secret, or `None` if it
+    /// already is. Catches values saved before sealing existed and ones
+    /// only the previous secret opens, so dropping `SESSION_SECRET_PREVIOUS`
+    /// after a rotation doesn't lose them. Values no configured secret opens
+    /// are left alone.
+    pub fn reseal(&self, stored: &str) -> Option<String> {
+        match self.open_with_key(stored)? {
+            (plaintext, SealKey::Unsealed | SealKey::Previous) => Some(self.seal(&plaintext)),
+            (_, SealKey::Current) => None,
+        }
+    }
+
+    fn open_with_key(&self, stored: &str) -> Option<(String, SealKey)> {
+        let Some(sealed) = stored.strip_prefix(SEALED_PREFIX) else {
+            return Some((stored.to_string(), SealKey::Unsealed));
+        };
+        let (nonce, ciphertext) = sealed.split_once('.')?;
+        let (Ok(nonce), Ok(ciphertext)) = (hex::decode(nonce), hex::decode(ciphertext)) else {
+            return None;
+        };
+        if nonce.len() != 24 {
+            return None;
+        }
+        let nonce = XNonce::from_slice(&nonce);
+        let (plaintext, key) = seal_cipher(&self.current)
+            .decrypt(nonce, ciphertext.as_slice())
+            .map(|plaintext| (plaintext, SealKey::Current))
+            .or_else(|_| {
+                let previous = self.previous.as_ref().ok_or(chacha20poly1305::Error)?;
+                seal_cipher(previous)
+                    .decrypt(nonce, ciphertext.as_slice())
+                    .map(|plaintext| (plaintext, SealKey::Previous))
+            })
+            .ok()?;
+        Some((String::from_utf8(plaintext).ok()?, key))
+    }
+}
+
+/// Which secret opened a stored value.
+enum SealKey {
+    Unsealed,
+    Current,
+    Previous,
+}
+
+/// Cipher keyed by a subkey of `secret`, so sealing never shares a key
+/// with session signing.
+fn seal_cipher(secret: &[u8]) -> XChaCha20Poly1305 {
+    let mut derive = Hmac::<Sha256>::new_from_slice(secret).expect("HMAC can accept any key size");
+    derive.update(b"ruststream-seal-key");
+    chacha20poly1305::KeyInit::new(&derive.finalize().into_bytes())
+}
+
+fn mac(secret: &[u8], message: &str) -> Hmac<Sha256> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret).expect("HMAC can accept any key size");
+    mac.update(message.as_bytes());
+    mac
+}
+
+/// Cookie holding the session token of a signed-in account. Without it,
+/// requests act as the local user.
+pub const SESSION_COOKIE: &str = "ruststream_session";
+
+/// How long a new session lasts, from the "remember me" checkbox.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SessionDuration {
+    /// Kept across browser restarts for a week.
+    Remembered,
+    /// Dropped when the browser closes, and expires after 12 hours even if
+    /// the browser keeps it.
+    Browser,
+}
+
+impl SessionDuration {
+    pub fn from_remember(remember: bool) -> Self {
+        if remember {
+            Self::Remembered
+        } else {
+            Self::Browser
+        }
+    }
+
+    fn lifetime(&self) -> Duration {
+        match self {
+            Self::Remembered => Duration::days(7),
+            Self::Browser => Duration::hours(12),
+        }
+    }
+}
+
+/// `Set-Cookie` value that signs the browser in with `token`. A remembered
+/// session's cookie outlives the browser; otherwise it has no `Max-Age`.
+pub fn session_cookie(token: &str, duration: SessionDuration) -> String {
+    let cookie = format!("{}={}; Path=/; HttpOnly; SameSite=Lax", SESSION_COOKIE, token);
+    match duration {
+        SessionDuration::Remembered => format!("{}; Max-Age={}", cookie, duration.lifetime().num_seconds()),
+        SessionDuration::Browser => cookie,
+    }
+}
+
+/// `Set-Cookie` value that drops the session cookie from the browser.
+pub fn clear_session_cookie() -> String {
+    format!("{}=; Path=/; HttpOnly; SameSite=Lax; Max-Age=0", SESSION_COOKIE)
+}
+
+/// A hash, at the cost new passwords use, to check against when the
+/// username doesn't exist.
+fn dummy_password_hash() -> &'static str {
+    static HASH: OnceLock<String> = OnceLock::new();
+    HASH.get_or_init(|| hash(uuid::Uuid::new_v4().to_string(), DEFAULT_COST).unwrap_or_default())
+}
+
+/// How often a session's `last_seen_at` is written, so browsing doesn't
+/// turn every request into a database write.
+const LAST_SEEN_INTERVAL_SECS: i64 = 60;
+
+/// Where a session was signed in from, shown on the sessions page.
+#[derive(Debug, Clone, Default)]
+pub struct SessionClient {
+    pub user_agent: Option<String>,
+    pub ip: Option<String>,
+}
+
+impl SessionClient {
+    pub fn new(headers: &HeaderMap, ip: IpAddr) -> Self {
+        let user_agent = headers
+            .get(header::USER_AGENT)
+            .and_then(|value| value.to_str().ok())
+            .map(|value| value.chars().take(MAX_USER_AGENT_LENGTH).collect());
+        Self {
+            user_agent,
+            ip: Some(ip.to_string()),
+        }
+    }
+}
+
+const MAX_USER_AGENT_LENGTH: usize = 256;
+
+/// One of a user's signed-in sessions.
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct ActiveSession {
+    pub id: String,
+    pub created_at: String,
+    /// Unix timestamp, to within a minute.
+    pub last_seen_at: Option<i64>,
+    pub user_agent: Option<String>,
+    pub ip: Option<String>,
+    pub expires_at: i64,
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Session {
     pub id: String,
     pub user_id: i64,
     pub username: String,
-    pub is_admin: bool,
+    pub role: Role,
     pub expires_at: i64,
 }
 
+/// The signed-in user and their role's permissions, extracted from the
+/// request. Handlers call [`CurrentUser::require`] before doing anything
+/// role-restricted, unless their route group is guarded by [`require_permission`].
+#[derive(Debug, Clone)]
+pub struct CurrentUser {
+    pub session: Session,
+    permissions: HashSet<Permission>,
+}
+
+impl CurrentUser {
+    pub fn require(&self, permission: Permission) -> Result<&Session, AppError> {
+        if self.permissions.contains(&permission) {
+            Ok(&self.session)
+        } else {
+            Err(AppError::Forbidden(format!(
+                "The {} role is not allowed to do this",
+                self.session.role.as_str()
+            )))
+        }
+    }
+}
+
+#[async_trait]
+impl FromRequestParts<AppState> for CurrentUser {
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &AppState) -> Result<Self, Self::Rejection> {
+        if let Some(user) = parts.extensions.get::<CurrentUser>() {
+            return Ok(user.clone());
+        }
+        let RequireSession(session) = RequireSession::from_request_parts(parts, state).await?;
+        Ok(CurrentUser {
+            permissions: state.roles.permissions(session.role),
+            session,
+        })
+    }
+}
+
+/// The account signed in through the session cookie, or else the local
+/// user; `None` only if neither resolves. The cookie is validated once per
+/// request and the result shared with [`RequireSession`], [`RequireAdmin`],
+/// and [`CurrentUser`].
+#[derive(Debug, Clone)]
+pub struct MaybeSession(pub Option<Session>);
+
+#[async_trait]
+impl FromRequestParts<AppState> for MaybeSession {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, state: &AppState) -> Result<Self, Self::Rejection> {
+        if let Some(session) = parts.extensions.get::<MaybeSession>() {
+            return Ok(session.clone());
+        }
+        let session = MaybeSession(resolve_session(state, &parts.headers).await);
+        parts.extensions.insert(session.clone());
+        Ok(session)
+    }
+}
+
+/// Like [`MaybeSession`], but rejects the request with 401 when there is no session.
+#[derive(Debug, Clone)]
+pub struct RequireSession(pub Session);
+
+#[async_trait]
+impl FromRequestParts<AppState> for RequireSession {
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &AppState) -> Result<Self, Self::Rejection> {
+        let Ok(MaybeSession(session)) = MaybeSession::from_request_parts(parts, state).await;
+        session.map(RequireSession).ok_or(AppError::Unauthorized)
+    }
+}
+
+/// A session with the admin role, whatever permissions other roles have been
+/// given. For things a `manage_users` grant alone mustn't reach, such as
+/// changing what roles may do.
+#[derive(Debug, Clone)]
+pub struct RequireAdmin(pub Session);
+
+#[async_trait]
+impl FromRequestParts<AppState> for RequireAdmin {
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &AppState) -> Result<Self, Self::Rejection> {
+        let RequireSession(session) = RequireSession::from_request_parts(parts, state).await?;
+        if session.role != Role::Admin {
+            return Err(AppError::Forbidden("Only admins can do this".to_string()));
+        }
+        Ok(RequireAdmin(session))
+    }
+}
+
+async fn resolve_session(state: &AppState, headers: &HeaderMap) -> Option<Session> {
+    if let Some(token) = cookie(headers, SESSION_COOKIE) {
+        match state.sessions.validate_session(token).await {
+            Ok(Some(session)) => return Some(session),
+            Ok(None) => {}
+            Err(e) => warn!("Could not validate session: {}", e),
+        }
+    }
+    match state.auth.mode() {
+        AuthMode::Local => state.auth.get_local_session().await.ok(),
+        AuthMode::Accounts => None,
+    }
+}
+
+pub(crate) fn cookie<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers
+        .get_all(header::COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(';'))
+        .find_map(|pair| {
+            let (key, value) = pair.trim().split_once('=')?;
+            (key == name).then_some(value)
+        })
+}
+
+/// A freshly issued session token, plus how many older sessions were signed
+/// out to make room for it so the caller can tell the user.
+#[derive(Debug, Clone)]
+pub struct NewSession {
+    pub token: String,
+    pub duration: SessionDuration,
+    pub evicted: u64,
+}
+
+impl NewSession {
+    /// `Set-Cookie` value for this session.
+    pub fn cookie(&self) -> String {
+        session_cookie(&self.token, self.duration)
+    }
+}
+
+/// How long a validated session is answered from memory before the
+/// database is checked again. Revoking through [`SessionStore`] drops cached
+/// entries straight away.
+const SESSION_CACHE_TTL_SECS: u64 = 5 * 60;
+
+#[derive(Debug, Clone)]
+struct CachedSession {
+    session: Session,
+    last_seen_at: i64,
+    cached_at: Instant,
+}
+
 #[derive(Debug)]
 pub struct SessionStore {
-    db: Pool<Sqlite>,
+    db: Db,
+    secrets: SessionSecrets,
+    max_sessions_per_user: Option<u32>,
+    metrics: Arc<AppMetrics>,
+    /// Recently validated sessions by session id.
+    cache: DashMap<String, CachedSession>,
 }
 
 impl SessionStore {
-    pub fn new(db: Pool<Sqlite>) -> Self {
-        Self { db }
+    pub fn new(
+        db: Db,
+        secrets: SessionSecrets,
+        max_sessions_per_user: Option<u32>,
+        metrics: Arc<AppMetrics>,
+    ) -> Self {
+        Self {
+            db,
+            secrets,
+            max_sessions_per_user,
+            metrics,
+            cache: DashMap::new(),
+        }
     }
 
-    pub async fn create_session(&self, user_id: i64, username: &str, is_admin: bool) -> anyhow::Result<String> {
+    pub async fn create_session(
+        &self,
+        user_id: i64,
+        username: &str,
+        role: Role,
+        client: &SessionClient,
+        duration: SessionDuration,
+    ) -> anyhow::Result<NewSession> {
         let session_id = uuid::Uuid::new_v4().to_string();
-        let expires_at = (Utc::now() + Duration::days(7)).timestamp();
+        let now = Utc::now();
+        let expires_at = (now + duration.lifetime()).timestamp();
         
-        let signature = self.create_signature(&session_id, user_id, expires_at);
-        let session_token = format!("{}.{}", session_id, signature);
+        let claims = format!("{}.{}.{}", session_id, user_id, expires_at);
+        let session_token = format!("{}.{}", claims, self.secrets.sign(&claims));
         
-        sqlx::query(
-            "INSERT INTO sessions (session_id, user_id, username, is_admin, expires_at) VALUES (?, ?, ?, ?, ?)"
+        db::query(
+            r#"
+            INSERT INTO sessions (session_id, user_id, username, is_admin, role, expires_at, last_seen_at, user_agent, ip)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)
+            "#
         )
         .bind(&session_id)
         .bind(user_id)
         .bind(username)
-        .bind(is_admin)
+        .bind(role == Role::Admin)
+        .bind(role.as_str())
         .bind(expires_at)
+        .bind(now.timestamp())
+        .bind(&client.user_agent)
+        .bind(&client.ip)
         .execute(&self.db)
         .await?;
         
         info!("Created session for user: {}", username);
-        Ok(session_token)
+
+        let evicted = match self.max_sessions_per_user {
+            Some(limit) => self.evict_oldest_sessions(user_id, limit).await?,
+            None => 0,
+        };
+        if evicted > 0 {
+            warn!(
+                "Session limit reached for user {}: signed out {} older session(s)",
+                username, evicted
+            );
+        }
+
+        Ok(NewSession {
+            token: session_token,
+            duration,
+            evicted,
+        })
+    }
+
+    async fn evict_oldest_sessions(&self, user_id: i64, limit: u32) -> anyhow::Result<u64> {
+        let result = db::query(
+            r#"
+            DELETE FROM sessions
+            WHERE user_id = ?
+            AND id NOT IN (
+                SELECT id FROM sessions WHERE user_id = ? ORDER BY id DESC LIMIT ?
+            )
+            "#
+        )
+        .bind(user_id)
+        .bind(user_id)
+        .bind(limit as i64)
+        .execute(&self.db)
+        .await?;
+
+        if result.rows_affected() > 0 {
+            self.forget_user(user_id);
+        }
+        Ok(result.rows_affected())
     }
 
+    /// Checks a `session_id.user_id.expires_at.signature` token. The
+    /// signature is verified first, so forged tokens never reach the
+    /// database, and sessions validated in the last few minutes are answered
+    /// from memory.
     pub async fn validate_session(&self, session_token: &str) -> anyhow::Result<Option<Session>> {
-        let parts: Vec<&str> = session_token.split('.').collect();
-        if parts.len() != 2 {
+        let Some((session_id, user_id, expires_at)) = self.verify_token(session_token) else {
+            self.metrics.record_auth_failure();
+            return Ok(None);
+        };
+
+        let now = Utc::now().timestamp();
+        if expires_at < now {
+            self.delete_session(session_id).await?;
             return Ok(None);
         }
-        
-        let (session_id, signature) = (parts[0], parts[1]);
-        
-        let session_row: Option<(String, i64, String, bool, i64)> = sqlx::query_as(
-            "SELECT session_id, user_id, username, is_admin, expires_at FROM sessions WHERE session_id = ?"
+
+        let cached = self.cache.get_mut(session_id).and_then(|mut cached| {
+            if cached.cached_at.elapsed().as_secs() >= SESSION_CACHE_TTL_SECS {
+                return None;
+            }
+            let stale = now - cached.last_seen_at >= LAST_SEEN_INTERVAL_SECS;
+            if stale {
+                cached.last_seen_at = now;
+            }
+            Some((cached.session.clone(), stale))
+        });
+        if let Some((session, stale)) = cached {
+            if stale {
+                self.touch(session_id, now).await?;
+            }
+            return Ok(Some(session));
+        }
+
+        let session_row: Option<(String, String, Option<i64>)> = db::query_as(
+            "SELECT username, role, last_seen_at FROM sessions WHERE session_id = ? AND user_id = ? AND expires_at = ?"
         )
         .bind(session_id)
+        .bind(user_id)
+        .bind(expires_at)
         .fetch_optional(&self.db)
         .await?;
-        
-        if let Some((_, user_id, username, is_admin, expires_at)) = session_row {
-            if expires_at < Utc::now().timestamp() {
-                sqlx::query("DELETE FROM sessions WHERE session_id = ?")
-                    .bind(session_id)
-                    .execute(&self.db)
-                    .await?;
-                return Ok(None);
-            }
-            
-            let expected_signature = self.create_signature(session_id, user_id, expires_at);
-            if signature != expected_signature {
-                return Ok(None);
+        let Some((username, role, last_seen_at)) = session_row else {
+            self.cache.remove(session_id);
+            self.metrics.record_auth_failure();
+            return Ok(None);
+        };
+
+        let last_seen_at = match last_seen_at {
+            Some(seen) if now - seen < LAST_SEEN_INTERVAL_SECS => seen,
+            _ => {
+                self.touch(session_id, now).await?;
+                now
             }
-            
-            Ok(Some(Session {
-                id: session_id.to_string(),
-                user_id,
-                username,
-                is_admin,
-                expires_at,
-            }))
-        } else {
-            Ok(None)
+        };
+        let session = Session {
+            id: session_id.to_string(),
+            user_id,
+            username,
+            role: Role::parse(&role).unwrap_or_default(),
+            expires_at,
+        };
+        self.cache.insert(
+            session_id.to_string(),
+            CachedSession {
+                session: session.clone(),
+                last_seen_at,
+                cached_at: Instant::now(),
+            },
+        );
+        Ok(Some(session))
+    }
+
+    /// Splits a token into its session id, user id, and expiry if its
+    /// signature is valid.
+    fn verify_token<'a>(&self, session_token: &'a str) -> Option<(&'a str, i64, i64)> {
+        let (claims, signature) = session_token.rsplit_once('.')?;
+        if !self.secrets.verify(claims, signature) {
+            return None;
         }
+        let mut parts = claims.splitn(3, '.');
+        let (session_id, user_id, expires_at) = (parts.next()?, parts.next()?, parts.next()?);
+        Some((session_id, user_id.parse().ok()?, expires_at.parse().ok()?))
+    }
+
+    async fn touch(&self, session_id: &str, now: i64) -> anyhow::Result<()> {
+        db::query("UPDATE sessions SET last_seen_at = ? WHERE session_id = ?")
+            .bind(now)
+            .bind(session_id)
+            .execute(&self.db)
+            .await?;
+        Ok(())
+    }
+
+    /// Drops the user's sessions from the in-memory cache after they were
+    /// removed from the database.
+    fn forget_user(&self, user_id: i64) {
+        self.cache.retain(|_, cached| cached.session.user_id != user_id);
     }
 
     pub async fn delete_session(&self, session_id: &str) -> anyhow::Result<()> {
-        sqlx::query("DELETE FROM sessions WHERE session_id = ?")
+        db::query("DELETE FROM sessions WHERE session_id = ?")
             .bind(session_id)
             .execute(&self.db)
             .await?;
+        self.cache.remove(session_id);
         Ok(())
     }
 
-    fn create_signature(&self, session_id: &str, user_id: i64, expires_at: i64) -> String {
-        let message = format!("{}.{}.{}", session_id, user_id, expires_at);
-        let mut mac = Hmac::<Sha256>::new_from_slice(SESSION_SECRET)
-            .expect("HMAC can accept any key size");
-        mac.update(message.as_bytes());
-        let result = mac.finalize().into_bytes();
-        hex::encode(result)
+    /// A user's unexpired sessions, most recently used first.
+    pub async fn list_sessions(&self, user_id: i64) -> anyhow::Result<Vec<ActiveSession>> {
+        let sessions = db::query_as(
+            r#"
+            SELECT session_id AS id, created_at, last_seen_at, user_agent, ip, expires_at
+            FROM sessions
+            WHERE user_id = ? AND expires_at >= ?
+            ORDER BY COALESCE(last_seen_at, 0) DESC, id DESC
+            "#
+        )
+        .bind(user_id)
+        .bind(Utc::now().timestamp())
+        .fetch_all(&self.db)
+        .await?;
+        Ok(sessions)
+    }
+
+    /// Signs out one of the user's sessions. Returns whether it existed.
+    pub async fn revoke_session(&self, user_id: i64, session_id: &str) -> anyhow::Result<bool> {
+        let result = db::query("DELETE FROM sessions WHERE user_id = ? AND session_id = ?")
+            .bind(user_id)
+            .bind(session_id)
+            .execute(&self.db)
+            .await?;
+        let revoked = result.rows_affected() > 0;
+        if revoked {
+            self.cache.remove(session_id);
+        }
+        Ok(revoked)
+    }
+
+    /// Signs the user out everywhere. Returns how many sessions were removed.
+    pub async fn revoke_all_sessions(&self, user_id: i64) -> anyhow::Result<u64> {
+        let result = db::query("DELETE FROM sessions WHERE user_id = ?")
+            .bind(user_id)
+            .execute(&self.db)
+            .await?;
+        self.forget_user(user_id);
+        info!("Signed out {} session(s) for user {}", result.rows_affected(), user_id);
+        Ok(result.rows_affected())
+    }
+
+    /// Deletes expired sessions, and drops cached sessions past their TTL.
+    /// Returns how many sessions were deleted.
+    pub async fn remove_expired(&self) -> anyhow::Result<u64> {
+        let now = Utc::now().timestamp();
+        let result = db::query("DELETE FROM sessions WHERE expires_at <= ?")
+            .bind(now)
+            .execute(&self.db)
+            .await?;
+        self.cache.retain(|_, cached| {
+            cached.session.expires_at > now && cached.cached_at.elapsed().as_secs() < SESSION_CACHE_TTL_SECS
+        });
+        Ok(result.rows_affected())
+    }
+}
+
+/// How requests without a session are treated, from `AUTH_MODE`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AuthMode {
+    /// Every request without a session acts as the local user, so a
+    /// single-user desktop setup never sees a sign-in page.
+    #[default]
+    Local,
+    /// Everyone signs in with an account; pages redirect to `/login`.
+    Accounts,
+}
+
+impl AuthMode {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "local" => Some(Self::Local),
+            "accounts" => Some(Self::Accounts),
+            _ => None,
+        }
     }
 }
 
 pub struct AuthManager {
-    db: Pool<Sqlite>,
+    db: Db,
+    mode: AuthMode,
 }
 
 impl AuthManager {
-    pub fn new(db: Pool<Sqlite>) -> Self {
-        Self { db }
+    pub fn new(db: Db, mode: AuthMode) -> Self {
+        Self { db, mode }
+    }
+
+    pub fn mode(&self) -> AuthMode {
+        self.mode
     }
 
-    pub async fn init_local_user(&self) -> anyhow::Result<()> {
-        let exists: bool = sqlx::query_scalar(
+    /// Creates the local user if it's missing and gives it its role: a
+    /// member, or an admin only when `LOCAL_USER_ADMIN` opts in, since in
+    /// local mode every request without a session acts as this user.
+    pub async fn init_local_user(&self, admin: bool) -> anyhow::Result<()> {
+        let role = if admin { Role::Admin } else { Role::Member };
+        let exists: bool = db::query_scalar(
             "SELECT EXISTS(SELECT 1 FROM users WHERE username = 'local')"
         )
         .fetch_one(&self.db)
         .await?;
 
-        if !exists {
+        if exists {
+            db::query("UPDATE users SET role = ?, is_admin = ? WHERE username = 'local'")
+                .bind(role.as_str())
+                .bind(admin)
+                .execute(&self.db)
+                .await?;
+        } else {
             info!("Creating local user...");
             let password_hash = hash("local", DEFAULT_COST)?;
             
-            sqlx::query(
-                "INSERT INTO users (username, password_hash, is_admin) VALUES (?, ?, ?)"
+            db::query(
+                "INSERT INTO users (username, password_hash, is_admin, role) VALUES (?, ?, ?, ?)"
             )
             .bind("local")
             .bind(&password_hash)
-            .bind(false)
+            .bind(admin)
+            .bind(role.as_str())
             .execute(&self.db)
             .await?;
         }
+        if admin && self.mode == AuthMode::Local {
+            warn!("LOCAL_USER_ADMIN is set: every request without a session has admin rights");
+        }
 
         Ok(())
     }
 
+    /// The account's id and role if `password` is right. The local user
+    /// has no password to sign in with.
+    pub async fn verify_password(&self, username: &str, password: &str) -> anyhow::Result<Option<(i64, Role)>> {
+        if username == "local" {
+            return Ok(None);
+        }
+        let row: Option<(i64, String, String)> =
+            db::query_as("SELECT id, password_hash, role FROM users WHERE username = ?")
+                .bind(username)
+                .fetch_optional(&self.db)
+                .await?;
+        let Some((user_id, password_hash, role)) = row else {
+            // Spend as long as a real check would, so timing doesn't reveal
+            // which usernames exist.
+            let _ = bcrypt::verify(password, dummy_password_hash());
+            return Ok(None);
+        };
+        if !bcrypt::verify(password, &password_hash)? {
+            return Ok(None);
+        }
+        Ok(Some((user_id, Role::parse(&role).unwrap_or_default())))
+    }
+
     pub async fn get_local_session(&self) -> anyhow::Result<Session> {
-        let row: Option<(i64, String, bool)> = sqlx::query_as(
-            "SELECT id, username, is_admin FROM users WHERE username = 'local' LIMIT 1"
+        let row: Option<(i64, String, String)> = db::query_as(
+            "SELECT id, username, role FROM users WHERE username = 'local' LIMIT 1"
         )
         .fetch_optional(&self.db)
         .await?;
 
-        let (user_id, username, is_admin) = row.ok_or_else(|| anyhow::anyhow!("Local user missing"))?;
+        let (user_id, username, role) = row.ok_or_else(|| anyhow::anyhow!("Local user missing"))?;
         Ok(Session {
             id: "local".to_string(),
             user_id,
             username,
-            is_admin,
+            role: Role::parse(&role).unwrap_or_default(),
             expires_at: i64::MAX,
         })
     }
 
     // User management is intentionally removed for the single-user local mode.
 
+    #[allow(clippy::too_many_arguments)]
     pub async fn add_to_watch_history(
         &self,
         user_id: i64,
@@ -174,13 +839,14 @@ impl AuthManager {
         let season_num = season_number.unwrap_or(-1);
         let episode_num = episode_number.unwrap_or(-1);
         
-        sqlx::query(
+        db::query(
             r#"
             INSERT INTO watch_history 
             (user_id, tmdb_id, media_type, title, poster_path, season_number, episode_number, episode_title)
             VALUES (?, ?, ?, ?, ?, ?, ?, ?)
             ON CONFLICT(user_id, tmdb_id, media_type, season_number, episode_number)
-            DO UPDATE SET watched_at = CURRENT_TIMESTAMP
+            DO UPDATE SET watched_at = datetime('now'),
+                          episode_title = COALESCE(excluded.episode_title, watch_history.episode_title)
             "#
         )
         .bind(user_id)
@@ -198,14 +864,14 @@ impl AuthManager {
     }
 
     pub async fn get_watch_history(&self, user_id: i64) -> anyhow::Result<Vec<WatchHistoryItem>> {
-        let items: Vec<WatchHistoryItem> = sqlx::query_as(
+        let items: Vec<WatchHistoryItem> = db::query_as(
             r#"
             SELECT id, user_id, tmdb_id, media_type, title, poster_path, 
                    season_number, episode_number, episode_title, progress_seconds, 
                    completed, watched_at
             FROM watch_history
             WHERE user_id = ?
-            ORDER BY watched_at DESC
+            ORDER BY watched_at DESC, id DESC
             LIMIT 50
             "#
         )
@@ -216,6 +882,88 @@ impl AuthManager {
         Ok(items)
     }
 
+    /// One page of the user's history, most recent first, with the total
+    /// number of entries. Movies have no season or episode.
+    pub async fn get_watch_history_page(
+        &self,
+        user_id: i64,
+        limit: i64,
+        offset: i64,
+    ) -> anyhow::Result<(Vec<WatchHistoryItem>, i64)> {
+        let items = db::query_as(
+            r#"
+            SELECT id, user_id, tmdb_id, media_type, title, poster_path,
+                   NULLIF(season_number, -1) AS season_number,
+                   NULLIF(episode_number, -1) AS episode_number,
+                   episode_title, progress_seconds, completed, watched_at
+            FROM watch_history
+            WHERE user_id = ?
+            ORDER BY watched_at DESC, id DESC
+            LIMIT ? OFFSET ?
+            "#
+        )
+        .bind(user_id)
+        .bind(limit)
+        .bind(offset)
+        .fetch_all(&self.db)
+        .await?;
+        let total = db::query_scalar("SELECT COUNT(*) FROM watch_history WHERE user_id = ?")
+            .bind(user_id)
+            .fetch_one(&self.db)
+            .await?;
+        Ok((items, total))
+    }
+
+    /// Titles to pick up again: those whose most recent history entry is
+    /// started but not finished, most recently watched first.
+    pub async fn get_continue_watching(&self, user_id: i64, limit: i64) -> anyhow::Result<Vec<ContinueWatchingItem>> {
+        let items = db::query_as(
+            r#"
+            SELECT h.tmdb_id, h.media_type, h.title, h.poster_path,
+                   NULLIF(h.season_number, -1) AS season_number,
+                   NULLIF(h.episode_number, -1) AS episode_number,
+                   h.episode_title, h.progress_seconds, h.watched_at
+            FROM watch_history h
+            WHERE h.user_id = ? AND h.completed = FALSE AND h.progress_seconds > 0
+                AND h.id = (
+                    SELECT latest.id FROM watch_history latest
+                    WHERE latest.user_id = h.user_id
+                        AND latest.tmdb_id = h.tmdb_id
+                        AND latest.media_type = h.media_type
+                    ORDER BY latest.watched_at DESC, latest.id DESC
+                    LIMIT 1
+                )
+            ORDER BY h.watched_at DESC, h.id DESC
+            LIMIT ?
+            "#
+        )
+        .bind(user_id)
+        .bind(limit)
+        .fetch_all(&self.db)
+        .await?;
+        Ok(items)
+    }
+
+    /// Every episode of a show the user has started or finished, in order.
+    pub async fn get_watched_episodes(&self, user_id: i64, tmdb_id: i64) -> anyhow::Result<Vec<WatchedEpisode>> {
+        let episodes = db::query_as(
+            r#"
+            SELECT season_number, episode_number, progress_seconds, completed
+            FROM watch_history
+            WHERE user_id = ? AND tmdb_id = ? AND media_type = 'tv'
+                AND season_number >= 0 AND episode_number >= 0
+            ORDER BY season_number, episode_number
+            "#
+        )
+        .bind(user_id)
+        .bind(tmdb_id)
+        .fetch_all(&self.db)
+        .await?;
+        Ok(episodes)
+    }
+
+    /// Returns whether this update is what marked the entry completed.
+    #[allow(clippy::too_many_arguments)]
     pub async fn update_watch_progress(
         &self,
         user_id: i64,
@@ -225,55 +973,758 @@ impl AuthManager {
         completed: bool,
         season_number: Option<i64>,
         episode_number: Option<i64>,
-    ) -> anyhow::Result<()> {
+    ) -> anyhow::Result<bool> {
         let season_num = season_number.unwrap_or(-1);
         let episode_num = episode_number.unwrap_or(-1);
         
-        sqlx::query(
+        let was_completed: Option<bool> = db::query_scalar(
             r#"
-            UPDATE watch_history 
-            SET progress_seconds = ?, completed = ?, watched_at = CURRENT_TIMESTAMP
+            SELECT completed FROM watch_history
             WHERE user_id = ? AND tmdb_id = ? AND media_type = ?
             AND season_number = ?
             AND episode_number = ?
             "#
         )
-        .bind(progress_seconds)
-        .bind(completed)
         .bind(user_id)
         .bind(tmdb_id)
         .bind(media_type)
         .bind(season_num)
         .bind(episode_num)
-        .execute(&self.db)
+        .fetch_optional(&self.db)
+        .await?;
+
+        db::query(
+            r#"
+            UPDATE watch_history 
+            SET progress_seconds = ?, completed = ?, watched_at = datetime('now')
+            WHERE user_id = ? AND tmdb_id = ? AND media_type = ?
+            AND season_number = ?
+            AND episode_number = ?
+            "#
+        )
+        .bind(progress_seconds)
+        .bind(completed)
+        .bind(user_id)
+        .bind(tmdb_id)
+        .bind(media_type)
+        .bind(season_num)
+        .bind(episode_num)
+        .execute(&self.db)
+        .await?;
+        
+        Ok(completed && was_completed == Some(false))
+    }
+
+    /// Where to resume a title or episode; `None` when unstarted or finished.
+    pub async fn get_resume_position(
+        &self,
+        user_id: i64,
+        tmdb_id: i64,
+        media_type: &str,
+        season_number: Option<i64>,
+        episode_number: Option<i64>,
+    ) -> anyhow::Result<Option<i64>> {
+        let progress: Option<i64> = db::query_scalar(
+            r#"
+            SELECT progress_seconds FROM watch_history
+            WHERE user_id = ? AND tmdb_id = ? AND media_type = ?
+            AND season_number = ? AND episode_number = ?
+            AND completed = FALSE AND progress_seconds > 0
+            "#
+        )
+        .bind(user_id)
+        .bind(tmdb_id)
+        .bind(media_type)
+        .bind(season_number.unwrap_or(-1))
+        .bind(episode_number.unwrap_or(-1))
+        .fetch_optional(&self.db)
+        .await?;
+
+        Ok(progress)
+    }
+
+    /// A value from the `user_settings` table, parsed as `T`. A value that
+    /// doesn't parse reads as unset, like a missing one.
+    pub async fn get_setting<T: FromStr>(&self, user_id: i64, key: &str) -> anyhow::Result<Option<T>> {
+        let value: Option<String> = db::query_scalar(
+            "SELECT value FROM user_settings WHERE user_id = ? AND key = ?"
+        )
+        .bind(user_id)
+        .bind(key)
+        .fetch_optional(&self.db)
+        .await?;
+
+        Ok(value.and_then(|v| v.parse().ok()))
+    }
+
+    /// Stores `value` as text, so it reads back with [`AuthManager::get_setting`].
+    pub async fn set_setting<T: ToString + ?Sized>(&self, user_id: i64, key: &str, value: &T) -> anyhow::Result<()> {
+        db::query(
+            r#"
+            INSERT INTO user_settings (user_id, key, value)
+            VALUES (?, ?, ?)
+            ON CONFLICT(user_id, key)
+            DO UPDATE SET value = excluded.value, updated_at = datetime('now')
+            "#
+        )
+        .bind(user_id)
+        .bind(key)
+        .bind(value.to_string())
+        .execute(&self.db)
         .await?;
-        
-        Ok(())
-    }
 
-    pub async fn remove_from_watch_history(&self, user_id: i64, history_id: i64) -> anyhow::Result<()> {
-        sqlx::query("DELETE FROM watch_history WHERE id = ? AND user_id = ?")
-            .bind(history_id)
-            .bind(user_id)
-            .execute(&self.db)
-            .await?;
-            
         Ok(())
     }
 
-    pub async fn clear_watch_history(&self, user_id: i64) -> anyhow::Result<()> {
-        sqlx::query("DELETE FROM watch_history WHERE user_id = ?")
+    pub async fn get_preferences(&self, user_id: i64) -> anyhow::Result<UserPreferences> {
+        let image_quality = self.get_setting(user_id, "image_quality").await?.unwrap_or_default();
+        let data_saver = self.get_setting(user_id, "data_saver").await?.unwrap_or(false);
+        let pause_history = self.get_setting(user_id, "pause_history").await?.unwrap_or(false);
+
+        Ok(UserPreferences {
+            image_quality,
+            data_saver,
+            pause_history,
+        })
+    }
+
+    pub async fn save_preferences(&self, user_id: i64, prefs: &UserPreferences) -> anyhow::Result<()> {
+        self.set_setting(user_id, "image_quality", prefs.image_quality.as_str()).await?;
+        self.set_setting(user_id, "data_saver", &prefs.data_saver).await?;
+        self.set_setting(user_id, "pause_history", &prefs.pause_history).await?;
+        Ok(())
+    }
+
+    pub async fn get_player_preferences(&self, user_id: i64) -> anyhow::Result<PlayerPreferences> {
+        let flag = |value: Option<bool>| value.unwrap_or(true);
+        Ok(PlayerPreferences {
+            accent_color: self
+                .get_setting::<String>(user_id, "player_color")
+                .await?
+                .and_then(|v| PlayerPreferences::parse_color(&v)),
+            autoplay: flag(self.get_setting(user_id, "player_autoplay").await?),
+            next_episode: flag(self.get_setting(user_id, "player_next_episode").await?),
+            episode_selector: flag(self.get_setting(user_id, "player_episode_selector").await?),
+            audio_language: self
+                .get_setting::<String>(user_id, "player_audio_language")
+                .await?
+                .and_then(|v| PlayerPreferences::parse_language(&v)),
+            max_quality: self
+                .get_setting::<String>(user_id, "player_max_quality")
+                .await?
+                .and_then(|v| PlayerPreferences::parse_quality(&v)),
+        })
+    }
+
+    pub async fn save_player_preferences(&self, user_id: i64, prefs: &PlayerPreferences) -> anyhow::Result<()> {
+        self.set_setting(user_id, "player_color", prefs.accent_color.as_deref().unwrap_or_default())
+            .await?;
+        self.set_setting(user_id, "player_autoplay", &prefs.autoplay).await?;
+        self.set_setting(user_id, "player_next_episode", &prefs.next_episode).await?;
+        self.set_setting(user_id, "player_episode_selector", &prefs.episode_selector).await?;
+        self.set_setting(user_id, "player_audio_language", prefs.audio_language.as_deref().unwrap_or_default())
+            .await?;
+        self.set_setting(user_id, "player_max_quality", &prefs.max_quality.map(|q| q.to_string()).unwrap_or_default())
+            .await?;
+        Ok(())
+    }
+
+    /// Whether the entry existed and was the user's.
+    pub async fn remove_from_watch_history(&self, user_id: i64, history_id: i64) -> anyhow::Result<bool> {
+        let result = db::query("DELETE FROM watch_history WHERE id = ? AND user_id = ?")
+            .bind(history_id)
+            .bind(user_id)
+            .execute(&self.db)
+            .await?;
+            
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Returns how many entries were removed.
+    pub async fn clear_watch_history(&self, user_id: i64) -> anyhow::Result<u64> {
+        let result = db::query("DELETE FROM watch_history WHERE user_id = ?")
             .bind(user_id)
             .execute(&self.db)
             .await?;
             
+        Ok(result.rows_affected())
+    }
+
+    /// Deletes every user's history older than `days`. Returns how many
+    /// entries were deleted.
+    pub async fn prune_watch_history(&self, days: u32) -> anyhow::Result<u64> {
+        let result = db::query("DELETE FROM watch_history WHERE watched_at < datetime('now', ?)")
+            .bind(format!("-{} days", days))
+            .execute(&self.db)
+            .await?;
+        Ok(result.rows_affected())
+    }
+
+    /// Keeps only each user's `max_rows` most recently watched history entries.
+    pub async fn cap_watch_history(&self, max_rows: u32) -> anyhow::Result<u64> {
+        let result = db::query(
+            r#"
+            DELETE FROM watch_history WHERE id IN (
+                SELECT id FROM (
+                    SELECT id, ROW_NUMBER() OVER (
+                        PARTITION BY user_id ORDER BY watched_at DESC, id DESC
+                    ) AS row_number
+                    FROM watch_history
+                ) AS ranked
+                WHERE row_number > ?
+            )
+            "#
+        )
+        .bind(max_rows)
+        .execute(&self.db)
+        .await?;
+        Ok(result.rows_affected())
+    }
+
+    pub async fn get_watchlist(&self, user_id: i64) -> anyhow::Result<Vec<WatchlistItem>> {
+        let items = db::query_as(
+            r#"
+            SELECT tmdb_id, media_type, title, poster_path, added_at
+            FROM watchlist
+            WHERE user_id = ?
+            ORDER BY added_at DESC, id DESC
+            "#
+        )
+        .bind(user_id)
+        .fetch_all(&self.db)
+        .await?;
+        Ok(items)
+    }
+
+    pub async fn is_on_watchlist(&self, user_id: i64, tmdb_id: i64, media_type: &str) -> anyhow::Result<bool> {
+        let exists = db::query_scalar(
+            "SELECT EXISTS(SELECT 1 FROM watchlist WHERE user_id = ? AND tmdb_id = ? AND media_type = ?)"
+        )
+        .bind(user_id)
+        .bind(tmdb_id)
+        .bind(media_type)
+        .fetch_one(&self.db)
+        .await?;
+        Ok(exists)
+    }
+
+    /// Returns whether the title was added; it may already be on the list.
+    /// New entries are pushed to a connected TMDB account on the next sync.
+    pub async fn add_to_watchlist(
+        &self,
+        user_id: i64,
+        tmdb_id: i64,
+        media_type: &str,
+        title: &str,
+        poster_path: Option<&str>,
+    ) -> anyhow::Result<bool> {
+        let result = db::query(
+            r#"
+            INSERT INTO watchlist (user_id, tmdb_id, media_type, title, poster_path)
+            VALUES (?, ?, ?, ?, ?)
+            ON CONFLICT(user_id, tmdb_id, media_type) DO NOTHING
+            "#
+        )
+        .bind(user_id)
+        .bind(tmdb_id)
+        .bind(media_type)
+        .bind(title)
+        .bind(poster_path)
+        .execute(&self.db)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// `None` if the title wasn't on the watchlist, otherwise whether TMDB
+    /// had it, in which case it needs removing there too.
+    pub async fn remove_from_watchlist(
+        &self,
+        user_id: i64,
+        tmdb_id: i64,
+        media_type: &str,
+    ) -> anyhow::Result<Option<bool>> {
+        let synced = db::query_scalar(
+            "DELETE FROM watchlist WHERE user_id = ? AND tmdb_id = ? AND media_type = ? RETURNING synced"
+        )
+        .bind(user_id)
+        .bind(tmdb_id)
+        .bind(media_type)
+        .fetch_optional(&self.db)
+        .await?;
+        Ok(synced)
+    }
+}
+
+/// What a user may do. Each role's permissions are kept in the
+/// `role_permissions` table; see [`RolePermissions`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    Admin,
+    Manager,
+    #[default]
+    Member,
+    Kid,
+    Guest,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Permission {
+    /// Approve or reject content requests.
+    ApproveRequests,
+    /// Create, edit, and remove user accounts.
+    ManageUsers,
+    /// Change instance-wide settings.
+    ManageSettings,
+    /// Configure live TV sources and guides.
+    ConfigureLiveTv,
+    /// Change one's own display and playback preferences.
+    EditPreferences,
+    /// Connect and sync external accounts such as TMDB.
+    ManageAccount,
+}
+
+impl Role {
+    pub const ALL: [Role; 5] = [Self::Admin, Self::Manager, Self::Member, Self::Kid, Self::Guest];
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "admin" => Some(Self::Admin),
+            "manager" => Some(Self::Manager),
+            "member" => Some(Self::Member),
+            "kid" => Some(Self::Kid),
+            "guest" => Some(Self::Guest),
+            _ => None,
+        }
+    }
+
+    /// Whether this role is above `other`: admin, then manager, member, kid,
+    /// and guest.
+    pub fn outranks(&self, other: Role) -> bool {
+        let rank = |role: Role| Self::ALL.iter().position(|r| *r == role).unwrap_or(Self::ALL.len());
+        rank(*self) < rank(other)
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Admin => "admin",
+            Self::Manager => "manager",
+            Self::Member => "member",
+            Self::Kid => "kid",
+            Self::Guest => "guest",
+        }
+    }
+
+    /// What a role starts with before an admin changes it.
+    pub fn default_permissions(&self) -> &'static [Permission] {
+        match self {
+            Self::Admin => &[
+                Permission::ApproveRequests,
+                Permission::ManageUsers,
+                Permission::ManageSettings,
+                Permission::ConfigureLiveTv,
+                Permission::EditPreferences,
+                Permission::ManageAccount,
+            ],
+            Self::Manager => &[
+                Permission::ApproveRequests,
+                Permission::ConfigureLiveTv,
+                Permission::EditPreferences,
+                Permission::ManageAccount,
+            ],
+            Self::Member => &[Permission::EditPreferences, Permission::ManageAccount],
+            Self::Kid => &[Permission::EditPreferences],
+            Self::Guest => &[],
+        }
+    }
+}
+
+impl Permission {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "approve_requests" => Some(Self::ApproveRequests),
+            "manage_users" => Some(Self::ManageUsers),
+            "manage_settings" => Some(Self::ManageSettings),
+            "configure_live_tv" => Some(Self::ConfigureLiveTv),
+            "edit_preferences" => Some(Self::EditPreferences),
+            "manage_account" => Some(Self::ManageAccount),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::ApproveRequests => "approve_requests",
+            Self::ManageUsers => "manage_users",
+            Self::ManageSettings => "manage_settings",
+            Self::ConfigureLiveTv => "configure_live_tv",
+            Self::EditPreferences => "edit_preferences",
+            Self::ManageAccount => "manage_account",
+        }
+    }
+}
+
+/// Permissions the admin role always keeps, so nobody can lock the instance
+/// out of its own admin area.
+const ADMIN_REQUIRED: &[Permission] = &[Permission::ManageUsers, Permission::ManageSettings];
+
+/// Role grants from the `role_permissions` table, read once and kept in memory.
+#[derive(Debug)]
+pub struct RolePermissions {
+    db: Db,
+    grants: std::sync::RwLock<HashMap<Role, HashSet<Permission>>>,
+}
+
+impl RolePermissions {
+    /// Loads the grants, seeding defaults for roles not in the `roles` table yet.
+    pub async fn load(db: Db) -> anyhow::Result<Self> {
+        for role in Role::ALL {
+            let added = db::query("INSERT INTO roles (name) VALUES (?) ON CONFLICT DO NOTHING")
+                .bind(role.as_str())
+                .execute(&db)
+                .await?;
+            if added.rows_affected() > 0 {
+                for permission in role.default_permissions() {
+                    db::query("INSERT INTO role_permissions (role, permission) VALUES (?, ?) ON CONFLICT DO NOTHING")
+                        .bind(role.as_str())
+                        .bind(permission.as_str())
+                        .execute(&db)
+                        .await?;
+                }
+            }
+        }
+
+        let rows: Vec<(String, String)> = db::query_as("SELECT role, permission FROM role_permissions")
+            .fetch_all(&db)
+            .await?;
+        let mut grants: HashMap<Role, HashSet<Permission>> = HashMap::new();
+        for (role, permission) in rows {
+            match (Role::parse(&role), Permission::parse(&permission)) {
+                (Some(role), Some(permission)) => {
+                    grants.entry(role).or_default().insert(permission);
+                }
+                _ => warn!("Ignoring unknown role permission {}/{}", role, permission),
+            }
+        }
+
+        Ok(Self {
+            db,
+            grants: std::sync::RwLock::new(grants),
+        })
+    }
+
+    pub fn permissions(&self, role: Role) -> HashSet<Permission> {
+        self.grants
+            .read()
+            .unwrap()
+            .get(&role)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    pub fn all(&self) -> Vec<(Role, Vec<Permission>)> {
+        let grants = self.grants.read().unwrap();
+        Role::ALL
+            .into_iter()
+            .map(|role| {
+                let mut permissions: Vec<Permission> =
+                    grants.get(&role).map(|p| p.iter().copied().collect()).unwrap_or_default();
+                permissions.sort_by_key(|p| p.as_str());
+                (role, permissions)
+            })
+            .collect()
+    }
+
+    /// Replaces a role's permissions. The admin role can't give up user or
+    /// settings management.
+    pub async fn set(&self, role: Role, permissions: &[Permission]) -> anyhow::Result<()> {
+        let mut permissions: HashSet<Permission> = permissions.iter().copied().collect();
+        if role == Role::Admin {
+            permissions.extend(ADMIN_REQUIRED);
+        }
+
+        let mut tx = self.db.begin().await?;
+        db::query("DELETE FROM role_permissions WHERE role = ?")
+            .bind(role.as_str())
+            .execute(&mut tx)
+            .await?;
+        for permission in &permissions {
+            db::query("INSERT INTO role_permissions (role, permission) VALUES (?, ?)")
+                .bind(role.as_str())
+                .bind(permission.as_str())
+                .execute(&mut tx)
+                .await?;
+        }
+        tx.commit().await?;
+
+        self.grants.write().unwrap().insert(role, permissions);
         Ok(())
     }
 }
 
-#[derive(Debug, Clone, sqlx::FromRow)]
+/// Route-group guard: rejects the request before any handler runs unless
+/// the user has `permission`. Handlers in the group still extract
+/// [`CurrentUser`] for the session; the checked user is passed along.
+pub async fn require_permission(
+    state: &AppState,
+    permission: Permission,
+    request: Request,
+    next: Next,
+) -> Result<Response, AppError> {
+    let (mut parts, body) = request.into_parts();
+    let user = CurrentUser::from_request_parts(&mut parts, state).await?;
+    user.require(permission)?;
+    parts.extensions.insert(user);
+    Ok(next.run(Request::from_parts(parts, body)).await)
+}
+
+pub async fn require_manage_settings(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Result<Response, AppError> {
+    require_permission(&state, Permission::ManageSettings, request, next).await
+}
+
+pub async fn require_manage_users(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Result<Response, AppError> {
+    require_permission(&state, Permission::ManageUsers, request, next).await
+}
+
+/// Paths reachable without a session when `AUTH_MODE=accounts`.
+const PUBLIC_PATHS: &[&str] = &[
+    "/login",
+    "/register",
+    "/api/auth/login",
+    "/api/auth/register",
+    "/favicon.ico",
+    "/favicon.svg",
+    "/apple-touch-icon.png",
+    "/api/openapi.json",
+    "/api/docs",
+];
+const PUBLIC_PREFIXES: &[&str] = &["/static/", "/img/", "/shared/", "/api/shared/"];
+
+/// App-wide guard for `AUTH_MODE=accounts`: without a session, API calls
+/// get a 401 and pages redirect to `/login`. Does nothing in local mode.
+pub async fn require_sign_in(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    if state.auth.mode() == AuthMode::Local {
+        return next.run(request).await;
+    }
+    let path = unversioned(request.uri().path());
+    if PUBLIC_PATHS.contains(&path.as_ref()) || PUBLIC_PREFIXES.iter().any(|prefix| path.starts_with(prefix)) {
+        return next.run(request).await;
+    }
+    if METRICS_PATHS.contains(&path.as_ref()) && has_metrics_token(&state, request.headers()) {
+        return next.run(request).await;
+    }
+    let is_api = path.starts_with("/api/");
+
+    let (mut parts, body) = request.into_parts();
+    let Ok(MaybeSession(session)) = MaybeSession::from_request_parts(&mut parts, &state).await;
+    if session.is_some() {
+        return next.run(Request::from_parts(parts, body)).await;
+    }
+    if is_api {
+        AppError::Unauthorized.into_response()
+    } else {
+        Redirect::to("/login").into_response()
+    }
+}
+
+/// Counters readable with `METRICS_TOKEN` instead of a session.
+const METRICS_PATHS: &[&str] = &["/metrics", "/api/metrics"];
+
+/// Whether the request sends `Authorization: Bearer <METRICS_TOKEN>`.
+fn has_metrics_token(state: &AppState, headers: &HeaderMap) -> bool {
+    let Some(expected) = state.metrics_token.as_deref() else {
+        return false;
+    };
+    let Some(sent) = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+    else {
+        return false;
+    };
+    // Comparing digests keeps the time taken from revealing how much of
+    // the token matched.
+    Sha256::digest(sent.trim().as_bytes()) == Sha256::digest(expected.as_bytes())
+}
+
+/// A caller allowed to read the metrics: a scraper sending `METRICS_TOKEN`
+/// as a bearer token, or a user whose role may manage settings.
+#[derive(Debug, Clone, Copy)]
+pub struct MetricsReader;
+
+#[async_trait]
+impl FromRequestParts<AppState> for MetricsReader {
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &AppState) -> Result<Self, Self::Rejection> {
+        if has_metrics_token(state, &parts.headers) {
+            return Ok(MetricsReader);
+        }
+        let user = CurrentUser::from_request_parts(parts, state).await?;
+        user.require(Permission::ManageSettings)?;
+        Ok(MetricsReader)
+    }
+}
+
+/// Networks allowed to reach admin pages and the admin API, from
+/// `ADMIN_ALLOWED_IPS`. Allows everyone when unset.
+#[derive(Debug, Clone, Default)]
+pub struct AdminAllowlist {
+    networks: Option<Vec<IpNet>>,
+}
+
+impl AdminAllowlist {
+    /// Accepts CIDRs like `192.168.1.0/24` and single addresses.
+    pub fn parse(list: Option<&[String]>) -> anyhow::Result<Self> {
+        let Some(list) = list else {
+            return Ok(Self::default());
+        };
+        Ok(Self {
+            networks: Some(parse_networks("ADMIN_ALLOWED_IPS", list)?),
+        })
+    }
+
+    pub fn allows(&self, ip: IpAddr) -> bool {
+        self.networks
+            .as_ref()
+            .map_or(true, |networks| contains(networks, ip))
+    }
+}
+
+/// Reverse proxies from `TRUSTED_PROXIES`, whose `X-Forwarded-For` header
+/// says which client they forwarded. Trusts no one when unset.
+#[derive(Debug, Clone, Default)]
+pub struct TrustedProxies {
+    networks: Vec<IpNet>,
+}
+
+impl TrustedProxies {
+    /// Accepts CIDRs like `10.0.0.0/8` and single addresses.
+    pub fn parse(list: Option<&[String]>) -> anyhow::Result<Self> {
+        Ok(Self {
+            networks: parse_networks("TRUSTED_PROXIES", list.unwrap_or_default())?,
+        })
+    }
+
+    /// The client behind `peer`. When `peer` is a trusted proxy, this is the
+    /// last `X-Forwarded-For` entry that isn't itself a trusted proxy;
+    /// earlier entries were written by the client and can't be believed.
+    /// An entry that isn't an address stops the walk at the trusted hop to
+    /// its right. `None` when the header names no such hop.
+    pub fn client_ip(&self, peer: IpAddr, headers: &HeaderMap) -> Option<IpAddr> {
+        if !contains(&self.networks, peer) {
+            return Some(peer);
+        }
+        let entries: Vec<&str> = headers
+            .get_all("x-forwarded-for")
+            .iter()
+            .flat_map(|value| value.to_str().unwrap_or_default().split(','))
+            .collect();
+        if entries.is_empty() {
+            // The proxy's own host connected directly rather than forwarding.
+            return Some(peer);
+        }
+        let mut nearest = None;
+        for entry in entries.into_iter().rev() {
+            let Ok(ip) = entry.trim().parse::<IpAddr>() else {
+                return nearest;
+            };
+            if !contains(&self.networks, ip) {
+                return Some(ip);
+            }
+            nearest = Some(ip);
+        }
+        nearest
+    }
+}
+
+fn parse_networks(setting: &str, list: &[String]) -> anyhow::Result<Vec<IpNet>> {
+    list.iter()
+        .map(|entry| {
+            entry
+                .parse::<IpNet>()
+                .or_else(|_| entry.parse::<IpAddr>().map(IpNet::from))
+                .map_err(|_| anyhow::anyhow!("{}: `{}` is not an IP address or CIDR", setting, entry))
+        })
+        .collect()
+}
+
+fn contains(networks: &[IpNet], ip: IpAddr) -> bool {
+    // IPv4 clients of a dual-stack listener show up as `::ffff:a.b.c.d`.
+    let ip = ip.to_canonical();
+    networks.iter().any(|network| network.contains(&ip))
+}
+
+/// The client's address, looking past `TRUSTED_PROXIES`. Used wherever an
+/// address is checked, throttled, or recorded.
+#[derive(Debug, Clone, Copy)]
+pub struct ClientIp(pub IpAddr);
+
+#[async_trait]
+impl FromRequestParts<AppState> for ClientIp {
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &AppState) -> Result<Self, Self::Rejection> {
+        let peer = parts
+            .extensions
+            .get::<ConnectInfo<SocketAddr>>()
+            .ok_or(AppError::Internal)?
+            .0;
+        state
+            .trusted_proxies
+            .client_ip(peer.ip(), &parts.headers)
+            .map(Self)
+            .ok_or_else(|| AppError::BadRequest("X-Forwarded-For doesn't name the client".to_string()))
+    }
+}
+
+/// An `/api/v1/...` path as the `/api/...` path it mirrors, so path-based
+/// checks need only list the unversioned form.
+fn unversioned(path: &str) -> Cow<'_, str> {
+    match path.strip_prefix(crate::api::V1_PREFIX) {
+        Some(rest) if rest.is_empty() || rest.starts_with('/') => Cow::Owned(format!("/api{}", rest)),
+        _ => Cow::Borrowed(path),
+    }
+}
+
+fn is_admin_path(path: &str) -> bool {
+    let path = unversioned(path);
+    METRICS_PATHS.contains(&path.as_ref())
+        || ["/admin", "/api/admin"]
+            .iter()
+            .any(|prefix| path == *prefix || path.starts_with(&format!("{}/", prefix)))
+}
+
+/// App-wide guard that turns away admin pages, the admin API, and the
+/// metrics for clients outside `ADMIN_ALLOWED_IPS`, before any sign-in or permission check.
+pub async fn restrict_admin_network(
+    State(state): State<AppState>,
+    ClientIp(ip): ClientIp,
+    request: Request,
+    next: Next,
+) -> Result<Response, AppError> {
+    if is_admin_path(request.uri().path()) && !state.admin_allowlist.allows(ip) {
+        warn!("Refused {} from {}: not in ADMIN_ALLOWED_IPS", request.uri().path(), ip);
+        return Err(AppError::Forbidden(
+            "Admin pages aren't available from this network".to_string(),
+        ));
+    }
+    Ok(next.run(request).await)
+}
+
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
 pub struct WatchHistoryItem {
     pub id: i64,
+    #[serde(skip)]
     pub user_id: i64,
     pub tmdb_id: i64,
     pub media_type: String,
@@ -286,3 +1737,205 @@ pub struct WatchHistoryItem {
     pub completed: bool,
     pub watched_at: String,
 }
+
+/// A movie or episode left partway through.
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct ContinueWatchingItem {
+    pub tmdb_id: i64,
+    pub media_type: String,
+    pub title: String,
+    pub poster_path: Option<String>,
+    pub season_number: Option<i64>,
+    pub episode_number: Option<i64>,
+    pub episode_title: Option<String>,
+    pub progress_seconds: i64,
+    pub watched_at: String,
+}
+
+impl ContinueWatchingItem {
+    /// The player, which resumes from the saved position.
+    pub fn player_url(&self) -> String {
+        match (self.season_number, self.episode_number) {
+            (Some(season), Some(episode)) => format!(
+                "/player/{}/{}?season={}&episode={}",
+                self.media_type, self.tmdb_id, season, episode
+            ),
+            _ => format!("/player/{}/{}", self.media_type, self.tmdb_id),
+        }
+    }
+}
+
+/// One episode's entry in the user's history.
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct WatchedEpisode {
+    pub season_number: i64,
+    pub episode_number: i64,
+    pub progress_seconds: i64,
+    pub completed: bool,
+}
+
+/// A title the user saved to watch later.
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct WatchlistItem {
+    pub tmdb_id: i64,
+    pub media_type: String,
+    pub title: String,
+    pub poster_path: Option<String>,
+    pub added_at: String,
+}
+
+/// Poster/backdrop resolution tier, mapped onto TMDB's image size buckets.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ImageQuality {
+    Low,
+    #[default]
+    Standard,
+    High,
+}
+
+impl FromStr for ImageQuality {
+    type Err = ();
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        Self::parse(value).ok_or(())
+    }
+}
+
+impl ImageQuality {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "low" => Some(Self::Low),
+            "standard" => Some(Self::Standard),
+            "high" => Some(Self::High),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Low => "low",
+            Self::Standard => "standard",
+            Self::High => "high",
+        }
+    }
+
+    /// Target pixel width for grid posters; the closest TMDB size is picked
+    /// from the loaded image configuration.
+    pub fn poster_width(&self) -> u32 {
+        match self {
+            Self::Low => 185,
+            Self::Standard => 342,
+            Self::High => 500,
+        }
+    }
+
+    pub fn detail_poster_width(&self) -> u32 {
+        match self {
+            Self::Low => 342,
+            Self::Standard => 500,
+            Self::High => 780,
+        }
+    }
+
+    pub fn poster_size(&self) -> &'static str {
+        image_config().best_poster_size(self.poster_width())
+    }
+
+    pub fn detail_poster_size(&self) -> &'static str {
+        image_config().best_poster_size(self.detail_poster_width())
+    }
+
+    pub fn backdrop_size(&self) -> &'static str {
+        match self {
+            Self::Low => image_config().best_backdrop_size(780),
+            Self::Standard => image_config().best_backdrop_size(1280),
+            Self::High => "original",
+        }
+    }
+
+    pub fn profile_size(&self) -> &'static str {
+        match self {
+            Self::Low => image_config().best_profile_size(45),
+            Self::Standard | Self::High => image_config().best_profile_size(185),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct UserPreferences {
+    pub image_quality: ImageQuality,
+    /// Smaller images, no backdrops, no autoplay — for metered connections.
+    pub data_saver: bool,
+    /// Nothing watched is added to history while set.
+    #[serde(default)]
+    pub pause_history: bool,
+}
+
+impl UserPreferences {
+    pub fn quality(&self) -> ImageQuality {
+        if self.data_saver {
+            ImageQuality::Low
+        } else {
+            self.image_quality
+        }
+    }
+
+    pub fn show_backdrops(&self) -> bool {
+        !self.data_saver
+    }
+
+    pub fn autoplay(&self) -> bool {
+        !self.data_saver
+    }
+}
+
+/// Options passed to embed players. Data saver still turns autoplay off.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlayerPreferences {
+    /// Hex color without `#`; the app accent color when unset.
+    pub accent_color: Option<String>,
+    pub autoplay: bool,
+    pub next_episode: bool,
+    pub episode_selector: bool,
+    /// ISO 639-1 code of the preferred audio language, e.g. `en`.
+    pub audio_language: Option<String>,
+    /// Highest video height to play, e.g. `720`; unlimited when unset.
+    pub max_quality: Option<u32>,
+}
+
+/// Heights offered as a maximum quality.
+pub const QUALITY_CAPS: &[u32] = &[2160, 1080, 720, 480, 360];
+
+impl Default for PlayerPreferences {
+    fn default() -> Self {
+        Self {
+            accent_color: None,
+            autoplay: true,
+            next_episode: true,
+            episode_selector: true,
+            audio_language: None,
+            max_quality: None,
+        }
+    }
+}
+
+impl PlayerPreferences {
+    /// Accepts `#e50914` or `e50914`; returns the lowercase digits.
+    pub fn parse_color(input: &str) -> Option<String> {
+        let hex = input.trim().trim_start_matches('#');
+        (hex.len() == 6 && hex.chars().all(|c| c.is_ascii_hexdigit())).then(|| hex.to_lowercase())
+    }
+
+    /// Accepts one of [`QUALITY_CAPS`], with or without a trailing `p`.
+    pub fn parse_quality(input: &str) -> Option<u32> {
+        let height = input.trim().trim_end_matches('p').parse().ok()?;
+        QUALITY_CAPS.contains(&height).then_some(height)
+    }
+
+    /// Accepts a two-letter ISO 639-1 code in any case; returns it lowercase.
+    pub fn parse_language(input: &str) -> Option<String> {
+        let code = input.trim();
+        (code.len() == 2 && code.chars().all(|c| c.is_ascii_alphabetic())).then(|| code.to_lowercase())
+    }
+}
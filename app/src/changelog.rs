@@ -0,0 +1,92 @@
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+
+/// Version of this build, as recorded in `user_settings.last_seen_version`.
+pub const APP_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Per-user setting holding the newest release whose notes were dismissed.
+pub const LAST_SEEN_VERSION_KEY: &str = "last_seen_version";
+
+/// Capabilities clients can check for instead of comparing version numbers.
+const FEATURES: &[&str] = &[
+    "discover_v1",
+    "network_browse",
+    "certifications",
+    "image_proxy",
+    "playback_heartbeat",
+    "metrics",
+    "roles",
+];
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Release {
+    pub version: String,
+    pub date: String,
+    pub highlights: Vec<String>,
+}
+
+/// Newest release first.
+static RELEASES: Lazy<Vec<Release>> = Lazy::new(|| {
+    let mut releases: Vec<Release> =
+        serde_json::from_str(include_str!("../changelog.json")).expect("changelog.json is valid");
+    releases.sort_by_key(|r| std::cmp::Reverse(version_key(&r.version)));
+    releases
+});
+
+#[derive(Debug, Serialize)]
+pub struct VersionInfo {
+    pub version: &'static str,
+    pub build: BuildInfo,
+    pub features: &'static [&'static str],
+}
+
+#[derive(Debug, Serialize)]
+pub struct BuildInfo {
+    /// Set by release builds through `RUSTSTREAM_GIT_SHA`.
+    pub commit: Option<&'static str>,
+    pub profile: &'static str,
+    pub os: &'static str,
+    pub arch: &'static str,
+}
+
+pub fn version_info() -> VersionInfo {
+    VersionInfo {
+        version: APP_VERSION,
+        build: BuildInfo {
+            commit: option_env!("RUSTSTREAM_GIT_SHA"),
+            profile: if cfg!(debug_assertions) { "debug" } else { "release" },
+            os: std::env::consts::OS,
+            arch: std::env::consts::ARCH,
+        },
+        features: FEATURES,
+    }
+}
+
+/// Releases a user has not seen yet, newest first. Users with no record yet
+/// only see the current release rather than the whole history.
+pub fn unseen_releases(last_seen: Option<&str>) -> Vec<&'static Release> {
+    let current = version_key(APP_VERSION);
+    match last_seen {
+        Some(seen) => {
+            let seen = version_key(seen);
+            RELEASES
+                .iter()
+                .filter(|r| {
+                    let version = version_key(&r.version);
+                    version > seen && version <= current
+                })
+                .collect()
+        }
+        None => RELEASES
+            .iter()
+            .filter(|r| version_key(&r.version) == current)
+            .collect(),
+    }
+}
+
+fn version_key(version: &str) -> Vec<u64> {
+    version
+        .split('.')
+        .map(|part| part.parse().unwrap_or(0))
+        .collect()
+}
@@ -0,0 +1,83 @@
+use std::time::Duration;
+
+use tracing::{error, info};
+
+use crate::AppState;
+
+pub const DEFAULT_CLEANUP_INTERVAL_MINUTES: u64 = 60;
+
+/// Longest `CLEANUP_INTERVAL_MINUTES` accepted: a week.
+pub const MAX_CLEANUP_INTERVAL_MINUTES: u64 = 7 * 24 * 60;
+
+/// What the cleanup job deletes, and how often, from `CLEANUP_INTERVAL_MINUTES`,
+/// `HISTORY_RETENTION_DAYS`, and `HISTORY_MAX_ROWS`.
+#[derive(Debug, Clone)]
+pub struct CleanupPolicy {
+    pub interval: Duration,
+    /// Watch history older than this many days is deleted; kept forever when unset.
+    pub history_retention_days: Option<u32>,
+    /// Each user's oldest history entries beyond this many are deleted.
+    pub history_max_rows: Option<u32>,
+}
+
+/// Periodically deletes expired sessions and cached streams, finished
+/// webhook deliveries, old notifications and provider counters, and
+/// history past the retention period. Nothing else removes them except on
+/// lookup.
+pub fn spawn_cleanup_job(state: AppState, policy: CleanupPolicy) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(policy.interval);
+        loop {
+            interval.tick().await;
+            run_cleanup(&state, &policy).await;
+        }
+    });
+}
+
+async fn run_cleanup(state: &AppState, policy: &CleanupPolicy) {
+    match state.sessions.remove_expired().await {
+        Ok(0) => {}
+        Ok(removed) => info!("Removed {} expired sessions", removed),
+        Err(e) => error!("Session cleanup failed: {}", e),
+    }
+
+    match state.providers.cache().remove_expired().await {
+        Ok(0) => {}
+        Ok(removed) => info!("Removed {} expired cached streams", removed),
+        Err(e) => error!("Stream cache cleanup failed: {}", e),
+    }
+
+    match state.webhooks.prune_deliveries(crate::webhooks::DELIVERY_RETENTION_DAYS).await {
+        Ok(0) => {}
+        Ok(removed) => info!("Removed {} old webhook deliveries", removed),
+        Err(e) => error!("Webhook delivery cleanup failed: {}", e),
+    }
+
+    match state.notifications.prune(crate::notifications::RETENTION_DAYS).await {
+        Ok(0) => {}
+        Ok(removed) => info!("Removed {} old notifications", removed),
+        Err(e) => error!("Notification cleanup failed: {}", e),
+    }
+
+    match state.providers.stats().prune().await {
+        Ok(0) => {}
+        Ok(removed) => info!("Removed {} old daily provider counters", removed),
+        Err(e) => error!("Provider stats cleanup failed: {}", e),
+    }
+
+    if let Some(days) = policy.history_retention_days {
+        match state.auth.prune_watch_history(days).await {
+            Ok(0) => {}
+            Ok(removed) => info!("Removed {} history entries older than {} days", removed, days),
+            Err(e) => error!("Watch history cleanup failed: {}", e),
+        }
+    }
+
+    if let Some(max_rows) = policy.history_max_rows {
+        match state.auth.cap_watch_history(max_rows).await {
+            Ok(0) => {}
+            Ok(removed) => info!("Removed {} history entries beyond {} per user", removed, max_rows),
+            Err(e) => error!("Watch history cleanup failed: {}", e),
+        }
+    }
+}
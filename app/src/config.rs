@@ -1,11 +1,66 @@
-use config::{Config as ConfigBuilder, File};
-use serde::Deserialize;
+use std::str::FromStr;
 
-#[derive(Debug, Deserialize, Clone)]
+use config::{Config as ConfigBuilder, ConfigError, File};
+use serde::{Deserialize, Serialize};
+
+use crate::auth::AuthMode;
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct Config {
     pub database_url: String,
+    /// Size of the SQLite connection pool.
+    pub database_max_connections: u32,
     pub tmdb_api_key: String,
+    /// TMDB language tag such as `de-DE`; TMDB defaults to English when unset.
+    pub tmdb_language: Option<String>,
+    /// Country code for release dates and watch providers, e.g. `GB`.
+    pub tmdb_region: String,
     pub port: u16,
+    /// How many ports after `port` to try when it is already taken.
+    pub port_retries: u16,
+    /// Where the port actually bound is written for the desktop wrapper and CLI.
+    pub port_file: String,
+    /// Maximum simultaneous sessions per user; the oldest are evicted beyond this.
+    pub max_sessions_per_user: Option<u32>,
+    /// Where proxied TMDB artwork is stored on disk.
+    pub image_cache_dir: String,
+    /// Enables IMDb and Rotten Tomatoes scores on movie pages.
+    pub omdb_api_key: Option<String>,
+    /// Stream providers to enable by name; every provider when unset.
+    pub stream_providers: Option<Vec<String>>,
+    /// TOML file of URL-template providers added to the built-in ones.
+    pub providers_file: String,
+    /// Directory of the user's own media files; enables the local provider.
+    pub media_dir: Option<String>,
+    /// Key for signing sessions and stream links; generated and kept in the
+    /// database when unset.
+    pub session_secret: Option<String>,
+    /// The key before the last rotation, still accepted for existing sessions.
+    pub session_secret_previous: Option<String>,
+    /// `local` acts as the local user without signing in; `accounts`
+    /// requires everyone to sign in.
+    pub auth_mode: AuthMode,
+    /// Makes the local user an admin. Off by default, since in local mode
+    /// every request without a session acts as the local user.
+    pub local_user_admin: bool,
+    /// Networks, as CIDRs or single addresses, allowed to reach admin pages
+    /// and the admin API; anywhere when unset.
+    pub admin_allowed_ips: Option<Vec<String>>,
+    /// Reverse proxies, as CIDRs or single addresses, whose
+    /// `X-Forwarded-For` is believed; the peer address is used otherwise.
+    pub trusted_proxies: Option<Vec<String>>,
+    /// How often expired sessions, cached streams, and old history are deleted.
+    pub cleanup_interval_minutes: u64,
+    /// Deletes watch history older than this; history is kept forever when unset.
+    pub history_retention_days: Option<u32>,
+    /// Keeps at most this many history entries per user; unlimited when unset.
+    pub history_max_rows: Option<u32>,
+    /// Where browsers reach the app, such as `https://stream.example.com`,
+    /// for links sent elsewhere like the TMDB sign-in callback.
+    /// `http://127.0.0.1:<port>` when unset.
+    pub public_url: Option<String>,
+    /// Bearer token that lets a scraper read `/metrics` without an account.
+    pub metrics_token: Option<String>,
 }
 
 impl Config {
@@ -19,7 +74,14 @@ impl Config {
         let config = ConfigBuilder::builder()
             .add_source(File::with_name("config").required(false))
             .set_default("database_url", "sqlite://./streaming.db")?
+            .set_default("database_max_connections", crate::db::DEFAULT_MAX_CONNECTIONS)?
             .set_default("port", 3000u16)?
+            .set_default("port_retries", 0u16)?
+            .set_default("port_file", crate::listen::DEFAULT_PORT_FILE)?
+            .set_default("tmdb_region", crate::tmdb::DEFAULT_REGION)?
+            .set_default("image_cache_dir", crate::images::DEFAULT_IMAGE_CACHE_DIR)?
+            .set_default("providers_file", crate::custom_providers::DEFAULT_PROVIDERS_FILE)?
+            .set_default("cleanup_interval_minutes", crate::cleanup::DEFAULT_CLEANUP_INTERVAL_MINUTES)?
             .build()?;
 
         let database_url = std::env::var("DATABASE_URL").unwrap_or_else(|_| {
@@ -28,14 +90,174 @@ impl Config {
                 .unwrap_or_else(|_| "sqlite://./streaming.db".to_string())
         });
 
+        let auth_mode = match std::env::var("AUTH_MODE")
+            .ok()
+            .or_else(|| config.get_string("auth_mode").ok())
+            .filter(|m| !m.trim().is_empty())
+        {
+            Some(mode) => AuthMode::parse(&mode).ok_or_else(|| {
+                anyhow::anyhow!("AUTH_MODE must be `local` or `accounts`, not `{}`", mode)
+            })?,
+            None => AuthMode::default(),
+        };
+
         Ok(Config {
             database_url,
+            database_max_connections: number_setting(&config, "DATABASE_MAX_CONNECTIONS", "database_max_connections")?
+                .filter(|v| *v > 0)
+                .unwrap_or(crate::db::DEFAULT_MAX_CONNECTIONS),
             tmdb_api_key: std::env::var("TMDB_API_KEY")
                 .map_err(|_| anyhow::anyhow!("TMDB_API_KEY environment variable not set"))?,
-            port: std::env::var("PORT")
+            tmdb_language: std::env::var("TMDB_LANGUAGE")
+                .ok()
+                .or_else(|| config.get_string("tmdb_language").ok())
+                .filter(|l| !l.trim().is_empty()),
+            tmdb_region: std::env::var("TMDB_REGION")
+                .ok()
+                .filter(|r| !r.trim().is_empty())
+                .unwrap_or_else(|| {
+                    config
+                        .get_string("tmdb_region")
+                        .unwrap_or_else(|_| crate::tmdb::DEFAULT_REGION.to_string())
+                }),
+            port: number_setting(&config, "PORT", "port")?.unwrap_or(3000),
+            port_retries: number_setting(&config, "PORT_RETRIES", "port_retries")?.unwrap_or(0),
+            port_file: std::env::var("PORT_FILE")
+                .ok()
+                .filter(|f| !f.trim().is_empty())
+                .unwrap_or_else(|| {
+                    config
+                        .get_string("port_file")
+                        .unwrap_or_else(|_| crate::listen::DEFAULT_PORT_FILE.to_string())
+                }),
+            max_sessions_per_user: number_setting(&config, "MAX_SESSIONS_PER_USER", "max_sessions_per_user")?
+                .filter(|v| *v > 0),
+            image_cache_dir: std::env::var("IMAGE_CACHE_DIR")
+                .ok()
+                .filter(|d| !d.trim().is_empty())
+                .unwrap_or_else(|| {
+                    config
+                        .get_string("image_cache_dir")
+                        .unwrap_or_else(|_| crate::images::DEFAULT_IMAGE_CACHE_DIR.to_string())
+                }),
+            omdb_api_key: std::env::var("OMDB_API_KEY")
+                .ok()
+                .or_else(|| config.get_string("omdb_api_key").ok())
+                .filter(|k| !k.trim().is_empty()),
+            stream_providers: std::env::var("STREAM_PROVIDERS")
+                .ok()
+                .or_else(|| config.get_string("stream_providers").ok())
+                .map(|list| {
+                    list.split(',')
+                        .map(|name| name.trim().to_lowercase())
+                        .filter(|name| !name.is_empty())
+                        .collect()
+                }),
+            providers_file: std::env::var("PROVIDERS_FILE")
+                .ok()
+                .filter(|f| !f.trim().is_empty())
+                .unwrap_or_else(|| {
+                    config
+                        .get_string("providers_file")
+                        .unwrap_or_else(|_| crate::custom_providers::DEFAULT_PROVIDERS_FILE.to_string())
+                }),
+            media_dir: std::env::var("MEDIA_DIR")
+                .ok()
+                .or_else(|| config.get_string("media_dir").ok())
+                .filter(|d| !d.trim().is_empty()),
+            session_secret: std::env::var("SESSION_SECRET")
+                .ok()
+                .or_else(|| config.get_string("session_secret").ok())
+                .filter(|s| !s.trim().is_empty()),
+            session_secret_previous: std::env::var("SESSION_SECRET_PREVIOUS")
                 .ok()
-                .and_then(|p| p.parse().ok())
-                .unwrap_or_else(|| config.get_int("port").unwrap_or(3000) as u16),
+                .or_else(|| config.get_string("session_secret_previous").ok())
+                .filter(|s| !s.trim().is_empty()),
+            auth_mode,
+            local_user_admin: flag_setting(&config, "LOCAL_USER_ADMIN", "local_user_admin")?.unwrap_or(false),
+            admin_allowed_ips: std::env::var("ADMIN_ALLOWED_IPS")
+                .ok()
+                .or_else(|| config.get_string("admin_allowed_ips").ok())
+                .map(|list| {
+                    list.split(',')
+                        .map(|network| network.trim().to_string())
+                        .filter(|network| !network.is_empty())
+                        .collect::<Vec<_>>()
+                })
+                .filter(|list| !list.is_empty()),
+            trusted_proxies: std::env::var("TRUSTED_PROXIES")
+                .ok()
+                .or_else(|| config.get_string("trusted_proxies").ok())
+                .map(|list| {
+                    list.split(',')
+                        .map(|network| network.trim().to_string())
+                        .filter(|network| !network.is_empty())
+                        .collect::<Vec<_>>()
+                })
+                .filter(|list| !list.is_empty()),
+            cleanup_interval_minutes: match number_setting(&config, "CLEANUP_INTERVAL_MINUTES", "cleanup_interval_minutes")? {
+                None => crate::cleanup::DEFAULT_CLEANUP_INTERVAL_MINUTES,
+                Some(minutes) if (1..=crate::cleanup::MAX_CLEANUP_INTERVAL_MINUTES).contains(&minutes) => minutes,
+                Some(minutes) => anyhow::bail!(
+                    "CLEANUP_INTERVAL_MINUTES must be between 1 and {}, not `{}`",
+                    crate::cleanup::MAX_CLEANUP_INTERVAL_MINUTES,
+                    minutes
+                ),
+            },
+            history_retention_days: number_setting(&config, "HISTORY_RETENTION_DAYS", "history_retention_days")?
+                .filter(|v| *v > 0),
+            history_max_rows: number_setting(&config, "HISTORY_MAX_ROWS", "history_max_rows")?
+                .filter(|v| *v > 0),
+            public_url: std::env::var("PUBLIC_URL")
+                .ok()
+                .or_else(|| config.get_string("public_url").ok())
+                .map(|url| url.trim().trim_end_matches('/').to_string())
+                .filter(|url| !url.is_empty()),
+            metrics_token: std::env::var("METRICS_TOKEN")
+                .ok()
+                .or_else(|| config.get_string("metrics_token").ok())
+                .map(|token| token.trim().to_string())
+                .filter(|token| !token.is_empty()),
         })
     }
 }
+
+/// A whole-number setting from the `env` variable or else the `key` in the
+/// config file. Values that aren't numbers, are negative, or are too large
+/// for `T` are an error rather than being wrapped or ignored.
+fn number_setting<T>(config: &ConfigBuilder, env: &str, key: &str) -> anyhow::Result<Option<T>>
+where
+    T: FromStr + TryFrom<i64>,
+{
+    if let Some(value) = std::env::var(env).ok().filter(|v| !v.trim().is_empty()) {
+        return value
+            .trim()
+            .parse()
+            .map(Some)
+            .map_err(|_| anyhow::anyhow!("{} must be a whole number in range, not `{}`", env, value));
+    }
+    match config.get_int(key) {
+        Ok(value) => T::try_from(value)
+            .map(Some)
+            .map_err(|_| anyhow::anyhow!("{} must be a whole number in range, not `{}`", key, value)),
+        Err(ConfigError::NotFound(_)) => Ok(None),
+        Err(e) => Err(anyhow::anyhow!("{}: {}", key, e)),
+    }
+}
+
+/// A yes/no setting from the `env` variable or else the `key` in the config
+/// file. Anything other than `true`/`false`, `1`/`0`, or `yes`/`no` is an error.
+fn flag_setting(config: &ConfigBuilder, env: &str, key: &str) -> anyhow::Result<Option<bool>> {
+    if let Some(value) = std::env::var(env).ok().filter(|v| !v.trim().is_empty()) {
+        return match value.trim().to_ascii_lowercase().as_str() {
+            "true" | "1" | "yes" => Ok(Some(true)),
+            "false" | "0" | "no" => Ok(Some(false)),
+            _ => Err(anyhow::anyhow!("{} must be `true` or `false`, not `{}`", env, value)),
+        };
+    }
+    match config.get_bool(key) {
+        Ok(value) => Ok(Some(value)),
+        Err(ConfigError::NotFound(_)) => Ok(None),
+        Err(e) => Err(anyhow::anyhow!("{}: {}", key, e)),
+    }
+}
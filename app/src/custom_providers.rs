@@ -0,0 +1,204 @@
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use config::{Config as ConfigBuilder, File};
+use serde::Deserialize;
+use tracing::{info, warn};
+
+use crate::hls::HlsProxy;
+use crate::providers::{
+    EmbedOptions, HostProbe, ProviderError, StreamKind, StreamProvider, StreamSource, DEFAULT_STREAM_CACHE_TTL,
+};
+
+pub const DEFAULT_PROVIDERS_FILE: &str = "./providers.toml";
+
+#[derive(Debug, Default, Deserialize)]
+struct ProvidersFile {
+    #[serde(default)]
+    providers: Vec<ProviderDefinition>,
+}
+
+/// One `[[providers]]` entry. URL templates may use `{type}` (`movie` or
+/// `tv`), `{tmdb_id}`, `{season}`, `{episode}`, `{absolute_episode}` (the
+/// episode counted across seasons for anime, otherwise `{episode}`),
+/// `{language}` (the viewer's preferred audio language, or empty), and
+/// `{quality}` (the viewer's maximum height such as `720`, or empty).
+#[derive(Debug, Clone, Deserialize)]
+pub struct ProviderDefinition {
+    pub name: String,
+    /// Shown on the source buttons; defaults to `name`.
+    #[serde(default)]
+    pub label: Option<String>,
+    pub url: String,
+    /// Overrides `url` for movies.
+    #[serde(default)]
+    pub movie_url: Option<String>,
+    /// Overrides `url` for episodes.
+    #[serde(default)]
+    pub tv_url: Option<String>,
+    #[serde(default)]
+    pub priority: i32,
+    /// Fetch the rendered page and play the `.m3u8` it references through
+    /// the stream proxy instead of embedding the page.
+    #[serde(default)]
+    pub hls: bool,
+}
+
+/// An embed provider defined by URL templates rather than code.
+#[derive(Debug)]
+pub struct TemplateProvider {
+    definition: ProviderDefinition,
+    probe: HostProbe,
+    hls: Arc<HlsProxy>,
+}
+
+impl TemplateProvider {
+    pub fn new(mut definition: ProviderDefinition, hls: Arc<HlsProxy>) -> anyhow::Result<Self> {
+        definition.name = definition.name.trim().to_lowercase();
+        if definition.name.is_empty() {
+            return Err(anyhow::anyhow!("provider name is empty"));
+        }
+        let origin = reqwest::Url::parse(&definition.url)
+            .map_err(|e| anyhow::anyhow!("invalid url for {}: {}", definition.name, e))?
+            .origin()
+            .ascii_serialization();
+        Ok(Self {
+            probe: HostProbe::new(&origin)?,
+            definition,
+            hls,
+        })
+    }
+
+    /// Fills in the template. For movies, the `{season}`/`{episode}` path
+    /// segments a shared template leaves empty are dropped.
+    fn render(&self, media_type: &str, tmdb_id: i64, episode: Option<(i64, i64)>, options: &EmbedOptions) -> String {
+        let template = match media_type {
+            "movie" => self.definition.movie_url.as_ref(),
+            _ => self.definition.tv_url.as_ref(),
+        }
+        .unwrap_or(&self.definition.url);
+
+        let (season, episode, absolute_episode) = match episode {
+            Some((s, e)) => (
+                s.to_string(),
+                e.to_string(),
+                options.absolute_episode.unwrap_or(e).to_string(),
+            ),
+            None => (String::new(), String::new(), String::new()),
+        };
+        let url = template
+            .replace("{type}", media_type)
+            .replace("{tmdb_id}", &tmdb_id.to_string())
+            .replace("{season}", &season)
+            .replace("{absolute_episode}", &absolute_episode)
+            .replace("{episode}", &episode)
+            .replace("{language}", options.language.as_deref().unwrap_or_default())
+            .replace(
+                "{quality}",
+                &options.max_quality.map(|q| q.to_string()).unwrap_or_default(),
+            );
+
+        match url.split_once('?') {
+            Some((path, query)) => format!("{}?{}", path.trim_end_matches('/'), query),
+            None => url.trim_end_matches('/').to_string(),
+        }
+    }
+
+    /// Turns a rendered URL into a stream, resolving its manifest for HLS
+    /// providers and checking that the page exists for embeds.
+    async fn stream(&self, url: String, viewer: Option<i64>) -> anyhow::Result<StreamSource> {
+        let (id, kind) = if self.definition.hls {
+            let manifest = self
+                .hls
+                .resolve(&url, viewer)
+                .await
+                .map_err(|e| match e.downcast_ref::<reqwest::Error>().and_then(|e| e.status()) {
+                    Some(reqwest::StatusCode::NOT_FOUND | reqwest::StatusCode::GONE) => ProviderError::NotFound.into(),
+                    _ => e,
+                })?
+                .ok_or(ProviderError::NotFound)?;
+            (manifest, StreamKind::Hls)
+        } else {
+            self.probe.validate(&url).await?;
+            (url, StreamKind::Embed)
+        };
+
+        Ok(StreamSource {
+            id,
+            name: self
+                .definition
+                .label
+                .clone()
+                .unwrap_or_else(|| self.definition.name.clone()),
+            quality: Some("Auto".to_string()),
+            language: None,
+            server: self.definition.name.clone(),
+            kind,
+        })
+    }
+}
+
+#[async_trait]
+impl StreamProvider for TemplateProvider {
+    fn name(&self) -> &str {
+        &self.definition.name
+    }
+
+    fn priority(&self) -> i32 {
+        self.definition.priority
+    }
+
+    /// Proxied manifests depend on in-memory host allowances and upstream
+    /// tokens that expire, so they are never cached.
+    fn cache_ttl(&self) -> Option<Duration> {
+        if self.definition.hls {
+            None
+        } else {
+            Some(DEFAULT_STREAM_CACHE_TTL)
+        }
+    }
+
+    async fn is_reachable(&self, force: bool) -> bool {
+        self.probe.check(force).await
+    }
+
+    async fn get_movie_streams(&self, tmdb_id: i64, options: &EmbedOptions) -> anyhow::Result<Vec<StreamSource>> {
+        Ok(vec![self.stream(self.render("movie", tmdb_id, None, options), options.viewer).await?])
+    }
+
+    async fn get_tv_streams(
+        &self,
+        tmdb_id: i64,
+        season: i64,
+        episode: i64,
+        options: &EmbedOptions,
+    ) -> anyhow::Result<Vec<StreamSource>> {
+        Ok(vec![self.stream(self.render("tv", tmdb_id, Some((season, episode)), options), options.viewer).await?])
+    }
+}
+
+/// Loads the providers defined in `path`. A missing file means none;
+/// invalid entries are skipped with a warning.
+pub fn load(path: &Path, hls: Arc<HlsProxy>) -> anyhow::Result<Vec<Arc<dyn StreamProvider>>> {
+    if !path.exists() {
+        return Ok(vec![]);
+    }
+
+    let file: ProvidersFile = ConfigBuilder::builder()
+        .add_source(File::from(path))
+        .build()?
+        .try_deserialize()
+        .map_err(|e| anyhow::anyhow!("Could not read {}: {}", path.display(), e))?;
+
+    let mut providers: Vec<Arc<dyn StreamProvider>> = Vec::new();
+    for definition in file.providers {
+        match TemplateProvider::new(definition, hls.clone()) {
+            Ok(provider) => providers.push(Arc::new(provider)),
+            Err(e) => warn!("Skipping provider in {}: {}", path.display(), e),
+        }
+    }
+    info!("Loaded {} custom providers from {}", providers.len(), path.display());
+    Ok(providers)
+}
@@ -1,180 +1,554 @@
-use sqlx::{sqlite::SqliteConnectOptions, sqlite::SqlitePoolOptions, Pool, Sqlite};
+use std::marker::PhantomData;
 use std::str::FromStr;
+use std::time::Duration;
+
+use sqlx::{
+    migrate::Migrator,
+    sqlite::{
+        SqliteArguments, SqliteConnectOptions, SqliteConnection, SqliteJournalMode, SqlitePoolOptions, SqliteRow,
+        SqliteSynchronous,
+    },
+    Arguments, Pool, Sqlite,
+};
+#[cfg(feature = "postgres")]
+use sqlx::{
+    postgres::{PgArguments, PgConnection, PgPoolOptions, PgRow},
+    Postgres,
+};
 use tracing::info;
 
-pub async fn init_db(database_url: &str) -> anyhow::Result<Pool<Sqlite>> {
+pub const DEFAULT_MAX_CONNECTIONS: u32 = 5;
+/// How long a connection waits for another's write lock before failing
+/// with "database is locked".
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Schema changes in `app/migrations`, applied in order and recorded in
+/// `_sqlx_migrations`. Add a new numbered file for each change; never edit
+/// one that has shipped.
+static MIGRATOR: Migrator = sqlx::migrate!("./migrations");
+
+/// The same changes for PostgreSQL, in `app/migrations/postgres`. Every
+/// SQLite migration has a counterpart here with the same number.
+#[cfg(feature = "postgres")]
+static POSTGRES_MIGRATOR: Migrator = sqlx::migrate!("./migrations/postgres");
+
+/// The connection pool for whichever database `DATABASE_URL` names.
+///
+/// Queries go through [`query`], [`query_as`], and [`query_scalar`], which
+/// take SQL written with `?` placeholders. SQL shared by both backends
+/// sticks to what both accept; the Postgres schema defines `datetime`,
+/// `date`, and `instr` to behave like SQLite's, and keeps timestamps as
+/// `YYYY-MM-DD HH:MM:SS` text as SQLite does.
+#[derive(Debug, Clone)]
+pub enum Db {
+    Sqlite(Pool<Sqlite>),
+    #[cfg(feature = "postgres")]
+    Postgres(Pool<Postgres>),
+}
+
+impl Db {
+    pub async fn begin(&self) -> Result<Transaction<'static>, sqlx::Error> {
+        match self {
+            Db::Sqlite(pool) => Ok(Transaction::Sqlite(pool.begin().await?)),
+            #[cfg(feature = "postgres")]
+            Db::Postgres(pool) => Ok(Transaction::Postgres(pool.begin().await?)),
+        }
+    }
+}
+
+/// Rolled back when dropped without [`Transaction::commit`].
+pub enum Transaction<'c> {
+    Sqlite(sqlx::Transaction<'c, Sqlite>),
+    #[cfg(feature = "postgres")]
+    Postgres(sqlx::Transaction<'c, Postgres>),
+}
+
+impl Transaction<'_> {
+    pub async fn commit(self) -> Result<(), sqlx::Error> {
+        match self {
+            Transaction::Sqlite(tx) => tx.commit().await,
+            #[cfg(feature = "postgres")]
+            Transaction::Postgres(tx) => tx.commit().await,
+        }
+    }
+}
+
+/// What a query runs on: a pool, or the connection of an open transaction.
+pub enum Conn<'e> {
+    SqlitePool(&'e Pool<Sqlite>),
+    SqliteTx(&'e mut SqliteConnection),
+    #[cfg(feature = "postgres")]
+    PostgresPool(&'e Pool<Postgres>),
+    #[cfg(feature = "postgres")]
+    PostgresTx(&'e mut PgConnection),
+}
+
+/// `&Db` or `&mut Transaction`.
+pub trait Executor<'e> {
+    fn into_conn(self) -> Conn<'e>;
+}
+
+impl<'e> Executor<'e> for &'e Db {
+    fn into_conn(self) -> Conn<'e> {
+        match self {
+            Db::Sqlite(pool) => Conn::SqlitePool(pool),
+            #[cfg(feature = "postgres")]
+            Db::Postgres(pool) => Conn::PostgresPool(pool),
+        }
+    }
+}
+
+impl<'e> Executor<'e> for &'e mut Transaction<'_> {
+    fn into_conn(self) -> Conn<'e> {
+        match self {
+            Transaction::Sqlite(tx) => Conn::SqliteTx(tx),
+            #[cfg(feature = "postgres")]
+            Transaction::Postgres(tx) => Conn::PostgresTx(tx),
+        }
+    }
+}
+
+/// A bound parameter. Each variant carries its type, so a NULL binds as
+/// the column's type on Postgres.
+#[derive(Debug)]
+pub enum Value {
+    Int(Option<i64>),
+    Float(Option<f64>),
+    Text(Option<String>),
+    Bool(Option<bool>),
+}
+
+/// A Rust value that can be bound to a `?` placeholder.
+pub trait Bind {
+    fn into_value(self) -> Value;
+    /// NULL of this type, for binding `None`.
+    fn null() -> Value;
+}
+
+macro_rules! bind_int {
+    ($($ty:ty),*) => {$(
+        impl Bind for $ty {
+            fn into_value(self) -> Value {
+                Value::Int(Some(i64::from(self)))
+            }
+            fn null() -> Value {
+                Value::Int(None)
+            }
+        }
+    )*};
+}
+
+bind_int!(i64, i32, u32, u16, u8);
+
+impl Bind for f64 {
+    fn into_value(self) -> Value {
+        Value::Float(Some(self))
+    }
+    fn null() -> Value {
+        Value::Float(None)
+    }
+}
+
+impl Bind for bool {
+    fn into_value(self) -> Value {
+        Value::Bool(Some(self))
+    }
+    fn null() -> Value {
+        Value::Bool(None)
+    }
+}
+
+impl Bind for String {
+    fn into_value(self) -> Value {
+        Value::Text(Some(self))
+    }
+    fn null() -> Value {
+        Value::Text(None)
+    }
+}
+
+impl Bind for &str {
+    fn into_value(self) -> Value {
+        Value::Text(Some(self.to_string()))
+    }
+    fn null() -> Value {
+        Value::Text(None)
+    }
+}
+
+impl<T: Bind + Clone> Bind for &T {
+    fn into_value(self) -> Value {
+        self.clone().into_value()
+    }
+    fn null() -> Value {
+        T::null()
+    }
+}
+
+impl<T: Bind> Bind for Option<T> {
+    fn into_value(self) -> Value {
+        self.map_or_else(T::null, T::into_value)
+    }
+    fn null() -> Value {
+        T::null()
+    }
+}
+
+/// A row type [`query_as`] can decode from either backend; anything
+/// deriving `sqlx::FromRow`, and tuples.
+#[cfg(not(feature = "postgres"))]
+pub trait FromRow: for<'r> sqlx::FromRow<'r, SqliteRow> + Send + Unpin {}
+#[cfg(not(feature = "postgres"))]
+impl<T> FromRow for T where T: for<'r> sqlx::FromRow<'r, SqliteRow> + Send + Unpin {}
+
+/// A row type [`query_as`] can decode from either backend; anything
+/// deriving `sqlx::FromRow`, and tuples.
+#[cfg(feature = "postgres")]
+pub trait FromRow: for<'r> sqlx::FromRow<'r, SqliteRow> + for<'r> sqlx::FromRow<'r, PgRow> + Send + Unpin {}
+#[cfg(feature = "postgres")]
+impl<T> FromRow for T where T: for<'r> sqlx::FromRow<'r, SqliteRow> + for<'r> sqlx::FromRow<'r, PgRow> + Send + Unpin {}
+
+pub struct QueryResult {
+    rows_affected: u64,
+}
+
+impl QueryResult {
+    pub fn rows_affected(&self) -> u64 {
+        self.rows_affected
+    }
+}
+
+/// Runs `sqlx::$build(sql, arguments).$method(conn)` on whichever
+/// connection the executor names, then `$then` on the result.
+macro_rules! dispatch {
+    ($executor:expr, $sql:expr, $args:expr, $build:path, $method:ident, $then:expr) => {
+        match $executor.into_conn() {
+            Conn::SqlitePool(pool) => $build($sql, sqlite_arguments($args)?).$method(pool).await.map($then),
+            Conn::SqliteTx(conn) => $build($sql, sqlite_arguments($args)?).$method(conn).await.map($then),
+            #[cfg(feature = "postgres")]
+            Conn::PostgresPool(pool) => {
+                $build(&postgres_sql($sql), postgres_arguments($args)?).$method(pool).await.map($then)
+            }
+            #[cfg(feature = "postgres")]
+            Conn::PostgresTx(conn) => {
+                $build(&postgres_sql($sql), postgres_arguments($args)?).$method(conn).await.map($then)
+            }
+        }
+    };
+}
+
+pub fn query(sql: &str) -> Query<'_> {
+    Query { sql, args: Vec::new() }
+}
+
+pub fn query_as<O: FromRow>(sql: &str) -> QueryAs<'_, O> {
+    QueryAs { query: query(sql), row: PhantomData }
+}
+
+pub fn query_scalar<O>(sql: &str) -> QueryScalar<'_, O>
+where
+    (O,): FromRow,
+    O: Send + Unpin,
+{
+    QueryScalar { query: query(sql), value: PhantomData }
+}
+
+pub struct Query<'q> {
+    sql: &'q str,
+    args: Vec<Value>,
+}
+
+impl<'q> Query<'q> {
+    pub fn bind(mut self, value: impl Bind) -> Self {
+        self.args.push(value.into_value());
+        self
+    }
+
+    pub async fn execute<'e>(self, executor: impl Executor<'e>) -> Result<QueryResult, sqlx::Error> {
+        dispatch!(executor, self.sql, self.args, sqlx::query_with, execute, |result| QueryResult {
+            rows_affected: result.rows_affected(),
+        })
+    }
+}
+
+pub struct QueryAs<'q, O> {
+    query: Query<'q>,
+    row: PhantomData<O>,
+}
+
+impl<'q, O: FromRow> QueryAs<'q, O> {
+    pub fn bind(mut self, value: impl Bind) -> Self {
+        self.query = self.query.bind(value);
+        self
+    }
+
+    pub async fn fetch_one<'e>(self, executor: impl Executor<'e>) -> Result<O, sqlx::Error> {
+        let Query { sql, args } = self.query;
+        dispatch!(executor, sql, args, sqlx::query_as_with, fetch_one, |row| row)
+    }
+
+    pub async fn fetch_optional<'e>(self, executor: impl Executor<'e>) -> Result<Option<O>, sqlx::Error> {
+        let Query { sql, args } = self.query;
+        dispatch!(executor, sql, args, sqlx::query_as_with, fetch_optional, |row| row)
+    }
+
+    pub async fn fetch_all<'e>(self, executor: impl Executor<'e>) -> Result<Vec<O>, sqlx::Error> {
+        let Query { sql, args } = self.query;
+        dispatch!(executor, sql, args, sqlx::query_as_with, fetch_all, |rows| rows)
+    }
+}
+
+pub struct QueryScalar<'q, O> {
+    query: Query<'q>,
+    value: PhantomData<O>,
+}
+
+impl<'q, O> QueryScalar<'q, O>
+where
+    (O,): FromRow,
+    O: Send + Unpin,
+{
+    pub fn bind(mut self, value: impl Bind) -> Self {
+        self.query = self.query.bind(value);
+        self
+    }
+
+    pub async fn fetch_one<'e>(self, executor: impl Executor<'e>) -> Result<O, sqlx::Error> {
+        let Query { sql, args } = self.query;
+        dispatch!(executor, sql, args, sqlx::query_scalar_with, fetch_one, |value| value)
+    }
+
+    pub async fn fetch_optional<'e>(self, executor: impl Executor<'e>) -> Result<Option<O>, sqlx::Error> {
+        let Query { sql, args } = self.query;
+        dispatch!(executor, sql, args, sqlx::query_scalar_with, fetch_optional, |value| value)
+    }
+
+    pub async fn fetch_all<'e>(self, executor: impl Executor<'e>) -> Result<Vec<O>, sqlx::Error> {
+        let Query { sql, args } = self.query;
+        dispatch!(executor, sql, args, sqlx::query_scalar_with, fetch_all, |values| values)
+    }
+}
+
+fn sqlite_arguments(values: Vec<Value>) -> Result<SqliteArguments<'static>, sqlx::Error> {
+    let mut arguments = SqliteArguments::default();
+    for value in values {
+        match value {
+            Value::Int(value) => arguments.add(value),
+            Value::Float(value) => arguments.add(value),
+            Value::Text(value) => arguments.add(value),
+            Value::Bool(value) => arguments.add(value),
+        }
+        .map_err(sqlx::Error::Encode)?;
+    }
+    Ok(arguments)
+}
+
+#[cfg(feature = "postgres")]
+fn postgres_arguments(values: Vec<Value>) -> Result<PgArguments, sqlx::Error> {
+    let mut arguments = PgArguments::default();
+    for value in values {
+        match value {
+            Value::Int(value) => arguments.add(value),
+            Value::Float(value) => arguments.add(value),
+            Value::Text(value) => arguments.add(value),
+            Value::Bool(value) => arguments.add(value),
+        }
+        .map_err(sqlx::Error::Encode)?;
+    }
+    Ok(arguments)
+}
+
+/// Numbers `?` placeholders as Postgres's `$1`, `$2`, and so on, leaving
+/// quoted strings and identifiers alone.
+#[cfg(feature = "postgres")]
+fn postgres_sql(sql: &str) -> String {
+    let mut numbered = String::with_capacity(sql.len() + 16);
+    let mut quote = None;
+    let mut placeholders = 0;
+    for c in sql.chars() {
+        match quote {
+            Some(open) if c == open => quote = None,
+            Some(_) => {}
+            None if c == '\'' || c == '"' => quote = Some(c),
+            None if c == '?' => {
+                placeholders += 1;
+                numbered.push_str(&format!("${}", placeholders));
+                continue;
+            }
+            None => {}
+        }
+        numbered.push(c);
+    }
+    numbered
+}
+
+pub async fn init_db(database_url: &str, max_connections: u32) -> anyhow::Result<Db> {
+    match database_url.split_once("://").map(|(scheme, _)| scheme) {
+        Some("postgres" | "postgresql") => init_postgres(database_url, max_connections).await,
+        // Anything else with a scheme would otherwise be taken for a file name.
+        Some(scheme) if scheme != "sqlite" => anyhow::bail!(
+            "DATABASE_URL uses `{}`, but only SQLite and PostgreSQL are supported; use a `sqlite://` or `postgres://` URL, or a file path",
+            scheme
+        ),
+        _ => Ok(Db::Sqlite(init_sqlite(database_url, max_connections).await?)),
+    }
+}
+
+#[cfg(feature = "postgres")]
+async fn init_postgres(database_url: &str, max_connections: u32) -> anyhow::Result<Db> {
+    let pool = PgPoolOptions::new()
+        .max_connections(max_connections)
+        .connect(database_url)
+        .await?;
+
+    check_schema_version(&Db::Postgres(pool.clone()), &POSTGRES_MIGRATOR).await?;
+    info!("Running database migrations...");
+    POSTGRES_MIGRATOR.run(&pool).await?;
+    info!("Database migrations completed");
+
+    Ok(Db::Postgres(pool))
+}
+
+#[cfg(not(feature = "postgres"))]
+async fn init_postgres(_database_url: &str, _max_connections: u32) -> anyhow::Result<Db> {
+    anyhow::bail!(
+        "DATABASE_URL names a PostgreSQL database, but this build leaves PostgreSQL out; rebuild with `--features postgres` or use a `sqlite://` URL"
+    )
+}
+
+pub fn is_postgres_url(database_url: &str) -> bool {
+    database_url.starts_with("postgres://") || database_url.starts_with("postgresql://")
+}
+
+/// Opens and migrates a SQLite database, for callers that work on the
+/// file itself.
+pub async fn init_sqlite(database_url: &str, max_connections: u32) -> anyhow::Result<Pool<Sqlite>> {
     let options = if database_url.starts_with("sqlite:") {
         SqliteConnectOptions::from_str(database_url)?
     } else {
         SqliteConnectOptions::new().filename(database_url)
     }
-    .create_if_missing(true);
+    .create_if_missing(true)
+    // WAL lets page loads read while a background job writes; NORMAL
+    // sync is durable enough in WAL mode and much cheaper per commit.
+    .journal_mode(SqliteJournalMode::Wal)
+    .synchronous(SqliteSynchronous::Normal)
+    .busy_timeout(BUSY_TIMEOUT)
+    .foreign_keys(true);
 
     let pool = SqlitePoolOptions::new()
-        .max_connections(5)
+        .max_connections(max_connections)
         .connect_with(options)
         .await?;
 
+    // Databases from before versioned migrations get the column changes
+    // those builds made in place; the baseline migration adds missing tables.
+    if table_exists(&pool, "users").await? && !table_exists(&pool, "_sqlx_migrations").await? {
+        info!("Upgrading a database from before versioned migrations...");
+        upgrade_unversioned(&pool).await?;
+    }
+
+    check_schema_version(&Db::Sqlite(pool.clone()), &MIGRATOR).await?;
     info!("Running database migrations...");
-    
-    sqlx::query(
-        r#"
-        CREATE TABLE IF NOT EXISTS movies (
-            id INTEGER PRIMARY KEY AUTOINCREMENT,
-            tmdb_id INTEGER UNIQUE NOT NULL,
-            title TEXT NOT NULL,
-            overview TEXT,
-            poster_path TEXT,
-            backdrop_path TEXT,
-            release_date TEXT,
-            vote_average REAL DEFAULT 0,
-            vote_count INTEGER DEFAULT 0,
-            genres TEXT DEFAULT '[]',
-            runtime INTEGER,
-            created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
-            updated_at DATETIME DEFAULT CURRENT_TIMESTAMP
-        )
-        "#
-    )
-    .execute(&pool)
-    .await?;
+    MIGRATOR.run(&pool).await?;
+    info!("Database migrations completed");
 
-    sqlx::query(
-        r#"
-        CREATE TABLE IF NOT EXISTS tv_shows (
-            id INTEGER PRIMARY KEY AUTOINCREMENT,
-            tmdb_id INTEGER UNIQUE NOT NULL,
-            name TEXT NOT NULL,
-            overview TEXT,
-            poster_path TEXT,
-            backdrop_path TEXT,
-            first_air_date TEXT,
-            vote_average REAL DEFAULT 0,
-            vote_count INTEGER DEFAULT 0,
-            genres TEXT DEFAULT '[]',
-            number_of_seasons INTEGER,
-            number_of_episodes INTEGER,
-            created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
-            updated_at DATETIME DEFAULT CURRENT_TIMESTAMP
-        )
-        "#
-    )
-    .execute(&pool)
-    .await?;
+    Ok(pool)
+}
 
-    sqlx::query(
-        r#"
-        CREATE TABLE IF NOT EXISTS seasons (
-            id INTEGER PRIMARY KEY AUTOINCREMENT,
-            tmdb_id INTEGER UNIQUE NOT NULL,
-            show_id INTEGER NOT NULL,
-            season_number INTEGER NOT NULL,
-            name TEXT,
-            overview TEXT,
-            poster_path TEXT,
-            air_date TEXT,
-            episode_count INTEGER DEFAULT 0,
-            created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
-            FOREIGN KEY (show_id) REFERENCES tv_shows(id) ON DELETE CASCADE
-        )
-        "#
-    )
-    .execute(&pool)
-    .await?;
+/// Column changes made in place by builds from before versioned
+/// migrations, for databases they created.
+async fn upgrade_unversioned(pool: &Pool<Sqlite>) -> anyhow::Result<()> {
+    // The first `streaming_cache` layout was never written to and had no
+    // provider column, so it is replaced rather than migrated.
+    if column_exists(pool, "streaming_cache", "vidking_id").await? {
+        sqlx::query("DROP TABLE streaming_cache").execute(pool).await?;
+        info!("Replaced unused streaming_cache table");
+    }
 
-    sqlx::query(
-        r#"
-        CREATE TABLE IF NOT EXISTS episodes (
-            id INTEGER PRIMARY KEY AUTOINCREMENT,
-            tmdb_id INTEGER UNIQUE NOT NULL,
-            season_id INTEGER NOT NULL,
-            episode_number INTEGER NOT NULL,
-            name TEXT,
-            overview TEXT,
-            still_path TEXT,
-            air_date TEXT,
-            runtime INTEGER,
-            created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
-            FOREIGN KEY (season_id) REFERENCES seasons(id) ON DELETE CASCADE
-        )
-        "#
-    )
-    .execute(&pool)
-    .await?;
+    // `role` replaced the boolean `is_admin`; carry existing admins over.
+    if add_column_if_missing(pool, "users", "role", "TEXT NOT NULL DEFAULT 'member'").await? {
+        sqlx::query("UPDATE users SET role = 'admin' WHERE is_admin = 1")
+            .execute(pool)
+            .await?;
+    }
+    if add_column_if_missing(pool, "sessions", "role", "TEXT NOT NULL DEFAULT 'member'").await? {
+        sqlx::query("UPDATE sessions SET role = 'admin' WHERE is_admin = 1")
+            .execute(pool)
+            .await?;
+    }
+    add_column_if_missing(pool, "sessions", "last_seen_at", "INTEGER").await?;
+    add_column_if_missing(pool, "sessions", "user_agent", "TEXT").await?;
+    add_column_if_missing(pool, "sessions", "ip", "TEXT").await?;
 
-    sqlx::query(
-        r#"
-        CREATE TABLE IF NOT EXISTS streaming_cache (
-            id INTEGER PRIMARY KEY AUTOINCREMENT,
-            tmdb_id INTEGER NOT NULL,
-            media_type TEXT NOT NULL,
-            season_number INTEGER,
-            episode_number INTEGER,
-            vidking_id TEXT,
-            stream_url TEXT,
-            quality TEXT,
-            expires_at DATETIME,
-            created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
-            UNIQUE(tmdb_id, media_type, season_number, episode_number)
-        )
-        "#
-    )
-    .execute(&pool)
-    .await?;
+    Ok(())
+}
 
-    sqlx::query(
-        r#"
-        CREATE TABLE IF NOT EXISTS users (
-            id INTEGER PRIMARY KEY AUTOINCREMENT,
-            username TEXT UNIQUE NOT NULL,
-            password_hash TEXT NOT NULL,
-            is_admin BOOLEAN DEFAULT 0,
-            created_at DATETIME DEFAULT CURRENT_TIMESTAMP
-        )
-        "#
-    )
-    .execute(&pool)
-    .await?;
+/// Refuses a database migrated by a newer build, whose schema this one
+/// doesn't know.
+async fn check_schema_version(db: &Db, migrator: &Migrator) -> anyhow::Result<()> {
+    let tracked = match db {
+        Db::Sqlite(pool) => table_exists(pool, "_sqlx_migrations").await?,
+        #[cfg(feature = "postgres")]
+        Db::Postgres(_) => {
+            query_scalar(
+                "SELECT EXISTS(SELECT 1 FROM information_schema.tables WHERE table_schema = current_schema() AND table_name = '_sqlx_migrations')",
+            )
+            .fetch_one(db)
+            .await?
+        }
+    };
+    if !tracked {
+        return Ok(());
+    }
+    let applied: Option<i64> = query_scalar("SELECT MAX(version) FROM _sqlx_migrations WHERE success = TRUE")
+        .fetch_one(db)
+        .await?;
+    let known = migrator.iter().map(|migration| migration.version).max().unwrap_or(0);
+    if let Some(applied) = applied.filter(|applied| *applied > known) {
+        anyhow::bail!(
+            "The database schema is at version {}, but this build only knows up to {}; upgrade RustStream or restore a backup",
+            applied,
+            known
+        );
+    }
+    Ok(())
+}
 
-    sqlx::query(
-        r#"
-        CREATE TABLE IF NOT EXISTS sessions (
-            id INTEGER PRIMARY KEY AUTOINCREMENT,
-            session_id TEXT UNIQUE NOT NULL,
-            user_id INTEGER NOT NULL,
-            username TEXT NOT NULL,
-            is_admin BOOLEAN DEFAULT 0,
-            expires_at INTEGER NOT NULL,
-            created_at DATETIME DEFAULT CURRENT_TIMESTAMP
-        )
-        "#
-    )
-    .execute(&pool)
-    .await?;
+/// Adds a column to a table created by an older build. Returns whether the
+/// column was added.
+async fn add_column_if_missing(
+    pool: &Pool<Sqlite>,
+    table: &str,
+    column: &str,
+    definition: &str,
+) -> anyhow::Result<bool> {
+    if column_exists(pool, table, column).await? {
+        return Ok(false);
+    }
 
-    sqlx::query(
-        r#"
-        CREATE TABLE IF NOT EXISTS watch_history (
-            id INTEGER PRIMARY KEY AUTOINCREMENT,
-            user_id INTEGER NOT NULL,
-            tmdb_id INTEGER NOT NULL,
-            media_type TEXT NOT NULL,
-            title TEXT NOT NULL,
-            poster_path TEXT,
-            season_number INTEGER DEFAULT -1,
-            episode_number INTEGER DEFAULT -1,
-            episode_title TEXT,
-            progress_seconds INTEGER DEFAULT 0,
-            completed BOOLEAN DEFAULT 0,
-            watched_at DATETIME DEFAULT CURRENT_TIMESTAMP,
-            UNIQUE(user_id, tmdb_id, media_type, season_number, episode_number)
-        )
-        "#
-    )
-    .execute(&pool)
-    .await?;
+    sqlx::query(&format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, definition))
+        .execute(pool)
+        .await?;
+    info!("Added {}.{} column", table, column);
+    Ok(true)
+}
 
-    info!("Database migrations completed");
-    
-    Ok(pool)
+async fn table_exists(pool: &Pool<Sqlite>, table: &str) -> anyhow::Result<bool> {
+    let exists = sqlx::query_scalar("SELECT EXISTS(SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = ?)")
+        .bind(table)
+        .fetch_one(pool)
+        .await?;
+    Ok(exists)
+}
+
+async fn column_exists(pool: &Pool<Sqlite>, table: &str, column: &str) -> anyhow::Result<bool> {
+    let exists = sqlx::query_scalar(&format!(
+        "SELECT EXISTS(SELECT 1 FROM pragma_table_info('{}') WHERE name = ?)",
+        table
+    ))
+    .bind(column)
+    .fetch_one(pool)
+    .await?;
+    Ok(exists)
 }
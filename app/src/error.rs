@@ -1,11 +1,15 @@
 use axum::{
-    http::StatusCode,
+    http::{header, StatusCode},
     response::{IntoResponse, Response},
-    Json,
 };
 use serde_json::json;
 use thiserror::Error;
 
+use crate::tmdb::TmdbError;
+
+/// Media type of every API error body.
+pub const PROBLEM_CONTENT_TYPE: &str = "application/problem+json";
+
 #[derive(Error, Debug)]
 pub enum AppError {
     #[error("Database error: {0}")]
@@ -17,8 +21,8 @@ pub enum AppError {
     #[error("TMDB API error: {0}")]
     Tmdb(String),
 
-    #[error("Vidking API error: {0}")]
-    Vidking(String),
+    #[error("TMDB is unavailable: {0}")]
+    TmdbUnavailable(String),
 
     #[error("Not found")]
     NotFound,
@@ -26,6 +30,20 @@ pub enum AppError {
     #[error("Bad request: {0}")]
     BadRequest(String),
 
+    /// A bad request with its own error code, for mistakes clients are
+    /// likely to handle specifically.
+    #[error("Bad request: {message}")]
+    Invalid { code: &'static str, message: String },
+
+    #[error("Unauthorized")]
+    Unauthorized,
+
+    #[error("Forbidden: {0}")]
+    Forbidden(String),
+
+    #[error("Too many requests: {0}")]
+    TooManyRequests(String),
+
     #[error("Internal server error")]
     Internal,
 
@@ -33,9 +51,34 @@ pub enum AppError {
     InternalWithMessage(String),
 }
 
+impl AppError {
+    pub fn invalid(code: &'static str, message: impl Into<String>) -> Self {
+        AppError::Invalid {
+            code,
+            message: message.into(),
+        }
+    }
+
+    /// The stable `code` in the error body. Variants without their own use
+    /// the code of their status.
+    fn code(&self, status: StatusCode) -> &'static str {
+        match self {
+            AppError::Tmdb(_) => "tmdb_error",
+            AppError::TmdbUnavailable(_) => "tmdb_unavailable",
+            AppError::Invalid { code, .. } => code,
+            _ => error_code(status),
+        }
+    }
+}
+
 impl From<anyhow::Error> for AppError {
     fn from(err: anyhow::Error) -> Self {
-        AppError::InternalWithMessage(err.to_string())
+        match err.downcast_ref::<TmdbError>() {
+            Some(TmdbError::NotFound) => AppError::NotFound,
+            Some(TmdbError::Unavailable(message)) => AppError::TmdbUnavailable(message.clone()),
+            Some(TmdbError::Api(message)) => AppError::Tmdb(message.clone()),
+            None => AppError::InternalWithMessage(err.to_string()),
+        }
     }
 }
 
@@ -48,9 +91,16 @@ impl IntoResponse for AppError {
             ),
             AppError::Http(_) => (StatusCode::INTERNAL_SERVER_ERROR, "HTTP error".to_string()),
             AppError::Tmdb(msg) => (StatusCode::BAD_GATEWAY, msg.clone()),
-            AppError::Vidking(msg) => (StatusCode::BAD_GATEWAY, msg.clone()),
+            AppError::TmdbUnavailable(msg) => (
+                StatusCode::SERVICE_UNAVAILABLE,
+                format!("TMDB is unavailable: {}", msg),
+            ),
             AppError::NotFound => (StatusCode::NOT_FOUND, "Not found".to_string()),
             AppError::BadRequest(msg) => (StatusCode::BAD_REQUEST, msg.clone()),
+            AppError::Invalid { message, .. } => (StatusCode::BAD_REQUEST, message.clone()),
+            AppError::Unauthorized => (StatusCode::UNAUTHORIZED, "Sign in required".to_string()),
+            AppError::Forbidden(msg) => (StatusCode::FORBIDDEN, msg.clone()),
+            AppError::TooManyRequests(msg) => (StatusCode::TOO_MANY_REQUESTS, msg.clone()),
             AppError::Internal => (
                 StatusCode::INTERNAL_SERVER_ERROR,
                 "Internal server error".to_string(),
@@ -61,10 +111,51 @@ impl IntoResponse for AppError {
             ),
         };
 
-        let body = Json(json!({
-            "error": error_message,
-        }));
+        problem_response(status, self.code(status), &error_message)
+    }
+}
+
+/// An RFC 7807 problem details response.
+pub fn problem_response(status: StatusCode, code: &str, message: &str) -> Response {
+    (
+        status,
+        [(header::CONTENT_TYPE, PROBLEM_CONTENT_TYPE)],
+        problem_body(status, code, message).to_string(),
+    )
+        .into_response()
+}
+
+/// The body every API error is answered with: RFC 7807 problem details,
+/// plus a stable `code` for scripts and the message again as `error`, which
+/// clients read before problem details were adopted. More fields may be
+/// added; these stay.
+pub fn problem_body(status: StatusCode, code: &str, message: &str) -> serde_json::Value {
+    json!({
+        "type": "about:blank",
+        "title": status.canonical_reason().unwrap_or("Error"),
+        "status": status.as_u16(),
+        "detail": message,
+        "code": code,
+        "error": message,
+    })
+}
 
-        (status, body).into_response()
+/// The `code` for an error that has nothing more specific than its status.
+pub fn error_code(status: StatusCode) -> &'static str {
+    match status {
+        StatusCode::BAD_REQUEST => "bad_request",
+        StatusCode::UNAUTHORIZED => "unauthorized",
+        StatusCode::FORBIDDEN => "forbidden",
+        StatusCode::NOT_FOUND => "not_found",
+        StatusCode::METHOD_NOT_ALLOWED => "method_not_allowed",
+        StatusCode::CONFLICT => "conflict",
+        StatusCode::PAYLOAD_TOO_LARGE => "payload_too_large",
+        StatusCode::UNSUPPORTED_MEDIA_TYPE => "unsupported_media_type",
+        StatusCode::UNPROCESSABLE_ENTITY => "unprocessable_entity",
+        StatusCode::TOO_MANY_REQUESTS => "too_many_requests",
+        StatusCode::BAD_GATEWAY => "bad_gateway",
+        StatusCode::SERVICE_UNAVAILABLE => "service_unavailable",
+        status if status.is_server_error() => "internal_error",
+        _ => "error",
     }
 }
@@ -0,0 +1,578 @@
+//! Imports watch history exported from Trakt (its JSON history) or Netflix
+//! (the viewing activity CSV), matching titles to TMDB by search.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use tracing::{error, info};
+
+use crate::db::{self, Db};
+use crate::notifications::NotificationKind;
+use crate::tmdb::{SearchResult, TmdbClient};
+use crate::AppState;
+
+/// Larger files are refused; every row may need a TMDB search.
+pub const MAX_IMPORT_ROWS: usize = 10_000;
+
+/// Request bodies up to this size are accepted by `POST /api/import`.
+pub const MAX_IMPORT_BYTES: usize = 16 * 1024 * 1024;
+
+/// Larger imports run in the background rather than within the request,
+/// one at a time per user; `GET /api/import/status` reports on them.
+pub const MAX_INLINE_ROWS: usize = 100;
+
+/// Netflix writes dates in the account's locale: US style by default, or
+/// day first with slashes in much of the world.
+const NETFLIX_DATE_FORMATS: &[&str] = &["%m/%d/%y", "%m/%d/%Y", "%Y-%m-%d", "%d.%m.%y", "%d.%m.%Y"];
+const NETFLIX_DAY_FIRST_FORMATS: &[&str] = &["%d/%m/%y", "%d/%m/%Y", "%Y-%m-%d", "%d.%m.%y", "%d.%m.%Y"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ImportFormat {
+    Trakt,
+    Netflix,
+}
+
+impl ImportFormat {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ImportFormat::Trakt => "trakt",
+            ImportFormat::Netflix => "netflix",
+        }
+    }
+
+    /// Trakt exports are JSON and Netflix's are CSV, so the first character
+    /// tells them apart.
+    pub fn detect(body: &str) -> Self {
+        match body.trim_start_matches('\u{feff}').trim_start().chars().next() {
+            Some('[' | '{') => ImportFormat::Trakt,
+            _ => ImportFormat::Netflix,
+        }
+    }
+}
+
+/// One row of an import file, before it is matched to TMDB.
+#[derive(Debug, Default)]
+pub struct ImportEntry {
+    pub title: String,
+    /// `movie` or `tv` when the file says; Netflix only says for episodes.
+    pub media_type: Option<&'static str>,
+    pub tmdb_id: Option<i64>,
+    pub year: Option<i64>,
+    pub season_number: Option<i64>,
+    pub episode_number: Option<i64>,
+    pub episode_title: Option<String>,
+    /// `YYYY-MM-DD HH:MM:SS` in UTC, like `CURRENT_TIMESTAMP`.
+    pub watched_at: Option<String>,
+    /// Why the row can't be imported, found while reading it.
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ImportReport {
+    pub format: &'static str,
+    pub dry_run: bool,
+    pub total: usize,
+    /// Rows added to or updated in history; with `dry_run`, the rows that
+    /// would have been.
+    pub imported: usize,
+    pub failed: usize,
+    pub rows: Vec<ImportedRow>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ImportedRow {
+    /// 1-based position in the file, not counting a CSV header.
+    pub row: usize,
+    pub title: String,
+    pub tmdb_id: Option<i64>,
+    pub media_type: Option<String>,
+    pub season_number: Option<i64>,
+    pub episode_number: Option<i64>,
+    pub watched_at: Option<String>,
+    pub error: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct TraktEntry {
+    #[serde(default, alias = "last_watched_at")]
+    watched_at: Option<String>,
+    #[serde(default)]
+    movie: Option<TraktTitle>,
+    #[serde(default)]
+    show: Option<TraktTitle>,
+    #[serde(default)]
+    episode: Option<TraktEpisode>,
+}
+
+#[derive(Deserialize)]
+struct TraktTitle {
+    #[serde(default)]
+    title: String,
+    #[serde(default)]
+    year: Option<i64>,
+    #[serde(default)]
+    ids: TraktIds,
+}
+
+#[derive(Deserialize)]
+struct TraktEpisode {
+    season: i64,
+    number: i64,
+    #[serde(default)]
+    title: Option<String>,
+}
+
+#[derive(Default, Deserialize)]
+struct TraktIds {
+    #[serde(default)]
+    tmdb: Option<i64>,
+}
+
+/// Reads the rows of an import file. Fails only when the file as a whole
+/// can't be read; problems with single rows are kept on the entry.
+/// `day_first` says how Netflix's slashed dates read; left out, they are
+/// taken as day first if any of them can only be read that way.
+pub fn parse(format: ImportFormat, body: &str, day_first: Option<bool>) -> Result<Vec<ImportEntry>, String> {
+    let entries = match format {
+        ImportFormat::Trakt => parse_trakt(body)?,
+        ImportFormat::Netflix => parse_netflix(body, day_first)?,
+    };
+    if entries.len() > MAX_IMPORT_ROWS {
+        return Err(format!("Imports are limited to {} rows", MAX_IMPORT_ROWS));
+    }
+    Ok(entries)
+}
+
+fn parse_trakt(body: &str) -> Result<Vec<ImportEntry>, String> {
+    let rows: Vec<serde_json::Value> = serde_json::from_str(body.trim_start_matches('\u{feff}'))
+        .map_err(|e| format!("Not a Trakt history export: {}", e))?;
+    Ok(rows.into_iter().map(trakt_entry).collect())
+}
+
+fn trakt_entry(row: serde_json::Value) -> ImportEntry {
+    let entry: TraktEntry = match serde_json::from_value(row) {
+        Ok(entry) => entry,
+        Err(e) => {
+            return ImportEntry {
+                error: Some(format!("Unreadable row: {}", e)),
+                ..Default::default()
+            }
+        }
+    };
+    let watched_at = entry.watched_at.as_deref().map(|value| {
+        DateTime::parse_from_rfc3339(value).map(|at| at.with_timezone(&Utc).format("%Y-%m-%d %H:%M:%S").to_string())
+    });
+    let (watched_at, error) = match watched_at {
+        Some(Ok(at)) => (Some(at), None),
+        Some(Err(_)) => (None, Some("Unreadable watched_at".to_string())),
+        None => (None, None),
+    };
+
+    match (entry.movie, entry.show, entry.episode) {
+        (Some(movie), _, _) => ImportEntry {
+            title: movie.title,
+            media_type: Some("movie"),
+            tmdb_id: movie.ids.tmdb,
+            year: movie.year,
+            watched_at,
+            error,
+            ..Default::default()
+        },
+        (None, Some(show), Some(episode)) => ImportEntry {
+            title: show.title,
+            media_type: Some("tv"),
+            tmdb_id: show.ids.tmdb,
+            year: show.year,
+            season_number: Some(episode.season),
+            episode_number: Some(episode.number),
+            episode_title: episode.title,
+            watched_at,
+            error,
+        },
+        (None, show, _) => ImportEntry {
+            title: show.map(|show| show.title).unwrap_or_default(),
+            error: Some("Only movies and episodes can be imported".to_string()),
+            ..Default::default()
+        },
+    }
+}
+
+fn parse_netflix(body: &str, day_first: Option<bool>) -> Result<Vec<ImportEntry>, String> {
+    let mut records = crate::csv::parse(body).into_iter();
+    let header = records.next().unwrap_or_default();
+    let column = |name: &str| header.iter().position(|h| h.trim().eq_ignore_ascii_case(name));
+    let (Some(title_column), Some(date_column)) = (column("Title"), column("Date")) else {
+        return Err("Not a Netflix viewing activity CSV: expected Title and Date columns".to_string());
+    };
+    let records: Vec<Vec<String>> = records.collect();
+    let date = |record: &Vec<String>| record.get(date_column).map(|d| d.trim().to_string()).unwrap_or_default();
+    let day_first = day_first.unwrap_or_else(|| records.iter().any(|record| is_day_first(&date(record))));
+    let formats = if day_first { NETFLIX_DAY_FIRST_FORMATS } else { NETFLIX_DATE_FORMATS };
+
+    Ok(records
+        .iter()
+        .map(|record| {
+            let title = record.get(title_column).map(|t| t.trim()).unwrap_or_default();
+            let date = date(record);
+            let mut entry = netflix_title(title);
+            match netflix_date(&date, formats) {
+                Some(date) => entry.watched_at = Some(format!("{} 00:00:00", date)),
+                None => entry.error = Some(format!("Unreadable date \"{}\"", date)),
+            }
+            if entry.title.is_empty() {
+                entry.error = Some("Missing title".to_string());
+            }
+            entry
+        })
+        .collect())
+}
+
+/// Netflix names episodes `Show: Season 2: Episode Title`, with `Part`,
+/// `Series`, `Volume`, or `Limited Series` in place of `Season` for some
+/// shows. Anything else is taken as a movie or show title as it stands.
+fn netflix_title(title: &str) -> ImportEntry {
+    let parts: Vec<&str> = title.split(": ").collect();
+    for (i, part) in parts.iter().enumerate().skip(1) {
+        let season = match part.split_once(' ') {
+            Some(("Season" | "Part" | "Series" | "Volume", number)) => number.trim().parse::<i64>().ok(),
+            _ if matches!(*part, "Limited Series" | "Miniseries") => Some(1),
+            _ => None,
+        };
+        if let Some(season) = season {
+            let episode_title = parts[i + 1..].join(": ");
+            return ImportEntry {
+                title: parts[..i].join(": "),
+                media_type: Some("tv"),
+                season_number: Some(season),
+                episode_title: Some(episode_title).filter(|t| !t.is_empty()),
+                ..Default::default()
+            };
+        }
+    }
+    ImportEntry {
+        title: title.to_string(),
+        ..Default::default()
+    }
+}
+
+fn netflix_date(date: &str, formats: &[&str]) -> Option<NaiveDate> {
+    formats
+        .iter()
+        .find_map(|format| NaiveDate::parse_from_str(date, format).ok())
+}
+
+/// Whether a slashed date can only be read day first, like `25/12/2023`.
+fn is_day_first(date: &str) -> bool {
+    let mut parts = date.split('/').map(|part| part.parse::<u32>().ok());
+    matches!(
+        (parts.next(), parts.next()),
+        (Some(Some(first)), Some(Some(second))) if first > 12 && second <= 12
+    )
+}
+
+/// Search results by title, type, and year. Netflix lists a show once per
+/// episode; it is searched for once.
+type Searches = HashMap<(String, Option<&'static str>, Option<i64>), Option<Match>>;
+
+/// A title as it will be stored in history.
+#[derive(Clone)]
+struct Match {
+    tmdb_id: i64,
+    media_type: String,
+    title: String,
+    poster_path: Option<String>,
+}
+
+/// Matches each entry to TMDB and, unless `dry_run`, adds the matches to
+/// the user's history as watched. Entries already in history are marked
+/// watched and keep the later of the two dates. Every row is matched before
+/// anything is written, so the write lock is held only for the inserts.
+pub async fn import(
+    db: &Db,
+    tmdb: &TmdbClient,
+    user_id: i64,
+    format: ImportFormat,
+    entries: Vec<ImportEntry>,
+    dry_run: bool,
+) -> anyhow::Result<ImportReport> {
+    let mut report = ImportReport {
+        format: format.as_str(),
+        dry_run,
+        total: entries.len(),
+        imported: 0,
+        failed: 0,
+        rows: Vec::with_capacity(entries.len()),
+    };
+    let mut searches = Searches::new();
+    let mut matched = Vec::new();
+
+    for (i, entry) in entries.into_iter().enumerate() {
+        let mut row = ImportedRow {
+            row: i + 1,
+            title: entry.title.clone(),
+            tmdb_id: None,
+            media_type: None,
+            season_number: entry.season_number,
+            episode_number: entry.episode_number,
+            watched_at: entry.watched_at.clone(),
+            error: entry.error.clone(),
+        };
+        if row.error.is_none() {
+            match resolve(tmdb, &mut searches, &entry).await {
+                Ok((found, episode_number)) => {
+                    row.tmdb_id = Some(found.tmdb_id);
+                    row.media_type = Some(found.media_type.clone());
+                    row.episode_number = episode_number;
+                    matched.push((found, entry, episode_number));
+                }
+                Err(e) => row.error = Some(e),
+            }
+        }
+        if row.error.is_some() {
+            report.failed += 1;
+        } else {
+            report.imported += 1;
+        }
+        report.rows.push(row);
+    }
+
+    if !dry_run {
+        let mut tx = db.begin().await?;
+        for (found, entry, episode_number) in &matched {
+            insert(&mut tx, user_id, found, entry, *episode_number).await?;
+        }
+        tx.commit().await?;
+        info!(
+            "Imported {} {} history rows for user {} ({} failed)",
+            report.imported, report.format, user_id, report.failed
+        );
+    }
+    Ok(report)
+}
+
+/// The TMDB title for an entry and, for episodes, the episode number.
+async fn resolve(
+    tmdb: &TmdbClient,
+    searches: &mut Searches,
+    entry: &ImportEntry,
+) -> Result<(Match, Option<i64>), String> {
+    let found = match (entry.tmdb_id, entry.media_type) {
+        (Some(tmdb_id), Some(media_type)) => Match {
+            tmdb_id,
+            media_type: media_type.to_string(),
+            title: entry.title.clone(),
+            poster_path: None,
+        },
+        _ => {
+            let key = (entry.title.to_lowercase(), entry.media_type, entry.year);
+            let found = match searches.get(&key) {
+                Some(found) => found.clone(),
+                None => {
+                    let found = search(tmdb, entry).await?;
+                    searches.insert(key, found.clone());
+                    found
+                }
+            };
+            found.ok_or_else(|| format!("No TMDB match for \"{}\"", entry.title))?
+        }
+    };
+
+    if found.media_type == "movie" {
+        return Ok((found, None));
+    }
+    let Some(season_number) = entry.season_number else {
+        return Err(format!("\"{}\" is a show; the row doesn't say which episode", entry.title));
+    };
+    if let Some(episode_number) = entry.episode_number {
+        return Ok((found, Some(episode_number)));
+    }
+
+    // Netflix names the episode but doesn't number it.
+    let episode_title = entry.episode_title.as_deref().unwrap_or_default();
+    let season = tmdb
+        .get_season(found.tmdb_id, season_number)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("{} has no season {}", found.title, season_number))?;
+    let episode = season
+        .episodes
+        .iter()
+        .find(|episode| episode.name.eq_ignore_ascii_case(episode_title))
+        .ok_or_else(|| format!("No episode \"{}\" in {} season {}", episode_title, found.title, season_number))?;
+    Ok((found, Some(episode.episode_number)))
+}
+
+/// The best search result for an entry: the first of the right type,
+/// preferring one from the right year when the file gives one.
+async fn search(tmdb: &TmdbClient, entry: &ImportEntry) -> Result<Option<Match>, String> {
+    let results = tmdb.search(&entry.title, 1).await.map_err(|e| e.to_string())?.results;
+    let candidates: Vec<&SearchResult> = results
+        .iter()
+        .filter(|result| match entry.media_type {
+            Some(media_type) => result.media_type == media_type,
+            None => matches!(result.media_type.as_str(), "movie" | "tv"),
+        })
+        .collect();
+    let year = |result: &SearchResult| {
+        result
+            .release_date
+            .as_deref()
+            .or(result.first_air_date.as_deref())
+            .and_then(|date| date.get(..4))
+            .and_then(|year| year.parse::<i64>().ok())
+    };
+    let best = entry
+        .year
+        .and_then(|wanted| candidates.iter().find(|result| year(result) == Some(wanted)))
+        .or(candidates.first());
+
+    Ok(best.map(|result| Match {
+        tmdb_id: result.id,
+        media_type: result.media_type.clone(),
+        title: result
+            .title
+            .clone()
+            .or_else(|| result.name.clone())
+            .unwrap_or_else(|| entry.title.clone()),
+        poster_path: result.poster_path.clone(),
+    }))
+}
+
+async fn insert(
+    tx: &mut db::Transaction<'_>,
+    user_id: i64,
+    found: &Match,
+    entry: &ImportEntry,
+    episode_number: Option<i64>,
+) -> anyhow::Result<()> {
+    let is_tv = found.media_type == "tv";
+    db::query(
+        r#"
+        INSERT INTO watch_history
+        (user_id, tmdb_id, media_type, title, poster_path, season_number, episode_number,
+         episode_title, completed, watched_at)
+        VALUES (?, ?, ?, ?, ?, ?, ?, ?, TRUE, COALESCE(?, datetime('now')))
+        ON CONFLICT(user_id, tmdb_id, media_type, season_number, episode_number)
+        DO UPDATE SET completed = TRUE,
+                      watched_at = CASE WHEN excluded.watched_at > watch_history.watched_at
+                                        THEN excluded.watched_at ELSE watch_history.watched_at END,
+                      episode_title = COALESCE(watch_history.episode_title, excluded.episode_title)
+        "#
+    )
+    .bind(user_id)
+    .bind(found.tmdb_id)
+    .bind(&found.media_type)
+    .bind(&found.title)
+    .bind(&found.poster_path)
+    .bind(entry.season_number.filter(|_| is_tv).unwrap_or(-1))
+    .bind(episode_number.filter(|_| is_tv).unwrap_or(-1))
+    .bind(entry.episode_title.as_deref().filter(|_| is_tv))
+    .bind(&entry.watched_at)
+    .execute(&mut *tx)
+    .await?;
+    Ok(())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ImportJobState {
+    Running,
+    Finished,
+    Failed,
+}
+
+/// A background import, as `GET /api/import/status` reports it.
+#[derive(Debug, Clone, Serialize)]
+pub struct ImportJob {
+    pub state: ImportJobState,
+    pub format: &'static str,
+    pub dry_run: bool,
+    pub total: usize,
+    pub started_at: String,
+    pub finished_at: Option<String>,
+    /// Set once the job finishes.
+    pub report: Option<ImportReport>,
+    pub error: Option<String>,
+}
+
+/// Each user's latest background import, kept until the next one starts
+/// or the server restarts.
+#[derive(Debug, Default)]
+pub struct ImportJobs {
+    jobs: Mutex<HashMap<i64, ImportJob>>,
+}
+
+impl ImportJobs {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn latest(&self, user_id: i64) -> Option<ImportJob> {
+        self.jobs.lock().unwrap().get(&user_id).cloned()
+    }
+
+    /// Starts importing `entries` in the background, unless the user already
+    /// has an import running, in which case it returns `None`. The user is
+    /// notified when it finishes.
+    pub fn start(
+        &self,
+        state: AppState,
+        user_id: i64,
+        format: ImportFormat,
+        entries: Vec<ImportEntry>,
+        dry_run: bool,
+    ) -> Option<ImportJob> {
+        let job = ImportJob {
+            state: ImportJobState::Running,
+            format: format.as_str(),
+            dry_run,
+            total: entries.len(),
+            started_at: Utc::now().to_rfc3339(),
+            finished_at: None,
+            report: None,
+            error: None,
+        };
+        {
+            let mut jobs = self.jobs.lock().unwrap();
+            if jobs.get(&user_id).is_some_and(|job| job.state == ImportJobState::Running) {
+                return None;
+            }
+            jobs.insert(user_id, job.clone());
+        }
+
+        tokio::spawn(async move {
+            let result = import(&state.db, &state.tmdb, user_id, format, entries, dry_run).await;
+            let message = match &result {
+                Ok(report) => format!("{} imported, {} failed", report.imported, report.failed),
+                Err(e) => {
+                    error!("History import for user {} failed: {}", user_id, e);
+                    "The import failed".to_string()
+                }
+            };
+            if let Some(job) = state.imports.jobs.lock().unwrap().get_mut(&user_id) {
+                job.finished_at = Some(Utc::now().to_rfc3339());
+                match result {
+                    Ok(report) => {
+                        job.state = ImportJobState::Finished;
+                        job.report = Some(report);
+                    }
+                    Err(e) => {
+                        job.state = ImportJobState::Failed;
+                        job.error = Some(e.to_string());
+                    }
+                }
+            }
+            state.notifications.notify_user(
+                user_id,
+                NotificationKind::JobFinished,
+                "History import finished",
+                message,
+                Some("/history".to_string()),
+            ).await;
+        });
+        Some(job)
+    }
+}
@@ -0,0 +1,302 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use axum::body::{Body, Bytes};
+use chrono::Utc;
+use futures_util::StreamExt;
+use reqwest::header::{CONTENT_TYPE, ORIGIN, REFERER};
+use reqwest::{Client, Response, Url};
+use tracing::debug;
+
+use crate::auth::SessionSecrets;
+use crate::outbound;
+
+/// Route that relays manifests and segments from upstream HLS hosts.
+pub const PROXY_PATH: &str = "/stream/proxy";
+
+/// How long a signed proxy URL works. Segment URLs are signed when their
+/// manifest is fetched, so this has to outlast a long film.
+const SIGNED_URL_TTL_SECS: i64 = 4 * 60 * 60;
+
+const MANIFEST_CONTENT_TYPE: &str = "application/vnd.apple.mpegurl";
+
+/// Largest embed page read when looking for a manifest.
+const MAX_PAGE_BYTES: usize = 4 * 1024 * 1024;
+
+/// Largest manifest read for rewriting; manifests are held in memory.
+const MAX_MANIFEST_BYTES: usize = 4 * 1024 * 1024;
+
+/// Largest key or segment relayed. Segments are a few seconds of video, so
+/// this leaves room for high bitrates; they are streamed through, not held.
+const MAX_RELAYED_BYTES: usize = 64 * 1024 * 1024;
+
+/// Enough of a body to tell a manifest (`#EXTM3U`) from a segment.
+const SNIFF_BYTES: usize = 7;
+
+/// A manifest or segment fetched on the player's behalf. Manifests are
+/// rewritten in memory; anything else streams from the upstream host.
+#[derive(Debug)]
+pub struct Relayed {
+    pub content_type: String,
+    pub body: Body,
+}
+
+/// Pulls `.m3u8` URLs out of embed pages and relays them with the
+/// Referer/Origin headers the upstream host expects, so a native player can
+/// play them without the provider's iframe.
+#[derive(Debug)]
+pub struct HlsProxy {
+    client: Client,
+    /// Upstream host -> (origin sent as Referer/Origin, when its last signed
+    /// link expires). Only hosts reached through a resolved manifest are
+    /// relayed, so this is not an open proxy.
+    hosts: Mutex<HashMap<String, (String, i64)>>,
+    secrets: SessionSecrets,
+}
+
+impl HlsProxy {
+    pub fn new(secrets: SessionSecrets) -> anyhow::Result<Self> {
+        let client = outbound::client_builder(Duration::from_secs(30)).build()?;
+
+        Ok(Self {
+            client,
+            hosts: Mutex::new(HashMap::new()),
+            secrets,
+        })
+    }
+
+    /// Fetches an embed page and returns a proxied URL for the first manifest
+    /// it references, or `None` when the page has none.
+    pub async fn resolve(&self, embed_url: &str, viewer: Option<i64>) -> anyhow::Result<Option<String>> {
+        let embed = Url::parse(embed_url)?;
+        outbound::check_url(&embed).map_err(anyhow::Error::msg)?;
+        let origin = embed.origin().ascii_serialization();
+        let response = self
+            .client
+            .get(embed)
+            .header(REFERER, format!("{}/", origin))
+            .send()
+            .await?
+            .error_for_status()?;
+        let page = read_limited(response, MAX_PAGE_BYTES).await?;
+        let page = String::from_utf8_lossy(&page);
+
+        let Some(manifest) = find_manifest_url(&page) else {
+            return Ok(None);
+        };
+        let manifest = Url::parse(&manifest)?;
+        outbound::check_url(&manifest).map_err(anyhow::Error::msg)?;
+        self.prune_hosts();
+        debug!("Resolved HLS manifest {} from {}", manifest, embed_url);
+        Ok(Some(self.relay(&manifest, &origin, viewer)))
+    }
+
+    /// Fetches `url` for the player. Manifests are rewritten so every nested
+    /// playlist, key, and segment also goes through the proxy. `Ok(None)`
+    /// means the host was never reached through a resolved manifest.
+    pub async fn fetch(&self, url: &str, user_id: i64) -> anyhow::Result<Option<Relayed>> {
+        let url = Url::parse(url)?;
+        let now = Utc::now().timestamp();
+        let Some(origin) = url.host_str().and_then(|host| {
+            self.hosts
+                .lock()
+                .unwrap()
+                .get(host)
+                .filter(|(_, expires)| *expires >= now)
+                .map(|(origin, _)| origin.clone())
+        }) else {
+            return Ok(None);
+        };
+        outbound::check_url(&url).map_err(anyhow::Error::msg)?;
+
+        let mut response = self
+            .client
+            .get(url.clone())
+            .header(REFERER, format!("{}/", origin))
+            .header(ORIGIN, &origin)
+            .send()
+            .await?
+            .error_for_status()?;
+        let content_type = response
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or("application/octet-stream")
+            .to_string();
+        check_length(&response, MAX_RELAYED_BYTES)?;
+        let mut head = Vec::new();
+        read_into(&mut response, &mut head, MAX_MANIFEST_BYTES, SNIFF_BYTES).await?;
+
+        if content_type.contains("mpegurl") || head.starts_with(b"#EXTM3U") {
+            check_length(&response, MAX_MANIFEST_BYTES)?;
+            read_into(&mut response, &mut head, MAX_MANIFEST_BYTES, usize::MAX).await?;
+            let manifest = self.rewrite_manifest(&String::from_utf8_lossy(&head), &url, &origin, Some(user_id));
+            return Ok(Some(Relayed {
+                content_type: MANIFEST_CONTENT_TYPE.to_string(),
+                body: Body::from(manifest),
+            }));
+        }
+
+        Ok(Some(Relayed {
+            content_type,
+            body: stream_limited(head, response, MAX_RELAYED_BYTES),
+        }))
+    }
+
+    /// Checks a proxy URL's signature and expiry, so upstream links can't be
+    /// scraped from a page and replayed later, elsewhere, or by another user.
+    pub fn verify(&self, url: &str, expires: i64, user_id: i64, signature: &str) -> bool {
+        if expires < Utc::now().timestamp() {
+            return false;
+        }
+        self.secrets.verify(&signed_message(url, expires, Some(user_id)), signature)
+    }
+
+    /// Allows `url`'s host until the returned signed, expiring proxied URL
+    /// for it stops working.
+    fn relay(&self, url: &Url, origin: &str, viewer: Option<i64>) -> String {
+        let expires = Utc::now().timestamp() + SIGNED_URL_TTL_SECS;
+        if let Some(host) = url.host_str() {
+            self.hosts
+                .lock()
+                .unwrap()
+                .insert(host.to_string(), (origin.to_string(), expires));
+        }
+        let signature = self.secrets.sign(&signed_message(url.as_str(), expires, viewer));
+        format!(
+            "{}?url={}&expires={}&sig={}",
+            PROXY_PATH,
+            encode_component(url.as_str()),
+            expires,
+            signature
+        )
+    }
+
+    /// Forgets hosts whose signed links have all expired.
+    fn prune_hosts(&self) {
+        let now = Utc::now().timestamp();
+        self.hosts.lock().unwrap().retain(|_, (_, expires)| *expires >= now);
+    }
+
+    fn rewrite_manifest(&self, manifest: &str, base: &Url, origin: &str, viewer: Option<i64>) -> String {
+        let mut rewritten = String::with_capacity(manifest.len());
+        for line in manifest.lines() {
+            let line = line.trim();
+            if line.starts_with('#') {
+                rewritten.push_str(&self.rewrite_uri_attribute(line, base, origin, viewer));
+            } else if let Some(url) = (!line.is_empty()).then(|| base.join(line).ok()).flatten() {
+                rewritten.push_str(&self.relay(&url, origin, viewer));
+            } else {
+                rewritten.push_str(line);
+            }
+            rewritten.push('\n');
+        }
+        rewritten
+    }
+
+    /// Tags such as `#EXT-X-KEY` and `#EXT-X-MEDIA` carry their URL in `URI="..."`.
+    fn rewrite_uri_attribute(&self, tag: &str, base: &Url, origin: &str, viewer: Option<i64>) -> String {
+        let Some(start) = tag.find("URI=\"").map(|i| i + "URI=\"".len()) else {
+            return tag.to_string();
+        };
+        let Some(end) = tag[start..].find('"').map(|i| start + i) else {
+            return tag.to_string();
+        };
+        match base.join(&tag[start..end]) {
+            Ok(url) => format!("{}{}{}", &tag[..start], self.relay(&url, origin, viewer), &tag[end..]),
+            Err(_) => tag.to_string(),
+        }
+    }
+}
+
+/// Reads a response body, failing once it passes `limit` bytes rather than
+/// buffering whatever the upstream host sends.
+async fn read_limited(mut response: Response, limit: usize) -> anyhow::Result<Vec<u8>> {
+    check_length(&response, limit)?;
+    let mut body = Vec::new();
+    read_into(&mut response, &mut body, limit, usize::MAX).await?;
+    Ok(body)
+}
+
+/// Fails early when the upstream host announces a body over `limit` bytes.
+fn check_length(response: &Response, limit: usize) -> anyhow::Result<()> {
+    if response.content_length().is_some_and(|len| len > limit as u64) {
+        anyhow::bail!("Upstream response is larger than {} bytes", limit);
+    }
+    Ok(())
+}
+
+/// Appends chunks to `body` until it holds `want` bytes or the response
+/// ends, failing once it passes `limit`.
+async fn read_into(response: &mut Response, body: &mut Vec<u8>, limit: usize, want: usize) -> anyhow::Result<()> {
+    while body.len() < want {
+        let Some(chunk) = response.chunk().await? else {
+            break;
+        };
+        if body.len() + chunk.len() > limit {
+            anyhow::bail!("Upstream response is larger than {} bytes", limit);
+        }
+        body.extend_from_slice(&chunk);
+    }
+    Ok(())
+}
+
+/// `head` and then the rest of the response, passed through as it arrives.
+/// The stream fails once the total passes `limit`, cutting the player off
+/// rather than relaying whatever the upstream host sends.
+fn stream_limited(head: Vec<u8>, response: Response, limit: usize) -> Body {
+    let sent = head.len();
+    let rest = futures_util::stream::try_unfold((response, sent), move |(mut response, sent)| async move {
+        let Some(chunk) = response.chunk().await.map_err(std::io::Error::other)? else {
+            return Ok(None);
+        };
+        let sent = sent + chunk.len();
+        if sent > limit {
+            return Err(std::io::Error::other(format!("Upstream response is larger than {} bytes", limit)));
+        }
+        Ok(Some((chunk, (response, sent))))
+    });
+    Body::from_stream(futures_util::stream::once(async move { Ok(Bytes::from(head)) }).chain(rest))
+}
+
+/// What a proxy link's signature covers: the upstream URL, its expiry, and
+/// the user it was made for. Links made without a user never verify.
+fn signed_message(url: &str, expires: i64, viewer: Option<i64>) -> String {
+    match viewer {
+        Some(user_id) => format!("{}.{}.{}", url, expires, user_id),
+        None => format!("{}.{}.-", url, expires),
+    }
+}
+
+/// The first absolute `.m3u8` URL in a page, including JSON-escaped ones.
+fn find_manifest_url(page: &str) -> Option<String> {
+    let page = page.replace("\\/", "/");
+    let is_delimiter = |c: char| c.is_whitespace() || matches!(c, '"' | '\'' | '`' | '<' | '>' | '(' | ')');
+
+    let mut from = 0;
+    while let Some(found) = page[from..].find(".m3u8") {
+        let extension_end = from + found + ".m3u8".len();
+        let start = page[..extension_end].rfind(is_delimiter).map_or(0, |i| i + 1);
+        let end = page[extension_end..]
+            .find(is_delimiter)
+            .map_or(page.len(), |i| extension_end + i);
+        let candidate = &page[start..end];
+        if candidate.starts_with("https://") || candidate.starts_with("http://") {
+            return Some(candidate.to_string());
+        }
+        from = extension_end;
+    }
+    None
+}
+
+fn encode_component(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => encoded.push(byte as char),
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
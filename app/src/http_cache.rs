@@ -0,0 +1,116 @@
+use axum::{
+    body::{Body, HttpBody},
+    extract::{Request, State},
+    http::{header, HeaderValue, Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use sha2::{Digest, Sha256};
+
+use crate::auth::AuthMode;
+use crate::error::AppError;
+use crate::AppState;
+
+/// TMDB listings such as trending and popular, the same for every viewer.
+/// Only `public` without accounts; see [`cache_control`].
+const LISTING: &str = "public, max-age=300";
+
+/// Search and discover results, which parental controls filter per viewer.
+const PERSONAL_LISTING: &str = "private, max-age=300";
+
+/// Lookups and genres, which TMDB rarely changes.
+const DETAIL: &str = "public, max-age=86400";
+
+/// Movie, show, and season details, which parental controls may refuse.
+const PERSONAL_DETAIL: &str = "private, max-age=86400";
+
+/// Everything else is per-user or changes as it is used, so it is always
+/// revalidated, which the `ETag` keeps cheap on the wire.
+const REVALIDATE: &str = "private, no-cache";
+
+/// Larger responses, and ones whose size isn't known up front, go out
+/// unchanged, without an `ETag`.
+const MAX_ETAG_BYTES: u64 = 64 * 1024 * 1024;
+
+/// How long a successful API `GET` may be reused, by path relative to `/api`.
+/// With `AUTH_MODE=accounts` the API is behind sign-in, so even listings are
+/// `private`: a shared cache must not hand them to signed-out clients.
+fn cache_control(path: &str, shared: bool) -> &'static str {
+    let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+    let is_id = |segment: &str| segment.parse::<i64>().is_ok();
+    match segments.as_slice() {
+        ["movies" | "tv", "popular" | "now_playing" | "upcoming" | "top_rated"]
+        | ["trending", _, _]
+        | ["people", "trending"]
+        | ["movie" | "tv", _, "providers" | "recommendations"] => {
+            if shared { LISTING } else { PERSONAL_LISTING }
+        }
+        ["search"] | ["discover"] => PERSONAL_LISTING,
+        ["movie" | "tv", id] if is_id(id) => PERSONAL_DETAIL,
+        ["tv", _, "season", _] => PERSONAL_DETAIL,
+        ["find", _] | ["genres"] => {
+            if shared { DETAIL } else { PERSONAL_DETAIL }
+        }
+        _ => REVALIDATE,
+    }
+}
+
+/// Adds `Cache-Control` and a weak `ETag` to successful JSON responses to
+/// `GET`, and answers `304 Not Modified` when the client already has them.
+/// Handlers that set their own `Cache-Control` keep it.
+pub async fn cache_headers(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    if request.method() != Method::GET && request.method() != Method::HEAD {
+        return next.run(request).await;
+    }
+    let shared = state.auth.mode() == AuthMode::Local;
+    let cache_control = cache_control(request.uri().path(), shared);
+    let if_none_match = request.headers().get(header::IF_NONE_MATCH).cloned();
+
+    let response = next.run(request).await;
+    let is_json = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.starts_with("application/json"));
+    let fits = response
+        .body()
+        .size_hint()
+        .upper()
+        .is_some_and(|size| size <= MAX_ETAG_BYTES);
+    if response.status() != StatusCode::OK || !is_json || !fits {
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+    // Only a body that fails while being read ends up here.
+    let Ok(bytes) = axum::body::to_bytes(body, usize::MAX).await else {
+        return AppError::Internal.into_response();
+    };
+    let digest = Sha256::digest(&bytes);
+    let etag = format!("W/\"{}\"", hex::encode(&digest[..16]));
+    let etag = HeaderValue::from_str(&etag).expect("hex is a valid header value");
+
+    parts.headers.insert(header::ETAG, etag.clone());
+    parts
+        .headers
+        .entry(header::CACHE_CONTROL)
+        .or_insert(HeaderValue::from_static(cache_control));
+
+    if if_none_match.is_some_and(|tags| matches_etag(&tags, &etag)) {
+        parts.status = StatusCode::NOT_MODIFIED;
+        parts.headers.remove(header::CONTENT_TYPE);
+        parts.headers.remove(header::CONTENT_LENGTH);
+        return Response::from_parts(parts, Body::empty());
+    }
+    Response::from_parts(parts, Body::from(bytes))
+}
+
+/// Weak comparison of an `If-None-Match` list, or `*`, against `etag`.
+fn matches_etag(if_none_match: &HeaderValue, etag: &HeaderValue) -> bool {
+    let Ok(tags) = if_none_match.to_str() else {
+        return false;
+    };
+    let opaque = |tag: &str| tag.trim().trim_start_matches("W/").to_string();
+    let ours = opaque(etag.to_str().unwrap_or_default());
+    tags.split(',').any(|tag| tag.trim() == "*" || opaque(tag) == ours)
+}
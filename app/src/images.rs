@@ -0,0 +1,111 @@
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use reqwest::Client;
+use tracing::{debug, warn};
+
+use crate::metrics::AppMetrics;
+use crate::tmdb::{image_config, TMDB_IMAGE_BASE};
+
+pub const DEFAULT_IMAGE_CACHE_DIR: &str = "./cache/images";
+
+/// TMDB artwork never changes under a given path, so clients may keep it forever.
+pub const IMAGE_CACHE_CONTROL: &str = "public, max-age=31536000, immutable";
+
+/// Proxies `image.tmdb.org` and keeps a copy of every image on disk, so pages
+/// never hotlink TMDB and the desktop app can show artwork offline.
+#[derive(Debug)]
+pub struct ImageCache {
+    client: Client,
+    dir: PathBuf,
+    metrics: Arc<AppMetrics>,
+}
+
+impl ImageCache {
+    pub fn new(dir: &str, metrics: Arc<AppMetrics>) -> anyhow::Result<Self> {
+        let client = Client::builder()
+            .timeout(Duration::from_secs(30))
+            .build()?;
+
+        let dir = PathBuf::from(dir);
+        std::fs::create_dir_all(&dir)?;
+
+        Ok(Self {
+            client,
+            dir,
+            metrics,
+        })
+    }
+
+    /// Returns the image bytes, downloading them on a miss. `Ok(None)` means
+    /// TMDB has no such image.
+    pub async fn get(&self, size: &str, file: &str) -> anyhow::Result<Option<Vec<u8>>> {
+        let path = self.dir.join(size).join(file);
+
+        if let Ok(bytes) = tokio::fs::read(&path).await {
+            self.metrics.record_cache("image", true);
+            return Ok(Some(bytes));
+        }
+        self.metrics.record_cache("image", false);
+
+        let url = format!("{}/{}/{}", TMDB_IMAGE_BASE, size, file);
+        let response = self.client.get(&url).send().await?;
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        if !response.status().is_success() {
+            return Err(anyhow::anyhow!("TMDB image error: {}", response.status()));
+        }
+        let bytes = response.bytes().await?.to_vec();
+
+        // Write to a temp file first so a concurrent reader never sees a partial image.
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        let partial = path.with_extension(format!("{}.part", uuid::Uuid::new_v4()));
+        match tokio::fs::write(&partial, &bytes).await {
+            Ok(()) => {
+                if let Err(e) = tokio::fs::rename(&partial, &path).await {
+                    warn!("Could not cache image {}: {}", path.display(), e);
+                    tokio::fs::remove_file(&partial).await.ok();
+                }
+            }
+            Err(e) => warn!("Could not cache image {}: {}", path.display(), e),
+        }
+
+        debug!("Cached image {}/{}", size, file);
+        Ok(Some(bytes))
+    }
+}
+
+/// Only sizes TMDB serves are proxied; anything else is rejected before
+/// touching the network.
+pub fn is_valid_size(size: &str) -> bool {
+    image_config().is_known_size(size)
+}
+
+/// TMDB image paths are a single flat file name; this also rules out traversal.
+pub fn is_valid_file(file: &str) -> bool {
+    let Some((stem, ext)) = file.rsplit_once('.') else {
+        return false;
+    };
+    !stem.is_empty()
+        && stem.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+        && content_type(ext).is_some()
+}
+
+pub fn content_type(extension: &str) -> Option<&'static str> {
+    match extension.to_ascii_lowercase().as_str() {
+        "jpg" | "jpeg" => Some("image/jpeg"),
+        "png" => Some("image/png"),
+        "webp" => Some("image/webp"),
+        "svg" => Some("image/svg+xml"),
+        _ => None,
+    }
+}
+
+/// Local URL for a TMDB image path such as `/abc123.jpg`.
+pub fn image_url(path: &str, size: &str) -> String {
+    format!("/img/{}/{}", size, path.trim_start_matches('/'))
+}
@@ -0,0 +1,317 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use chrono::Utc;
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use serde::{Deserialize, Serialize};
+use sqlx::sqlite::SqliteConnectOptions;
+use sqlx::{ConnectOptions, Connection};
+use tracing::{info, warn};
+
+use crate::config::Config;
+use crate::db;
+
+/// Bumped whenever the archive layout changes in a way older builds can't read.
+const ARCHIVE_FORMAT_VERSION: u32 = 1;
+
+const MANIFEST_ENTRY: &str = "manifest.json";
+const CONFIG_ENTRY: &str = "config.json";
+const DATABASE_ENTRY: &str = "streaming.db";
+
+#[derive(Debug, Serialize, Deserialize)]
+struct InstanceManifest {
+    format_version: u32,
+    app_version: String,
+    created_at: String,
+    tables: Vec<TableSummary>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct TableSummary {
+    name: String,
+    rows: i64,
+}
+
+/// Writes a `.tar.gz` containing a consistent snapshot of the database, the
+/// active configuration, and a manifest used for compatibility checks.
+/// The image cache is left out; it is rebuilt from TMDB on demand.
+pub async fn export_instance(config: &Config, output: &Path) -> anyhow::Result<()> {
+    require_sqlite(&config.database_url)?;
+    let pool = db::init_sqlite(&config.database_url, config.database_max_connections).await?;
+
+    let table_names: Vec<String> = sqlx::query_scalar(
+        "SELECT name FROM sqlite_master WHERE type = 'table' AND name NOT LIKE 'sqlite_%' ORDER BY name"
+    )
+    .fetch_all(&pool)
+    .await?;
+
+    let mut tables = Vec::with_capacity(table_names.len());
+    for name in table_names {
+        let rows: i64 = sqlx::query_scalar(&format!("SELECT COUNT(*) FROM \"{}\"", name))
+            .fetch_one(&pool)
+            .await?;
+        tables.push(TableSummary { name, rows });
+    }
+
+    let snapshot = std::env::temp_dir().join(format!("ruststream-export-{}.db", uuid::Uuid::new_v4()));
+    sqlx::query("VACUUM INTO ?")
+        .bind(snapshot.to_string_lossy().to_string())
+        .execute(&pool)
+        .await?;
+    pool.close().await;
+
+    let manifest = InstanceManifest {
+        format_version: ARCHIVE_FORMAT_VERSION,
+        app_version: env!("CARGO_PKG_VERSION").to_string(),
+        created_at: Utc::now().to_rfc3339(),
+        tables,
+    };
+
+    let result = write_archive(output, &manifest, config, &snapshot);
+    fs::remove_file(&snapshot).ok();
+    result?;
+
+    info!("Exported instance to {}", output.display());
+    Ok(())
+}
+
+fn write_archive(
+    output: &Path,
+    manifest: &InstanceManifest,
+    config: &Config,
+    snapshot: &Path,
+) -> anyhow::Result<()> {
+    // The configuration includes the session secret and API keys, so only
+    // the owner may read the archive.
+    let mut options = OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    let file = options.open(output)?;
+    // `mode` applies only to new files; an archive being replaced keeps its own.
+    #[cfg(unix)]
+    file.set_permissions(std::os::unix::fs::PermissionsExt::from_mode(0o600))?;
+    let encoder = GzEncoder::new(file, Compression::default());
+    let mut archive = tar::Builder::new(encoder);
+
+    append_json(&mut archive, MANIFEST_ENTRY, manifest)?;
+    append_json(&mut archive, CONFIG_ENTRY, config)?;
+    archive.append_path_with_name(snapshot, DATABASE_ENTRY)?;
+
+    archive.into_inner()?.finish()?;
+    Ok(())
+}
+
+fn append_json<W: io::Write, T: Serialize>(
+    archive: &mut tar::Builder<W>,
+    name: &str,
+    value: &T,
+) -> anyhow::Result<()> {
+    let bytes = serde_json::to_vec_pretty(value)?;
+    let mut header = tar::Header::new_gnu();
+    header.set_size(bytes.len() as u64);
+    header.set_mode(0o600);
+    header.set_mtime(Utc::now().timestamp() as u64);
+    header.set_cksum();
+    archive.append_data(&mut header, name, bytes.as_slice())?;
+    Ok(())
+}
+
+/// Restores an archive produced by [`export_instance`] into the database at
+/// `database_url` and writes its configuration to `.env`. Refuses to
+/// overwrite existing data unless `force` is set.
+pub async fn import_instance(archive_path: &Path, database_url: &str, force: bool) -> anyhow::Result<()> {
+    let db_path = sqlite_path(database_url)?;
+    if db_path.exists() && !force {
+        return Err(anyhow::anyhow!(
+            "{} already exists; re-run with --force to replace it",
+            db_path.display()
+        ));
+    }
+
+    let staging = db_path.with_extension("importing");
+    let (manifest, config) = match unpack_archive(archive_path, &staging).await {
+        Ok(unpacked) => unpacked,
+        Err(e) => {
+            fs::remove_file(&staging).ok();
+            return Err(e);
+        }
+    };
+    if let Err(e) = replace_database(&staging, &db_path) {
+        fs::remove_file(&staging).ok();
+        return Err(e.into());
+    }
+
+    // Bring an older snapshot's schema up to date with this build.
+    db::init_sqlite(database_url, db::DEFAULT_MAX_CONNECTIONS)
+        .await?
+        .close()
+        .await;
+
+    if let Some(config) = config {
+        let env_path = Path::new(".env");
+        if env_path.exists() && !force {
+            warn!(".env already exists; leaving it untouched (use --force to replace it)");
+        } else {
+            fs::write(env_path, env_file_contents(&config, database_url))?;
+            info!("Wrote imported configuration to .env");
+        }
+    }
+
+    for table in &manifest.tables {
+        info!("Imported {} ({} rows)", table.name, table.rows);
+    }
+    info!(
+        "Imported instance exported by RustStream {} on {}",
+        manifest.app_version, manifest.created_at
+    );
+    Ok(())
+}
+
+/// Reads the archive, writing its database to `staging` and checking that
+/// file's integrity, so nothing in place is touched until it is known good.
+async fn unpack_archive(
+    archive_path: &Path,
+    staging: &Path,
+) -> anyhow::Result<(InstanceManifest, Option<serde_json::Value>)> {
+    let mut manifest: Option<InstanceManifest> = None;
+    let mut config: Option<serde_json::Value> = None;
+    let mut has_database = false;
+
+    let mut archive = tar::Archive::new(GzDecoder::new(File::open(archive_path)?));
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let name = entry.path()?.to_string_lossy().to_string();
+
+        match name.as_str() {
+            MANIFEST_ENTRY => {
+                let mut contents = String::new();
+                entry.read_to_string(&mut contents)?;
+                let parsed: InstanceManifest = serde_json::from_str(&contents)?;
+                check_compatibility(&parsed)?;
+                manifest = Some(parsed);
+            }
+            CONFIG_ENTRY => {
+                let mut contents = String::new();
+                entry.read_to_string(&mut contents)?;
+                config = Some(serde_json::from_str(&contents)?);
+            }
+            DATABASE_ENTRY => {
+                if manifest.is_none() {
+                    return Err(anyhow::anyhow!("Archive is missing its manifest"));
+                }
+                if let Some(parent) = staging.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+                io::copy(&mut entry, &mut File::create(staging)?)?;
+                has_database = true;
+            }
+            other => warn!("Skipping unknown archive entry: {}", other),
+        }
+    }
+
+    let manifest = manifest.ok_or_else(|| anyhow::anyhow!("Archive is missing its manifest"))?;
+    if !has_database {
+        return Err(anyhow::anyhow!("Archive does not contain a database"));
+    }
+
+    let mut conn = SqliteConnectOptions::new().filename(staging).connect().await?;
+    let integrity: String = sqlx::query_scalar("PRAGMA integrity_check")
+        .fetch_one(&mut conn)
+        .await?;
+    conn.close().await?;
+    if integrity != "ok" {
+        return Err(anyhow::anyhow!("Imported database failed integrity check: {}", integrity));
+    }
+
+    Ok((manifest, config))
+}
+
+/// Moves the checked `staging` file over `db_path`.
+fn replace_database(staging: &Path, db_path: &Path) -> io::Result<()> {
+    // A write-ahead log left beside the old database would be replayed
+    // into the new one.
+    for suffix in ["-wal", "-shm"] {
+        let mut sidecar = db_path.as_os_str().to_owned();
+        sidecar.push(suffix);
+        match fs::remove_file(&sidecar) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+            _ => {}
+        }
+    }
+    fs::rename(staging, db_path)
+}
+
+fn check_compatibility(manifest: &InstanceManifest) -> anyhow::Result<()> {
+    if manifest.format_version > ARCHIVE_FORMAT_VERSION {
+        return Err(anyhow::anyhow!(
+            "Archive format v{} is newer than this build supports (v{})",
+            manifest.format_version,
+            ARCHIVE_FORMAT_VERSION
+        ));
+    }
+
+    let archive_version = parse_version(&manifest.app_version)
+        .ok_or_else(|| anyhow::anyhow!("Invalid app version in archive: {}", manifest.app_version))?;
+    let current_version = parse_version(env!("CARGO_PKG_VERSION")).unwrap_or_default();
+    if archive_version > current_version {
+        return Err(anyhow::anyhow!(
+            "Archive was exported by RustStream {}, which is newer than this install ({}); upgrade first",
+            manifest.app_version,
+            env!("CARGO_PKG_VERSION")
+        ));
+    }
+
+    Ok(())
+}
+
+fn parse_version(version: &str) -> Option<(u64, u64, u64)> {
+    let mut parts = version.split('.').map(|p| p.parse::<u64>().ok());
+    Some((parts.next()??, parts.next()??, parts.next()??))
+}
+
+/// Archives hold a SQLite file; a PostgreSQL database is backed up with
+/// its own tools.
+fn require_sqlite(database_url: &str) -> anyhow::Result<()> {
+    if db::is_postgres_url(database_url) {
+        anyhow::bail!("Instance export and import only work with SQLite; back up PostgreSQL with pg_dump instead");
+    }
+    Ok(())
+}
+
+fn sqlite_path(database_url: &str) -> anyhow::Result<PathBuf> {
+    require_sqlite(database_url)?;
+    let options = if database_url.starts_with("sqlite:") {
+        SqliteConnectOptions::from_str(database_url)?
+    } else {
+        SqliteConnectOptions::new().filename(database_url)
+    };
+    Ok(options.get_filename().to_path_buf())
+}
+
+/// Config field names double as their environment variable names.
+fn env_file_contents(config: &serde_json::Value, database_url: &str) -> String {
+    let mut contents = String::from("# Imported by `ruststream import-instance`\n");
+
+    if let Some(fields) = config.as_object() {
+        for (key, value) in fields {
+            let value = match (key.as_str(), value) {
+                ("database_url", _) => database_url.to_string(),
+                (_, serde_json::Value::Null) => continue,
+                (_, serde_json::Value::String(s)) => s.clone(),
+                // Lists such as ADMIN_ALLOWED_IPS are comma-separated in the environment.
+                (_, serde_json::Value::Array(items)) => items
+                    .iter()
+                    .map(|item| item.as_str().map_or_else(|| item.to_string(), str::to_string))
+                    .collect::<Vec<_>>()
+                    .join(","),
+                (_, other) => other.to_string(),
+            };
+            contents.push_str(&format!("{}={}\n", key.to_uppercase(), value));
+        }
+    }
+
+    contents
+}
@@ -0,0 +1,207 @@
+use bcrypt::{hash, DEFAULT_COST};
+use chrono::{Duration, Utc};
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::auth::Role;
+use crate::db::{self, Db};
+use crate::error::AppError;
+
+pub const MIN_PASSWORD_LENGTH: usize = 8;
+const MAX_USERNAME_LENGTH: usize = 32;
+
+/// A single-use code an admin hands out so someone can create an account.
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct Invite {
+    pub code: String,
+    pub role: String,
+    /// Unix timestamp; `None` never expires.
+    pub expires_at: Option<i64>,
+    pub created_at: String,
+    pub used_by: Option<i64>,
+    pub used_at: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Registration {
+    pub invite_code: String,
+    pub username: String,
+    pub password: String,
+}
+
+/// Why a registration was turned down. All of these are the caller's to fix.
+#[derive(Debug, thiserror::Error)]
+pub enum RegisterError {
+    #[error("Invite code is invalid, used, or expired")]
+    InvalidInvite,
+
+    #[error("Username is already taken")]
+    UsernameTaken,
+
+    #[error("{0}")]
+    Invalid(String),
+
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+impl From<sqlx::Error> for RegisterError {
+    fn from(err: sqlx::Error) -> Self {
+        Self::Other(err.into())
+    }
+}
+
+/// The account a redeemed invite created.
+#[derive(Debug, Clone)]
+pub struct NewUser {
+    pub id: i64,
+    pub username: String,
+    pub role: Role,
+}
+
+/// Refuses an invite to a role above the inviter's own, so a `manage_users`
+/// grant can't be used to make new admins.
+pub fn check_invite_role(inviter: Role, role: Role) -> Result<(), AppError> {
+    if role.outranks(inviter) {
+        return Err(AppError::Forbidden(format!(
+            "Only admins can invite {} accounts",
+            role.as_str()
+        )));
+    }
+    Ok(())
+}
+
+/// Invite codes kept in the `invite_codes` table.
+#[derive(Debug, Clone)]
+pub struct Invites {
+    db: Db,
+}
+
+impl Invites {
+    pub fn new(db: Db) -> Self {
+        Self { db }
+    }
+
+    pub async fn create(&self, created_by: i64, role: Role, expires_in_days: Option<i64>) -> anyhow::Result<Invite> {
+        let code = uuid::Uuid::new_v4().simple().to_string()[..16].to_string();
+        let expires_at = expires_in_days.map(|days| (Utc::now() + Duration::days(days)).timestamp());
+        let invite = db::query_as(
+            r#"
+            INSERT INTO invite_codes (code, role, created_by, expires_at)
+            VALUES (?, ?, ?, ?)
+            RETURNING code, role, expires_at, created_at, used_by, used_at
+            "#
+        )
+        .bind(&code)
+        .bind(role.as_str())
+        .bind(created_by)
+        .bind(expires_at)
+        .fetch_one(&self.db)
+        .await?;
+        Ok(invite)
+    }
+
+    pub async fn list(&self) -> anyhow::Result<Vec<Invite>> {
+        let invites = db::query_as(
+            r#"
+            SELECT code, role, expires_at, created_at, used_by, used_at
+            FROM invite_codes
+            ORDER BY created_at DESC
+            "#
+        )
+        .fetch_all(&self.db)
+        .await?;
+        Ok(invites)
+    }
+
+    /// Removes an unused invite. Returns whether one was removed.
+    pub async fn revoke(&self, code: &str) -> anyhow::Result<bool> {
+        let result = db::query("DELETE FROM invite_codes WHERE code = ? AND used_by IS NULL")
+            .bind(code)
+            .execute(&self.db)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Creates the account and uses up the invite in one transaction, so a
+    /// code can't be redeemed twice.
+    pub async fn redeem(&self, registration: &Registration) -> Result<NewUser, RegisterError> {
+        let username = registration.username.trim();
+        if username.is_empty() || username.len() > MAX_USERNAME_LENGTH {
+            return Err(RegisterError::Invalid(format!(
+                "Username must be 1 to {} characters",
+                MAX_USERNAME_LENGTH
+            )));
+        }
+        if registration.password.len() < MIN_PASSWORD_LENGTH {
+            return Err(RegisterError::Invalid(format!(
+                "Password must be at least {} characters",
+                MIN_PASSWORD_LENGTH
+            )));
+        }
+
+        let mut tx = self.db.begin().await?;
+        let role: Option<String> = db::query_scalar(
+            r#"
+            SELECT role FROM invite_codes
+            WHERE code = ? AND used_by IS NULL
+                AND (expires_at IS NULL OR expires_at > ?)
+            "#
+        )
+        .bind(registration.invite_code.trim())
+        .bind(Utc::now().timestamp())
+        .fetch_optional(&mut tx)
+        .await?;
+        let role = role
+            .as_deref()
+            .and_then(Role::parse)
+            .ok_or(RegisterError::InvalidInvite)?;
+
+        let taken: bool = db::query_scalar("SELECT EXISTS(SELECT 1 FROM users WHERE username = ?)")
+            .bind(username)
+            .fetch_one(&mut tx)
+            .await?;
+        if taken {
+            return Err(RegisterError::UsernameTaken);
+        }
+
+        let password_hash = hash(&registration.password, DEFAULT_COST).map_err(anyhow::Error::from)?;
+        let user_id: i64 = db::query_scalar(
+            "INSERT INTO users (username, password_hash, is_admin, role) VALUES (?, ?, ?, ?) RETURNING id"
+        )
+        .bind(username)
+        .bind(&password_hash)
+        .bind(role == Role::Admin)
+        .bind(role.as_str())
+        .fetch_one(&mut tx)
+        .await?;
+
+        let claimed = db::query(
+            "UPDATE invite_codes SET used_by = ?, used_at = datetime('now') WHERE code = ? AND used_by IS NULL"
+        )
+        .bind(user_id)
+        .bind(registration.invite_code.trim())
+        .execute(&mut tx)
+        .await?;
+        if claimed.rows_affected() != 1 {
+            return Err(RegisterError::InvalidInvite);
+        }
+        tx.commit().await?;
+
+        info!("Registered user {} with an invite", username);
+        Ok(NewUser {
+            id: user_id,
+            username: username.to_string(),
+            role,
+        })
+    }
+}
+
+impl From<RegisterError> for AppError {
+    fn from(err: RegisterError) -> Self {
+        match err {
+            RegisterError::Other(e) => e.into(),
+            e => AppError::BadRequest(e.to_string()),
+        }
+    }
+}
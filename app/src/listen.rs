@@ -0,0 +1,134 @@
+use std::io::ErrorKind;
+use std::net::SocketAddr;
+use std::path::Path;
+use std::process::Command;
+
+use serde::{Deserialize, Serialize};
+use tokio::net::TcpListener;
+use tracing::{info, warn};
+
+pub const DEFAULT_PORT_FILE: &str = "./ruststream.port";
+
+/// Written once the server is listening so the desktop wrapper and CLI can
+/// find the port actually in use rather than the one they asked for.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PortFile {
+    pub port: u16,
+    pub pid: u32,
+    pub url: String,
+}
+
+/// Binds `127.0.0.1:port`, moving on to the next of up to `retries` ports
+/// while the address is taken. Returns the listener and the port it got.
+pub async fn bind_with_retry(port: u16, retries: u16) -> anyhow::Result<(TcpListener, u16)> {
+    let last = port.saturating_add(retries);
+    for candidate in port..=last {
+        let addr = SocketAddr::from(([127, 0, 0, 1], candidate));
+        match TcpListener::bind(addr).await {
+            Ok(listener) => {
+                if candidate != port {
+                    warn!("Port {} was busy; listening on {} instead", port, candidate);
+                }
+                return Ok((listener, candidate));
+            }
+            Err(e) if e.kind() == ErrorKind::AddrInUse => {
+                warn!("Port {} is already in use", candidate);
+                log_port_owner(candidate);
+            }
+            Err(e) => {
+                return Err(anyhow::anyhow!("Could not bind {}: {}", addr, e));
+            }
+        }
+    }
+
+    if retries == 0 {
+        Err(anyhow::anyhow!(
+            "Port {} is already in use. Stop the other process, change PORT, or set PORT_RETRIES to try the following ports",
+            port
+        ))
+    } else {
+        Err(anyhow::anyhow!("Ports {}-{} are all in use", port, last))
+    }
+}
+
+/// Best-effort lookup of the process listening on `port`, using whichever
+/// system tool is available. Failures are ignored.
+fn log_port_owner(port: u16) {
+    let output = if cfg!(windows) {
+        Command::new("netstat").args(["-ano", "-p", "TCP"]).output()
+    } else if cfg!(target_os = "linux") {
+        Command::new("ss")
+            .args(["-H", "-ltnp", &format!("sport = :{}", port)])
+            .output()
+    } else {
+        Command::new("lsof")
+            .args(["-nP", &format!("-iTCP:{}", port), "-sTCP:LISTEN"])
+            .output()
+    };
+
+    let Ok(output) = output else {
+        return;
+    };
+    let needle = format!(":{} ", port);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    for line in stdout
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .filter(|line| !cfg!(windows) || (line.contains(&needle) && line.contains("LISTENING")))
+    {
+        warn!("Port {} held by: {}", port, line);
+    }
+}
+
+pub fn write_port_file(path: &Path, port: u16) -> anyhow::Result<()> {
+    let contents = PortFile {
+        port,
+        pid: std::process::id(),
+        url: format!("http://127.0.0.1:{}", port),
+    };
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(path, serde_json::to_string_pretty(&contents)?)?;
+    info!("Wrote port file {}", path.display());
+    Ok(())
+}
+
+pub fn read_port_file(path: &Path) -> anyhow::Result<PortFile> {
+    let contents = std::fs::read_to_string(path)
+        .map_err(|e| anyhow::anyhow!("Could not read {}: {}", path.display(), e))?;
+    Ok(serde_json::from_str(&contents)?)
+}
+
+/// Removes the port file on shutdown, but only if it still describes this process.
+pub fn remove_port_file(path: &Path) {
+    if read_port_file(path).is_ok_and(|file| file.pid == std::process::id()) {
+        std::fs::remove_file(path).ok();
+    }
+}
+
+/// Resolves on Ctrl+C, or on SIGTERM where that exists, so the port file is
+/// cleaned up when the desktop wrapper or a service manager stops the server.
+pub async fn shutdown_signal() {
+    #[cfg(unix)]
+    {
+        let mut terminate =
+            match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+                Ok(signal) => signal,
+                Err(_) => {
+                    tokio::signal::ctrl_c().await.ok();
+                    return;
+                }
+            };
+        tokio::select! {
+            _ = tokio::signal::ctrl_c() => {}
+            _ = terminate.recv() => {}
+        }
+    }
+
+    #[cfg(not(unix))]
+    {
+        tokio::signal::ctrl_c().await.ok();
+    }
+}
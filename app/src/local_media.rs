@@ -0,0 +1,174 @@
+use std::path::PathBuf;
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::db::{self, Db};
+use crate::providers::{EmbedOptions, StreamKind, StreamProvider, StreamSource};
+
+/// Route prefix the files are served from, followed by the mapping id.
+pub const MEDIA_PATH: &str = "/media/local";
+
+/// Maps a title or episode to a file under `MEDIA_DIR`. Movies use season
+/// and episode 0.
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct LocalMedia {
+    #[serde(default)]
+    pub id: i64,
+    pub tmdb_id: i64,
+    pub media_type: String,
+    #[serde(default)]
+    pub season_number: i64,
+    #[serde(default)]
+    pub episode_number: i64,
+    /// Relative to the media directory.
+    pub path: String,
+}
+
+/// Plays the user's own files from `MEDIA_DIR`, matched to titles through
+/// the `local_media` table.
+#[derive(Debug)]
+pub struct LocalMediaProvider {
+    db: Db,
+    dir: PathBuf,
+}
+
+impl LocalMediaProvider {
+    pub fn new(db: Db, dir: &str) -> anyhow::Result<Self> {
+        let dir = PathBuf::from(dir)
+            .canonicalize()
+            .map_err(|e| anyhow::anyhow!("MEDIA_DIR {}: {}", dir, e))?;
+        info!("Serving local media from {}", dir.display());
+        Ok(Self { db, dir })
+    }
+
+    /// The file a mapping points at, or `None` when it is missing or
+    /// resolves outside the media directory.
+    pub fn resolve(&self, path: &str) -> Option<PathBuf> {
+        let full = self.dir.join(path).canonicalize().ok()?;
+        (full.starts_with(&self.dir) && full.is_file()).then_some(full)
+    }
+
+    pub async fn get(&self, id: i64) -> anyhow::Result<Option<LocalMedia>> {
+        let entry = db::query_as(
+            r#"
+            SELECT id, tmdb_id, media_type, season_number, episode_number, path
+            FROM local_media WHERE id = ?
+            "#
+        )
+        .bind(id)
+        .fetch_optional(&self.db)
+        .await?;
+        Ok(entry)
+    }
+
+    async fn find(&self, tmdb_id: i64, media_type: &str, season: i64, episode: i64) -> anyhow::Result<Option<LocalMedia>> {
+        let entry = db::query_as(
+            r#"
+            SELECT id, tmdb_id, media_type, season_number, episode_number, path
+            FROM local_media
+            WHERE tmdb_id = ? AND media_type = ? AND season_number = ? AND episode_number = ?
+            "#
+        )
+        .bind(tmdb_id)
+        .bind(media_type)
+        .bind(season)
+        .bind(episode)
+        .fetch_optional(&self.db)
+        .await?;
+        Ok(entry)
+    }
+
+    pub async fn list(&self) -> anyhow::Result<Vec<LocalMedia>> {
+        let entries = db::query_as(
+            r#"
+            SELECT id, tmdb_id, media_type, season_number, episode_number, path
+            FROM local_media
+            ORDER BY tmdb_id, season_number, episode_number
+            "#
+        )
+        .fetch_all(&self.db)
+        .await?;
+        Ok(entries)
+    }
+
+    /// Maps a file, replacing any earlier file for the same title or episode.
+    pub async fn set(&self, entry: &LocalMedia) -> anyhow::Result<LocalMedia> {
+        let saved = db::query_as(
+            r#"
+            INSERT INTO local_media (tmdb_id, media_type, season_number, episode_number, path)
+            VALUES (?, ?, ?, ?, ?)
+            ON CONFLICT(tmdb_id, media_type, season_number, episode_number) DO UPDATE SET
+                path = excluded.path
+            RETURNING id, tmdb_id, media_type, season_number, episode_number, path
+            "#
+        )
+        .bind(entry.tmdb_id)
+        .bind(&entry.media_type)
+        .bind(entry.season_number)
+        .bind(entry.episode_number)
+        .bind(&entry.path)
+        .fetch_one(&self.db)
+        .await?;
+        Ok(saved)
+    }
+
+    /// Returns whether a mapping was removed.
+    pub async fn remove(&self, id: i64) -> anyhow::Result<bool> {
+        let result = db::query("DELETE FROM local_media WHERE id = ?")
+            .bind(id)
+            .execute(&self.db)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn streams(&self, tmdb_id: i64, media_type: &str, season: i64, episode: i64) -> anyhow::Result<Vec<StreamSource>> {
+        let Some(entry) = self.find(tmdb_id, media_type, season, episode).await? else {
+            return Ok(vec![]);
+        };
+        if self.resolve(&entry.path).is_none() {
+            return Err(anyhow::anyhow!("local file {} is missing", entry.path));
+        }
+
+        Ok(vec![StreamSource {
+            id: format!("{}/{}", MEDIA_PATH, entry.id),
+            name: "Local file".to_string(),
+            quality: Some("Original".to_string()),
+            language: None,
+            server: self.name().to_string(),
+            kind: StreamKind::File,
+        }])
+    }
+}
+
+#[async_trait]
+impl StreamProvider for LocalMediaProvider {
+    fn name(&self) -> &str {
+        "local"
+    }
+
+    /// The user's own copy beats any embed.
+    fn priority(&self) -> i32 {
+        200
+    }
+
+    /// A lookup is one indexed query, and mappings change without notice.
+    fn cache_ttl(&self) -> Option<std::time::Duration> {
+        None
+    }
+
+    async fn get_movie_streams(&self, tmdb_id: i64, _options: &EmbedOptions) -> anyhow::Result<Vec<StreamSource>> {
+        self.streams(tmdb_id, "movie", 0, 0).await
+    }
+
+    async fn get_tv_streams(
+        &self,
+        tmdb_id: i64,
+        season: i64,
+        episode: i64,
+        _options: &EmbedOptions,
+    ) -> anyhow::Result<Vec<StreamSource>> {
+        self.streams(tmdb_id, "tv", season, episode).await
+    }
+}
@@ -0,0 +1,140 @@
+use chrono::Utc;
+use serde::Deserialize;
+use tracing::warn;
+
+use crate::db::{self, Db};
+use crate::error::AppError;
+
+/// Failed sign-ins for one username before it is locked out.
+const MAX_USERNAME_FAILURES: i64 = 5;
+/// Failed sign-ins from one IP, across all usernames, before it is locked out.
+const MAX_IP_FAILURES: i64 = 20;
+/// How far back failures count. A lockout ends once enough of them are older
+/// than this; a successful sign-in clears the count for that username and IP.
+const FAILURE_WINDOW_SECS: i64 = 15 * 60;
+
+#[derive(Debug, Deserialize)]
+pub struct Credentials {
+    pub username: String,
+    pub password: String,
+    /// Keep the session for a week instead of until the browser closes.
+    #[serde(default)]
+    pub remember: bool,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum LoginError {
+    #[error("Incorrect username or password")]
+    InvalidCredentials,
+
+    #[error("Too many failed sign-ins. Try again in {} minute(s)", (.retry_after_secs + 59) / 60)]
+    LockedOut { retry_after_secs: i64 },
+
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+impl From<LoginError> for AppError {
+    fn from(err: LoginError) -> Self {
+        match err {
+            LoginError::Other(e) => e.into(),
+            e @ LoginError::LockedOut { .. } => AppError::TooManyRequests(e.to_string()),
+            e => AppError::BadRequest(e.to_string()),
+        }
+    }
+}
+
+/// What happened to a sign-in attempt, as stored in `login_attempts`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    Success,
+    Failure,
+    /// Turned away without checking the password. Doesn't extend the lockout.
+    LockedOut,
+}
+
+impl Outcome {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Success => "success",
+            Self::Failure => "failure",
+            Self::LockedOut => "locked_out",
+        }
+    }
+}
+
+/// Per-username and per-IP sign-in throttling, counted from the
+/// `login_attempts` table. Every attempt is kept there as an audit trail.
+#[derive(Debug, Clone)]
+pub struct LoginThrottle {
+    db: Db,
+}
+
+impl LoginThrottle {
+    pub fn new(db: Db) -> Self {
+        Self { db }
+    }
+
+    /// Seconds until `username` or `ip` may try again, or `None` if neither
+    /// is locked out.
+    pub async fn retry_after(&self, username: &str, ip: Option<&str>) -> anyhow::Result<Option<i64>> {
+        let by_username = self.locked_for("username", username, MAX_USERNAME_FAILURES).await?;
+        let by_ip = match ip {
+            Some(ip) => self.locked_for("ip", ip, MAX_IP_FAILURES).await?,
+            None => None,
+        };
+        Ok(by_username.max(by_ip))
+    }
+
+    /// How long `column = value` stays locked out, if it is.
+    async fn locked_for(&self, column: &str, value: &str, max_failures: i64) -> anyhow::Result<Option<i64>> {
+        let now = Utc::now().timestamp();
+        let last_success: Option<i64> = db::query_scalar(&format!(
+            "SELECT MAX(attempted_at) FROM login_attempts WHERE {column} = ? AND outcome = 'success'"
+        ))
+        .bind(value)
+        .fetch_one(&self.db)
+        .await?;
+        let since = last_success.unwrap_or(0).max(now - FAILURE_WINDOW_SECS);
+
+        // Failures after `since`, oldest first. The lockout lasts until all but
+        // `max_failures - 1` of them have left the window.
+        let failures: Vec<i64> = db::query_scalar(&format!(
+            r#"
+            SELECT attempted_at FROM login_attempts
+            WHERE {column} = ? AND outcome = 'failure' AND attempted_at > ?
+            ORDER BY attempted_at
+            "#
+        ))
+        .bind(value)
+        .bind(since)
+        .fetch_all(&self.db)
+        .await?;
+
+        let count = failures.len() as i64;
+        if count < max_failures {
+            return Ok(None);
+        }
+        let expiring = failures[(count - max_failures) as usize];
+        Ok(Some((expiring + FAILURE_WINDOW_SECS - now).max(1)))
+    }
+
+    pub async fn record(&self, username: &str, ip: Option<&str>, outcome: Outcome) -> anyhow::Result<()> {
+        if outcome != Outcome::Success {
+            warn!(
+                "Failed sign-in for {} from {} ({})",
+                username,
+                ip.unwrap_or("unknown IP"),
+                outcome.as_str()
+            );
+        }
+        db::query("INSERT INTO login_attempts (username, ip, outcome, attempted_at) VALUES (?, ?, ?, ?)")
+            .bind(username)
+            .bind(ip)
+            .bind(outcome.as_str())
+            .bind(Utc::now().timestamp())
+            .execute(&self.db)
+            .await?;
+        Ok(())
+    }
+}
@@ -1,40 +1,110 @@
 use axum::{
-    extract::{Path, Query, State},
-    http,
-    response::Html,
+    extract::{OriginalUri, Path, Query, State},
+    http, middleware,
+    response::{Html, IntoResponse, Redirect, Response},
     routing::{get, post},
-    Json, Router,
+    Form, Json, Router,
 };
 use http::HeaderMap;
 use serde::Deserialize;
-use sqlx::Pool;
+use std::collections::{HashMap, HashSet};
 use std::net::SocketAddr;
 use std::sync::Arc;
-use tower_http::services::ServeDir;
+use tokio::task::JoinSet;
+use tower::Service;
+use tower_http::services::{ServeDir, ServeFile};
 use tracing::info;
 
+mod account;
 mod api;
 mod auth;
+mod changelog;
+mod cleanup;
 mod config;
+mod csv;
+mod custom_providers;
 mod db;
 mod error;
-mod models;
+mod history_import;
+mod hls;
+mod http_cache;
+mod images;
+mod invites;
+mod instance;
+mod listen;
+mod local_media;
+mod login;
+mod metadata;
+mod metrics;
+mod notifications;
+mod omdb;
+mod openapi;
+mod people;
 mod tmdb;
+mod tmdb_account;
+mod tmdb_cache;
+mod tmdb_flight;
+mod tmdb_keys;
 mod vidking;
+mod vidsrc;
 mod templates;
 mod onboarding;
+mod outbound;
+mod parental;
+mod playback;
+mod playlists;
+mod prefetch;
+mod provider_health;
+mod provider_settings;
+mod provider_stats;
+mod providers;
+mod search_history;
+mod stats;
+mod stream_cache;
+mod stream_overrides;
+mod stream_reports;
+mod reconcile;
+mod request_log;
+mod webhooks;
 
-use crate::auth::{AuthManager, Session, SessionStore};
+use crate::auth::{AuthManager, ClientIp, CurrentUser, ImageQuality, MaybeSession, Permission, PlayerPreferences, RequireSession, Role, Session, SessionStore, UserPreferences};
 use crate::config::Config;
 use crate::error::AppError;
 
 #[derive(Clone)]
 pub struct AppState {
-    pub db: Pool<sqlx::Sqlite>,
+    pub db: db::Db,
     pub tmdb: tmdb::TmdbClient,
-    pub vidking: Arc<vidking::VidkingClient>,
+    pub providers: Arc<providers::ProviderRegistry>,
     pub auth: Arc<AuthManager>,
     pub sessions: Arc<SessionStore>,
+    pub prefetch: Arc<prefetch::StreamPrefetchCache>,
+    pub metrics: Arc<metrics::AppMetrics>,
+    pub images: Arc<images::ImageCache>,
+    pub playback: Arc<playback::PlaybackMonitor>,
+    pub omdb: Option<Arc<omdb::OmdbClient>>,
+    pub hls: Arc<hls::HlsProxy>,
+    pub local_media: Option<Arc<local_media::LocalMediaProvider>>,
+    pub invites: Arc<invites::Invites>,
+    pub roles: Arc<auth::RolePermissions>,
+    pub logins: Arc<login::LoginThrottle>,
+    pub parental: Arc<parental::ParentalControls>,
+    pub accounts: Arc<account::Accounts>,
+    pub playlists: Arc<playlists::Playlists>,
+    pub stats: Arc<stats::WatchStats>,
+    pub search_history: Arc<search_history::SearchHistory>,
+    pub admin_allowlist: Arc<auth::AdminAllowlist>,
+    pub trusted_proxies: Arc<auth::TrustedProxies>,
+    pub webhooks: Arc<webhooks::Webhooks>,
+    pub notifications: Arc<notifications::Notifications>,
+    pub imports: Arc<history_import::ImportJobs>,
+    /// Signs and seals with `SESSION_SECRET`.
+    pub secrets: auth::SessionSecrets,
+    /// Lets scrapers read the metrics without an account, from `METRICS_TOKEN`.
+    pub metrics_token: Option<Arc<str>>,
+    /// Where browsers reach the app: `PUBLIC_URL`, or the local address once
+    /// the port is bound.
+    pub public_url: Arc<std::sync::OnceLock<String>>,
 }
 
 #[tokio::main]
@@ -43,6 +113,11 @@ async fn main() -> anyhow::Result<()> {
         .with_env_filter("info")
         .init();
 
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if let Some(command) = args.first() {
+        return run_command(command, &args[1..]).await;
+    }
+
     info!("Starting streaming app...");
 
     onboarding::maybe_run_onboarding()?;
@@ -50,61 +125,366 @@ async fn main() -> anyhow::Result<()> {
     let config = Config::new()?;
     info!("Configuration loaded");
 
-    let db_pool = db::init_db(&config.database_url).await?;
+    let db_pool = db::init_db(&config.database_url, config.database_max_connections).await?;
     info!("Database initialized");
 
-    let auth_manager = AuthManager::new(db_pool.clone());
-    auth_manager.init_local_user().await?;
+    let app_metrics = Arc::new(metrics::AppMetrics::new());
+
+    let auth_manager = AuthManager::new(db_pool.clone(), config.auth_mode);
+    auth_manager.init_local_user(config.local_user_admin).await?;
     
-    let session_store = SessionStore::new(db_pool.clone());
+    let secrets = auth::SessionSecrets::load(
+        &db_pool,
+        config.session_secret.as_deref(),
+        config.session_secret_previous.as_deref(),
+    )
+    .await?;
+    let session_store = SessionStore::new(
+        db_pool.clone(),
+        secrets.clone(),
+        config.max_sessions_per_user,
+        app_metrics.clone(),
+    );
 
-    let tmdb_client = tmdb::TmdbClient::new(&config.tmdb_api_key)?;
+    let tmdb_client = tmdb::TmdbClient::new(
+        &config.tmdb_api_key,
+        config.tmdb_language.as_deref(),
+        &config.tmdb_region,
+        app_metrics.clone(),
+    )?;
+    tmdb_client.load_image_configuration().await;
     info!("TMDB client initialized");
 
-    let vidking_client = vidking::VidkingClient::new()?;
-    info!("Vidking client initialized");
+    let image_cache = images::ImageCache::new(&config.image_cache_dir, app_metrics.clone())?;
+    info!("Image cache at {}", config.image_cache_dir);
+
+    let parental_controls = parental::ParentalControls::new(secrets.clone());
+    let hls_proxy = Arc::new(hls::HlsProxy::new(secrets.clone())?);
+    let mut available: Vec<Arc<dyn providers::StreamProvider>> = vec![
+        Arc::new(vidking::VidkingClient::new()?),
+        Arc::new(vidsrc::VidsrcClient::new()?),
+    ];
+    let local_media = match config.media_dir.as_deref() {
+        Some(dir) => {
+            let provider = Arc::new(local_media::LocalMediaProvider::new(db_pool.clone(), dir)?);
+            available.push(provider.clone());
+            Some(provider)
+        }
+        None => None,
+    };
+    available.extend(custom_providers::load(
+        std::path::Path::new(&config.providers_file),
+        hls_proxy.clone(),
+    )?);
+    let stream_providers = providers::ProviderRegistry::new(
+        available,
+        config.stream_providers.as_deref(),
+        stream_cache::StreamCache::new(db_pool.clone()),
+        stream_reports::StreamReports::new(db_pool.clone()),
+        stream_overrides::StreamOverrides::new(db_pool.clone()),
+        provider_stats::ProviderStats::new(db_pool.clone()),
+        provider_settings::ProviderSettings::new(db_pool.clone()),
+    );
+    stream_providers.load_settings().await?;
+
+    let omdb_client = match config.omdb_api_key.as_deref() {
+        Some(key) => {
+            info!("OMDb ratings enabled");
+            Some(Arc::new(omdb::OmdbClient::new(key, app_metrics.clone())?))
+        }
+        None => None,
+    };
 
+    let invites = invites::Invites::new(db_pool.clone());
+    let roles = auth::RolePermissions::load(db_pool.clone()).await?;
+    let logins = login::LoginThrottle::new(db_pool.clone());
+    let accounts = account::Accounts::new(db_pool.clone());
+    let playlists = playlists::Playlists::new(db_pool.clone());
+    let stats = stats::WatchStats::new(db_pool.clone());
+    let search_history = search_history::SearchHistory::new(db_pool.clone());
+    let admin_allowlist = auth::AdminAllowlist::parse(config.admin_allowed_ips.as_deref())?;
+    let trusted_proxies = auth::TrustedProxies::parse(config.trusted_proxies.as_deref())?;
+    let webhooks = webhooks::Webhooks::new(db_pool.clone(), secrets.clone())?;
+    let notifications = notifications::Notifications::load(db_pool.clone()).await?;
     let state = AppState {
         db: db_pool,
         tmdb: tmdb_client,
-        vidking: Arc::new(vidking_client),
+        providers: Arc::new(stream_providers),
         auth: Arc::new(auth_manager),
         sessions: Arc::new(session_store),
+        prefetch: Arc::new(prefetch::StreamPrefetchCache::new()),
+        metrics: app_metrics,
+        images: Arc::new(image_cache),
+        playback: Arc::new(playback::PlaybackMonitor::new()),
+        omdb: omdb_client,
+        hls: hls_proxy,
+        local_media,
+        invites: Arc::new(invites),
+        roles: Arc::new(roles),
+        logins: Arc::new(logins),
+        parental: Arc::new(parental_controls),
+        accounts: Arc::new(accounts),
+        playlists: Arc::new(playlists),
+        stats: Arc::new(stats),
+        search_history: Arc::new(search_history),
+        admin_allowlist: Arc::new(admin_allowlist),
+        trusted_proxies: Arc::new(trusted_proxies),
+        webhooks: Arc::new(webhooks),
+        notifications: Arc::new(notifications),
+        imports: Arc::new(history_import::ImportJobs::new()),
+        secrets,
+        metrics_token: config.metrics_token.as_deref().map(Arc::from),
+        public_url: Arc::new(std::sync::OnceLock::new()),
     };
+    if let Some(url) = config.public_url.clone() {
+        let _ = state.public_url.set(url);
+    }
+    if let Err(e) = tmdb_account::seal_stored_tokens(&state).await {
+        tracing::warn!("Could not seal stored TMDB credentials: {}", e);
+    }
+    if let Err(e) = state.webhooks.seal_stored_secrets().await {
+        tracing::warn!("Could not seal stored webhook secrets: {}", e);
+    }
+
+    reconcile::spawn_reconcile_job(state.clone());
+    tmdb_account::spawn_account_sync_job(state.clone());
+    metadata::spawn_refresh_job(state.clone());
+    stats::spawn_rollup_job(state.clone());
+    provider_health::spawn_health_check_job(state.clone());
+    webhooks::spawn_delivery_job(state.clone());
+    notifications::spawn_new_episode_job(state.clone());
+    cleanup::spawn_cleanup_job(
+        state.clone(),
+        cleanup::CleanupPolicy {
+            interval: config
+                .cleanup_interval_minutes
+                .checked_mul(60)
+                .map(std::time::Duration::from_secs)
+                .ok_or_else(|| anyhow::anyhow!("CLEANUP_INTERVAL_MINUTES is too large"))?,
+            history_retention_days: config.history_retention_days,
+            history_max_rows: config.history_max_rows,
+        },
+    );
+
+    let api_routes = api::routes(state.clone())
+        .route("/progress", post(api_update_progress))
+        .route("/playback/heartbeat", post(api_playback_heartbeat))
+        .route("/streams/report", post(api_report_stream))
+        .route("/player/quality", post(api_set_max_quality));
 
     let app = Router::new()
         .route("/", get(home_page))
         .route("/search", get(search_page))
+        .route("/search/history", post(search_history_action))
         .route("/history", get(watch_history_page))
+        .route("/stats", get(stats_page))
+        .route("/watchlist", get(watchlist_page))
+        .route("/watchlist/:media_type/:id", post(watchlist_action))
+        .route("/playlists", get(playlists_page).post(create_playlist))
+        .route("/playlists/add-title", post(add_title_to_playlist))
+        .route("/playlists/:id", get(playlist_page).post(playlist_action))
+        .route("/playlists/:id/items", post(playlist_item_action))
+        .route("/shared/playlists/:token", get(shared_playlist_page))
         .route("/movie/:id", get(movie_detail_page))
         .route("/tv/:id", get(tv_detail_page))
+        .route("/keyword/:id", get(keyword_page))
+        .route("/anime", get(anime_page))
+        .route("/network/:id", get(network_page))
+        .route("/company/:id", get(company_page))
         .route("/player/:media_type/:id", get(player_page))
-        .route("/api/progress", post(api_update_progress))
-        .nest("/api", api::routes(state.clone()))
+        .route("/settings", get(settings_page).post(save_settings))
+        .route("/settings/player", post(save_player_settings))
+        .route("/settings/parental", post(save_parental_settings))
+        .route("/parental/unlock", post(parental_unlock))
+        .route("/whats-new/dismiss", post(dismiss_whats_new))
+        .route("/settings/tmdb", post(tmdb_account_action))
+        .route("/settings/tmdb/list", post(import_tmdb_list))
+        .route("/settings/tmdb/connect", get(tmdb_connect))
+        .route("/settings/tmdb/callback", get(tmdb_callback))
+        .route("/register", get(register_page).post(register_submit))
+        .route("/login", get(login_page).post(login_submit))
+        .route("/settings/sessions", get(sessions_page))
+        .route("/settings/sessions/revoke-all", post(revoke_all_sessions))
+        .route("/settings/sessions/:id/revoke", post(revoke_session))
+        .route("/settings/account/delete", get(delete_account_page).post(delete_account_submit))
+        .merge(
+            Router::new()
+                .route("/admin/invites", get(admin_invites_page).post(create_invite))
+                .route("/admin/invites/:code/revoke", post(revoke_invite))
+                .route_layer(middleware::from_fn_with_state(state.clone(), auth::require_manage_users)),
+        )
+        .merge(
+            Router::new()
+                .route("/admin/providers", get(admin_providers_page))
+                .route("/admin/providers/:name", post(save_provider_config))
+                .route("/admin/providers/:name/reset", post(reset_provider_config))
+                .route_layer(middleware::from_fn_with_state(state.clone(), auth::require_manage_settings)),
+        )
+        .route("/favicon.ico", get(favicon_ico))
+        .route("/favicon.svg", get(favicon_svg))
+        .route("/apple-touch-icon.png", get(apple_touch_icon))
+        .route("/metrics", get(metrics_endpoint))
+        .route("/img/:size/*path", get(proxy_image))
+        .route(hls::PROXY_PATH, get(stream_proxy))
+        .route(&format!("{}/:id", local_media::MEDIA_PATH), get(serve_local_media))
+        .nest(
+            api::V1_PREFIX,
+            api_routes
+                .clone()
+                .layer(middleware::from_fn(api::v1_envelope))
+                .layer(middleware::from_fn_with_state(state.clone(), http_cache::cache_headers)),
+        )
+        .nest(
+            "/api",
+            api_routes.layer(middleware::from_fn_with_state(state.clone(), http_cache::cache_headers)),
+        )
         .nest_service("/static", ServeDir::new("app/static"))
-        .with_state(state);
+        .layer(middleware::from_fn_with_state(state.clone(), auth::require_sign_in))
+        .layer(middleware::from_fn_with_state(state.clone(), auth::restrict_admin_network))
+        .layer(middleware::from_fn(request_log::request_log))
+        .with_state(state.clone());
+
+    let (listener, port) = listen::bind_with_retry(config.port, config.port_retries).await?;
+    info!("Server running on http://127.0.0.1:{}", port);
+    let _ = state.public_url.set(format!("http://127.0.0.1:{}", port));
 
-    let addr: SocketAddr = format!("127.0.0.1:{}", config.port).parse()?;
-    info!("Server running on http://{}", addr);
+    let port_file = std::path::PathBuf::from(&config.port_file);
+    if let Err(e) = listen::write_port_file(&port_file, port) {
+        tracing::warn!("Could not write port file {}: {}", port_file.display(), e);
+    }
 
-    let listener = tokio::net::TcpListener::bind(&addr).await?;
-    axum::serve(listener, app).await?;
+    let served = axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>())
+        .with_graceful_shutdown(async move {
+            listen::shutdown_signal().await;
+            state.notifications.close();
+        })
+        .await;
+    listen::remove_port_file(&port_file);
+    served?;
 
     Ok(())
 }
 
-async fn get_session(state: &AppState, _headers: &HeaderMap) -> Option<Session> {
-    state.auth.get_local_session().await.ok()
+async fn run_command(command: &str, args: &[String]) -> anyhow::Result<()> {
+    match command {
+        "export-instance" => {
+            let output = args
+                .first()
+                .map(String::as_str)
+                .unwrap_or("ruststream-instance.tar.gz");
+            let config = Config::new()?;
+            instance::export_instance(&config, std::path::Path::new(output)).await
+        }
+        "import-instance" => {
+            let archive = args
+                .iter()
+                .find(|a| !a.starts_with("--"))
+                .ok_or_else(|| anyhow::anyhow!("Usage: ruststream import-instance <archive> [--force]"))?;
+            let force = args.iter().any(|a| a == "--force");
+
+            dotenvy::dotenv().ok();
+            let database_url = std::env::var("DATABASE_URL")
+                .unwrap_or_else(|_| "sqlite://./streaming.db".to_string());
+            instance::import_instance(std::path::Path::new(archive), &database_url, force).await
+        }
+        "reconcile-titles" => {
+            let full = args.iter().any(|a| a == "--full");
+            let config = Config::new()?;
+            let db_pool = db::init_db(&config.database_url, config.database_max_connections).await?;
+            let tmdb_client = tmdb::TmdbClient::new(
+                &config.tmdb_api_key,
+                config.tmdb_language.as_deref(),
+                &config.tmdb_region,
+                Arc::new(metrics::AppMetrics::new()),
+            )?;
+            let report = reconcile::reconcile_titles(&db_pool, &tmdb_client, full).await?;
+            info!(
+                "Checked {} titles: {} remapped, {} orphaned",
+                report.checked, report.remapped, report.orphaned
+            );
+            Ok(())
+        }
+        "port" => {
+            dotenvy::dotenv().ok();
+            let port_file = std::env::var("PORT_FILE")
+                .unwrap_or_else(|_| listen::DEFAULT_PORT_FILE.to_string());
+            let file = listen::read_port_file(std::path::Path::new(&port_file))?;
+            println!("{}", file.port);
+            Ok(())
+        }
+        other => Err(anyhow::anyhow!(
+            "Unknown command '{}'. Available commands: export-instance [output], import-instance <archive> [--force], reconcile-titles [--full], port",
+            other
+        )),
+    }
+}
+
+async fn get_viewer<'a>(state: &AppState, session: Option<&'a Session>) -> templates::Viewer<'a> {
+    let prefs = match session {
+        Some(s) => state.auth.get_preferences(s.user_id).await.unwrap_or_default(),
+        None => UserPreferences::default(),
+    };
+
+    let role = session.map(|s| s.role).unwrap_or(Role::Guest);
+    templates::Viewer {
+        user_id: session.map(|s| s.user_id),
+        username: session.map(|s| s.username.as_str()),
+        role,
+        permissions: state.roles.permissions(role),
+        prefs,
+    }
 }
 
-async fn home_page(State(state): State<AppState>, headers: HeaderMap) -> Result<Html<String>, AppError> {
-    let session = get_session(&state, &headers).await;
-    let username = session.as_ref().map(|s| s.username.as_str());
-    let trending = state.tmdb.get_trending("movie", "week").await?;
+/// Titles shown in the home page's Continue Watching row.
+pub(crate) const CONTINUE_WATCHING_LIMIT: i64 = 20;
+
+async fn home_page(State(state): State<AppState>, MaybeSession(session): MaybeSession) -> Result<Html<String>, AppError> {
+    let viewer = get_viewer(&state, session.as_ref()).await;
+    let trending = state.tmdb.get_trending("movie", "week", 1).await?;
     let popular_tv = state.tmdb.get_popular_tv(1).await?;
+    let now_playing = state.tmdb.get_now_playing(1).await.map(|r| r.results).unwrap_or_default();
+    let upcoming = state.tmdb.get_upcoming(1).await.map(|r| r.results).unwrap_or_default();
+    let top_rated = tmdb::interleave_results(
+        state.tmdb.get_top_rated_movies(1).await.map(|r| r.results).unwrap_or_default(),
+        state.tmdb.get_top_rated_tv(1).await.map(|r| r.results).unwrap_or_default(),
+    );
     let trending_searches = state.tmdb.get_trending_searches().await;
+    let people = state.tmdb.get_trending_people("week", 1).await.map(|r| r.results).unwrap_or_default();
+    let continue_watching = match session {
+        Some(ref s) => state
+            .auth
+            .get_continue_watching(s.user_id, CONTINUE_WATCHING_LIMIT)
+            .await
+            .unwrap_or_else(|e| {
+                tracing::warn!("Could not load continue watching: {}", e);
+                vec![]
+            }),
+        None => vec![],
+    };
+    let whats_new = match session {
+        Some(ref s) => {
+            let last_seen = state
+                .auth
+                .get_setting::<String>(s.user_id, changelog::LAST_SEEN_VERSION_KEY)
+                .await
+                .unwrap_or_default();
+            changelog::unseen_releases(last_seen.as_deref())
+        }
+        None => vec![],
+    };
     
-    let html = templates::render_home(username, &trending.results, &popular_tv.results, &trending_searches);
+    let html = templates::render_home(
+        &viewer,
+        &whats_new,
+        &continue_watching,
+        &trending.results,
+        &popular_tv.results,
+        &now_playing,
+        &upcoming,
+        &top_rated,
+        &people,
+        &trending_searches,
+    );
     Ok(Html(html))
 }
 
@@ -112,33 +492,130 @@ async fn home_page(State(state): State<AppState>, headers: HeaderMap) -> Result<
 #[derive(Deserialize)]
 struct SearchQuery {
     q: Option<String>,
+    #[serde(rename = "type")]
+    media_type: Option<String>,
     genre: Option<String>,
     year: Option<i32>,
     min_rating: Option<f64>,
     sort_by: Option<String>,
+    cursor: Option<String>,
+    /// For `actor:` and `director:` queries, list the person's full credits
+    /// instead of discover results.
+    filmography: Option<bool>,
+}
+
+impl SearchQuery {
+    fn discover_filters(&self) -> tmdb::DiscoverFilters {
+        tmdb::DiscoverFilters {
+            q: self.q.clone().unwrap_or_default(),
+            media_type: self.media_type.clone().filter(|t| !t.is_empty()),
+            genre: self.genre.clone(),
+            year: self.year,
+            min_rating: self.min_rating,
+            sort_by: self.sort_by.clone().filter(|s| !s.is_empty()),
+            network: None,
+            company: None,
+            person: None,
+        }
+    }
+
+    /// Link to the same person query, with or without the full filmography.
+    fn person_url(&self, filmography: bool) -> String {
+        let mut params = vec![("q", self.q.as_deref().unwrap_or_default())];
+        if filmography {
+            params.push(("filmography", "true"));
+        }
+        let url = reqwest::Url::parse_with_params("http://localhost/search", params)
+            .expect("static base url");
+        format!("/search?{}", url.query().unwrap_or_default())
+    }
+
+    /// Link to the next page of filtered results, keeping the current filters.
+    fn next_page_url(&self, cursor: &str) -> String {
+        let year = self.year.map(|y| y.to_string());
+        let min_rating = self.min_rating.map(|r| r.to_string());
+        let params = [
+            ("q", self.q.as_deref()),
+            ("type", self.media_type.as_deref()),
+            ("genre", self.genre.as_deref()),
+            ("year", year.as_deref()),
+            ("min_rating", min_rating.as_deref()),
+            ("sort_by", self.sort_by.as_deref()),
+            ("cursor", Some(cursor)),
+        ];
+        let url = reqwest::Url::parse_with_params(
+            "http://localhost/search",
+            params.iter().filter_map(|(k, v)| v.map(|v| (*k, v))),
+        )
+        .expect("static base url");
+        format!("/search?{}", url.query().unwrap_or_default())
+    }
 }
 
 async fn search_page(
     State(state): State<AppState>,
-    headers: HeaderMap,
+    MaybeSession(session): MaybeSession,
+    restriction: parental::Restriction,
     Query(params): Query<SearchQuery>,
-) -> Result<Html<String>, AppError> {
-    let session = get_session(&state, &headers).await;
-    let username = session.as_ref().map(|s| s.username.as_str());
+) -> Result<Response, AppError> {
+    let viewer = get_viewer(&state, session.as_ref()).await;
     let query = params.q.clone().unwrap_or_default();
+
+    let recent_searches = match session {
+        Some(ref s) if query.trim().is_empty() => state
+            .search_history
+            .recent(s.user_id, search_history::RECENT_LIMIT)
+            .await
+            .unwrap_or_else(|e| {
+                tracing::warn!("Could not load search history: {}", e);
+                vec![]
+            }),
+        // Only the first page counts; following "more results" isn't a new search.
+        Some(ref s) if !viewer.prefs.pause_history && params.cursor.is_none() => {
+            if let Err(e) = state.search_history.record(s.user_id, &query).await {
+                tracing::warn!("Could not record search: {}", e);
+            }
+            vec![]
+        }
+        _ => vec![],
+    };
+
+    if let Some(imdb_id) = tmdb::parse_imdb_id(&query) {
+        let found = state.tmdb.find_by_external_id(&imdb_id).await?;
+        if let Some(title) = found.first_title() {
+            return Ok(Redirect::to(&format!("/{}/{}", title.media_type, title.id)).into_response());
+        }
+    }
     
-    let has_filters = params.genre.is_some() || params.year.is_some() || params.min_rating.is_some();
+    let has_filters = tmdb::is_filter_query(&query)
+        || params.media_type.is_some()
+        || params.genre.is_some() || params.year.is_some() || params.min_rating.is_some();
     
+    let mut next_page = None;
+    let mut mode_link = None;
     let results = if has_filters {
-        state.tmdb.search_advanced(
-            &query,
-            None,
-            params.year,
-            params.genre.as_deref(),
-            params.min_rating,
-            &params.sort_by.unwrap_or_else(|| "popularity.desc".to_string()),
-            1,
-        ).await?.results
+        let mut filters = params.discover_filters();
+        let person = people::resolve_filters(&state.db, &state.tmdb, &mut filters)
+            .await?
+            .filter(|(_, id)| *id > 0);
+        match person {
+            Some((role, id)) if params.filmography.unwrap_or(false) => {
+                mode_link = Some((params.person_url(false), "Discover results"));
+                state
+                    .tmdb
+                    .get_filmography(id, role == people::PersonRole::Director)
+                    .await?
+            }
+            person => {
+                if person.is_some() {
+                    mode_link = Some((params.person_url(true), "Full filmography"));
+                }
+                let cursor = params.cursor.as_deref().and_then(tmdb::DiscoverCursor::parse);
+                let page = state.tmdb.discover_combined(&filters, cursor).await?;
+                next_page = page.next_cursor.map(|c| params.next_page_url(&c));
+                page.results
+            }
+        }
     } else if query.len() >= 2 {
         let mut results = state.tmdb.search(&query, 1).await?.results;
         results.retain(|r| r.media_type != "person");
@@ -146,28 +623,54 @@ async fn search_page(
     } else {
         vec![]
     };
+    let results = restriction.filter_results(&state.tmdb, results).await;
     
     let genres = state.tmdb.get_genres().await?;
-    let html = templates::render_search(username, &query, &results, &genres);
-    Ok(Html(html))
+    let html = templates::render_search(
+        &viewer,
+        &query,
+        &results,
+        &genres,
+        &recent_searches,
+        next_page.as_deref(),
+        mode_link.as_ref().map(|(url, label)| (url.as_str(), *label)),
+    );
+    Ok(Html(html).into_response())
 }
 
 async fn watch_history_page(
     State(state): State<AppState>,
-    headers: HeaderMap,
+    MaybeSession(session): MaybeSession,
 ) -> Result<Html<String>, AppError> {
-    let session = get_session(&state, &headers).await;
-    let username = session.as_ref().map(|s| s.username.as_str());
+    let viewer = get_viewer(&state, session.as_ref()).await;
     
     let history = match session {
         Some(ref s) => state.auth.get_watch_history(s.user_id).await?,
         None => vec![],
     };
     
-    let html = templates::render_watch_history(username, &history);
+    let html = templates::render_watch_history(&viewer, &history);
     Ok(Html(html))
 }
 
+#[derive(Deserialize)]
+struct StatsQuery {
+    /// Only the last this many days; all time when unset.
+    #[serde(default)]
+    days: Option<u32>,
+}
+
+async fn stats_page(
+    State(state): State<AppState>,
+    RequireSession(session): RequireSession,
+    Query(params): Query<StatsQuery>,
+) -> Result<Html<String>, AppError> {
+    let viewer = get_viewer(&state, Some(&session)).await;
+    let days = params.days.filter(|d| *d > 0);
+    let summary = state.stats.summary(session.user_id, days).await?;
+    Ok(Html(templates::render_stats(&viewer, &summary)))
+}
+
 #[derive(Deserialize)]
 struct ProgressRequest {
     tmdb_id: i64,
@@ -185,60 +688,687 @@ struct ProgressRequest {
 
 async fn api_update_progress(
     State(state): State<AppState>,
-    headers: HeaderMap,
+    MaybeSession(session): MaybeSession,
     Json(data): Json<ProgressRequest>,
 ) -> Result<Json<()>, AppError> {
-    let session = get_session(&state, &headers).await;
     
     if let Some(s) = session {
-        state.auth.add_to_watch_history(
-            s.user_id,
-            data.tmdb_id,
-            &data.media_type,
-            &data.title,
-            data.poster_path.as_deref(),
-            data.season,
-            data.episode,
-            data.episode_title.as_deref(),
-        ).await?;
+        let prefs = state.auth.get_preferences(s.user_id).await.unwrap_or_default();
+        if !prefs.pause_history {
+            let episode_title = match (data.episode_title.as_deref(), data.season, data.episode) {
+                (Some(title), _, _) if !title.is_empty() => Some(title.to_string()),
+                (_, Some(season), Some(episode)) if data.media_type == "tv" => {
+                    state.tmdb.get_episode_title(data.tmdb_id, season, episode).await
+                }
+                _ => None,
+            };
+
+            state.auth.add_to_watch_history(
+                s.user_id,
+                data.tmdb_id,
+                &data.media_type,
+                &data.title,
+                data.poster_path.as_deref(),
+                data.season,
+                data.episode,
+                episode_title.as_deref(),
+            ).await?;
         
-        state.auth.update_watch_progress(
-            s.user_id,
-            data.tmdb_id,
-            &data.media_type,
-            data.current_time as i64,
-            data.completed,
-            data.season,
-            data.episode,
-        ).await?;
+            let newly_completed = state.auth.update_watch_progress(
+                s.user_id,
+                data.tmdb_id,
+                &data.media_type,
+                data.current_time as i64,
+                data.completed,
+                data.season,
+                data.episode,
+            ).await?;
+
+            if newly_completed {
+                state
+                    .webhooks
+                    .emit(
+                        webhooks::WebhookEvent::PlaybackCompleted,
+                        serde_json::json!({
+                            "user": webhooks::user_json(&s),
+                            "tmdb_id": data.tmdb_id,
+                            "media_type": data.media_type,
+                            "title": data.title,
+                            "season": data.season,
+                            "episode": data.episode,
+                            "episode_title": episode_title,
+                        }),
+                    )
+                    .await;
+            }
+        }
+
+        let watched_pct = if data.duration > 0.0 {
+            data.current_time / data.duration * 100.0
+        } else {
+            data.progress
+        };
+
+        if data.media_type == "tv" && !data.completed && watched_pct >= prefetch::PREFETCH_THRESHOLD {
+            if let (Some(season), Some(episode)) = (data.season, data.episode) {
+                let player = state.auth.get_player_preferences(s.user_id).await.unwrap_or_default();
+                let options = providers::EmbedOptions {
+                    viewer: Some(s.user_id),
+                    ..embed_options(&prefs, &player)
+                };
+                let watching = prefetch::PrefetchKey::new(data.tmdb_id, season, episode, &options);
+                if state.prefetch.start(watching) {
+                    let state = state.clone();
+                    tokio::spawn(async move {
+                        prefetch::prefetch_next_episode(&state, data.tmdb_id, season, episode, &options).await;
+                    });
+                }
+            }
+        }
     }
     
     Ok(Json(()))
 }
 
+async fn api_report_stream(
+    State(state): State<AppState>,
+    RequireSession(session): RequireSession,
+    ClientIp(ip): ClientIp,
+    Json(report): Json<stream_reports::StreamReport>,
+) -> Result<Json<()>, AppError> {
+    if report.media_type != "movie" && report.media_type != "tv" {
+        return Err(AppError::invalid("invalid_media_type", "media_type must be movie or tv"));
+    }
+    if state.providers.get(&report.provider).is_none() {
+        return Err(AppError::invalid("unknown_provider", format!("Unknown stream provider \"{}\"", report.provider)));
+    }
+
+    let ip = ip.to_string();
+    let reports = state.providers.reports();
+    if reports.rate_limited(session.user_id, Some(&ip)).await? {
+        return Err(AppError::TooManyRequests("Too many stream reports. Try again later".to_string()));
+    }
+    // The cached URL is what the viewer just saw fail, so resolve it afresh
+    // next time; a viewer reporting the same stream again doesn't repeat that.
+    if reports.record(session.user_id, Some(&ip), &report).await? {
+        state
+            .providers
+            .cache()
+            .remove(
+                &report.provider,
+                report.tmdb_id,
+                &report.media_type,
+                report.season.unwrap_or(0),
+                report.episode.unwrap_or(0),
+            )
+            .await?;
+    }
+
+    Ok(Json(()))
+}
+
+/// Player state from a signed-in viewer. Each user may have a few playbacks
+/// going at once, heartbeats closer than a few seconds are ignored, and only
+/// the first QoS events of a playback are stored.
+async fn api_playback_heartbeat(
+    State(state): State<AppState>,
+    RequireSession(s): RequireSession,
+    Json(heartbeat): Json<playback::Heartbeat>,
+) -> Result<Json<playback::HeartbeatOutcome>, AppError> {
+    if heartbeat.media_type != "movie" && heartbeat.media_type != "tv" {
+        return Err(AppError::invalid("invalid_media_type", "media_type must be movie or tv"));
+    }
+    if heartbeat.playback_id.is_empty() || heartbeat.playback_id.len() > playback::MAX_PLAYBACK_ID_LEN {
+        return Err(AppError::invalid("invalid_playback_id", "playback_id must be 1 to 64 characters"));
+    }
+    let (events, outcome) = state
+        .playback
+        .observe(s.user_id, &heartbeat)
+        .ok_or_else(|| AppError::TooManyRequests("Too many playbacks at once".to_string()))?;
+
+    for kind in events {
+        state.metrics.record_qos_event();
+        playback::record_qos_event(&state.db, s.user_id, &heartbeat, kind).await?;
+    }
+
+    // Only known providers get a row, whatever the player sends.
+    if let Some(provider) = heartbeat.provider.as_deref().filter(|name| state.providers.get(name).is_some()) {
+        let stats = state.providers.stats();
+        if outcome.new_playback {
+            stats.record_playback(provider, false).await?;
+        }
+        if outcome.first_trouble {
+            stats.record_playback(provider, true).await?;
+        }
+    }
+
+    // Viewers who paused their history aren't announced either.
+    if outcome.started {
+        let prefs = state.auth.get_preferences(s.user_id).await.unwrap_or_default();
+        if !prefs.pause_history {
+            state
+                .webhooks
+                .emit(
+                    webhooks::WebhookEvent::PlaybackStarted,
+                    serde_json::json!({
+                        "user": webhooks::user_json(&s),
+                        "tmdb_id": heartbeat.tmdb_id,
+                        "media_type": heartbeat.media_type,
+                        "season": heartbeat.season,
+                        "episode": heartbeat.episode,
+                        "provider": heartbeat.provider,
+                    }),
+                )
+                .await;
+        }
+    }
+
+    Ok(Json(outcome))
+}
+
+fn embed_options(prefs: &UserPreferences, player: &PlayerPreferences) -> providers::EmbedOptions {
+    providers::EmbedOptions {
+        color: Some(
+            player
+                .accent_color
+                .clone()
+                .unwrap_or_else(|| templates::ACCENT_COLOR.to_string()),
+        ),
+        auto_play: prefs.autoplay() && player.autoplay,
+        next_episode: player.next_episode,
+        episode_selector: player.episode_selector,
+        language: player.audio_language.clone(),
+        // Data saver caps quality, below the viewer's own cap when that is higher.
+        max_quality: match (player.max_quality, data_saver_quality_cap(prefs)) {
+            (Some(quality), Some(cap)) => Some(quality.min(cap)),
+            (quality, cap) => quality.or(cap),
+        },
+        ..Default::default()
+    }
+}
+
+const DATA_SAVER_MAX_QUALITY: u32 = 480;
+
+fn data_saver_quality_cap(prefs: &UserPreferences) -> Option<u32> {
+    prefs.data_saver.then_some(DATA_SAVER_MAX_QUALITY)
+}
+
 async fn movie_detail_page(
     State(state): State<AppState>,
-    headers: HeaderMap,
+    MaybeSession(session): MaybeSession,
+    restriction: parental::Restriction,
+    Path(id): Path<i64>,
+) -> Result<Response, AppError> {
+    let viewer = get_viewer(&state, session.as_ref()).await;
+    let mut movie = state.tmdb.get_movie(id).await?;
+    if !restriction.allows(movie.certification.as_ref()) {
+        return Ok(restricted_page(&state, &viewer, &movie.title, movie.certification.as_ref(), &format!("/movie/{}", id)).await);
+    }
+    if let Some(omdb) = &state.omdb {
+        omdb.enrich(&mut movie).await;
+    }
+    if let Err(e) = metadata::store_movie(&state.db, &movie).await {
+        tracing::warn!("Could not store movie {}: {}", id, e);
+    }
+    let providers = state
+        .tmdb
+        .get_watch_providers("movie", id, state.tmdb.region())
+        .await
+        .ok()
+        .flatten();
+    let saved = saved_lists(&state, session.as_ref(), "movie", id).await;
+    let html = templates::render_movie_detail(&viewer, &movie, providers.as_ref(), saved.as_ref());
+    Ok(Html(html).into_response())
+}
+
+async fn tv_detail_page(
+    State(state): State<AppState>,
+    MaybeSession(session): MaybeSession,
+    restriction: parental::Restriction,
+    Path(id): Path<i64>,
+) -> Result<Response, AppError> {
+    let viewer = get_viewer(&state, session.as_ref()).await;
+    let show = state.tmdb.get_tv_show(id).await?;
+    if !restriction.allows(show.certification.as_ref()) {
+        return Ok(restricted_page(&state, &viewer, &show.name, show.certification.as_ref(), &format!("/tv/{}", id)).await);
+    }
+    if let Err(e) = metadata::store_tv_show(&state.db, &show).await {
+        tracing::warn!("Could not store show {}: {}", id, e);
+    }
+    let providers = state
+        .tmdb
+        .get_watch_providers("tv", id, state.tmdb.region())
+        .await
+        .ok()
+        .flatten();
+    let saved = saved_lists(&state, session.as_ref(), "tv", id).await;
+    let progress = show_progress(&state, session.as_ref(), &show).await;
+    let html = templates::render_tv_detail(&viewer, &show, providers.as_ref(), saved.as_ref(), progress.as_ref());
+    Ok(Html(html).into_response())
+}
+
+/// Which episodes of the show the viewer has watched, with the episode lists
+/// of the seasons they've started. `None` without a session.
+async fn show_progress(state: &AppState, session: Option<&Session>, show: &tmdb::TvShowDetail) -> Option<templates::ShowProgress> {
+    let session = session?;
+    let episodes = match state.auth.get_watched_episodes(session.user_id, show.id).await {
+        Ok(episodes) => episodes,
+        Err(e) => {
+            tracing::warn!("Could not load watched episodes of show {}: {}", show.id, e);
+            return None;
+        }
+    };
+
+    let started: HashSet<i64> = episodes.iter().map(|e| e.season_number).collect();
+    let mut lookups = JoinSet::new();
+    for season_number in started {
+        let (tmdb, show_id) = (state.tmdb.clone(), show.id);
+        lookups.spawn(async move { (season_number, tmdb.get_season(show_id, season_number).await) });
+    }
+    let mut seasons = HashMap::new();
+    while let Some(lookup) = lookups.join_next().await {
+        match lookup {
+            Ok((season_number, Ok(Some(season)))) => {
+                seasons.insert(season_number, season);
+            }
+            Ok((_, Ok(None))) => {}
+            Ok((season_number, Err(e))) => {
+                tracing::warn!("Could not load season {} of show {}: {}", season_number, show.id, e);
+            }
+            Err(e) => tracing::warn!("Season lookup failed: {}", e),
+        }
+    }
+    Some(templates::ShowProgress { episodes, seasons })
+}
+
+/// The viewer's watchlist and playlists, for the buttons on a title's page.
+/// `None` without a session, which hides them.
+async fn saved_lists(state: &AppState, session: Option<&Session>, media_type: &str, id: i64) -> Option<templates::SavedLists> {
+    let session = session?;
+    let lists = async {
+        anyhow::Ok(templates::SavedLists {
+            on_watchlist: state.auth.is_on_watchlist(session.user_id, id, media_type).await?,
+            playlists: state.playlists.list(session.user_id).await?,
+        })
+    };
+    match lists.await {
+        Ok(lists) => Some(lists),
+        Err(e) => {
+            tracing::warn!("Could not load the lists of user {}: {}", session.user_id, e);
+            None
+        }
+    }
+}
+
+async fn watchlist_page(
+    State(state): State<AppState>,
+    RequireSession(session): RequireSession,
+) -> Result<Html<String>, AppError> {
+    let viewer = get_viewer(&state, Some(&session)).await;
+    let items = state.auth.get_watchlist(session.user_id).await?;
+    Ok(Html(templates::render_watchlist(&viewer, &items)))
+}
+
+#[derive(Deserialize)]
+struct WatchlistForm {
+    action: String,
+    #[serde(default)]
+    title: String,
+    #[serde(default)]
+    poster_path: String,
+    /// Set by the watchlist page; otherwise back to the title's page.
+    #[serde(default)]
+    from_watchlist: Option<String>,
+}
+
+async fn watchlist_action(
+    State(state): State<AppState>,
+    RequireSession(session): RequireSession,
+    Path((media_type, id)): Path<(String, i64)>,
+    Form(form): Form<WatchlistForm>,
+) -> Result<Redirect, AppError> {
+    let media_type = list_media_type(&media_type)?;
+    match form.action.as_str() {
+        "add" => {
+            let title = form.title.trim();
+            if title.is_empty() {
+                return Err(AppError::BadRequest("Title is required".to_string()));
+            }
+            let poster_path = Some(form.poster_path.as_str()).filter(|p| !p.is_empty());
+            add_to_watchlist(&state, &session, media_type, id, title, poster_path).await?;
+        }
+        "remove" => {
+            remove_from_watchlist(&state, session.user_id, media_type, id).await?;
+        }
+        _ => return Err(AppError::BadRequest("Unknown watchlist action".to_string())),
+    }
+
+    Ok(match form.from_watchlist {
+        Some(_) => Redirect::to("/watchlist"),
+        None => Redirect::to(&format!("/{}/{}", media_type, id)),
+    })
+}
+
+#[derive(Deserialize)]
+struct SearchHistoryForm {
+    action: String,
+    #[serde(default)]
+    id: Option<i64>,
+}
+
+async fn search_history_action(
+    State(state): State<AppState>,
+    RequireSession(session): RequireSession,
+    Form(form): Form<SearchHistoryForm>,
+) -> Result<Redirect, AppError> {
+    match (form.action.as_str(), form.id) {
+        ("delete", Some(id)) => {
+            state.search_history.delete(session.user_id, id).await?;
+        }
+        ("clear", _) => {
+            state.search_history.clear(session.user_id).await?;
+        }
+        _ => return Err(AppError::BadRequest("Unknown search history action".to_string())),
+    }
+    Ok(Redirect::to("/search"))
+}
+
+/// Only movies and shows can go on the watchlist or a playlist.
+pub(crate) fn list_media_type(media_type: &str) -> Result<&'static str, AppError> {
+    match media_type {
+        "movie" => Ok("movie"),
+        "tv" => Ok("tv"),
+        _ => Err(AppError::invalid("invalid_media_type", "media_type must be movie or tv")),
+    }
+}
+
+/// Adds a title to the watchlist and tells webhooks about it. Returns
+/// `false` if it was already on the list.
+pub(crate) async fn add_to_watchlist(
+    state: &AppState,
+    session: &Session,
+    media_type: &str,
+    id: i64,
+    title: &str,
+    poster_path: Option<&str>,
+) -> Result<bool, AppError> {
+    let added = state
+        .auth
+        .add_to_watchlist(session.user_id, id, media_type, title, poster_path)
+        .await?;
+    if added {
+        state
+            .webhooks
+            .emit(
+                webhooks::WebhookEvent::WatchlistAdded,
+                serde_json::json!({
+                    "user": webhooks::user_json(session),
+                    "tmdb_id": id,
+                    "media_type": media_type,
+                    "title": title,
+                    "poster_path": poster_path,
+                }),
+            )
+            .await;
+    }
+    Ok(added)
+}
+
+/// Removes a title from the watchlist, and from the connected TMDB account's
+/// if it came from there. Returns whether it was on the list.
+pub(crate) async fn remove_from_watchlist(
+    state: &AppState,
+    user_id: i64,
+    media_type: &str,
+    id: i64,
+) -> Result<bool, AppError> {
+    let Some(synced) = state.auth.remove_from_watchlist(user_id, id, media_type).await? else {
+        return Ok(false);
+    };
+    if synced {
+        if let Err(e) = tmdb_account::push_watchlist_removal(state, user_id, media_type, id).await {
+            tracing::warn!("Could not remove {} {} from the TMDB watchlist: {}", media_type, id, e);
+        }
+    }
+    Ok(true)
+}
+
+async fn playlists_page(
+    State(state): State<AppState>,
+    RequireSession(session): RequireSession,
+) -> Result<Html<String>, AppError> {
+    let viewer = get_viewer(&state, Some(&session)).await;
+    let playlists = state.playlists.list(session.user_id).await?;
+    Ok(Html(templates::render_playlists(&viewer, &playlists, None)))
+}
+
+#[derive(Deserialize)]
+struct PlaylistForm {
+    #[serde(default)]
+    action: String,
+    #[serde(default)]
+    name: String,
+}
+
+async fn create_playlist(
+    State(state): State<AppState>,
+    RequireSession(session): RequireSession,
+    Form(form): Form<PlaylistForm>,
+) -> Result<Response, AppError> {
+    match state.playlists.create(session.user_id, &form.name).await {
+        Ok(playlist) => Ok(Redirect::to(&format!("/playlists/{}", playlist.id)).into_response()),
+        Err(playlists::PlaylistError::Invalid(message)) => {
+            let viewer = get_viewer(&state, Some(&session)).await;
+            let playlists = state.playlists.list(session.user_id).await?;
+            Ok(Html(templates::render_playlists(&viewer, &playlists, Some(&message))).into_response())
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+async fn playlist_page(
+    State(state): State<AppState>,
+    RequireSession(session): RequireSession,
+    Path(id): Path<i64>,
+) -> Result<Html<String>, AppError> {
+    let viewer = get_viewer(&state, Some(&session)).await;
+    let playlist = state.playlists.get_with_items(session.user_id, id).await?;
+    Ok(Html(templates::render_playlist(&viewer, &playlist, true)))
+}
+
+/// Renames, shares, stops sharing, or deletes a playlist.
+async fn playlist_action(
+    State(state): State<AppState>,
+    RequireSession(session): RequireSession,
+    Path(id): Path<i64>,
+    Form(form): Form<PlaylistForm>,
+) -> Result<Redirect, AppError> {
+    let playlists = &state.playlists;
+    match form.action.as_str() {
+        "rename" => playlists.rename(session.user_id, id, &form.name).await?,
+        "share" => {
+            playlists.set_shared(session.user_id, id, true).await?;
+        }
+        "unshare" => {
+            playlists.set_shared(session.user_id, id, false).await?;
+        }
+        "delete" => {
+            playlists.delete(session.user_id, id).await?;
+            return Ok(Redirect::to("/playlists"));
+        }
+        _ => return Err(AppError::BadRequest("Unknown playlist action".to_string())),
+    }
+    Ok(Redirect::to(&format!("/playlists/{}", id)))
+}
+
+#[derive(Deserialize)]
+struct PlaylistItemForm {
+    action: String,
+    tmdb_id: i64,
+    media_type: String,
+}
+
+/// Removes a title from a playlist or moves it up or down.
+async fn playlist_item_action(
+    State(state): State<AppState>,
+    RequireSession(session): RequireSession,
+    Path(id): Path<i64>,
+    Form(form): Form<PlaylistItemForm>,
+) -> Result<Redirect, AppError> {
+    let title = playlists::TitleRef {
+        tmdb_id: form.tmdb_id,
+        media_type: list_media_type(&form.media_type)?.to_string(),
+    };
+    let playlists = &state.playlists;
+    match form.action.as_str() {
+        "remove" => {
+            playlists.remove_item(session.user_id, id, &title).await?;
+        }
+        "up" => playlists.move_item(session.user_id, id, &title, -1).await?,
+        "down" => playlists.move_item(session.user_id, id, &title, 1).await?,
+        _ => return Err(AppError::BadRequest("Unknown playlist action".to_string())),
+    }
+    Ok(Redirect::to(&format!("/playlists/{}", id)))
+}
+
+#[derive(Deserialize)]
+struct AddTitleForm {
+    /// Empty creates a playlist named `new_playlist`.
+    #[serde(default)]
+    playlist_id: String,
+    #[serde(default)]
+    new_playlist: String,
+    tmdb_id: i64,
+    media_type: String,
+    title: String,
+    #[serde(default)]
+    poster_path: String,
+}
+
+/// The "Add to playlist" form on movie and TV pages.
+async fn add_title_to_playlist(
+    State(state): State<AppState>,
+    RequireSession(session): RequireSession,
+    Form(form): Form<AddTitleForm>,
+) -> Result<Redirect, AppError> {
+    let media_type = list_media_type(&form.media_type)?;
+    let title = form.title.trim();
+    if title.is_empty() {
+        return Err(AppError::BadRequest("Title is required".to_string()));
+    }
+    let playlist_id = match form.playlist_id.trim() {
+        "" => state.playlists.create(session.user_id, &form.new_playlist).await?.id,
+        id => id
+            .parse()
+            .map_err(|_| AppError::BadRequest("Unknown playlist".to_string()))?,
+    };
+    let poster_path = Some(form.poster_path.as_str()).filter(|p| !p.is_empty());
+    let title_ref = playlists::TitleRef {
+        tmdb_id: form.tmdb_id,
+        media_type: media_type.to_string(),
+    };
+    state
+        .playlists
+        .add_item(session.user_id, playlist_id, &title_ref, title, poster_path)
+        .await?;
+    Ok(Redirect::to(&format!("/{}/{}", media_type, form.tmdb_id)))
+}
+
+/// Read-only view of a shared playlist. Needs no session.
+async fn shared_playlist_page(
+    State(state): State<AppState>,
+    MaybeSession(session): MaybeSession,
+    Path(token): Path<String>,
+) -> Result<Html<String>, AppError> {
+    let viewer = get_viewer(&state, session.as_ref()).await;
+    let playlist = state.playlists.get_shared(&token).await?;
+    Ok(Html(templates::render_playlist(&viewer, &playlist, false)))
+}
+
+#[derive(Deserialize)]
+struct BrowseQuery {
+    #[serde(default)]
+    page: Option<i32>,
+}
+
+async fn keyword_page(
+    State(state): State<AppState>,
+    MaybeSession(session): MaybeSession,
     Path(id): Path<i64>,
+    Query(params): Query<BrowseQuery>,
 ) -> Result<Html<String>, AppError> {
-    let session = get_session(&state, &headers).await;
-    let username = session.as_ref().map(|s| s.username.as_str());
-    let movie = state.tmdb.get_movie(id).await?;
-    let poster_path = movie.poster_path.as_deref();
-    let html = templates::render_movie_detail(username, &movie);
+    let viewer = get_viewer(&state, session.as_ref()).await;
+    let page = params.page.unwrap_or(1).max(1);
+    let keyword = state.tmdb.get_keyword(id).await?;
+    let results = state.tmdb.discover_by_keyword(id, page).await?;
+    let html = templates::render_browse(
+        &viewer,
+        &format!("Keyword: {}", keyword.name),
+        &format!("/keyword/{}", id),
+        &results,
+    );
     Ok(Html(html))
 }
 
-async fn tv_detail_page(
+async fn anime_page(
     State(state): State<AppState>,
-    headers: HeaderMap,
+    MaybeSession(session): MaybeSession,
+    Query(params): Query<BrowseQuery>,
+) -> Result<Html<String>, AppError> {
+    let viewer = get_viewer(&state, session.as_ref()).await;
+    let page = params.page.unwrap_or(1).max(1);
+    let results = state.tmdb.discover_anime(page).await?;
+    let html = templates::render_browse(&viewer, "Anime", "/anime", &results);
+    Ok(Html(html))
+}
+
+async fn network_page(
+    State(state): State<AppState>,
+    MaybeSession(session): MaybeSession,
     Path(id): Path<i64>,
+    Query(params): Query<BrowseQuery>,
 ) -> Result<Html<String>, AppError> {
-    let session = get_session(&state, &headers).await;
-    let username = session.as_ref().map(|s| s.username.as_str());
-    let show = state.tmdb.get_tv_show(id).await?;
-    let poster_path = show.poster_path.as_deref();
-    let html = templates::render_tv_detail(username, &show);
+    let viewer = get_viewer(&state, session.as_ref()).await;
+    let page = params.page.unwrap_or(1).max(1);
+    let network = state.tmdb.get_network(id).await?;
+    let results = state.tmdb.discover_by_network(id, page).await?;
+    let html = templates::render_browse(
+        &viewer,
+        &format!("All {} shows", network.name),
+        &format!("/network/{}", id),
+        &results,
+    );
+    Ok(Html(html))
+}
+
+#[derive(Deserialize)]
+struct CompanyQuery {
+    #[serde(default)]
+    page: Option<i32>,
+    #[serde(default, rename = "type")]
+    media_type: Option<String>,
+}
+
+async fn company_page(
+    State(state): State<AppState>,
+    MaybeSession(session): MaybeSession,
+    Path(id): Path<i64>,
+    Query(params): Query<CompanyQuery>,
+) -> Result<Html<String>, AppError> {
+    let viewer = get_viewer(&state, session.as_ref()).await;
+    let page = params.page.unwrap_or(1).max(1);
+    let (media_type, label) = match params.media_type.as_deref() {
+        Some("tv") => ("tv", "shows"),
+        _ => ("movie", "movies"),
+    };
+    let company = state.tmdb.get_company(id).await?;
+    let results = state.tmdb.discover_by_company(id, media_type, page).await?;
+    let html = templates::render_browse(
+        &viewer,
+        &format!("{} {}", company.name, label),
+        &format!("/company/{}?type={}", id, media_type),
+        &results,
+    );
     Ok(Html(html))
 }
 
@@ -248,34 +1378,852 @@ struct PlayerQuery {
     season: Option<i64>,
     #[serde(default)]
     episode: Option<i64>,
+    #[serde(default)]
+    retry: Option<u8>,
+    /// `native` for the self-hosted HLS player.
+    #[serde(default)]
+    player: Option<String>,
 }
 
 async fn player_page(
     State(state): State<AppState>,
-    headers: HeaderMap,
+    MaybeSession(session): MaybeSession,
+    restriction: parental::Restriction,
     Path((media_type, id)): Path<(String, i64)>,
     Query(params): Query<PlayerQuery>,
-) -> Result<Html<String>, AppError> {
-    let session = get_session(&state, &headers).await;
+    OriginalUri(uri): OriginalUri,
+) -> Result<Response, AppError> {
     let is_admin = false;
-    let username = session.as_ref().map(|s| s.username.as_str());
+    let viewer = get_viewer(&state, session.as_ref()).await;
     
-    let (title, poster_path) = if media_type == "movie" {
+    let (title, poster_path, absolute_episode, certification) = if media_type == "movie" {
         let movie = state.tmdb.get_movie(id).await?;
-        (movie.title, movie.poster_path)
+        (movie.title, movie.poster_path, None, movie.certification)
     } else {
         let show = state.tmdb.get_tv_show(id).await?;
-        (show.name, show.poster_path)
+        let absolute_episode = match (params.season, params.episode) {
+            (Some(season), Some(episode)) => show.provider_episode_number(season, episode),
+            _ => None,
+        };
+        (show.name, show.poster_path, absolute_episode, show.certification)
     };
+    if !restriction.allows(certification.as_ref()) {
+        return Ok(restricted_page(&state, &viewer, &title, certification.as_ref(), &uri.to_string()).await);
+    }
+
+    let player_prefs = match session {
+        Some(ref s) => state.auth.get_player_preferences(s.user_id).await.unwrap_or_default(),
+        None => PlayerPreferences::default(),
+    };
+    let mut options = embed_options(&viewer.prefs, &player_prefs);
+    options.absolute_episode = absolute_episode;
+    options.viewer = session.as_ref().map(|s| s.user_id);
+    if let Some(ref s) = session {
+        options.progress = state
+            .auth
+            .get_resume_position(s.user_id, id, &media_type, params.season, params.episode)
+            .await
+            .unwrap_or_default();
+    }
 
-    let streams = if media_type == "movie" {
-        state.vidking.get_movie_streams(id).await?
+    let resolution = if media_type == "movie" {
+        state.providers.get_movie_streams(id, &options).await
     } else {
-        let season = params.season.ok_or_else(|| AppError::BadRequest("Season required".to_string()))?;
-        let episode = params.episode.ok_or_else(|| AppError::BadRequest("Episode required".to_string()))?;
-        state.vidking.get_tv_streams(id, season, episode).await?
+        let season = params.season.ok_or_else(|| AppError::invalid("season_required", "Season required"))?;
+        let episode = params.episode.ok_or_else(|| AppError::invalid("episode_required", "Episode required"))?;
+        let key = prefetch::PrefetchKey::new(id, season, episode, &options);
+        let prefetched = state.prefetch.get(&key);
+        state.metrics.record_cache("prefetch", prefetched.is_some());
+        match prefetched {
+            Some(streams) => providers::StreamResolution::new(streams, Vec::new()),
+            None => state.providers.get_tv_streams(id, season, episode, &options).await,
+        }
     };
+    let stream_status = resolution.status;
+    let mut streams = resolution.streams;
+    // The iframe player can only open embeds; HLS and local files need the
+    // native player, which is where a local file starts out.
+    let native = match params.player.as_deref() {
+        Some(mode) => mode == "native",
+        None => streams.first().is_some_and(|s| s.kind == providers::StreamKind::File),
+    };
+    let plays = |s: &providers::StreamSource| (s.kind != providers::StreamKind::Embed) == native;
+    let other_mode_available = streams.iter().any(|s| !plays(s));
+    streams.retain(plays);
+    if let Some(language) = options.language.as_deref() {
+        providers::prefer_language(&mut streams, language);
+    }
+    state.metrics.record_stream_launch();
     
-    let html = templates::render_player(username, &title, &media_type, id, poster_path.as_deref(), &streams, is_admin);
+    let embed_reachable = match streams.first() {
+        Some(stream) => state.providers.is_reachable(&stream.server, params.retry.is_some()).await,
+        None => true,
+    };
+    let providers = if embed_reachable {
+        None
+    } else {
+        state
+            .tmdb
+            .get_watch_providers(&media_type, id, state.tmdb.region())
+            .await
+            .ok()
+            .flatten()
+    };
+
+    let html = templates::render_player(
+        &viewer,
+        &templates::PlayerView {
+            title: &title,
+            media_type: &media_type,
+            id,
+            season: params.season,
+            episode: params.episode,
+            poster_path: poster_path.as_deref(),
+            streams: &streams,
+            stream_status,
+            is_admin,
+            embed_reachable,
+            providers: providers.as_ref(),
+            native,
+            other_mode_available,
+            resume_at: options.progress,
+            autoplay: options.auto_play,
+            max_quality: options.max_quality,
+            quality_limit: data_saver_quality_cap(&viewer.prefs),
+        },
+    );
+    Ok(Html(html).into_response())
+}
+
+async fn settings_page(
+    State(state): State<AppState>,
+    user: CurrentUser,
+) -> Result<Html<String>, AppError> {
+    let session = user.require(Permission::EditPreferences)?;
+    let viewer = get_viewer(&state, Some(session)).await;
+    let player = state.auth.get_player_preferences(session.user_id).await?;
+    let tmdb_account = tmdb_account::get_account(&state, session.user_id).await?;
+    let parental = parental::ParentalSettings::load(&state.auth, session.user_id).await?;
+    let html = templates::render_settings(&viewer, &player, tmdb_account.as_ref(), &parental);
     Ok(Html(html))
 }
+
+/// The page shown instead of a title above the profile's rating limit.
+async fn restricted_page(
+    state: &AppState,
+    viewer: &templates::Viewer<'_>,
+    title: &str,
+    certification: Option<&tmdb::Certification>,
+    return_to: &str,
+) -> Response {
+    let has_pin = match viewer.user_id {
+        Some(user_id) => parental::ParentalSettings::load(&state.auth, user_id)
+            .await
+            .map(|settings| settings.has_pin())
+            .unwrap_or(false),
+        None => false,
+    };
+    let html = templates::render_restricted(viewer, Some(title), certification, return_to, has_pin, None);
+    (http::StatusCode::FORBIDDEN, Html(html)).into_response()
+}
+
+#[derive(Deserialize)]
+struct ParentalForm {
+    #[serde(default)]
+    max_certification: String,
+    #[serde(default)]
+    new_pin: String,
+    #[serde(default)]
+    current_pin: String,
+}
+
+/// Saves the rating limit and PIN. Once a PIN is set, changing either needs
+/// it; wrong PINs count toward the same lockout as at [`parental_unlock`].
+async fn save_parental_settings(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    ClientIp(ip): ClientIp,
+    Form(form): Form<ParentalForm>,
+) -> Result<Redirect, AppError> {
+    let session = user.require(Permission::EditPreferences)?;
+    let settings = parental::ParentalSettings::load(&state.auth, session.user_id).await?;
+    if settings.has_pin() {
+        let throttle_key = format!("parental-pin:{}", session.username);
+        let ip = ip.to_string();
+        if let Some(retry_after_secs) = state.logins.retry_after(&throttle_key, Some(&ip)).await? {
+            state.logins.record(&throttle_key, Some(&ip), login::Outcome::LockedOut).await?;
+            return Err(login::LoginError::LockedOut { retry_after_secs }.into());
+        }
+        if !settings.check_pin(&form.current_pin) {
+            state.logins.record(&throttle_key, Some(&ip), login::Outcome::Failure).await?;
+            return Err(AppError::Forbidden("Incorrect PIN".to_string()));
+        }
+        state.logins.record(&throttle_key, Some(&ip), login::Outcome::Success).await?;
+    }
+
+    let limit = match form.max_certification.as_str() {
+        "" => None,
+        label => Some(
+            parental::find_limit(label)
+                .ok_or_else(|| AppError::BadRequest("Unknown rating".to_string()))?,
+        ),
+    };
+    let new_pin = form.new_pin.trim();
+    if !new_pin.is_empty() && (new_pin.len() < parental::MIN_PIN_LENGTH || !new_pin.chars().all(|c| c.is_ascii_digit())) {
+        return Err(AppError::BadRequest(format!(
+            "The PIN must be at least {} digits",
+            parental::MIN_PIN_LENGTH
+        )));
+    }
+    let new_pin = (!new_pin.is_empty()).then_some(new_pin);
+    parental::ParentalSettings::save(&state.auth, session.user_id, limit, new_pin).await?;
+
+    Ok(Redirect::to("/settings"))
+}
+
+#[derive(Deserialize)]
+struct UnlockForm {
+    pin: String,
+    return_to: String,
+}
+
+/// Lifts the rating limit for a while once the PIN is entered. Wrong PINs
+/// count toward the same lockout as failed sign-ins.
+async fn parental_unlock(
+    State(state): State<AppState>,
+    RequireSession(session): RequireSession,
+    ClientIp(ip): ClientIp,
+    Form(form): Form<UnlockForm>,
+) -> Result<Response, AppError> {
+    // Only paths on this site, so the form can't be used to redirect elsewhere.
+    let return_to = if form.return_to.starts_with('/') && !form.return_to.starts_with("//") {
+        form.return_to.as_str()
+    } else {
+        "/"
+    };
+    let throttle_key = format!("parental-pin:{}", session.username);
+    let ip = ip.to_string();
+
+    let error = if let Some(retry_after_secs) = state.logins.retry_after(&throttle_key, Some(&ip)).await? {
+        state.logins.record(&throttle_key, Some(&ip), login::Outcome::LockedOut).await?;
+        login::LoginError::LockedOut { retry_after_secs }.to_string()
+    } else {
+        let settings = parental::ParentalSettings::load(&state.auth, session.user_id).await?;
+        if settings.check_pin(&form.pin) {
+            state.logins.record(&throttle_key, Some(&ip), login::Outcome::Success).await?;
+            return Ok((
+                [(http::header::SET_COOKIE, state.parental.unlock_cookie(session.user_id))],
+                Redirect::to(return_to),
+            )
+                .into_response());
+        }
+        state.logins.record(&throttle_key, Some(&ip), login::Outcome::Failure).await?;
+        "Incorrect PIN".to_string()
+    };
+
+    let viewer = get_viewer(&state, Some(&session)).await;
+    let html = templates::render_restricted(&viewer, None, None, return_to, true, Some(&error));
+    Ok((http::StatusCode::FORBIDDEN, Html(html)).into_response())
+}
+
+/// Sends the user to TMDB to approve access to their account. TMDB sends
+/// them back to `PUBLIC_URL`, never to a host taken from the request.
+async fn tmdb_connect(
+    State(state): State<AppState>,
+    user: CurrentUser,
+) -> Result<Redirect, AppError> {
+    let session = user.require(Permission::ManageAccount)?;
+    let public_url = state.public_url.get().ok_or(AppError::Internal)?;
+    let callback = format!("{}/settings/tmdb/callback", public_url);
+    let approve_url = tmdb_account::begin_connect(&state, session.user_id, &callback).await?;
+    Ok(Redirect::to(&approve_url))
+}
+
+async fn tmdb_callback(
+    State(state): State<AppState>,
+    user: CurrentUser,
+) -> Result<Redirect, AppError> {
+    let session = user.require(Permission::ManageAccount)?;
+    let report = tmdb_account::finish_connect(&state, session.user_id).await?;
+    info!(
+        "Connected TMDB account for {}: {} watchlist, {} ratings imported",
+        session.username, report.watchlist, report.ratings
+    );
+    Ok(Redirect::to("/settings"))
+}
+
+#[derive(Deserialize)]
+struct TmdbAccountForm {
+    action: String,
+    #[serde(default)]
+    sync_enabled: Option<String>,
+}
+
+async fn tmdb_account_action(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    Form(form): Form<TmdbAccountForm>,
+) -> Result<Redirect, AppError> {
+    let session = user.require(Permission::ManageAccount)?;
+    match form.action.as_str() {
+        "save" => {
+            tmdb_account::set_sync_enabled(&state.db, session.user_id, form.sync_enabled.is_some()).await?;
+        }
+        "sync" => {
+            let account = tmdb_account::get_account(&state, session.user_id)
+                .await?
+                .ok_or_else(|| AppError::BadRequest("No TMDB account connected".to_string()))?;
+            tmdb_account::sync_account(&state.db, &state.tmdb, &account).await?;
+        }
+        "disconnect" => tmdb_account::disconnect(&state, session.user_id).await?,
+        _ => return Err(AppError::BadRequest("Unknown action".to_string())),
+    }
+    Ok(Redirect::to("/settings"))
+}
+
+#[derive(Deserialize)]
+struct ListImportForm {
+    list: String,
+}
+
+async fn import_tmdb_list(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    Form(form): Form<ListImportForm>,
+) -> Result<Redirect, AppError> {
+    let session = user.require(Permission::ManageAccount)?;
+    let list_id = tmdb::parse_list_id(&form.list)
+        .ok_or_else(|| AppError::BadRequest("Enter a TMDB list id or URL".to_string()))?;
+    let report = tmdb_account::import_list(&state.db, &state.tmdb, session.user_id, &list_id).await?;
+    info!(
+        "{} imported TMDB list \"{}\": {} added, {} skipped",
+        session.username, report.name, report.added, report.skipped
+    );
+    Ok(Redirect::to("/settings"))
+}
+
+#[derive(Deserialize)]
+struct SettingsForm {
+    image_quality: String,
+    #[serde(default)]
+    data_saver: Option<String>,
+    #[serde(default)]
+    pause_history: Option<String>,
+}
+
+async fn save_settings(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    Form(form): Form<SettingsForm>,
+) -> Result<Redirect, AppError> {
+    let session = user.require(Permission::EditPreferences)?;
+
+    let prefs = UserPreferences {
+        image_quality: ImageQuality::parse(&form.image_quality)
+            .ok_or_else(|| AppError::BadRequest("Unknown image quality".to_string()))?,
+        data_saver: form.data_saver.is_some(),
+        pause_history: form.pause_history.is_some(),
+    };
+    state.auth.save_preferences(session.user_id, &prefs).await?;
+
+    Ok(Redirect::to("/settings"))
+}
+
+#[derive(Deserialize)]
+struct PlayerSettingsForm {
+    accent_color: String,
+    #[serde(default)]
+    autoplay: Option<String>,
+    #[serde(default)]
+    next_episode: Option<String>,
+    #[serde(default)]
+    episode_selector: Option<String>,
+    #[serde(default)]
+    audio_language: String,
+    #[serde(default)]
+    max_quality: String,
+}
+
+async fn save_player_settings(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    Form(form): Form<PlayerSettingsForm>,
+) -> Result<Redirect, AppError> {
+    let session = user.require(Permission::EditPreferences)?;
+
+    let accent_color = PlayerPreferences::parse_color(&form.accent_color)
+        .ok_or_else(|| AppError::BadRequest("Accent color must be a hex color like #e50914".to_string()))?;
+    let prefs = PlayerPreferences {
+        // Keep following the app color until the user picks a different one.
+        accent_color: (accent_color != templates::ACCENT_COLOR).then_some(accent_color),
+        autoplay: form.autoplay.is_some(),
+        next_episode: form.next_episode.is_some(),
+        episode_selector: form.episode_selector.is_some(),
+        audio_language: PlayerPreferences::parse_language(&form.audio_language),
+        max_quality: PlayerPreferences::parse_quality(&form.max_quality),
+    };
+    state.auth.save_player_preferences(session.user_id, &prefs).await?;
+
+    Ok(Redirect::to("/settings"))
+}
+
+/// Creates an account from an invite and signs it in.
+pub(crate) async fn register_account(
+    state: &AppState,
+    registration: &invites::Registration,
+    client: &auth::SessionClient,
+) -> Result<(invites::NewUser, auth::NewSession), AppError> {
+    let user = state.invites.redeem(registration).await?;
+    let session = state
+        .sessions
+        .create_session(user.id, &user.username, user.role, client, auth::SessionDuration::Remembered)
+        .await?;
+    Ok((user, session))
+}
+
+#[derive(Deserialize)]
+struct RegisterQuery {
+    #[serde(default)]
+    code: Option<String>,
+}
+
+async fn register_page(
+    State(state): State<AppState>,
+    Query(params): Query<RegisterQuery>,
+) -> Html<String> {
+    let viewer = get_viewer(&state, None).await;
+    Html(templates::render_register(&viewer, params.code.as_deref().unwrap_or_default(), None))
+}
+
+async fn register_submit(
+    State(state): State<AppState>,
+    ClientIp(ip): ClientIp,
+    headers: HeaderMap,
+    Form(registration): Form<invites::Registration>,
+) -> Result<Response, AppError> {
+    let client = auth::SessionClient::new(&headers, ip);
+    match register_account(&state, &registration, &client).await {
+        Ok((_, session)) => Ok((
+            [(http::header::SET_COOKIE, session.cookie())],
+            Redirect::to("/"),
+        )
+            .into_response()),
+        Err(AppError::BadRequest(message)) => {
+            let viewer = get_viewer(&state, None).await;
+            let html = templates::render_register(&viewer, &registration.invite_code, Some(&message));
+            Ok((http::StatusCode::BAD_REQUEST, Html(html)).into_response())
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// Checks a username and password and signs the account in, unless the
+/// username or client IP is locked out after repeated failures.
+pub(crate) async fn sign_in(
+    state: &AppState,
+    credentials: &login::Credentials,
+    client: &auth::SessionClient,
+) -> Result<(i64, Role, auth::NewSession), login::LoginError> {
+    let username = credentials.username.trim();
+    let ip = client.ip.as_deref();
+
+    if let Some(retry_after_secs) = state.logins.retry_after(username, ip).await? {
+        state.logins.record(username, ip, login::Outcome::LockedOut).await?;
+        return Err(login::LoginError::LockedOut { retry_after_secs });
+    }
+
+    let Some((user_id, role)) = state.auth.verify_password(username, &credentials.password).await? else {
+        state.logins.record(username, ip, login::Outcome::Failure).await?;
+        return Err(login::LoginError::InvalidCredentials);
+    };
+    state.logins.record(username, ip, login::Outcome::Success).await?;
+
+    let duration = auth::SessionDuration::from_remember(credentials.remember);
+    let session = state
+        .sessions
+        .create_session(user_id, username, role, client, duration)
+        .await?;
+    Ok((user_id, role, session))
+}
+
+async fn login_page(State(state): State<AppState>) -> Html<String> {
+    let viewer = get_viewer(&state, None).await;
+    Html(templates::render_login(&viewer, "", None))
+}
+
+async fn login_submit(
+    State(state): State<AppState>,
+    ClientIp(ip): ClientIp,
+    headers: HeaderMap,
+    Form(credentials): Form<login::Credentials>,
+) -> Result<Response, AppError> {
+    let client = auth::SessionClient::new(&headers, ip);
+    let (status, error) = match sign_in(&state, &credentials, &client).await {
+        Ok((_, _, session)) => {
+            // Sessions signed out to make room are listed with a notice.
+            let to = match session.evicted {
+                0 => "/".to_string(),
+                evicted => format!("/settings/sessions?evicted={}", evicted),
+            };
+            return Ok((
+                [(http::header::SET_COOKIE, session.cookie())],
+                Redirect::to(&to),
+            )
+                .into_response())
+        }
+        Err(login::LoginError::Other(e)) => return Err(e.into()),
+        Err(e @ login::LoginError::LockedOut { .. }) => (http::StatusCode::TOO_MANY_REQUESTS, e),
+        Err(e) => (http::StatusCode::BAD_REQUEST, e),
+    };
+    let viewer = get_viewer(&state, None).await;
+    let html = templates::render_login(&viewer, &credentials.username, Some(&error.to_string()));
+    Ok((status, Html(html)).into_response())
+}
+
+#[derive(Deserialize)]
+struct SessionsQuery {
+    /// Older sessions signed out when this one signed in.
+    evicted: Option<u64>,
+}
+
+async fn sessions_page(
+    State(state): State<AppState>,
+    RequireSession(session): RequireSession,
+    Query(params): Query<SessionsQuery>,
+) -> Result<Html<String>, AppError> {
+    let viewer = get_viewer(&state, Some(&session)).await;
+    let sessions = state.sessions.list_sessions(session.user_id).await?;
+    Ok(Html(templates::render_sessions(&viewer, &sessions, &session.id, params.evicted)))
+}
+
+/// Signs out one session. Signing out the current one also drops its cookie.
+async fn revoke_session(
+    State(state): State<AppState>,
+    RequireSession(session): RequireSession,
+    Path(id): Path<String>,
+) -> Result<Response, AppError> {
+    if !state.sessions.revoke_session(session.user_id, &id).await? {
+        return Err(AppError::NotFound);
+    }
+    if id == session.id {
+        return Ok(([(http::header::SET_COOKIE, auth::clear_session_cookie())], Redirect::to("/")).into_response());
+    }
+    Ok(Redirect::to("/settings/sessions").into_response())
+}
+
+async fn revoke_all_sessions(
+    State(state): State<AppState>,
+    RequireSession(session): RequireSession,
+) -> Result<Response, AppError> {
+    state.sessions.revoke_all_sessions(session.user_id).await?;
+    Ok(([(http::header::SET_COOKIE, auth::clear_session_cookie())], Redirect::to("/")).into_response())
+}
+
+/// Deletes the signed-in user's account and data once their password checks
+/// out. The local user owns the install and can't be deleted.
+pub(crate) async fn delete_own_account(state: &AppState, session: &Session, password: &str) -> Result<(), AppError> {
+    if session.username == "local" {
+        return Err(AppError::BadRequest("The local user can't be deleted".to_string()));
+    }
+    if state.auth.verify_password(&session.username, password).await?.is_none() {
+        return Err(AppError::BadRequest("Incorrect password".to_string()));
+    }
+    state.sessions.revoke_all_sessions(session.user_id).await?;
+    state.accounts.delete(session.user_id).await?;
+    Ok(())
+}
+
+async fn delete_account_page(
+    State(state): State<AppState>,
+    RequireSession(session): RequireSession,
+) -> Html<String> {
+    let viewer = get_viewer(&state, Some(&session)).await;
+    Html(templates::render_delete_account(&viewer, None))
+}
+
+#[derive(Deserialize)]
+struct DeleteAccountForm {
+    password: String,
+}
+
+async fn delete_account_submit(
+    State(state): State<AppState>,
+    RequireSession(session): RequireSession,
+    Form(form): Form<DeleteAccountForm>,
+) -> Result<Response, AppError> {
+    match delete_own_account(&state, &session, &form.password).await {
+        Ok(()) => Ok(([(http::header::SET_COOKIE, auth::clear_session_cookie())], Redirect::to("/")).into_response()),
+        Err(AppError::BadRequest(error)) => {
+            let viewer = get_viewer(&state, Some(&session)).await;
+            let html = templates::render_delete_account(&viewer, Some(&error));
+            Ok((http::StatusCode::BAD_REQUEST, Html(html)).into_response())
+        }
+        Err(e) => Err(e),
+    }
+}
+
+async fn admin_invites_page(
+    State(state): State<AppState>,
+    user: CurrentUser,
+) -> Result<Html<String>, AppError> {
+    let session = &user.session;
+    let viewer = get_viewer(&state, Some(session)).await;
+    let invites = state.invites.list().await?;
+    Ok(Html(templates::render_admin_invites(&viewer, &invites)))
+}
+
+#[derive(Deserialize)]
+struct InviteForm {
+    role: String,
+    /// Empty never expires.
+    #[serde(default)]
+    expires_in_days: String,
+}
+
+async fn create_invite(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    Form(form): Form<InviteForm>,
+) -> Result<Redirect, AppError> {
+    let session = &user.session;
+    let role = Role::parse(&form.role).ok_or_else(|| AppError::BadRequest("Unknown role".to_string()))?;
+    let expires_in_days = match form.expires_in_days.trim() {
+        "" => None,
+        days => Some(
+            days.parse::<i64>()
+                .ok()
+                .filter(|&d| d > 0)
+                .ok_or_else(|| AppError::BadRequest("Expiry must be a number of days".to_string()))?,
+        ),
+    };
+    invites::check_invite_role(session.role, role)?;
+    state.invites.create(session.user_id, role, expires_in_days).await?;
+    Ok(Redirect::to("/admin/invites"))
+}
+
+async fn revoke_invite(
+    State(state): State<AppState>,
+    Path(code): Path<String>,
+) -> Result<Redirect, AppError> {
+    state.invites.revoke(&code).await?;
+    Ok(Redirect::to("/admin/invites"))
+}
+
+async fn admin_providers_page(
+    State(state): State<AppState>,
+    user: CurrentUser,
+) -> Result<Html<String>, AppError> {
+    let session = &user.session;
+    let viewer = get_viewer(&state, Some(session)).await;
+    let html = templates::render_admin_providers(&viewer, &state.providers.configs());
+    Ok(Html(html))
+}
+
+#[derive(Deserialize)]
+struct ProviderConfigForm {
+    enabled: Option<String>,
+    /// Empty keeps the provider's own priority.
+    #[serde(default)]
+    priority: String,
+    /// `name=value` per line.
+    #[serde(default)]
+    options: String,
+}
+
+async fn save_provider_config(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    Form(form): Form<ProviderConfigForm>,
+) -> Result<Redirect, AppError> {
+    let provider = state.providers.get(&name).ok_or(AppError::NotFound)?;
+
+    let priority = match form.priority.trim() {
+        "" => None,
+        value => Some(
+            value
+                .parse::<i32>()
+                .map_err(|_| AppError::BadRequest("Priority must be a whole number".to_string()))?,
+        ),
+    };
+    let mut options = std::collections::BTreeMap::new();
+    for line in form.options.lines().map(str::trim).filter(|l| !l.is_empty()) {
+        match line.split_once('=') {
+            Some((key, value)) if !key.trim().is_empty() => {
+                options.insert(key.trim().to_string(), value.trim().to_string());
+            }
+            _ => return Err(AppError::BadRequest(format!("Options must be name=value, got \"{}\"", line))),
+        }
+    }
+
+    let setting = provider_settings::ProviderSetting {
+        name: provider.name().to_string(),
+        enabled: form.enabled.is_some(),
+        // Saving the form shouldn't pin the default, so a later code change still applies.
+        priority: priority.filter(|&p| p != provider.priority()),
+        options,
+    };
+    state.providers.configure(setting).await?;
+    Ok(Redirect::to("/admin/providers"))
+}
+
+async fn reset_provider_config(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+) -> Result<Redirect, AppError> {
+    state.providers.reset(&name).await?;
+    Ok(Redirect::to("/admin/providers"))
+}
+
+#[derive(Deserialize)]
+struct QualityRequest {
+    /// `null` removes the cap.
+    max_quality: Option<u32>,
+}
+
+/// Backs the quality dropdown on the player page.
+async fn api_set_max_quality(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    Json(request): Json<QualityRequest>,
+) -> Result<Json<()>, AppError> {
+    let session = user.require(Permission::EditPreferences)?;
+    if request.max_quality.is_some_and(|q| !auth::QUALITY_CAPS.contains(&q)) {
+        return Err(AppError::BadRequest(format!("max_quality must be one of {:?}", auth::QUALITY_CAPS)));
+    }
+
+    let mut prefs = state.auth.get_player_preferences(session.user_id).await?;
+    prefs.max_quality = request.max_quality;
+    state.auth.save_player_preferences(session.user_id, &prefs).await?;
+    Ok(Json(()))
+}
+
+async fn dismiss_whats_new(
+    State(state): State<AppState>,
+    user: CurrentUser,
+) -> Result<Redirect, AppError> {
+    state
+        .auth
+        .set_setting(user.session.user_id, changelog::LAST_SEEN_VERSION_KEY, changelog::APP_VERSION)
+        .await?;
+    Ok(Redirect::to("/"))
+}
+
+const FAVICON_ICO: &[u8] = include_bytes!("../../desktop/src-tauri/icons/icon.ico");
+const APP_ICON_PNG: &[u8] = include_bytes!("../../desktop/src-tauri/icons/icon.png");
+const ICON_CACHE_CONTROL: &str = "public, max-age=604800";
+
+async fn favicon_ico() -> impl IntoResponse {
+    (
+        [
+            (http::header::CONTENT_TYPE, "image/x-icon"),
+            (http::header::CACHE_CONTROL, ICON_CACHE_CONTROL),
+        ],
+        FAVICON_ICO,
+    )
+}
+
+async fn favicon_svg() -> impl IntoResponse {
+    (
+        [
+            (http::header::CONTENT_TYPE, "image/svg+xml"),
+            (http::header::CACHE_CONTROL, ICON_CACHE_CONTROL),
+        ],
+        templates::render_favicon_svg(),
+    )
+}
+
+async fn apple_touch_icon() -> impl IntoResponse {
+    (
+        [
+            (http::header::CONTENT_TYPE, "image/png"),
+            (http::header::CACHE_CONTROL, ICON_CACHE_CONTROL),
+        ],
+        APP_ICON_PNG,
+    )
+}
+
+async fn metrics_endpoint(State(state): State<AppState>, _reader: auth::MetricsReader) -> impl IntoResponse {
+    (
+        [(http::header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        state.metrics.render_prometheus(),
+    )
+}
+
+#[derive(Deserialize)]
+struct StreamProxyQuery {
+    url: String,
+    expires: i64,
+    sig: String,
+}
+
+async fn stream_proxy(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    Query(params): Query<StreamProxyQuery>,
+) -> Result<Response, AppError> {
+    let user_id = user.session.user_id;
+    if !state.hls.verify(&params.url, params.expires, user_id, &params.sig) {
+        return Err(AppError::Forbidden("Stream link is invalid or expired".to_string()));
+    }
+    let relayed = state
+        .hls
+        .fetch(&params.url, user_id)
+        .await?
+        .ok_or_else(|| AppError::Forbidden("Stream host is not allowed".to_string()))?;
+
+    Ok((
+        [
+            (http::header::CONTENT_TYPE, relayed.content_type),
+            (http::header::CACHE_CONTROL, "no-store".to_string()),
+        ],
+        relayed.body,
+    )
+        .into_response())
+}
+
+async fn serve_local_media(
+    State(state): State<AppState>,
+    _user: CurrentUser,
+    restriction: parental::Restriction,
+    Path(id): Path<i64>,
+    request: axum::extract::Request,
+) -> Result<Response, AppError> {
+    let library = state.local_media.as_ref().ok_or(AppError::NotFound)?;
+    let entry = library.get(id).await?.ok_or(AppError::NotFound)?;
+    // The player page checks the rating too, but the file URL can be
+    // fetched directly.
+    restriction.check_title(&state.tmdb, &entry.media_type, entry.tmdb_id).await?;
+    let path = library.resolve(&entry.path).ok_or(AppError::NotFound)?;
+
+    // ServeFile handles Range requests, so the player can seek. It is always
+    // ready, so it can be called without polling readiness first.
+    let response = ServeFile::new(path)
+        .call(request)
+        .await
+        .map_err(|_| AppError::Internal)?;
+    Ok(response.into_response())
+}
+
+async fn proxy_image(
+    State(state): State<AppState>,
+    Path((size, path)): Path<(String, String)>,
+) -> Result<Response, AppError> {
+    if !images::is_valid_size(&size) || !images::is_valid_file(&path) {
+        return Err(AppError::BadRequest("Invalid image path".to_string()));
+    }
+
+    let bytes = state.images.get(&size, &path).await?.ok_or(AppError::NotFound)?;
+    let content_type = path
+        .rsplit_once('.')
+        .and_then(|(_, ext)| images::content_type(ext))
+        .unwrap_or("application/octet-stream");
+
+    Ok((
+        [
+            (http::header::CONTENT_TYPE, content_type),
+            (http::header::CACHE_CONTROL, images::IMAGE_CACHE_CONTROL),
+        ],
+        bytes,
+    )
+        .into_response())
+}
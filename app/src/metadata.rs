@@ -0,0 +1,177 @@
+use std::collections::HashSet;
+use std::time::Duration;
+
+use tracing::{error, info, warn};
+
+use crate::db::{self, Db};
+use crate::notifications::NotificationKind;
+use crate::tmdb::{MovieDetail, TmdbClient, TvShowDetail};
+use crate::AppState;
+
+/// How often stored movie and show records are refreshed.
+const REFRESH_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Records older than this are refreshed even if TMDB reported no change.
+const MAX_AGE_DAYS: i64 = 7;
+
+/// Keeps a nightly run from turning into a TMDB crawl.
+const MAX_REFRESHES_PER_RUN: usize = 500;
+
+#[derive(Debug, Default)]
+pub struct RefreshReport {
+    pub refreshed: usize,
+    pub failed: usize,
+}
+
+/// Upserts the summary of a movie into the `movies` table.
+pub async fn store_movie(db: &Db, movie: &MovieDetail) -> anyhow::Result<()> {
+    db::query(
+        r#"
+        INSERT INTO movies
+        (tmdb_id, title, overview, poster_path, backdrop_path, release_date, vote_average, vote_count, genres, runtime)
+        VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+        ON CONFLICT(tmdb_id) DO UPDATE SET
+            title = excluded.title,
+            overview = excluded.overview,
+            poster_path = excluded.poster_path,
+            backdrop_path = excluded.backdrop_path,
+            release_date = excluded.release_date,
+            vote_average = excluded.vote_average,
+            vote_count = excluded.vote_count,
+            genres = excluded.genres,
+            runtime = excluded.runtime,
+            updated_at = datetime('now')
+        "#
+    )
+    .bind(movie.id)
+    .bind(&movie.title)
+    .bind(&movie.overview)
+    .bind(&movie.poster_path)
+    .bind(&movie.backdrop_path)
+    .bind(&movie.release_date)
+    .bind(movie.vote_average)
+    .bind(movie.vote_count)
+    .bind(serde_json::to_string(&movie.genres)?)
+    .bind(movie.runtime)
+    .execute(db)
+    .await?;
+    Ok(())
+}
+
+/// Upserts the summary of a show into the `tv_shows` table.
+pub async fn store_tv_show(db: &Db, show: &TvShowDetail) -> anyhow::Result<()> {
+    db::query(
+        r#"
+        INSERT INTO tv_shows
+        (tmdb_id, name, overview, poster_path, backdrop_path, first_air_date, vote_average, vote_count, genres, number_of_seasons, number_of_episodes)
+        VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+        ON CONFLICT(tmdb_id) DO UPDATE SET
+            name = excluded.name,
+            overview = excluded.overview,
+            poster_path = excluded.poster_path,
+            backdrop_path = excluded.backdrop_path,
+            first_air_date = excluded.first_air_date,
+            vote_average = excluded.vote_average,
+            vote_count = excluded.vote_count,
+            genres = excluded.genres,
+            number_of_seasons = excluded.number_of_seasons,
+            number_of_episodes = excluded.number_of_episodes,
+            updated_at = datetime('now')
+        "#
+    )
+    .bind(show.id)
+    .bind(&show.name)
+    .bind(&show.overview)
+    .bind(&show.poster_path)
+    .bind(&show.backdrop_path)
+    .bind(&show.first_air_date)
+    .bind(show.vote_average)
+    .bind(show.vote_count)
+    .bind(serde_json::to_string(&show.genres)?)
+    .bind(show.number_of_seasons)
+    .bind(show.number_of_episodes)
+    .execute(db)
+    .await?;
+    Ok(())
+}
+
+/// Re-fetches stored titles that TMDB changed in the last day or that have
+/// not been refreshed in [`MAX_AGE_DAYS`], so episode counts and ratings
+/// stay current.
+pub async fn refresh_stale(db: &Db, tmdb: &TmdbClient) -> anyhow::Result<RefreshReport> {
+    let mut report = RefreshReport::default();
+
+    for (media_type, table) in [("movie", "movies"), ("tv", "tv_shows")] {
+        let stored: Vec<(i64, bool)> = db::query_as(&format!(
+            "SELECT tmdb_id, COALESCE(updated_at < datetime('now', ?), TRUE) FROM {}",
+            table
+        ))
+        .bind(format!("-{} days", MAX_AGE_DAYS))
+        .fetch_all(db)
+        .await?;
+        if stored.is_empty() {
+            continue;
+        }
+
+        let changed: HashSet<i64> = match tmdb.get_changed_ids(media_type, 1).await {
+            Ok(ids) => ids.into_iter().collect(),
+            Err(e) => {
+                warn!("Could not load TMDB {} changes, refreshing by age only: {}", media_type, e);
+                HashSet::new()
+            }
+        };
+
+        let due = stored
+            .into_iter()
+            .filter(|(id, expired)| *expired || changed.contains(id))
+            .map(|(id, _)| id)
+            .take(MAX_REFRESHES_PER_RUN.saturating_sub(report.refreshed + report.failed));
+
+        for id in due {
+            let result = match media_type {
+                "movie" => match tmdb.get_movie(id).await {
+                    Ok(movie) => store_movie(db, &movie).await,
+                    Err(e) => Err(e),
+                },
+                _ => match tmdb.get_tv_show(id).await {
+                    Ok(show) => store_tv_show(db, &show).await,
+                    Err(e) => Err(e),
+                },
+            };
+            match result {
+                Ok(()) => report.refreshed += 1,
+                Err(e) => {
+                    warn!("Could not refresh {} {}: {}", media_type, id, e);
+                    report.failed += 1;
+                }
+            }
+        }
+    }
+
+    Ok(report)
+}
+
+/// Refreshes stored metadata once a day for the life of the server.
+pub fn spawn_refresh_job(state: AppState) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(REFRESH_INTERVAL);
+        interval.tick().await;
+        loop {
+            interval.tick().await;
+            match refresh_stale(&state.db, &state.tmdb).await {
+                Ok(report) => {
+                    info!(
+                        "Metadata refresh: {} refreshed, {} failed",
+                        report.refreshed, report.failed
+                    );
+                    state.notifications.notify_admins(
+                        NotificationKind::JobFinished,
+                        "Metadata refresh finished",
+                        format!("{} refreshed, {} failed", report.refreshed, report.failed),
+                    ).await;
+                }
+                Err(e) => error!("Metadata refresh failed: {}", e),
+            }
+        }
+    });
+}
@@ -0,0 +1,156 @@
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Instant;
+
+use dashmap::DashMap;
+use serde::Serialize;
+
+/// Process-wide counters shared by every module through `AppState`. This is
+/// the single source for `/metrics`, the admin dashboard, and health checks.
+#[derive(Debug)]
+pub struct AppMetrics {
+    started_at: Instant,
+    tmdb_requests: AtomicU64,
+    tmdb_errors: AtomicU64,
+    stream_launches: AtomicU64,
+    auth_failures: AtomicU64,
+    qos_events: AtomicU64,
+    caches: DashMap<&'static str, CacheCounters>,
+}
+
+#[derive(Debug, Default)]
+struct CacheCounters {
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct MetricsSnapshot {
+    pub uptime_seconds: u64,
+    pub tmdb_requests: u64,
+    pub tmdb_errors: u64,
+    pub stream_launches: u64,
+    pub auth_failures: u64,
+    pub qos_events: u64,
+    pub caches: Vec<CacheSnapshot>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CacheSnapshot {
+    pub name: String,
+    pub hits: u64,
+    pub misses: u64,
+}
+
+impl MetricsSnapshot {
+    /// Share of TMDB requests that failed, or `0.0` before the first request.
+    pub fn tmdb_error_rate(&self) -> f64 {
+        if self.tmdb_requests == 0 {
+            0.0
+        } else {
+            self.tmdb_errors as f64 / self.tmdb_requests as f64
+        }
+    }
+}
+
+impl Default for AppMetrics {
+    fn default() -> Self {
+        Self {
+            started_at: Instant::now(),
+            tmdb_requests: AtomicU64::new(0),
+            tmdb_errors: AtomicU64::new(0),
+            stream_launches: AtomicU64::new(0),
+            auth_failures: AtomicU64::new(0),
+            qos_events: AtomicU64::new(0),
+            caches: DashMap::new(),
+        }
+    }
+}
+
+impl AppMetrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records one TMDB round trip; `ok` is false for transport errors and
+    /// non-2xx responses.
+    pub fn record_tmdb_request(&self, ok: bool) {
+        self.tmdb_requests.fetch_add(1, Ordering::Relaxed);
+        if !ok {
+            self.tmdb_errors.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    pub fn record_stream_launch(&self) {
+        self.stream_launches.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_auth_failure(&self) {
+        self.auth_failures.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_qos_event(&self) {
+        self.qos_events.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_cache(&self, cache: &'static str, hit: bool) {
+        let counters = self.caches.entry(cache).or_default();
+        if hit {
+            counters.hits.fetch_add(1, Ordering::Relaxed);
+        } else {
+            counters.misses.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    pub fn snapshot(&self) -> MetricsSnapshot {
+        let mut caches: Vec<CacheSnapshot> = self
+            .caches
+            .iter()
+            .map(|entry| CacheSnapshot {
+                name: entry.key().to_string(),
+                hits: entry.hits.load(Ordering::Relaxed),
+                misses: entry.misses.load(Ordering::Relaxed),
+            })
+            .collect();
+        caches.sort_by(|a, b| a.name.cmp(&b.name));
+
+        MetricsSnapshot {
+            uptime_seconds: self.started_at.elapsed().as_secs(),
+            tmdb_requests: self.tmdb_requests.load(Ordering::Relaxed),
+            tmdb_errors: self.tmdb_errors.load(Ordering::Relaxed),
+            stream_launches: self.stream_launches.load(Ordering::Relaxed),
+            auth_failures: self.auth_failures.load(Ordering::Relaxed),
+            qos_events: self.qos_events.load(Ordering::Relaxed),
+            caches,
+        }
+    }
+
+    /// Prometheus text exposition format.
+    pub fn render_prometheus(&self) -> String {
+        let snapshot = self.snapshot();
+        let mut out = String::new();
+
+        let counters = [
+            ("ruststream_uptime_seconds", "gauge", "Seconds since the server started", snapshot.uptime_seconds),
+            ("ruststream_tmdb_requests_total", "counter", "TMDB API requests sent", snapshot.tmdb_requests),
+            ("ruststream_tmdb_errors_total", "counter", "TMDB API requests that failed", snapshot.tmdb_errors),
+            ("ruststream_stream_launches_total", "counter", "Player pages opened with resolved streams", snapshot.stream_launches),
+            ("ruststream_auth_failures_total", "counter", "Rejected session tokens", snapshot.auth_failures),
+            ("ruststream_qos_events_total", "counter", "Playback stalls, errors, and silent sessions", snapshot.qos_events),
+        ];
+        for (name, kind, help, value) in counters {
+            let _ = writeln!(out, "# HELP {} {}", name, help);
+            let _ = writeln!(out, "# TYPE {} {}", name, kind);
+            let _ = writeln!(out, "{} {}", name, value);
+        }
+
+        let _ = writeln!(out, "# HELP ruststream_cache_requests_total Cache lookups by cache and result");
+        let _ = writeln!(out, "# TYPE ruststream_cache_requests_total counter");
+        for cache in &snapshot.caches {
+            let _ = writeln!(out, "ruststream_cache_requests_total{{cache=\"{}\",result=\"hit\"}} {}", cache.name, cache.hits);
+            let _ = writeln!(out, "ruststream_cache_requests_total{{cache=\"{}\",result=\"miss\"}} {}", cache.name, cache.misses);
+        }
+
+        out
+    }
+}
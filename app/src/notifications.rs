@@ -0,0 +1,389 @@
+//! In-app notifications, streamed to the browser over `/api/events`:
+//! new episodes of watchlisted shows, and background jobs finishing.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use chrono::{NaiveDate, Utc};
+use serde::Serialize;
+use tokio::sync::{broadcast, mpsc, watch};
+use tokio_stream::wrappers::ReceiverStream;
+use tracing::{error, info, warn};
+
+use crate::db::{self, Db};
+use crate::AppState;
+
+/// Notifications replayed to a browser that connects, or reconnects, later.
+const RECENT_LIMIT: i64 = 100;
+
+/// Days a notification is kept in the `notifications` table.
+pub const RETENTION_DAYS: u32 = 30;
+
+/// Open streams allowed per user. Browsers allow about six connections per
+/// host, so more tabs than this would starve the pages themselves.
+const MAX_STREAMS_PER_USER: usize = 4;
+
+/// Notifications a slow connection may fall behind by before it skips some.
+const CHANNEL_CAPACITY: usize = 256;
+
+/// How often watchlisted shows are checked for new episodes.
+const NEW_EPISODE_INTERVAL: Duration = Duration::from_secs(6 * 60 * 60);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NotificationKind {
+    NewEpisode,
+    JobFinished,
+}
+
+impl NotificationKind {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::NewEpisode => "new_episode",
+            Self::JobFinished => "job_finished",
+        }
+    }
+
+    fn parse(value: &str) -> Self {
+        match value {
+            "new_episode" => Self::NewEpisode,
+            _ => Self::JobFinished,
+        }
+    }
+}
+
+/// Who a notification is for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Audience {
+    User(i64),
+    /// Users whose role may manage settings.
+    Admins,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Notification {
+    /// Increases with every notification, across restarts too.
+    pub id: u64,
+    pub kind: NotificationKind,
+    pub title: String,
+    pub message: String,
+    /// Where the notification leads when clicked.
+    pub url: Option<String>,
+    pub created_at: String,
+    #[serde(skip)]
+    audience: Audience,
+}
+
+impl Notification {
+    fn is_for(&self, user_id: i64, admin: bool) -> bool {
+        match self.audience {
+            Audience::User(id) => id == user_id,
+            Audience::Admins => admin,
+        }
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct NotificationRow {
+    id: i64,
+    user_id: Option<i64>,
+    kind: String,
+    title: String,
+    message: String,
+    url: Option<String>,
+    created_at: String,
+}
+
+impl From<NotificationRow> for Notification {
+    fn from(row: NotificationRow) -> Self {
+        Self {
+            id: row.id.max(0) as u64,
+            kind: NotificationKind::parse(&row.kind),
+            title: row.title,
+            message: row.message,
+            url: row.url,
+            created_at: row.created_at,
+            audience: row.user_id.map_or(Audience::Admins, Audience::User),
+        }
+    }
+}
+
+/// Counts a user's open stream until dropped.
+struct StreamSlot {
+    streams: Arc<Mutex<HashMap<i64, usize>>>,
+    user_id: i64,
+}
+
+impl Drop for StreamSlot {
+    fn drop(&mut self) {
+        let mut streams = self.streams.lock().unwrap();
+        if let Some(open) = streams.get_mut(&self.user_id) {
+            *open -= 1;
+            if *open == 0 {
+                streams.remove(&self.user_id);
+            }
+        }
+    }
+}
+
+pub struct Notifications {
+    db: Db,
+    sender: broadcast::Sender<Notification>,
+    /// Starts at the current time in milliseconds, or past the newest stored
+    /// id, so ids keep increasing after a restart and browsers can tell what
+    /// they have seen.
+    next_id: AtomicU64,
+    /// User id -> open streams.
+    streams: Arc<Mutex<HashMap<i64, usize>>>,
+    closed: watch::Sender<bool>,
+}
+
+impl Notifications {
+    pub async fn load(db: Db) -> anyhow::Result<Self> {
+        let newest: Option<i64> = db::query_scalar("SELECT MAX(id) FROM notifications")
+            .fetch_one(&db)
+            .await?;
+        let next_id = Utc::now().timestamp_millis().max(newest.map_or(0, |id| id + 1)).max(0) as u64;
+        let (sender, _) = broadcast::channel(CHANNEL_CAPACITY);
+        let (closed, _) = watch::channel(false);
+        Ok(Self {
+            db,
+            sender,
+            next_id: AtomicU64::new(next_id),
+            streams: Arc::new(Mutex::new(HashMap::new())),
+            closed,
+        })
+    }
+
+    pub async fn notify_user(
+        &self,
+        user_id: i64,
+        kind: NotificationKind,
+        title: impl Into<String>,
+        message: impl Into<String>,
+        url: Option<String>,
+    ) {
+        self.send(Audience::User(user_id), kind, title.into(), message.into(), url).await;
+    }
+
+    pub async fn notify_admins(&self, kind: NotificationKind, title: impl Into<String>, message: impl Into<String>) {
+        self.send(Audience::Admins, kind, title.into(), message.into(), None).await;
+    }
+
+    /// Stores the notification, then tells open streams. Stored first, so a
+    /// browser reconnecting in between replays it rather than missing it.
+    async fn send(&self, audience: Audience, kind: NotificationKind, title: String, message: String, url: Option<String>) {
+        let notification = Notification {
+            id: self.next_id.fetch_add(1, Ordering::Relaxed),
+            kind,
+            title,
+            message,
+            url,
+            created_at: Utc::now().to_rfc3339(),
+            audience,
+        };
+        let user_id = match audience {
+            Audience::User(id) => Some(id),
+            Audience::Admins => None,
+        };
+        let stored = db::query(
+            "INSERT INTO notifications (id, user_id, for_admins, kind, title, message, url, created_at)
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
+        )
+        .bind(notification.id as i64)
+        .bind(user_id)
+        .bind(audience == Audience::Admins)
+        .bind(notification.kind.as_str())
+        .bind(&notification.title)
+        .bind(&notification.message)
+        .bind(&notification.url)
+        .bind(&notification.created_at)
+        .execute(&self.db)
+        .await;
+        if let Err(e) = stored {
+            warn!("Could not store notification {}: {}", notification.id, e);
+        }
+        // No one listening is not an error; the table still has it.
+        let _ = self.sender.send(notification);
+    }
+
+    /// The user's latest stored notifications after `after` (or the latest
+    /// ones at all), oldest first.
+    async fn recent(&self, user_id: i64, admin: bool, after: Option<u64>) -> anyhow::Result<Vec<Notification>> {
+        let rows: Vec<NotificationRow> = db::query_as(
+            "SELECT id, user_id, kind, title, message, url, created_at FROM notifications
+             WHERE id > ? AND (user_id = ? OR (for_admins AND ?))
+             ORDER BY id DESC LIMIT ?",
+        )
+        .bind(after.unwrap_or(0) as i64)
+        .bind(user_id)
+        .bind(admin)
+        .bind(RECENT_LIMIT)
+        .fetch_all(&self.db)
+        .await?;
+        Ok(rows.into_iter().rev().map(Notification::from).collect())
+    }
+
+    /// Deletes notifications older than `days`. Returns how many.
+    pub async fn prune(&self, days: u32) -> anyhow::Result<u64> {
+        let cutoff = Utc::now().timestamp_millis() - i64::from(days) * 24 * 60 * 60 * 1000;
+        let result = db::query("DELETE FROM notifications WHERE id < ?")
+            .bind(cutoff)
+            .execute(&self.db)
+            .await?;
+        Ok(result.rows_affected())
+    }
+
+    /// One user's notifications: the stored ones after `after` (or the
+    /// latest ones), then new ones as they happen. Ends when the server shuts
+    /// down. `None` when the user already has [`MAX_STREAMS_PER_USER`] open.
+    pub async fn stream(
+        &self,
+        user_id: i64,
+        admin: bool,
+        after: Option<u64>,
+    ) -> anyhow::Result<Option<ReceiverStream<Notification>>> {
+        let slot = {
+            let mut streams = self.streams.lock().unwrap();
+            let open = streams.entry(user_id).or_insert(0);
+            if *open >= MAX_STREAMS_PER_USER {
+                return Ok(None);
+            }
+            *open += 1;
+            StreamSlot {
+                streams: self.streams.clone(),
+                user_id,
+            }
+        };
+        let (tx, rx) = mpsc::channel(16);
+        // Subscribed before reading the table, so nothing sent in between is lost.
+        let mut receiver = self.sender.subscribe();
+        let mut closed = self.closed.subscribe();
+        let backlog = self.recent(user_id, admin, after).await?;
+        let mut last_sent = after.unwrap_or(0);
+
+        tokio::spawn(async move {
+            let _slot = slot;
+            for notification in backlog {
+                last_sent = notification.id;
+                if tx.send(notification).await.is_err() {
+                    return;
+                }
+            }
+            loop {
+                let notification = tokio::select! {
+                    _ = closed.wait_for(|closed| *closed) => return,
+                    received = receiver.recv() => match received {
+                        Ok(notification) => notification,
+                        Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                            warn!("Notification stream for user {} skipped {}", user_id, skipped);
+                            continue;
+                        }
+                        Err(broadcast::error::RecvError::Closed) => return,
+                    },
+                };
+                // Sent after subscribing but before the backlog was read.
+                if notification.id <= last_sent || !notification.is_for(user_id, admin) {
+                    continue;
+                }
+                last_sent = notification.id;
+                if tx.send(notification).await.is_err() {
+                    return;
+                }
+            }
+        });
+        Ok(Some(ReceiverStream::new(rx)))
+    }
+
+    /// Ends every open stream, so graceful shutdown isn't held up by them.
+    pub fn close(&self) {
+        self.closed.send_replace(true);
+    }
+}
+
+/// Tells users when a show on their watchlist airs a new episode, checking
+/// every six hours. Episodes airing on the day the server starts are not
+/// announced, so a restart doesn't repeat them.
+pub fn spawn_new_episode_job(state: AppState) {
+    tokio::spawn(async move {
+        let mut checked_through = Utc::now().date_naive();
+        let mut interval = tokio::time::interval(NEW_EPISODE_INTERVAL);
+        interval.tick().await;
+        loop {
+            interval.tick().await;
+            let today = Utc::now().date_naive();
+            if today <= checked_through {
+                continue;
+            }
+            match announce_new_episodes(&state, checked_through, today).await {
+                Ok(announced) => info!("New episode check: {} announced", announced),
+                Err(e) => error!("New episode check failed: {}", e),
+            }
+            checked_through = today;
+        }
+    });
+}
+
+/// Notifies watchlisting users of episodes that aired after `after`, up to
+/// and including `through`. Returns how many notifications were sent.
+async fn announce_new_episodes(state: &AppState, after: NaiveDate, through: NaiveDate) -> anyhow::Result<usize> {
+    let shows: Vec<i64> =
+        db::query_scalar("SELECT DISTINCT tmdb_id FROM watchlist WHERE media_type = 'tv'")
+            .fetch_all(&state.db)
+            .await?;
+
+    let mut announced = 0;
+    for tv_id in shows {
+        let show = match state.tmdb.get_tv_show(tv_id).await {
+            Ok(show) => show,
+            Err(e) => {
+                warn!("Could not check show {} for new episodes: {}", tv_id, e);
+                continue;
+            }
+        };
+        let Some(episode) = show.last_episode_to_air else {
+            continue;
+        };
+        let aired = episode
+            .air_date
+            .as_deref()
+            .and_then(|date| NaiveDate::parse_from_str(date, "%Y-%m-%d").ok());
+        if !aired.is_some_and(|aired| aired > after && aired <= through) {
+            continue;
+        }
+
+        let users: Vec<i64> =
+            db::query_scalar("SELECT user_id FROM watchlist WHERE media_type = 'tv' AND tmdb_id = ?")
+                .bind(tv_id)
+                .fetch_all(&state.db)
+                .await?;
+        let message = if episode.name.is_empty() {
+            format!("Season {}, episode {}", episode.season_number, episode.episode_number)
+        } else {
+            format!(
+                "S{}E{}: {}",
+                episode.season_number, episode.episode_number, episode.name
+            )
+        };
+        let url = format!(
+            "/player/tv/{}?season={}&episode={}",
+            tv_id, episode.season_number, episode.episode_number
+        );
+        for user_id in users {
+            state
+                .notifications
+                .notify_user(
+                    user_id,
+                    NotificationKind::NewEpisode,
+                    format!("New episode of {}", show.name),
+                    message.clone(),
+                    Some(url.clone()),
+                )
+                .await;
+            announced += 1;
+        }
+    }
+    Ok(announced)
+}
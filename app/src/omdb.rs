@@ -0,0 +1,129 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use crate::metrics::AppMetrics;
+use crate::tmdb::MovieDetail;
+
+const OMDB_BASE_URL: &str = "https://www.omdbapi.com/";
+
+/// Scores move slowly, and the free OMDb tier allows 1,000 requests a day.
+const RATINGS_CACHE_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// IMDb and Rotten Tomatoes scores as OMDb formats them, e.g. `7.8` and `93%`.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct ExternalRatings {
+    pub imdb_rating: Option<String>,
+    pub imdb_votes: Option<String>,
+    pub rotten_tomatoes: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct OmdbResponse {
+    response: String,
+    #[serde(default, rename = "imdbRating")]
+    imdb_rating: Option<String>,
+    #[serde(default, rename = "imdbVotes")]
+    imdb_votes: Option<String>,
+    #[serde(default)]
+    ratings: Vec<OmdbRating>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct OmdbRating {
+    source: String,
+    value: String,
+}
+
+type RatingsCache = Mutex<HashMap<String, (Instant, Option<ExternalRatings>)>>;
+
+/// Optional OMDb client, enabled by `OMDB_API_KEY`.
+#[derive(Debug)]
+pub struct OmdbClient {
+    client: Client,
+    api_key: String,
+    metrics: Arc<AppMetrics>,
+    cache: RatingsCache,
+}
+
+impl OmdbClient {
+    pub fn new(api_key: &str, metrics: Arc<AppMetrics>) -> anyhow::Result<Self> {
+        let client = Client::builder()
+            .timeout(Duration::from_secs(10))
+            .build()?;
+
+        Ok(Self {
+            client,
+            api_key: api_key.trim().to_string(),
+            metrics,
+            cache: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// Scores for an IMDb id such as `tt0111161`, or `None` if OMDb has none.
+    pub async fn get_ratings(&self, imdb_id: &str) -> anyhow::Result<Option<ExternalRatings>> {
+        let cached = self
+            .cache
+            .lock()
+            .unwrap()
+            .get(imdb_id)
+            .filter(|(stored_at, _)| stored_at.elapsed() < RATINGS_CACHE_TTL)
+            .map(|(_, ratings)| ratings.clone());
+        self.metrics.record_cache("omdb", cached.is_some());
+        if let Some(ratings) = cached {
+            return Ok(ratings);
+        }
+
+        let response = self
+            .client
+            .get(OMDB_BASE_URL)
+            .query(&[("apikey", self.api_key.as_str()), ("i", imdb_id)])
+            .send()
+            .await?;
+        if !response.status().is_success() {
+            return Err(anyhow::anyhow!("OMDb returned {}", response.status()));
+        }
+
+        let body: OmdbResponse = response.json().await?;
+        let ratings = (body.response == "True").then(|| {
+            let rotten_tomatoes = body
+                .ratings
+                .iter()
+                .find(|r| r.source == "Rotten Tomatoes")
+                .map(|r| r.value.clone());
+            ExternalRatings {
+                imdb_rating: available(body.imdb_rating),
+                imdb_votes: available(body.imdb_votes),
+                rotten_tomatoes: available(rotten_tomatoes),
+            }
+        });
+
+        self.cache
+            .lock()
+            .unwrap()
+            .insert(imdb_id.to_string(), (Instant::now(), ratings.clone()));
+        Ok(ratings)
+    }
+
+    /// Fills in `external_ratings`. OMDb being down never fails the page.
+    pub async fn enrich(&self, movie: &mut MovieDetail) {
+        let Some(imdb_id) = movie.imdb_id.as_deref().filter(|id| !id.is_empty()) else {
+            return;
+        };
+        match self.get_ratings(imdb_id).await {
+            Ok(ratings) => movie.external_ratings = ratings,
+            Err(e) => warn!("Could not load OMDb ratings for {}: {}", imdb_id, e),
+        }
+    }
+}
+
+/// OMDb reports missing values as `N/A`.
+fn available(value: Option<String>) -> Option<String> {
+    value.filter(|v| v != "N/A")
+}
@@ -0,0 +1,1311 @@
+//! The OpenAPI description of `/api/v1`, served at `/api/v1/openapi.json`
+//! and browsable at `/api/v1/docs`.
+//!
+//! Kept by hand next to the routes in `api.rs`: when an endpoint or a field
+//! it returns changes, change its entry here too.
+
+use once_cell::sync::Lazy;
+use serde_json::{json, Map, Value};
+
+use crate::auth::SESSION_COOKIE;
+
+static DOCUMENT: Lazy<Value> = Lazy::new(build_document);
+
+/// The OpenAPI 3.1 document for the JSON API.
+pub fn document() -> &'static Value {
+    &DOCUMENT
+}
+
+/// Who may call an endpoint.
+#[derive(Clone, Copy)]
+enum Access {
+    /// Anyone, even with `AUTH_MODE=accounts`.
+    Public,
+    /// Anyone with a session; in local mode, everyone.
+    Session,
+    /// A session whose role has this permission.
+    Permission(&'static str),
+}
+
+struct Param {
+    name: &'static str,
+    schema: Value,
+    description: &'static str,
+    required: bool,
+}
+
+fn query(name: &'static str, schema: Value, description: &'static str) -> Param {
+    Param { name, schema, description, required: false }
+}
+
+fn required_query(name: &'static str, schema: Value, description: &'static str) -> Param {
+    Param { name, schema, description, required: true }
+}
+
+struct Operation {
+    method: &'static str,
+    path: &'static str,
+    tag: &'static str,
+    summary: &'static str,
+    access: Access,
+    query: Vec<Param>,
+    /// Path parameters that are strings; all others are integers.
+    string_params: &'static [&'static str],
+    body: Option<Value>,
+    /// Media types the body may be sent as.
+    body_types: &'static [&'static str],
+    status: &'static str,
+    response: Option<Value>,
+    response_type: &'static str,
+    /// Also answers with `text/csv` when asked to.
+    csv: bool,
+    /// Also answers `202` with this schema when the work continues in the background.
+    accepted: Option<Value>,
+}
+
+impl Operation {
+    fn new(method: &'static str, path: &'static str, tag: &'static str, summary: &'static str) -> Self {
+        Self {
+            method,
+            path,
+            tag,
+            summary,
+            access: Access::Session,
+            query: Vec::new(),
+            string_params: &["media_type", "time_window", "imdb_id", "token", "code", "role", "name"],
+            body: None,
+            body_types: &["application/json"],
+            status: "200",
+            response: Some(json!({ "type": "object" })),
+            response_type: "application/json",
+            csv: false,
+            accepted: None,
+        }
+    }
+
+    fn get(path: &'static str, tag: &'static str, summary: &'static str) -> Self {
+        Self::new("get", path, tag, summary)
+    }
+
+    fn post(path: &'static str, tag: &'static str, summary: &'static str) -> Self {
+        Self::new("post", path, tag, summary)
+    }
+
+    fn put(path: &'static str, tag: &'static str, summary: &'static str) -> Self {
+        Self::new("put", path, tag, summary)
+    }
+
+    fn patch(path: &'static str, tag: &'static str, summary: &'static str) -> Self {
+        Self::new("patch", path, tag, summary)
+    }
+
+    fn delete(path: &'static str, tag: &'static str, summary: &'static str) -> Self {
+        Self::new("delete", path, tag, summary)
+    }
+
+    fn public(mut self) -> Self {
+        self.access = Access::Public;
+        self
+    }
+
+    fn permission(mut self, permission: &'static str) -> Self {
+        self.access = Access::Permission(permission);
+        self
+    }
+
+    fn query(mut self, params: Vec<Param>) -> Self {
+        self.query = params;
+        self
+    }
+
+    fn paged(self) -> Self {
+        self.query(vec![page_param()])
+    }
+
+    fn string_id(mut self) -> Self {
+        self.string_params = &["id"];
+        self
+    }
+
+    fn body(mut self, schema: Value) -> Self {
+        self.body = Some(schema);
+        self
+    }
+
+    /// A file uploaded as the request body, in any of `types`.
+    fn file_body(mut self, types: &'static [&'static str]) -> Self {
+        self.body = Some(json!({ "type": "string" }));
+        self.body_types = types;
+        self
+    }
+
+    fn returns(mut self, schema: Value) -> Self {
+        self.response = Some(schema);
+        self
+    }
+
+    fn created(mut self, schema: Value) -> Self {
+        self.status = "201";
+        self.response = Some(schema);
+        self
+    }
+
+    /// Server-sent events whose data is `schema`.
+    fn event_stream(mut self, schema: Value) -> Self {
+        self.response = Some(schema);
+        self.response_type = "text/event-stream";
+        self
+    }
+
+    fn or_accepted(mut self, schema: Value) -> Self {
+        self.accepted = Some(schema);
+        self
+    }
+
+    fn or_csv(mut self) -> Self {
+        self.csv = true;
+        self
+    }
+
+    fn no_content(mut self) -> Self {
+        self.status = "204";
+        self.response = None;
+        self
+    }
+
+    fn to_json(&self) -> Value {
+        let mut parameters: Vec<Value> = path_params(self.path)
+            .map(|name| {
+                let kind = if self.string_params.contains(&name) { "string" } else { "integer" };
+                json!({ "name": name, "in": "path", "required": true, "schema": { "type": kind } })
+            })
+            .collect();
+        parameters.extend(self.query.iter().map(|param| {
+            json!({
+                "name": param.name,
+                "in": "query",
+                "required": param.required,
+                "description": param.description,
+                "schema": param.schema,
+            })
+        }));
+
+        let mut success = match self.response.as_ref().map(enveloped) {
+            Some(schema) => json!({
+                "description": "Success",
+                "content": { self.response_type: { "schema": schema } },
+            }),
+            None => json!({ "description": "Success" }),
+        };
+        if self.csv {
+            success["content"]["text/csv"] = json!({ "schema": { "type": "string" } });
+        }
+        let mut responses = Map::new();
+        responses.insert(self.status.to_string(), success);
+        if let Some(schema) = &self.accepted {
+            responses.insert(
+                "202".to_string(),
+                json!({
+                    "description": "Accepted; continues in the background",
+                    "content": { "application/json": { "schema": schema } },
+                }),
+            );
+        }
+        responses.insert("default".to_string(), json!({ "$ref": "#/components/responses/Error" }));
+
+        let mut operation = json!({
+            "tags": [self.tag],
+            "summary": self.summary,
+            "parameters": parameters,
+            "responses": responses,
+        });
+        match self.access {
+            Access::Public => {
+                operation["security"] = json!([]);
+            }
+            Access::Session => {}
+            Access::Permission(permission) => {
+                operation["x-required-permission"] = json!(permission);
+            }
+        }
+        if let Some(body) = &self.body {
+            let content: Map<String, Value> = self
+                .body_types
+                .iter()
+                .map(|media_type| (media_type.to_string(), json!({ "schema": body })))
+                .collect();
+            operation["requestBody"] = json!({ "required": true, "content": content });
+        }
+        operation
+    }
+}
+
+/// Lists are answered as `{"results": [...]}`; see `api::v1_envelope`.
+fn enveloped(schema: &Value) -> Value {
+    if schema["type"] == "array" {
+        json!({ "type": "object", "required": ["results"], "properties": { "results": schema } })
+    } else {
+        schema.clone()
+    }
+}
+
+/// Names of the `{name}` segments in an OpenAPI path.
+fn path_params(path: &str) -> impl Iterator<Item = &str> {
+    path.split('/')
+        .filter_map(|segment| segment.strip_prefix('{')?.strip_suffix('}'))
+}
+
+fn schema(name: &str) -> Value {
+    json!({ "$ref": format!("#/components/schemas/{}", name) })
+}
+
+fn list_of(name: &str) -> Value {
+    json!({ "type": "array", "items": schema(name) })
+}
+
+fn nullable(kind: &str) -> Value {
+    json!({ "type": [kind, "null"] })
+}
+
+fn page_param() -> Param {
+    query(
+        "page",
+        json!({ "type": "integer", "minimum": 1, "maximum": crate::tmdb::TMDB_MAX_PAGE, "default": 1 }),
+        "Page of the TMDB listing",
+    )
+}
+
+fn media_type_param() -> Param {
+    required_query("media_type", json!({ "type": "string", "enum": ["movie", "tv"] }), "")
+}
+
+fn operations() -> Vec<Operation> {
+    vec![
+        // Catalog
+        Operation::get("/movies/popular", "Catalog", "Popular movies in TMDB_REGION")
+            .paged()
+            .returns(schema("Page")),
+        Operation::get("/movies/now_playing", "Catalog", "Movies in theaters in TMDB_REGION")
+            .paged()
+            .returns(schema("Page")),
+        Operation::get("/movies/upcoming", "Catalog", "Movies releasing soon in TMDB_REGION")
+            .paged()
+            .returns(schema("Page")),
+        Operation::get("/movies/top_rated", "Catalog", "Highest rated movies")
+            .paged()
+            .returns(schema("Page")),
+        Operation::get("/tv/popular", "Catalog", "Popular TV shows")
+            .paged()
+            .returns(schema("Page")),
+        Operation::get("/tv/top_rated", "Catalog", "Highest rated TV shows")
+            .paged()
+            .returns(schema("Page")),
+        Operation::get("/trending/{media_type}/{time_window}", "Catalog", "Trending titles or people")
+            .paged()
+            .returns(schema("Page")),
+        Operation::get("/people/trending", "Catalog", "People trending this week")
+            .paged()
+            .returns(schema("Page")),
+        Operation::get("/movie/{id}", "Catalog", "Movie details, certification, and ratings")
+            .returns(schema("MovieDetail")),
+        Operation::get("/tv/{id}", "Catalog", "TV show details and certification")
+            .returns(schema("TvShowDetail")),
+        Operation::get("/tv/{id}/season/{season_number}", "Catalog", "A season and all of its episodes")
+            .returns(schema("SeasonDetail")),
+        Operation::get("/{media_type}/{id}/providers", "Catalog", "Where a title streams, rents, or sells")
+            .query(vec![query("region", json!({ "type": "string" }), "ISO 3166-1 country; TMDB_REGION when unset")])
+            .returns(schema("WatchProviders")),
+        Operation::get("/{media_type}/{id}/recommendations", "Catalog", "TMDB recommendations")
+            .paged()
+            .returns(schema("Page")),
+        Operation::get("/find/{imdb_id}", "Catalog", "Look up a title by IMDb id or URL")
+            .returns(schema("FindResponse")),
+        Operation::get("/genres", "Catalog", "Movie and TV genres, merged by id")
+            .query(vec![query("media_type", json!({ "type": "string", "enum": ["movie", "tv"] }), "Only this type's genres")])
+            .returns(list_of("MediaGenre")),
+        // Search
+        Operation::get("/search", "Search", "Search titles, or people only")
+            .query(vec![
+                query(
+                    "q",
+                    json!({ "type": "string" }),
+                    "Search text, or a genre:, actor:, director:, or keyword: prefix. Required without filters",
+                ),
+                page_param(),
+                query("limit", json!({ "type": "integer", "maximum": 200 }), "Gather this many results across pages"),
+                query(
+                    "media_type",
+                    json!({ "type": "string", "enum": ["movie", "tv", "person"] }),
+                    "Only people, or only titles of one type",
+                ),
+                query("year", json!({ "type": "integer" }), ""),
+                query("genre", json!({ "type": "string" }), "Genre id or name"),
+                query("min_rating", json!({ "type": "number" }), ""),
+                query(
+                    "sort_by",
+                    json!({
+                        "type": "string",
+                        "enum": ["popularity.desc", "vote_average.desc", "release_date.desc", "revenue.desc"],
+                    }),
+                    "",
+                ),
+            ])
+            .returns(schema("Page")),
+        Operation::get("/search/suggest", "Search", "Typeahead matches, past searches first")
+            .query(vec![query("q", json!({ "type": "string" }), "Partial search text")])
+            .returns(list_of("Suggestion")),
+        Operation::get("/search/history", "Search", "Your most recent searches")
+            .returns(list_of("SearchHistoryEntry")),
+        Operation::delete("/search/history", "Search", "Clear your search history").no_content(),
+        Operation::delete("/search/history/{id}", "Search", "Forget one search").no_content(),
+        Operation::get("/discover", "Search", "Discover movies and TV with one set of filters")
+            .query(vec![
+                query("q", json!({ "type": "string" }), "Text, or a genre:, actor:, director:, or keyword: prefix"),
+                query("type", json!({ "type": "string", "enum": ["movie", "tv"] }), "Both when unset"),
+                query("genre", json!({ "type": "string" }), "Genre id"),
+                query("year", json!({ "type": "integer" }), ""),
+                query("min_rating", json!({ "type": "number" }), ""),
+                query("sort_by", json!({ "type": "string" }), "TMDB sort, e.g. popularity.desc"),
+                query("network", json!({ "type": "integer" }), "TMDB network id; TV only"),
+                query("company", json!({ "type": "integer" }), "TMDB production company id"),
+                query("cursor", json!({ "type": "string" }), "next_cursor from the previous page"),
+                query("filmography", json!({ "type": "boolean" }), "Full credits for an actor: or director: query"),
+            ])
+            .returns(schema("DiscoverPage")),
+        // Streams
+        Operation::get("/providers", "Streams", "Enabled stream providers with their health and latency")
+            .returns(list_of("ProviderStatus")),
+        Operation::get("/movie/{id}/streams", "Streams", "Resolve streams for a movie")
+            .returns(schema("StreamResolution")),
+        Operation::get("/tv/{id}/streams", "Streams", "Resolve streams for an episode")
+            .query(vec![
+                query("season", json!({ "type": "integer" }), ""),
+                query("episode", json!({ "type": "integer" }), ""),
+            ])
+            .returns(schema("StreamResolution")),
+        Operation::post("/streams/report", "Streams", "Report a broken stream")
+            .body(schema("StreamReport")),
+        Operation::post("/playback/heartbeat", "Streams", "Player state, for failover and QoS")
+            .body(schema("Heartbeat"))
+            .returns(schema("HeartbeatOutcome")),
+        Operation::post("/progress", "Streams", "Save playback progress to watch history")
+            .body(schema("ProgressUpdate")),
+        Operation::post("/player/quality", "Streams", "Set or clear your maximum quality")
+            .body(json!({
+                "type": "object",
+                "required": ["max_quality"],
+                "properties": { "max_quality": nullable("integer") },
+            })),
+        // Account
+        Operation::post("/auth/login", "Account", "Sign in; sets the session cookie")
+            .public()
+            .body(schema("Credentials"))
+            .returns(schema("SignedIn")),
+        Operation::post("/auth/register", "Account", "Create an account with an invite code")
+            .public()
+            .body(schema("Registration"))
+            .returns(schema("SignedIn")),
+        Operation::get("/auth/sessions", "Account", "Your signed-in sessions")
+            .returns(list_of("Session")),
+        Operation::delete("/auth/sessions", "Account", "Sign out everywhere")
+            .returns(json!({ "type": "object", "properties": { "revoked": { "type": "integer" } } })),
+        Operation::delete("/auth/sessions/{id}", "Account", "Sign out one session").string_id(),
+        Operation::get("/settings", "Account", "Your display and player preferences")
+            .returns(schema("Settings")),
+        Operation::put("/settings", "Account", "Change preferences; omitted fields are unchanged")
+            .permission("edit_preferences")
+            .body(schema("Settings"))
+            .returns(schema("Settings")),
+        Operation::get("/account/export", "Account", "Download everything stored about you")
+            .returns(json!({ "type": "object" })),
+        Operation::get("/export", "Account", "Download your history, watchlist, ratings, and settings")
+            .query(vec![query(
+                "format",
+                json!({ "type": "string", "enum": ["json", "csv"], "default": "json" }),
+                "csv for just the watch history as CSV",
+            )])
+            .returns(schema("DataExport"))
+            .or_csv(),
+        Operation::get("/events", "Account", "Your notifications as server-sent events")
+            .event_stream(schema("Notification")),
+        Operation::post("/import", "Account", "Add a Trakt or Netflix export to your watch history")
+            .query(vec![
+                query(
+                    "format",
+                    json!({ "type": "string", "enum": ["trakt", "netflix"] }),
+                    "Guessed from the body when left out: JSON is Trakt, anything else Netflix",
+                ),
+                query("dry_run", json!({ "type": "boolean" }), "Report what would be imported without saving it"),
+                query(
+                    "day_first",
+                    json!({ "type": "boolean" }),
+                    "Read Netflix dates like 03/04/2023 as 3 April; guessed from the file when left out",
+                ),
+            ])
+            .file_body(&["application/json", "text/csv"])
+            .returns(schema("ImportReport"))
+            .or_accepted(schema("ImportJob")),
+        Operation::get("/import/status", "Account", "Your latest background import")
+            .returns(schema("ImportJob")),
+        Operation::delete("/account", "Account", "Delete your account")
+            .body(json!({
+                "type": "object",
+                "required": ["password"],
+                "properties": { "password": { "type": "string" } },
+            })),
+        // Library
+        Operation::get("/watchlist", "Library", "Your watchlist, newest first")
+            .returns(list_of("WatchlistItem")),
+        Operation::post("/watchlist", "Library", "Add a title to your watchlist")
+            .body(schema("TitleInput"))
+            .returns(schema("Added")),
+        Operation::get("/watchlist/{media_type}/{id}", "Library", "Whether a title is on your watchlist")
+            .returns(json!({ "type": "object", "properties": { "on_watchlist": { "type": "boolean" } } })),
+        Operation::delete("/watchlist/{media_type}/{id}", "Library", "Remove a title from your watchlist"),
+        Operation::get("/continue-watching", "Library", "Titles left partway through")
+            .returns(list_of("ContinueWatchingItem")),
+        Operation::get("/history", "Library", "Your watch history, most recent first")
+            .query(vec![
+                query("page", json!({ "type": "integer", "minimum": 1, "default": 1 }), "Page of history"),
+                query(
+                    "per_page",
+                    json!({ "type": "integer", "minimum": 1, "maximum": 200, "default": 50 }),
+                    "Entries per page",
+                ),
+            ])
+            .returns(schema("HistoryPage")),
+        Operation::delete("/history", "Library", "Clear your watch history")
+            .returns(json!({ "type": "object", "properties": { "removed": { "type": "integer" } } })),
+        Operation::delete("/history/{id}", "Library", "Remove one history entry").no_content(),
+        Operation::get("/stats", "Library", "Your watch time by day, genre, and weekday")
+            .query(vec![query("days", json!({ "type": "integer" }), "Only the last this many days")])
+            .returns(schema("StatsSummary")),
+        Operation::get("/playlists", "Library", "Your playlists").returns(list_of("Playlist")),
+        Operation::post("/playlists", "Library", "Create a playlist")
+            .body(json!({ "type": "object", "required": ["name"], "properties": { "name": { "type": "string" } } }))
+            .created(schema("Playlist")),
+        Operation::get("/playlists/{id}", "Library", "A playlist with its titles in order")
+            .returns(schema("PlaylistWithItems")),
+        Operation::patch("/playlists/{id}", "Library", "Rename, share, or unshare a playlist")
+            .body(json!({
+                "type": "object",
+                "properties": { "name": { "type": "string" }, "shared": { "type": "boolean" } },
+            }))
+            .returns(schema("Playlist")),
+        Operation::delete("/playlists/{id}", "Library", "Delete a playlist"),
+        Operation::post("/playlists/{id}/items", "Library", "Add a title to the end of a playlist")
+            .body(schema("TitleInput"))
+            .returns(schema("Added")),
+        Operation::put("/playlists/{id}/items", "Library", "Reorder a playlist; list every title once")
+            .body(json!({ "type": "object", "required": ["items"], "properties": { "items": list_of("TitleRef") } }))
+            .returns(schema("PlaylistWithItems")),
+        Operation::delete("/playlists/{id}/items/{media_type}/{tmdb_id}", "Library", "Remove a title from a playlist"),
+        Operation::get("/shared/playlists/{token}", "Library", "A playlist shared by link")
+            .public()
+            .returns(schema("PlaylistWithItems")),
+        // Admin
+        Operation::get("/admin/invites", "Admin", "Invite codes")
+            .permission("manage_users")
+            .returns(list_of("Invite")),
+        Operation::post("/admin/invites", "Admin", "Create an invite code")
+            .permission("manage_users")
+            .body(json!({
+                "type": "object",
+                "properties": {
+                    "role": schema("Role"),
+                    "expires_in_days": { "type": "integer", "description": "Never expires when omitted" },
+                },
+            }))
+            .returns(schema("Invite")),
+        Operation::delete("/admin/invites/{code}", "Admin", "Revoke an unused invite").permission("manage_users"),
+        Operation::get("/admin/roles", "Admin", "What each role may do")
+            .permission("manage_users")
+            .returns(list_of("RolePermissions")),
+        Operation::put("/admin/roles/{role}", "Admin", "Replace a role's permissions")
+            .permission("manage_users")
+            .body(json!({ "type": "object", "required": ["permissions"], "properties": { "permissions": list_of("Permission") } }))
+            .returns(schema("RolePermissions")),
+        Operation::get("/admin/providers", "Admin", "Stream providers and their settings")
+            .permission("manage_settings")
+            .returns(list_of("ProviderConfig")),
+        Operation::get("/admin/providers/stats", "Admin", "Provider latency, success rate, and score over the last 7 days")
+            .permission("manage_settings")
+            .returns(list_of("ProviderStats")),
+        Operation::put("/admin/providers/{name}", "Admin", "Enable, disable, or reprioritize a provider")
+            .permission("manage_settings")
+            .body(schema("ProviderSetting"))
+            .returns(list_of("ProviderConfig")),
+        Operation::delete("/admin/providers/{name}", "Admin", "Reset a provider to its defaults")
+            .permission("manage_settings")
+            .returns(list_of("ProviderConfig")),
+        Operation::get("/admin/local-media", "Admin", "Files mapped from MEDIA_DIR")
+            .permission("manage_settings")
+            .returns(list_of("LocalMedia")),
+        Operation::put("/admin/local-media", "Admin", "Map a file to a title or episode")
+            .permission("manage_settings")
+            .body(schema("LocalMedia"))
+            .returns(schema("LocalMedia")),
+        Operation::delete("/admin/local-media/{id}", "Admin", "Remove a file mapping").permission("manage_settings"),
+        Operation::get("/admin/stream-overrides", "Admin", "Pinned sources")
+            .permission("manage_settings")
+            .returns(list_of("StreamOverride")),
+        Operation::put("/admin/stream-overrides", "Admin", "Pin a source for a title or episode")
+            .permission("manage_settings")
+            .body(schema("StreamOverride"))
+            .returns(schema("StreamOverride")),
+        Operation::delete("/admin/stream-overrides", "Admin", "Unpin a source")
+            .permission("manage_settings")
+            .query(vec![
+                required_query("tmdb_id", json!({ "type": "integer" }), ""),
+                media_type_param(),
+                query("season", json!({ "type": "integer" }), ""),
+                query("episode", json!({ "type": "integer" }), ""),
+            ]),
+        Operation::get("/admin/webhooks", "Admin", "Registered webhooks")
+            .permission("manage_settings")
+            .returns(list_of("Webhook")),
+        Operation::post("/admin/webhooks", "Admin", "Register a webhook")
+            .permission("manage_settings")
+            .body(json!({
+                "type": "object",
+                "required": ["url", "events"],
+                "properties": {
+                    "url": { "type": "string", "format": "uri" },
+                    "events": { "type": "array", "items": schema("WebhookEvent"), "minItems": 1 },
+                },
+            }))
+            .created(json!({
+                "allOf": [
+                    schema("Webhook"),
+                    {
+                        "type": "object",
+                        "properties": { "secret": { "type": "string", "description": "Shown only once" } },
+                    },
+                ],
+            })),
+        Operation::patch("/admin/webhooks/{id}", "Admin", "Pause or resume a webhook")
+            .permission("manage_settings")
+            .body(json!({ "type": "object", "required": ["enabled"], "properties": { "enabled": { "type": "boolean" } } }))
+            .returns(schema("Webhook")),
+        Operation::delete("/admin/webhooks/{id}", "Admin", "Delete a webhook and its deliveries")
+            .permission("manage_settings")
+            .no_content(),
+        Operation::get("/admin/webhooks/{id}/deliveries", "Admin", "A webhook's 50 latest deliveries")
+            .permission("manage_settings")
+            .returns(list_of("WebhookDelivery")),
+        // Instance
+        Operation::get("/metrics", "Instance", "Counters, cache hit rates, and TMDB key usage")
+            .permission("manage_settings")
+            .returns(json!({ "type": "object" })),
+        Operation::get("/version", "Instance", "Version, build, and enabled features")
+            .returns(schema("VersionInfo")),
+    ]
+}
+
+fn schemas() -> Value {
+    let string = json!({ "type": "string" });
+    let integer = json!({ "type": "integer" });
+    let number = json!({ "type": "number" });
+    let boolean = json!({ "type": "boolean" });
+    let media_type = json!({ "type": "string", "enum": ["movie", "tv"] });
+
+    let mut schemas = Map::new();
+    schemas.insert("Problem".to_string(), json!({
+        "type": "object",
+        "description": "RFC 7807 problem details",
+        "required": ["type", "title", "status", "detail", "code", "error"],
+        "properties": {
+            "type": { "type": "string", "description": "Always about:blank" },
+            "title": { "type": "string", "description": "The HTTP status's reason phrase" },
+            "status": integer,
+            "detail": { "type": "string", "description": "Human-readable message" },
+            "code": {
+                "type": "string",
+                "description": "Stable for scripts. Specific codes such as season_required or tmdb_unavailable where one applies, otherwise one named after the status, such as not_found or unauthorized.",
+                "examples": [
+                    "bad_request", "unauthorized", "forbidden", "not_found", "conflict",
+                    "too_many_requests", "internal_error", "tmdb_error", "tmdb_unavailable",
+                    "invalid_media_type", "invalid_page", "invalid_sort",
+                    "invalid_cursor", "invalid_imdb_id", "invalid_url", "invalid_format", "invalid_import", "query_required",
+                    "season_required", "episode_required", "title_required", "unknown_provider",
+                ],
+            },
+            "error": { "type": "string", "description": "Same as detail, kept for older clients" },
+        },
+    }));
+    schemas.insert("SearchResult".to_string(), json!({
+        "type": "object",
+        "description": "A movie, show, or person from a TMDB listing. Movies have `title` and `release_date`; shows `name` and `first_air_date`.",
+        "required": ["id", "media_type"],
+        "properties": {
+            "id": integer,
+            "media_type": { "type": "string", "enum": ["movie", "tv", "person"] },
+            "title": nullable("string"),
+            "name": nullable("string"),
+            "overview": nullable("string"),
+            "poster_path": nullable("string"),
+            "backdrop_path": nullable("string"),
+            "release_date": nullable("string"),
+            "first_air_date": nullable("string"),
+            "vote_average": number,
+            "vote_count": integer,
+            "genre_ids": { "type": ["array", "null"], "items": integer },
+            "popularity": number,
+        },
+        "additionalProperties": true,
+    }));
+    schemas.insert("Page".to_string(), json!({
+        "type": "object",
+        "required": ["page", "results", "total_pages", "total_results"],
+        "properties": {
+            "page": integer,
+            "results": list_of("SearchResult"),
+            "total_pages": integer,
+            "total_results": integer,
+        },
+    }));
+    schemas.insert("DiscoverPage".to_string(), json!({
+        "type": "object",
+        "properties": {
+            "results": list_of("SearchResult"),
+            "total_results": integer,
+            "total_pages": integer,
+            "next_cursor": { "type": ["string", "null"], "description": "Absent on the last page" },
+        },
+    }));
+    schemas.insert("MediaGenre".to_string(), json!({
+        "type": "object",
+        "properties": {
+            "id": integer,
+            "name": string,
+            "media_types": { "type": "array", "items": media_type },
+        },
+    }));
+    schemas.insert("FindResponse".to_string(), json!({
+        "type": "object",
+        "properties": { "movie_results": list_of("SearchResult"), "tv_results": list_of("SearchResult") },
+    }));
+    schemas.insert("Suggestion".to_string(), json!({
+        "type": "object",
+        "properties": { "id": integer, "media_type": media_type, "title": string, "year": nullable("integer") },
+    }));
+    schemas.insert("SearchHistoryEntry".to_string(), json!({
+        "type": "object",
+        "properties": { "id": integer, "query": string, "search_count": integer, "last_searched_at": string },
+    }));
+    schemas.insert("MovieDetail".to_string(), json!({
+        "type": "object",
+        "description": "TMDB movie details with credits, similar titles, and recommendations appended.",
+        "properties": {
+            "id": integer,
+            "title": string,
+            "overview": nullable("string"),
+            "poster_path": nullable("string"),
+            "backdrop_path": nullable("string"),
+            "release_date": nullable("string"),
+            "runtime": nullable("integer"),
+            "vote_average": number,
+            "genres": { "type": "array", "items": { "type": "object" } },
+            "certification": { "type": ["object", "null"] },
+            "imdb_id": nullable("string"),
+            "external_ratings": { "type": ["object", "null"] },
+            "external_ids": { "type": ["object", "null"] },
+        },
+        "additionalProperties": true,
+    }));
+    schemas.insert("SeasonDetail".to_string(), json!({
+        "type": "object",
+        "properties": {
+            "id": integer,
+            "season_number": integer,
+            "name": string,
+            "overview": nullable("string"),
+            "air_date": nullable("string"),
+            "poster_path": nullable("string"),
+            "episodes": {
+                "type": "array",
+                "items": {
+                    "type": "object",
+                    "properties": {
+                        "id": integer,
+                        "episode_number": integer,
+                        "name": string,
+                        "overview": nullable("string"),
+                        "still_path": nullable("string"),
+                        "air_date": nullable("string"),
+                        "runtime": { "type": ["integer", "null"], "description": "Minutes" },
+                        "vote_average": number,
+                    },
+                },
+            },
+        },
+    }));
+    schemas.insert("TvShowDetail".to_string(), json!({
+        "type": "object",
+        "description": "TMDB show details with credits, similar titles, and recommendations appended.",
+        "properties": {
+            "id": integer,
+            "name": string,
+            "overview": nullable("string"),
+            "poster_path": nullable("string"),
+            "backdrop_path": nullable("string"),
+            "first_air_date": nullable("string"),
+            "number_of_seasons": nullable("integer"),
+            "number_of_episodes": nullable("integer"),
+            "vote_average": number,
+            "genres": { "type": "array", "items": { "type": "object" } },
+            "seasons": { "type": "array", "items": { "type": "object" } },
+            "networks": { "type": "array", "items": { "type": "object" } },
+            "certification": { "type": ["object", "null"] },
+            "external_ids": { "type": ["object", "null"] },
+        },
+        "additionalProperties": true,
+    }));
+    schemas.insert("WatchProviders".to_string(), json!({
+        "type": "object",
+        "properties": {
+            "link": nullable("string"),
+            "flatrate": list_of("WatchProvider"),
+            "free": list_of("WatchProvider"),
+            "ads": list_of("WatchProvider"),
+            "rent": list_of("WatchProvider"),
+            "buy": list_of("WatchProvider"),
+        },
+    }));
+    schemas.insert("WatchProvider".to_string(), json!({
+        "type": "object",
+        "properties": {
+            "provider_id": integer,
+            "provider_name": string,
+            "logo_path": nullable("string"),
+            "display_priority": integer,
+        },
+    }));
+    schemas.insert("StreamResolution".to_string(), json!({
+        "type": "object",
+        "description": "Returned with 200 when streams were found, 404 when no provider has the title, and 503 when a provider failed.",
+        "properties": {
+            "status": { "type": "string", "enum": ["available", "not_found", "provider_down"] },
+            "streams": list_of("StreamSource"),
+            "providers": {
+                "type": "array",
+                "items": {
+                    "type": "object",
+                    "properties": {
+                        "provider": string,
+                        "status": { "type": "string", "enum": ["available", "not_found", "provider_down"] },
+                        "error": string,
+                    },
+                },
+            },
+        },
+    }));
+    schemas.insert("StreamSource".to_string(), json!({
+        "type": "object",
+        "properties": {
+            "id": string,
+            "name": string,
+            "quality": nullable("string"),
+            "language": nullable("string"),
+            "server": { "type": "string", "description": "Provider that produced the stream" },
+            "kind": { "type": "string", "enum": ["embed", "hls", "file"] },
+        },
+    }));
+    schemas.insert("StreamReport".to_string(), json!({
+        "type": "object",
+        "required": ["tmdb_id", "media_type", "provider"],
+        "properties": {
+            "tmdb_id": integer,
+            "media_type": media_type,
+            "provider": string,
+            "season": nullable("integer"),
+            "episode": nullable("integer"),
+            "reason": nullable("string"),
+        },
+    }));
+    schemas.insert("Heartbeat".to_string(), json!({
+        "type": "object",
+        "required": ["playback_id", "tmdb_id", "media_type", "state"],
+        "properties": {
+            "playback_id": { "type": "string", "description": "Random id generated once per player load" },
+            "tmdb_id": integer,
+            "media_type": media_type,
+            "season": nullable("integer"),
+            "episode": nullable("integer"),
+            "provider": nullable("string"),
+            "state": { "type": "string", "enum": ["playing", "paused", "buffering", "error", "ended"] },
+            "position": number,
+            "detail": nullable("string"),
+        },
+    }));
+    schemas.insert("HeartbeatOutcome".to_string(), json!({
+        "type": "object",
+        "properties": { "suggest_switch": boolean },
+    }));
+    schemas.insert("ProgressUpdate".to_string(), json!({
+        "type": "object",
+        "required": ["tmdb_id", "media_type", "progress", "current_time", "duration", "title", "completed"],
+        "properties": {
+            "tmdb_id": integer,
+            "media_type": media_type,
+            "progress": { "type": "number", "description": "Percent watched" },
+            "current_time": { "type": "number", "description": "Seconds" },
+            "duration": { "type": "number", "description": "Seconds" },
+            "season": nullable("integer"),
+            "episode": nullable("integer"),
+            "title": string,
+            "poster_path": nullable("string"),
+            "episode_title": nullable("string"),
+            "completed": boolean,
+        },
+    }));
+    schemas.insert("Credentials".to_string(), json!({
+        "type": "object",
+        "required": ["username", "password"],
+        "properties": {
+            "username": string,
+            "password": string,
+            "remember": { "type": "boolean", "description": "Keep the session for a week" },
+        },
+    }));
+    schemas.insert("Registration".to_string(), json!({
+        "type": "object",
+        "required": ["invite_code", "username", "password"],
+        "properties": { "invite_code": string, "username": string, "password": string },
+    }));
+    schemas.insert("SignedIn".to_string(), json!({
+        "type": "object",
+        "properties": {
+            "user_id": integer,
+            "username": string,
+            "role": schema("Role"),
+            "token": { "type": "string", "description": format!("Also set as the `{}` cookie", SESSION_COOKIE) },
+            "evicted_sessions": { "type": "integer", "description": "Older sessions signed out to stay within MAX_SESSIONS_PER_USER" },
+        },
+    }));
+    schemas.insert("Session".to_string(), json!({
+        "type": "object",
+        "properties": {
+            "id": string,
+            "created_at": string,
+            "last_seen_at": nullable("integer"),
+            "user_agent": nullable("string"),
+            "ip": nullable("string"),
+            "expires_at": integer,
+            "current": { "type": "boolean", "description": "Whether this is the session making the request" },
+        },
+    }));
+    schemas.insert("Settings".to_string(), json!({
+        "type": "object",
+        "properties": {
+            "image_quality": { "type": "string", "enum": ["low", "standard", "high"] },
+            "data_saver": boolean,
+            "pause_history": boolean,
+            "accent_color": nullable("string"),
+            "autoplay": boolean,
+            "next_episode": boolean,
+            "episode_selector": boolean,
+            "audio_language": nullable("string"),
+            "max_quality": nullable("integer"),
+        },
+    }));
+    schemas.insert("TitleInput".to_string(), json!({
+        "type": "object",
+        "required": ["tmdb_id", "media_type", "title"],
+        "properties": {
+            "tmdb_id": integer,
+            "media_type": media_type,
+            "title": string,
+            "poster_path": nullable("string"),
+        },
+    }));
+    schemas.insert("TitleRef".to_string(), json!({
+        "type": "object",
+        "required": ["tmdb_id", "media_type"],
+        "properties": { "tmdb_id": integer, "media_type": media_type },
+    }));
+    schemas.insert("Added".to_string(), json!({
+        "type": "object",
+        "properties": { "added": { "type": "boolean", "description": "false if the title was already there" } },
+    }));
+    schemas.insert("DataExport".to_string(), json!({
+        "type": "object",
+        "properties": {
+            "exported_at": { "type": "string", "format": "date-time" },
+            "settings": { "type": "object", "additionalProperties": string },
+            "watch_history": {
+                "type": "array",
+                "items": {
+                    "type": "object",
+                    "properties": {
+                        "tmdb_id": integer,
+                        "media_type": media_type,
+                        "title": string,
+                        "season_number": { "type": ["integer", "null"] },
+                        "episode_number": { "type": ["integer", "null"] },
+                        "episode_title": { "type": ["string", "null"] },
+                        "progress_seconds": integer,
+                        "completed": boolean,
+                        "watched_at": string,
+                    },
+                },
+            },
+            "watchlist": {
+                "type": "array",
+                "items": {
+                    "type": "object",
+                    "properties": {
+                        "tmdb_id": integer,
+                        "media_type": media_type,
+                        "title": string,
+                        "added_at": string,
+                    },
+                },
+            },
+            "ratings": {
+                "type": "array",
+                "items": {
+                    "type": "object",
+                    "properties": {
+                        "tmdb_id": integer,
+                        "media_type": media_type,
+                        "title": string,
+                        "rating": number,
+                        "rated_at": string,
+                    },
+                },
+            },
+        },
+    }));
+    schemas.insert("ProviderStatus".to_string(), json!({
+        "type": "object",
+        "properties": {
+            "name": string,
+            "priority": integer,
+            "healthy": { "type": "boolean", "description": "As of the latest health check, every 5 minutes" },
+            "last_checked_at": { "type": ["string", "null"] },
+            "average_latency_ms": { "type": ["number", "null"] },
+            "last_latency_ms": { "type": ["integer", "null"] },
+            "success_rate": { "type": ["number", "null"], "description": "0 to 1" },
+        },
+    }));
+    schemas.insert("Notification".to_string(), json!({
+        "type": "object",
+        "description": "The data of a `notification` event. The event id is the notification's id; send it back as Last-Event-ID to resume after it.",
+        "properties": {
+            "id": integer,
+            "kind": { "type": "string", "enum": ["new_episode", "job_finished"] },
+            "title": string,
+            "message": string,
+            "url": { "type": ["string", "null"] },
+            "created_at": { "type": "string", "format": "date-time" },
+        },
+    }));
+    schemas.insert("ImportReport".to_string(), json!({
+        "type": "object",
+        "properties": {
+            "format": { "type": "string", "enum": ["trakt", "netflix"] },
+            "dry_run": boolean,
+            "total": integer,
+            "imported": { "type": "integer", "description": "With dry_run, the rows that would be imported" },
+            "failed": integer,
+            "rows": {
+                "type": "array",
+                "items": {
+                    "type": "object",
+                    "properties": {
+                        "row": { "type": "integer", "description": "1-based, not counting a CSV header" },
+                        "title": string,
+                        "tmdb_id": { "type": ["integer", "null"] },
+                        "media_type": { "type": ["string", "null"], "enum": ["movie", "tv", null] },
+                        "season_number": { "type": ["integer", "null"] },
+                        "episode_number": { "type": ["integer", "null"] },
+                        "watched_at": { "type": ["string", "null"] },
+                        "error": { "type": ["string", "null"], "description": "Why the row wasn't imported" },
+                    },
+                },
+            },
+        },
+    }));
+    schemas.insert("ImportJob".to_string(), json!({
+        "type": "object",
+        "properties": {
+            "state": { "type": "string", "enum": ["running", "finished", "failed"] },
+            "format": { "type": "string", "enum": ["trakt", "netflix"] },
+            "dry_run": boolean,
+            "total": integer,
+            "started_at": { "type": "string", "format": "date-time" },
+            "finished_at": { "type": ["string", "null"], "format": "date-time" },
+            "report": { "oneOf": [schema("ImportReport"), { "type": "null" }] },
+            "error": nullable("string"),
+        },
+    }));
+    schemas.insert("WatchlistItem".to_string(), json!({
+        "type": "object",
+        "properties": {
+            "tmdb_id": integer,
+            "media_type": media_type,
+            "title": string,
+            "poster_path": nullable("string"),
+            "added_at": string,
+        },
+    }));
+    schemas.insert("ContinueWatchingItem".to_string(), json!({
+        "type": "object",
+        "properties": {
+            "tmdb_id": integer,
+            "media_type": media_type,
+            "title": string,
+            "poster_path": nullable("string"),
+            "season_number": nullable("integer"),
+            "episode_number": nullable("integer"),
+            "episode_title": nullable("string"),
+            "progress_seconds": integer,
+            "watched_at": string,
+            "player_url": string,
+        },
+    }));
+    schemas.insert("HistoryEntry".to_string(), json!({
+        "type": "object",
+        "properties": {
+            "id": integer,
+            "tmdb_id": integer,
+            "media_type": media_type,
+            "title": string,
+            "poster_path": nullable("string"),
+            "season_number": nullable("integer"),
+            "episode_number": nullable("integer"),
+            "episode_title": nullable("string"),
+            "progress_seconds": integer,
+            "completed": { "type": "boolean" },
+            "watched_at": string,
+        },
+    }));
+    schemas.insert("HistoryPage".to_string(), json!({
+        "type": "object",
+        "properties": {
+            "page": integer,
+            "per_page": integer,
+            "total_pages": integer,
+            "total_results": integer,
+            "results": { "type": "array", "items": schema("HistoryEntry") },
+        },
+    }));
+    schemas.insert("StatsSummary".to_string(), json!({
+        "type": "object",
+        "properties": {
+            "period_days": nullable("integer"),
+            "total_seconds": integer,
+            "total_hours": number,
+            "days_watched": integer,
+            "top_genres": {
+                "type": "array",
+                "items": { "type": "object", "properties": { "genre": string, "seconds": integer } },
+            },
+            "busiest_days": {
+                "type": "array",
+                "items": { "type": "object", "properties": { "day": string, "seconds": integer, "titles": integer } },
+            },
+            "weekdays": {
+                "type": "array",
+                "items": { "type": "object", "properties": { "weekday": string, "seconds": integer } },
+            },
+        },
+    }));
+    schemas.insert("Playlist".to_string(), json!({
+        "type": "object",
+        "properties": {
+            "id": integer,
+            "name": string,
+            "share_token": nullable("string"),
+            "item_count": integer,
+            "created_at": string,
+            "updated_at": string,
+        },
+    }));
+    schemas.insert("PlaylistWithItems".to_string(), json!({
+        "allOf": [
+            schema("Playlist"),
+            {
+                "type": "object",
+                "properties": {
+                    "items": {
+                        "type": "array",
+                        "items": {
+                            "type": "object",
+                            "properties": {
+                                "tmdb_id": integer,
+                                "media_type": media_type,
+                                "title": string,
+                                "poster_path": nullable("string"),
+                                "added_at": string,
+                            },
+                        },
+                    },
+                },
+            },
+        ],
+    }));
+    schemas.insert("Role".to_string(), json!({ "type": "string", "enum": ["admin", "manager", "member", "kid", "guest"] }));
+    schemas.insert("Permission".to_string(), json!({
+        "type": "string",
+        "enum": [
+            "approve_requests",
+            "manage_users",
+            "manage_settings",
+            "configure_live_tv",
+            "edit_preferences",
+            "manage_account",
+        ],
+    }));
+    schemas.insert("RolePermissions".to_string(), json!({
+        "type": "object",
+        "properties": { "role": schema("Role"), "permissions": list_of("Permission") },
+    }));
+    schemas.insert("Invite".to_string(), json!({
+        "type": "object",
+        "properties": {
+            "code": string,
+            "role": schema("Role"),
+            "expires_at": nullable("integer"),
+            "created_at": string,
+            "used_by": nullable("integer"),
+            "used_at": nullable("string"),
+        },
+    }));
+    schemas.insert("ProviderConfig".to_string(), json!({
+        "type": "object",
+        "properties": {
+            "name": string,
+            "enabled": boolean,
+            "priority": integer,
+            "default_priority": integer,
+            "options": { "type": "object", "additionalProperties": string },
+            "customized": boolean,
+            "healthy": boolean,
+        },
+    }));
+    schemas.insert("ProviderSetting".to_string(), json!({
+        "type": "object",
+        "required": ["enabled"],
+        "properties": {
+            "enabled": boolean,
+            "priority": nullable("integer"),
+            "options": { "type": "object", "additionalProperties": string },
+        },
+    }));
+    schemas.insert("ProviderStats".to_string(), json!({
+        "type": "object",
+        "properties": {
+            "name": string,
+            "attempts": integer,
+            "successes": integer,
+            "total_latency_ms": integer,
+            "last_latency_ms": integer,
+            "playbacks": integer,
+            "troubled_playbacks": integer,
+            "updated_at": string,
+            "success_rate": number,
+            "average_latency_ms": number,
+            "score": nullable("number"),
+        },
+    }));
+    schemas.insert("LocalMedia".to_string(), json!({
+        "type": "object",
+        "required": ["tmdb_id", "media_type", "path"],
+        "properties": {
+            "id": integer,
+            "tmdb_id": integer,
+            "media_type": media_type,
+            "season_number": integer,
+            "episode_number": integer,
+            "path": { "type": "string", "description": "Relative to MEDIA_DIR" },
+        },
+    }));
+    schemas.insert("StreamOverride".to_string(), json!({
+        "type": "object",
+        "required": ["tmdb_id", "media_type"],
+        "properties": {
+            "tmdb_id": integer,
+            "media_type": media_type,
+            "season_number": integer,
+            "episode_number": integer,
+            "provider": nullable("string"),
+            "url": nullable("string"),
+        },
+    }));
+    schemas.insert("WebhookEvent".to_string(), json!({
+        "type": "string",
+        "enum": ["playback.started", "playback.completed", "watchlist.added"],
+    }));
+    schemas.insert("Webhook".to_string(), json!({
+        "type": "object",
+        "properties": {
+            "id": integer,
+            "url": string,
+            "events": { "type": "array", "items": schema("WebhookEvent") },
+            "enabled": { "type": "boolean" },
+            "created_at": string,
+        },
+    }));
+    schemas.insert("WebhookDelivery".to_string(), json!({
+        "type": "object",
+        "properties": {
+            "id": integer,
+            "event": string,
+            "status": { "type": "string", "enum": ["pending", "delivered", "failed"] },
+            "attempts": integer,
+            "response_status": nullable("integer"),
+            "last_error": nullable("string"),
+            "next_attempt_at": string,
+            "created_at": string,
+            "delivered_at": nullable("string"),
+        },
+    }));
+    schemas.insert("VersionInfo".to_string(), json!({
+        "type": "object",
+        "properties": {
+            "version": string,
+            "build": {
+                "type": "object",
+                "properties": { "commit": nullable("string"), "profile": string },
+                "additionalProperties": true,
+            },
+            "features": { "type": "array", "items": string },
+        },
+    }));
+    Value::Object(schemas)
+}
+
+fn build_document() -> Value {
+    let mut paths = Map::new();
+    for operation in operations() {
+        let item = paths
+            .entry(format!("{}{}", crate::api::V1_PREFIX, operation.path))
+            .or_insert_with(|| json!({}));
+        item[operation.method] = operation.to_json();
+    }
+
+    json!({
+        "openapi": "3.1.0",
+        "info": {
+            "title": "RustStream API",
+            "version": env!("CARGO_PKG_VERSION"),
+            "description": "JSON API behind the RustStream web app. With AUTH_MODE=accounts, calls need the session cookie from /api/v1/auth/login; in local mode every call acts as the local user.\n\nResponses are objects so fields can be added without breaking clients: ignore fields you don't know. Lists are wrapped as `{\"results\": [...]}`, and errors are `application/problem+json` (RFC 7807) with a stable `code`.\n\nThe same endpoints are also served under `/api` with their original shapes (bare arrays for lists), for existing scripts.",
+        },
+        "paths": paths,
+        "security": [{ "session": [] }],
+        "components": {
+            "securitySchemes": {
+                "session": { "type": "apiKey", "in": "cookie", "name": SESSION_COOKIE },
+            },
+            "responses": {
+                "Error": {
+                    "description": "The request failed",
+                    "content": { "application/problem+json": { "schema": schema("Problem") } },
+                },
+            },
+            "schemas": schemas(),
+        },
+    })
+}
@@ -0,0 +1,140 @@
+//! Guards for requests sent to addresses users chose, such as webhooks and
+//! relayed HLS streams, so they can't be pointed at the server's own network.
+
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::sync::Arc;
+use std::time::Duration;
+
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use reqwest::{redirect, ClientBuilder, Url};
+
+/// Whether `ip` is on the public internet: not loopback, private, link-local,
+/// shared (CGNAT), multicast, documentation, or unspecified. IPv6 addresses
+/// that embed an IPv4 one are judged by that address.
+pub fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_public_v4(ip),
+        IpAddr::V6(ip) => match embedded_v4(ip) {
+            Some(ip) => is_public_v4(ip),
+            None => is_public_v6(ip),
+        },
+    }
+}
+
+/// The IPv4 address an IPv6 one stands for: IPv4-mapped (`::ffff:0:0/96`),
+/// IPv4-compatible (`::/96`), NAT64 (`64:ff9b::/96`), or 6to4 (`2002::/16`).
+fn embedded_v4(ip: Ipv6Addr) -> Option<Ipv4Addr> {
+    if let Some(ip) = ip.to_ipv4_mapped() {
+        return Some(ip);
+    }
+    let [a, b, c, d, e, f, g, h] = ip.segments();
+    let v4 = |high: u16, low: u16| Some(Ipv4Addr::from((u32::from(high) << 16) | u32::from(low)));
+    match (a, b, c, d, e, f) {
+        (0, 0, 0, 0, 0, 0) | (0x64, 0xff9b, 0, 0, 0, 0) => v4(g, h),
+        (0x2002, ..) => v4(b, c),
+        _ => None,
+    }
+}
+
+fn is_public_v4(ip: Ipv4Addr) -> bool {
+    let [a, b, ..] = ip.octets();
+    !(ip.is_private()
+        || ip.is_loopback()
+        || ip.is_link_local()
+        || ip.is_unspecified()
+        || ip.is_broadcast()
+        || ip.is_multicast()
+        || ip.is_documentation()
+        || a == 0
+        || (a == 100 && (64..128).contains(&b))
+        || (a == 198 && (b == 18 || b == 19))
+        || a >= 240)
+}
+
+fn is_public_v6(ip: Ipv6Addr) -> bool {
+    let first = ip.segments()[0];
+    !(ip.is_loopback()
+        || ip.is_unspecified()
+        || ip.is_multicast()
+        || (first & 0xfe00) == 0xfc00
+        || (first & 0xffc0) == 0xfe80
+        || (first == 0x64 && ip.segments()[1..3] == [0xff9b, 1])
+        || first == 0x2001 && ip.segments()[1] == 0x0db8)
+}
+
+/// Checks what can be told from the URL alone: the scheme, and that the host
+/// isn't a private address or `localhost`. Names are checked as they are
+/// resolved, by [`client_builder`]'s resolver.
+pub fn check_url(url: &Url) -> Result<(), String> {
+    if url.scheme() != "https" && url.scheme() != "http" {
+        return Err("url must be http or https".to_string());
+    }
+    let Some(host) = url.host_str() else {
+        return Err("url must have a host".to_string());
+    };
+    match host_ip(host) {
+        Some(ip) if !is_public(ip) => Err("url must not be a private address".to_string()),
+        None if is_local_name(host) => Err("url must not be a local host".to_string()),
+        _ => Ok(()),
+    }
+}
+
+/// The address in a URL's host, if it is one rather than a name.
+fn host_ip(host: &str) -> Option<IpAddr> {
+    host.trim_start_matches('[').trim_end_matches(']').parse().ok()
+}
+
+/// Like [`check_url`], also resolving the host now, so a URL whose name
+/// points into a private network is refused when it is saved rather than
+/// failing on every send.
+pub async fn check_url_resolves(url: &Url) -> Result<(), String> {
+    check_url(url)?;
+    let host = url.host_str().unwrap_or_default();
+    if host_ip(host).is_some() {
+        return Ok(());
+    }
+    let port = url.port_or_known_default().unwrap_or(443);
+    let addrs: Vec<SocketAddr> = tokio::net::lookup_host((host, port))
+        .await
+        .map_err(|e| format!("Could not resolve {}: {}", host, e))?
+        .collect();
+    if addrs.is_empty() || addrs.iter().any(|addr| !is_public(addr.ip())) {
+        return Err(format!("{} resolves to a private address", host));
+    }
+    Ok(())
+}
+
+fn is_local_name(host: &str) -> bool {
+    let host = host.trim_end_matches('.').to_ascii_lowercase();
+    host == "localhost" || host.ends_with(".localhost") || host.ends_with(".local") || host.ends_with(".internal")
+}
+
+/// Resolves names as usual but drops private addresses, so a name can't be
+/// made to point inside the network after it was checked.
+struct PublicResolver;
+
+impl Resolve for PublicResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        Box::pin(async move {
+            let host = name.as_str().to_string();
+            let addrs: Vec<SocketAddr> = tokio::net::lookup_host((host.as_str(), 0))
+                .await?
+                .filter(|addr| is_public(addr.ip()))
+                .collect();
+            if addrs.is_empty() {
+                return Err(format!("{} has no public address", host).into());
+            }
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
+
+/// A client for user-chosen URLs: it doesn't follow redirects, and only
+/// connects to public addresses. Callers still check IP literals in URLs
+/// with [`check_url`], since those aren't resolved.
+pub fn client_builder(timeout: Duration) -> ClientBuilder {
+    reqwest::Client::builder()
+        .timeout(timeout)
+        .redirect(redirect::Policy::none())
+        .dns_resolver(Arc::new(PublicResolver))
+}
@@ -0,0 +1,255 @@
+use axum::{async_trait, extract::FromRequestParts, http::request::Parts};
+use bcrypt::{hash, verify, DEFAULT_COST};
+use chrono::{Duration, Utc};
+use tokio::task::JoinSet;
+
+use crate::auth::{self, AuthManager, MaybeSession, SessionSecrets};
+use crate::error::AppError;
+use crate::tmdb::{Certification, SearchResult, TmdbClient};
+use crate::AppState;
+
+/// `user_settings` key holding the profile's limit, a label from [`LIMITS`].
+const MAX_CERTIFICATION_KEY: &str = "parental_max_certification";
+/// `user_settings` key holding the bcrypt hash of the profile's PIN.
+pub(crate) const PIN_KEY: &str = "parental_pin";
+
+/// Cookie that lifts the limit for a while after the PIN was entered.
+const UNLOCK_COOKIE: &str = "ruststream_parental_unlock";
+const UNLOCK_DURATION_MINUTES: i64 = 60;
+
+pub const MIN_PIN_LENGTH: usize = 4;
+
+/// A rating a profile can be limited to, and the age it stands for.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Limit {
+    pub label: &'static str,
+    pub max_age: u8,
+}
+
+/// The limits offered in settings, by US movie rating. TV and other boards'
+/// ratings are compared by the age in [`certification_age`].
+pub const LIMITS: &[Limit] = &[
+    Limit { label: "G", max_age: 0 },
+    Limit { label: "PG", max_age: 10 },
+    Limit { label: "PG-13", max_age: 13 },
+    Limit { label: "R", max_age: 17 },
+];
+
+pub fn find_limit(label: &str) -> Option<&'static Limit> {
+    LIMITS.iter().find(|limit| limit.label == label)
+}
+
+/// The youngest age a rating is meant for. US and UK ratings are mapped by
+/// name; most other boards rate by age, e.g. `FSK 16` or `12`. `None` for
+/// ratings this can't place.
+pub fn certification_age(certification: &Certification) -> Option<u8> {
+    let rating = certification.rating.trim().to_ascii_uppercase();
+    let age = match rating.as_str() {
+        "G" | "U" | "TV-Y" | "TV-G" => 0,
+        "TV-Y7" | "TV-Y7-FV" => 7,
+        "PG" | "TV-PG" => 10,
+        "PG-13" | "TV-14" => 13,
+        "R" | "TV-MA" => 17,
+        "NC-17" | "R18" | "X" => 18,
+        _ => {
+            let digits: String = rating
+                .chars()
+                .skip_while(|c| !c.is_ascii_digit())
+                .take_while(|c| c.is_ascii_digit())
+                .collect();
+            return digits.parse().ok();
+        }
+    };
+    Some(age)
+}
+
+/// A profile's parental settings, from `user_settings`.
+#[derive(Debug, Clone, Default)]
+pub struct ParentalSettings {
+    pub limit: Option<&'static Limit>,
+    pin_hash: Option<String>,
+}
+
+impl ParentalSettings {
+    pub async fn load(auth: &AuthManager, user_id: i64) -> anyhow::Result<Self> {
+        let limit = auth
+            .get_setting::<String>(user_id, MAX_CERTIFICATION_KEY)
+            .await?
+            .as_deref()
+            .and_then(find_limit);
+        let pin_hash = auth
+            .get_setting::<String>(user_id, PIN_KEY)
+            .await?
+            .filter(|hash| !hash.is_empty());
+        Ok(Self { limit, pin_hash })
+    }
+
+    pub fn has_pin(&self) -> bool {
+        self.pin_hash.is_some()
+    }
+
+    pub fn check_pin(&self, pin: &str) -> bool {
+        self.pin_hash
+            .as_deref()
+            .is_some_and(|pin_hash| verify(pin.trim(), pin_hash).unwrap_or(false))
+    }
+
+    /// Saves the limit, and the PIN if `new_pin` is set. An empty PIN removes it.
+    pub async fn save(
+        auth: &AuthManager,
+        user_id: i64,
+        limit: Option<&'static Limit>,
+        new_pin: Option<&str>,
+    ) -> anyhow::Result<()> {
+        auth.set_setting(user_id, MAX_CERTIFICATION_KEY, limit.map_or("", |l| l.label))
+            .await?;
+        if let Some(pin) = new_pin {
+            let pin_hash = match pin.trim() {
+                "" => String::new(),
+                pin => hash(pin, DEFAULT_COST)?,
+            };
+            auth.set_setting(user_id, PIN_KEY, &pin_hash).await?;
+        }
+        Ok(())
+    }
+}
+
+/// Signs and checks the unlock cookie.
+#[derive(Debug, Clone)]
+pub struct ParentalControls {
+    secrets: SessionSecrets,
+}
+
+impl ParentalControls {
+    pub fn new(secrets: SessionSecrets) -> Self {
+        Self { secrets }
+    }
+
+    /// `Set-Cookie` value that lifts `user_id`'s limit in this browser.
+    pub fn unlock_cookie(&self, user_id: i64) -> String {
+        let expires = (Utc::now() + Duration::minutes(UNLOCK_DURATION_MINUTES)).timestamp();
+        let signature = self.secrets.sign(&unlock_message(user_id, expires));
+        format!(
+            "{}={}.{}.{}; Path=/; HttpOnly; SameSite=Lax; Max-Age={}",
+            UNLOCK_COOKIE,
+            user_id,
+            expires,
+            signature,
+            Duration::minutes(UNLOCK_DURATION_MINUTES).num_seconds()
+        )
+    }
+
+    fn is_unlocked(&self, cookie: &str, user_id: i64) -> bool {
+        let mut parts = cookie.splitn(3, '.');
+        let (Some(id), Some(expires), Some(signature)) = (parts.next(), parts.next(), parts.next()) else {
+            return false;
+        };
+        let (Ok(id), Ok(expires)) = (id.parse::<i64>(), expires.parse::<i64>()) else {
+            return false;
+        };
+        id == user_id
+            && expires > Utc::now().timestamp()
+            && self.secrets.verify(&unlock_message(user_id, expires), signature)
+    }
+}
+
+fn unlock_message(user_id: i64, expires: i64) -> String {
+    format!("parental-unlock.{}.{}", user_id, expires)
+}
+
+/// The limit in force for this request: the profile's, unless it was
+/// unlocked with the PIN. `None` allows everything.
+#[derive(Debug, Clone, Copy)]
+pub struct Restriction(pub Option<&'static Limit>);
+
+impl Restriction {
+    /// Whether a title with this rating may be shown. Unrated titles are
+    /// held back whenever a limit is on.
+    pub fn allows(&self, certification: Option<&Certification>) -> bool {
+        match self.0 {
+            None => true,
+            Some(limit) => certification
+                .and_then(certification_age)
+                .is_some_and(|age| age <= limit.max_age),
+        }
+    }
+
+    /// Refuses a title above the limit with 403, for the API; pages show a
+    /// "restricted" page instead.
+    pub fn check(&self, certification: Option<&Certification>) -> Result<(), AppError> {
+        if self.allows(certification) {
+            Ok(())
+        } else {
+            Err(AppError::Forbidden("This title is above the profile's parental limit".to_string()))
+        }
+    }
+
+    /// Like [`Self::check`], looking up the title's rating first. Costs
+    /// nothing when no limit is on.
+    pub async fn check_title(&self, tmdb: &TmdbClient, media_type: &str, id: i64) -> Result<(), AppError> {
+        if self.0.is_none() {
+            return Ok(());
+        }
+        let certification = tmdb.get_certification(media_type, id).await?;
+        self.check(certification.as_ref())
+    }
+
+    /// Drops results above the limit, looking up each title's rating.
+    /// People are kept.
+    pub async fn filter_results(&self, tmdb: &TmdbClient, results: Vec<SearchResult>) -> Vec<SearchResult> {
+        if self.0.is_none() {
+            return results;
+        }
+
+        let mut lookups = JoinSet::new();
+        for (index, result) in results.iter().enumerate() {
+            let media_type = match result.media_type.as_str() {
+                "person" => continue,
+                "" if result.title.is_some() => "movie",
+                "" => "tv",
+                media_type => media_type,
+            }
+            .to_string();
+            let (tmdb, id) = (tmdb.clone(), result.id);
+            lookups.spawn(async move {
+                let certification = tmdb.get_certification(&media_type, id).await.unwrap_or_else(|e| {
+                    tracing::warn!("Could not look up the rating of {} {}: {}", media_type, id, e);
+                    None
+                });
+                (index, certification)
+            });
+        }
+
+        let mut blocked: Vec<bool> = results.iter().map(|r| r.media_type != "person").collect();
+        while let Some(lookup) = lookups.join_next().await {
+            if let Ok((index, certification)) = lookup {
+                blocked[index] = results[index].adult || !self.allows(certification.as_ref());
+            }
+        }
+        results
+            .into_iter()
+            .zip(blocked)
+            .filter(|(_, blocked)| !blocked)
+            .map(|(result, _)| result)
+            .collect()
+    }
+}
+
+#[async_trait]
+impl FromRequestParts<AppState> for Restriction {
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &AppState) -> Result<Self, Self::Rejection> {
+        let Ok(MaybeSession(session)) = MaybeSession::from_request_parts(parts, state).await;
+        let Some(session) = session else {
+            return Ok(Restriction(None));
+        };
+        let settings = ParentalSettings::load(&state.auth, session.user_id).await?;
+        if settings.limit.is_none() {
+            return Ok(Restriction(None));
+        }
+        let unlocked = auth::cookie(&parts.headers, UNLOCK_COOKIE)
+            .is_some_and(|cookie| state.parental.is_unlocked(cookie, session.user_id));
+        Ok(Restriction(if unlocked { None } else { settings.limit }))
+    }
+}
@@ -0,0 +1,63 @@
+use crate::db::{self, Db};
+use crate::tmdb::{DiscoverFilters, TmdbClient};
+
+/// The person named by an `actor:` or `director:` query.
+pub fn person_query(query: &str) -> Option<(PersonRole, &str)> {
+    let query = query.trim();
+    if let Some(name) = query.strip_prefix("actor:") {
+        Some((PersonRole::Actor, name.trim()))
+    } else if let Some(name) = query.strip_prefix("director:") {
+        Some((PersonRole::Director, name.trim()))
+    } else {
+        None
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PersonRole {
+    Actor,
+    Director,
+}
+
+/// Looks up the TMDB person id for a name, remembering successful lookups
+/// in `person_ids` so repeated searches skip TMDB. Returns 0 when nobody matches.
+pub async fn resolve_person(db: &Db, tmdb: &TmdbClient, name: &str) -> anyhow::Result<i64> {
+    let key = name.trim().to_lowercase();
+    let cached: Option<i64> = db::query_scalar("SELECT tmdb_id FROM person_ids WHERE name = ?")
+        .bind(&key)
+        .fetch_optional(db)
+        .await?;
+    if let Some(id) = cached {
+        return Ok(id);
+    }
+
+    let id = tmdb.search_person(&key).await?;
+    if id > 0 {
+        db::query(
+            r#"
+            INSERT INTO person_ids (name, tmdb_id) VALUES (?, ?)
+            ON CONFLICT(name) DO UPDATE SET tmdb_id = excluded.tmdb_id, resolved_at = datetime('now')
+            "#
+        )
+        .bind(&key)
+        .bind(id)
+        .execute(db)
+        .await?;
+    }
+    Ok(id)
+}
+
+/// Fills in [`DiscoverFilters::person`] for `actor:` and `director:` queries
+/// and returns what was resolved.
+pub async fn resolve_filters(
+    db: &Db,
+    tmdb: &TmdbClient,
+    filters: &mut DiscoverFilters,
+) -> anyhow::Result<Option<(PersonRole, i64)>> {
+    let Some((role, name)) = person_query(&filters.q) else {
+        return Ok(None);
+    };
+    let id = resolve_person(db, tmdb, name).await?;
+    filters.person = Some(id);
+    Ok(Some((role, id)))
+}
@@ -0,0 +1,239 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use tracing::{debug, warn};
+
+use crate::db::{self, Db};
+
+/// How often the player page sends a heartbeat.
+pub const HEARTBEAT_INTERVAL_SECS: u64 = 15;
+
+/// A playing session that misses heartbeats for this long is considered stalled.
+const SILENCE_THRESHOLD: Duration = Duration::from_secs(HEARTBEAT_INTERVAL_SECS * 3);
+
+/// Sessions with no heartbeat for this long are forgotten.
+const SESSION_EXPIRY: Duration = Duration::from_secs(10 * 60);
+
+/// Trouble events in one session before the viewer is nudged to switch sources.
+const SWITCH_PROMPT_THRESHOLD: u32 = 2;
+
+/// Heartbeats for one playback closer together than this are ignored.
+const MIN_HEARTBEAT_GAP: Duration = Duration::from_secs(5);
+
+/// Playbacks one user may have going at once; more are refused.
+const MAX_PLAYBACKS_PER_USER: usize = 8;
+
+/// QoS events stored per playback. Later ones still count toward the
+/// switch prompt but aren't written.
+const MAX_EVENTS_PER_PLAYBACK: u32 = 20;
+
+/// Longest accepted `playback_id`.
+pub const MAX_PLAYBACK_ID_LEN: usize = 64;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PlaybackState {
+    Playing,
+    Paused,
+    Buffering,
+    Error,
+    Ended,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QosEventKind {
+    /// Still buffering on consecutive heartbeats.
+    Stall,
+    /// The player reported an error.
+    Error,
+    /// Heartbeats stopped arriving mid-playback.
+    Silent,
+}
+
+impl QosEventKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            QosEventKind::Stall => "stall",
+            QosEventKind::Error => "error",
+            QosEventKind::Silent => "silent",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Heartbeat {
+    /// Random id the player page generates once per load.
+    pub playback_id: String,
+    pub tmdb_id: i64,
+    pub media_type: String,
+    pub season: Option<i64>,
+    pub episode: Option<i64>,
+    pub provider: Option<String>,
+    pub state: PlaybackState,
+    #[serde(default)]
+    pub position: f64,
+    pub detail: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct HeartbeatOutcome {
+    /// The player should offer another source.
+    pub suggest_switch: bool,
+    /// This is the first `playing` heartbeat of the playback.
+    #[serde(skip)]
+    pub started: bool,
+    /// This is the playback's first heartbeat.
+    #[serde(skip)]
+    pub new_playback: bool,
+    /// The playback just had its first trouble.
+    #[serde(skip)]
+    pub first_trouble: bool,
+}
+
+#[derive(Debug)]
+struct ActiveSession {
+    last_seen: Instant,
+    state: PlaybackState,
+    position: f64,
+    trouble: u32,
+    started: bool,
+}
+
+/// Tracks heartbeat gaps and player states for every active playback,
+/// keyed by user and `playback_id`.
+#[derive(Debug, Default)]
+pub struct PlaybackMonitor {
+    sessions: Mutex<HashMap<(i64, String), ActiveSession>>,
+}
+
+impl PlaybackMonitor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Updates the user's session for `heartbeat` and returns the QoS events
+    /// worth storing along with whether the viewer should be prompted to
+    /// switch. `None` when the user already has too many playbacks going.
+    pub fn observe(&self, user_id: i64, heartbeat: &Heartbeat) -> Option<(Vec<QosEventKind>, HeartbeatOutcome)> {
+        let now = Instant::now();
+        let mut sessions = self.sessions.lock().unwrap();
+        sessions.retain(|_, s| now.duration_since(s.last_seen) < SESSION_EXPIRY);
+
+        let mut events = Vec::new();
+        if heartbeat.state == PlaybackState::Error {
+            events.push(QosEventKind::Error);
+        }
+
+        let playing = heartbeat.state == PlaybackState::Playing;
+        let key = (user_id, heartbeat.playback_id.clone());
+        let Some(session) = sessions.get_mut(&key) else {
+            if sessions.keys().filter(|(id, _)| *id == user_id).count() >= MAX_PLAYBACKS_PER_USER {
+                return None;
+            }
+            sessions.insert(
+                key,
+                ActiveSession {
+                    last_seen: now,
+                    state: heartbeat.state,
+                    position: heartbeat.position,
+                    trouble: events.len() as u32,
+                    started: playing,
+                },
+            );
+            let suggest_switch = !events.is_empty();
+            return Some((
+                events,
+                HeartbeatOutcome {
+                    suggest_switch,
+                    started: playing,
+                    new_playback: true,
+                    first_trouble: suggest_switch,
+                },
+            ));
+        };
+        if now.duration_since(session.last_seen) < MIN_HEARTBEAT_GAP {
+            return Some((Vec::new(), HeartbeatOutcome::default()));
+        }
+        let started = playing && !session.started;
+        session.started |= playing;
+
+        if session.state == PlaybackState::Playing
+            && now.duration_since(session.last_seen) > SILENCE_THRESHOLD
+        {
+            events.push(QosEventKind::Silent);
+        }
+
+        if heartbeat.state == PlaybackState::Buffering
+            && session.state == PlaybackState::Buffering
+            && heartbeat.position <= session.position
+        {
+            events.push(QosEventKind::Stall);
+        }
+
+        let first_trouble = session.trouble == 0 && !events.is_empty();
+        let stored = MAX_EVENTS_PER_PLAYBACK.saturating_sub(session.trouble) as usize;
+        session.trouble += events.len() as u32;
+        session.last_seen = now;
+        session.state = heartbeat.state;
+        session.position = heartbeat.position;
+
+        let suggest_switch = heartbeat.state == PlaybackState::Error
+            || (session.trouble >= SWITCH_PROMPT_THRESHOLD
+                && heartbeat.state != PlaybackState::Playing);
+        events.truncate(stored);
+
+        Some((
+            events,
+            HeartbeatOutcome {
+                suggest_switch,
+                started,
+                new_playback: false,
+                first_trouble,
+            },
+        ))
+    }
+}
+
+/// Persists a QoS event so provider scoring can weigh sources by reliability.
+pub async fn record_qos_event(
+    db: &Db,
+    user_id: i64,
+    heartbeat: &Heartbeat,
+    kind: QosEventKind,
+) -> anyhow::Result<()> {
+    debug!(
+        "QoS {} for {} {} (provider {:?})",
+        kind.as_str(),
+        heartbeat.media_type,
+        heartbeat.tmdb_id,
+        heartbeat.provider
+    );
+
+    db::query(
+        r#"
+        INSERT INTO playback_events
+        (user_id, playback_id, tmdb_id, media_type, season_number, episode_number, provider, kind, position_seconds, detail)
+        VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+        "#
+    )
+    .bind(user_id)
+    .bind(&heartbeat.playback_id)
+    .bind(heartbeat.tmdb_id)
+    .bind(&heartbeat.media_type)
+    .bind(heartbeat.season)
+    .bind(heartbeat.episode)
+    .bind(heartbeat.provider.as_deref())
+    .bind(kind.as_str())
+    .bind(heartbeat.position as i64)
+    .bind(heartbeat.detail.as_deref())
+    .execute(db)
+    .await
+    .map_err(|e| {
+        warn!("Failed to record QoS event: {}", e);
+        e
+    })?;
+
+    Ok(())
+}
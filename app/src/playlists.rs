@@ -0,0 +1,339 @@
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::db::{self, Db};
+use crate::error::AppError;
+
+pub const MAX_NAME_LENGTH: usize = 100;
+
+/// A named list of titles, such as "Halloween movies".
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct Playlist {
+    pub id: i64,
+    pub name: String,
+    /// Set while the playlist is shared at `/shared/playlists/{token}`.
+    pub share_token: Option<String>,
+    pub item_count: i64,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct PlaylistItem {
+    pub tmdb_id: i64,
+    pub media_type: String,
+    pub title: String,
+    pub poster_path: Option<String>,
+    pub added_at: String,
+}
+
+/// A playlist with its titles in order.
+#[derive(Debug, Serialize)]
+pub struct PlaylistWithItems {
+    #[serde(flatten)]
+    pub playlist: Playlist,
+    pub items: Vec<PlaylistItem>,
+}
+
+/// Identifies a title within a playlist.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize)]
+pub struct TitleRef {
+    pub tmdb_id: i64,
+    pub media_type: String,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum PlaylistError {
+    /// Missing, or someone else's.
+    #[error("Playlist not found")]
+    NotFound,
+
+    #[error("{0}")]
+    Invalid(String),
+
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+impl From<sqlx::Error> for PlaylistError {
+    fn from(err: sqlx::Error) -> Self {
+        Self::Other(err.into())
+    }
+}
+
+impl From<PlaylistError> for AppError {
+    fn from(err: PlaylistError) -> Self {
+        match err {
+            PlaylistError::NotFound => AppError::NotFound,
+            PlaylistError::Other(e) => e.into(),
+            e => AppError::BadRequest(e.to_string()),
+        }
+    }
+}
+
+const PLAYLIST_COLUMNS: &str = r#"
+    p.id, p.name, p.share_token, p.created_at, p.updated_at,
+    (SELECT COUNT(*) FROM playlist_items i WHERE i.playlist_id = p.id) AS item_count
+"#;
+
+fn validate_name(name: &str) -> Result<&str, PlaylistError> {
+    let name = name.trim();
+    if name.is_empty() || name.chars().count() > MAX_NAME_LENGTH {
+        return Err(PlaylistError::Invalid(format!(
+            "Playlist name must be 1 to {} characters",
+            MAX_NAME_LENGTH
+        )));
+    }
+    Ok(name)
+}
+
+/// Users' playlists, kept in the `playlists` and `playlist_items` tables.
+/// Every change is scoped to the owning user.
+#[derive(Debug, Clone)]
+pub struct Playlists {
+    db: Db,
+}
+
+impl Playlists {
+    pub fn new(db: Db) -> Self {
+        Self { db }
+    }
+
+    /// The user's playlists, most recently changed first.
+    pub async fn list(&self, user_id: i64) -> anyhow::Result<Vec<Playlist>> {
+        let playlists = db::query_as(&format!(
+            "SELECT {PLAYLIST_COLUMNS} FROM playlists p WHERE p.user_id = ? ORDER BY p.updated_at DESC, p.id DESC"
+        ))
+        .bind(user_id)
+        .fetch_all(&self.db)
+        .await?;
+        Ok(playlists)
+    }
+
+    pub async fn create(&self, user_id: i64, name: &str) -> Result<Playlist, PlaylistError> {
+        let name = validate_name(name)?;
+        let id: i64 = db::query_scalar("INSERT INTO playlists (user_id, name) VALUES (?, ?) RETURNING id")
+            .bind(user_id)
+            .bind(name)
+            .fetch_one(&self.db)
+            .await?;
+        self.get(user_id, id).await
+    }
+
+    pub async fn get(&self, user_id: i64, id: i64) -> Result<Playlist, PlaylistError> {
+        db::query_as(&format!(
+            "SELECT {PLAYLIST_COLUMNS} FROM playlists p WHERE p.id = ? AND p.user_id = ?"
+        ))
+        .bind(id)
+        .bind(user_id)
+        .fetch_optional(&self.db)
+        .await?
+        .ok_or(PlaylistError::NotFound)
+    }
+
+    pub async fn get_with_items(&self, user_id: i64, id: i64) -> Result<PlaylistWithItems, PlaylistError> {
+        let playlist = self.get(user_id, id).await?;
+        let items = self.items(playlist.id).await?;
+        Ok(PlaylistWithItems { playlist, items })
+    }
+
+    /// A playlist its owner shared, looked up by its share token.
+    pub async fn get_shared(&self, token: &str) -> Result<PlaylistWithItems, PlaylistError> {
+        let playlist: Playlist = db::query_as(&format!(
+            "SELECT {PLAYLIST_COLUMNS} FROM playlists p WHERE p.share_token = ?"
+        ))
+        .bind(token)
+        .fetch_optional(&self.db)
+        .await?
+        .ok_or(PlaylistError::NotFound)?;
+        let items = self.items(playlist.id).await?;
+        Ok(PlaylistWithItems { playlist, items })
+    }
+
+    async fn items(&self, playlist_id: i64) -> anyhow::Result<Vec<PlaylistItem>> {
+        let items = db::query_as(
+            r#"
+            SELECT tmdb_id, media_type, title, poster_path, added_at
+            FROM playlist_items
+            WHERE playlist_id = ?
+            ORDER BY position, id
+            "#
+        )
+        .bind(playlist_id)
+        .fetch_all(&self.db)
+        .await?;
+        Ok(items)
+    }
+
+    pub async fn rename(&self, user_id: i64, id: i64, name: &str) -> Result<(), PlaylistError> {
+        let name = validate_name(name)?;
+        let result = db::query(
+            "UPDATE playlists SET name = ?, updated_at = datetime('now') WHERE id = ? AND user_id = ?"
+        )
+        .bind(name)
+        .bind(id)
+        .bind(user_id)
+        .execute(&self.db)
+        .await?;
+        if result.rows_affected() == 0 {
+            return Err(PlaylistError::NotFound);
+        }
+        Ok(())
+    }
+
+    /// Turns the public link on or off. Sharing again after turning it off
+    /// makes a new link, so the old one stays dead.
+    pub async fn set_shared(&self, user_id: i64, id: i64, shared: bool) -> Result<Playlist, PlaylistError> {
+        let playlist = self.get(user_id, id).await?;
+        if shared == playlist.share_token.is_some() {
+            return Ok(playlist);
+        }
+        let token = shared.then(|| uuid::Uuid::new_v4().simple().to_string());
+        db::query("UPDATE playlists SET share_token = ? WHERE id = ? AND user_id = ?")
+            .bind(&token)
+            .bind(id)
+            .bind(user_id)
+            .execute(&self.db)
+            .await?;
+        Ok(Playlist {
+            share_token: token,
+            ..playlist
+        })
+    }
+
+    pub async fn delete(&self, user_id: i64, id: i64) -> Result<(), PlaylistError> {
+        let mut tx = self.db.begin().await?;
+        let result = db::query("DELETE FROM playlists WHERE id = ? AND user_id = ?")
+            .bind(id)
+            .bind(user_id)
+            .execute(&mut tx)
+            .await?;
+        if result.rows_affected() == 0 {
+            return Err(PlaylistError::NotFound);
+        }
+        db::query("DELETE FROM playlist_items WHERE playlist_id = ?")
+            .bind(id)
+            .execute(&mut tx)
+            .await?;
+        tx.commit().await?;
+        info!("Deleted playlist {} of user {}", id, user_id);
+        Ok(())
+    }
+
+    /// Appends a title. Returns whether it was added; it may already be on
+    /// the playlist.
+    pub async fn add_item(
+        &self,
+        user_id: i64,
+        id: i64,
+        title: &TitleRef,
+        name: &str,
+        poster_path: Option<&str>,
+    ) -> Result<bool, PlaylistError> {
+        self.get(user_id, id).await?;
+        let result = db::query(
+            r#"
+            INSERT INTO playlist_items (playlist_id, tmdb_id, media_type, title, poster_path, position)
+            VALUES (?, ?, ?, ?, ?, (SELECT COALESCE(MAX(position) + 1, 0) FROM playlist_items WHERE playlist_id = ?))
+            ON CONFLICT(playlist_id, tmdb_id, media_type) DO NOTHING
+            "#
+        )
+        .bind(id)
+        .bind(title.tmdb_id)
+        .bind(&title.media_type)
+        .bind(name)
+        .bind(poster_path)
+        .bind(id)
+        .execute(&self.db)
+        .await?;
+        let added = result.rows_affected() > 0;
+        if added {
+            self.touch(id).await?;
+        }
+        Ok(added)
+    }
+
+    /// Returns whether the title was on the playlist.
+    pub async fn remove_item(&self, user_id: i64, id: i64, title: &TitleRef) -> Result<bool, PlaylistError> {
+        self.get(user_id, id).await?;
+        let result = db::query(
+            "DELETE FROM playlist_items WHERE playlist_id = ? AND tmdb_id = ? AND media_type = ?"
+        )
+        .bind(id)
+        .bind(title.tmdb_id)
+        .bind(&title.media_type)
+        .execute(&self.db)
+        .await?;
+        let removed = result.rows_affected() > 0;
+        if removed {
+            self.touch(id).await?;
+        }
+        Ok(removed)
+    }
+
+    /// Puts the titles in the given order, which must list each title on
+    /// the playlist exactly once.
+    pub async fn reorder(&self, user_id: i64, id: i64, order: &[TitleRef]) -> Result<(), PlaylistError> {
+        let current = self.get_with_items(user_id, id).await?.items;
+        let mut expected: Vec<(i64, &str)> = current.iter().map(|i| (i.tmdb_id, i.media_type.as_str())).collect();
+        let mut given: Vec<(i64, &str)> = order.iter().map(|t| (t.tmdb_id, t.media_type.as_str())).collect();
+        expected.sort_unstable();
+        given.sort_unstable();
+        if expected != given {
+            return Err(PlaylistError::Invalid(
+                "Order must list every title on the playlist exactly once".to_string(),
+            ));
+        }
+
+        let mut tx = self.db.begin().await?;
+        for (position, title) in order.iter().enumerate() {
+            db::query(
+                "UPDATE playlist_items SET position = ? WHERE playlist_id = ? AND tmdb_id = ? AND media_type = ?"
+            )
+            .bind(position as i64)
+            .bind(id)
+            .bind(title.tmdb_id)
+            .bind(&title.media_type)
+            .execute(&mut tx)
+            .await?;
+        }
+        db::query("UPDATE playlists SET updated_at = datetime('now') WHERE id = ?")
+            .bind(id)
+            .execute(&mut tx)
+            .await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Moves a title one place earlier (`-1`) or later (`1`). Moving past
+    /// either end does nothing.
+    pub async fn move_item(&self, user_id: i64, id: i64, title: &TitleRef, offset: isize) -> Result<(), PlaylistError> {
+        let mut order: Vec<TitleRef> = self
+            .get_with_items(user_id, id)
+            .await?
+            .items
+            .into_iter()
+            .map(|item| TitleRef {
+                tmdb_id: item.tmdb_id,
+                media_type: item.media_type,
+            })
+            .collect();
+        let Some(from) = order.iter().position(|t| t == title) else {
+            return Err(PlaylistError::Invalid("That title isn't on the playlist".to_string()));
+        };
+        let to = from as isize + offset;
+        if to < 0 || to as usize >= order.len() {
+            return Ok(());
+        }
+        order.swap(from, to as usize);
+        self.reorder(user_id, id, &order).await
+    }
+
+    async fn touch(&self, id: i64) -> anyhow::Result<()> {
+        db::query("UPDATE playlists SET updated_at = datetime('now') WHERE id = ?")
+            .bind(id)
+            .execute(&self.db)
+            .await?;
+        Ok(())
+    }
+}
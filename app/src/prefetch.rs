@@ -0,0 +1,139 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use tracing::{debug, warn};
+
+use crate::tmdb::TvShowDetail;
+use crate::providers::{EmbedOptions, StreamSource, StreamStatus};
+use crate::AppState;
+
+/// Playback percentage after which the next episode is resolved ahead of time.
+pub const PREFETCH_THRESHOLD: f64 = 80.0;
+
+const PREFETCH_TTL: Duration = Duration::from_secs(30 * 60);
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PrefetchKey {
+    pub tmdb_id: i64,
+    pub season: i64,
+    pub episode: i64,
+    /// Embed options change the resolved URLs, so they are part of the key.
+    pub options: String,
+    /// Relayed HLS links only work for the user they were signed for.
+    pub viewer: Option<i64>,
+}
+
+impl PrefetchKey {
+    pub fn new(tmdb_id: i64, season: i64, episode: i64, options: &EmbedOptions) -> Self {
+        Self {
+            tmdb_id,
+            season,
+            episode,
+            options: options.cache_key(),
+            viewer: options.viewer,
+        }
+    }
+}
+
+/// Short-lived store of stream sources resolved before the viewer asks for them.
+#[derive(Debug, Default)]
+pub struct StreamPrefetchCache {
+    entries: Mutex<HashMap<PrefetchKey, (Instant, Vec<StreamSource>)>>,
+    /// Episodes whose next episode was already prefetched, or is being,
+    /// keyed by the episode being watched.
+    started: Mutex<HashMap<PrefetchKey, Instant>>,
+}
+
+impl StreamPrefetchCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn get(&self, key: &PrefetchKey) -> Option<Vec<StreamSource>> {
+        let mut entries = self.entries.lock().unwrap();
+        entries.retain(|_, (stored_at, _)| stored_at.elapsed() < PREFETCH_TTL);
+        entries.get(key).map(|(_, streams)| streams.clone())
+    }
+
+    /// Claims the prefetch that watching `watching` triggers. False when it
+    /// was claimed in the last [`PREFETCH_TTL`], so progress saved every few
+    /// seconds past the threshold starts one prefetch, not one each time.
+    pub fn start(&self, watching: PrefetchKey) -> bool {
+        let mut started = self.started.lock().unwrap();
+        started.retain(|_, at| at.elapsed() < PREFETCH_TTL);
+        if started.contains_key(&watching) {
+            return false;
+        }
+        started.insert(watching, Instant::now());
+        true
+    }
+
+    pub fn insert(&self, key: PrefetchKey, streams: Vec<StreamSource>) {
+        self.entries
+            .lock()
+            .unwrap()
+            .insert(key, (Instant::now(), streams));
+    }
+}
+
+/// The episode after `season`/`episode`, rolling over into the next season.
+pub fn next_episode(show: &TvShowDetail, season: i64, episode: i64) -> Option<(i64, i64)> {
+    let current = show.seasons.iter().find(|s| s.season_number == season)?;
+    if episode < current.episode_count {
+        return Some((season, episode + 1));
+    }
+
+    show.seasons
+        .iter()
+        .filter(|s| s.season_number > season && s.episode_count > 0)
+        .min_by_key(|s| s.season_number)
+        .map(|s| (s.season_number, 1))
+}
+
+pub async fn prefetch_next_episode(
+    state: &AppState,
+    tmdb_id: i64,
+    season: i64,
+    episode: i64,
+    options: &EmbedOptions,
+) {
+    let show = match state.tmdb.get_tv_show(tmdb_id).await {
+        Ok(show) => show,
+        Err(e) => {
+            warn!("Prefetch skipped, could not load show {}: {}", tmdb_id, e);
+            return;
+        }
+    };
+
+    let Some((next_season, next_episode)) = next_episode(&show, season, episode) else {
+        return;
+    };
+
+    let key = PrefetchKey::new(tmdb_id, next_season, next_episode, options);
+    if state.prefetch.get(&key).is_some() {
+        return;
+    }
+
+    let options = EmbedOptions {
+        absolute_episode: show.provider_episode_number(next_season, next_episode),
+        ..options.clone()
+    };
+    let resolution = state
+        .providers
+        .get_tv_streams(tmdb_id, next_season, next_episode, &options)
+        .await;
+    match resolution.status {
+        StreamStatus::Available => {
+            debug!(
+                "Prefetched streams for {} S{}E{}",
+                tmdb_id, next_season, next_episode
+            );
+            state.prefetch.insert(key, resolution.streams);
+        }
+        status => warn!(
+            "Prefetch found nothing for {} S{}E{}: {:?}",
+            tmdb_id, next_season, next_episode, status
+        ),
+    }
+}
@@ -0,0 +1,128 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use serde::Serialize;
+use tracing::{error, info, warn};
+
+use crate::db::{self, Db};
+use crate::providers::ProviderRegistry;
+use crate::AppState;
+
+/// How often every stream provider's embed host is probed.
+const HEALTH_CHECK_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+#[derive(Debug, Default)]
+pub struct HealthReport {
+    pub healthy: usize,
+    pub unhealthy: usize,
+}
+
+/// An enabled provider as players see it, to offer as a source.
+#[derive(Debug, Clone, Serialize)]
+pub struct ProviderStatus {
+    pub name: String,
+    pub priority: i32,
+    /// As of the latest health check; `true` until the first one.
+    pub healthy: bool,
+    pub last_checked_at: Option<String>,
+    /// Over uncached resolutions; `None` before the first.
+    pub average_latency_ms: Option<f64>,
+    pub last_latency_ms: Option<i64>,
+    pub success_rate: Option<f64>,
+}
+
+/// Enabled providers, highest priority first, with their health and
+/// resolution stats.
+pub async fn statuses(db: &Db, registry: &ProviderRegistry) -> anyhow::Result<Vec<ProviderStatus>> {
+    let checked: HashMap<String, Option<String>> =
+        db::query_as::<(String, Option<String>)>("SELECT name, last_checked_at FROM provider_health")
+            .fetch_all(db)
+            .await?
+            .into_iter()
+            .collect();
+    let stats: HashMap<String, _> = registry
+        .stats()
+        .all()
+        .await?
+        .into_iter()
+        .map(|stat| (stat.name.clone(), stat))
+        .collect();
+
+    Ok(registry
+        .configs()
+        .into_iter()
+        .filter(|config| config.enabled)
+        .map(|config| {
+            let stat = stats.get(&config.name).filter(|stat| stat.attempts > 0);
+            ProviderStatus {
+                last_checked_at: checked.get(&config.name).cloned().flatten(),
+                average_latency_ms: stat.map(|stat| stat.average_latency_ms()),
+                last_latency_ms: stat.map(|stat| stat.last_latency_ms),
+                success_rate: stat.map(|stat| stat.success_rate()),
+                name: config.name,
+                priority: config.priority,
+                healthy: config.healthy,
+            }
+        })
+        .collect())
+}
+
+/// Probes each provider, records the result in `provider_health`, and
+/// updates the registry so unhealthy providers are skipped.
+pub async fn check_providers(db: &Db, registry: &ProviderRegistry) -> anyhow::Result<HealthReport> {
+    let mut report = HealthReport::default();
+
+    for provider in registry.providers() {
+        let name = provider.name();
+        let healthy = provider.is_reachable(true).await;
+        let previous = registry.set_healthy(name, healthy);
+        if !healthy && previous != Some(false) {
+            warn!("Stream provider {} is unreachable; skipping it", name);
+        } else if healthy && previous == Some(false) {
+            info!("Stream provider {} recovered", name);
+        }
+
+        db::query(
+            r#"
+            INSERT INTO provider_health (name, healthy, consecutive_failures, last_checked_at, last_healthy_at)
+            VALUES (?, ?, CASE WHEN ? THEN 0 ELSE 1 END, datetime('now'), CASE WHEN ? THEN datetime('now') END)
+            ON CONFLICT(name) DO UPDATE SET
+                healthy = excluded.healthy,
+                consecutive_failures = CASE WHEN excluded.healthy THEN 0 ELSE provider_health.consecutive_failures + 1 END,
+                last_checked_at = excluded.last_checked_at,
+                last_healthy_at = COALESCE(excluded.last_healthy_at, provider_health.last_healthy_at)
+            "#
+        )
+        .bind(name)
+        .bind(healthy)
+        .bind(healthy)
+        .bind(healthy)
+        .execute(db)
+        .await?;
+
+        if healthy {
+            report.healthy += 1;
+        } else {
+            report.unhealthy += 1;
+        }
+    }
+
+    Ok(report)
+}
+
+/// Checks providers at startup and then every few minutes.
+pub fn spawn_health_check_job(state: AppState) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(HEALTH_CHECK_INTERVAL);
+        loop {
+            interval.tick().await;
+            match check_providers(&state.db, &state.providers).await {
+                Ok(report) => info!(
+                    "Provider health: {} healthy, {} unhealthy",
+                    report.healthy, report.unhealthy
+                ),
+                Err(e) => error!("Provider health check failed: {}", e),
+            }
+        }
+    });
+}
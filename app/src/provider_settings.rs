@@ -0,0 +1,91 @@
+use std::collections::{BTreeMap, HashMap};
+
+use serde::{Deserialize, Serialize};
+
+use crate::db::{self, Db};
+
+/// An admin's runtime configuration for one provider. Unset fields fall back
+/// to `STREAM_PROVIDERS` and the provider's own priority.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProviderSetting {
+    #[serde(default)]
+    pub name: String,
+    pub enabled: bool,
+    #[serde(default)]
+    pub priority: Option<i32>,
+    /// Extra query parameters added to the provider's embed URLs.
+    #[serde(default)]
+    pub options: BTreeMap<String, String>,
+}
+
+#[derive(sqlx::FromRow)]
+struct ProviderSettingRow {
+    name: String,
+    enabled: bool,
+    priority: Option<i32>,
+    options: String,
+}
+
+impl From<ProviderSettingRow> for ProviderSetting {
+    fn from(row: ProviderSettingRow) -> Self {
+        Self {
+            name: row.name,
+            enabled: row.enabled,
+            priority: row.priority,
+            options: serde_json::from_str(&row.options).unwrap_or_default(),
+        }
+    }
+}
+
+/// Provider settings kept in the `provider_settings` table.
+#[derive(Debug, Clone)]
+pub struct ProviderSettings {
+    db: Db,
+}
+
+impl ProviderSettings {
+    pub fn new(db: Db) -> Self {
+        Self { db }
+    }
+
+    pub async fn all(&self) -> anyhow::Result<HashMap<String, ProviderSetting>> {
+        let rows: Vec<ProviderSettingRow> =
+            db::query_as("SELECT name, enabled, priority, options FROM provider_settings")
+                .fetch_all(&self.db)
+                .await?;
+        Ok(rows
+            .into_iter()
+            .map(|row| (row.name.clone(), row.into()))
+            .collect())
+    }
+
+    pub async fn set(&self, setting: &ProviderSetting) -> anyhow::Result<()> {
+        db::query(
+            r#"
+            INSERT INTO provider_settings (name, enabled, priority, options, updated_at)
+            VALUES (?, ?, ?, ?, datetime('now'))
+            ON CONFLICT(name) DO UPDATE SET
+                enabled = excluded.enabled,
+                priority = excluded.priority,
+                options = excluded.options,
+                updated_at = excluded.updated_at
+            "#
+        )
+        .bind(&setting.name)
+        .bind(setting.enabled)
+        .bind(setting.priority)
+        .bind(serde_json::to_string(&setting.options)?)
+        .execute(&self.db)
+        .await?;
+        Ok(())
+    }
+
+    /// Returns whether a setting was removed.
+    pub async fn remove(&self, name: &str) -> anyhow::Result<bool> {
+        let result = db::query("DELETE FROM provider_settings WHERE name = ?")
+            .bind(name)
+            .execute(&self.db)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+}
@@ -0,0 +1,198 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use serde::Serialize;
+
+use crate::db::{self, Db};
+
+/// Resolutions a provider needs before its score is trusted; until then it
+/// keeps its place in priority order.
+const MIN_SAMPLES: i64 = 5;
+
+/// Days of counters behind the stats and scores, so a provider that broke
+/// this week isn't carried by months of good history.
+pub const WINDOW_DAYS: u32 = 7;
+
+/// Resolution counters for one provider over the last [`WINDOW_DAYS`] days.
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct ProviderStat {
+    pub name: String,
+    pub attempts: i64,
+    pub successes: i64,
+    pub total_latency_ms: i64,
+    pub last_latency_ms: i64,
+    /// Playbacks started from the provider, from player heartbeats.
+    pub playbacks: i64,
+    /// Of those, the ones that stalled, errored, or went silent.
+    pub troubled_playbacks: i64,
+    pub updated_at: String,
+}
+
+impl ProviderStat {
+    pub fn success_rate(&self) -> f64 {
+        if self.attempts == 0 {
+            return 0.0;
+        }
+        self.successes as f64 / self.attempts as f64
+    }
+
+    pub fn average_latency_ms(&self) -> f64 {
+        if self.attempts == 0 {
+            return 0.0;
+        }
+        self.total_latency_ms as f64 / self.attempts as f64
+    }
+
+    /// Share of playbacks that ran into trouble; `None` until there are
+    /// enough playbacks.
+    pub fn trouble_rate(&self) -> Option<f64> {
+        (self.playbacks >= MIN_SAMPLES).then(|| self.troubled_playbacks as f64 / self.playbacks as f64)
+    }
+
+    /// Success rate discounted by average latency, then by the trouble rate:
+    /// a provider that always works in under a second and plays smoothly
+    /// scores close to 1, a slow or flaky one near 0. `None` until there are
+    /// enough resolutions.
+    pub fn score(&self) -> Option<f64> {
+        (self.attempts >= MIN_SAMPLES).then(|| {
+            self.success_rate() / (1.0 + self.average_latency_ms() / 1000.0)
+                * (1.0 - self.trouble_rate().unwrap_or(0.0))
+        })
+    }
+}
+
+/// Per-provider resolution latency and success counts.
+#[derive(Debug, Clone)]
+pub struct ProviderStats {
+    db: Db,
+}
+
+impl ProviderStats {
+    pub fn new(db: Db) -> Self {
+        Self { db }
+    }
+
+    /// Records one uncached resolution.
+    pub async fn record(&self, name: &str, latency: Duration, success: bool) -> anyhow::Result<()> {
+        let latency_ms = latency.as_millis() as i64;
+        let mut tx = self.db.begin().await?;
+        db::query(
+            r#"
+            INSERT INTO provider_stats (name, attempts, successes, total_latency_ms, last_latency_ms, updated_at)
+            VALUES (?, 1, ?, ?, ?, datetime('now'))
+            ON CONFLICT(name) DO UPDATE SET
+                attempts = provider_stats.attempts + 1,
+                successes = provider_stats.successes + excluded.successes,
+                total_latency_ms = provider_stats.total_latency_ms + excluded.total_latency_ms,
+                last_latency_ms = excluded.last_latency_ms,
+                updated_at = excluded.updated_at
+            "#
+        )
+        .bind(name)
+        .bind(i64::from(success))
+        .bind(latency_ms)
+        .bind(latency_ms)
+        .execute(&mut tx)
+        .await?;
+
+        db::query(
+            r#"
+            INSERT INTO provider_stats_daily (name, day, attempts, successes, total_latency_ms)
+            VALUES (?, date('now'), 1, ?, ?)
+            ON CONFLICT(name, day) DO UPDATE SET
+                attempts = provider_stats_daily.attempts + 1,
+                successes = provider_stats_daily.successes + excluded.successes,
+                total_latency_ms = provider_stats_daily.total_latency_ms + excluded.total_latency_ms
+            "#
+        )
+        .bind(name)
+        .bind(i64::from(success))
+        .bind(latency_ms)
+        .execute(&mut tx)
+        .await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Records a playback started from `name`, or, with `troubled`, that
+    /// one already counted ran into trouble.
+    pub async fn record_playback(&self, name: &str, troubled: bool) -> anyhow::Result<()> {
+        let (playbacks, troubled) = if troubled { (0, 1) } else { (1, 0) };
+        let mut tx = self.db.begin().await?;
+        db::query(
+            r#"
+            INSERT INTO provider_stats (name, playbacks, troubled_playbacks, updated_at)
+            VALUES (?, ?, ?, datetime('now'))
+            ON CONFLICT(name) DO UPDATE SET
+                playbacks = provider_stats.playbacks + excluded.playbacks,
+                troubled_playbacks = provider_stats.troubled_playbacks + excluded.troubled_playbacks,
+                updated_at = excluded.updated_at
+            "#
+        )
+        .bind(name)
+        .bind(playbacks)
+        .bind(troubled)
+        .execute(&mut tx)
+        .await?;
+
+        db::query(
+            r#"
+            INSERT INTO provider_stats_daily (name, day, playbacks, troubled_playbacks)
+            VALUES (?, date('now'), ?, ?)
+            ON CONFLICT(name, day) DO UPDATE SET
+                playbacks = provider_stats_daily.playbacks + excluded.playbacks,
+                troubled_playbacks = provider_stats_daily.troubled_playbacks + excluded.troubled_playbacks
+            "#
+        )
+        .bind(name)
+        .bind(playbacks)
+        .bind(troubled)
+        .execute(&mut tx)
+        .await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Every provider's counters over the last [`WINDOW_DAYS`] days.
+    pub async fn all(&self) -> anyhow::Result<Vec<ProviderStat>> {
+        let stats = db::query_as(
+            r#"
+            SELECT s.name,
+                   CAST(COALESCE(SUM(d.attempts), 0) AS BIGINT) AS attempts,
+                   CAST(COALESCE(SUM(d.successes), 0) AS BIGINT) AS successes,
+                   CAST(COALESCE(SUM(d.total_latency_ms), 0) AS BIGINT) AS total_latency_ms,
+                   s.last_latency_ms,
+                   CAST(COALESCE(SUM(d.playbacks), 0) AS BIGINT) AS playbacks,
+                   CAST(COALESCE(SUM(d.troubled_playbacks), 0) AS BIGINT) AS troubled_playbacks,
+                   s.updated_at
+            FROM provider_stats s
+            LEFT JOIN provider_stats_daily d ON d.name = s.name AND d.day > date('now', ?)
+            GROUP BY s.name
+            ORDER BY s.name
+            "#
+        )
+        .bind(format!("-{} days", WINDOW_DAYS))
+        .fetch_all(&self.db)
+        .await?;
+        Ok(stats)
+    }
+
+    /// Deletes daily counters that have left the window.
+    pub async fn prune(&self) -> anyhow::Result<u64> {
+        let result = db::query("DELETE FROM provider_stats_daily WHERE day <= date('now', ?)")
+            .bind(format!("-{} days", WINDOW_DAYS))
+            .execute(&self.db)
+            .await?;
+        Ok(result.rows_affected())
+    }
+
+    /// Scores of the providers with enough samples.
+    pub async fn scores(&self) -> anyhow::Result<HashMap<String, f64>> {
+        Ok(self
+            .all()
+            .await?
+            .into_iter()
+            .filter_map(|stat| stat.score().map(|score| (stat.name, score)))
+            .collect())
+    }
+}
@@ -0,0 +1,731 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use futures_util::future::join_all;
+use reqwest::{Client, StatusCode, Url};
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use crate::provider_settings::{ProviderSetting, ProviderSettings};
+use crate::provider_stats::ProviderStats;
+use crate::stream_cache::{self, StreamCache, StreamCacheKey};
+use crate::stream_overrides::{StreamOverride, StreamOverrides, PINNED_SERVER};
+use crate::stream_reports::StreamReports;
+
+/// How long resolved streams are cached unless a provider says otherwise.
+pub const DEFAULT_STREAM_CACHE_TTL: Duration = Duration::from_secs(6 * 60 * 60);
+
+const PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+const PROBE_TTL: Duration = Duration::from_secs(60);
+
+/// Why a provider returned no streams. Providers fail with these so the
+/// registry can tell a missing title from an outage; any other error counts
+/// as the provider being down.
+#[derive(Debug, thiserror::Error)]
+pub enum ProviderError {
+    #[error("title not available")]
+    NotFound,
+
+    #[error("provider down: {0}")]
+    Down(String),
+}
+
+/// Outcome of resolving a title, for one provider or for all of them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StreamStatus {
+    Available,
+    NotFound,
+    ProviderDown,
+}
+
+/// What one provider answered.
+#[derive(Debug, Clone, Serialize)]
+pub struct ProviderOutcome {
+    pub provider: String,
+    pub status: StreamStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Streams for a title, with the per-provider outcomes behind them.
+#[derive(Debug, Clone, Serialize)]
+pub struct StreamResolution {
+    pub status: StreamStatus,
+    pub streams: Vec<StreamSource>,
+    pub providers: Vec<ProviderOutcome>,
+}
+
+impl StreamResolution {
+    /// Available when there is any stream. Otherwise the title only counts
+    /// as missing when no provider failed, since a down provider might have it.
+    pub fn new(streams: Vec<StreamSource>, providers: Vec<ProviderOutcome>) -> Self {
+        let status = if !streams.is_empty() {
+            StreamStatus::Available
+        } else if providers.iter().any(|p| p.status == StreamStatus::ProviderDown) {
+            StreamStatus::ProviderDown
+        } else {
+            StreamStatus::NotFound
+        };
+        Self {
+            status,
+            streams,
+            providers,
+        }
+    }
+}
+
+fn is_not_found(error: &anyhow::Error) -> bool {
+    matches!(error.downcast_ref::<ProviderError>(), Some(ProviderError::NotFound))
+}
+
+/// A source of playable streams. Providers are registered in
+/// [`ProviderRegistry`] and tried in priority order, highest first.
+#[async_trait]
+pub trait StreamProvider: Send + Sync {
+    /// Short lowercase id, used in `STREAM_PROVIDERS` and as `StreamSource::server`.
+    fn name(&self) -> &str;
+
+    fn priority(&self) -> i32;
+
+    async fn get_movie_streams(&self, tmdb_id: i64, options: &EmbedOptions) -> anyhow::Result<Vec<StreamSource>>;
+
+    async fn get_tv_streams(
+        &self,
+        tmdb_id: i64,
+        season: i64,
+        episode: i64,
+        options: &EmbedOptions,
+    ) -> anyhow::Result<Vec<StreamSource>>;
+
+    /// How long resolved streams stay in `streaming_cache`; `None` disables caching.
+    fn cache_ttl(&self) -> Option<Duration> {
+        Some(DEFAULT_STREAM_CACHE_TTL)
+    }
+
+    /// Whether the provider's host answers from this machine's network.
+    /// `force` bypasses any cached probe.
+    async fn is_reachable(&self, _force: bool) -> bool {
+        true
+    }
+}
+
+/// Checks whether an embed host answers from this machine's network.
+#[derive(Debug)]
+pub struct HostProbe {
+    client: Client,
+    url: String,
+    last_probe: Mutex<Option<(Instant, bool)>>,
+}
+
+impl HostProbe {
+    pub fn new(url: &str) -> anyhow::Result<Self> {
+        let client = Client::builder().timeout(PROBE_TIMEOUT).build()?;
+
+        Ok(Self {
+            client,
+            url: url.to_string(),
+            last_probe: Mutex::new(None),
+        })
+    }
+
+    /// Results are cached briefly; `force` bypasses the cache for explicit retries.
+    pub async fn check(&self, force: bool) -> bool {
+        if !force {
+            if let Some((checked_at, reachable)) = *self.last_probe.lock().unwrap() {
+                if checked_at.elapsed() < PROBE_TTL {
+                    return reachable;
+                }
+            }
+        }
+
+        let reachable = match self.client.head(&self.url).send().await {
+            // Any HTTP answer, even an error status, means the host isn't blocked.
+            Ok(_) => true,
+            Err(e) => {
+                warn!("Embed host {} unreachable: {}", self.url, e);
+                false
+            }
+        };
+
+        *self.last_probe.lock().unwrap() = Some((Instant::now(), reachable));
+        reachable
+    }
+
+    /// Requests an embed URL before handing it out: 404 or 410 means the
+    /// provider doesn't have the title, a server error or no answer that it
+    /// is down.
+    pub async fn validate(&self, url: &str) -> anyhow::Result<()> {
+        let response = self
+            .client
+            .get(url)
+            .send()
+            .await
+            .map_err(|e| ProviderError::Down(e.to_string()))?;
+        match response.status() {
+            StatusCode::NOT_FOUND | StatusCode::GONE => Err(ProviderError::NotFound.into()),
+            status if status.is_server_error() => {
+                Err(ProviderError::Down(format!("{} answered {}", self.url, status)).into())
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// A provider as the admin area shows it: its effective settings, after
+/// any saved in `provider_settings`.
+#[derive(Debug, Clone, Serialize)]
+pub struct ProviderConfig {
+    pub name: String,
+    pub enabled: bool,
+    pub priority: i32,
+    pub default_priority: i32,
+    pub options: std::collections::BTreeMap<String, String>,
+    /// Whether an admin has changed anything from the defaults.
+    pub customized: bool,
+    pub healthy: bool,
+}
+
+/// Every registered stream provider. Which ones are asked, and in what
+/// order, is decided per request from `STREAM_PROVIDERS` and the admin's
+/// provider settings.
+pub struct ProviderRegistry {
+    providers: Vec<Arc<dyn StreamProvider>>,
+    /// Enabled when the admin hasn't said otherwise: `STREAM_PROVIDERS`, or all.
+    default_enabled: HashSet<String>,
+    /// Latest background health check per provider; unchecked counts as healthy.
+    health: Mutex<HashMap<String, bool>>,
+    cache: StreamCache,
+    reports: StreamReports,
+    overrides: StreamOverrides,
+    stats: ProviderStats,
+    settings: ProviderSettings,
+    /// Copy of `provider_settings`, so resolving streams doesn't read the database.
+    configured: Mutex<HashMap<String, ProviderSetting>>,
+}
+
+impl ProviderRegistry {
+    /// Enables the providers named in `enabled`, or all of them when unset.
+    /// The others stay registered so an admin can turn them on.
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        mut providers: Vec<Arc<dyn StreamProvider>>,
+        enabled: Option<&[String]>,
+        cache: StreamCache,
+        reports: StreamReports,
+        overrides: StreamOverrides,
+        stats: ProviderStats,
+        settings: ProviderSettings,
+    ) -> Self {
+        let default_enabled: HashSet<String> = match enabled {
+            Some(names) => {
+                for name in names {
+                    if !providers.iter().any(|p| p.name() == name) {
+                        warn!("Unknown stream provider \"{}\" in STREAM_PROVIDERS", name);
+                    }
+                }
+                names.iter().cloned().collect()
+            }
+            None => providers.iter().map(|p| p.name().to_string()).collect(),
+        };
+        let mut seen = HashSet::new();
+        providers.retain(|p| {
+            let unique = seen.insert(p.name().to_string());
+            if !unique {
+                warn!("Ignoring duplicate stream provider \"{}\"", p.name());
+            }
+            unique
+        });
+        providers.sort_by_key(|p| std::cmp::Reverse(p.priority()));
+
+        let names: Vec<&str> = providers
+            .iter()
+            .map(|p| p.name())
+            .filter(|name| default_enabled.contains(*name))
+            .collect();
+        info!("Stream providers: {}", names.join(", "));
+        Self {
+            providers,
+            default_enabled,
+            health: Mutex::new(HashMap::new()),
+            cache,
+            reports,
+            overrides,
+            stats,
+            settings,
+            configured: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Reads the admin's provider settings; called once at startup.
+    pub async fn load_settings(&self) -> anyhow::Result<()> {
+        let settings = self.settings.all().await?;
+        *self.configured.lock().unwrap() = settings;
+        Ok(())
+    }
+
+    /// Saves a provider's settings; they apply from the next request.
+    pub async fn configure(&self, setting: ProviderSetting) -> anyhow::Result<()> {
+        self.settings.set(&setting).await?;
+        self.configured
+            .lock()
+            .unwrap()
+            .insert(setting.name.clone(), setting);
+        Ok(())
+    }
+
+    /// Drops a provider's saved settings, going back to its defaults.
+    /// Returns whether there were any.
+    pub async fn reset(&self, name: &str) -> anyhow::Result<bool> {
+        let removed = self.settings.remove(name).await?;
+        self.configured.lock().unwrap().remove(name);
+        Ok(removed)
+    }
+
+    /// Every registered provider, in the order they are asked.
+    pub fn configs(&self) -> Vec<ProviderConfig> {
+        let configured = self.configured.lock().unwrap();
+        let mut configs: Vec<ProviderConfig> = self
+            .providers
+            .iter()
+            .map(|p| {
+                let setting = configured.get(p.name());
+                ProviderConfig {
+                    name: p.name().to_string(),
+                    enabled: setting.map_or_else(|| self.default_enabled.contains(p.name()), |s| s.enabled),
+                    priority: setting.and_then(|s| s.priority).unwrap_or(p.priority()),
+                    default_priority: p.priority(),
+                    options: setting.map(|s| s.options.clone()).unwrap_or_default(),
+                    customized: setting.is_some(),
+                    healthy: self.is_healthy(p.name()),
+                }
+            })
+            .collect();
+        configs.sort_by_key(|c| (!c.enabled, std::cmp::Reverse(c.priority)));
+        configs
+    }
+
+    /// Enabled providers, highest effective priority first.
+    fn enabled(&self) -> Vec<&Arc<dyn StreamProvider>> {
+        let configured = self.configured.lock().unwrap();
+        let mut enabled: Vec<_> = self
+            .providers
+            .iter()
+            .filter(|p| match configured.get(p.name()) {
+                Some(setting) => setting.enabled,
+                None => self.default_enabled.contains(p.name()),
+            })
+            .collect();
+        enabled.sort_by_key(|p| {
+            std::cmp::Reverse(
+                configured
+                    .get(p.name())
+                    .and_then(|s| s.priority)
+                    .unwrap_or(p.priority()),
+            )
+        });
+        enabled
+    }
+
+    /// Adds the admin's options for `name` to its embed URLs as query parameters.
+    fn with_options(&self, name: &str, mut streams: Vec<StreamSource>) -> Vec<StreamSource> {
+        let configured = self.configured.lock().unwrap();
+        let Some(options) = configured.get(name).map(|s| &s.options).filter(|o| !o.is_empty()) else {
+            return streams;
+        };
+        for stream in streams.iter_mut().filter(|s| s.kind == StreamKind::Embed) {
+            if let Ok(mut url) = Url::parse(&stream.id) {
+                url.query_pairs_mut().extend_pairs(options);
+                stream.id = url.into();
+            }
+        }
+        streams
+    }
+
+    pub fn cache(&self) -> &StreamCache {
+        &self.cache
+    }
+
+    pub fn reports(&self) -> &StreamReports {
+        &self.reports
+    }
+
+    pub fn overrides(&self) -> &StreamOverrides {
+        &self.overrides
+    }
+
+    pub fn stats(&self) -> &ProviderStats {
+        &self.stats
+    }
+
+    pub fn providers(&self) -> &[Arc<dyn StreamProvider>] {
+        &self.providers
+    }
+
+    /// Records a health check result and returns the previous one.
+    pub fn set_healthy(&self, name: &str, healthy: bool) -> Option<bool> {
+        self.health.lock().unwrap().insert(name.to_string(), healthy)
+    }
+
+    fn is_healthy(&self, name: &str) -> bool {
+        self.health.lock().unwrap().get(name).copied().unwrap_or(true)
+    }
+
+    /// Providers to ask for streams: unhealthy ones are skipped while any
+    /// healthy provider remains, so the player doesn't open a dead embed.
+    /// An enabled provider pinned for the title is always asked, healthy or not.
+    fn available(&self, pin: Option<&StreamOverride>) -> Vec<&Arc<dyn StreamProvider>> {
+        let pinned = pin.and_then(|p| p.provider.as_deref());
+        let enabled = self.enabled();
+        let healthy: Vec<_> = enabled
+            .iter()
+            .copied()
+            .filter(|p| self.is_healthy(p.name()) || pinned == Some(p.name()))
+            .collect();
+        if healthy.is_empty() {
+            enabled
+        } else {
+            healthy
+        }
+    }
+
+    async fn pin(&self, tmdb_id: i64, media_type: &str, season: i64, episode: i64) -> Option<StreamOverride> {
+        match self.overrides.get(tmdb_id, media_type, season, episode).await {
+            Ok(pin) => pin,
+            Err(e) => {
+                warn!("Could not read stream override: {}", e);
+                None
+            }
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Arc<dyn StreamProvider>> {
+        self.providers.iter().find(|p| p.name() == name)
+    }
+
+    /// Streams from every provider, with what each one answered. Providers
+    /// are asked at once, so the slowest one sets the wait, not their sum.
+    pub async fn get_movie_streams(&self, tmdb_id: i64, options: &EmbedOptions) -> StreamResolution {
+        let pin = self.pin(tmdb_id, "movie", 0, 0).await;
+        let fetches = self.available(pin.as_ref()).into_iter().map(|provider| async move {
+            let key = StreamCacheKey {
+                provider: provider.name(),
+                tmdb_id,
+                media_type: "movie",
+                season: 0,
+                episode: 0,
+                options: options.cache_key(),
+            };
+            let fetch = provider.get_movie_streams(tmdb_id, options);
+            let result = self.cached(provider.as_ref(), &key, fetch).await;
+            (provider.name(), result.map(|s| self.with_options(provider.name(), s)))
+        });
+        let results = join_all(fetches).await;
+        let (streams, outcomes) = collect_streams(results);
+        let streams = self.rank(streams).await;
+        let streams = self.demote(streams, tmdb_id, "movie", 0, 0).await;
+        StreamResolution::new(apply_pin(streams, pin), outcomes)
+    }
+
+    pub async fn get_tv_streams(
+        &self,
+        tmdb_id: i64,
+        season: i64,
+        episode: i64,
+        options: &EmbedOptions,
+    ) -> StreamResolution {
+        let pin = self.pin(tmdb_id, "tv", season, episode).await;
+        let fetches = self.available(pin.as_ref()).into_iter().map(|provider| async move {
+            let key = StreamCacheKey {
+                provider: provider.name(),
+                tmdb_id,
+                media_type: "tv",
+                season,
+                episode,
+                options: options.cache_key(),
+            };
+            let fetch = provider.get_tv_streams(tmdb_id, season, episode, options);
+            let result = self.cached(provider.as_ref(), &key, fetch).await;
+            (provider.name(), result.map(|s| self.with_options(provider.name(), s)))
+        });
+        let results = join_all(fetches).await;
+        let (streams, outcomes) = collect_streams(results);
+        let streams = self.rank(streams).await;
+        let streams = self.demote(streams, tmdb_id, "tv", season, episode).await;
+        StreamResolution::new(apply_pin(streams, pin), outcomes)
+    }
+
+    /// Orders streams from providers with enough history by score, fastest
+    /// and most reliable first. They only trade places with each other, so
+    /// providers without a score keep their priority position.
+    async fn rank(&self, mut streams: Vec<StreamSource>) -> Vec<StreamSource> {
+        let scores = match self.stats.scores().await {
+            Ok(scores) => scores,
+            Err(e) => {
+                warn!("Could not read provider stats: {}", e);
+                return streams;
+            }
+        };
+
+        let slots: Vec<usize> = streams
+            .iter()
+            .enumerate()
+            .filter(|(_, s)| scores.contains_key(&s.server))
+            .map(|(i, _)| i)
+            .collect();
+        let mut scored: Vec<StreamSource> = slots.iter().map(|&i| streams[i].clone()).collect();
+        scored.sort_by(|a, b| scores[&b.server].total_cmp(&scores[&a.server]));
+        for (slot, stream) in slots.into_iter().zip(scored) {
+            streams[slot] = stream;
+        }
+        streams
+    }
+
+    /// Moves streams from providers with many recent broken-stream reports
+    /// behind the rest, keeping them as a last resort.
+    async fn demote(
+        &self,
+        mut streams: Vec<StreamSource>,
+        tmdb_id: i64,
+        media_type: &str,
+        season: i64,
+        episode: i64,
+    ) -> Vec<StreamSource> {
+        match self.reports.demoted(tmdb_id, media_type, season, episode).await {
+            Ok(demoted) => streams.sort_by_key(|s| demoted.contains(&s.server)),
+            Err(e) => warn!("Could not read stream reports: {}", e),
+        }
+        streams
+    }
+
+    /// Serves streams from `streaming_cache` when fresh, otherwise runs
+    /// `fetch` and stores what it returns.
+    async fn cached(
+        &self,
+        provider: &dyn StreamProvider,
+        key: &StreamCacheKey<'_>,
+        fetch: impl std::future::Future<Output = anyhow::Result<Vec<StreamSource>>>,
+    ) -> anyhow::Result<Vec<StreamSource>> {
+        let Some(ttl) = provider.cache_ttl() else {
+            return self.timed(provider.name(), fetch).await;
+        };
+        if let Some(streams) = stream_cache::lookup(&self.cache, key).await {
+            return Ok(streams);
+        }
+
+        let streams = self.timed(provider.name(), fetch).await?;
+        if !streams.is_empty() {
+            if let Err(e) = self.cache.put(key, &streams, ttl).await {
+                warn!("Could not cache streams for {}: {}", key.provider, e);
+            }
+        }
+        Ok(streams)
+    }
+
+    /// Runs an uncached resolution and records its latency and outcome.
+    async fn timed(
+        &self,
+        name: &str,
+        fetch: impl std::future::Future<Output = anyhow::Result<Vec<StreamSource>>>,
+    ) -> anyhow::Result<Vec<StreamSource>> {
+        let started = Instant::now();
+        let result = fetch.await;
+        // No streams just means the provider doesn't have the title.
+        let success = match &result {
+            Ok(_) => true,
+            Err(e) => is_not_found(e),
+        };
+        if let Err(e) = self.stats.record(name, started.elapsed(), success).await {
+            warn!("Could not record stats for {}: {}", name, e);
+        }
+        result
+    }
+
+    /// Probes the provider behind a stream; unknown providers count as reachable.
+    pub async fn is_reachable(&self, name: &str, force: bool) -> bool {
+        match self.get(name) {
+            Some(provider) => provider.is_reachable(force).await,
+            None => true,
+        }
+    }
+}
+
+/// Puts the admin-pinned source first, so the player opens it by default.
+/// The other sources stay available as alternatives.
+fn apply_pin(mut streams: Vec<StreamSource>, pin: Option<StreamOverride>) -> Vec<StreamSource> {
+    let Some(pin) = pin else {
+        return streams;
+    };
+    match (pin.url, pin.provider) {
+        (Some(url), _) => streams.insert(
+            0,
+            StreamSource {
+                id: url,
+                name: "Pinned source".to_string(),
+                quality: None,
+                language: None,
+                server: PINNED_SERVER.to_string(),
+                kind: StreamKind::Embed,
+            },
+        ),
+        (None, Some(provider)) => streams.sort_by_key(|s| s.server != provider),
+        (None, None) => {}
+    }
+    streams
+}
+
+/// Merges provider results in order. A failing provider is skipped and
+/// reported in the outcomes rather than failing the whole resolution.
+fn collect_streams(results: Vec<(&str, anyhow::Result<Vec<StreamSource>>)>) -> (Vec<StreamSource>, Vec<ProviderOutcome>) {
+    let mut streams = Vec::new();
+    let mut outcomes = Vec::new();
+    for (name, result) in results {
+        let (status, error) = match result {
+            Ok(found) if !found.is_empty() => {
+                streams.extend(found);
+                (StreamStatus::Available, None)
+            }
+            Ok(_) => (StreamStatus::NotFound, None),
+            Err(e) if is_not_found(&e) => (StreamStatus::NotFound, None),
+            Err(e) => {
+                warn!("Stream provider {} failed: {}", name, e);
+                (StreamStatus::ProviderDown, Some(e.to_string()))
+            }
+        };
+        outcomes.push(ProviderOutcome {
+            provider: name.to_string(),
+            status,
+            error,
+        });
+    }
+    (streams, outcomes)
+}
+
+#[derive(Debug, Clone)]
+pub struct EmbedOptions {
+    pub color: Option<String>,
+    pub auto_play: bool,
+    pub next_episode: bool,
+    pub episode_selector: bool,
+    pub progress: Option<i64>,
+    /// Preferred audio language (ISO 639-1), for providers that take a hint.
+    pub language: Option<String>,
+    /// Highest video height to play, for providers that take a cap.
+    pub max_quality: Option<u32>,
+    /// Absolute episode number for anime, whose providers usually ignore
+    /// TMDB seasons. Like `progress`, this is per-episode rather than a setting.
+    pub absolute_episode: Option<i64>,
+    /// User the streams are resolved for. Relayed HLS links are signed for
+    /// them alone, so this is never part of a shared cache key.
+    pub viewer: Option<i64>,
+}
+
+impl Default for EmbedOptions {
+    fn default() -> Self {
+        Self {
+            color: Some(crate::templates::ACCENT_COLOR.to_string()), // Netflix red
+            auto_play: true,
+            next_episode: true,
+            episode_selector: true,
+            progress: None,
+            language: None,
+            max_quality: None,
+            absolute_episode: None,
+            viewer: None,
+        }
+    }
+}
+
+impl EmbedOptions {
+    /// Identifies these options in stream caches. Unlike the query string, it
+    /// includes the language and quality hints, which only some providers
+    /// put in the URL.
+    pub fn cache_key(&self) -> String {
+        let mut key = self.to_query_string();
+        if let Some(language) = &self.language {
+            key.push_str(&format!("#lang={}", language));
+        }
+        if let Some(quality) = self.max_quality {
+            key.push_str(&format!("#q={}", quality));
+        }
+        key
+    }
+
+    pub fn to_query_string(&self) -> String {
+        let mut params = vec![];
+        
+        if let Some(color) = &self.color {
+            params.push(format!("color={}", color));
+        }
+        
+        if self.auto_play {
+            params.push("autoPlay=true".to_string());
+        }
+        
+        if self.next_episode {
+            params.push("nextEpisode=true".to_string());
+        }
+        
+        if self.episode_selector {
+            params.push("episodeSelector=true".to_string());
+        }
+        
+        if let Some(progress) = self.progress {
+            params.push(format!("progress={}", progress));
+        }
+        
+        if params.is_empty() {
+            String::new()
+        } else {
+            format!("?{}", params.join("&"))
+        }
+    }
+}
+
+/// How the player opens a stream.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StreamKind {
+    /// A third-party player page, loaded in an iframe.
+    #[default]
+    Embed,
+    /// An HLS manifest relayed through `/stream/proxy`, for a native player.
+    Hls,
+    /// A file from `MEDIA_DIR`, played directly by the native player.
+    File,
+}
+
+impl StreamKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Embed => "embed",
+            Self::Hls => "hls",
+            Self::File => "file",
+        }
+    }
+}
+
+/// Stable-sorts streams in `language` first, keeping a pinned source on top.
+pub fn prefer_language(streams: &mut [StreamSource], language: &str) {
+    streams.sort_by_key(|s| {
+        let matches = s
+            .language
+            .as_deref()
+            .is_some_and(|l| l.eq_ignore_ascii_case(language));
+        s.server != PINNED_SERVER && !matches
+    });
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct StreamSource {
+    pub id: String,
+    pub name: String,
+    pub quality: Option<String>,
+    pub language: Option<String>,
+    /// Name of the provider that produced this stream.
+    pub server: String,
+    #[serde(default)]
+    pub kind: StreamKind,
+}
@@ -0,0 +1,178 @@
+use std::collections::HashSet;
+use std::time::Duration;
+
+use tracing::{error, info, warn};
+
+use crate::db::{self, Db};
+use crate::notifications::NotificationKind;
+use crate::tmdb::TmdbClient;
+use crate::AppState;
+
+/// How often the background job checks stored ids against TMDB.
+const RECONCILE_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Changes window inspected by the incremental run; TMDB allows at most 14 days.
+const CHANGES_WINDOW_DAYS: i64 = 14;
+
+/// Tables keyed by `(tmdb_id, media_type)` whose rows follow a remapped
+/// title, with the other columns of their unique key, if they have one.
+const REMAP_TABLES: &[(&str, &[&str])] = &[
+    ("watch_history", &["user_id", "season_number", "episode_number"]),
+    ("playback_events", &[]),
+    ("watchlist", &["user_id"]),
+    ("ratings", &["user_id"]),
+];
+
+#[derive(Debug, Default)]
+pub struct ReconcileReport {
+    pub checked: usize,
+    pub remapped: usize,
+    pub orphaned: usize,
+}
+
+/// Finds stored titles TMDB no longer serves and moves their rows to the
+/// replacement id when one can be identified. Titles with no clear
+/// replacement are recorded in `title_orphans` for an admin to review.
+///
+/// The incremental run only probes ids that appear in TMDB's changes feed;
+/// `full` probes every stored id.
+pub async fn reconcile_titles(
+    db: &Db,
+    tmdb: &TmdbClient,
+    full: bool,
+) -> anyhow::Result<ReconcileReport> {
+    let stored: Vec<(i64, String, String)> = db::query_as(
+        "SELECT tmdb_id, media_type, MAX(title) FROM watch_history GROUP BY tmdb_id, media_type"
+    )
+    .fetch_all(db)
+    .await?;
+
+    let mut report = ReconcileReport::default();
+    for media_type in ["movie", "tv"] {
+        let changed: Option<HashSet<i64>> = if full {
+            None
+        } else {
+            Some(
+                tmdb.get_changed_ids(media_type, CHANGES_WINDOW_DAYS)
+                    .await?
+                    .into_iter()
+                    .collect(),
+            )
+        };
+
+        for (tmdb_id, _, title) in stored.iter().filter(|(id, kind, _)| {
+            kind == media_type && changed.as_ref().map_or(true, |ids| ids.contains(id))
+        }) {
+            report.checked += 1;
+            if tmdb.title_exists(media_type, *tmdb_id).await? {
+                continue;
+            }
+
+            match find_replacement(tmdb, media_type, title).await? {
+                Some(new_id) if new_id != *tmdb_id => {
+                    remap_title(db, media_type, *tmdb_id, new_id).await?;
+                    info!("Remapped {} {} ({}) to {}", media_type, tmdb_id, title, new_id);
+                    report.remapped += 1;
+                }
+                _ => {
+                    db::query(
+                        "INSERT INTO title_orphans (tmdb_id, media_type, title) VALUES (?, ?, ?) ON CONFLICT DO NOTHING"
+                    )
+                    .bind(tmdb_id)
+                    .bind(media_type)
+                    .bind(title)
+                    .execute(db)
+                    .await?;
+                    warn!("Orphaned {} {} ({}) needs review", media_type, tmdb_id, title);
+                    report.orphaned += 1;
+                }
+            }
+        }
+    }
+
+    Ok(report)
+}
+
+/// TMDB does not publish merge targets, so only an unambiguous exact title
+/// match is trusted as the replacement.
+async fn find_replacement(tmdb: &TmdbClient, media_type: &str, title: &str) -> anyhow::Result<Option<i64>> {
+    let results = tmdb.search(title, 1).await?.results;
+    let mut matches = results.iter().filter(|r| {
+        r.media_type == media_type
+            && r.title
+                .as_deref()
+                .or(r.name.as_deref())
+                .is_some_and(|name| name.eq_ignore_ascii_case(title))
+    });
+
+    match (matches.next(), matches.next()) {
+        (Some(only), None) => Ok(Some(only.id)),
+        _ => Ok(None),
+    }
+}
+
+async fn remap_title(db: &Db, media_type: &str, old_id: i64, new_id: i64) -> anyhow::Result<()> {
+    let mut tx = db.begin().await?;
+    for (table, key) in REMAP_TABLES {
+        // Rows that would collide with an existing row for the new id are
+        // duplicates of it, so they are dropped rather than moved.
+        if !key.is_empty() {
+            let same_key: String = key
+                .iter()
+                .map(|column| format!(" AND kept.{column} = {table}.{column}"))
+                .collect();
+            db::query(&format!(
+                "DELETE FROM {table} WHERE tmdb_id = ? AND media_type = ? AND EXISTS (
+                    SELECT 1 FROM {table} kept
+                    WHERE kept.tmdb_id = ? AND kept.media_type = {table}.media_type{same_key}
+                )"
+            ))
+            .bind(old_id)
+            .bind(media_type)
+            .bind(new_id)
+            .execute(&mut tx)
+            .await?;
+        }
+        db::query(&format!("UPDATE {table} SET tmdb_id = ? WHERE tmdb_id = ? AND media_type = ?"))
+            .bind(new_id)
+            .bind(old_id)
+            .bind(media_type)
+            .execute(&mut tx)
+            .await?;
+    }
+    db::query("DELETE FROM title_orphans WHERE tmdb_id = ? AND media_type = ?")
+        .bind(old_id)
+        .bind(media_type)
+        .execute(&mut tx)
+        .await?;
+    tx.commit().await?;
+    Ok(())
+}
+
+/// Runs the incremental reconciliation once a day for the life of the server.
+pub fn spawn_reconcile_job(state: AppState) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(RECONCILE_INTERVAL);
+        interval.tick().await;
+        loop {
+            interval.tick().await;
+            match reconcile_titles(&state.db, &state.tmdb, false).await {
+                Ok(report) => {
+                    info!(
+                        "Title reconciliation: {} checked, {} remapped, {} orphaned",
+                        report.checked, report.remapped, report.orphaned
+                    );
+                    state.notifications.notify_admins(
+                        NotificationKind::JobFinished,
+                        "Title reconciliation finished",
+                        format!(
+                            "{} checked, {} remapped, {} orphaned",
+                            report.checked, report.remapped, report.orphaned
+                        ),
+                    ).await;
+                }
+                Err(e) => error!("Title reconciliation failed: {}", e),
+            }
+        }
+    });
+}
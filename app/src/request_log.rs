@@ -0,0 +1,86 @@
+//! Gives every request an id, returned in `X-Request-Id`, and logs it with
+//! the method, path, status, and duration.
+
+use std::borrow::Cow;
+use std::time::Instant;
+
+use axum::{
+    extract::Request,
+    http::{HeaderName, HeaderValue},
+    middleware::Next,
+    response::Response,
+};
+use tracing::{info, info_span, warn, Instrument};
+
+const REQUEST_ID_HEADER: HeaderName = HeaderName::from_static("x-request-id");
+
+/// Longer ids from clients or proxies are replaced with our own.
+const MAX_REQUEST_ID_LEN: usize = 64;
+
+/// Path segments that are secrets, by what precedes them, and what is
+/// logged instead. Matched anywhere in the path, so `/api` and `/api/v1`
+/// forms are covered too.
+const SECRET_SEGMENTS: &[(&str, &str)] = &[
+    ("/shared/playlists/", ":token"),
+    ("/admin/invites/", ":code"),
+];
+
+/// Keeps an `X-Request-Id` sent by a proxy in front of the app, if it looks
+/// like an id, so both logs agree; otherwise makes one up. Everything logged
+/// while handling the request carries it. Query strings are left out of the
+/// log, since they can hold search terms and tokens, and so are share tokens
+/// and invite codes in paths. The duration runs until
+/// the response starts, so streams are logged as they open.
+pub async fn request_log(request: Request, next: Next) -> Response {
+    let id = request
+        .headers()
+        .get(&REQUEST_ID_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .filter(|id| is_valid_id(id))
+        .map(str::to_string)
+        .unwrap_or_else(|| uuid::Uuid::new_v4().simple().to_string());
+
+    let span = info_span!(
+        "request",
+        request_id = %id,
+        method = %request.method(),
+        path = %redacted_path(request.uri().path()),
+    );
+    let started = Instant::now();
+    let mut response = next.run(request).instrument(span.clone()).await;
+    let status = response.status().as_u16();
+    let duration_ms = started.elapsed().as_millis() as u64;
+    span.in_scope(|| {
+        if response.status().is_server_error() {
+            warn!(status, duration_ms, "request failed");
+        } else {
+            info!(status, duration_ms, "request finished");
+        }
+    });
+
+    if let Ok(value) = HeaderValue::from_str(&id) {
+        response.headers_mut().insert(REQUEST_ID_HEADER, value);
+    }
+    response
+}
+
+/// `path` with [`SECRET_SEGMENTS`] replaced by their placeholders.
+fn redacted_path(path: &str) -> Cow<'_, str> {
+    let mut path = Cow::Borrowed(path);
+    for (prefix, placeholder) in SECRET_SEGMENTS {
+        let Some(start) = path.find(prefix).map(|i| i + prefix.len()) else {
+            continue;
+        };
+        let end = path[start..].find('/').map_or(path.len(), |i| start + i);
+        if end > start {
+            path = Cow::Owned(format!("{}{}{}", &path[..start], placeholder, &path[end..]));
+        }
+    }
+    path
+}
+
+fn is_valid_id(id: &str) -> bool {
+    !id.is_empty()
+        && id.len() <= MAX_REQUEST_ID_LEN
+        && id.bytes().all(|b| b.is_ascii_alphanumeric() || matches!(b, b'-' | b'_' | b'.'))
+}
@@ -0,0 +1,149 @@
+use serde::Serialize;
+
+use crate::db::{self, Db};
+
+/// Recent searches shown on the search page.
+pub const RECENT_LIMIT: i64 = 10;
+
+/// Each user's least recently searched queries beyond this many are dropped.
+const MAX_ENTRIES_PER_USER: i64 = 100;
+
+/// Longer queries are not remembered.
+const MAX_QUERY_LENGTH: usize = 200;
+
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct SearchHistoryEntry {
+    pub id: i64,
+    pub query: String,
+    pub search_count: i64,
+    pub last_searched_at: String,
+}
+
+/// What each user has searched for, and how often.
+#[derive(Debug, Clone)]
+pub struct SearchHistory {
+    db: Db,
+}
+
+impl SearchHistory {
+    pub fn new(db: Db) -> Self {
+        Self { db }
+    }
+
+    /// Counts a search. Queries shorter than two characters are ignored.
+    pub async fn record(&self, user_id: i64, query: &str) -> anyhow::Result<()> {
+        let query = query.split_whitespace().collect::<Vec<_>>().join(" ");
+        if query.chars().count() < 2 || query.len() > MAX_QUERY_LENGTH {
+            return Ok(());
+        }
+
+        let mut tx = self.db.begin().await?;
+        db::query(
+            r#"
+            INSERT INTO search_history (user_id, query) VALUES (?, ?)
+            ON CONFLICT(user_id, query) DO UPDATE SET
+                query = excluded.query,
+                search_count = search_history.search_count + 1,
+                last_searched_at = datetime('now')
+            "#
+        )
+        .bind(user_id)
+        .bind(&query)
+        .execute(&mut tx)
+        .await?;
+        db::query(
+            r#"
+            DELETE FROM search_history
+            WHERE user_id = ? AND id NOT IN (
+                SELECT id FROM search_history
+                WHERE user_id = ?
+                ORDER BY last_searched_at DESC, id DESC
+                LIMIT ?
+            )
+            "#
+        )
+        .bind(user_id)
+        .bind(user_id)
+        .bind(MAX_ENTRIES_PER_USER)
+        .execute(&mut tx)
+        .await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Most recently searched first.
+    pub async fn recent(&self, user_id: i64, limit: i64) -> anyhow::Result<Vec<SearchHistoryEntry>> {
+        let entries = db::query_as(
+            r#"
+            SELECT id, query, search_count, last_searched_at
+            FROM search_history
+            WHERE user_id = ?
+            ORDER BY last_searched_at DESC, id DESC
+            LIMIT ?
+            "#
+        )
+        .bind(user_id)
+        .bind(limit)
+        .fetch_all(&self.db)
+        .await?;
+        Ok(entries)
+    }
+
+    /// Past queries starting with `prefix`, most often searched first.
+    pub async fn frequent(&self, user_id: i64, prefix: &str) -> anyhow::Result<Vec<SearchHistoryEntry>> {
+        let pattern = format!(
+            "{}%",
+            prefix.trim().replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_")
+        );
+        let entries = db::query_as(
+            r#"
+            SELECT id, query, search_count, last_searched_at
+            FROM search_history
+            WHERE user_id = ? AND query LIKE ? ESCAPE '\'
+            ORDER BY search_count DESC, last_searched_at DESC
+            LIMIT ?
+            "#
+        )
+        .bind(user_id)
+        .bind(pattern)
+        .bind(RECENT_LIMIT)
+        .fetch_all(&self.db)
+        .await?;
+        Ok(entries)
+    }
+
+    /// Whether the entry existed and was the user's.
+    pub async fn delete(&self, user_id: i64, id: i64) -> anyhow::Result<bool> {
+        let result = db::query("DELETE FROM search_history WHERE id = ? AND user_id = ?")
+            .bind(id)
+            .bind(user_id)
+            .execute(&self.db)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Returns how many entries were removed.
+    pub async fn clear(&self, user_id: i64) -> anyhow::Result<u64> {
+        let result = db::query("DELETE FROM search_history WHERE user_id = ?")
+            .bind(user_id)
+            .execute(&self.db)
+            .await?;
+        Ok(result.rows_affected())
+    }
+}
+
+/// Moves suggestions for titles the user has searched for before to the
+/// front, most searched first. Otherwise TMDB's order is kept.
+pub fn rank_suggestions(
+    suggestions: &mut [crate::tmdb::Suggestion],
+    past: &[SearchHistoryEntry],
+) {
+    let score = |title: &str| -> i64 {
+        let title = title.to_lowercase();
+        past.iter()
+            .filter(|entry| title.contains(&entry.query.to_lowercase()))
+            .map(|entry| entry.search_count)
+            .sum()
+    };
+    suggestions.sort_by_cached_key(|suggestion| std::cmp::Reverse(score(&suggestion.title)));
+}
@@ -0,0 +1,222 @@
+use std::time::Duration;
+
+use chrono::{Datelike, NaiveDate, Utc};
+use serde::Serialize;
+use tracing::{error, info};
+
+use crate::db::{self, Db};
+use crate::AppState;
+
+/// How often `watch_history` is rolled up into the stats tables.
+const ROLLUP_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Days, today included, that the rollup recomputes. History can still
+/// change on them; earlier days are final.
+const OPEN_DAYS: u64 = 2;
+
+/// How many genres and days the summary lists.
+const TOP_LIMIT: i64 = 10;
+
+const WEEKDAYS: [&str; 7] = ["Sunday", "Monday", "Tuesday", "Wednesday", "Thursday", "Friday", "Saturday"];
+
+#[derive(Debug, Serialize)]
+pub struct StatsSummary {
+    /// Days counted, today included, or `None` for all time.
+    pub period_days: Option<u32>,
+    pub total_seconds: i64,
+    pub total_hours: f64,
+    pub days_watched: i64,
+    pub top_genres: Vec<GenreTotal>,
+    pub busiest_days: Vec<DayTotal>,
+    /// Sunday first.
+    pub weekdays: Vec<WeekdayTotal>,
+}
+
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct GenreTotal {
+    pub genre: String,
+    pub seconds: i64,
+}
+
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct DayTotal {
+    /// `YYYY-MM-DD`, UTC.
+    pub day: String,
+    pub seconds: i64,
+    /// Distinct movies and shows watched that day.
+    pub titles: i64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct WeekdayTotal {
+    pub weekday: &'static str,
+    pub seconds: i64,
+}
+
+/// Per-day and per-genre watch time, kept in `watch_stats_daily` and
+/// `watch_stats_genres`.
+#[derive(Debug, Clone)]
+pub struct WatchStats {
+    db: Db,
+}
+
+impl WatchStats {
+    pub fn new(db: Db) -> Self {
+        Self { db }
+    }
+
+    /// Recomputes today and yesterday (UTC), which history can still change,
+    /// and adds older days not rolled up yet. Days before that are final:
+    /// history later pruned, or moved to a newer day by a rewatch, doesn't
+    /// change their totals.
+    pub async fn rollup(&self) -> anyhow::Result<u64> {
+        let open_since = (Utc::now().date_naive() - chrono::Days::new(OPEN_DAYS - 1)).to_string();
+        let mut tx = self.db.begin().await?;
+
+        for table in ["watch_stats_daily", "watch_stats_genres"] {
+            db::query(&format!("DELETE FROM {table} WHERE day >= ?"))
+                .bind(&open_since)
+                .execute(&mut tx)
+                .await?;
+        }
+
+        // Genres come from stored metadata, so titles whose page was never
+        // opened count toward the totals only. Inserted before the totals,
+        // so a day counts as rolled up only once both are in.
+        db::query(
+            r#"
+            INSERT INTO watch_stats_genres (user_id, day, genre, seconds)
+            SELECT h.user_id, date(h.watched_at), json_extract(g.value, '$.name'), SUM(h.progress_seconds)
+            FROM watch_history h
+            JOIN (
+                SELECT tmdb_id, 'movie' AS media_type, genres FROM movies
+                UNION ALL
+                SELECT tmdb_id, 'tv' AS media_type, genres FROM tv_shows
+            ) t ON t.tmdb_id = h.tmdb_id AND t.media_type = h.media_type
+            CROSS JOIN json_each(t.genres) g
+            WHERE json_extract(g.value, '$.name') IS NOT NULL
+              AND NOT EXISTS (
+                  SELECT 1 FROM watch_stats_daily d
+                  WHERE d.user_id = h.user_id AND d.day = date(h.watched_at)
+              )
+            GROUP BY h.user_id, date(h.watched_at), json_extract(g.value, '$.name')
+            "#
+        )
+        .execute(&mut tx)
+        .await?;
+
+        let days = db::query(
+            r#"
+            INSERT INTO watch_stats_daily (user_id, day, seconds, titles)
+            SELECT user_id, date(watched_at), SUM(progress_seconds), COUNT(DISTINCT media_type || ':' || tmdb_id)
+            FROM watch_history
+            GROUP BY user_id, date(watched_at)
+            ON CONFLICT DO NOTHING
+            "#
+        )
+        .execute(&mut tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(days.rows_affected())
+    }
+
+    /// Totals for the last `days` days, or all time.
+    pub async fn summary(&self, user_id: i64, days: Option<u32>) -> anyhow::Result<StatsSummary> {
+        // Days are `YYYY-MM-DD` text, so the empty string precedes them all.
+        let since = match days {
+            Some(days) => (Utc::now().date_naive() - chrono::Days::new(u64::from(days.max(1)) - 1)).to_string(),
+            None => String::new(),
+        };
+
+        let (total_seconds, days_watched): (i64, i64) = db::query_as(
+            r#"
+            SELECT CAST(COALESCE(SUM(seconds), 0) AS BIGINT), COUNT(*)
+            FROM watch_stats_daily
+            WHERE user_id = ? AND day >= ?
+            "#
+        )
+        .bind(user_id)
+        .bind(&since)
+        .fetch_one(&self.db)
+        .await?;
+
+        let top_genres = db::query_as(
+            r#"
+            SELECT genre, CAST(SUM(seconds) AS BIGINT) AS seconds
+            FROM watch_stats_genres
+            WHERE user_id = ? AND day >= ?
+            GROUP BY genre
+            ORDER BY seconds DESC, genre
+            LIMIT ?
+            "#
+        )
+        .bind(user_id)
+        .bind(&since)
+        .bind(TOP_LIMIT)
+        .fetch_all(&self.db)
+        .await?;
+
+        let busiest_days = db::query_as(
+            r#"
+            SELECT day, seconds, titles
+            FROM watch_stats_daily
+            WHERE user_id = ? AND day >= ? AND seconds > 0
+            ORDER BY seconds DESC, day DESC
+            LIMIT ?
+            "#
+        )
+        .bind(user_id)
+        .bind(&since)
+        .bind(TOP_LIMIT)
+        .fetch_all(&self.db)
+        .await?;
+
+        let by_day: Vec<(String, i64)> = db::query_as(
+            r#"
+            SELECT day, seconds
+            FROM watch_stats_daily
+            WHERE user_id = ? AND day >= ?
+            "#
+        )
+        .bind(user_id)
+        .bind(&since)
+        .fetch_all(&self.db)
+        .await?;
+        let mut by_weekday = [0; 7];
+        for (day, seconds) in by_day {
+            if let Ok(day) = NaiveDate::parse_from_str(&day, "%Y-%m-%d") {
+                by_weekday[day.weekday().num_days_from_sunday() as usize] += seconds;
+            }
+        }
+        let weekdays = WEEKDAYS
+            .iter()
+            .zip(by_weekday)
+            .map(|(weekday, seconds)| WeekdayTotal { weekday, seconds })
+            .collect();
+
+        Ok(StatsSummary {
+            period_days: days,
+            total_seconds,
+            total_hours: (total_seconds as f64 / 360.0).round() / 10.0,
+            days_watched,
+            top_genres,
+            busiest_days,
+            weekdays,
+        })
+    }
+}
+
+/// Rolls up watch history once an hour, starting at startup.
+pub fn spawn_rollup_job(state: AppState) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(ROLLUP_INTERVAL);
+        loop {
+            interval.tick().await;
+            match state.stats.rollup().await {
+                Ok(days) => info!("Rolled up watch stats for {} user-days", days),
+                Err(e) => error!("Watch stats rollup failed: {}", e),
+            }
+        }
+    });
+}
@@ -0,0 +1,122 @@
+use std::time::Duration;
+
+use tracing::warn;
+
+use crate::db::{self, Db};
+use crate::providers::StreamSource;
+
+/// Identifies one provider's streams for a title. Movies use season and episode 0.
+#[derive(Debug, Clone)]
+pub struct StreamCacheKey<'a> {
+    pub provider: &'a str,
+    pub tmdb_id: i64,
+    pub media_type: &'a str,
+    pub season: i64,
+    pub episode: i64,
+    /// `EmbedOptions::cache_key()`; different options resolve to different URLs.
+    pub options: String,
+}
+
+/// Resolved stream URLs kept in the `streaming_cache` table until they expire.
+#[derive(Debug, Clone)]
+pub struct StreamCache {
+    db: Db,
+}
+
+impl StreamCache {
+    pub fn new(db: Db) -> Self {
+        Self { db }
+    }
+
+    pub async fn get(&self, key: &StreamCacheKey<'_>) -> anyhow::Result<Option<Vec<StreamSource>>> {
+        let streams: Option<String> = db::query_scalar(
+            r#"
+            SELECT streams FROM streaming_cache
+            WHERE provider = ? AND tmdb_id = ? AND media_type = ? AND season_number = ?
+                AND episode_number = ? AND options = ? AND expires_at > datetime('now')
+            "#
+        )
+        .bind(key.provider)
+        .bind(key.tmdb_id)
+        .bind(key.media_type)
+        .bind(key.season)
+        .bind(key.episode)
+        .bind(&key.options)
+        .fetch_optional(&self.db)
+        .await?;
+
+        match streams {
+            Some(json) => Ok(Some(serde_json::from_str(&json)?)),
+            None => Ok(None),
+        }
+    }
+
+    pub async fn put(&self, key: &StreamCacheKey<'_>, streams: &[StreamSource], ttl: Duration) -> anyhow::Result<()> {
+        db::query(
+            r#"
+            INSERT INTO streaming_cache
+            (provider, tmdb_id, media_type, season_number, episode_number, options, streams, expires_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?, datetime('now', ?))
+            ON CONFLICT(provider, tmdb_id, media_type, season_number, episode_number, options) DO UPDATE SET
+                streams = excluded.streams,
+                expires_at = excluded.expires_at,
+                created_at = datetime('now')
+            "#
+        )
+        .bind(key.provider)
+        .bind(key.tmdb_id)
+        .bind(key.media_type)
+        .bind(key.season)
+        .bind(key.episode)
+        .bind(&key.options)
+        .bind(serde_json::to_string(streams)?)
+        .bind(format!("+{} seconds", ttl.as_secs()))
+        .execute(&self.db)
+        .await?;
+        Ok(())
+    }
+
+    /// Drops one provider's cached streams for a title or episode, whatever the options.
+    pub async fn remove(
+        &self,
+        provider: &str,
+        tmdb_id: i64,
+        media_type: &str,
+        season: i64,
+        episode: i64,
+    ) -> anyhow::Result<()> {
+        db::query(
+            r#"
+            DELETE FROM streaming_cache
+            WHERE provider = ? AND tmdb_id = ? AND media_type = ? AND season_number = ? AND episode_number = ?
+            "#
+        )
+        .bind(provider)
+        .bind(tmdb_id)
+        .bind(media_type)
+        .bind(season)
+        .bind(episode)
+        .execute(&self.db)
+        .await?;
+        Ok(())
+    }
+
+    /// Deletes expired rows and returns how many were removed.
+    pub async fn remove_expired(&self) -> anyhow::Result<u64> {
+        let result = db::query("DELETE FROM streaming_cache WHERE expires_at <= datetime('now')")
+            .execute(&self.db)
+            .await?;
+        Ok(result.rows_affected())
+    }
+}
+
+/// Looks up cached streams, logging rather than failing on database errors.
+pub async fn lookup(cache: &StreamCache, key: &StreamCacheKey<'_>) -> Option<Vec<StreamSource>> {
+    match cache.get(key).await {
+        Ok(streams) => streams,
+        Err(e) => {
+            warn!("Could not read stream cache for {}: {}", key.provider, e);
+            None
+        }
+    }
+}
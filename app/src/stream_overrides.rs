@@ -0,0 +1,114 @@
+use serde::{Deserialize, Serialize};
+
+use crate::db::{self, Db};
+
+/// `StreamSource::server` for streams that come from a pinned URL rather
+/// than a provider.
+pub const PINNED_SERVER: &str = "pinned";
+
+/// An admin's choice of source for one title or episode: either a
+/// registered provider or a fixed embed URL. Movies use season and episode 0.
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct StreamOverride {
+    pub tmdb_id: i64,
+    pub media_type: String,
+    #[serde(default)]
+    pub season_number: i64,
+    #[serde(default)]
+    pub episode_number: i64,
+    #[serde(default)]
+    pub provider: Option<String>,
+    #[serde(default)]
+    pub url: Option<String>,
+}
+
+/// Per-title source pins kept in the `stream_overrides` table.
+#[derive(Debug, Clone)]
+pub struct StreamOverrides {
+    db: Db,
+}
+
+impl StreamOverrides {
+    pub fn new(db: Db) -> Self {
+        Self { db }
+    }
+
+    pub async fn get(
+        &self,
+        tmdb_id: i64,
+        media_type: &str,
+        season: i64,
+        episode: i64,
+    ) -> anyhow::Result<Option<StreamOverride>> {
+        let pin = db::query_as(
+            r#"
+            SELECT tmdb_id, media_type, season_number, episode_number, provider, url
+            FROM stream_overrides
+            WHERE tmdb_id = ? AND media_type = ? AND season_number = ? AND episode_number = ?
+            "#
+        )
+        .bind(tmdb_id)
+        .bind(media_type)
+        .bind(season)
+        .bind(episode)
+        .fetch_optional(&self.db)
+        .await?;
+        Ok(pin)
+    }
+
+    pub async fn list(&self) -> anyhow::Result<Vec<StreamOverride>> {
+        let pins = db::query_as(
+            r#"
+            SELECT tmdb_id, media_type, season_number, episode_number, provider, url
+            FROM stream_overrides
+            ORDER BY created_at DESC
+            "#
+        )
+        .fetch_all(&self.db)
+        .await?;
+        Ok(pins)
+    }
+
+    /// Pins a source, replacing any earlier pin for the same title or episode.
+    pub async fn set(&self, pin: &StreamOverride, user_id: i64) -> anyhow::Result<()> {
+        db::query(
+            r#"
+            INSERT INTO stream_overrides
+            (tmdb_id, media_type, season_number, episode_number, provider, url, created_by)
+            VALUES (?, ?, ?, ?, ?, ?, ?)
+            ON CONFLICT(tmdb_id, media_type, season_number, episode_number) DO UPDATE SET
+                provider = excluded.provider,
+                url = excluded.url,
+                created_by = excluded.created_by,
+                created_at = datetime('now')
+            "#
+        )
+        .bind(pin.tmdb_id)
+        .bind(&pin.media_type)
+        .bind(pin.season_number)
+        .bind(pin.episode_number)
+        .bind(pin.provider.as_deref())
+        .bind(pin.url.as_deref())
+        .bind(user_id)
+        .execute(&self.db)
+        .await?;
+        Ok(())
+    }
+
+    /// Returns whether a pin was removed.
+    pub async fn remove(&self, tmdb_id: i64, media_type: &str, season: i64, episode: i64) -> anyhow::Result<bool> {
+        let result = db::query(
+            r#"
+            DELETE FROM stream_overrides
+            WHERE tmdb_id = ? AND media_type = ? AND season_number = ? AND episode_number = ?
+            "#
+        )
+        .bind(tmdb_id)
+        .bind(media_type)
+        .bind(season)
+        .bind(episode)
+        .execute(&self.db)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+}
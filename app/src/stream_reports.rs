@@ -0,0 +1,140 @@
+use std::collections::HashSet;
+
+use serde::Deserialize;
+
+use crate::db::{self, Db};
+
+/// Reports older than this no longer count against a provider.
+const REPORT_WINDOW: &str = "-24 hours";
+
+/// Viewers who recently reported one title or episode before a provider's
+/// stream is moved behind the others.
+const DEMOTE_THRESHOLD: i64 = 3;
+
+/// How far back reports count against the per-user and per-IP limits.
+const RATE_WINDOW: &str = "-1 hours";
+/// Reports one user may send within `RATE_WINDOW`.
+const MAX_REPORTS_PER_USER: i64 = 20;
+/// Reports one IP may send within `RATE_WINDOW`, across all users.
+const MAX_REPORTS_PER_IP: i64 = 60;
+
+/// A viewer's report that a provider's stream doesn't play.
+#[derive(Debug, Deserialize)]
+pub struct StreamReport {
+    pub tmdb_id: i64,
+    pub media_type: String,
+    /// Provider name, as in `StreamSource::server`.
+    pub provider: String,
+    #[serde(default)]
+    pub season: Option<i64>,
+    #[serde(default)]
+    pub episode: Option<i64>,
+    #[serde(default)]
+    pub reason: Option<String>,
+}
+
+/// Broken-stream reports kept in the `stream_reports` table. Movies use
+/// season and episode 0, like `streaming_cache`.
+#[derive(Debug, Clone)]
+pub struct StreamReports {
+    db: Db,
+}
+
+impl StreamReports {
+    pub fn new(db: Db) -> Self {
+        Self { db }
+    }
+
+    /// Whether `user_id` or `ip` has sent too many reports lately to send another.
+    pub async fn rate_limited(&self, user_id: i64, ip: Option<&str>) -> anyhow::Result<bool> {
+        let (by_user, by_ip): (i64, i64) = db::query_as(
+            r#"
+            SELECT
+                (SELECT COUNT(*) FROM stream_reports WHERE user_id = ? AND reported_at > datetime('now', ?)),
+                (SELECT COUNT(*) FROM stream_reports WHERE ip = ? AND reported_at > datetime('now', ?))
+            "#
+        )
+        .bind(user_id)
+        .bind(RATE_WINDOW)
+        .bind(ip)
+        .bind(RATE_WINDOW)
+        .fetch_one(&self.db)
+        .await?;
+        Ok(by_user >= MAX_REPORTS_PER_USER || by_ip >= MAX_REPORTS_PER_IP)
+    }
+
+    /// Stores a report. Returns false if the user had already reported this
+    /// provider for the same title or episode within `REPORT_WINDOW`.
+    pub async fn record(&self, user_id: i64, ip: Option<&str>, report: &StreamReport) -> anyhow::Result<bool> {
+        let season = report.season.unwrap_or(0);
+        let episode = report.episode.unwrap_or(0);
+        let repeated: bool = db::query_scalar(
+            r#"
+            SELECT EXISTS(
+                SELECT 1 FROM stream_reports
+                WHERE user_id = ? AND provider = ? AND tmdb_id = ? AND media_type = ?
+                    AND season_number = ? AND episode_number = ?
+                    AND reported_at > datetime('now', ?)
+            )
+            "#
+        )
+        .bind(user_id)
+        .bind(&report.provider)
+        .bind(report.tmdb_id)
+        .bind(&report.media_type)
+        .bind(season)
+        .bind(episode)
+        .bind(REPORT_WINDOW)
+        .fetch_one(&self.db)
+        .await?;
+
+        db::query(
+            r#"
+            INSERT INTO stream_reports
+            (user_id, ip, provider, tmdb_id, media_type, season_number, episode_number, reason)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+            "#
+        )
+        .bind(user_id)
+        .bind(ip)
+        .bind(&report.provider)
+        .bind(report.tmdb_id)
+        .bind(&report.media_type)
+        .bind(season)
+        .bind(episode)
+        .bind(report.reason.as_deref())
+        .execute(&self.db)
+        .await?;
+        Ok(!repeated)
+    }
+
+    /// Providers reported for this title or episode by enough different
+    /// viewers to be demoted. Repeat reports from one viewer count once.
+    pub async fn demoted(
+        &self,
+        tmdb_id: i64,
+        media_type: &str,
+        season: i64,
+        episode: i64,
+    ) -> anyhow::Result<HashSet<String>> {
+        let providers: Vec<String> = db::query_scalar(
+            r#"
+            SELECT provider FROM stream_reports
+            WHERE tmdb_id = ? AND media_type = ? AND season_number = ? AND episode_number = ?
+                AND reported_at > datetime('now', ?)
+            GROUP BY provider
+            HAVING COUNT(DISTINCT user_id) >= ?
+            "#
+        )
+        .bind(tmdb_id)
+        .bind(media_type)
+        .bind(season)
+        .bind(episode)
+        .bind(REPORT_WINDOW)
+        .bind(DEMOTE_THRESHOLD)
+        .fetch_all(&self.db)
+        .await?;
+
+        Ok(providers.into_iter().collect())
+    }
+}
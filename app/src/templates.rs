@@ -1,22 +1,95 @@
-use crate::tmdb::{Genre, MovieDetail, SearchResult, TvShowDetail};
-use crate::vidking::StreamSource;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
 
+use crate::auth::{ActiveSession, ContinueWatchingItem, ImageQuality, Permission, PlayerPreferences, Role, UserPreferences, WatchedEpisode, QUALITY_CAPS};
+use crate::changelog::Release;
+use crate::invites::{Invite, MIN_PASSWORD_LENGTH};
+use crate::omdb::ExternalRatings;
+use crate::parental::{ParentalSettings, LIMITS, MIN_PIN_LENGTH};
+use crate::playlists::{Playlist, PlaylistWithItems, MAX_NAME_LENGTH};
+use crate::search_history::SearchHistoryEntry;
+use crate::stats::StatsSummary;
+use crate::tmdb_account::TmdbAccount;
+use crate::tmdb::{Certification, ExternalIds, MediaGenre, MovieDetail, Organization, SearchResponse, SearchResult, SeasonDetail, TvShowDetail, WatchProviders};
+use crate::providers::{ProviderConfig, StreamSource, StreamStatus};
+
+/// Brand color shared by the favicon, browser theme color, and embed accent.
+pub const ACCENT_COLOR: &str = "e50914";
+
+/// Who is looking at a page and how they want it rendered.
+pub struct Viewer<'a> {
+    pub user_id: Option<i64>,
+    pub username: Option<&'a str>,
+    pub role: Role,
+    /// What `role` currently grants, from `role_permissions`.
+    pub permissions: HashSet<Permission>,
+    pub prefs: UserPreferences,
+}
+
+impl Viewer<'_> {
+    pub fn can(&self, permission: Permission) -> bool {
+        self.permissions.contains(&permission)
+    }
+}
+
+/// How far the viewer is through a show, for the TV page.
+pub struct ShowProgress {
+    pub episodes: Vec<WatchedEpisode>,
+    /// Episode lists of the seasons the viewer has started.
+    pub seasons: HashMap<i64, Arc<SeasonDetail>>,
+}
+
+impl ShowProgress {
+    fn watched_in(&self, season_number: i64) -> usize {
+        self.episodes
+            .iter()
+            .filter(|e| e.season_number == season_number && e.completed)
+            .count()
+    }
+
+    fn episode(&self, season_number: i64, episode_number: i64) -> Option<&WatchedEpisode> {
+        self.episodes
+            .iter()
+            .find(|e| e.season_number == season_number && e.episode_number == episode_number)
+    }
+}
+
+/// The viewer's lists, for the watchlist and playlist buttons on a title's page.
+pub struct SavedLists {
+    pub on_watchlist: bool,
+    pub playlists: Vec<Playlist>,
+}
+
+#[allow(clippy::too_many_arguments)]
 pub fn render_home(
-    username: Option<&str>,
+    viewer: &Viewer,
+    whats_new: &[&Release],
+    continue_watching: &[ContinueWatchingItem],
     trending: &[SearchResult],
     popular_tv: &[SearchResult],
+    now_playing: &[SearchResult],
+    upcoming: &[SearchResult],
+    top_rated: &[SearchResult],
+    people: &[SearchResult],
     trending_searches: &[SearchResult],
 ) -> String {
     let mut html = String::new();
 
-    html.push_str(&base_start("RustStream", username));
+    html.push_str(&base_start("RustStream", "/", viewer));
 
     html.push_str(
         r#"
     <div class="home-page">
         <h1>Welcome to RustStream</h1>
         <p>Your favorite movies and TV shows, streamed locally.</p>
-        
+"#,
+    );
+
+    html.push_str(&render_whats_new(whats_new));
+    html.push_str(&render_continue_watching(viewer, continue_watching));
+
+    html.push_str(
+        r#"
         <section class="search-suggestions">
             <h2>Trending Searches</h2>
             <div class="suggestion-tags">
@@ -56,16 +129,15 @@ pub fn render_home(
         let poster = movie
             .poster_path
             .as_ref()
-            .map(|p| format!("https://image.tmdb.org/t/p/w342{}", p))
+            .map(|p| tmdb_image(p, viewer.prefs.quality().poster_size()))
             .unwrap_or_else(|| "/static/placeholder.jpg".to_string());
         let title = movie
             .title
-            .as_ref()
-            .map(|s| s.as_str())
+            .as_deref()
             .unwrap_or("Unknown");
         html.push_str(&format!(
-            r#"<div class="content-card"><a href="/movie/{}"><img src="{}" alt="Movie" onerror="this.src='/static/placeholder.jpg'"><div class="card-info"><h3>{}</h3><p class="rating">⭐ {:.1}</p></div></a></div>"#,
-            movie.id, poster, title, movie.vote_average
+            r#"<div class="content-card"><a href="/movie/{}"><img src="{}"{} alt="Movie" onerror="this.src='/static/placeholder.jpg'"><div class="card-info"><h3>{}</h3><p class="rating">⭐ {:.1}</p></div></a></div>"#,
+            movie.id, poster, poster_srcset(viewer, movie.poster_path.as_deref(), CARD_WIDTH), title, movie.vote_average
         ));
     }
 
@@ -84,12 +156,12 @@ pub fn render_home(
         let poster = show
             .poster_path
             .as_ref()
-            .map(|p| format!("https://image.tmdb.org/t/p/w342{}", p))
+            .map(|p| tmdb_image(p, viewer.prefs.quality().poster_size()))
             .unwrap_or_else(|| "/static/placeholder.jpg".to_string());
-        let name = show.name.as_ref().map(|s| s.as_str()).unwrap_or("Unknown");
+        let name = show.name.as_deref().unwrap_or("Unknown");
         html.push_str(&format!(
-            r#"<div class="content-card"><a href="/tv/{}"><img src="{}" alt="TV Show" onerror="this.src='/static/placeholder.jpg'"><div class="card-info"><h3>{}</h3><p class="rating">⭐ {:.1}</p></div></a></div>"#,
-            show.id, poster, name, show.vote_average
+            r#"<div class="content-card"><a href="/tv/{}"><img src="{}"{} alt="TV Show" onerror="this.src='/static/placeholder.jpg'"><div class="card-info"><h3>{}</h3><p class="rating">⭐ {:.1}</p></div></a></div>"#,
+            show.id, poster, poster_srcset(viewer, show.poster_path.as_deref(), CARD_WIDTH), name, show.vote_average
         ));
     }
 
@@ -97,23 +169,128 @@ pub fn render_home(
         r#"
             </div>
         </section>
-    </div>
 "#,
     );
 
+    html.push_str(&render_movie_section(viewer, "Now Playing in Theaters", now_playing));
+    html.push_str(&render_movie_section(viewer, "Coming Soon", upcoming));
+    html.push_str(&render_top_rated_section(viewer, top_rated));
+    html.push_str(&render_people_section(viewer, people));
+    html.push_str("</div>");
+
     html.push_str(&base_end());
     html
 }
 
+fn render_movie_section(viewer: &Viewer, heading: &str, movies: &[SearchResult]) -> String {
+    if movies.is_empty() {
+        return String::new();
+    }
+
+    let mut html = format!(
+        r#"<section class="content-section"><h2>{}</h2><div class="content-grid">"#,
+        heading
+    );
+    for movie in movies {
+        let poster = movie
+            .poster_path
+            .as_ref()
+            .map(|p| tmdb_image(p, viewer.prefs.quality().poster_size()))
+            .unwrap_or_else(|| "/static/placeholder.jpg".to_string());
+        let title = movie.title.as_deref().unwrap_or("Unknown");
+        let subtitle = match movie.release_date.as_deref() {
+            Some(date) if !date.is_empty() => format!("📅 {}", date),
+            _ => format!("⭐ {:.1}", movie.vote_average),
+        };
+        html.push_str(&format!(
+            r#"<div class="content-card"><a href="/movie/{}"><img src="{}"{} alt="Movie" onerror="this.src='/static/placeholder.jpg'"><div class="card-info"><h3>{}</h3><p class="rating">{}</p></div></a></div>"#,
+            movie.id, poster, poster_srcset(viewer, movie.poster_path.as_deref(), CARD_WIDTH), title, subtitle
+        ));
+    }
+    html.push_str("</div></section>");
+    html
+}
+
+/// Movies and shows together, linked by their `media_type`.
+fn render_top_rated_section(viewer: &Viewer, titles: &[SearchResult]) -> String {
+    if titles.is_empty() {
+        return String::new();
+    }
+
+    let mut html = String::from(
+        r#"<section class="content-section"><h2>Top Rated</h2><div class="content-grid">"#,
+    );
+    for item in titles.iter().take(20) {
+        let poster = item
+            .poster_path
+            .as_ref()
+            .map(|p| tmdb_image(p, viewer.prefs.quality().poster_size()))
+            .unwrap_or_else(|| "/static/placeholder.jpg".to_string());
+        let title = item
+            .title
+            .as_deref()
+            .or(item.name.as_deref())
+            .unwrap_or("Unknown");
+        html.push_str(&format!(
+            r#"<div class="content-card"><a href="/{}/{}"><img src="{}"{} alt="Poster" onerror="this.src='/static/placeholder.jpg'"><div class="card-info"><h3>{}</h3><p class="rating">⭐ {:.1}</p></div></a></div>"#,
+            item.media_type, item.id, poster, poster_srcset(viewer, item.poster_path.as_deref(), CARD_WIDTH), title, item.vote_average
+        ));
+    }
+    html.push_str("</div></section>");
+    html
+}
+
+/// People link to an `actor:` search, which lists their films.
+fn render_people_section(viewer: &Viewer, people: &[SearchResult]) -> String {
+    if people.is_empty() {
+        return String::new();
+    }
+
+    let mut html = String::from(
+        r#"<section class="cast-section"><h2>Popular People</h2><div class="cast-grid">"#,
+    );
+    for person in people.iter().take(12) {
+        let name = person.name.as_deref().unwrap_or("Unknown");
+        let profile = person
+            .profile_path
+            .as_ref()
+            .map(|p| tmdb_image(p, viewer.prefs.quality().profile_size()))
+            .unwrap_or_else(|| "/static/placeholder-avatar.jpg".to_string());
+        let search = reqwest::Url::parse_with_params(
+            "http://localhost/search",
+            [("q", format!("actor:{}", name))],
+        )
+        .expect("static base url");
+        html.push_str(&format!(
+            r#"<div class="cast-member"><a href="/search?{}"><img src="{}" alt="{}" onerror="this.src='/static/placeholder-avatar.jpg'"><h4>{}</h4><p>{}</p></a></div>"#,
+            escape_html(search.query().unwrap_or_default()),
+            profile,
+            escape_html(name),
+            escape_html(name),
+            escape_html(person.known_for_department.as_deref().unwrap_or(""))
+        ));
+    }
+    html.push_str("</div></section>");
+    html
+}
+
 pub fn render_search(
-    username: Option<&str>,
+    viewer: &Viewer,
     query: &str,
     results: &[SearchResult],
-    genres: &[Genre],
+    genres: &[MediaGenre],
+    recent_searches: &[SearchHistoryEntry],
+    next_page: Option<&str>,
+    mode_link: Option<(&str, &str)>,
 ) -> String {
     let mut html = String::new();
 
-    html.push_str(&base_start("Search - RustStream", username));
+    let page_title = if query.is_empty() {
+        "Search - RustStream".to_string()
+    } else {
+        format!("{} - Search - RustStream", query)
+    };
+    html.push_str(&base_start(&page_title, "/search", viewer));
 
     html.push_str(
         r#"
@@ -124,10 +301,41 @@ pub fn render_search(
     );
     html.push_str(query);
     html.push_str(
-        r#"" autofocus>
+        r#"" list="search-suggestions" autocomplete="off" autofocus>
+            <datalist id="search-suggestions"></datalist>
             <button type="submit">Search</button>
         </form>
-        
+        <script>
+        (function() {
+            var input = document.querySelector(".search-box input[name=q]");
+            var list = document.getElementById("search-suggestions");
+            var timer;
+            input.addEventListener("input", function() {
+                clearTimeout(timer);
+                var q = input.value.trim();
+                if (q.length < 2) return;
+                timer = setTimeout(function() {
+                    fetch("/api/search/suggest?q=" + encodeURIComponent(q))
+                        .then(function(r) { return r.ok ? r.json() : []; })
+                        .then(function(items) {
+                            list.innerHTML = "";
+                            items.forEach(function(item) {
+                                var option = document.createElement("option");
+                                option.value = item.title;
+                                option.label = item.year ? item.title + " (" + item.year + ")" : item.title;
+                                list.appendChild(option);
+                            });
+                        })
+                        .catch(function() {});
+                }, 200);
+            });
+        })();
+        </script>
+"#,
+    );
+    html.push_str(&render_recent_searches(recent_searches));
+    html.push_str(
+        r#"
         <details class="search-filters">
             <summary>Filters</summary>
             <div class="filter-grid">
@@ -141,8 +349,12 @@ pub fn render_search(
     for genre in genres {
         html.push_str(&format!(
             r#"<option value="{}">{}</option>"#,
-            genre.name.to_lowercase(),
-            genre.name
+            genre.id,
+            if genre.is_tv_only() {
+                format!("{} (TV)", genre.name)
+            } else {
+                genre.name.clone()
+            }
         ));
     }
 
@@ -179,18 +391,24 @@ pub fn render_search(
 "#,
     );
 
-    if !query.is_empty() || results.is_empty() == false {
+    if let Some((url, label)) = mode_link {
+        html.push_str(&format!(
+            r#"<div class="search-mode"><a href="{}" class="play-button-small">{} →</a></div>"#,
+            escape_html(url),
+            label
+        ));
+    }
+
+    if !query.is_empty() || !results.is_empty() {
         if results.is_empty() {
-            html.push_str(&format!(
-                r#"<div class="no-results">No results found</div>"#,
-            ));
+            html.push_str(r#"<div class="no-results">No results found</div>"#);
         } else {
             html.push_str(r#"<div class="content-grid">"#);
             for item in results {
                 let poster = item
                     .poster_path
                     .as_ref()
-                    .map(|p| format!("https://image.tmdb.org/t/p/w342{}", p))
+                    .map(|p| tmdb_image(p, viewer.prefs.quality().poster_size()))
                     .unwrap_or_else(|| "/static/placeholder.jpg".to_string());
                 let name = item
                     .title
@@ -203,39 +421,313 @@ pub fn render_search(
                 } else {
                     format!("/tv/{}", item.id)
                 };
-                let media_label = if item.media_type == "movie" {
+                let media_label = if item.is_anime() {
+                    "Anime"
+                } else if item.media_type == "movie" {
                     "Movie"
                 } else {
                     "TV Show"
                 };
                 html.push_str(&format!(
-                    r#"<div class="content-card"><a href="{}"><img src="{}" alt="Content" onerror="this.src='/static/placeholder.jpg'"><div class="card-info"><h3>{}</h3><p class="rating">⭐ {:.1}</p><span class="media-type">{}</span></div></a></div>"#,
-                    link, poster, name, item.vote_average, media_label
+                    r#"<div class="content-card"><a href="{}"><img src="{}"{} alt="Content" onerror="this.src='/static/placeholder.jpg'"><div class="card-info"><h3>{}</h3><p class="rating">⭐ {:.1}</p><span class="media-type">{}</span></div></a></div>"#,
+                    link, poster, poster_srcset(viewer, item.poster_path.as_deref(), CARD_WIDTH), name, item.vote_average, media_label
                 ));
             }
             html.push_str("</div>");
         }
     }
 
+    if let Some(url) = next_page {
+        html.push_str(&format!(
+            r#"<div class="pagination"><a href="{}" class="play-button-small">More results →</a></div>"#,
+            escape_html(url)
+        ));
+    }
+
+    html.push_str("</div>");
+    html.push_str(&base_end());
+    html
+}
+
+/// A paginated grid of titles, shared by the keyword and other browse pages.
+pub fn render_browse(
+    viewer: &Viewer,
+    heading: &str,
+    base_path: &str,
+    results: &SearchResponse,
+) -> String {
+    let mut html = String::new();
+
+    html.push_str(&base_start(
+        &format!("{} - RustStream", heading),
+        base_path,
+        viewer,
+    ));
+
+    html.push_str(&format!(
+        r#"<div class="search-page browse-page"><h1>{}</h1>"#,
+        heading
+    ));
+
+    if results.results.is_empty() {
+        html.push_str(r#"<div class="no-results">No titles found</div>"#);
+    } else {
+        html.push_str(r#"<div class="content-grid">"#);
+        for item in &results.results {
+            let poster = item
+                .poster_path
+                .as_ref()
+                .map(|p| tmdb_image(p, viewer.prefs.quality().poster_size()))
+                .unwrap_or_else(|| "/static/placeholder.jpg".to_string());
+            let name = item
+                .title
+                .as_ref()
+                .or(item.name.as_ref())
+                .map(|s| s.as_str())
+                .unwrap_or("Unknown");
+            let link = if item.media_type == "tv" {
+                format!("/tv/{}", item.id)
+            } else {
+                format!("/movie/{}", item.id)
+            };
+            html.push_str(&format!(
+                r#"<div class="content-card"><a href="{}"><img src="{}"{} alt="Content" onerror="this.src='/static/placeholder.jpg'"><div class="card-info"><h3>{}</h3><p class="rating">⭐ {:.1}</p></div></a></div>"#,
+                link, poster, poster_srcset(viewer, item.poster_path.as_deref(), CARD_WIDTH), name, item.vote_average
+            ));
+        }
+        html.push_str("</div>");
+    }
+
+    if results.total_pages > 1 {
+        let separator = if base_path.contains('?') { '&' } else { '?' };
+        html.push_str(r#"<div class="pagination">"#);
+        if results.page > 1 {
+            html.push_str(&format!(
+                r#"<a href="{}{}page={}" class="play-button-small">← Previous</a>"#,
+                base_path,
+                separator,
+                results.page - 1
+            ));
+        }
+        html.push_str(&format!(
+            r#"<span class="page-info">Page {} of {}</span>"#,
+            results.page, results.total_pages
+        ));
+        if results.page < results.total_pages {
+            html.push_str(&format!(
+                r#"<a href="{}{}page={}" class="play-button-small">Next →</a>"#,
+                base_path,
+                separator,
+                results.page + 1
+            ));
+        }
+        html.push_str("</div>");
+    }
+
     html.push_str("</div>");
     html.push_str(&base_end());
     html
 }
 
-pub fn render_movie_detail(username: Option<&str>, movie: &MovieDetail) -> String {
+fn render_whats_new(releases: &[&Release]) -> String {
+    if releases.is_empty() {
+        return String::new();
+    }
+
+    let mut html = String::from(r#"<section class="whats-new"><h2>What's new</h2>"#);
+    for release in releases {
+        html.push_str(&format!(
+            r#"<h3>Version {} <span class="release-date">{}</span></h3><ul>"#,
+            escape_html(&release.version),
+            escape_html(&release.date)
+        ));
+        for highlight in &release.highlights {
+            html.push_str(&format!("<li>{}</li>", escape_html(highlight)));
+        }
+        html.push_str("</ul>");
+    }
+    html.push_str(
+        r#"<form action="/whats-new/dismiss" method="post"><button type="submit" class="play-button-small">Got it</button></form></section>"#,
+    );
+    html
+}
+
+/// Links to the title's IMDb, TheTVDB, and Wikidata pages, where TMDB has
+/// the ids.
+fn render_external_links(ids: Option<&ExternalIds>) -> String {
+    let links = ids.map(|ids| ids.links()).unwrap_or_default();
+    if links.is_empty() {
+        return String::new();
+    }
+    let links: Vec<String> = links
+        .iter()
+        .map(|(site, url)| {
+            format!(
+                r#"<a href="{}" target="_blank" rel="noopener noreferrer">{}</a>"#,
+                escape_html(url),
+                site
+            )
+        })
+        .collect();
+    format!(r#"<p class="external-links">{}</p>"#, links.join(" · "))
+}
+
+/// Links to the network and company browse pages behind a title.
+fn render_studio_links(networks: &[Organization], companies: &[Organization], media_type: &str) -> String {
+    let links = |items: &[Organization], path: &str, suffix: &str| {
+        items
+            .iter()
+            .map(|o| {
+                format!(
+                    r#"<a href="/{}/{}{}">{}</a>"#,
+                    path,
+                    o.id,
+                    suffix,
+                    escape_html(&o.name)
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+
+    let mut parts = Vec::new();
+    if !networks.is_empty() {
+        parts.push(format!("Network: {}", links(networks, "network", "")));
+    }
+    if !companies.is_empty() {
+        let suffix = if media_type == "tv" { "?type=tv" } else { "" };
+        parts.push(format!("Studio: {}", links(companies, "company", suffix)));
+    }
+
+    if parts.is_empty() {
+        String::new()
+    } else {
+        format!(r#"<p class="studios">{}</p>"#, parts.join(" · "))
+    }
+}
+
+fn certification_badge(certification: Option<&Certification>) -> String {
+    certification
+        .map(|c| {
+            format!(
+                r#"<span class="certification" title="Rated in {}">{}</span>"#,
+                escape_html(&c.region),
+                escape_html(&c.rating)
+            )
+        })
+        .unwrap_or_default()
+}
+
+/// IMDb and Rotten Tomatoes scores next to the TMDB rating, when OMDb is set up.
+fn external_ratings_badges(ratings: Option<&ExternalRatings>) -> String {
+    let Some(ratings) = ratings else {
+        return String::new();
+    };
+    let mut html = String::new();
+    if let Some(imdb) = &ratings.imdb_rating {
+        let votes = ratings
+            .imdb_votes
+            .as_deref()
+            .map(|v| format!(" ({} votes)", escape_html(v)))
+            .unwrap_or_default();
+        html.push_str(&format!(
+            r#"<span class="external-rating" title="IMDb{}">IMDb {}</span>"#,
+            votes,
+            escape_html(imdb)
+        ));
+    }
+    if let Some(tomatoes) = &ratings.rotten_tomatoes {
+        html.push_str(&format!(
+            r#"<span class="external-rating" title="Rotten Tomatoes">🍅 {}</span>"#,
+            escape_html(tomatoes)
+        ));
+    }
+    html
+}
+
+/// Links to run a past search again, each with a button to forget it.
+fn render_recent_searches(entries: &[SearchHistoryEntry]) -> String {
+    if entries.is_empty() {
+        return String::new();
+    }
+    let mut html = String::from(
+        r#"<section class="search-suggestions recent-searches"><h2>Recent Searches</h2><div class="suggestion-tags">"#,
+    );
+    for entry in entries {
+        let link = reqwest::Url::parse_with_params("http://localhost/search", [("q", &entry.query)])
+            .map(|url| format!("/search?{}", url.query().unwrap_or_default()))
+            .unwrap_or_else(|_| "/search".to_string());
+        html.push_str(&format!(
+            r#"<span class="recent-search"><a href="{}" class="suggestion-tag">{}</a><form action="/search/history" method="post"><input type="hidden" name="action" value="delete"><input type="hidden" name="id" value="{}"><button type="submit" class="recent-search-remove" title="Remove" aria-label="Remove {}">&times;</button></form></span>"#,
+            escape_html(&link),
+            escape_html(&entry.query),
+            entry.id,
+            escape_html(&entry.query)
+        ));
+    }
+    html.push_str(
+        r#"</div><form class="recent-searches-clear" action="/search/history" method="post"><input type="hidden" name="action" value="clear"><button type="submit" class="secondary-button">Clear search history</button></form></section>"#,
+    );
+    html
+}
+
+/// The home page's row of titles left partway through. Nothing when empty.
+fn render_continue_watching(viewer: &Viewer, items: &[ContinueWatchingItem]) -> String {
+    if items.is_empty() {
+        return String::new();
+    }
+    let mut html = String::from(
+        r#"<section class="content-section"><h2>Continue Watching</h2><div class="content-grid">"#,
+    );
+    for item in items {
+        let poster = item
+            .poster_path
+            .as_ref()
+            .map(|p| tmdb_image(p, viewer.prefs.quality().poster_size()))
+            .unwrap_or_else(|| "/static/placeholder.jpg".to_string());
+        let label = match (item.season_number, item.episode_number) {
+            (Some(season), Some(episode)) => format!("S{:02}E{:02}", season, episode),
+            _ => "Movie".to_string(),
+        };
+        html.push_str(&format!(
+            r#"<div class="content-card"><a href="{}"><img src="{}"{} alt="{}" onerror="this.src='/static/placeholder.jpg'"><div class="card-info"><h3>{}</h3><p class="rating">{}</p><span class="progress-time">{} min in</span></div></a></div>"#,
+            escape_html(&item.player_url()),
+            poster,
+            poster_srcset(viewer, item.poster_path.as_deref(), CARD_WIDTH),
+            escape_html(&item.title),
+            escape_html(&item.title),
+            label,
+            item.progress_seconds / 60
+        ));
+    }
+    html.push_str("</div></section>");
+    html
+}
+
+pub fn render_movie_detail(
+    viewer: &Viewer,
+    movie: &MovieDetail,
+    providers: Option<&WatchProviders>,
+    saved: Option<&SavedLists>,
+) -> String {
     let mut html = String::new();
 
-    html.push_str(&base_start(&movie.title, username));
+    html.push_str(&base_start(
+        &titled_page(&movie.title, movie.release_date.as_deref()),
+        &format!("/movie/{}", movie.id),
+        viewer,
+    ));
 
     let backdrop = movie
         .backdrop_path
         .as_ref()
-        .map(|p| format!("https://image.tmdb.org/t/p/original{}", p))
+        .filter(|_| viewer.prefs.show_backdrops())
+        .map(|p| tmdb_image(p, viewer.prefs.quality().backdrop_size()))
         .unwrap_or_default();
     let poster = movie
         .poster_path
         .as_ref()
-        .map(|p| format!("https://image.tmdb.org/t/p/w500{}", p))
+        .map(|p| tmdb_image(p, viewer.prefs.quality().detail_poster_size()))
         .unwrap_or_else(|| "/static/placeholder.jpg".to_string());
     let year = movie
         .release_date
@@ -250,22 +742,25 @@ pub fn render_movie_detail(username: Option<&str>, movie: &MovieDetail) -> Strin
     let genres_str = genres.join(", ");
     let overview = movie
         .overview
-        .as_ref()
-        .map(|s| s.as_str())
+        .as_deref()
         .unwrap_or("No overview available.");
 
     html.push_str(&format!(
-        r#"<div class="detail-page"><div class="detail-hero" style="background-image: linear-gradient(rgba(0,0,0,0.7), rgba(0,0,0,0.9)), url({});"><div class="detail-content"><img class="detail-poster" src="{}" alt="{}" onerror="this.src='/static/placeholder.jpg'"><div class="detail-info"><h1>{}</h1><div class="meta"><span class="rating">⭐ {:.1} ({} votes)</span><span class="year">{}</span><span class="runtime">{}</span></div><p class="genres">{}</p><p class="overview">{}</p><div class="actions"><a href="/player/movie/{}" class="play-button">▶ Watch Now</a></div></div></div></div>"#,
-        backdrop, poster, movie.title, movie.title, movie.vote_average, movie.vote_count, year, runtime, genres_str, overview, movie.id
+        r#"<div class="detail-page"><div class="detail-hero" style="background-image: linear-gradient(rgba(0,0,0,0.7), rgba(0,0,0,0.9)), url({});"><div class="detail-content"><img class="detail-poster" src="{}"{} alt="{}" onerror="this.src='/static/placeholder.jpg'"><div class="detail-info"><h1>{}</h1><div class="meta"><span class="rating">⭐ {:.1} ({} votes)</span>{}{}<span class="year">{}</span><span class="runtime">{}</span></div><p class="genres">{}</p>{}{}<p class="overview">{}</p><div class="actions"><a href="/player/movie/{}" class="play-button">▶ Watch Now</a> {}</div></div></div></div>"#,
+        backdrop, poster, poster_srcset(viewer, movie.poster_path.as_deref(), DETAIL_POSTER_WIDTH), movie.title, movie.title, movie.vote_average, movie.vote_count, external_ratings_badges(movie.external_ratings.as_ref()), certification_badge(movie.certification.as_ref()), year, runtime, genres_str, render_studio_links(&[], &movie.production_companies, "movie"), render_external_links(movie.external_ids.as_ref()), overview, movie.id, list_buttons("movie", movie.id, &movie.title, movie.poster_path.as_deref(), saved)
     ));
 
+    if let Some(providers) = providers {
+        html.push_str(&render_watch_providers(providers));
+    }
+
     if let Some(ref credits) = movie.credits {
         html.push_str(r#"<section class="cast-section"><h2>Cast</h2><div class="cast-grid">"#);
         for member in &credits.cast {
             let profile = member
                 .profile_path
                 .as_ref()
-                .map(|p| format!("https://image.tmdb.org/t/p/w185{}", p))
+                .map(|p| tmdb_image(p, viewer.prefs.quality().profile_size()))
                 .unwrap_or_else(|| "/static/placeholder-avatar.jpg".to_string());
             html.push_str(&format!(
                 r#"<div class="cast-member"><img src="{}" alt="{}" onerror="this.src='/static/placeholder-avatar.jpg'"><h4>{}</h4><p>{}</p></div>"#,
@@ -275,6 +770,27 @@ pub fn render_movie_detail(username: Option<&str>, movie: &MovieDetail) -> Strin
         html.push_str("</div></section>");
     }
 
+    if let Some(ref recommendations) = movie.recommendations {
+        if !recommendations.results.is_empty() {
+            html.push_str(
+                r#"<section class="similar-section"><h2>Recommended</h2><div class="content-grid">"#,
+            );
+            for item in &recommendations.results {
+                let poster = item
+                    .poster_path
+                    .as_ref()
+                    .map(|p| tmdb_image(p, viewer.prefs.quality().poster_size()))
+                    .unwrap_or_else(|| "/static/placeholder.jpg".to_string());
+                let title = item.title.as_deref().unwrap_or("Unknown");
+                html.push_str(&format!(
+                    r#"<div class="content-card"><a href="/movie/{}"><img src="{}" alt="Movie" onerror="this.src='/static/placeholder.jpg'"><div class="card-info"><h3>{}</h3></div></a></div>"#,
+                    item.id, poster, title
+                ));
+            }
+            html.push_str("</div></section>");
+        }
+    }
+
     if let Some(ref similar) = movie.similar {
         html.push_str(
             r#"<section class="similar-section"><h2>Similar Movies</h2><div class="content-grid">"#,
@@ -283,9 +799,9 @@ pub fn render_movie_detail(username: Option<&str>, movie: &MovieDetail) -> Strin
             let poster = item
                 .poster_path
                 .as_ref()
-                .map(|p| format!("https://image.tmdb.org/t/p/w342{}", p))
+                .map(|p| tmdb_image(p, viewer.prefs.quality().poster_size()))
                 .unwrap_or_else(|| "/static/placeholder.jpg".to_string());
-            let title = item.title.as_ref().map(|s| s.as_str()).unwrap_or("Unknown");
+            let title = item.title.as_deref().unwrap_or("Unknown");
             html.push_str(&format!(
                 r#"<div class="content-card"><a href="/movie/{}"><img src="{}" alt="Movie" onerror="this.src='/static/placeholder.jpg'"><div class="card-info"><h3>{}</h3></div></a></div>"#,
                 item.id, poster, title
@@ -299,20 +815,101 @@ pub fn render_movie_detail(username: Option<&str>, movie: &MovieDetail) -> Strin
     html
 }
 
-pub fn render_tv_detail(username: Option<&str>, show: &TvShowDetail) -> String {
+/// A season's progress bar and, once started, its episodes with the
+/// watched ones checked off.
+fn render_season_progress(show_id: i64, season_number: i64, episode_count: i64, progress: &ShowProgress) -> String {
+    let watched = progress.watched_in(season_number);
+    let pct = if episode_count > 0 {
+        (watched as i64 * 100 / episode_count).min(100)
+    } else {
+        0
+    };
+    let mut html = format!(
+        r#"<div class="progress-bar"><div class="progress-bar-fill" style="width: {}%;"></div></div><span class="progress-time">{}/{} watched</span>"#,
+        pct, watched, episode_count
+    );
+
+    let Some(season) = progress.seasons.get(&season_number) else {
+        return html;
+    };
+    html.push_str(r#"<details class="season-episodes"><summary>Episodes</summary><ol>"#);
+    for episode in &season.episodes {
+        let (class, mark) = match progress.episode(season_number, episode.episode_number) {
+            Some(e) if e.completed => ("watched", "✓ "),
+            Some(e) if e.progress_seconds > 0 => ("in-progress", "◐ "),
+            _ => ("", ""),
+        };
+        html.push_str(&format!(
+            r#"<li class="{}"><a href="/player/tv/{}?season={}&episode={}">{}{}. {}</a></li>"#,
+            class,
+            show_id,
+            season_number,
+            episode.episode_number,
+            mark,
+            episode.episode_number,
+            escape_html(&episode.name)
+        ));
+    }
+    html.push_str("</ol></details>");
+    html
+}
+
+/// Adds the title to or removes it from the viewer's watchlist, and adds it
+/// to one of their playlists or a new one. Nothing without a session.
+fn list_buttons(media_type: &str, id: i64, title: &str, poster_path: Option<&str>, saved: Option<&SavedLists>) -> String {
+    let Some(saved) = saved else {
+        return String::new();
+    };
+    let (action, label) = if saved.on_watchlist {
+        ("remove", "✓ On Watchlist")
+    } else {
+        ("add", "+ Watchlist")
+    };
+    let title = escape_html(title);
+    let poster_path = escape_html(poster_path.unwrap_or(""));
+    let playlist_options: String = saved
+        .playlists
+        .iter()
+        .map(|p| format!(r#"<option value="{}">{}</option>"#, p.id, escape_html(&p.name)))
+        .collect();
+    format!(
+        r#"<form class="watchlist-form" action="/watchlist/{mt}/{id}" method="post"><input type="hidden" name="title" value="{title}"><input type="hidden" name="poster_path" value="{poster}"><button type="submit" name="action" value="{action}" class="secondary-button">{label}</button></form> <form class="watchlist-form" action="/playlists/add-title" method="post"><input type="hidden" name="tmdb_id" value="{id}"><input type="hidden" name="media_type" value="{mt}"><input type="hidden" name="title" value="{title}"><input type="hidden" name="poster_path" value="{poster}"><select name="playlist_id" aria-label="Playlist">{options}<option value="">New playlist:</option></select> <input type="text" name="new_playlist" placeholder="New playlist name" maxlength="{max}" aria-label="New playlist name"> <button type="submit" class="secondary-button">+ Playlist</button></form>"#,
+        mt = media_type,
+        id = id,
+        title = title,
+        poster = poster_path,
+        action = action,
+        label = label,
+        options = playlist_options,
+        max = MAX_NAME_LENGTH,
+    )
+}
+
+pub fn render_tv_detail(
+    viewer: &Viewer,
+    show: &TvShowDetail,
+    providers: Option<&WatchProviders>,
+    saved: Option<&SavedLists>,
+    progress: Option<&ShowProgress>,
+) -> String {
     let mut html = String::new();
 
-    html.push_str(&base_start(&show.name, username));
+    html.push_str(&base_start(
+        &titled_page(&show.name, show.first_air_date.as_deref()),
+        &format!("/tv/{}", show.id),
+        viewer,
+    ));
 
     let backdrop = show
         .backdrop_path
         .as_ref()
-        .map(|p| format!("https://image.tmdb.org/t/p/original{}", p))
+        .filter(|_| viewer.prefs.show_backdrops())
+        .map(|p| tmdb_image(p, viewer.prefs.quality().backdrop_size()))
         .unwrap_or_default();
     let poster = show
         .poster_path
         .as_ref()
-        .map(|p| format!("https://image.tmdb.org/t/p/w500{}", p))
+        .map(|p| tmdb_image(p, viewer.prefs.quality().detail_poster_size()))
         .unwrap_or_else(|| "/static/placeholder.jpg".to_string());
     let year = show
         .first_air_date
@@ -327,24 +924,40 @@ pub fn render_tv_detail(username: Option<&str>, show: &TvShowDetail) -> String {
     let genres_str = genres.join(", ");
     let overview = show
         .overview
-        .as_ref()
-        .map(|s| s.as_str())
+        .as_deref()
         .unwrap_or("No overview available.");
+    // Specials (season 0) count toward neither side.
+    let regular_seasons = show.seasons.iter().filter(|s| s.season_number > 0);
+    let watched_summary = match progress {
+        Some(progress) if !progress.episodes.is_empty() => {
+            let total: i64 = regular_seasons.clone().map(|s| s.episode_count).sum();
+            let watched: usize = regular_seasons.map(|s| progress.watched_in(s.season_number)).sum();
+            format!(r#"<span class="show-progress">{}/{} episodes watched</span>"#, watched, total)
+        }
+        _ => String::new(),
+    };
 
     html.push_str(&format!(
-        r#"<div class="detail-page"><div class="detail-hero" style="background-image: linear-gradient(rgba(0,0,0,0.7), rgba(0,0,0,0.9)), url({});"><div class="detail-content"><img class="detail-poster" src="{}" alt="{}" onerror="this.src='/static/placeholder.jpg'"><div class="detail-info"><h1>{}</h1><div class="meta"><span class="rating">⭐ {:.1} ({} votes)</span><span class="year">{}</span><span class="seasons">{}</span></div><p class="genres">{}</p><p class="overview">{}</p></div></div></div>"#,
-        backdrop, poster, show.name, show.name, show.vote_average, show.vote_count, year, seasons, genres_str, overview
+        r#"<div class="detail-page"><div class="detail-hero" style="background-image: linear-gradient(rgba(0,0,0,0.7), rgba(0,0,0,0.9)), url({});"><div class="detail-content"><img class="detail-poster" src="{}"{} alt="{}" onerror="this.src='/static/placeholder.jpg'"><div class="detail-info"><h1>{}</h1><div class="meta"><span class="rating">⭐ {:.1} ({} votes)</span>{}<span class="year">{}</span><span class="seasons">{}</span>{}</div><p class="genres">{}</p>{}{}<p class="overview">{}</p><div class="actions">{}</div></div></div></div>"#,
+        backdrop, poster, poster_srcset(viewer, show.poster_path.as_deref(), DETAIL_POSTER_WIDTH), show.name, show.name, show.vote_average, show.vote_count, certification_badge(show.certification.as_ref()), year, seasons, watched_summary, genres_str, render_studio_links(&show.networks, &show.production_companies, "tv"), render_external_links(show.external_ids.as_ref()), overview, list_buttons("tv", show.id, &show.name, show.poster_path.as_deref(), saved)
     ));
 
+    if let Some(providers) = providers {
+        html.push_str(&render_watch_providers(providers));
+    }
+
     if !show.seasons.is_empty() {
         html.push_str(
             r#"<section class="seasons-section"><h2>Seasons</h2><div class="season-list">"#,
         );
         for season in &show.seasons {
             if season.season_number > 0 {
+                let season_progress = progress
+                    .map(|p| render_season_progress(show.id, season.season_number, season.episode_count, p))
+                    .unwrap_or_default();
                 html.push_str(&format!(
-                    r#"<div class="season-item"><h3>{}</h3><p>{} episodes</p><a href="/player/tv/{}?season={}&episode=1" class="play-button-small">▶ Play</a></div>"#,
-                    season.name, season.episode_count, show.id, season.season_number
+                    r#"<div class="season-item"><h3>{}</h3><p>{} episodes</p>{}<a href="/player/tv/{}?season={}&episode=1" class="play-button-small">▶ Play</a></div>"#,
+                    season.name, season.episode_count, season_progress, show.id, season.season_number
                 ));
             }
         }
@@ -357,7 +970,7 @@ pub fn render_tv_detail(username: Option<&str>, show: &TvShowDetail) -> String {
             let profile = member
                 .profile_path
                 .as_ref()
-                .map(|p| format!("https://image.tmdb.org/t/p/w185{}", p))
+                .map(|p| tmdb_image(p, viewer.prefs.quality().profile_size()))
                 .unwrap_or_else(|| "/static/placeholder-avatar.jpg".to_string());
             html.push_str(&format!(
                 r#"<div class="cast-member"><img src="{}" alt="{}" onerror="this.src='/static/placeholder-avatar.jpg'"><h4>{}</h4><p>{}</p></div>"#,
@@ -367,6 +980,27 @@ pub fn render_tv_detail(username: Option<&str>, show: &TvShowDetail) -> String {
         html.push_str("</div></section>");
     }
 
+    if let Some(ref recommendations) = show.recommendations {
+        if !recommendations.results.is_empty() {
+            html.push_str(
+                r#"<section class="similar-section"><h2>Recommended</h2><div class="content-grid">"#,
+            );
+            for item in &recommendations.results {
+                let poster = item
+                    .poster_path
+                    .as_ref()
+                    .map(|p| tmdb_image(p, viewer.prefs.quality().poster_size()))
+                    .unwrap_or_else(|| "/static/placeholder.jpg".to_string());
+                let name = item.name.as_deref().unwrap_or("Unknown");
+                html.push_str(&format!(
+                    r#"<div class="content-card"><a href="/tv/{}"><img src="{}" alt="Show" onerror="this.src='/static/placeholder.jpg'"><div class="card-info"><h3>{}</h3></div></a></div>"#,
+                    item.id, poster, name
+                ));
+            }
+            html.push_str("</div></section>");
+        }
+    }
+
     if let Some(ref similar) = show.similar {
         html.push_str(
             r#"<section class="similar-section"><h2>Similar Shows</h2><div class="content-grid">"#,
@@ -375,9 +1009,9 @@ pub fn render_tv_detail(username: Option<&str>, show: &TvShowDetail) -> String {
             let poster = item
                 .poster_path
                 .as_ref()
-                .map(|p| format!("https://image.tmdb.org/t/p/w342{}", p))
+                .map(|p| tmdb_image(p, viewer.prefs.quality().poster_size()))
                 .unwrap_or_else(|| "/static/placeholder.jpg".to_string());
-            let name = item.name.as_ref().map(|s| s.as_str()).unwrap_or("Unknown");
+            let name = item.name.as_deref().unwrap_or("Unknown");
             html.push_str(&format!(
                 r#"<div class="content-card"><a href="/tv/{}"><img src="{}" alt="Show" onerror="this.src='/static/placeholder.jpg'"><div class="card-info"><h3>{}</h3></div></a></div>"#,
                 item.id, poster, name
@@ -391,18 +1025,92 @@ pub fn render_tv_detail(username: Option<&str>, show: &TvShowDetail) -> String {
     html
 }
 
-pub fn render_player(
-    username: Option<&str>,
-    title: &str,
-    media_type: &str,
-    id: i64,
-    poster_path: Option<&str>,
-    streams: &[StreamSource],
-    _is_admin: bool,
-) -> String {
+/// Everything the player page needs besides the viewer.
+pub struct PlayerView<'a> {
+    pub title: &'a str,
+    pub media_type: &'a str,
+    pub id: i64,
+    pub season: Option<i64>,
+    pub episode: Option<i64>,
+    pub poster_path: Option<&'a str>,
+    pub streams: &'a [StreamSource],
+    /// Why `streams` may be empty: the title is missing everywhere, or
+    /// providers failed and a retry may help.
+    pub stream_status: StreamStatus,
+    pub is_admin: bool,
+    /// Result of the server-side probe of the embed host.
+    pub embed_reachable: bool,
+    /// Legitimate streaming services, offered when the embed host is blocked.
+    pub providers: Option<&'a WatchProviders>,
+    /// A `<video>` element instead of an iframe: `?player=native`, or by
+    /// default when a local file is the first source.
+    pub native: bool,
+    /// Streams exist for the other player mode, so link to it.
+    pub other_mode_available: bool,
+    /// Where the native player starts, in seconds.
+    pub resume_at: Option<i64>,
+    pub autoplay: bool,
+    /// Effective quality cap, including the data saver default.
+    pub max_quality: Option<u32>,
+    /// Data saver's cap; higher choices are disabled in the picker.
+    pub quality_limit: Option<u32>,
+}
+
+/// Loaded only on native player pages.
+const HLS_JS_URL: &str = "https://cdn.jsdelivr.net/npm/hls.js@1/dist/hls.min.js";
+/// Swagger UI, pinned to an exact release so the CDN can't change what runs.
+const SWAGGER_UI_VERSION: &str = "5.17.14";
+/// A copy of `swagger-ui-dist`'s `swagger-ui.css` and `swagger-ui-bundle.js`
+/// here is served instead of the CDN's.
+const SWAGGER_UI_VENDORED_DIR: &str = "app/static/swagger-ui";
+
+/// Where the API docs load Swagger UI from: the vendored copy when present,
+/// otherwise the pinned release on jsDelivr.
+fn swagger_ui_url() -> String {
+    if std::path::Path::new(SWAGGER_UI_VENDORED_DIR).join("swagger-ui-bundle.js").is_file() {
+        "/static/swagger-ui".to_string()
+    } else {
+        format!("https://cdn.jsdelivr.net/npm/swagger-ui-dist@{}", SWAGGER_UI_VERSION)
+    }
+}
+
+/// A standalone Swagger UI page for the API description at `spec_url`.
+pub fn render_api_docs(spec_url: &str) -> String {
+    format!(
+        r##"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta charset="UTF-8">
+    <meta name="viewport" content="width=device-width, initial-scale=1.0">
+    <title>API - RustStream</title>
+    <link rel="stylesheet" href="{ui}/swagger-ui.css" crossorigin="anonymous" referrerpolicy="no-referrer">
+</head>
+<body>
+    <div id="swagger-ui"></div>
+    <script src="{ui}/swagger-ui-bundle.js" crossorigin="anonymous" referrerpolicy="no-referrer"></script>
+    <script>
+    SwaggerUIBundle({{ url: "{spec}", dom_id: "#swagger-ui", withCredentials: true }});
+    </script>
+</body>
+</html>"##,
+        ui = swagger_ui_url(),
+        spec = escape_html(spec_url)
+    )
+}
+
+pub fn render_player(viewer: &Viewer, player: &PlayerView) -> String {
     let mut html = String::new();
+    let title = player.title;
+    let media_type = player.media_type;
+    let id = player.id;
+    let streams = player.streams;
+    let _is_admin = player.is_admin;
 
-    html.push_str(&base_start(&format!("{} - RustStream", title), username));
+    html.push_str(&base_start(
+        &format!("Watch {} - RustStream", title),
+        &format!("/player/{}/{}", media_type, id),
+        viewer,
+    ));
 
     let back_link = if media_type == "movie" {
         format!("/movie/{}", id)
@@ -410,17 +1118,38 @@ pub fn render_player(
         format!("/tv/{}", id)
     };
 
-    let poster_url = poster_path.map(|p| format!("https://image.tmdb.org/t/p/w500{}", p));
-
+    let mode_link = if player.other_mode_available {
+        let mut url = format!("/player/{}/{}", media_type, id);
+        let mut query = vec![];
+        if let (Some(season), Some(episode)) = (player.season, player.episode) {
+            query.push(format!("season={}&episode={}", season, episode));
+        }
+        query.push(format!("player={}", if player.native { "embed" } else { "native" }));
+        url.push('?');
+        url.push_str(&query.join("&"));
+        format!(
+            r#"<a href="{}" class="player-mode-link">{}</a>"#,
+            escape_html(&url),
+            if player.native { "Embedded player" } else { "Native player" }
+        )
+    } else {
+        String::new()
+    };
+
     html.push_str(&format!(
-        r#"<div class="player-page" data-media-id="{}" data-media-type="{}"><div class="player-header"><a href="{}" class="back-button">← Back</a><h1>{}</h1></div><div class="player-container">"#,
-        id, media_type, back_link, title
+        r#"<div class="player-page" data-media-id="{}" data-media-type="{}"><div class="player-header"><a href="{}" class="back-button">← Back</a><h1>{}</h1>{}{}</div><div class="player-container">"#,
+        id, media_type, back_link, title, mode_link, render_quality_picker(player)
     ));
 
     if streams.is_empty() {
-        html.push_str(
-            r#"<div class="no-streams"><p>No streams available for this title.</p></div>"#,
-        );
+        html.push_str(&render_no_streams(player));
+    } else if !player.embed_reachable {
+        html.push_str(&render_player_fallback(player, true));
+    } else if player.native {
+        html.push_str(&render_native_player(player));
+        html.push_str(&render_player_fallback(player, false));
+        html.push_str(&render_heartbeat_script(player, &streams[0].name));
+        html.push_str(&render_report_button(player));
     } else {
         // Use iframe for vidking embed
         // Admin users get ad-blocking features
@@ -431,33 +1160,78 @@ pub fn render_player(
         };
 
         html.push_str(&format!(
-            r#"<iframe id="videoPlayer" class="video-player" src="{}" frameborder="0" allowfullscreen scrolling="no" allow="autoplay; fullscreen" {}></iframe>"#,
-            streams[0].id, sandbox_attr
+            r#"<iframe id="videoPlayer" class="video-player" src="{}" data-provider="{}" data-server="{}" frameborder="0" allowfullscreen scrolling="no" allow="autoplay; fullscreen" {}></iframe>"#,
+            escape_html(&streams[0].id), escape_html(&streams[0].name), escape_html(&streams[0].server), sandbox_attr
         ));
 
         if streams.len() > 1 {
             html.push_str(r#"<div class="stream-selector"><h3>Select Source:</h3>"#);
-            for stream in streams {
+            for (i, stream) in streams.iter().enumerate() {
                 let quality = stream
                     .quality
-                    .as_ref()
-                    .map(|s| s.as_str())
+                    .as_deref()
                     .unwrap_or("Unknown");
                 html.push_str(&format!(
-                    r#"<button>{} - {}</button>"#,
-                    stream.name, quality
+                    r#"<button type="button" data-src="{}" data-provider="{}" data-server="{}"{}>{} - {}</button>"#,
+                    escape_html(&stream.id),
+                    escape_html(&stream.name),
+                    escape_html(&stream.server),
+                    if i == 0 { r#" class="active""# } else { "" },
+                    escape_html(&stream.name),
+                    escape_html(quality)
                 ));
             }
             html.push_str("</div>");
+            html.push_str(SOURCE_STATUS_SCRIPT);
+            html.push_str(
+                r#"<script>
+    (function() {
+        var frame = document.getElementById("videoPlayer");
+        var buttons = document.querySelectorAll(".stream-selector button");
+        buttons.forEach(function(button) {
+            button.addEventListener("click", function() {
+                if (button.classList.contains("active")) return;
+                buttons.forEach(function(b) { b.classList.remove("active"); });
+                button.classList.add("active");
+                frame.src = button.dataset.src;
+                frame.dataset.provider = button.dataset.provider;
+                frame.dataset.server = button.dataset.server;
+                frame.style.display = "";
+                document.getElementById("playerFallback").style.display = "none";
+            });
+        });
+    })();
+    </script>"#,
+            );
         }
+
+        // A blocked host usually leaves the iframe hanging rather than erroring,
+        // so treat a load that never completes as unreachable.
+        html.push_str(&render_player_fallback(player, false));
+        html.push_str(
+            r#"<script>
+    (function() {
+        var frame = document.getElementById("videoPlayer");
+        var loaded = false;
+        frame.addEventListener("load", function() { loaded = true; });
+        setTimeout(function() {
+            if (!loaded) {
+                frame.style.display = "none";
+                document.getElementById("playerFallback").style.display = "block";
+            }
+        }, 20000);
+    })();
+    </script>"#,
+        );
+
+        html.push_str(&render_heartbeat_script(player, &streams[0].name));
+        html.push_str(&render_report_button(player));
     }
 
     html.push_str("</div></div>");
 
     // Admin ad-blocking was removed along with login/accounts.
 
-    let tmdb_id = id;
-    let media_type = media_type;
     let progress_tracking_script = format!(
         r#"
     <script>
@@ -507,10 +1281,7 @@ pub fn render_player(
         id,
         media_type,
         title,
-        poster_url
-            .as_ref()
-            .map(|p| p.replace("https://image.tmdb.org/t/p/w500", ""))
-            .unwrap_or_default()
+        player.poster_path.unwrap_or_default()
     );
 
     html.push_str(&progress_tracking_script);
@@ -564,21 +1335,468 @@ pub fn render_player(
     html
 }
 
+/// Reports playback state every few seconds so the server can spot stalls and
+/// errors, and shows a "try another source" prompt when it suggests one.
+fn render_heartbeat_script(player: &PlayerView, provider: &str) -> String {
+    format!(
+        r#"<div id="sourcePrompt" class="source-prompt" style="display: none;">Having trouble? <button type="button" id="sourcePromptButton">Try another source</button></div>
+    <script>
+    (function() {{
+        var playbackId = Math.random().toString(36).slice(2) + Date.now().toString(36);
+        var state = "paused";
+        var position = 0;
+        var lastBeatPosition = -1;
+        var detail = null;
+
+        function onPlayerEvent(event) {{
+            position = event.currentTime || position;
+            switch (event.event) {{
+                case "play":
+                case "timeupdate":
+                case "seeked":
+                    state = "playing";
+                    break;
+                case "pause":
+                    state = "paused";
+                    break;
+                case "ended":
+                    state = "ended";
+                    break;
+                case "error":
+                    state = "error";
+                    detail = event.message || null;
+                    break;
+            }}
+        }}
+
+        // Embeds report over postMessage; the native player dispatches its own events.
+        window.addEventListener("message", function(event) {{
+            try {{
+                var data = JSON.parse(event.data);
+                if (data.type === "PLAYER_EVENT") onPlayerEvent(data.data);
+            }} catch (e) {{}}
+        }});
+        document.addEventListener("playerevent", function(event) {{
+            onPlayerEvent(event.detail);
+        }});
+
+        // Move on to the next source when there is one, otherwise show the fallback.
+        document.getElementById("sourcePromptButton").addEventListener("click", function() {{
+            document.getElementById("sourcePrompt").style.display = "none";
+            var next = document.querySelector(".stream-selector button.active + button");
+            if (next) {{
+                next.click();
+                return;
+            }}
+            document.getElementById("videoPlayer").style.display = "none";
+            document.getElementById("playerFallback").style.display = "block";
+        }});
+
+        setInterval(function() {{
+            var reported = state;
+            if (state === "playing" && position === lastBeatPosition) {{
+                reported = "buffering";
+            }}
+            lastBeatPosition = position;
+
+            fetch("/api/playback/heartbeat", {{
+                method: "POST",
+                headers: {{ "Content-Type": "application/json" }},
+                body: JSON.stringify({{
+                    playback_id: playbackId,
+                    tmdb_id: {},
+                    media_type: "{}",
+                    season: {},
+                    episode: {},
+                    provider: document.getElementById("videoPlayer").dataset.provider || "{}",
+                    state: reported,
+                    position: position,
+                    detail: detail
+                }})
+            }})
+                .then(function(r) {{ return r.json(); }})
+                .then(function(outcome) {{
+                    if (outcome.suggest_switch) {{
+                        document.getElementById("sourcePrompt").style.display = "block";
+                    }}
+                }})
+                .catch(function() {{}});
+            detail = null;
+        }}, {});
+    }})();
+    </script>"#,
+        player.id,
+        player.media_type,
+        player.season.map(|s| s.to_string()).unwrap_or_else(|| "null".to_string()),
+        player.episode.map(|e| e.to_string()).unwrap_or_else(|| "null".to_string()),
+        escape_html(provider),
+        crate::playback::HEARTBEAT_INTERVAL_SECS * 1000
+    )
+}
+
+/// A self-hosted `<video>` element fed by hls.js (or the browser's own HLS
+/// support) or a local file, with keyboard shortcuts, subtitle selection, and
+/// progress saved straight to `/api/progress`.
+fn render_native_player(player: &PlayerView) -> String {
+    let streams = player.streams;
+    let mut html = format!(
+        r#"<video id="videoPlayer" class="video-player" data-src="{}" data-kind="{}" data-provider="{}" data-server="{}" data-title="{}" data-poster="{}" data-season="{}" data-episode="{}" data-resume="{}" data-max-quality="{}" controls playsinline{}></video><div class="native-controls"><label class="subtitle-picker" style="display: none;">Subtitles <select id="subtitleSelect"></select></label><span class="shortcut-hint">Space play/pause · ←/→ seek 10s · ↑/↓ volume · M mute · F fullscreen</span></div>"#,
+        escape_html(&streams[0].id),
+        streams[0].kind.as_str(),
+        escape_html(&streams[0].name),
+        escape_html(&streams[0].server),
+        escape_html(player.title),
+        escape_html(player.poster_path.unwrap_or_default()),
+        player.season.map(|s| s.to_string()).unwrap_or_default(),
+        player.episode.map(|e| e.to_string()).unwrap_or_default(),
+        player.resume_at.unwrap_or_default(),
+        player.max_quality.unwrap_or_default(),
+        if player.autoplay { " autoplay" } else { "" }
+    );
+
+    if streams.len() > 1 {
+        html.push_str(r#"<div class="stream-selector"><h3>Select Source:</h3>"#);
+        for (i, stream) in streams.iter().enumerate() {
+            html.push_str(&format!(
+                r#"<button type="button" data-src="{}" data-kind="{}" data-provider="{}" data-server="{}"{}>{} - {}</button>"#,
+                escape_html(&stream.id),
+                stream.kind.as_str(),
+                escape_html(&stream.name),
+                escape_html(&stream.server),
+                if i == 0 { r#" class="active""# } else { "" },
+                escape_html(&stream.name),
+                escape_html(stream.quality.as_deref().unwrap_or("Unknown"))
+            ));
+        }
+        html.push_str("</div>");
+        html.push_str(SOURCE_STATUS_SCRIPT);
+    }
+
+    html.push_str(&format!(r#"<script src="{}"></script>"#, HLS_JS_URL));
+    html.push_str(
+        r#"<script>
+    (function() {
+        var page = document.querySelector(".player-page");
+        var video = document.getElementById("videoPlayer");
+        var subtitles = document.getElementById("subtitleSelect");
+        var resumeAt = parseInt(video.dataset.resume, 10) || 0;
+        var maxQuality = parseInt(video.dataset.maxQuality, 10) || 0;
+        var hls = null;
+        var lastSaved = 0;
+
+        function emit(name, message) {
+            document.dispatchEvent(new CustomEvent("playerevent", {
+                detail: { event: name, currentTime: video.currentTime, message: message || null }
+            }));
+        }
+
+        function listSubtitles(names) {
+            subtitles.innerHTML = '<option value="-1">Off</option>';
+            names.forEach(function(name, i) {
+                var option = document.createElement("option");
+                option.value = i;
+                option.textContent = name || "Track " + (i + 1);
+                subtitles.appendChild(option);
+            });
+            subtitles.parentNode.style.display = names.length ? "" : "none";
+        }
+
+        function load(src, kind) {
+            if (hls) {
+                hls.destroy();
+                hls = null;
+            }
+            if (kind === "file") {
+                video.src = src;
+            } else if (window.Hls && Hls.isSupported()) {
+                hls = new Hls();
+                hls.on(Hls.Events.MANIFEST_PARSED, function() {
+                    if (!maxQuality) return;
+                    var cap = -1;
+                    hls.levels.forEach(function(level, i) {
+                        if (level.height && level.height <= maxQuality) cap = i;
+                    });
+                    // Levels are sorted by bitrate; keep the lowest when none fit.
+                    hls.autoLevelCapping = Math.max(cap, 0);
+                });
+                hls.on(Hls.Events.SUBTITLE_TRACKS_UPDATED, function() {
+                    listSubtitles(hls.subtitleTracks.map(function(t) { return t.name || t.lang; }));
+                });
+                hls.on(Hls.Events.ERROR, function(_, data) {
+                    if (data.fatal) emit("error", data.details);
+                });
+                hls.loadSource(src);
+                hls.attachMedia(video);
+            } else {
+                // Safari plays HLS without hls.js.
+                video.src = src;
+                video.addEventListener("loadedmetadata", function() {
+                    listSubtitles(Array.prototype.map.call(video.textTracks, function(t) { return t.label || t.language; }));
+                }, { once: true });
+            }
+        }
+
+        function saveProgress(completed) {
+            if (!video.duration) return;
+            lastSaved = Date.now();
+            fetch("/api/progress", {
+                method: "POST",
+                headers: { "Content-Type": "application/json" },
+                body: JSON.stringify({
+                    tmdb_id: parseInt(page.dataset.mediaId, 10),
+                    media_type: page.dataset.mediaType,
+                    progress: video.currentTime / video.duration * 100,
+                    current_time: video.currentTime,
+                    duration: video.duration,
+                    season: parseInt(video.dataset.season, 10) || null,
+                    episode: parseInt(video.dataset.episode, 10) || null,
+                    title: video.dataset.title,
+                    poster_path: video.dataset.poster || null,
+                    episode_title: null,
+                    completed: completed
+                })
+            }).catch(function() {});
+        }
+
+        video.addEventListener("loadedmetadata", function() {
+            if (resumeAt > 0 && resumeAt < video.duration) video.currentTime = resumeAt;
+            resumeAt = 0;
+        });
+        ["play", "pause", "seeked", "ended"].forEach(function(name) {
+            video.addEventListener(name, function() { emit(name); });
+        });
+        video.addEventListener("timeupdate", function() {
+            emit("timeupdate");
+            if (Date.now() - lastSaved > 15000) saveProgress(false);
+        });
+        video.addEventListener("pause", function() { saveProgress(false); });
+        video.addEventListener("ended", function() { saveProgress(true); });
+        video.addEventListener("error", function() {
+            emit("error", video.error && video.error.message);
+        });
+
+        subtitles.addEventListener("change", function() {
+            var index = parseInt(subtitles.value, 10);
+            if (hls) {
+                hls.subtitleTrack = index;
+                return;
+            }
+            Array.prototype.forEach.call(video.textTracks, function(track, i) {
+                track.mode = i === index ? "showing" : "disabled";
+            });
+        });
+
+        document.addEventListener("keydown", function(e) {
+            if (e.ctrlKey || e.metaKey || e.altKey || e.target.closest("input, select, textarea")) return;
+            switch (e.key) {
+                case " ":
+                case "k":
+                    if (video.paused) video.play(); else video.pause();
+                    break;
+                case "ArrowLeft":
+                case "j":
+                    video.currentTime = Math.max(0, video.currentTime - 10);
+                    break;
+                case "ArrowRight":
+                case "l":
+                    video.currentTime = video.currentTime + 10;
+                    break;
+                case "ArrowUp":
+                    video.volume = Math.min(1, video.volume + 0.1);
+                    break;
+                case "ArrowDown":
+                    video.volume = Math.max(0, video.volume - 0.1);
+                    break;
+                case "m":
+                    video.muted = !video.muted;
+                    break;
+                case "f":
+                    if (document.fullscreenElement) document.exitFullscreen(); else video.requestFullscreen();
+                    break;
+                default:
+                    return;
+            }
+            e.preventDefault();
+        });
+
+        var buttons = document.querySelectorAll(".stream-selector button");
+        buttons.forEach(function(button) {
+            button.addEventListener("click", function() {
+                if (button.classList.contains("active")) return;
+                buttons.forEach(function(b) { b.classList.remove("active"); });
+                button.classList.add("active");
+                resumeAt = Math.floor(video.currentTime);
+                video.dataset.provider = button.dataset.provider;
+                video.dataset.server = button.dataset.server;
+                video.style.display = "";
+                document.getElementById("playerFallback").style.display = "none";
+                load(button.dataset.src, button.dataset.kind);
+            });
+        });
+
+        load(video.dataset.src, video.dataset.kind);
+    })();
+    </script>"#,
+    );
+    html
+}
+
+/// Max-quality dropdown. Embed URLs carry the cap, so a change reloads the page.
+fn render_quality_picker(player: &PlayerView) -> String {
+    format!(
+        r#"<label class="quality-picker">Max quality <select id="qualitySelect">{}</select></label>
+    <script>
+    document.getElementById("qualitySelect").addEventListener("change", function() {{
+        fetch("/api/player/quality", {{
+            method: "POST",
+            headers: {{ "Content-Type": "application/json" }},
+            body: JSON.stringify({{ max_quality: this.value ? parseInt(this.value, 10) : null }})
+        }}).then(function() {{ location.reload(); }});
+    }});
+    </script>"#,
+        max_quality_options(player.max_quality, player.quality_limit)
+    )
+}
+
+/// Marks each source in the picker with its provider's health and speed from
+/// `/api/providers`: providers failing their health check are dimmed, and
+/// each button's tooltip gives the average resolution time.
+const SOURCE_STATUS_SCRIPT: &str = r#"<script>
+    (function() {
+        fetch("/api/providers", { credentials: "same-origin" })
+            .then(function(response) { return response.ok ? response.json() : []; })
+            .then(function(providers) {
+                var byName = {};
+                providers.forEach(function(provider) { byName[provider.name] = provider; });
+                document.querySelectorAll(".stream-selector button").forEach(function(button) {
+                    var provider = byName[button.dataset.server];
+                    if (!provider) return;
+                    var notes = [];
+                    if (!provider.healthy) {
+                        button.classList.add("unhealthy");
+                        notes.push("Not answering at the last check");
+                    }
+                    if (provider.average_latency_ms !== null) {
+                        notes.push("Usually ready in " + (provider.average_latency_ms / 1000).toFixed(1) + "s");
+                    }
+                    button.title = notes.join(" · ");
+                });
+            })
+            .catch(function() {});
+    })();
+    </script>"#;
+
+/// Lets the viewer flag the current source as broken, then moves on to the
+/// next one. Enough reports push a provider behind the others.
+fn render_report_button(player: &PlayerView) -> String {
+    format!(
+        r#"<div class="stream-report"><button type="button" id="reportStreamButton">Report broken stream</button> <span id="reportStreamStatus"></span></div>
+    <script>
+    (function() {{
+        var button = document.getElementById("reportStreamButton");
+        var status = document.getElementById("reportStreamStatus");
+        button.addEventListener("click", function() {{
+            button.disabled = true;
+            fetch("/api/streams/report", {{
+                method: "POST",
+                headers: {{ "Content-Type": "application/json" }},
+                body: JSON.stringify({{
+                    tmdb_id: {},
+                    media_type: "{}",
+                    provider: document.getElementById("videoPlayer").dataset.server,
+                    season: {},
+                    episode: {}
+                }})
+            }})
+                .then(function(r) {{
+                    if (!r.ok) throw new Error(r.status);
+                    status.textContent = "Thanks, reported.";
+                    var next = document.querySelector(".stream-selector button.active + button");
+                    if (next) {{
+                        next.click();
+                        button.disabled = false;
+                    }}
+                }})
+                .catch(function() {{
+                    status.textContent = "Could not send the report.";
+                    button.disabled = false;
+                }});
+        }});
+    }})();
+    </script>"#,
+        player.id,
+        player.media_type,
+        player.season.map(|s| s.to_string()).unwrap_or_else(|| "null".to_string()),
+        player.episode.map(|e| e.to_string()).unwrap_or_else(|| "null".to_string())
+    )
+}
+
+/// Reloads the player, forcing fresh host probes.
+fn player_retry_url(player: &PlayerView) -> String {
+    let mut retry_url = format!("/player/{}/{}?retry=1", player.media_type, player.id);
+    if let (Some(season), Some(episode)) = (player.season, player.episode) {
+        retry_url.push_str(&format!("&season={}&episode={}", season, episode));
+    }
+    if player.native {
+        retry_url.push_str("&player=native");
+    }
+    retry_url
+}
+
+fn render_no_streams(player: &PlayerView) -> String {
+    match player.stream_status {
+        StreamStatus::ProviderDown => format!(
+            r#"<div class="no-streams"><p>Stream providers aren't responding right now.</p><a href="{}" class="play-button">↻ Retry</a></div>"#,
+            player_retry_url(player)
+        ),
+        StreamStatus::NotFound => {
+            r#"<div class="no-streams"><p>None of the stream providers have this title.</p></div>"#.to_string()
+        }
+        StreamStatus::Available => {
+            r#"<div class="no-streams"><p>No streams available for this title.</p></div>"#.to_string()
+        }
+    }
+}
+
+fn render_player_fallback(player: &PlayerView, visible: bool) -> String {
+    let retry_url = player_retry_url(player);
+
+    let mut html = format!(
+        r#"<div id="playerFallback" class="player-fallback"{}><h2>Can't reach the video host</h2><p>The embed provider looks blocked or unreachable from this network. This often happens on filtered school, work, or ISP connections.</p><a href="{}" class="play-button">↻ Retry</a>"#,
+        if visible { "" } else { r#" style="display: none;""# },
+        retry_url
+    );
+
+    if let Some(providers) = player.providers {
+        html.push_str(&render_watch_providers(providers));
+    }
+
+    html.push_str("</div>");
+    html
+}
+
 pub fn render_watch_history(
-    username: Option<&str>,
+    viewer: &Viewer,
     history: &[crate::auth::WatchHistoryItem],
 ) -> String {
     let mut html = String::new();
 
-    html.push_str(&base_start("Watch History - RustStream", username));
+    html.push_str(&base_start("Watch History - RustStream", "/history", viewer));
 
     html.push_str(
         r#"
     <div class="history-page">
         <h1>Your Watch History</h1>
+        <p class="settings-hint"><a href="/stats">See your watch-time stats</a></p>
 "#,
     );
 
+    if viewer.prefs.pause_history {
+        html.push_str(
+            r#"<p class="settings-hint">History is paused. <a href="/settings">Resume it in Settings</a>.</p>"#,
+        );
+    }
+
     if history.is_empty() {
         html.push_str(
             r#"<div class="no-results">
@@ -593,7 +1811,7 @@ pub fn render_watch_history(
             let poster = item
                 .poster_path
                 .as_ref()
-                .map(|p| format!("https://image.tmdb.org/t/p/w342{}", p))
+                .map(|p| tmdb_image(p, viewer.prefs.quality().poster_size()))
                 .unwrap_or_else(|| "/static/placeholder.jpg".to_string());
 
             let link = if item.media_type == "movie" {
@@ -610,12 +1828,15 @@ pub fn render_watch_history(
             };
 
             let label = if item.media_type == "movie" {
-                "Movie"
+                "Movie".to_string()
             } else if let (Some(season), Some(episode)) = (item.season_number, item.episode_number)
             {
-                &format!("S{}E{}", season, episode)
+                match item.episode_title.as_deref() {
+                    Some(name) => format!("S{:02}E{:02} — {}", season, episode, escape_html(name)),
+                    None => format!("S{:02}E{:02}", season, episode),
+                }
             } else {
-                "TV Show"
+                "TV Show".to_string()
             };
 
             let progress_bar = if item.completed {
@@ -646,22 +1867,888 @@ pub fn render_watch_history(
     html
 }
 
-fn base_start(title: &str, username: Option<&str>) -> String {
+pub fn render_watchlist(viewer: &Viewer, items: &[crate::auth::WatchlistItem]) -> String {
+    let mut html = String::new();
+
+    html.push_str(&base_start("Watchlist - RustStream", "/watchlist", viewer));
+
+    html.push_str(
+        r#"
+    <div class="history-page">
+        <h1>Your Watchlist</h1>
+"#,
+    );
+
+    if items.is_empty() {
+        html.push_str(
+            r#"<div class="no-results">
+            <p>Your watchlist is empty. Add titles from their pages to watch them later.</p>
+            <a href="/search" class="play-button">Browse Movies & TV Shows</a>
+        </div>"#,
+        );
+    } else {
+        html.push_str(r#"<div class="content-grid">"#);
+
+        for item in items {
+            let poster = item
+                .poster_path
+                .as_ref()
+                .map(|p| tmdb_image(p, viewer.prefs.quality().poster_size()))
+                .unwrap_or_else(|| "/static/placeholder.jpg".to_string());
+            let label = if item.media_type == "movie" { "Movie" } else { "TV Show" };
+            let title = escape_html(&item.title);
+
+            html.push_str(&format!(
+                r#"<div class="content-card"><a href="/{}/{}"><img src="{}" alt="{}" onerror="this.src='/static/placeholder.jpg'"><div class="card-info"><h3>{}</h3><p class="rating">{}</p></div></a><form class="watchlist-form" action="/watchlist/{}/{}" method="post"><input type="hidden" name="from_watchlist" value="1"><button type="submit" name="action" value="remove" class="secondary-button">Remove</button></form></div>"#,
+                item.media_type, item.tmdb_id, poster, title, title, label, item.media_type, item.tmdb_id
+            ));
+        }
+        html.push_str("</div>");
+    }
+
+    html.push_str("</div>");
+    html.push_str(&base_end());
+    html
+}
+
+pub fn render_playlists(viewer: &Viewer, playlists: &[Playlist], error: Option<&str>) -> String {
+    let mut html = String::new();
+
+    html.push_str(&base_start("Playlists - RustStream", "/playlists", viewer));
+    html.push_str(r#"<div class="settings-page"><h1>Playlists</h1>"#);
+
+    let error = error
+        .map(|e| format!(r#"<p class="form-error">{}</p>"#, escape_html(e)))
+        .unwrap_or_default();
+    html.push_str(&format!(
+        r#"<form class="settings-form" action="/playlists" method="post">{}<div class="filter-group"><label for="name">New playlist</label><input type="text" id="name" name="name" placeholder="Halloween movies" maxlength="{}" required></div><button type="submit" class="play-button">Create playlist</button></form>"#,
+        error, MAX_NAME_LENGTH
+    ));
+
+    if playlists.is_empty() {
+        html.push_str(r#"<p class="settings-hint">No playlists yet. Create one here, or add titles to a new one from their pages.</p>"#);
+    } else {
+        html.push_str(r#"<table class="invite-table"><thead><tr><th>Name</th><th>Titles</th><th>Updated</th></tr></thead><tbody>"#);
+        for playlist in playlists {
+            let shared = if playlist.share_token.is_some() {
+                r#" <span class="role-badge">shared</span>"#
+            } else {
+                ""
+            };
+            html.push_str(&format!(
+                r#"<tr><td><a href="/playlists/{}">{}</a>{}</td><td>{}</td><td>{}</td></tr>"#,
+                playlist.id,
+                escape_html(&playlist.name),
+                shared,
+                playlist.item_count,
+                escape_html(&playlist.updated_at)
+            ));
+        }
+        html.push_str("</tbody></table>");
+    }
+
+    html.push_str("</div>");
+    html.push_str(&base_end());
+    html
+}
+
+/// A playlist's titles. The owner also gets the controls for ordering,
+/// renaming, sharing, and deleting it; the shared view is read-only.
+pub fn render_playlist(viewer: &Viewer, playlist: &PlaylistWithItems, owner: bool) -> String {
+    let mut html = String::new();
+    let name = escape_html(&playlist.playlist.name);
+    let id = playlist.playlist.id;
+
+    let canonical = match (&playlist.playlist.share_token, owner) {
+        (Some(token), false) => format!("/shared/playlists/{}", token),
+        _ => format!("/playlists/{}", id),
+    };
+    // base_start escapes the title itself.
+    html.push_str(&base_start(&format!("{} - RustStream", playlist.playlist.name), &canonical, viewer));
+    html.push_str(&format!(r#"<div class="history-page"><h1>{}</h1>"#, name));
+
+    if owner {
+        let share = match &playlist.playlist.share_token {
+            Some(token) => format!(
+                r#"<p class="settings-hint">Anyone with this link can see the playlist: <a href="/shared/playlists/{token}">/shared/playlists/{token}</a></p><button type="submit" name="action" value="unshare" class="secondary-button">Stop sharing</button>"#,
+                token = escape_html(token)
+            ),
+            None => r#"<button type="submit" name="action" value="share" class="secondary-button">Share with a link</button>"#.to_string(),
+        };
+        html.push_str(&format!(
+            r#"<form class="settings-form" action="/playlists/{id}" method="post"><div class="filter-group"><label for="name">Name</label><input type="text" id="name" name="name" value="{name}" maxlength="{max}" required></div><div class="actions"><button type="submit" name="action" value="rename" class="play-button-small">Rename</button></div></form><form class="settings-form" action="/playlists/{id}" method="post">{share} <button type="submit" name="action" value="delete" class="secondary-button">Delete playlist</button></form>"#,
+            id = id,
+            name = name,
+            max = MAX_NAME_LENGTH,
+            share = share
+        ));
+    }
+
+    if playlist.items.is_empty() {
+        html.push_str(
+            r#"<div class="no-results">
+            <p>This playlist is empty.</p>
+            <a href="/search" class="play-button">Browse Movies & TV Shows</a>
+        </div>"#,
+        );
+    } else {
+        html.push_str(r#"<div class="content-grid">"#);
+        let last = playlist.items.len() - 1;
+        for (index, item) in playlist.items.iter().enumerate() {
+            let poster = item
+                .poster_path
+                .as_ref()
+                .map(|p| tmdb_image(p, viewer.prefs.quality().poster_size()))
+                .unwrap_or_else(|| "/static/placeholder.jpg".to_string());
+            let label = if item.media_type == "movie" { "Movie" } else { "TV Show" };
+            let title = escape_html(&item.title);
+
+            let controls = if owner {
+                let button = |action: &str, text: &str, shown: bool| {
+                    if shown {
+                        format!(r#"<button type="submit" name="action" value="{}" class="secondary-button">{}</button>"#, action, text)
+                    } else {
+                        String::new()
+                    }
+                };
+                format!(
+                    r#"<form class="watchlist-form" action="/playlists/{}/items" method="post"><input type="hidden" name="tmdb_id" value="{}"><input type="hidden" name="media_type" value="{}">{}{}{}</form>"#,
+                    id,
+                    item.tmdb_id,
+                    item.media_type,
+                    button("up", "↑", index > 0),
+                    button("down", "↓", index < last),
+                    button("remove", "Remove", true)
+                )
+            } else {
+                String::new()
+            };
+
+            html.push_str(&format!(
+                r#"<div class="content-card"><a href="/{}/{}"><img src="{}" alt="{}" onerror="this.src='/static/placeholder.jpg'"><div class="card-info"><h3>{}</h3><p class="rating">{}</p></div></a>{}</div>"#,
+                item.media_type, item.tmdb_id, poster, title, title, label, controls
+            ));
+        }
+        html.push_str("</div>");
+    }
+
+    html.push_str("</div>");
+    html.push_str(&base_end());
+    html
+}
+
+/// Hours and minutes, e.g. `3h 05m`.
+fn format_watch_time(seconds: i64) -> String {
+    let minutes = seconds / 60;
+    format!("{}h {:02}m", minutes / 60, minutes % 60)
+}
+
+pub fn render_stats(viewer: &Viewer, summary: &StatsSummary) -> String {
+    let mut html = String::new();
+
+    html.push_str(&base_start("Stats - RustStream", "/stats", viewer));
+    html.push_str(r#"<div class="settings-page stats-page"><h1>Your Stats</h1>"#);
+
+    let periods = [(None, "All time"), (Some(365), "Past year"), (Some(30), "Past 30 days"), (Some(7), "Past week")];
+    let links: Vec<String> = periods
+        .iter()
+        .map(|(days, label)| {
+            let href = days.map_or("/stats".to_string(), |d| format!("/stats?days={}", d));
+            if *days == summary.period_days {
+                format!("<strong>{}</strong>", label)
+            } else {
+                format!(r#"<a href="{}">{}</a>"#, href, label)
+            }
+        })
+        .collect();
+    html.push_str(&format!(r#"<p class="stats-periods">{}</p>"#, links.join(" · ")));
+
+    if summary.total_seconds == 0 {
+        html.push_str(r#"<p class="settings-hint">Nothing watched in this period yet. Stats are updated hourly.</p></div>"#);
+        html.push_str(&base_end());
+        return html;
+    }
+
+    html.push_str(&format!(
+        r#"<div class="stats-totals"><div><span class="stats-number">{:.1}</span> hours watched</div><div><span class="stats-number">{}</span> days with something watched</div></div>"#,
+        summary.total_hours, summary.days_watched
+    ));
+
+    // Bars are scaled to the largest value in each list.
+    let bar = |label: &str, seconds: i64, max: i64| {
+        let pct = if max > 0 { seconds * 100 / max } else { 0 };
+        format!(
+            r#"<tr><td>{}</td><td class="stats-bar"><div class="progress-bar"><div class="progress-bar-fill" style="width: {}%;"></div></div></td><td>{}</td></tr>"#,
+            label,
+            pct,
+            format_watch_time(seconds)
+        )
+    };
+
+    if !summary.top_genres.is_empty() {
+        html.push_str(r#"<h2>Most-watched genres</h2><table class="invite-table"><tbody>"#);
+        let max = summary.top_genres.first().map_or(0, |g| g.seconds);
+        for genre in &summary.top_genres {
+            html.push_str(&bar(&escape_html(&genre.genre), genre.seconds, max));
+        }
+        html.push_str("</tbody></table>");
+    }
+
+    html.push_str(r#"<h2>Busiest days</h2><table class="invite-table"><tbody>"#);
+    let max = summary.busiest_days.first().map_or(0, |d| d.seconds);
+    for day in &summary.busiest_days {
+        let label = format!(
+            "{} · {} title{}",
+            escape_html(&day.day),
+            day.titles,
+            if day.titles == 1 { "" } else { "s" }
+        );
+        html.push_str(&bar(&label, day.seconds, max));
+    }
+    html.push_str("</tbody></table>");
+
+    html.push_str(r#"<h2>By day of the week</h2><table class="invite-table"><tbody>"#);
+    let max = summary.weekdays.iter().map(|w| w.seconds).max().unwrap_or(0);
+    for weekday in &summary.weekdays {
+        html.push_str(&bar(weekday.weekday, weekday.seconds, max));
+    }
+    html.push_str("</tbody></table>");
+
+    html.push_str(r#"<p class="settings-hint">Stats are updated hourly. Each title counts toward the day it was last watched.</p></div>"#);
+    html.push_str(&base_end());
+    html
+}
+
+pub fn render_settings(
+    viewer: &Viewer,
+    player: &PlayerPreferences,
+    tmdb_account: Option<&TmdbAccount>,
+    parental: &ParentalSettings,
+) -> String {
+    let mut html = String::new();
+
+    html.push_str(&base_start("Settings - RustStream", "/settings", viewer));
+
+    let quality = viewer.prefs.image_quality;
+    let option = |value: ImageQuality, label: &str| {
+        format!(
+            r#"<option value="{}"{}>{}</option>"#,
+            value.as_str(),
+            if quality == value { " selected" } else { "" },
+            label
+        )
+    };
+
+    html.push_str(&format!(
+        r#"<div class="settings-page"><h1>Settings</h1><form class="settings-form" action="/settings" method="post"><div class="filter-group"><label for="image_quality">Image quality</label><select id="image_quality" name="image_quality">{}{}{}</select></div><div class="settings-toggle"><label><input type="checkbox" name="data_saver"{}> Data saver</label><p class="settings-hint">Uses the smallest posters, hides backdrops, and turns off autoplay. Useful on mobile hotspots.</p></div><div class="settings-toggle"><label><input type="checkbox" name="pause_history"{}> Pause watch history</label><p class="settings-hint">Nothing you watch is added to history, Continue Watching, or stats until this is turned off.</p></div><button type="submit" class="play-button">Save</button></form></div>"#,
+        option(ImageQuality::Low, "Low"),
+        option(ImageQuality::Standard, "Standard"),
+        option(ImageQuality::High, "High"),
+        if viewer.prefs.data_saver { " checked" } else { "" },
+        if viewer.prefs.pause_history { " checked" } else { "" }
+    ));
+
+    let checked = |on: bool| if on { " checked" } else { "" };
+    html.push_str(&format!(
+        r##"<div class="settings-page"><h2>Player</h2><form class="settings-form" action="/settings/player" method="post"><div class="filter-group"><label for="accent_color">Accent color</label><input type="color" id="accent_color" name="accent_color" value="#{}"></div><div class="settings-toggle"><label><input type="checkbox" name="autoplay"{}> Autoplay</label><p class="settings-hint">Data saver turns autoplay off regardless.</p></div><div class="settings-toggle"><label><input type="checkbox" name="next_episode"{}> Next episode button</label></div><div class="settings-toggle"><label><input type="checkbox" name="episode_selector"{}> Episode selector</label></div><div class="filter-group"><label for="audio_language">Audio language</label><select id="audio_language" name="audio_language">{}</select><p class="settings-hint">Sources in this language are listed and picked first.</p></div><div class="filter-group"><label for="max_quality">Max quality</label><select id="max_quality" name="max_quality">{}</select><p class="settings-hint">Data saver caps quality at 480p.</p></div><button type="submit" class="play-button">Save</button></form></div>"##,
+        escape_html(player.accent_color.as_deref().unwrap_or(ACCENT_COLOR)),
+        checked(player.autoplay),
+        checked(player.next_episode),
+        checked(player.episode_selector),
+        audio_language_options(player.audio_language.as_deref()),
+        max_quality_options(player.max_quality, None)
+    ));
+
+    html.push_str(&render_parental_settings(parental));
+
+    if viewer.can(Permission::ManageAccount) {
+        html.push_str(&render_tmdb_account_settings(tmdb_account));
+    }
+
+    html.push_str(
+        r#"<div class="settings-page"><h2>Sessions</h2><p class="settings-hint">See where your account is signed in, and sign out devices you don't recognize.</p><a href="/settings/sessions" class="secondary-button">Manage sessions</a></div>"#,
+    );
+
+    html.push_str(&format!(
+        r#"<div class="settings-page"><h2>Your data</h2><p class="settings-hint">Download your watch history, watchlist, ratings, and settings as JSON.</p><a href="/api/account/export" class="secondary-button">Download my data</a>{}</div>"#,
+        if viewer.username == Some("local") {
+            ""
+        } else {
+            r#" <a href="/settings/account/delete" class="secondary-button">Delete account</a>"#
+        }
+    ));
+
+    html.push_str(&base_end());
+    html
+}
+
+fn render_parental_settings(parental: &ParentalSettings) -> String {
+    let selected = parental.limit.map(|limit| limit.label);
+    let mut options = format!(
+        r#"<option value=""{}>No limit</option>"#,
+        if selected.is_none() { " selected" } else { "" }
+    );
+    for limit in LIMITS {
+        options.push_str(&format!(
+            r#"<option value="{}"{}>{}</option>"#,
+            limit.label,
+            if selected == Some(limit.label) { " selected" } else { "" },
+            limit.label
+        ));
+    }
+    let current_pin = if parental.has_pin() {
+        r#"<div class="filter-group"><label for="current_pin">Current PIN</label><input type="password" id="current_pin" name="current_pin" inputmode="numeric" autocomplete="off" required><p class="settings-hint">Needed to change these settings.</p></div>"#
+    } else {
+        ""
+    };
+
+    format!(
+        r#"<div class="settings-page"><h2>Parental controls</h2><form class="settings-form" action="/settings/parental" method="post"><div class="filter-group"><label for="max_certification">Maximum rating</label><select id="max_certification" name="max_certification">{}</select><p class="settings-hint">Titles rated above this, and unrated titles, are hidden from search and can't be opened or played without the PIN. TV and other countries' ratings are compared by age.</p></div><div class="filter-group"><label for="new_pin">{}</label><input type="password" id="new_pin" name="new_pin" inputmode="numeric" pattern="[0-9]*" minlength="{}" autocomplete="off"><p class="settings-hint">Leave empty to keep the current PIN.</p></div>{}<button type="submit" class="play-button">Save</button></form></div>"#,
+        options,
+        if parental.has_pin() { "New PIN" } else { "PIN" },
+        MIN_PIN_LENGTH,
+        current_pin
+    )
+}
+
+/// Shown instead of a title that is above the profile's rating limit.
+pub fn render_restricted(
+    viewer: &Viewer,
+    title: Option<&str>,
+    certification: Option<&Certification>,
+    return_to: &str,
+    has_pin: bool,
+    error: Option<&str>,
+) -> String {
+    let mut html = String::new();
+
+    html.push_str(&base_start("Restricted - RustStream", return_to, viewer));
+    let reason = match (title, certification) {
+        (Some(title), Some(certification)) => format!(
+            "{} is rated {}, above this profile's limit.",
+            escape_html(title),
+            escape_html(&certification.rating)
+        ),
+        (Some(title), None) => format!(
+            "{} has no rating, and unrated titles are hidden while a limit is on.",
+            escape_html(title)
+        ),
+        (None, _) => "This title is above this profile's limit.".to_string(),
+    };
+    let unlock = if has_pin {
+        format!(
+            r#"<form class="settings-form" action="/parental/unlock" method="post"><input type="hidden" name="return_to" value="{}"><div class="filter-group"><label for="pin">PIN</label><input type="password" id="pin" name="pin" inputmode="numeric" autocomplete="off" required autofocus></div><button type="submit" class="play-button">Unlock for an hour</button></form>"#,
+            escape_html(return_to)
+        )
+    } else {
+        r#"<p class="settings-hint">Set a PIN in <a href="/settings">Settings</a> to be able to unlock titles.</p>"#.to_string()
+    };
+    html.push_str(&format!(
+        r#"<div class="settings-page"><h1>Restricted</h1>{}<p>{}</p>{}</div>"#,
+        error
+            .map(|e| format!(r#"<p class="form-error">{}</p>"#, escape_html(e)))
+            .unwrap_or_default(),
+        reason,
+        unlock
+    ));
+    html.push_str(&base_end());
+    html
+}
+
+/// Password sign-in for accounts created with an invite.
+pub fn render_login(viewer: &Viewer, username: &str, error: Option<&str>) -> String {
+    let mut html = String::new();
+
+    html.push_str(&base_start("Sign In - RustStream", "/login", viewer));
+    html.push_str(&format!(
+        r#"<div class="settings-page"><h1>Sign In</h1>{}<form class="settings-form" action="/login" method="post"><div class="filter-group"><label for="username">Username</label><input type="text" id="username" name="username" value="{}" autocomplete="username" required></div><div class="filter-group"><label for="password">Password</label><input type="password" id="password" name="password" autocomplete="current-password" required></div><div class="settings-toggle"><label><input type="checkbox" name="remember" value="true"> Remember me</label><p class="settings-hint">Stay signed in for a week. Otherwise you're signed out when the browser closes.</p></div><button type="submit" class="play-button">Sign in</button></form><p class="settings-hint">Have an invite code? <a href="/register">Create an account</a>.</p></div>"#,
+        error
+            .map(|e| format!(r#"<p class="form-error">{}</p>"#, escape_html(e)))
+            .unwrap_or_default(),
+        escape_html(username)
+    ));
+    html.push_str(&base_end());
+    html
+}
+
+/// Confirms deleting the viewer's account with their password.
+pub fn render_delete_account(viewer: &Viewer, error: Option<&str>) -> String {
+    let mut html = String::new();
+
+    html.push_str(&base_start("Delete Account - RustStream", "/settings/account/delete", viewer));
+    html.push_str(&format!(
+        r#"<div class="settings-page"><h1>Delete Account</h1>{}<p class="settings-hint">This deletes your account, watch history, watchlist, ratings, and settings, and signs out all your sessions. It can't be undone. <a href="/api/account/export">Download your data</a> first if you want to keep it.</p><form class="settings-form" action="/settings/account/delete" method="post"><div class="filter-group"><label for="password">Password</label><input type="password" id="password" name="password" autocomplete="current-password" required></div><button type="submit" class="play-button">Delete my account</button></form></div>"#,
+        error
+            .map(|e| format!(r#"<p class="form-error">{}</p>"#, escape_html(e)))
+            .unwrap_or_default()
+    ));
+    html.push_str(&base_end());
+    html
+}
+
+/// Account sign-up for someone holding an invite code.
+pub fn render_register(viewer: &Viewer, code: &str, error: Option<&str>) -> String {
+    let mut html = String::new();
+
+    html.push_str(&base_start("Create Account - RustStream", "/register", viewer));
+    html.push_str(&format!(
+        r#"<div class="settings-page"><h1>Create Account</h1>{}<form class="settings-form" action="/register" method="post"><div class="filter-group"><label for="invite_code">Invite code</label><input type="text" id="invite_code" name="invite_code" value="{}" required></div><div class="filter-group"><label for="username">Username</label><input type="text" id="username" name="username" autocomplete="username" required></div><div class="filter-group"><label for="password">Password</label><input type="password" id="password" name="password" autocomplete="new-password" minlength="{}" required></div><button type="submit" class="play-button">Create account</button></form><p class="settings-hint">Already have an account? <a href="/login">Sign in</a>.</p></div>"#,
+        error
+            .map(|e| format!(r#"<p class="form-error">{}</p>"#, escape_html(e)))
+            .unwrap_or_default(),
+        escape_html(code),
+        MIN_PASSWORD_LENGTH
+    ));
+    html.push_str(&base_end());
+    html
+}
+
+/// The signed-in user's sessions, each with a sign-out button.
+/// `evicted` is how many older sessions signing in just signed out.
+pub fn render_sessions(viewer: &Viewer, sessions: &[ActiveSession], current_id: &str, evicted: Option<u64>) -> String {
+    let mut html = String::new();
+
+    html.push_str(&base_start("Sessions - RustStream", "/settings/sessions", viewer));
+    html.push_str(r#"<div class="settings-page"><h1>Sessions</h1>"#);
+    if let Some(evicted) = evicted.filter(|&n| n > 0) {
+        html.push_str(&format!(
+            r#"<p class="form-error">You were signed in on too many devices, so signing in here signed out the {} oldest {}.</p>"#,
+            evicted,
+            if evicted == 1 { "session" } else { "sessions" }
+        ));
+    }
+
+    if sessions.is_empty() {
+        html.push_str(r#"<p class="settings-hint">No signed-in sessions. Without a session cookie you're browsing as the local user.</p>"#);
+    } else {
+        html.push_str(r#"<table class="invite-table"><thead><tr><th>Device</th><th>IP</th><th>Signed in</th><th>Last seen</th><th></th></tr></thead><tbody>"#);
+        for session in sessions {
+            let last_seen = session
+                .last_seen_at
+                .and_then(|t| chrono::DateTime::from_timestamp(t, 0))
+                .map(|t| t.format("%Y-%m-%d %H:%M UTC").to_string())
+                .unwrap_or_else(|| "Unknown".to_string());
+            let label = if session.id == current_id {
+                r#" <span class="role-badge">this device</span>"#
+            } else {
+                ""
+            };
+            html.push_str(&format!(
+                r#"<tr><td>{}{}</td><td>{}</td><td>{}</td><td>{}</td><td><form action="/settings/sessions/{}/revoke" method="post"><button type="submit" class="secondary-button">Sign out</button></form></td></tr>"#,
+                escape_html(session.user_agent.as_deref().unwrap_or("Unknown browser")),
+                label,
+                escape_html(session.ip.as_deref().unwrap_or("-")),
+                escape_html(&session.created_at),
+                last_seen,
+                escape_html(&session.id)
+            ));
+        }
+        html.push_str("</tbody></table>");
+        html.push_str(r#"<form action="/settings/sessions/revoke-all" method="post"><button type="submit" class="play-button">Log out everywhere</button></form>"#);
+    }
+
+    html.push_str("</div>");
+    html.push_str(&base_end());
+    html
+}
+
+/// Admin page for handing out and revoking invite codes.
+pub fn render_admin_invites(viewer: &Viewer, invites: &[Invite]) -> String {
+    let mut html = String::new();
+
+    html.push_str(&base_start("Invites - RustStream", "/admin/invites", viewer));
+    // Only roles the viewer may hand out.
+    let role_options: String = [
+        (Role::Member, "Member"),
+        (Role::Manager, "Manager"),
+        (Role::Kid, "Kid"),
+        (Role::Guest, "Guest"),
+        (Role::Admin, "Admin"),
+    ]
+    .iter()
+    .filter(|(role, _)| !role.outranks(viewer.role))
+    .map(|(role, label)| {
+        let selected = if *role == Role::Member { " selected" } else { "" };
+        format!(r#"<option value="{}"{}>{}</option>"#, role.as_str(), selected, label)
+    })
+    .collect();
+    html.push_str(&format!(
+        r#"<div class="settings-page"><h1>Invites</h1><form class="settings-form" action="/admin/invites" method="post"><div class="filter-group"><label for="role">Role</label><select id="role" name="role">{}</select></div><div class="filter-group"><label for="expires_in_days">Expires after (days)</label><input type="number" id="expires_in_days" name="expires_in_days" min="1" value="7"><p class="settings-hint">Leave empty for an invite that never expires. Each code creates one account at <code>/register</code>.</p></div><button type="submit" class="play-button">Create invite</button></form>"#,
+        role_options
+    ));
+
+    if invites.is_empty() {
+        html.push_str(r#"<p class="settings-hint">No invites yet.</p>"#);
+    } else {
+        html.push_str(r#"<table class="invite-table"><thead><tr><th>Code</th><th>Role</th><th>Status</th><th></th></tr></thead><tbody>"#);
+        let now = chrono::Utc::now().timestamp();
+        for invite in invites {
+            let code = escape_html(&invite.code);
+            let (status, action) = if invite.used_by.is_some() {
+                (
+                    format!("Used {}", escape_html(invite.used_at.as_deref().unwrap_or_default())),
+                    String::new(),
+                )
+            } else if invite.expires_at.is_some_and(|t| t <= now) {
+                ("Expired".to_string(), String::new())
+            } else {
+                (
+                    format!(r#"<a href="/register?code={}">Sign-up link</a>"#, code),
+                    format!(
+                        r#"<form action="/admin/invites/{}/revoke" method="post"><button type="submit" class="secondary-button">Revoke</button></form>"#,
+                        code
+                    ),
+                )
+            };
+            html.push_str(&format!(
+                "<tr><td><code>{}</code></td><td>{}</td><td>{}</td><td>{}</td></tr>",
+                code,
+                escape_html(&invite.role),
+                status,
+                action
+            ));
+        }
+        html.push_str("</tbody></table>");
+    }
+
+    html.push_str("</div>");
+    html.push_str(&base_end());
+    html
+}
+
+/// Admin page for turning stream providers on and off, reordering them, and
+/// setting the query parameters added to their embed URLs.
+pub fn render_admin_providers(viewer: &Viewer, providers: &[ProviderConfig]) -> String {
+    let mut html = String::new();
+
+    html.push_str(&base_start("Stream Providers - RustStream", "/admin/providers", viewer));
+    html.push_str(
+        r#"<div class="settings-page"><h1>Stream Providers</h1><p class="settings-hint">Enabled providers are asked for streams in priority order, highest first. Options are added to the provider's embed URLs as query parameters, one <code>name=value</code> per line. Changes apply to the next stream lookup.</p>"#,
+    );
+
+    for provider in providers {
+        let options: Vec<String> = provider
+            .options
+            .iter()
+            .map(|(key, value)| format!("{}={}", key, value))
+            .collect();
+        let name = escape_html(&provider.name);
+        html.push_str(&format!(
+            r#"<form class="settings-form provider-config" action="/admin/providers/{name}" method="post"><h2>{name}{health}</h2><div class="settings-toggle"><label><input type="checkbox" name="enabled"{enabled}> Enabled</label></div><div class="filter-group"><label for="priority-{name}">Priority</label><input type="number" id="priority-{name}" name="priority" value="{priority}" placeholder="{default_priority}"><p class="settings-hint">Default: {default_priority}</p></div><div class="filter-group"><label for="options-{name}">Options</label><textarea id="options-{name}" name="options" rows="3">{options}</textarea></div><button type="submit" class="play-button">Save</button>{reset}</form>"#,
+            name = name,
+            health = if provider.healthy { "" } else { r#" <span class="role-badge">unreachable</span>"# },
+            enabled = if provider.enabled { " checked" } else { "" },
+            priority = provider.priority,
+            default_priority = provider.default_priority,
+            options = escape_html(&options.join("\n")),
+            reset = if provider.customized {
+                format!(
+                    r#" <button type="submit" formaction="/admin/providers/{}/reset" class="secondary-button">Reset to defaults</button>"#,
+                    name
+                )
+            } else {
+                String::new()
+            },
+        ));
+    }
+
+    html.push_str("</div>");
+    html.push_str(&base_end());
+    html
+}
+
+/// Audio languages offered in the player settings, as ISO 639-1 codes.
+const AUDIO_LANGUAGES: &[(&str, &str)] = &[
+    ("en", "English"),
+    ("es", "Spanish"),
+    ("fr", "French"),
+    ("de", "German"),
+    ("it", "Italian"),
+    ("pt", "Portuguese"),
+    ("ja", "Japanese"),
+    ("ko", "Korean"),
+    ("zh", "Chinese"),
+    ("hi", "Hindi"),
+];
+
+fn audio_language_options(selected: Option<&str>) -> String {
+    let mut html = format!(
+        r#"<option value=""{}>No preference</option>"#,
+        if selected.is_none() { " selected" } else { "" }
+    );
+    for (code, name) in AUDIO_LANGUAGES {
+        html.push_str(&format!(
+            r#"<option value="{}"{}>{}</option>"#,
+            code,
+            if selected == Some(*code) { " selected" } else { "" },
+            name
+        ));
+    }
+    html
+}
+
+/// Cap choices, with those above `limit` shown but disabled.
+fn max_quality_options(selected: Option<u32>, limit: Option<u32>) -> String {
+    let mut html = format!(
+        r#"<option value=""{}>Auto</option>"#,
+        if selected.is_none() { " selected" } else { "" }
+    );
+    for height in QUALITY_CAPS {
+        let over_limit = limit.is_some_and(|limit| *height > limit);
+        html.push_str(&format!(
+            r#"<option value="{0}"{1}{2}>{0}p{3}</option>"#,
+            height,
+            if selected == Some(*height) { " selected" } else { "" },
+            if over_limit { " disabled" } else { "" },
+            if over_limit { " (data saver)" } else { "" }
+        ));
+    }
+    html
+}
+
+fn render_tmdb_account_settings(account: Option<&TmdbAccount>) -> String {
+    let import_form = r#"<form class="settings-form" action="/settings/tmdb/list" method="post"><div class="filter-group"><label for="list">Import a public TMDB list</label><input type="text" id="list" name="list" placeholder="https://www.themoviedb.org/list/8136" required><p class="settings-hint">Adds the list's movies and shows to your watchlist.</p></div><div class="actions"><button type="submit" class="play-button-small">Import list</button></div></form>"#;
+
+    let Some(account) = account else {
+        return format!(
+            r#"<div class="settings-page"><h2>TMDB account</h2><p class="settings-hint">Import your TMDB watchlist and ratings, and keep them in sync.</p><a href="/settings/tmdb/connect" class="play-button">Connect TMDB account</a>{}</div>"#,
+            import_form
+        );
+    };
+
+    format!(
+        r#"<div class="settings-page"><h2>TMDB account</h2><p class="settings-hint">Connected. Last synced: {}</p><form class="settings-form" action="/settings/tmdb" method="post"><div class="settings-toggle"><label><input type="checkbox" name="sync_enabled"{}> Keep watchlist and ratings in sync</label><p class="settings-hint">Syncs hourly in both directions. Titles removed on TMDB are removed here.</p></div><div class="actions"><button type="submit" name="action" value="save" class="play-button">Save</button> <button type="submit" name="action" value="sync" class="play-button-small">Sync now</button> <button type="submit" name="action" value="disconnect" class="play-button-small">Disconnect</button></div></form>{}</div>"#,
+        escape_html(account.last_synced_at.as_deref().unwrap_or("never")),
+        if account.sync_enabled { " checked" } else { "" },
+        import_form
+    )
+}
+
+/// Rendered width of grid and detail posters in `style.css`.
+const CARD_WIDTH: u32 = 200;
+const DETAIL_POSTER_WIDTH: u32 = 300;
+
+/// ` srcset=".." sizes=".."` so high-density screens get a sharper poster.
+/// Sizes stay within the viewer's image quality, and data saver keeps the
+/// single small image.
+fn poster_srcset(viewer: &Viewer, path: Option<&str>, display_width: u32) -> String {
+    let Some(path) = path else {
+        return String::new();
+    };
+    if viewer.prefs.data_saver {
+        return String::new();
+    }
+
+    let max_width = viewer.prefs.quality().detail_poster_width();
+    let candidates: Vec<String> = crate::tmdb::image_config()
+        .poster_widths(max_width)
+        .into_iter()
+        .map(|(size, width)| format!("{} {}w", tmdb_image(path, size), width))
+        .collect();
+    if candidates.len() < 2 {
+        return String::new();
+    }
+
+    format!(r#" srcset="{}" sizes="{}px""#, candidates.join(", "), display_width)
+}
+
+fn tmdb_image(path: &str, size: &str) -> String {
+    crate::images::image_url(path, size)
+}
+
+fn render_watch_providers(providers: &WatchProviders) -> String {
+    if providers.is_empty() {
+        return String::new();
+    }
+
+    let mut html = String::new();
+    html.push_str(r#"<section class="providers-section"><h2>Where to Watch</h2>"#);
+
+    let groups = [
+        ("Stream", &providers.flatrate),
+        ("Free", &providers.free),
+        ("With Ads", &providers.ads),
+        ("Rent", &providers.rent),
+        ("Buy", &providers.buy),
+    ];
+
+    for (label, list) in groups {
+        if list.is_empty() {
+            continue;
+        }
+        html.push_str(&format!(
+            r#"<div class="provider-group"><h3>{}</h3><div class="provider-logos">"#,
+            label
+        ));
+        for provider in list {
+            let logo = provider
+                .logo_path
+                .as_ref()
+                .map(|p| tmdb_image(p, crate::tmdb::image_config().best_logo_size(92)))
+                .unwrap_or_else(|| "/static/placeholder.jpg".to_string());
+            let name = escape_html(&provider.provider_name);
+            html.push_str(&format!(
+                r#"<img class="provider-logo" src="{}" alt="{}" title="{}">"#,
+                escape_html(&logo), name, name
+            ));
+        }
+        html.push_str("</div></div>");
+    }
+
+    let link = providers
+        .link
+        .as_ref()
+        .filter(|link| link.starts_with("https://") || link.starts_with("http://"));
+    if let Some(link) = link {
+        html.push_str(&format!(
+            r#"<p class="provider-attribution"><a href="{}" target="_blank" rel="noopener">Availability data by JustWatch</a></p>"#,
+            escape_html(link)
+        ));
+    }
+
+    html.push_str("</section>");
+    html
+}
+
+/// "Title (Year) - RustStream" for detail pages.
+fn titled_page(name: &str, date: Option<&str>) -> String {
+    match date.and_then(|d| d.split('-').next()).filter(|y| !y.is_empty()) {
+        Some(year) => format!("{} ({}) - RustStream", name, year),
+        None => format!("{} - RustStream", name),
+    }
+}
+
+/// Escapes text for element content and for attributes in either quote style.
+fn escape_html(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+/// The navbar bell: notifications from `/api/events`, unread since the
+/// bell was last opened in this browser.
+const NOTIFICATION_BELL: &str = r#"<div class="notification-bell" id="notification-bell">
+                <button type="button" aria-label="Notifications" aria-expanded="false">🔔<span class="notification-count" hidden></span></button>
+                <div class="notification-panel" hidden>
+                    <p class="notification-empty">No notifications</p>
+                    <ul></ul>
+                </div>
+            </div>
+            <script>
+            (function() {
+                var bell = document.getElementById("notification-bell");
+                if (!window.EventSource) return;
+                var button = bell.querySelector("button");
+                var count = bell.querySelector(".notification-count");
+                var panel = bell.querySelector(".notification-panel");
+                var list = panel.querySelector("ul");
+                var empty = panel.querySelector(".notification-empty");
+                var key = "ruststream-notifications-read";
+                var lastRead = Number(localStorage.getItem(key)) || 0;
+                var newest = 0;
+                var unread = 0;
+                function render() {
+                    count.textContent = unread > 9 ? "9+" : String(unread);
+                    count.hidden = unread === 0;
+                    empty.hidden = list.children.length > 0;
+                }
+                function setOpen(open) {
+                    panel.hidden = !open;
+                    button.setAttribute("aria-expanded", String(open));
+                    if (open && newest > lastRead) {
+                        lastRead = newest;
+                        localStorage.setItem(key, String(lastRead));
+                        unread = 0;
+                        render();
+                    }
+                }
+                new EventSource("/api/events").addEventListener("notification", function(event) {
+                    var notification = JSON.parse(event.data);
+                    newest = Math.max(newest, notification.id);
+                    var item = document.createElement("li");
+                    if (notification.id > lastRead) {
+                        item.className = "unread";
+                        unread++;
+                    }
+                    var body = document.createElement(notification.url ? "a" : "div");
+                    if (notification.url) body.href = notification.url;
+                    var title = document.createElement("strong");
+                    title.textContent = notification.title;
+                    var message = document.createElement("span");
+                    message.textContent = notification.message;
+                    body.appendChild(title);
+                    body.appendChild(message);
+                    item.appendChild(body);
+                    list.insertBefore(item, list.firstChild);
+                    while (list.children.length > 20) list.removeChild(list.lastChild);
+                    render();
+                });
+                button.addEventListener("click", function() { setOpen(panel.hidden); });
+                document.addEventListener("click", function(event) {
+                    if (!bell.contains(event.target)) setOpen(false);
+                });
+                render();
+            })();
+            </script>"#;
+
+fn base_start(title: &str, canonical_path: &str, viewer: &Viewer) -> String {
+    let settings_link = if viewer.can(Permission::EditPreferences) {
+        r#"<a href="/settings">Settings</a>"#
+    } else {
+        ""
+    };
+    let admin_link = if viewer.can(Permission::ManageSettings) {
+        r#"<a href="/admin/providers">Providers</a>"#
+    } else {
+        ""
+    };
+    let invites_link = if viewer.can(Permission::ManageUsers) {
+        r#"<a href="/admin/invites">Invites</a>"#
+    } else {
+        ""
+    };
+    let role_badge = match viewer.role {
+        Role::Member => String::new(),
+        role => format!(r#" <span class="role-badge">{}</span>"#, role.as_str()),
+    };
+    let user_info = match viewer.username {
+        Some(username) => format!(r#"<span class="user-info">👤 {}{}</span>"#, escape_html(username), role_badge),
+        None => r#"<a href="/login">Sign in</a>"#.to_string(),
+    };
     let nav_links = format!(
         r#"<a href="/">Home</a>
             <a href="/search">Search</a>
+            <a href="/anime">Anime</a>
             <a href="/history">History</a>
-            <span class="user-info">👤 {}</span>"#,
-        username.unwrap_or("Local")
+            <a href="/watchlist">Watchlist</a>
+            <a href="/playlists">Playlists</a>
+            {}
+            {}
+            {}
+            {}
+            {}"#,
+        settings_link,
+        admin_link,
+        invites_link,
+        if viewer.user_id.is_some() { NOTIFICATION_BELL } else { "" },
+        user_info
     );
 
     format!(
-        r#"<!DOCTYPE html>
+        r##"<!DOCTYPE html>
 <html lang="en">
 <head>
     <meta charset="UTF-8">
     <meta name="viewport" content="width=device-width, initial-scale=1.0">
     <title>{}</title>
+    <link rel="canonical" href="{}">
+    <link rel="icon" href="/favicon.svg" type="image/svg+xml">
+    <link rel="icon" href="/favicon.ico" sizes="any">
+    <link rel="apple-touch-icon" href="/apple-touch-icon.png">
+    <meta name="theme-color" content="#{}">
     <link rel="stylesheet" href="/static/style.css">
 </head>
 <body>
@@ -673,8 +2760,19 @@ fn base_start(title: &str, username: Option<&str>) -> String {
             {}
         </div>
     </nav>
-    <main>"#,
-        title, nav_links
+    <main>"##,
+        escape_html(title),
+        escape_html(canonical_path),
+        ACCENT_COLOR,
+        nav_links
+    )
+}
+
+/// Square "R" mark in the accent color, served as the SVG favicon.
+pub fn render_favicon_svg() -> String {
+    format!(
+        r##"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 64 64"><rect width="64" height="64" rx="12" fill="#{}"/><text x="32" y="46" font-family="Arial, Helvetica, sans-serif" font-size="40" font-weight="bold" fill="#fff" text-anchor="middle">R</text></svg>"##,
+        ACCENT_COLOR
     )
 }
 
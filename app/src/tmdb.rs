@@ -1,34 +1,249 @@
-use reqwest::Client;
+use once_cell::sync::{Lazy, OnceCell};
+use reqwest::header::{HeaderValue, AUTHORIZATION, IF_NONE_MATCH};
+use reqwest::{Client, Request, RequestBuilder, Response};
 use serde::{Deserialize, Serialize};
-use std::time::Duration;
-use tracing::{debug, error};
+use std::collections::{HashMap, HashSet};
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::{debug, error, info, warn};
+
+use crate::metrics::AppMetrics;
+use crate::tmdb_cache::EtagCache;
+use crate::tmdb_flight::{InFlight, TmdbResponse};
+use crate::tmdb_keys::{KeyPool, KeyStatus};
 
 const TMDB_BASE_URL: &str = "https://api.themoviedb.org/3";
-const TMDB_IMAGE_BASE: &str = "https://image.tmdb.org/t/p";
+/// User sign-in and account lists are only available on the v4 API.
+const TMDB_V4_BASE_URL: &str = "https://api.themoviedb.org/4";
+pub const TMDB_IMAGE_BASE: &str = "https://image.tmdb.org/t/p";
+pub const DEFAULT_REGION: &str = "US";
+
+/// TMDB rejects `page` values above this.
+pub const TMDB_MAX_PAGE: i32 = 500;
+
+/// Title suggestions change slowly and are requested on every keystroke.
+const SUGGEST_CACHE_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Expired suggestions are pruned once the cache reaches this many queries.
+const SUGGEST_CACHE_MAX: usize = 5000;
+
+const SUGGEST_LIMIT: usize = 8;
+
+/// The Animation genre, shared by the movie and TV genre lists.
+const ANIMATION_GENRE_ID: i64 = 16;
+
+/// TMDB's `anime` keyword.
+const ANIME_KEYWORD_ID: i64 = 210024;
+
+/// Episode lists rarely change once a season has aired.
+const SEASON_CACHE_TTL: Duration = Duration::from_secs(6 * 60 * 60);
+
+/// Expired seasons are pruned once the cache reaches this many; each holds
+/// a full episode list, so this is lower than the other caches.
+const SEASON_CACHE_MAX: usize = 2000;
+
+/// Age ratings are looked up for every search result when parental limits
+/// are on, and almost never change.
+const CERTIFICATION_CACHE_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Expired ratings are pruned once the cache reaches this many titles.
+const CERTIFICATION_CACHE_MAX: usize = 10_000;
+
+/// Image sizes reported by TMDB's `/configuration`, loaded once at startup.
+static IMAGE_CONFIG: OnceCell<ImageConfiguration> = OnceCell::new();
+static DEFAULT_IMAGE_CONFIG: Lazy<ImageConfiguration> = Lazy::new(ImageConfiguration::default);
+
+/// The image configuration TMDB reported, or TMDB's long-standing defaults
+/// if it has not been loaded.
+pub fn image_config() -> &'static ImageConfiguration {
+    IMAGE_CONFIG.get().unwrap_or(&DEFAULT_IMAGE_CONFIG)
+}
+
+type SeasonCache = Mutex<HashMap<(i64, i64), (Instant, Arc<SeasonDetail>)>>;
+type SuggestCache = Mutex<HashMap<String, (Instant, Arc<Vec<Suggestion>>)>>;
+type CertificationCache = Mutex<HashMap<(String, i64), (Instant, Option<Certification>)>>;
 
 #[derive(Debug, Clone)]
 pub struct TmdbClient {
     client: Client,
-    api_key: String,
+    keys: Arc<KeyPool>,
+    language: Option<String>,
+    region: String,
+    metrics: Arc<AppMetrics>,
+    seasons: Arc<SeasonCache>,
+    suggestions: Arc<SuggestCache>,
+    certifications: Arc<CertificationCache>,
+    in_flight: Arc<InFlight>,
+    etags: Arc<EtagCache>,
 }
 
 impl TmdbClient {
-    pub fn new(api_key: &str) -> anyhow::Result<Self> {
+    pub fn new(
+        api_key: &str,
+        language: Option<&str>,
+        region: &str,
+        metrics: Arc<AppMetrics>,
+    ) -> anyhow::Result<Self> {
         let client = Client::builder()
             .timeout(Duration::from_secs(30))
             .build()?;
 
         Ok(Self {
             client,
-            api_key: api_key.to_string(),
+            keys: Arc::new(KeyPool::parse(api_key)?),
+            language: language.map(|l| l.to_string()),
+            region: region.to_uppercase(),
+            metrics,
+            seasons: Arc::new(Mutex::new(HashMap::new())),
+            suggestions: Arc::new(Mutex::new(HashMap::new())),
+            certifications: Arc::new(Mutex::new(HashMap::new())),
+            in_flight: Arc::new(InFlight::new()),
+            etags: Arc::new(EtagCache::new()),
         })
     }
 
-    fn auth_header(&self) -> String {
-        if self.api_key.starts_with("Bearer ") {
-            self.api_key.clone()
+    /// ISO 3166-1 country code used for release dates and availability.
+    pub fn region(&self) -> &str {
+        &self.region
+    }
+
+    /// Request counts and bench state for each configured API key.
+    pub fn key_status(&self) -> Vec<KeyStatus> {
+        self.keys.status()
+    }
+
+    /// Starts a GET with the configured language applied, so every call
+    /// returns localized titles, overviews, and genre names. Credentials are
+    /// added by [`Self::send`].
+    fn get(&self, url: &str) -> RequestBuilder {
+        let request = self.client.get(url);
+
+        match &self.language {
+            Some(language) => request.query(&[("language", language)]),
+            None => request,
+        }
+    }
+
+    /// Sends a request built by [`Self::get`]. Identical requests already in
+    /// flight share one upstream call instead of each hitting TMDB.
+    async fn send(&self, request: RequestBuilder) -> anyhow::Result<TmdbResponse> {
+        let request = request.build()?;
+        let response = if request.method() != reqwest::Method::GET {
+            self.send_upstream(request).await
         } else {
-            format!("Bearer {}", self.api_key)
+            let key = request.url().to_string();
+            self.in_flight.run(key, || self.send_upstream(request)).await
+        };
+        response.map_err(|e| TmdbError::Unavailable(e.to_string()).into())
+    }
+
+    /// Sends a request that already carries a user's own access token,
+    /// bypassing the shared keys and caches.
+    async fn send_as_user(&self, request: RequestBuilder) -> anyhow::Result<TmdbResponse> {
+        let result = request.send().await;
+        let ok = matches!(&result, Ok(response) if response.status().is_success());
+        self.metrics.record_tmdb_request(ok);
+        let response = result.map_err(|e| TmdbError::Unavailable(e.to_string()))?;
+        Ok(TmdbResponse::read(response).await?)
+    }
+
+    /// Sends `request` with the next API key in the rotation and counts it
+    /// in the metrics. A 401 or 429 benches that key and retries with the
+    /// next one. URLs fetched before are revalidated with their `ETag`.
+    async fn send_upstream(&self, mut request: Request) -> anyhow::Result<TmdbResponse> {
+        let url = request.url().to_string();
+        let cached = (request.method() == reqwest::Method::GET)
+            .then(|| self.etags.get(&url))
+            .flatten();
+        if let Some(value) = cached.as_ref().and_then(|(etag, _)| HeaderValue::from_str(etag).ok()) {
+            request.headers_mut().insert(IF_NONE_MATCH, value);
+        }
+
+        let mut attempts_left = self.keys.key_count();
+        let result = loop {
+            let attempt = match request.try_clone() {
+                Some(attempt) if attempts_left > 1 => attempt,
+                _ => {
+                    let (_, auth_header) = self.keys.next_key();
+                    break self.execute(request, auth_header).await;
+                }
+            };
+            attempts_left -= 1;
+
+            let (index, auth_header) = self.keys.next_key();
+            let result = self.execute(attempt, auth_header).await;
+            match &result {
+                Ok(response)
+                    if response.status() == reqwest::StatusCode::UNAUTHORIZED
+                        || response.status() == reqwest::StatusCode::TOO_MANY_REQUESTS =>
+                {
+                    let retry_after = response
+                        .headers()
+                        .get(reqwest::header::RETRY_AFTER)
+                        .and_then(|v| v.to_str().ok())
+                        .and_then(|v| v.parse().ok())
+                        .map(Duration::from_secs);
+                    self.keys.bench(index, response.status(), retry_after);
+                    self.metrics.record_tmdb_request(false);
+                }
+                _ => break result,
+            }
+        };
+
+        let ok = matches!(&result, Ok(response)
+            if response.status().is_success() || response.status() == reqwest::StatusCode::NOT_MODIFIED);
+        self.metrics.record_tmdb_request(ok);
+        let response = result?;
+
+        if let Some((_, cached)) = cached {
+            let not_modified = response.status() == reqwest::StatusCode::NOT_MODIFIED;
+            self.metrics.record_cache("tmdb_etag", not_modified);
+            if not_modified {
+                return Ok(cached);
+            }
+        }
+
+        let response = TmdbResponse::read(response).await?;
+        if response.status().is_success() {
+            if let Some(etag) = response.etag() {
+                self.etags.store(&url, etag, &response);
+            }
+        }
+        Ok(response)
+    }
+
+    async fn execute(&self, mut request: Request, auth_header: &str) -> reqwest::Result<Response> {
+        if let Ok(value) = HeaderValue::from_str(auth_header) {
+            request.headers_mut().insert(AUTHORIZATION, value);
+        }
+        self.client.execute(request).await
+    }
+
+    /// Fetches `/configuration` and makes its image sizes available through
+    /// [`image_config`]. Failures fall back to the built-in sizes.
+    pub async fn load_image_configuration(&self) {
+        #[derive(Debug, Deserialize)]
+        struct ConfigurationResponse {
+            images: ImageConfiguration,
+        }
+
+        let url = format!("{}/configuration", TMDB_BASE_URL);
+        let result = async {
+            let response = self.send(self.get(&url)).await?;
+            if !response.status().is_success() {
+                return Err(anyhow::anyhow!("TMDB API error: {}", response.status()));
+            }
+            Ok(response.json::<ConfigurationResponse>()?)
+        }
+        .await;
+
+        match result {
+            Ok(config) => {
+                info!("Loaded TMDB image configuration ({} poster sizes)", config.images.poster_sizes.len());
+                IMAGE_CONFIG.set(config.images).ok();
+            }
+            Err(e) => warn!("Using default TMDB image sizes: {}", e),
         }
     }
 
@@ -37,110 +252,483 @@ impl TmdbClient {
         
         debug!("Searching TMDB for: {}", query);
         
-        let response = self
-            .client
+        let request = self
             .get(&url)
-            .header("Authorization", self.auth_header())
             .query(&[
                 ("query", query),
                 ("page", &page.to_string()),
                 ("include_adult", "false"),
-            ])
-            .send()
-            .await?;
+            ]);
+        let response = self.send(request).await?;
 
         if !response.status().is_success() {
-            let error_text = response.text().await?;
+            let error_text = response.text();
             error!("TMDB search error: {}", error_text);
-            return Err(anyhow::anyhow!("TMDB API error: {}", error_text));
+            return Err(TmdbError::from_status(response.status(), error_text).into());
         }
 
-        let search_results: SearchResponse = response.json().await?;
+        let search_results: SearchResponse = response.json()?;
         Ok(search_results)
     }
 
-    pub async fn search_advanced(
+    /// Every movie and show a person acted in, or directed, newest first.
+    /// Unlike discover by cast this includes TV and uncredited roles.
+    pub async fn get_filmography(&self, person_id: i64, directing: bool) -> anyhow::Result<Vec<SearchResult>> {
+        let url = format!("{}/person/{}/combined_credits", TMDB_BASE_URL, person_id);
+        let response = self.send(self.get(&url)).await?;
+
+        if !response.status().is_success() {
+            let error_text = response.text();
+            error!("TMDB person credits error: {}", error_text);
+            return Err(TmdbError::from_status(response.status(), error_text).into());
+        }
+
+        let credits: PersonCredits = response.json()?;
+        let mut titles: Vec<SearchResult> = if directing {
+            credits
+                .crew
+                .into_iter()
+                .filter(|credit| credit.job == "Director")
+                .map(|credit| credit.title)
+                .collect()
+        } else {
+            credits.cast
+        };
+
+        let mut seen = HashSet::new();
+        titles.retain(|t| seen.insert((t.media_type.clone(), t.id)));
+        titles.sort_by_key(|t| {
+            std::cmp::Reverse(
+                t.release_date
+                    .clone()
+                    .or_else(|| t.first_air_date.clone())
+                    .unwrap_or_default(),
+            )
+        });
+        Ok(titles)
+    }
+
+    /// The top movie and TV matches for a partial query, for typeahead.
+    /// Results are cached by normalized query for a day.
+    pub async fn suggest(&self, query: &str) -> anyhow::Result<Arc<Vec<Suggestion>>> {
+        let key = query.trim().to_lowercase();
+        if key.is_empty() {
+            return Ok(Arc::default());
+        }
+
+        let cached = self
+            .suggestions
+            .lock()
+            .unwrap()
+            .get(&key)
+            .filter(|(stored_at, _)| stored_at.elapsed() < SUGGEST_CACHE_TTL)
+            .map(|(_, suggestions)| suggestions.clone());
+        self.metrics.record_cache("suggest", cached.is_some());
+        if let Some(suggestions) = cached {
+            return Ok(suggestions);
+        }
+
+        let results = self.search(&key, 1).await?;
+        let suggestions: Arc<Vec<Suggestion>> = Arc::new(
+            results
+                .results
+                .iter()
+                .filter_map(Suggestion::from_result)
+                .take(SUGGEST_LIMIT)
+                .collect(),
+        );
+
+        let mut cache = self.suggestions.lock().unwrap();
+        if cache.len() >= SUGGEST_CACHE_MAX {
+            cache.retain(|_, (stored_at, _)| stored_at.elapsed() < SUGGEST_CACHE_TTL);
+            if cache.len() >= SUGGEST_CACHE_MAX {
+                cache.clear();
+            }
+        }
+        cache.insert(key, (Instant::now(), suggestions.clone()));
+        Ok(suggestions)
+    }
+
+    /// Discover across movies and TV with one set of filters. Both listings
+    /// are fetched a page at a time and interleaved, so the order is stable
+    /// across requests; `cursor` resumes where the previous page stopped.
+    pub async fn discover_combined(
         &self,
-        query: &str,
-        media_type: Option<&str>,
-        year: Option<i32>,
-        genre: Option<&str>,
-        min_rating: Option<f64>,
-        sort_by: &str,
-        page: i32,
-    ) -> anyhow::Result<SearchResponse> {
-        let url = format!("{}/discover/movie", TMDB_BASE_URL);
-        
-        debug!("Advanced search: query={}, type={:?}, year={:?}, genre={:?}, min_rating={:?}, sort={}",
-               query, media_type, year, genre, min_rating, sort_by);
-        
+        filters: &DiscoverFilters,
+        cursor: Option<DiscoverCursor>,
+    ) -> anyhow::Result<DiscoverPage> {
+        debug!("Discover: {:?}, cursor={:?}", filters, cursor);
+
+        let mut cursor = cursor.unwrap_or_default();
+        match filters.media_type.as_deref() {
+            Some("movie") => cursor.tv_page = 0,
+            Some("tv") => cursor.movie_page = 0,
+            Some(other) => return Err(anyhow::anyhow!("Unsupported media type: {}", other)),
+            None => {}
+        }
+
+        let (query_params, scope, genre) = self.discover_params(filters).await?;
+        if !scope.tv {
+            cursor.tv_page = 0;
+        }
+        if !scope.movies {
+            cursor.movie_page = 0;
+        }
+
+        let sort_by = filters.sort_by.as_deref().unwrap_or("popularity.desc");
+        let fetch = |media_type: &'static str, page: i32| {
+            let query_params = &query_params;
+            async move {
+                if page == 0 {
+                    return Ok(None);
+                }
+                self.discover(media_type, query_params, genre, filters.year, sort_by, page)
+                    .await
+                    .map(Some)
+            }
+        };
+        let (movies, shows) = tokio::try_join!(
+            fetch("movie", cursor.movie_page),
+            fetch("tv", cursor.tv_page)
+        )?;
+
+        let next_page = |response: &Option<SearchResponse>| match response {
+            Some(r) if r.page < r.total_pages.min(TMDB_MAX_PAGE) => r.page + 1,
+            _ => 0,
+        };
+        let next = DiscoverCursor {
+            movie_page: next_page(&movies),
+            tv_page: next_page(&shows),
+        };
+
+        let total_results = [&movies, &shows]
+            .iter()
+            .filter_map(|r| r.as_ref())
+            .map(|r| r.total_results as i64)
+            .sum();
+        let total_pages = [&movies, &shows]
+            .iter()
+            .filter_map(|r| r.as_ref())
+            .map(|r| r.total_pages.min(TMDB_MAX_PAGE))
+            .max()
+            .unwrap_or(0);
+        let results = interleave_results(
+            movies.map(|r| r.results).unwrap_or_default(),
+            shows.map(|r| r.results).unwrap_or_default(),
+        );
+
+        Ok(DiscoverPage {
+            results,
+            total_results,
+            total_pages,
+            next_cursor: next.has_more().then(|| next.encode()),
+        })
+    }
+
+    /// Page `page` of [`Self::discover_combined`], for callers that number
+    /// pages rather than pass cursors.
+    pub async fn discover_page(&self, filters: &DiscoverFilters, page: i32) -> anyhow::Result<SearchResponse> {
+        let cursor = DiscoverCursor {
+            movie_page: page,
+            tv_page: page,
+        };
+        let combined = self.discover_combined(filters, Some(cursor)).await?;
+        Ok(SearchResponse {
+            page,
+            results: combined.results,
+            total_pages: combined.total_pages,
+            total_results: combined.total_results.min(i32::MAX as i64) as i32,
+        })
+    }
+
+    /// Discover parameters shared by movie and TV, which of the two
+    /// listings can answer them, and the requested genre, which each listing
+    /// numbers differently.
+    async fn discover_params(
+        &self,
+        filters: &DiscoverFilters,
+    ) -> anyhow::Result<(Vec<(&'static str, String)>, DiscoverScope, Option<i64>)> {
+        let query = filters.q.trim();
         let mut query_params: Vec<(&str, String)> = Vec::new();
-        
+        let mut scope = DiscoverScope { movies: true, tv: true };
+        let mut genre = None;
+
         if let Some(q) = query.strip_prefix("genre:") {
-            let genre_id = get_genre_id(q);
-            query_params.push(("with_genres", genre_id.to_string()));
+            genre = Some(get_genre_id(q)).filter(|id| *id > 0);
         } else if let Some(q) = query.strip_prefix("actor:") {
-            let person_id = self.search_person(q).await?;
+            let person_id = match filters.person {
+                Some(id) => id,
+                None => self.search_person(q).await?,
+            };
             if person_id > 0 {
                 query_params.push(("with_cast", person_id.to_string()));
             }
+            // TMDB's TV discover has no cast/crew filters.
+            scope.tv = false;
         } else if let Some(q) = query.strip_prefix("director:") {
-            let person_id = self.search_person(q).await?;
+            let person_id = match filters.person {
+                Some(id) => id,
+                None => self.search_person(q).await?,
+            };
             if person_id > 0 {
                 query_params.push(("with_crew", person_id.to_string()));
             }
+            scope.tv = false;
+        } else if let Some(q) = query.strip_prefix("keyword:") {
+            let keyword_id = self.search_keyword(q).await?;
+            if keyword_id > 0 {
+                query_params.push(("with_keywords", keyword_id.to_string()));
+            }
         } else if !query.is_empty() {
             query_params.push(("query", query.to_string()));
         }
-        
-        if let Some(mt) = media_type {
-            query_params.push(("media_type", mt.to_string()));
+
+        if let Some(g) = filters.genre.as_deref().filter(|g| !g.is_empty()) {
+            let genre_id = g.parse::<i64>().unwrap_or_else(|_| get_genre_id(g));
+            if genre_id > 0 {
+                genre = Some(genre_id);
+            }
         }
-        
-        if let Some(y) = year {
-            query_params.push(("year", y.to_string()));
-            query_params.push(("primary_release_year", y.to_string()));
+
+        if let Some(id) = genre {
+            // A genre only one side has, such as Horror or Reality, would
+            // otherwise list the other side unfiltered.
+            scope.movies &= movie_genres(id).is_some();
+            scope.tv &= tv_genre(id).is_some();
         }
-        
-        if let Some(rating) = min_rating {
+
+        if let Some(rating) = filters.min_rating {
             query_params.push(("vote_average.gte", rating.to_string()));
         }
-        
-        query_params.push(("sort_by", sort_by.to_string()));
-        query_params.push(("page", page.to_string()));
+
+        if let Some(network) = filters.network {
+            query_params.push(("with_networks", network.to_string()));
+            // Networks only exist on the TV side.
+            scope.movies = false;
+        }
+
+        if let Some(company) = filters.company {
+            query_params.push(("with_companies", company.to_string()));
+        }
+
         query_params.push(("include_adult", "false".to_string()));
+        query_params.push(("watch_region", self.region.clone()));
+
+        Ok((query_params, scope, genre))
+    }
+
+    async fn discover(
+        &self,
+        media_type: &str,
+        shared_params: &[(&str, String)],
+        genre: Option<i64>,
+        year: Option<i32>,
+        sort_by: &str,
+        page: i32,
+    ) -> anyhow::Result<SearchResponse> {
+        let url = format!("{}/discover/{}", TMDB_BASE_URL, media_type);
+        let mut query_params = shared_params.to_vec();
+        query_params.push(("page", page.to_string()));
+
+        if media_type == "tv" {
+            if let Some(id) = genre.and_then(tv_genre) {
+                query_params.push(("with_genres", id.to_string()));
+            }
+            if let Some(y) = year {
+                query_params.push(("first_air_date_year", y.to_string()));
+            }
+            let sort_by = match sort_by {
+                "release_date.desc" | "primary_release_date.desc" => "first_air_date.desc",
+                "revenue.desc" => "popularity.desc",
+                other => other,
+            };
+            query_params.push(("sort_by", sort_by.to_string()));
+        } else {
+            if let Some(ids) = genre.and_then(movie_genres) {
+                query_params.push(("with_genres", ids));
+            }
+            if let Some(y) = year {
+                query_params.push(("year", y.to_string()));
+                query_params.push(("primary_release_year", y.to_string()));
+            }
+            query_params.push(("sort_by", sort_by.to_string()));
+            query_params.push(("region", self.region.clone()));
+        }
         
-        let response = self
-            .client
+        let request = self
             .get(&url)
-            .header("Authorization", self.auth_header())
-            .query(&query_params)
-            .send()
-            .await?;
+            .query(&query_params);
+        let response = self.send(request).await?;
 
         if !response.status().is_success() {
-            let error_text = response.text().await?;
+            let error_text = response.text();
             error!("TMDB advanced search error: {}", error_text);
-            return Err(anyhow::anyhow!("TMDB API error: {}", error_text));
+            return Err(TmdbError::from_status(response.status(), error_text).into());
         }
 
-        let search_results: SearchResponse = response.json().await?;
+        let mut search_results: SearchResponse = response.json()?;
+        for item in &mut search_results.results {
+            item.media_type = media_type.to_string();
+        }
         Ok(search_results)
     }
 
-    async fn search_person(&self, name: &str) -> anyhow::Result<i64> {
+    async fn search_keyword(&self, name: &str) -> anyhow::Result<i64> {
+        let url = format!("{}/search/keyword", TMDB_BASE_URL);
+
+        let request = self
+            .get(&url)
+            .query(&[("query", name.trim())]);
+        let response = self.send(request).await?;
+
+        if !response.status().is_success() {
+            return Ok(0);
+        }
+
+        #[derive(Debug, Deserialize)]
+        struct KeywordResponse {
+            pub results: Vec<Keyword>,
+        }
+
+        let keyword_results: KeywordResponse = response.json()?;
+        Ok(keyword_results.results.first().map(|k| k.id).unwrap_or(0))
+    }
+
+    pub async fn get_keyword(&self, id: i64) -> anyhow::Result<Keyword> {
+        let url = format!("{}/keyword/{}", TMDB_BASE_URL, id);
+
+        let response = self.send(self.get(&url)).await?;
+
+        if !response.status().is_success() {
+            return Err(anyhow::anyhow!("Failed to fetch keyword"));
+        }
+
+        Ok(response.json()?)
+    }
+
+    pub async fn discover_by_keyword(&self, keyword_id: i64, page: i32) -> anyhow::Result<SearchResponse> {
+        self.discover_with("movie", "with_keywords", keyword_id, page).await
+    }
+
+    /// TV networks such as HBO; only shows are listed.
+    pub async fn get_network(&self, id: i64) -> anyhow::Result<Organization> {
+        self.get_organization("network", id).await
+    }
+
+    pub async fn get_company(&self, id: i64) -> anyhow::Result<Organization> {
+        self.get_organization("company", id).await
+    }
+
+    async fn get_organization(&self, kind: &str, id: i64) -> anyhow::Result<Organization> {
+        let url = format!("{}/{}/{}", TMDB_BASE_URL, kind, id);
+
+        let response = self.send(self.get(&url)).await?;
+
+        if !response.status().is_success() {
+            return Err(anyhow::anyhow!("Failed to fetch {}", kind));
+        }
+
+        Ok(response.json()?)
+    }
+
+    /// Japanese animation, movies and shows interleaved. TMDB has no anime
+    /// genre, so this combines Animation with the `anime` keyword.
+    pub async fn discover_anime(&self, page: i32) -> anyhow::Result<SearchResponse> {
+        let (movies, shows) = tokio::try_join!(
+            self.discover_anime_for("movie", page),
+            self.discover_anime_for("tv", page)
+        )?;
+        Ok(SearchResponse {
+            page,
+            total_pages: movies.total_pages.max(shows.total_pages),
+            total_results: movies.total_results + shows.total_results,
+            results: interleave_results(movies.results, shows.results),
+        })
+    }
+
+    async fn discover_anime_for(&self, media_type: &str, page: i32) -> anyhow::Result<SearchResponse> {
+        let url = format!("{}/discover/{}", TMDB_BASE_URL, media_type);
+
+        let query_params = [
+            ("with_genres", ANIMATION_GENRE_ID.to_string()),
+            ("with_keywords", ANIME_KEYWORD_ID.to_string()),
+            ("sort_by", "popularity.desc".to_string()),
+            ("page", page.to_string()),
+            ("include_adult", "false".to_string()),
+        ];
+        let response = self.send(self.get(&url).query(&query_params)).await?;
+
+        if !response.status().is_success() {
+            let error_text = response.text();
+            error!("TMDB anime discover error: {}", error_text);
+            return Err(TmdbError::from_status(response.status(), error_text).into());
+        }
+
+        let mut results: SearchResponse = response.json()?;
+        for item in &mut results.results {
+            item.media_type = media_type.to_string();
+        }
+        Ok(results)
+    }
+
+    pub async fn discover_by_network(&self, network_id: i64, page: i32) -> anyhow::Result<SearchResponse> {
+        self.discover_with("tv", "with_networks", network_id, page).await
+    }
+
+    pub async fn discover_by_company(
+        &self,
+        company_id: i64,
+        media_type: &str,
+        page: i32,
+    ) -> anyhow::Result<SearchResponse> {
+        self.discover_with(media_type, "with_companies", company_id, page).await
+    }
+
+    /// Popular titles matching a single discover filter such as `with_keywords`.
+    async fn discover_with(
+        &self,
+        media_type: &str,
+        filter: &str,
+        id: i64,
+        page: i32,
+    ) -> anyhow::Result<SearchResponse> {
+        let url = format!("{}/discover/{}", TMDB_BASE_URL, media_type);
+
+        let mut query_params = vec![
+            (filter, id.to_string()),
+            ("sort_by", "popularity.desc".to_string()),
+            ("page", page.to_string()),
+            ("include_adult", "false".to_string()),
+        ];
+        if media_type == "movie" {
+            query_params.push(("region", self.region.clone()));
+        }
+        let response = self.send(self.get(&url).query(&query_params)).await?;
+
+        if !response.status().is_success() {
+            let error_text = response.text();
+            error!("TMDB {} discover error: {}", filter, error_text);
+            return Err(TmdbError::from_status(response.status(), error_text).into());
+        }
+
+        let mut results: SearchResponse = response.json()?;
+        for item in &mut results.results {
+            item.media_type = media_type.to_string();
+        }
+        Ok(results)
+    }
+
+    /// The id of the best match for a person's name, or 0 if none.
+    pub async fn search_person(&self, name: &str) -> anyhow::Result<i64> {
         let url = format!("{}/search/person", TMDB_BASE_URL);
         
-        let response = self
-            .client
+        let request = self
             .get(&url)
-            .header("Authorization", self.auth_header())
             .query(&[
                 ("query", name),
                 ("include_adult", "false"),
-            ])
-            .send()
-            .await?;
+            ]);
+        let response = self.send(request).await?;
 
         if !response.status().is_success() {
             return Ok(0);
@@ -149,7 +737,6 @@ impl TmdbClient {
         #[derive(Debug, Deserialize)]
         struct PersonResult {
             pub id: i64,
-            pub name: String,
         }
         
         #[derive(Debug, Deserialize)]
@@ -157,32 +744,56 @@ impl TmdbClient {
             pub results: Vec<PersonResult>,
         }
 
-        let person_results: PersonResponse = response.json().await?;
+        let person_results: PersonResponse = response.json()?;
         Ok(person_results.results.first().map(|p| p.id).unwrap_or(0))
     }
 
-    pub async fn get_genres(&self) -> anyhow::Result<Vec<Genre>> {
-        let url = format!("{}/genre/movie/list", TMDB_BASE_URL);
+    /// Movie and TV genres merged by id, each tagged with the media types it applies to.
+    pub async fn get_genres(&self) -> anyhow::Result<Vec<MediaGenre>> {
+        let movie_genres = self.get_genre_list("movie").await?;
+        let tv_genres = self.get_genre_list("tv").await?;
+
+        let mut merged: Vec<MediaGenre> = Vec::new();
+        for (media_type, genres) in [("movie", movie_genres), ("tv", tv_genres)] {
+            for genre in genres {
+                match merged.iter_mut().find(|g| g.id == genre.id) {
+                    Some(existing) => existing.media_types.push(media_type.to_string()),
+                    None => merged.push(MediaGenre {
+                        id: genre.id,
+                        name: genre.name,
+                        media_types: vec![media_type.to_string()],
+                    }),
+                }
+            }
+        }
+
+        merged.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(merged)
+    }
+
+    async fn get_genre_list(&self, media_type: &str) -> anyhow::Result<Vec<Genre>> {
+        let url = format!("{}/genre/{}/list", TMDB_BASE_URL, media_type);
         
         #[derive(Debug, Deserialize)]
         struct GenreResponse {
             pub genres: Vec<Genre>,
         }
 
-        let response = self
-            .client
-            .get(&url)
-            .header("Authorization", self.auth_header())
-            .send()
-            .await?;
+        let response = self.send(self.get(&url)).await?;
+
+        if !response.status().is_success() {
+            let error_text = response.text();
+            error!("TMDB genre list error: {}", error_text);
+            return Err(TmdbError::from_status(response.status(), error_text).into());
+        }
 
-        let genre_response: GenreResponse = response.json().await?;
+        let genre_response: GenreResponse = response.json()?;
         Ok(genre_response.genres)
     }
 
     pub async fn get_trending_searches(&self) -> Vec<SearchResult> {
-        let trending_movies = self.get_trending("movie", "day").await.ok().map(|r| r.results).unwrap_or_default();
-        let trending_tv = self.get_trending("tv", "day").await.ok().map(|r| r.results).unwrap_or_default();
+        let trending_movies = self.get_trending("movie", "day", 1).await.ok().map(|r| r.results).unwrap_or_default();
+        let trending_tv = self.get_trending("tv", "day", 1).await.ok().map(|r| r.results).unwrap_or_default();
         
         let mut combined = trending_movies;
         combined.extend(trending_tv);
@@ -193,92 +804,655 @@ impl TmdbClient {
     pub async fn get_movie(&self, id: i64) -> anyhow::Result<MovieDetail> {
         let url = format!("{}/movie/{}", TMDB_BASE_URL, id);
         
-        let response = self
-            .client
+        let request = self
             .get(&url)
-            .header("Authorization", self.auth_header())
-            .query(&[("append_to_response", "credits,similar")])
-            .send()
-            .await?;
+            .query(&[("append_to_response", "credits,similar,recommendations,release_dates,external_ids")]);
+        let response = self.send(request).await?;
 
         if !response.status().is_success() {
-            return Err(anyhow::anyhow!("Failed to fetch movie details"));
+            return Err(TmdbError::from_status(response.status(), "Failed to fetch movie details".to_string()).into());
         }
 
-        let movie: MovieDetail = response.json().await?;
+        let mut movie: MovieDetail = response.json()?;
+        movie.certification = movie
+            .release_dates
+            .as_ref()
+            .and_then(|dates| dates.certification(&self.region));
         Ok(movie)
     }
 
     pub async fn get_tv_show(&self, id: i64) -> anyhow::Result<TvShowDetail> {
         let url = format!("{}/tv/{}", TMDB_BASE_URL, id);
         
-        let response = self
-            .client
+        let request = self
             .get(&url)
-            .header("Authorization", self.auth_header())
-            .query(&[("append_to_response", "credits,similar")])
-            .send()
-            .await?;
+            .query(&[("append_to_response", "credits,similar,recommendations,content_ratings,external_ids")]);
+        let response = self.send(request).await?;
 
         if !response.status().is_success() {
-            return Err(anyhow::anyhow!("Failed to fetch TV show details"));
+            return Err(TmdbError::from_status(response.status(), "Failed to fetch TV show details".to_string()).into());
         }
 
-        let show: TvShowDetail = response.json().await?;
+        let mut show: TvShowDetail = response.json()?;
+        show.certification = show
+            .content_ratings
+            .as_ref()
+            .and_then(|ratings| ratings.certification(&self.region));
         Ok(show)
     }
 
-    pub async fn get_popular_movies(&self, page: i32) -> anyhow::Result<MovieListResponse> {
-        let url = format!("{}/movie/popular", TMDB_BASE_URL);
-        
-        let response = self
-            .client
-            .get(&url)
-            .header("Authorization", self.auth_header())
-            .query(&[("page", page.to_string())])
-            .send()
-            .await?;
+    /// Season details with its episodes, cached per show and season. `None`
+    /// when TMDB has no such season.
+    pub async fn get_season(&self, tv_id: i64, season_number: i64) -> anyhow::Result<Option<Arc<SeasonDetail>>> {
+        let key = (tv_id, season_number);
+        let cached = self
+            .seasons
+            .lock()
+            .unwrap()
+            .get(&key)
+            .filter(|(stored_at, _)| stored_at.elapsed() < SEASON_CACHE_TTL)
+            .map(|(_, season)| season.clone());
+        self.metrics.record_cache("season", cached.is_some());
+        if let Some(season) = cached {
+            return Ok(Some(season));
+        }
+
+        let url = format!("{}/tv/{}/season/{}", TMDB_BASE_URL, tv_id, season_number);
+        let response = self.send(self.get(&url)).await?;
+
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        if !response.status().is_success() {
+            let error_text = response.text();
+            error!("TMDB season error: {}", error_text);
+            return Err(TmdbError::from_status(response.status(), error_text).into());
+        }
 
-        Ok(response.json().await?)
+        let season = Arc::new(response.json::<SeasonDetail>()?);
+        let mut cache = self.seasons.lock().unwrap();
+        if cache.len() >= SEASON_CACHE_MAX {
+            cache.retain(|_, (stored_at, _)| stored_at.elapsed() < SEASON_CACHE_TTL);
+            if cache.len() >= SEASON_CACHE_MAX {
+                cache.clear();
+            }
+        }
+        cache.insert(key, (Instant::now(), season.clone()));
+        Ok(Some(season))
     }
 
-    pub async fn get_popular_tv(&self, page: i32) -> anyhow::Result<TvListResponse> {
-        let url = format!("{}/tv/popular", TMDB_BASE_URL);
-        
-        let response = self
-            .client
-            .get(&url)
-            .header("Authorization", self.auth_header())
-            .query(&[("page", page.to_string())])
-            .send()
-            .await?;
+    /// A title's age rating for the configured region, without fetching the
+    /// full details. Cached per title, including titles that have no rating.
+    pub async fn get_certification(&self, media_type: &str, id: i64) -> anyhow::Result<Option<Certification>> {
+        let key = (media_type.to_string(), id);
+        let cached = self
+            .certifications
+            .lock()
+            .unwrap()
+            .get(&key)
+            .filter(|(stored_at, _)| stored_at.elapsed() < CERTIFICATION_CACHE_TTL)
+            .map(|(_, certification)| certification.clone());
+        self.metrics.record_cache("certification", cached.is_some());
+        if let Some(certification) = cached {
+            return Ok(certification);
+        }
+
+        let certification = match media_type {
+            "movie" => {
+                let url = format!("{}/movie/{}/release_dates", TMDB_BASE_URL, id);
+                let response = self.send(self.get(&url)).await?;
+                if !response.status().is_success() {
+                    return Err(anyhow::anyhow!("Failed to fetch release dates"));
+                }
+                response.json::<ReleaseDates>()?.certification(&self.region)
+            }
+            "tv" => {
+                let url = format!("{}/tv/{}/content_ratings", TMDB_BASE_URL, id);
+                let response = self.send(self.get(&url)).await?;
+                if !response.status().is_success() {
+                    return Err(anyhow::anyhow!("Failed to fetch content ratings"));
+                }
+                response.json::<ContentRatings>()?.certification(&self.region)
+            }
+            _ => return Ok(None),
+        };
 
-        Ok(response.json().await?)
+        let mut cache = self.certifications.lock().unwrap();
+        if cache.len() >= CERTIFICATION_CACHE_MAX {
+            cache.retain(|_, (stored_at, _)| stored_at.elapsed() < CERTIFICATION_CACHE_TTL);
+            if cache.len() >= CERTIFICATION_CACHE_MAX {
+                cache.clear();
+            }
+        }
+        cache.insert(key, (Instant::now(), certification.clone()));
+        Ok(certification)
     }
 
-    pub async fn get_trending(&self, media_type: &str, time_window: &str) -> anyhow::Result<SearchResponse> {
-        let url = format!("{}/trending/{}/{}", TMDB_BASE_URL, media_type, time_window);
-        
-        let response = self
-            .client
-            .get(&url)
-            .header("Authorization", self.auth_header())
-            .send()
-            .await?;
+    pub async fn get_episode_title(&self, tv_id: i64, season_number: i64, episode_number: i64) -> Option<String> {
+        let season = self.get_season(tv_id, season_number).await.ok()??;
+        season
+            .episodes
+            .iter()
+            .find(|e| e.episode_number == episode_number)
+            .map(|e| e.name.clone())
+            .filter(|name| !name.is_empty())
+    }
 
-        Ok(response.json().await?)
+    pub async fn get_popular_movies(&self, page: i32) -> anyhow::Result<MovieListResponse> {
+        self.get_movie_list("popular", page).await
     }
 
-    pub fn get_poster_url(&self, path: Option<&str>, size: &str) -> Option<String> {
-        path.map(|p| format!("{}/{}{}", TMDB_IMAGE_BASE, size, p))
+    /// Movies currently in theaters in the configured region.
+    pub async fn get_now_playing(&self, page: i32) -> anyhow::Result<MovieListResponse> {
+        self.get_movie_list("now_playing", page).await
     }
 
-    pub fn get_backdrop_url(&self, path: Option<&str>, size: &str) -> Option<String> {
-        path.map(|p| format!("{}/{}{}", TMDB_IMAGE_BASE, size, p))
+    /// Movies releasing soon in the configured region.
+    pub async fn get_upcoming(&self, page: i32) -> anyhow::Result<MovieListResponse> {
+        self.get_movie_list("upcoming", page).await
     }
-}
 
-fn get_genre_id(genre_name: &str) -> i64 {
+    /// Highest rated movies on TMDB.
+    pub async fn get_top_rated_movies(&self, page: i32) -> anyhow::Result<MovieListResponse> {
+        self.get_movie_list("top_rated", page).await
+    }
+
+    async fn get_movie_list(&self, list: &str, page: i32) -> anyhow::Result<MovieListResponse> {
+        let url = format!("{}/movie/{}", TMDB_BASE_URL, list);
+
+        let request = self
+            .get(&url)
+            .query(&[("page", page.to_string()), ("region", self.region.clone())]);
+        let response = self.send(request).await?;
+
+        if !response.status().is_success() {
+            let error_text = response.text();
+            error!("TMDB {} error: {}", list, error_text);
+            return Err(TmdbError::from_status(response.status(), error_text).into());
+        }
+
+        let mut movies: MovieListResponse = response.json()?;
+        for item in &mut movies.results {
+            item.media_type = "movie".to_string();
+        }
+        Ok(movies)
+    }
+
+    pub async fn get_popular_tv(&self, page: i32) -> anyhow::Result<TvListResponse> {
+        self.get_tv_list("popular", page).await
+    }
+
+    /// Highest rated TV shows on TMDB.
+    pub async fn get_top_rated_tv(&self, page: i32) -> anyhow::Result<TvListResponse> {
+        self.get_tv_list("top_rated", page).await
+    }
+
+    async fn get_tv_list(&self, list: &str, page: i32) -> anyhow::Result<TvListResponse> {
+        let url = format!("{}/tv/{}", TMDB_BASE_URL, list);
+
+        let request = self
+            .get(&url)
+            .query(&[("page", page.to_string())]);
+        let response = self.send(request).await?;
+
+        if !response.status().is_success() {
+            let error_text = response.text();
+            error!("TMDB {} tv error: {}", list, error_text);
+            return Err(TmdbError::from_status(response.status(), error_text).into());
+        }
+
+        let mut shows: TvListResponse = response.json()?;
+        for item in &mut shows.results {
+            item.media_type = "tv".to_string();
+        }
+        Ok(shows)
+    }
+
+    pub async fn get_trending(&self, media_type: &str, time_window: &str, page: i32) -> anyhow::Result<SearchResponse> {
+        let url = format!("{}/trending/{}/{}", TMDB_BASE_URL, media_type, time_window);
+        
+        let request = self
+            .get(&url)
+            .query(&[("region", self.region.clone()), ("page", page.to_string())]);
+        let response = self.send(request).await?;
+
+        Ok(response.json()?)
+    }
+
+    /// People trending on TMDB, for the "Popular People" row.
+    pub async fn get_trending_people(&self, time_window: &str, page: i32) -> anyhow::Result<SearchResponse> {
+        let mut people = self.get_trending("person", time_window, page).await?;
+        for person in &mut people.results {
+            person.media_type = "person".to_string();
+        }
+        Ok(people)
+    }
+
+    /// Person-only search, for clients that want actors rather than titles.
+    pub async fn search_people(&self, query: &str, page: i32) -> anyhow::Result<SearchResponse> {
+        let url = format!("{}/search/person", TMDB_BASE_URL);
+
+        let request = self
+            .get(&url)
+            .query(&[("query", query), ("page", &page.to_string()), ("include_adult", "false")]);
+        let response = self.send(request).await?;
+
+        if !response.status().is_success() {
+            let error_text = response.text();
+            error!("TMDB person search error: {}", error_text);
+            return Err(TmdbError::from_status(response.status(), error_text).into());
+        }
+
+        let mut people: SearchResponse = response.json()?;
+        for person in &mut people.results {
+            person.media_type = "person".to_string();
+        }
+        Ok(people)
+    }
+
+    pub async fn get_recommendations(&self, media_type: &str, id: i64, page: i32) -> anyhow::Result<SearchResponse> {
+        let url = format!("{}/{}/{}/recommendations", TMDB_BASE_URL, media_type, id);
+
+        let request = self
+            .get(&url)
+            .query(&[("page", page.to_string())]);
+        let response = self.send(request).await?;
+
+        if !response.status().is_success() {
+            let error_text = response.text();
+            error!("TMDB recommendations error: {}", error_text);
+            return Err(TmdbError::from_status(response.status(), error_text).into());
+        }
+
+        let mut recommendations: SearchResponse = response.json()?;
+        for item in &mut recommendations.results {
+            if item.media_type.is_empty() {
+                item.media_type = media_type.to_string();
+            }
+        }
+        Ok(recommendations)
+    }
+
+    /// Whether TMDB still has a title under this id; `false` on a 404.
+    pub async fn title_exists(&self, media_type: &str, id: i64) -> anyhow::Result<bool> {
+        let url = format!("{}/{}/{}", TMDB_BASE_URL, media_type, id);
+        let response = self.send(self.get(&url)).await?;
+
+        match response.status() {
+            status if status.is_success() => Ok(true),
+            reqwest::StatusCode::NOT_FOUND => Ok(false),
+            status => Err(TmdbError::from_status(status, status.to_string()).into()),
+        }
+    }
+
+    /// Ids of titles TMDB changed (including merges and deletions) in the
+    /// last `days` days. TMDB caps the window at 14 days.
+    pub async fn get_changed_ids(&self, media_type: &str, days: i64) -> anyhow::Result<Vec<i64>> {
+        let url = format!("{}/{}/changes", TMDB_BASE_URL, media_type);
+        let end = chrono::Utc::now().date_naive();
+        let start = end - chrono::Duration::days(days.clamp(1, 14));
+
+        #[derive(Debug, Deserialize)]
+        struct ChangesResponse {
+            results: Vec<ChangedId>,
+            total_pages: i32,
+        }
+
+        #[derive(Debug, Deserialize)]
+        struct ChangedId {
+            id: i64,
+        }
+
+        let mut ids = Vec::new();
+        let mut page = 1;
+        loop {
+            let request = self.get(&url).query(&[
+                ("start_date", start.to_string()),
+                ("end_date", end.to_string()),
+                ("page", page.to_string()),
+            ]);
+            let response = self.send(request).await?;
+
+            if !response.status().is_success() {
+                let error_text = response.text();
+                error!("TMDB changes error: {}", error_text);
+                return Err(TmdbError::from_status(response.status(), error_text).into());
+            }
+
+            let changes: ChangesResponse = response.json()?;
+            ids.extend(changes.results.into_iter().map(|c| c.id));
+            if page >= changes.total_pages.min(TMDB_MAX_PAGE) {
+                break;
+            }
+            page += 1;
+        }
+
+        Ok(ids)
+    }
+
+    /// One page of a public TMDB list, items tagged with `media_type`.
+    pub async fn get_list(&self, list_id: &str, page: i32) -> anyhow::Result<TmdbList> {
+        let url = format!("{}/list/{}", TMDB_BASE_URL, list_id);
+
+        let request = self
+            .get(&url)
+            .query(&[("page", page.to_string())]);
+        let response = self.send(request).await?;
+
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Err(anyhow::anyhow!("TMDB list {} not found or not public", list_id));
+        }
+        if !response.status().is_success() {
+            let error_text = response.text();
+            error!("TMDB list error: {}", error_text);
+            return Err(TmdbError::from_status(response.status(), error_text).into());
+        }
+
+        Ok(response.json()?)
+    }
+
+    pub async fn find_by_external_id(&self, imdb_id: &str) -> anyhow::Result<FindResponse> {
+        let url = format!("{}/find/{}", TMDB_BASE_URL, imdb_id);
+
+        let request = self
+            .get(&url)
+            .query(&[("external_source", "imdb_id")]);
+        let response = self.send(request).await?;
+
+        if !response.status().is_success() {
+            let error_text = response.text();
+            error!("TMDB find error: {}", error_text);
+            return Err(TmdbError::from_status(response.status(), error_text).into());
+        }
+
+        let mut found: FindResponse = response.json()?;
+        for item in &mut found.movie_results {
+            item.media_type = "movie".to_string();
+        }
+        for item in &mut found.tv_results {
+            item.media_type = "tv".to_string();
+        }
+        Ok(found)
+    }
+
+    pub async fn get_watch_providers(
+        &self,
+        media_type: &str,
+        id: i64,
+        region: &str,
+    ) -> anyhow::Result<Option<WatchProviders>> {
+        let url = format!("{}/{}/{}/watch/providers", TMDB_BASE_URL, media_type, id);
+
+        let response = self.send(self.get(&url)).await?;
+
+        if !response.status().is_success() {
+            let error_text = response.text();
+            error!("TMDB watch providers error: {}", error_text);
+            return Err(TmdbError::from_status(response.status(), error_text).into());
+        }
+
+        let mut providers: WatchProvidersResponse = response.json()?;
+        Ok(providers.results.remove(&region.to_uppercase()))
+    }
+
+    /// Starts TMDB's v4 user sign-in. The user approves the returned token on
+    /// themoviedb.org and is sent back to `redirect_to`.
+    pub async fn create_request_token(&self, redirect_to: &str) -> anyhow::Result<String> {
+        #[derive(Debug, Deserialize)]
+        struct RequestTokenResponse {
+            request_token: String,
+        }
+
+        let url = format!("{}/auth/request_token", TMDB_V4_BASE_URL);
+        let request = self
+            .client
+            .post(&url)
+            .json(&serde_json::json!({ "redirect_to": redirect_to }));
+        let response = self.send(request).await?;
+
+        if !response.status().is_success() {
+            return Err(anyhow::anyhow!("TMDB request token error: {}", response.text()));
+        }
+
+        Ok(response.json::<RequestTokenResponse>()?.request_token)
+    }
+
+    /// Exchanges an approved request token for the user's access token.
+    pub async fn create_access_token(&self, request_token: &str) -> anyhow::Result<AccountAccess> {
+        let url = format!("{}/auth/access_token", TMDB_V4_BASE_URL);
+        let request = self
+            .client
+            .post(&url)
+            .json(&serde_json::json!({ "request_token": request_token }));
+        let response = self.send(request).await?;
+
+        if !response.status().is_success() {
+            return Err(anyhow::anyhow!("TMDB access token error: {}", response.text()));
+        }
+
+        Ok(response.json()?)
+    }
+
+    /// Signs the user's access token out of TMDB.
+    pub async fn revoke_access_token(&self, access_token: &str) -> anyhow::Result<()> {
+        let url = format!("{}/auth/access_token", TMDB_V4_BASE_URL);
+        let request = self
+            .client
+            .delete(&url)
+            .json(&serde_json::json!({ "access_token": access_token }));
+        let response = self.send(request).await?;
+
+        if !response.status().is_success() {
+            return Err(anyhow::anyhow!("TMDB revoke error: {}", response.text()));
+        }
+        Ok(())
+    }
+
+    /// Writes still go through v3, which needs a session converted from the
+    /// v4 access token and the numeric v3 account id.
+    pub async fn create_v3_session(&self, access_token: &str) -> anyhow::Result<(String, i64)> {
+        #[derive(Debug, Deserialize)]
+        struct SessionResponse {
+            session_id: String,
+        }
+
+        #[derive(Debug, Deserialize)]
+        struct AccountResponse {
+            id: i64,
+        }
+
+        let url = format!("{}/authentication/session/convert/4", TMDB_BASE_URL);
+        let request = self
+            .client
+            .post(&url)
+            .json(&serde_json::json!({ "access_token": access_token }));
+        let response = self.send(request).await?;
+        if !response.status().is_success() {
+            return Err(anyhow::anyhow!("TMDB session error: {}", response.text()));
+        }
+        let session_id = response.json::<SessionResponse>()?.session_id;
+
+        let url = format!("{}/account", TMDB_BASE_URL);
+        let response = self
+            .send(self.get(&url).query(&[("session_id", &session_id)]))
+            .await?;
+        if !response.status().is_success() {
+            return Err(anyhow::anyhow!("TMDB account error: {}", response.text()));
+        }
+        let account_id = response.json::<AccountResponse>()?.id;
+
+        Ok((session_id, account_id))
+    }
+
+    /// One page of a user's watchlist or rated titles, read with their own token.
+    pub async fn get_account_titles(
+        &self,
+        access: &AccountAccess,
+        list: AccountList,
+        media_type: &str,
+        page: i32,
+    ) -> anyhow::Result<AccountTitlesPage> {
+        let url = format!(
+            "{}/account/{}/{}/{}",
+            TMDB_V4_BASE_URL,
+            access.account_id,
+            media_type,
+            list.as_str()
+        );
+        let request = self
+            .get(&url)
+            .query(&[("page", page.to_string())])
+            .bearer_auth(&access.access_token);
+        let response = self.send_as_user(request).await?;
+
+        if !response.status().is_success() {
+            return Err(anyhow::anyhow!("TMDB account list error: {}", response.text()));
+        }
+
+        let mut titles: AccountTitlesPage = response.json()?;
+        for item in &mut titles.results {
+            item.media_type = media_type.to_string();
+        }
+        Ok(titles)
+    }
+
+    pub async fn set_watchlist(
+        &self,
+        session_id: &str,
+        v3_account_id: i64,
+        media_type: &str,
+        tmdb_id: i64,
+        on_watchlist: bool,
+    ) -> anyhow::Result<()> {
+        let url = format!("{}/account/{}/watchlist", TMDB_BASE_URL, v3_account_id);
+        let request = self
+            .client
+            .post(&url)
+            .query(&[("session_id", session_id)])
+            .json(&serde_json::json!({
+                "media_type": media_type,
+                "media_id": tmdb_id,
+                "watchlist": on_watchlist,
+            }));
+        let response = self.send(request).await?;
+
+        if !response.status().is_success() {
+            return Err(anyhow::anyhow!("TMDB watchlist error: {}", response.text()));
+        }
+        Ok(())
+    }
+
+    /// Rates a title on TMDB; `value` runs from 0.5 to 10 in half steps.
+    pub async fn rate_title(
+        &self,
+        session_id: &str,
+        media_type: &str,
+        tmdb_id: i64,
+        value: f64,
+    ) -> anyhow::Result<()> {
+        let url = format!("{}/{}/{}/rating", TMDB_BASE_URL, media_type, tmdb_id);
+        let value = ((value * 2.0).round() / 2.0).clamp(0.5, 10.0);
+        let request = self
+            .client
+            .post(&url)
+            .query(&[("session_id", session_id)])
+            .json(&serde_json::json!({ "value": value }));
+        let response = self.send(request).await?;
+
+        if !response.status().is_success() {
+            return Err(anyhow::anyhow!("TMDB rating error: {}", response.text()));
+        }
+        Ok(())
+    }
+
+    pub fn get_poster_url(&self, path: Option<&str>, size: &str) -> Option<String> {
+        path.map(|p| format!("{}/{}{}", TMDB_IMAGE_BASE, size, p))
+    }
+
+    pub fn get_backdrop_url(&self, path: Option<&str>, size: &str) -> Option<String> {
+        path.map(|p| format!("{}/{}{}", TMDB_IMAGE_BASE, size, p))
+    }
+}
+
+/// Walks TMDB's `page`/`total_pages` from `start_page`, calling `fetch_page`
+/// until `limit` results are collected or the listing runs out.
+///
+/// ```ignore
+/// let all = fetch_pages(1, 100, |page| tmdb.search("alien", page)).await?;
+/// ```
+pub async fn fetch_pages<F, Fut>(start_page: i32, limit: usize, fetch_page: F) -> anyhow::Result<SearchResponse>
+where
+    F: Fn(i32) -> Fut,
+    Fut: Future<Output = anyhow::Result<SearchResponse>>,
+{
+    let start_page = start_page.max(1);
+    let mut combined = SearchResponse {
+        page: start_page,
+        results: Vec::new(),
+        total_pages: 0,
+        total_results: 0,
+    };
+
+    let mut page = start_page;
+    while combined.results.len() < limit && page <= TMDB_MAX_PAGE {
+        let response = fetch_page(page).await?;
+        combined.total_pages = response.total_pages;
+        combined.total_results = response.total_results;
+        if response.results.is_empty() {
+            break;
+        }
+        combined.results.extend(response.results);
+        if page >= response.total_pages {
+            break;
+        }
+        page += 1;
+    }
+
+    combined.results.truncate(limit);
+    Ok(combined)
+}
+
+/// Accepts a bare list id or a list URL such as
+/// `https://www.themoviedb.org/list/8136-best-picture-winners`.
+pub fn parse_list_id(input: &str) -> Option<String> {
+    let input = input.trim();
+    let rest = match input.find("/list/") {
+        Some(start) => &input[start + "/list/".len()..],
+        None => input,
+    };
+    let digits: String = rest.chars().take_while(|c| c.is_ascii_digit()).collect();
+    (!digits.is_empty()).then_some(digits)
+}
+
+/// Extracts an IMDb title id (`tt1234567`) from a bare id or an IMDb URL:
+/// the first `tt` followed by at least 7 digits, so the `tt` in `https` or
+/// a title doesn't hide the id after it.
+pub fn parse_imdb_id(input: &str) -> Option<String> {
+    input.match_indices("tt").find_map(|(start, _)| {
+        let digits: String = input[start + 2..]
+            .chars()
+            .take_while(|c| c.is_ascii_digit())
+            .collect();
+        (digits.len() >= 7).then(|| format!("tt{}", digits))
+    })
+}
+
+/// Alternates movie and TV results so neither list buries the other, keeping
+/// each list's own sort order.
+pub fn interleave_results(movies: Vec<SearchResult>, shows: Vec<SearchResult>) -> Vec<SearchResult> {
+    let mut results = Vec::with_capacity(movies.len() + shows.len());
+    let mut movie_iter = movies.into_iter();
+    let mut show_iter = shows.into_iter();
+    loop {
+        match (movie_iter.next(), show_iter.next()) {
+            (None, None) => break,
+            (movie, show) => results.extend(movie.into_iter().chain(show)),
+        }
+    }
+    results
+}
+
+fn get_genre_id(genre_name: &str) -> i64 {
     let genre_map: Vec<(&str, i64)> = vec![
         ("action", 28),
         ("adventure", 12),
@@ -298,6 +1472,11 @@ fn get_genre_id(genre_name: &str) -> i64 {
         ("thriller", 53),
         ("war", 10752),
         ("western", 37),
+        ("kids", 10762),
+        ("news", 10763),
+        ("reality", 10764),
+        ("soap", 10766),
+        ("talk", 10767),
     ];
     
     let normalized = genre_name.to_lowercase().replace(' ', "-");
@@ -307,6 +1486,171 @@ fn get_genre_id(genre_name: &str) -> i64 {
         .unwrap_or(0)
 }
 
+/// TMDB's movie genre ids.
+const MOVIE_GENRE_IDS: &[i64] = &[
+    28, 12, 16, 35, 80, 99, 18, 10751, 14, 36, 27, 10402, 9648, 10749, 878, 10770, 53, 10752, 37,
+];
+
+/// TMDB's TV genre ids. Some movie genres are merged on the TV side, such as
+/// Action & Adventure, and some have no TV counterpart at all.
+const TV_GENRE_IDS: &[i64] = &[
+    10759, 16, 35, 80, 99, 18, 10751, 10762, 9648, 10763, 10764, 10765, 10766, 10767, 10768, 37,
+];
+
+/// Movie genres and the merged TV genre that covers them.
+const MERGED_TV_GENRES: &[(i64, i64)] = &[
+    (28, 10759),
+    (12, 10759),
+    (14, 10765),
+    (878, 10765),
+    (10752, 10768),
+];
+
+/// The TV genre matching a movie or TV genre id, if TV has one.
+fn tv_genre(id: i64) -> Option<i64> {
+    if TV_GENRE_IDS.contains(&id) {
+        return Some(id);
+    }
+    MERGED_TV_GENRES
+        .iter()
+        .find(|(movie, _)| *movie == id)
+        .map(|(_, tv)| *tv)
+}
+
+/// The movie genres matching a movie or TV genre id, as a `with_genres`
+/// value; a merged TV genre matches any of the movie genres it covers.
+fn movie_genres(id: i64) -> Option<String> {
+    if MOVIE_GENRE_IDS.contains(&id) {
+        return Some(id.to_string());
+    }
+    let ids: Vec<String> = MERGED_TV_GENRES
+        .iter()
+        .filter(|(_, tv)| *tv == id)
+        .map(|(movie, _)| movie.to_string())
+        .collect();
+    (!ids.is_empty()).then(|| ids.join("|"))
+}
+
+/// Why a TMDB request failed, so API errors can tell a missing title from
+/// an outage.
+#[derive(Debug, thiserror::Error)]
+pub enum TmdbError {
+    /// TMDB couldn't be reached, is failing, or is rate-limiting every key.
+    #[error("TMDB is unavailable: {0}")]
+    Unavailable(String),
+    /// Nothing exists under the requested id.
+    #[error("Not found on TMDB")]
+    NotFound,
+    /// TMDB rejected the request.
+    #[error("TMDB API error: {0}")]
+    Api(String),
+}
+
+impl TmdbError {
+    pub fn from_status(status: reqwest::StatusCode, message: String) -> Self {
+        match status {
+            reqwest::StatusCode::NOT_FOUND => TmdbError::NotFound,
+            reqwest::StatusCode::TOO_MANY_REQUESTS => TmdbError::Unavailable(message),
+            status if status.is_server_error() => TmdbError::Unavailable(message),
+            _ => TmdbError::Api(message),
+        }
+    }
+}
+
+/// Search prefixes that turn a query into a discover filter.
+pub const FILTER_PREFIXES: [&str; 4] = ["genre:", "actor:", "director:", "keyword:"];
+
+/// Whether `q` is a `genre:`, `actor:`, `director:`, or `keyword:` query.
+pub fn is_filter_query(q: &str) -> bool {
+    FILTER_PREFIXES.iter().any(|prefix| q.starts_with(prefix))
+}
+
+/// Filters shared by movie and TV discover. `q` may carry a `genre:`,
+/// `actor:`, `director:`, or `keyword:` prefix.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct DiscoverFilters {
+    #[serde(default)]
+    pub q: String,
+    /// `movie` or `tv`; both when unset.
+    #[serde(default, rename = "type")]
+    pub media_type: Option<String>,
+    #[serde(default)]
+    pub genre: Option<String>,
+    #[serde(default)]
+    pub year: Option<i32>,
+    #[serde(default)]
+    pub min_rating: Option<f64>,
+    #[serde(default)]
+    pub sort_by: Option<String>,
+    /// TMDB network id; limits results to TV.
+    #[serde(default)]
+    pub network: Option<i64>,
+    /// TMDB production company id.
+    #[serde(default)]
+    pub company: Option<i64>,
+    /// Person id already resolved for an `actor:` or `director:` query,
+    /// which saves looking the name up again.
+    #[serde(skip)]
+    pub person: Option<i64>,
+}
+
+/// Which discover listings a set of filters can be answered by.
+#[derive(Debug, Clone, Copy)]
+struct DiscoverScope {
+    movies: bool,
+    tv: bool,
+}
+
+/// Position in the combined discover listing: the next TMDB page of each
+/// media type, with `0` once that listing is exhausted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DiscoverCursor {
+    pub movie_page: i32,
+    pub tv_page: i32,
+}
+
+impl Default for DiscoverCursor {
+    fn default() -> Self {
+        Self {
+            movie_page: 1,
+            tv_page: 1,
+        }
+    }
+}
+
+impl DiscoverCursor {
+    /// Cursors are opaque to clients; the format is `m<page>t<page>`.
+    pub fn parse(cursor: &str) -> Option<Self> {
+        let (movie, tv) = cursor.strip_prefix('m')?.split_once('t')?;
+        let cursor = Self {
+            movie_page: movie.parse().ok()?,
+            tv_page: tv.parse().ok()?,
+        };
+        let in_range = |page: i32| (0..=TMDB_MAX_PAGE).contains(&page);
+        (in_range(cursor.movie_page) && in_range(cursor.tv_page) && cursor.has_more()).then_some(cursor)
+    }
+
+    pub fn encode(&self) -> String {
+        format!("m{}t{}", self.movie_page, self.tv_page)
+    }
+
+    fn has_more(&self) -> bool {
+        self.movie_page > 0 || self.tv_page > 0
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DiscoverPage {
+    pub results: Vec<SearchResult>,
+    /// Combined total TMDB reports for the listings queried.
+    pub total_results: i64,
+    /// Pages in the combined listing. Each page takes the next page of
+    /// both media types, so this is the longer listing's page count.
+    pub total_pages: i32,
+    /// Pass back as `cursor` for the next page; absent on the last page.
+    pub next_cursor: Option<String>,
+}
+
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct SearchResponse {
     pub page: i32,
@@ -350,6 +1694,95 @@ pub struct SearchResult {
     pub original_language: Option<String>,
     #[serde(default)]
     pub origin_country: Option<Vec<String>>,
+    /// Set on `person` results.
+    #[serde(default)]
+    pub profile_path: Option<String>,
+    #[serde(default)]
+    pub known_for_department: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct PersonCredits {
+    #[serde(default)]
+    cast: Vec<SearchResult>,
+    #[serde(default)]
+    crew: Vec<CrewCredit>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CrewCredit {
+    #[serde(flatten)]
+    title: SearchResult,
+    #[serde(default)]
+    job: String,
+}
+
+/// A public TMDB list, one page of items at a time.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct TmdbList {
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub items: Vec<SearchResult>,
+    #[serde(default = "first_page")]
+    pub page: i32,
+    #[serde(default = "first_page")]
+    pub total_pages: i32,
+}
+
+fn first_page() -> i32 {
+    1
+}
+
+/// A typeahead entry: just enough to show and link a title.
+#[derive(Debug, Clone, Serialize)]
+pub struct Suggestion {
+    pub id: i64,
+    pub media_type: String,
+    pub title: String,
+    pub year: Option<i32>,
+}
+
+impl Suggestion {
+    /// People and untitled results are skipped.
+    fn from_result(result: &SearchResult) -> Option<Self> {
+        if result.media_type != "movie" && result.media_type != "tv" {
+            return None;
+        }
+        let title = result.title.as_ref().or(result.name.as_ref())?.clone();
+        let year = result
+            .release_date
+            .as_deref()
+            .or(result.first_air_date.as_deref())
+            .and_then(|date| date.get(..4))
+            .and_then(|year| year.parse().ok());
+        Some(Self {
+            id: result.id,
+            media_type: result.media_type.clone(),
+            title,
+            year,
+        })
+    }
+}
+
+impl SearchResult {
+    /// Animation from Japan. Search results carry no keywords, so origin
+    /// country (or original language for movies) stands in for the
+    /// `anime` keyword used by [`TmdbClient::discover_anime`].
+    pub fn is_anime(&self) -> bool {
+        let animated = self
+            .genre_ids
+            .as_ref()
+            .is_some_and(|ids| ids.contains(&ANIMATION_GENRE_ID));
+        let japanese = self
+            .origin_country
+            .as_ref()
+            .is_some_and(|countries| countries.iter().any(|c| c == "JP"))
+            || self.original_language.as_deref() == Some("ja");
+        animated && japanese
+    }
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -366,6 +1799,22 @@ pub struct MovieDetail {
     pub genres: Vec<Genre>,
     pub credits: Option<Credits>,
     pub similar: Option<SimilarMovies>,
+    #[serde(default)]
+    pub recommendations: Option<Recommendations>,
+    #[serde(default)]
+    pub production_companies: Vec<Organization>,
+    #[serde(default, skip_serializing)]
+    pub release_dates: Option<ReleaseDates>,
+    /// Age rating for the configured region, filled in by `get_movie`.
+    #[serde(default)]
+    pub certification: Option<Certification>,
+    #[serde(default)]
+    pub imdb_id: Option<String>,
+    /// IMDb and Rotten Tomatoes scores, filled in when OMDb is configured.
+    #[serde(default)]
+    pub external_ratings: Option<crate::omdb::ExternalRatings>,
+    #[serde(default)]
+    pub external_ids: Option<ExternalIds>,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -382,8 +1831,204 @@ pub struct TvShowDetail {
     pub vote_count: i64,
     pub genres: Vec<Genre>,
     pub seasons: Vec<SeasonInfo>,
+    #[serde(default)]
+    pub origin_country: Vec<String>,
+    #[serde(default)]
+    pub original_language: Option<String>,
     pub credits: Option<Credits>,
     pub similar: Option<SimilarTvShows>,
+    #[serde(default)]
+    pub recommendations: Option<Recommendations>,
+    #[serde(default)]
+    pub networks: Vec<Organization>,
+    #[serde(default)]
+    pub production_companies: Vec<Organization>,
+    #[serde(default, skip_serializing)]
+    pub content_ratings: Option<ContentRatings>,
+    /// Age rating for the configured region, filled in by `get_tv_show`.
+    #[serde(default)]
+    pub certification: Option<Certification>,
+    #[serde(default)]
+    pub external_ids: Option<ExternalIds>,
+    /// The most recent episode to have aired.
+    #[serde(default)]
+    pub last_episode_to_air: Option<AiredEpisode>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct AiredEpisode {
+    pub season_number: i64,
+    pub episode_number: i64,
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub air_date: Option<String>,
+}
+
+impl TvShowDetail {
+    /// Same test as [`SearchResult::is_anime`], on the full show record.
+    pub fn is_anime(&self) -> bool {
+        let animated = self.genres.iter().any(|g| g.id == ANIMATION_GENRE_ID);
+        let japanese = self.origin_country.iter().any(|c| c == "JP")
+            || self.original_language.as_deref() == Some("ja");
+        animated && japanese
+    }
+
+    /// The episode's position counting from the first episode of season 1,
+    /// as anime sites number them. Specials (season 0) keep their own numbers.
+    pub fn absolute_episode_number(&self, season: i64, episode: i64) -> i64 {
+        if season < 1 {
+            return episode;
+        }
+        let earlier: i64 = self
+            .seasons
+            .iter()
+            .filter(|s| s.season_number >= 1 && s.season_number < season)
+            .map(|s| s.episode_count)
+            .sum();
+        earlier + episode
+    }
+
+    /// The absolute number providers should get for an anime episode, or
+    /// `None` for shows that use TMDB's season numbering.
+    pub fn provider_episode_number(&self, season: i64, episode: i64) -> Option<i64> {
+        self.is_anime()
+            .then(|| self.absolute_episode_number(season, episode))
+    }
+}
+
+/// Ids on other sites, from `append_to_response=external_ids`. TVDB ids
+/// are only set for shows.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct ExternalIds {
+    #[serde(default)]
+    pub imdb_id: Option<String>,
+    #[serde(default)]
+    pub tvdb_id: Option<i64>,
+    #[serde(default)]
+    pub wikidata_id: Option<String>,
+    #[serde(default)]
+    pub facebook_id: Option<String>,
+    #[serde(default)]
+    pub instagram_id: Option<String>,
+    #[serde(default)]
+    pub twitter_id: Option<String>,
+}
+
+impl ExternalIds {
+    /// Outbound `(site, url)` links for the ids that are set.
+    pub fn links(&self) -> Vec<(&'static str, String)> {
+        let present = |id: &Option<String>| id.clone().filter(|id| !id.is_empty());
+        let mut links = Vec::new();
+        if let Some(id) = present(&self.imdb_id) {
+            links.push(("IMDb", format!("https://www.imdb.com/title/{}/", id)));
+        }
+        if let Some(id) = self.tvdb_id {
+            links.push(("TheTVDB", format!("https://thetvdb.com/dereferrer/series/{}", id)));
+        }
+        if let Some(id) = present(&self.wikidata_id) {
+            links.push(("Wikidata", format!("https://www.wikidata.org/wiki/{}", id)));
+        }
+        links
+    }
+}
+
+/// An age rating normalized for display, e.g. `PG-13`, `TV-MA`, or `FSK 16`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Certification {
+    /// Region the rating was issued for; differs from the configured region
+    /// when only the US rating was available.
+    pub region: String,
+    pub rating: String,
+}
+
+impl Certification {
+    fn new(region: &str, rating: &str) -> Option<Self> {
+        let rating = rating.trim();
+        if rating.is_empty() {
+            return None;
+        }
+
+        // Some boards publish bare ages; prefix them the way they are usually shown.
+        let is_age = rating.chars().all(|c| c.is_ascii_digit());
+        let rating = match region {
+            "DE" if is_age => format!("FSK {}", rating),
+            "AT" if is_age => format!("FSK {}", rating),
+            "NL" if is_age => format!("Kijkwijzer {}", rating),
+            _ => rating.to_string(),
+        };
+
+        Some(Self {
+            region: region.to_string(),
+            rating,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct ReleaseDates {
+    #[serde(default)]
+    pub results: Vec<RegionReleaseDates>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct RegionReleaseDates {
+    pub iso_3166_1: String,
+    #[serde(default)]
+    pub release_dates: Vec<ReleaseDate>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ReleaseDate {
+    #[serde(default)]
+    pub certification: String,
+    /// TMDB release type: 1 premiere, 2 limited, 3 theatrical, 4 digital, 5 physical, 6 TV.
+    #[serde(rename = "type")]
+    pub release_type: i64,
+}
+
+impl ReleaseDates {
+    /// Rating for `region`, falling back to the US rating. The theatrical
+    /// release's rating wins over other release types.
+    pub fn certification(&self, region: &str) -> Option<Certification> {
+        [region, DEFAULT_REGION].into_iter().find_map(|code| {
+            let entry = self.results.iter().find(|r| r.iso_3166_1 == code)?;
+            let mut dates: Vec<&ReleaseDate> = entry
+                .release_dates
+                .iter()
+                .filter(|d| !d.certification.trim().is_empty())
+                .collect();
+            dates.sort_by_key(|d| (d.release_type != 3, d.release_type));
+            dates
+                .first()
+                .and_then(|d| Certification::new(code, &d.certification))
+        })
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct ContentRatings {
+    #[serde(default)]
+    pub results: Vec<ContentRating>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ContentRating {
+    pub iso_3166_1: String,
+    #[serde(default)]
+    pub rating: String,
+}
+
+impl ContentRatings {
+    /// Rating for `region`, falling back to the US rating.
+    pub fn certification(&self, region: &str) -> Option<Certification> {
+        [region, DEFAULT_REGION].into_iter().find_map(|code| {
+            self.results
+                .iter()
+                .find(|r| r.iso_3166_1 == code)
+                .and_then(|r| Certification::new(code, &r.rating))
+        })
+    }
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -392,6 +2037,175 @@ pub struct Genre {
     pub name: String,
 }
 
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ImageConfiguration {
+    pub poster_sizes: Vec<String>,
+    pub backdrop_sizes: Vec<String>,
+    pub profile_sizes: Vec<String>,
+    pub logo_sizes: Vec<String>,
+    pub still_sizes: Vec<String>,
+}
+
+impl Default for ImageConfiguration {
+    fn default() -> Self {
+        let sizes = |list: &[&str]| list.iter().map(|s| s.to_string()).collect();
+        Self {
+            poster_sizes: sizes(&["w92", "w154", "w185", "w342", "w500", "w780", "original"]),
+            backdrop_sizes: sizes(&["w300", "w780", "w1280", "original"]),
+            profile_sizes: sizes(&["w45", "w185", "h632", "original"]),
+            logo_sizes: sizes(&["w45", "w92", "w154", "w185", "w300", "w500", "original"]),
+            still_sizes: sizes(&["w92", "w185", "w300", "original"]),
+        }
+    }
+}
+
+impl ImageConfiguration {
+    /// Smallest poster at least `width` pixels wide.
+    pub fn best_poster_size(&self, width: u32) -> &str {
+        best_size(&self.poster_sizes, width)
+    }
+
+    pub fn best_backdrop_size(&self, width: u32) -> &str {
+        best_size(&self.backdrop_sizes, width)
+    }
+
+    pub fn best_profile_size(&self, width: u32) -> &str {
+        best_size(&self.profile_sizes, width)
+    }
+
+    pub fn best_logo_size(&self, width: u32) -> &str {
+        best_size(&self.logo_sizes, width)
+    }
+
+    /// Width-based poster sizes up to `max_width`, for building a `srcset`.
+    pub fn poster_widths(&self, max_width: u32) -> Vec<(&str, u32)> {
+        self.poster_sizes
+            .iter()
+            .filter_map(|size| Some((size.as_str(), size_width(size)?)))
+            .filter(|(_, width)| *width <= max_width)
+            .collect()
+    }
+
+    pub fn is_known_size(&self, size: &str) -> bool {
+        [
+            &self.poster_sizes,
+            &self.backdrop_sizes,
+            &self.profile_sizes,
+            &self.logo_sizes,
+            &self.still_sizes,
+        ]
+        .iter()
+        .any(|sizes| sizes.iter().any(|s| s == size))
+    }
+}
+
+/// Pixel width of a `w<N>` size; height-based and `original` sizes have none.
+fn size_width(size: &str) -> Option<u32> {
+    size.strip_prefix('w')?.parse().ok()
+}
+
+fn best_size(sizes: &[String], width: u32) -> &str {
+    sizes
+        .iter()
+        .filter_map(|size| Some((size.as_str(), size_width(size)?)))
+        .filter(|(_, w)| *w >= width)
+        .min_by_key(|(_, w)| *w)
+        .map(|(size, _)| size)
+        .or_else(|| sizes.last().map(String::as_str))
+        .unwrap_or("original")
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct MediaGenre {
+    pub id: i64,
+    pub name: String,
+    /// `movie`, `tv`, or both.
+    pub media_types: Vec<String>,
+}
+
+impl MediaGenre {
+    pub fn is_tv_only(&self) -> bool {
+        self.media_types.iter().all(|m| m == "tv")
+    }
+}
+
+/// A user's TMDB v4 access token and account object id.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct AccountAccess {
+    pub access_token: String,
+    pub account_id: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AccountList {
+    Watchlist,
+    Rated,
+}
+
+impl AccountList {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AccountList::Watchlist => "watchlist",
+            AccountList::Rated => "rated",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct AccountTitlesPage {
+    pub page: i32,
+    pub total_pages: i32,
+    #[serde(default)]
+    pub results: Vec<AccountTitle>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct AccountTitle {
+    pub id: i64,
+    #[serde(default)]
+    pub media_type: String,
+    #[serde(default)]
+    pub title: Option<String>,
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub poster_path: Option<String>,
+    /// Only present on rated lists.
+    #[serde(default)]
+    pub account_rating: Option<AccountRating>,
+}
+
+impl AccountTitle {
+    pub fn display_title(&self) -> &str {
+        self.title
+            .as_deref()
+            .or(self.name.as_deref())
+            .unwrap_or("Unknown")
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct AccountRating {
+    pub value: f64,
+}
+
+/// A TV network or production company.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Organization {
+    pub id: i64,
+    pub name: String,
+    #[serde(default)]
+    pub logo_path: Option<String>,
+    #[serde(default)]
+    pub origin_country: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Keyword {
+    pub id: i64,
+    pub name: String,
+}
+
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct Credits {
     pub cast: Vec<CastMember>,
@@ -415,6 +2229,11 @@ pub struct SimilarTvShows {
     pub results: Vec<SearchResult>,
 }
 
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Recommendations {
+    pub results: Vec<SearchResult>,
+}
+
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct SeasonInfo {
     pub id: i64,
@@ -425,6 +2244,95 @@ pub struct SeasonInfo {
     pub episode_count: i64,
 }
 
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct SeasonDetail {
+    pub id: i64,
+    pub season_number: i64,
+    pub name: String,
+    #[serde(default)]
+    pub overview: Option<String>,
+    #[serde(default)]
+    pub air_date: Option<String>,
+    #[serde(default)]
+    pub poster_path: Option<String>,
+    #[serde(default)]
+    pub episodes: Vec<EpisodeInfo>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct EpisodeInfo {
+    pub id: i64,
+    pub episode_number: i64,
+    #[serde(default)]
+    pub name: String,
+    pub overview: Option<String>,
+    pub still_path: Option<String>,
+    pub air_date: Option<String>,
+    /// Minutes.
+    pub runtime: Option<i64>,
+    #[serde(default)]
+    pub vote_average: f64,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct FindResponse {
+    #[serde(default)]
+    pub movie_results: Vec<SearchResult>,
+    #[serde(default)]
+    pub tv_results: Vec<SearchResult>,
+}
+
+impl FindResponse {
+    /// The title an IMDb id refers to, preferring movies over shows.
+    pub fn first_title(&self) -> Option<&SearchResult> {
+        self.movie_results.first().or_else(|| self.tv_results.first())
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct WatchProvidersResponse {
+    pub id: i64,
+    #[serde(default)]
+    pub results: HashMap<String, WatchProviders>,
+}
+
+/// Availability of a title in a single region, as reported by JustWatch via TMDB.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct WatchProviders {
+    #[serde(default)]
+    pub link: Option<String>,
+    #[serde(default)]
+    pub flatrate: Vec<WatchProvider>,
+    #[serde(default)]
+    pub free: Vec<WatchProvider>,
+    #[serde(default)]
+    pub ads: Vec<WatchProvider>,
+    #[serde(default)]
+    pub rent: Vec<WatchProvider>,
+    #[serde(default)]
+    pub buy: Vec<WatchProvider>,
+}
+
+impl WatchProviders {
+    pub fn is_empty(&self) -> bool {
+        self.flatrate.is_empty()
+            && self.free.is_empty()
+            && self.ads.is_empty()
+            && self.rent.is_empty()
+            && self.buy.is_empty()
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct WatchProvider {
+    pub provider_id: i64,
+    pub provider_name: String,
+    #[serde(default)]
+    pub logo_path: Option<String>,
+    #[serde(default)]
+    pub display_priority: i64,
+}
+
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct MovieListResponse {
     pub page: i32,
@@ -0,0 +1,482 @@
+use std::collections::HashSet;
+use std::time::Duration;
+
+use tracing::{error, info, warn};
+
+use crate::auth::SessionSecrets;
+use crate::db::{self, Db};
+use crate::notifications::NotificationKind;
+use crate::tmdb::{AccountAccess, AccountList, AccountTitle, TmdbClient};
+use crate::AppState;
+
+/// How often accounts with sync turned on are synced in the background.
+const SYNC_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Stops runaway imports; 50 pages is 1,000 titles per list. A list cut
+/// short here isn't used to remove titles, since the rest weren't seen.
+const MAX_IMPORT_PAGES: i32 = 50;
+
+/// `user_settings` key holding a request token while the user approves it on TMDB.
+const REQUEST_TOKEN_KEY: &str = "tmdb_request_token";
+
+const TMDB_APPROVE_URL: &str = "https://www.themoviedb.org/auth/access";
+
+/// A connected TMDB account. The access token and session id are sealed
+/// with [`crate::auth::SessionSecrets::seal`] in `tmdb_accounts`; loaded
+/// accounts hold them opened.
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct TmdbAccount {
+    pub user_id: i64,
+    pub account_id: String,
+    pub access_token: String,
+    pub session_id: String,
+    pub v3_account_id: i64,
+    pub sync_enabled: bool,
+    pub last_synced_at: Option<String>,
+}
+
+impl TmdbAccount {
+    fn access(&self) -> AccountAccess {
+        AccountAccess {
+            access_token: self.access_token.clone(),
+            account_id: self.account_id.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct SyncReport {
+    pub pushed: usize,
+    pub watchlist: usize,
+    pub ratings: usize,
+    pub removed: usize,
+}
+
+pub async fn get_account(state: &AppState, user_id: i64) -> anyhow::Result<Option<TmdbAccount>> {
+    let account: Option<TmdbAccount> = db::query_as(
+        r#"
+        SELECT user_id, account_id, access_token, session_id, v3_account_id, sync_enabled, last_synced_at
+        FROM tmdb_accounts WHERE user_id = ?
+        "#
+    )
+    .bind(user_id)
+    .fetch_optional(&state.db)
+    .await?;
+    account.map(|account| open(&state.secrets, account)).transpose()
+}
+
+/// Opens a stored account's sealed credentials.
+fn open(secrets: &SessionSecrets, mut account: TmdbAccount) -> anyhow::Result<TmdbAccount> {
+    let (Some(access_token), Some(session_id)) = (secrets.open(&account.access_token), secrets.open(&account.session_id)) else {
+        anyhow::bail!("The TMDB account was saved under a different SESSION_SECRET; disconnect and connect it again");
+    };
+    account.access_token = access_token;
+    account.session_id = session_id;
+    Ok(account)
+}
+
+/// Seals credentials saved before they were sealed, and seals again the
+/// ones only `SESSION_SECRET_PREVIOUS` still opens. Run at startup.
+pub async fn seal_stored_tokens(state: &AppState) -> anyhow::Result<()> {
+    let accounts: Vec<(i64, String, String)> =
+        db::query_as("SELECT user_id, access_token, session_id FROM tmdb_accounts")
+            .fetch_all(&state.db)
+            .await?;
+    for (user_id, access_token, session_id) in accounts {
+        let resealed_token = state.secrets.reseal(&access_token);
+        let resealed_session = state.secrets.reseal(&session_id);
+        if resealed_token.is_none() && resealed_session.is_none() {
+            continue;
+        }
+        db::query("UPDATE tmdb_accounts SET access_token = ?, session_id = ? WHERE user_id = ?")
+            .bind(resealed_token.unwrap_or(access_token))
+            .bind(resealed_session.unwrap_or(session_id))
+            .bind(user_id)
+            .execute(&state.db)
+            .await?;
+        info!("Sealed the stored TMDB credentials of user {} under the current secret", user_id);
+    }
+    Ok(())
+}
+
+/// Creates a request token and returns the TMDB page where the user approves it.
+pub async fn begin_connect(
+    state: &AppState,
+    user_id: i64,
+    redirect_to: &str,
+) -> anyhow::Result<String> {
+    let request_token = state.tmdb.create_request_token(redirect_to).await?;
+    state
+        .auth
+        .set_setting(user_id, REQUEST_TOKEN_KEY, &request_token)
+        .await?;
+    Ok(format!("{}?request_token={}", TMDB_APPROVE_URL, request_token))
+}
+
+/// Completes sign-in after the user approved the request token, then runs
+/// the first import.
+pub async fn finish_connect(state: &AppState, user_id: i64) -> anyhow::Result<SyncReport> {
+    let request_token = state
+        .auth
+        .get_setting::<String>(user_id, REQUEST_TOKEN_KEY)
+        .await?
+        .ok_or_else(|| anyhow::anyhow!("No TMDB sign-in in progress"))?;
+
+    let access = state.tmdb.create_access_token(&request_token).await?;
+    let (session_id, v3_account_id) = state.tmdb.create_v3_session(&access.access_token).await?;
+
+    db::query(
+        r#"
+        INSERT INTO tmdb_accounts (user_id, account_id, access_token, session_id, v3_account_id)
+        VALUES (?, ?, ?, ?, ?)
+        ON CONFLICT(user_id) DO UPDATE SET
+            account_id = excluded.account_id,
+            access_token = excluded.access_token,
+            session_id = excluded.session_id,
+            v3_account_id = excluded.v3_account_id,
+            connected_at = datetime('now')
+        "#
+    )
+    .bind(user_id)
+    .bind(&access.account_id)
+    .bind(state.secrets.seal(&access.access_token))
+    .bind(state.secrets.seal(&session_id))
+    .bind(v3_account_id)
+    .execute(&state.db)
+    .await?;
+
+    db::query("DELETE FROM user_settings WHERE user_id = ? AND key = ?")
+        .bind(user_id)
+        .bind(REQUEST_TOKEN_KEY)
+        .execute(&state.db)
+        .await?;
+
+    let account = get_account(state, user_id)
+        .await?
+        .ok_or_else(|| anyhow::anyhow!("TMDB account was not saved"))?;
+    sync_account(&state.db, &state.tmdb, &account).await
+}
+
+/// Forgets the TMDB account. Imported titles stay, but are no longer synced.
+pub async fn disconnect(state: &AppState, user_id: i64) -> anyhow::Result<()> {
+    let db = &state.db;
+    match get_account(state, user_id).await {
+        Ok(Some(account)) => {
+            if let Err(e) = state.tmdb.revoke_access_token(&account.access_token).await {
+                warn!("Could not revoke TMDB access token: {}", e);
+            }
+        }
+        Ok(None) => {}
+        // Still forgotten here, even if it can't be revoked.
+        Err(e) => warn!("Could not revoke TMDB access token: {}", e),
+    }
+
+    db::query("DELETE FROM tmdb_accounts WHERE user_id = ?")
+        .bind(user_id)
+        .execute(db)
+        .await?;
+    for table in ["watchlist", "ratings"] {
+        db::query(&format!("UPDATE {} SET synced = FALSE WHERE user_id = ?", table))
+            .bind(user_id)
+            .execute(db)
+            .await?;
+    }
+    Ok(())
+}
+
+/// Takes a title off the user's TMDB watchlist after it was removed here,
+/// so the next sync doesn't bring it back. Does nothing without an account.
+pub async fn push_watchlist_removal(
+    state: &AppState,
+    user_id: i64,
+    media_type: &str,
+    tmdb_id: i64,
+) -> anyhow::Result<()> {
+    let Some(account) = get_account(state, user_id).await? else {
+        return Ok(());
+    };
+    state.tmdb.set_watchlist(&account.session_id, account.v3_account_id, media_type, tmdb_id, false)
+        .await
+}
+
+pub async fn set_sync_enabled(db: &Db, user_id: i64, enabled: bool) -> anyhow::Result<()> {
+    db::query("UPDATE tmdb_accounts SET sync_enabled = ? WHERE user_id = ?")
+        .bind(enabled)
+        .bind(user_id)
+        .execute(db)
+        .await?;
+    Ok(())
+}
+
+/// Pushes local watchlist entries and ratings TMDB has not seen, then
+/// imports the account's lists. Titles removed on TMDB are removed locally.
+pub async fn sync_account(
+    db: &Db,
+    tmdb: &TmdbClient,
+    account: &TmdbAccount,
+) -> anyhow::Result<SyncReport> {
+    let mut report = SyncReport::default();
+
+    let pending: Vec<(i64, String)> = db::query_as(
+        "SELECT tmdb_id, media_type FROM watchlist WHERE user_id = ? AND synced = FALSE"
+    )
+    .bind(account.user_id)
+    .fetch_all(db)
+    .await?;
+    for (tmdb_id, media_type) in pending {
+        tmdb.set_watchlist(&account.session_id, account.v3_account_id, &media_type, tmdb_id, true)
+            .await?;
+        report.pushed += 1;
+    }
+
+    let pending: Vec<(i64, String, f64)> = db::query_as(
+        "SELECT tmdb_id, media_type, rating FROM ratings WHERE user_id = ? AND synced = FALSE"
+    )
+    .bind(account.user_id)
+    .fetch_all(db)
+    .await?;
+    for (tmdb_id, media_type, rating) in pending {
+        tmdb.rate_title(&account.session_id, &media_type, tmdb_id, rating).await?;
+        report.pushed += 1;
+    }
+
+    let (watchlist, watchlist_complete) = fetch_list(tmdb, account, AccountList::Watchlist).await?;
+    let (ratings, ratings_complete) = fetch_list(tmdb, account, AccountList::Rated).await?;
+
+    let mut tx = db.begin().await?;
+    for item in &watchlist {
+        db::query(
+            r#"
+            INSERT INTO watchlist (user_id, tmdb_id, media_type, title, poster_path, synced)
+            VALUES (?, ?, ?, ?, ?, TRUE)
+            ON CONFLICT(user_id, tmdb_id, media_type) DO UPDATE SET
+                title = excluded.title,
+                poster_path = excluded.poster_path,
+                synced = TRUE
+            "#
+        )
+        .bind(account.user_id)
+        .bind(item.id)
+        .bind(&item.media_type)
+        .bind(item.display_title())
+        .bind(&item.poster_path)
+        .execute(&mut tx)
+        .await?;
+    }
+    for item in &ratings {
+        let Some(rating) = &item.account_rating else {
+            continue;
+        };
+        db::query(
+            r#"
+            INSERT INTO ratings (user_id, tmdb_id, media_type, title, rating, synced)
+            VALUES (?, ?, ?, ?, ?, TRUE)
+            ON CONFLICT(user_id, tmdb_id, media_type) DO UPDATE SET
+                title = excluded.title,
+                rating = excluded.rating,
+                synced = TRUE
+            "#
+        )
+        .bind(account.user_id)
+        .bind(item.id)
+        .bind(&item.media_type)
+        .bind(item.display_title())
+        .bind(rating.value)
+        .execute(&mut tx)
+        .await?;
+    }
+
+    // Synced rows TMDB no longer lists were removed there. A list longer
+    // than the import limit wasn't seen in full, so nothing is removed from it.
+    for (table, items, complete) in [
+        ("watchlist", &watchlist, watchlist_complete),
+        ("ratings", &ratings, ratings_complete),
+    ] {
+        if !complete {
+            warn!(
+                "TMDB {} of user {} is longer than {} pages; not removing titles missing from it",
+                table, account.user_id, MAX_IMPORT_PAGES
+            );
+            continue;
+        }
+        let remote: HashSet<(i64, &str)> = items
+            .iter()
+            .map(|item| (item.id, item.media_type.as_str()))
+            .collect();
+        let local: Vec<(i64, i64, String)> = db::query_as(&format!(
+            "SELECT id, tmdb_id, media_type FROM {} WHERE user_id = ? AND synced = TRUE",
+            table
+        ))
+        .bind(account.user_id)
+        .fetch_all(&mut tx)
+        .await?;
+        for (id, tmdb_id, media_type) in local {
+            if !remote.contains(&(tmdb_id, media_type.as_str())) {
+                db::query(&format!("DELETE FROM {} WHERE id = ?", table))
+                    .bind(id)
+                    .execute(&mut tx)
+                    .await?;
+                report.removed += 1;
+            }
+        }
+    }
+
+    db::query("UPDATE tmdb_accounts SET last_synced_at = datetime('now') WHERE user_id = ?")
+        .bind(account.user_id)
+        .execute(&mut tx)
+        .await?;
+    tx.commit().await?;
+
+    report.watchlist = watchlist.len();
+    report.ratings = ratings.len();
+    Ok(report)
+}
+
+/// Every title on one of the account's lists, up to [`MAX_IMPORT_PAGES`]
+/// per media type, and whether that was all of them.
+async fn fetch_list(
+    tmdb: &TmdbClient,
+    account: &TmdbAccount,
+    list: AccountList,
+) -> anyhow::Result<(Vec<AccountTitle>, bool)> {
+    let access = account.access();
+    let mut titles = Vec::new();
+    let mut complete = true;
+    for media_type in ["movie", "tv"] {
+        let mut page = 1;
+        loop {
+            let response = tmdb.get_account_titles(&access, list, media_type, page).await?;
+            titles.extend(response.results);
+            if page >= response.total_pages {
+                break;
+            }
+            if page >= MAX_IMPORT_PAGES {
+                complete = false;
+                break;
+            }
+            page += 1;
+        }
+    }
+    Ok((titles, complete))
+}
+
+#[derive(Debug, Default)]
+pub struct ListImport {
+    pub name: String,
+    pub added: usize,
+    pub skipped: usize,
+}
+
+/// Copies the movies and shows on a public TMDB list into the user's
+/// watchlist. No TMDB account is needed; if one is connected, the new
+/// entries are pushed on the next sync.
+pub async fn import_list(
+    db: &Db,
+    tmdb: &TmdbClient,
+    user_id: i64,
+    list_id: &str,
+) -> anyhow::Result<ListImport> {
+    let mut report = ListImport::default();
+    let mut items = Vec::new();
+    let mut page = 1;
+    loop {
+        let list = tmdb.get_list(list_id, page).await?;
+        if page == 1 {
+            report.name = list.name;
+        }
+        items.extend(list.items);
+        if page >= list.total_pages.min(MAX_IMPORT_PAGES) {
+            break;
+        }
+        page += 1;
+    }
+
+    let mut tx = db.begin().await?;
+    for item in items {
+        let title = item.title.as_ref().or(item.name.as_ref());
+        let (Some(title), "movie" | "tv") = (title, item.media_type.as_str()) else {
+            report.skipped += 1;
+            continue;
+        };
+        let result = db::query(
+            r#"
+            INSERT INTO watchlist (user_id, tmdb_id, media_type, title, poster_path)
+            VALUES (?, ?, ?, ?, ?)
+            ON CONFLICT(user_id, tmdb_id, media_type) DO NOTHING
+            "#
+        )
+        .bind(user_id)
+        .bind(item.id)
+        .bind(&item.media_type)
+        .bind(title)
+        .bind(&item.poster_path)
+        .execute(&mut tx)
+        .await?;
+        if result.rows_affected() > 0 {
+            report.added += 1;
+        } else {
+            report.skipped += 1;
+        }
+    }
+    tx.commit().await?;
+
+    Ok(report)
+}
+
+/// Syncs every account that opted in, once an hour.
+pub fn spawn_account_sync_job(state: AppState) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(SYNC_INTERVAL);
+        interval.tick().await;
+        loop {
+            interval.tick().await;
+            let accounts: Vec<TmdbAccount> = match db::query_as(
+                r#"
+                SELECT user_id, account_id, access_token, session_id, v3_account_id, sync_enabled, last_synced_at
+                FROM tmdb_accounts WHERE sync_enabled = TRUE
+                "#
+            )
+            .fetch_all(&state.db)
+            .await
+            {
+                Ok(accounts) => accounts,
+                Err(e) => {
+                    error!("Could not load TMDB accounts: {}", e);
+                    continue;
+                }
+            };
+
+            for account in accounts {
+                let account = match open(&state.secrets, account) {
+                    Ok(account) => account,
+                    Err(e) => {
+                        warn!("Skipping TMDB sync for a user: {}", e);
+                        continue;
+                    }
+                };
+                match sync_account(&state.db, &state.tmdb, &account).await {
+                    Ok(report) => {
+                        info!(
+                            "TMDB sync for user {}: {} pushed, {} watchlist, {} ratings, {} removed",
+                            account.user_id, report.pushed, report.watchlist, report.ratings, report.removed
+                        );
+                        // Hourly syncs that change nothing aren't worth a notification.
+                        if report.pushed + report.watchlist + report.ratings + report.removed > 0 {
+                            state.notifications.notify_user(
+                                account.user_id,
+                                NotificationKind::JobFinished,
+                                "TMDB sync finished",
+                                format!(
+                                    "{} pushed, {} watchlist, {} ratings, {} removed",
+                                    report.pushed, report.watchlist, report.ratings, report.removed
+                                ),
+                                Some("/settings".to_string()),
+                            ).await;
+                        }
+                    }
+                    Err(e) => warn!("TMDB sync for user {} failed: {}", account.user_id, e),
+                }
+            }
+        }
+    });
+}
@@ -0,0 +1,60 @@
+use std::sync::Mutex;
+use std::time::Instant;
+
+use dashmap::DashMap;
+
+use crate::tmdb_flight::TmdbResponse;
+
+/// Upper bound on remembered responses; the least recently stored is dropped.
+const MAX_ENTRIES: usize = 2000;
+
+#[derive(Debug)]
+struct Entry {
+    etag: String,
+    response: TmdbResponse,
+    stored_at: Instant,
+}
+
+/// Last successful response and its `ETag` for each TMDB URL. Requests for a
+/// known URL are sent with `If-None-Match`, and a 304 reuses the stored body.
+#[derive(Debug, Default)]
+pub struct EtagCache {
+    entries: DashMap<String, Entry>,
+    evicting: Mutex<()>,
+}
+
+impl EtagCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The stored `ETag` and response for `url`, if any.
+    pub fn get(&self, url: &str) -> Option<(String, TmdbResponse)> {
+        self.entries
+            .get(url)
+            .map(|entry| (entry.etag.clone(), entry.response.clone()))
+    }
+
+    pub fn store(&self, url: &str, etag: &str, response: &TmdbResponse) {
+        if self.entries.len() >= MAX_ENTRIES && !self.entries.contains_key(url) {
+            let _evicting = self.evicting.lock().unwrap();
+            let oldest = self
+                .entries
+                .iter()
+                .min_by_key(|entry| entry.stored_at)
+                .map(|entry| entry.key().clone());
+            if let Some(oldest) = oldest {
+                self.entries.remove(&oldest);
+            }
+        }
+
+        self.entries.insert(
+            url.to_string(),
+            Entry {
+                etag: etag.to_string(),
+                response: response.clone(),
+                stored_at: Instant::now(),
+            },
+        );
+    }
+}
@@ -0,0 +1,116 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+
+use reqwest::StatusCode;
+use serde::de::DeserializeOwned;
+use tokio::sync::OnceCell;
+use tracing::debug;
+
+/// A TMDB response read fully into memory so concurrent callers can share it.
+#[derive(Debug, Clone)]
+pub struct TmdbResponse {
+    status: StatusCode,
+    etag: Option<String>,
+    body: Arc<[u8]>,
+}
+
+impl TmdbResponse {
+    pub async fn read(response: reqwest::Response) -> reqwest::Result<Self> {
+        let status = response.status();
+        let etag = response
+            .headers()
+            .get(reqwest::header::ETAG)
+            .and_then(|v| v.to_str().ok())
+            .map(str::to_string);
+        let body = response.bytes().await?;
+        Ok(Self {
+            status,
+            etag,
+            body: body.to_vec().into(),
+        })
+    }
+
+    pub fn status(&self) -> StatusCode {
+        self.status
+    }
+
+    pub fn etag(&self) -> Option<&str> {
+        self.etag.as_deref()
+    }
+
+    pub fn json<T: DeserializeOwned>(&self) -> serde_json::Result<T> {
+        serde_json::from_slice(&self.body)
+    }
+
+    pub fn text(&self) -> String {
+        String::from_utf8_lossy(&self.body).into_owned()
+    }
+}
+
+type Flight = Arc<OnceCell<Result<TmdbResponse, String>>>;
+
+/// Coalesces identical concurrent requests: while a URL is being fetched,
+/// later callers for the same URL wait for that response instead of sending
+/// their own. Nothing is kept once the request finishes.
+#[derive(Debug, Default)]
+pub struct InFlight {
+    flights: Mutex<HashMap<String, Flight>>,
+}
+
+impl InFlight {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub async fn run<F, Fut>(&self, key: String, fetch: F) -> anyhow::Result<TmdbResponse>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = anyhow::Result<TmdbResponse>>,
+    {
+        let (flight, leader) = {
+            let mut flights = self.flights.lock().unwrap();
+            match flights.get(&key) {
+                Some(flight) => (flight.clone(), false),
+                None => {
+                    let flight = Flight::default();
+                    flights.insert(key.clone(), flight.clone());
+                    (flight, true)
+                }
+            }
+        };
+
+        // The first caller forgets the flight when it finishes or is dropped,
+        // so a cancelled request never leaves a cached result behind.
+        let _guard = leader.then(|| FlightGuard {
+            flights: &self.flights,
+            key: &key,
+            flight: &flight,
+        });
+        if !leader {
+            debug!("Joining in-flight TMDB request {}", key);
+        }
+
+        // If the caller doing the fetch is cancelled, the next waiter runs its own.
+        flight
+            .get_or_init(|| async { fetch().await.map_err(|e| e.to_string()) })
+            .await
+            .clone()
+            .map_err(|e| anyhow::anyhow!(e))
+    }
+}
+
+struct FlightGuard<'a> {
+    flights: &'a Mutex<HashMap<String, Flight>>,
+    key: &'a str,
+    flight: &'a Flight,
+}
+
+impl Drop for FlightGuard<'_> {
+    fn drop(&mut self) {
+        let mut flights = self.flights.lock().unwrap();
+        if flights.get(self.key).is_some_and(|f| Arc::ptr_eq(f, self.flight)) {
+            flights.remove(self.key);
+        }
+    }
+}
@@ -0,0 +1,157 @@
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+use tracing::warn;
+
+/// A key that TMDB rejected as invalid is retried after this long.
+const UNAUTHORIZED_BENCH: Duration = Duration::from_secs(60 * 60);
+
+/// Used when a 429 carries no `Retry-After`.
+const RATE_LIMIT_BENCH: Duration = Duration::from_secs(60);
+
+/// Width of the window behind `requests_last_minute`.
+const RATE_WINDOW: Duration = Duration::from_secs(60);
+
+#[derive(Debug)]
+struct ApiKey {
+    auth_header: String,
+    requests: AtomicU64,
+    state: Mutex<KeyState>,
+}
+
+#[derive(Debug)]
+struct KeyState {
+    benched_until: Option<Instant>,
+    window_start: Instant,
+    window_requests: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct KeyStatus {
+    /// 1-based position in `TMDB_API_KEY`; keys themselves are never exposed.
+    pub key: usize,
+    pub requests: u64,
+    pub requests_last_minute: u64,
+    pub benched_for_seconds: Option<u64>,
+}
+
+/// Round-robin pool of TMDB credentials. Keys that answer 401 or 429 are
+/// benched for a while so the remaining keys carry the load.
+#[derive(Debug)]
+pub struct KeyPool {
+    keys: Vec<ApiKey>,
+    next: AtomicUsize,
+}
+
+impl KeyPool {
+    /// Accepts one key or several separated by commas.
+    pub fn parse(keys: &str) -> anyhow::Result<Self> {
+        let keys: Vec<ApiKey> = keys
+            .split(',')
+            .map(str::trim)
+            .filter(|k| !k.is_empty())
+            .map(|k| ApiKey {
+                auth_header: if k.starts_with("Bearer ") {
+                    k.to_string()
+                } else {
+                    format!("Bearer {}", k)
+                },
+                requests: AtomicU64::new(0),
+                state: Mutex::new(KeyState {
+                    benched_until: None,
+                    window_start: Instant::now(),
+                    window_requests: 0,
+                }),
+            })
+            .collect();
+
+        if keys.is_empty() {
+            return Err(anyhow::anyhow!("TMDB_API_KEY does not contain any keys"));
+        }
+
+        Ok(Self {
+            keys,
+            next: AtomicUsize::new(0),
+        })
+    }
+
+    pub fn key_count(&self) -> usize {
+        self.keys.len()
+    }
+
+    /// Picks the next available key and counts a request against it. When
+    /// every key is benched, the one that recovers soonest is used anyway.
+    pub fn next_key(&self) -> (usize, &str) {
+        let now = Instant::now();
+        let start = self.next.fetch_add(1, Ordering::Relaxed);
+
+        let available = (0..self.keys.len())
+            .map(|offset| (start + offset) % self.keys.len())
+            .find(|&index| {
+                let state = self.keys[index].state.lock().unwrap();
+                state.benched_until.map_or(true, |until| until <= now)
+            });
+
+        let index = available.unwrap_or_else(|| {
+            (0..self.keys.len())
+                .min_by_key(|&index| self.keys[index].state.lock().unwrap().benched_until)
+                .unwrap_or(0)
+        });
+
+        let key = &self.keys[index];
+        key.requests.fetch_add(1, Ordering::Relaxed);
+        let mut state = key.state.lock().unwrap();
+        if now.duration_since(state.window_start) >= RATE_WINDOW {
+            state.window_start = now;
+            state.window_requests = 0;
+        }
+        state.window_requests += 1;
+
+        (index, key.auth_header.as_str())
+    }
+
+    /// Takes a key out of rotation after TMDB rejected it.
+    pub fn bench(&self, index: usize, status: reqwest::StatusCode, retry_after: Option<Duration>) {
+        let duration = if status == reqwest::StatusCode::UNAUTHORIZED {
+            UNAUTHORIZED_BENCH
+        } else {
+            retry_after.unwrap_or(RATE_LIMIT_BENCH)
+        };
+
+        if let Some(key) = self.keys.get(index) {
+            key.state.lock().unwrap().benched_until = Some(Instant::now() + duration);
+            warn!(
+                "TMDB key {} returned {}; benched for {}s",
+                index + 1,
+                status.as_u16(),
+                duration.as_secs()
+            );
+        }
+    }
+
+    pub fn status(&self) -> Vec<KeyStatus> {
+        let now = Instant::now();
+        self.keys
+            .iter()
+            .enumerate()
+            .map(|(index, key)| {
+                let state = key.state.lock().unwrap();
+                KeyStatus {
+                    key: index + 1,
+                    requests: key.requests.load(Ordering::Relaxed),
+                    requests_last_minute: if now.duration_since(state.window_start) < RATE_WINDOW {
+                        state.window_requests
+                    } else {
+                        0
+                    },
+                    benched_for_seconds: state
+                        .benched_until
+                        .filter(|until| *until > now)
+                        .map(|until| (until - now).as_secs()),
+                }
+            })
+            .collect()
+    }
+}
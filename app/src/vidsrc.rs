@@ -0,0 +1,101 @@
+use async_trait::async_trait;
+use tracing::debug;
+
+use crate::providers::{EmbedOptions, HostProbe, StreamKind, StreamProvider, StreamSource};
+
+const VIDSRC_BASE_URL: &str = "https://vidsrc.xyz";
+
+#[derive(Debug)]
+pub struct VidsrcClient {
+    probe: HostProbe,
+}
+
+impl VidsrcClient {
+    pub fn new() -> anyhow::Result<Self> {
+        Ok(Self {
+            probe: HostProbe::new(VIDSRC_BASE_URL)?,
+        })
+    }
+
+    /// Vidsrc takes no color or episode-selector options; it autoplays unless told not to.
+    fn query_suffix(options: &EmbedOptions) -> &'static str {
+        if options.auto_play {
+            ""
+        } else {
+            "&autoplay=0"
+        }
+    }
+
+    pub fn get_movie_embed_url(&self, tmdb_id: i64, options: &EmbedOptions) -> String {
+        let url = format!(
+            "{}/embed/movie?tmdb={}{}",
+            VIDSRC_BASE_URL,
+            tmdb_id,
+            Self::query_suffix(options)
+        );
+        debug!("Generated vidsrc movie embed URL: {}", url);
+        url
+    }
+
+    pub fn get_tv_embed_url(&self, tmdb_id: i64, season: i64, episode: i64, options: &EmbedOptions) -> String {
+        let url = format!(
+            "{}/embed/tv?tmdb={}&season={}&episode={}{}",
+            VIDSRC_BASE_URL,
+            tmdb_id,
+            season,
+            episode,
+            Self::query_suffix(options)
+        );
+        debug!("Generated vidsrc TV embed URL: {}", url);
+        url
+    }
+}
+
+#[async_trait]
+impl StreamProvider for VidsrcClient {
+    fn name(&self) -> &str {
+        "vidsrc"
+    }
+
+    fn priority(&self) -> i32 {
+        50
+    }
+
+    async fn is_reachable(&self, force: bool) -> bool {
+        self.probe.check(force).await
+    }
+
+    async fn get_movie_streams(&self, tmdb_id: i64, options: &EmbedOptions) -> anyhow::Result<Vec<StreamSource>> {
+        let url = self.get_movie_embed_url(tmdb_id, options);
+        self.probe.validate(&url).await?;
+
+        Ok(vec![StreamSource {
+            id: url,
+            name: "Vidsrc".to_string(),
+            quality: Some("Auto".to_string()),
+            language: None,
+            server: "vidsrc".to_string(),
+            kind: StreamKind::Embed,
+        }])
+    }
+
+    async fn get_tv_streams(
+        &self,
+        tmdb_id: i64,
+        season: i64,
+        episode: i64,
+        options: &EmbedOptions,
+    ) -> anyhow::Result<Vec<StreamSource>> {
+        let url = self.get_tv_embed_url(tmdb_id, season, episode, options);
+        self.probe.validate(&url).await?;
+
+        Ok(vec![StreamSource {
+            id: url,
+            name: "Vidsrc".to_string(),
+            quality: Some("Auto".to_string()),
+            language: None,
+            server: "vidsrc".to_string(),
+            kind: StreamKind::Embed,
+        }])
+    }
+}
@@ -0,0 +1,440 @@
+use std::time::Duration;
+
+use hmac::{Hmac, Mac};
+use reqwest::{Client, Url};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sha2::Sha256;
+use tokio::sync::Notify;
+use tracing::{debug, error, info, warn};
+
+use crate::auth::{Session, SessionSecrets};
+use crate::db::{self, Db};
+use crate::AppState;
+
+/// How often due retries are looked for when nothing new was queued.
+const POLL_INTERVAL: Duration = Duration::from_secs(15);
+
+/// A delivery is given up on after this many failed attempts.
+const MAX_ATTEMPTS: i64 = 6;
+
+/// Delay before the first retry, doubled after every further failure.
+const RETRY_BASE_SECS: i64 = 30;
+
+/// Deliveries sent per pass, so one pass can't hold a huge backlog in memory.
+const BATCH_SIZE: i64 = 50;
+
+/// Finished deliveries are kept this long for the admin API.
+pub const DELIVERY_RETENTION_DAYS: u32 = 7;
+
+/// Header carrying `sha256=<hex HMAC>` of the timestamp, a `.`, and the body,
+/// keyed by the webhook's secret.
+pub const SIGNATURE_HEADER: &str = "X-RustStream-Signature";
+
+/// Header carrying the Unix time the delivery was signed at, so receivers can
+/// refuse old deliveries replayed to them.
+pub const TIMESTAMP_HEADER: &str = "X-RustStream-Timestamp";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum WebhookEvent {
+    #[serde(rename = "playback.started")]
+    PlaybackStarted,
+    #[serde(rename = "playback.completed")]
+    PlaybackCompleted,
+    #[serde(rename = "watchlist.added")]
+    WatchlistAdded,
+}
+
+impl WebhookEvent {
+    pub const ALL: [WebhookEvent; 3] = [
+        WebhookEvent::PlaybackStarted,
+        WebhookEvent::PlaybackCompleted,
+        WebhookEvent::WatchlistAdded,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            WebhookEvent::PlaybackStarted => "playback.started",
+            WebhookEvent::PlaybackCompleted => "playback.completed",
+            WebhookEvent::WatchlistAdded => "watchlist.added",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|event| event.as_str() == name)
+    }
+}
+
+/// A registered webhook. The secret is only shown when it is created.
+#[derive(Debug, Clone, Serialize)]
+pub struct Webhook {
+    pub id: i64,
+    pub url: String,
+    pub events: Vec<WebhookEvent>,
+    pub enabled: bool,
+    pub created_at: String,
+}
+
+#[derive(sqlx::FromRow)]
+struct WebhookRow {
+    id: i64,
+    url: String,
+    events: String,
+    enabled: bool,
+    created_at: String,
+}
+
+impl From<WebhookRow> for Webhook {
+    fn from(row: WebhookRow) -> Self {
+        Self {
+            id: row.id,
+            url: row.url,
+            events: row.events.split(',').filter_map(WebhookEvent::parse).collect(),
+            enabled: row.enabled,
+            created_at: row.created_at,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct WebhookDelivery {
+    pub id: i64,
+    pub event: String,
+    /// `pending`, `delivered`, or `failed` once attempts run out.
+    pub status: String,
+    pub attempts: i64,
+    pub response_status: Option<i64>,
+    pub last_error: Option<String>,
+    pub next_attempt_at: String,
+    pub created_at: String,
+    pub delivered_at: Option<String>,
+}
+
+#[derive(sqlx::FromRow)]
+struct DueDelivery {
+    id: i64,
+    event: String,
+    payload: String,
+    attempts: i64,
+    url: String,
+    secret: String,
+}
+
+/// Admin-configured webhooks and the queue of deliveries to them. Signing
+/// secrets are stored sealed with [`SessionSecrets::seal`], like TMDB tokens.
+#[derive(Debug)]
+pub struct Webhooks {
+    db: Db,
+    client: Client,
+    queued: Notify,
+    secrets: SessionSecrets,
+}
+
+impl Webhooks {
+    pub fn new(db: Db, secrets: SessionSecrets) -> anyhow::Result<Self> {
+        let client = crate::outbound::client_builder(Duration::from_secs(10)).build()?;
+
+        Ok(Self {
+            db,
+            client,
+            queued: Notify::new(),
+            secrets,
+        })
+    }
+
+    /// Seals secrets saved before they were sealed, and seals again the ones
+    /// only `SESSION_SECRET_PREVIOUS` still opens. Run at startup.
+    pub async fn seal_stored_secrets(&self) -> anyhow::Result<()> {
+        let webhooks: Vec<(i64, String)> = db::query_as("SELECT id, secret FROM webhooks")
+            .fetch_all(&self.db)
+            .await?;
+        for (id, secret) in webhooks {
+            let Some(resealed) = self.secrets.reseal(&secret) else {
+                continue;
+            };
+            db::query("UPDATE webhooks SET secret = ? WHERE id = ?")
+                .bind(resealed)
+                .bind(id)
+                .execute(&self.db)
+                .await?;
+            info!("Sealed the stored secret of webhook {} under the current secret", id);
+        }
+        Ok(())
+    }
+
+    pub async fn list(&self) -> anyhow::Result<Vec<Webhook>> {
+        let rows: Vec<WebhookRow> = db::query_as(
+            "SELECT id, url, events, enabled, created_at FROM webhooks ORDER BY id"
+        )
+        .fetch_all(&self.db)
+        .await?;
+        Ok(rows.into_iter().map(Webhook::from).collect())
+    }
+
+    /// Registers a webhook and returns it with its newly generated secret.
+    pub async fn create(
+        &self,
+        url: &str,
+        events: &[WebhookEvent],
+        user_id: i64,
+    ) -> anyhow::Result<(Webhook, String)> {
+        let secret = format!("{}{}", uuid::Uuid::new_v4().simple(), uuid::Uuid::new_v4().simple());
+        let events = events.iter().map(|e| e.as_str()).collect::<Vec<_>>().join(",");
+        let row: WebhookRow = db::query_as(
+            r#"
+            INSERT INTO webhooks (url, secret, events, created_by)
+            VALUES (?, ?, ?, ?)
+            RETURNING id, url, events, enabled, created_at
+            "#
+        )
+        .bind(url)
+        .bind(self.secrets.seal(&secret))
+        .bind(events)
+        .bind(user_id)
+        .fetch_one(&self.db)
+        .await?;
+        Ok((row.into(), secret))
+    }
+
+    /// Pauses or resumes a webhook. Pausing drops its pending deliveries, so
+    /// resuming doesn't send stale events. Returns `None` when there is no
+    /// such webhook.
+    pub async fn set_enabled(&self, id: i64, enabled: bool) -> anyhow::Result<Option<Webhook>> {
+        let mut tx = self.db.begin().await?;
+        let row: Option<WebhookRow> = db::query_as(
+            r#"
+            UPDATE webhooks SET enabled = ? WHERE id = ?
+            RETURNING id, url, events, enabled, created_at
+            "#
+        )
+        .bind(enabled)
+        .bind(id)
+        .fetch_optional(&mut tx)
+        .await?;
+        if !enabled {
+            db::query("DELETE FROM webhook_deliveries WHERE webhook_id = ? AND status = 'pending'")
+                .bind(id)
+                .execute(&mut tx)
+                .await?;
+        }
+        tx.commit().await?;
+        Ok(row.map(Webhook::from))
+    }
+
+    /// Deletes a webhook and its deliveries. Returns whether it existed.
+    pub async fn remove(&self, id: i64) -> anyhow::Result<bool> {
+        let result = db::query("DELETE FROM webhooks WHERE id = ?")
+            .bind(id)
+            .execute(&self.db)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// A webhook's most recent deliveries, newest first.
+    pub async fn deliveries(&self, id: i64, limit: i64) -> anyhow::Result<Vec<WebhookDelivery>> {
+        let deliveries = db::query_as(
+            r#"
+            SELECT id, event, status, attempts, response_status, last_error,
+                   next_attempt_at, created_at, delivered_at
+            FROM webhook_deliveries
+            WHERE webhook_id = ?
+            ORDER BY id DESC
+            LIMIT ?
+            "#
+        )
+        .bind(id)
+        .bind(limit)
+        .fetch_all(&self.db)
+        .await?;
+        Ok(deliveries)
+    }
+
+    /// Queues `event` for every enabled webhook subscribed to it. Failures
+    /// are logged rather than returned so they never fail the request that
+    /// caused the event.
+    pub async fn emit(&self, event: WebhookEvent, data: Value) {
+        let payload = json!({
+            "event": event.as_str(),
+            "created_at": chrono::Utc::now().to_rfc3339(),
+            "data": data,
+        })
+        .to_string();
+
+        let result = db::query(
+            r#"
+            INSERT INTO webhook_deliveries (webhook_id, event, payload)
+            SELECT id, ?, ? FROM webhooks
+            WHERE enabled = TRUE AND instr(',' || events || ',', ',' || ? || ',') > 0
+            "#
+        )
+        .bind(event.as_str())
+        .bind(&payload)
+        .bind(event.as_str())
+        .execute(&self.db)
+        .await;
+
+        match result {
+            Ok(r) if r.rows_affected() > 0 => self.queued.notify_one(),
+            Ok(_) => {}
+            Err(e) => warn!("Failed to queue {} webhooks: {}", event.as_str(), e),
+        }
+    }
+
+    /// Sends every delivery that is due and records the outcome. Returns
+    /// how many were attempted.
+    pub async fn deliver_due(&self) -> anyhow::Result<usize> {
+        let due: Vec<DueDelivery> = db::query_as(
+            r#"
+            SELECT d.id, d.event, d.payload, d.attempts, w.url, w.secret
+            FROM webhook_deliveries d
+            JOIN webhooks w ON w.id = d.webhook_id
+            WHERE d.status = 'pending' AND d.next_attempt_at <= datetime('now') AND w.enabled = TRUE
+            ORDER BY d.id
+            LIMIT ?
+            "#
+        )
+        .bind(BATCH_SIZE)
+        .fetch_all(&self.db)
+        .await?;
+
+        for delivery in &due {
+            let (response_status, error) = match self.send(delivery).await {
+                Ok(status) if status.is_success() => (Some(status.as_u16()), None),
+                Ok(status) => (Some(status.as_u16()), Some(format!("HTTP {}", status))),
+                Err(e) => (None, Some(e)),
+            };
+            self.record_attempt(delivery, response_status, error.as_deref()).await?;
+        }
+
+        Ok(due.len())
+    }
+
+    /// Posts a delivery. Errors are kept vague, since admins see them: the
+    /// URL is checked again in case it was saved before the checks existed.
+    async fn send(&self, delivery: &DueDelivery) -> Result<reqwest::StatusCode, String> {
+        let url = Url::parse(&delivery.url).map_err(|e| format!("Invalid url: {}", e))?;
+        crate::outbound::check_url(&url)?;
+        let secret = self
+            .secrets
+            .open(&delivery.secret)
+            .ok_or_else(|| "Secret was saved under a different SESSION_SECRET; delete and register the webhook again".to_string())?;
+        let timestamp = chrono::Utc::now().timestamp();
+        let response = self
+            .client
+            .post(url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .header("X-RustStream-Event", &delivery.event)
+            .header("X-RustStream-Delivery", delivery.id.to_string())
+            .header(TIMESTAMP_HEADER, timestamp.to_string())
+            .header(
+                SIGNATURE_HEADER,
+                format!("sha256={}", sign(&secret, timestamp, &delivery.payload)),
+            )
+            .body(delivery.payload.clone())
+            .send()
+            .await
+            .map_err(|e| {
+                debug!("Webhook delivery {} failed: {:?}", delivery.id, e);
+                if e.is_timeout() {
+                    "Timed out".to_string()
+                } else {
+                    "Could not connect".to_string()
+                }
+            })?;
+        Ok(response.status())
+    }
+
+    async fn record_attempt(
+        &self,
+        delivery: &DueDelivery,
+        response_status: Option<u16>,
+        error: Option<&str>,
+    ) -> anyhow::Result<()> {
+        let attempts = delivery.attempts + 1;
+        let Some(error) = error else {
+            db::query(
+                r#"
+                UPDATE webhook_deliveries
+                SET status = 'delivered', attempts = ?, response_status = ?, last_error = NULL,
+                    delivered_at = datetime('now')
+                WHERE id = ?
+                "#
+            )
+            .bind(attempts)
+            .bind(response_status)
+            .bind(delivery.id)
+            .execute(&self.db)
+            .await?;
+            return Ok(());
+        };
+
+        let status = if attempts >= MAX_ATTEMPTS {
+            warn!("Giving up on webhook delivery {} to {}: {}", delivery.id, delivery.url, error);
+            "failed"
+        } else {
+            debug!("Webhook delivery {} to {} failed, will retry: {}", delivery.id, delivery.url, error);
+            "pending"
+        };
+        let retry_in = RETRY_BASE_SECS << (attempts - 1);
+        db::query(
+            r#"
+            UPDATE webhook_deliveries
+            SET status = ?, attempts = ?, response_status = ?, last_error = ?,
+                next_attempt_at = datetime('now', ?)
+            WHERE id = ?
+            "#
+        )
+        .bind(status)
+        .bind(attempts)
+        .bind(response_status)
+        .bind(error)
+        .bind(format!("+{} seconds", retry_in))
+        .bind(delivery.id)
+        .execute(&self.db)
+        .await?;
+        Ok(())
+    }
+
+    /// Deletes delivered and failed deliveries older than `days`.
+    pub async fn prune_deliveries(&self, days: u32) -> anyhow::Result<u64> {
+        let result = db::query(
+            "DELETE FROM webhook_deliveries WHERE status != 'pending' AND created_at < datetime('now', ?)"
+        )
+        .bind(format!("-{} days", days))
+        .execute(&self.db)
+        .await?;
+        Ok(result.rows_affected())
+    }
+}
+
+/// Who caused an event, as it appears in payloads.
+pub fn user_json(session: &Session) -> Value {
+    json!({ "id": session.user_id, "username": session.username })
+}
+
+/// Hex HMAC-SHA256 of `<timestamp>.<payload>`, which receivers recompute to
+/// check a delivery came from this instance, and when.
+fn sign(secret: &str, timestamp: i64, payload: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC can accept any key size");
+    mac.update(format!("{}.{}", timestamp, payload).as_bytes());
+    hex::encode(mac.finalize().into_bytes())
+}
+
+/// Sends queued deliveries as soon as they are queued, and retries failed
+/// ones once their backoff has passed.
+pub fn spawn_delivery_job(state: AppState) {
+    tokio::spawn(async move {
+        loop {
+            match state.webhooks.deliver_due().await {
+                // A full batch may have left more behind.
+                Ok(sent) if sent as i64 == BATCH_SIZE => continue,
+                Ok(_) => {}
+                Err(e) => error!("Webhook delivery failed: {}", e),
+            }
+            tokio::select! {
+                _ = state.webhooks.queued.notified() => {}
+                _ = tokio::time::sleep(POLL_INTERVAL) => {}
+            }
+        }
+    });
+}
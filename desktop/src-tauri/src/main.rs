@@ -1,5 +1,5 @@
 use std::net::{SocketAddr, TcpStream};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::process::{Child, Command};
 use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant};
@@ -8,6 +8,9 @@ use tauri::{Manager, State, WindowUrl};
 
 const DEFAULT_PORT: u16 = 3000;
 
+/// Extra ports the backend may try when the preferred one is taken.
+const PORT_RETRIES: u16 = 10;
+
 struct BackendState {
     child: Arc<Mutex<Option<Child>>>,
 }
@@ -60,17 +63,39 @@ fn start_backend_and_open_main(
 ) {
     std::thread::spawn(move || {
         let port = read_port();
-        if !is_port_open(port) {
-            match spawn_backend(&app_handle, port) {
-                Ok(child) => {
-                    *child_slot.lock().expect("backend lock") = Some(child);
+        let port_file = match port_file_path(&app_handle) {
+            Ok(path) => path,
+            Err(err) => {
+                eprintln!("Failed to resolve port file: {err}");
+                return;
+            }
+        };
+
+        // A backend left running by an earlier launch is reused as long as its
+        // port file still points at a live server.
+        let port = match read_port_file(&port_file).filter(|p| is_port_open(*p)) {
+            Some(port) => port,
+            None => {
+                std::fs::remove_file(&port_file).ok();
+                match spawn_backend(&app_handle, port, &port_file) {
+                    Ok(child) => {
+                        *child_slot.lock().expect("backend lock") = Some(child);
+                    }
+                    Err(err) => {
+                        eprintln!("Failed to start backend: {err}");
+                        return;
+                    }
                 }
-                Err(err) => {
-                    eprintln!("Failed to start backend: {err}");
-                    return;
+
+                match wait_for_port_file(&port_file, Duration::from_secs(20)) {
+                    Some(port) => port,
+                    None => {
+                        eprintln!("Backend did not report a port in {}", port_file.display());
+                        return;
+                    }
                 }
             }
-        }
+        };
 
         if !wait_for_port(port, Duration::from_secs(20)) {
             eprintln!("Backend did not become ready on port {port}");
@@ -164,28 +189,28 @@ fn read_port() -> u16 {
         .unwrap_or(DEFAULT_PORT)
 }
 
-fn spawn_backend(app: &tauri::AppHandle, port: u16) -> anyhow::Result<Child> {
+fn spawn_backend(app: &tauri::AppHandle, port: u16, port_file: &Path) -> anyhow::Result<Child> {
     ensure_default_env(app)?;
     let database_url = build_database_url(app)?;
 
     if let Some(path) = resolve_packaged_backend(app) {
         let env_path = default_env_path(app)?;
-        return spawn_command(path, port, Some(database_url), Some(env_path));
+        return spawn_command(path, port, Some(database_url), Some(env_path), port_file);
     }
 
     if let Ok(path) = std::env::var("RUSTSTREAM_BACKEND") {
         let env_path = default_env_path(app)?;
-        return spawn_command(PathBuf::from(path), port, Some(database_url), Some(env_path));
+        return spawn_command(PathBuf::from(path), port, Some(database_url), Some(env_path), port_file);
     }
 
     if let Some(path) = resolve_local_backend_near_exe() {
         let env_path = default_env_path(app)?;
-        return spawn_command(path, port, Some(database_url), Some(env_path));
+        return spawn_command(path, port, Some(database_url), Some(env_path), port_file);
     }
 
     if let Some(path) = resolve_workspace_backend() {
         let env_path = default_env_path(app)?;
-        return spawn_command(path, port, Some(database_url), Some(env_path));
+        return spawn_command(path, port, Some(database_url), Some(env_path), port_file);
     }
 
     anyhow::bail!("Unable to locate backend binary");
@@ -196,9 +221,12 @@ fn spawn_command(
     port: u16,
     database_url: Option<String>,
     env_path: Option<PathBuf>,
+    port_file: &Path,
 ) -> anyhow::Result<Child> {
     let mut cmd = Command::new(path);
     cmd.env("PORT", port.to_string());
+    cmd.env("PORT_RETRIES", PORT_RETRIES.to_string());
+    cmd.env("PORT_FILE", port_file);
     if let Some(url) = database_url {
         cmd.env("DATABASE_URL", url);
     }
@@ -268,6 +296,31 @@ fn build_database_url(app: &tauri::AppHandle) -> anyhow::Result<String> {
     Ok(db_path.to_string_lossy().to_string())
 }
 
+fn port_file_path(app: &tauri::AppHandle) -> anyhow::Result<PathBuf> {
+    let data_dir = tauri::api::path::app_data_dir(&app.config())
+        .ok_or_else(|| anyhow::anyhow!("Unable to resolve app data directory"))?;
+    std::fs::create_dir_all(&data_dir)?;
+    Ok(data_dir.join("ruststream.port"))
+}
+
+/// Reads the port the backend reported after binding.
+fn read_port_file(path: &Path) -> Option<u16> {
+    let contents = std::fs::read_to_string(path).ok()?;
+    let value: serde_json::Value = serde_json::from_str(&contents).ok()?;
+    value.get("port")?.as_u64()?.try_into().ok()
+}
+
+fn wait_for_port_file(path: &Path, timeout: Duration) -> Option<u16> {
+    let start = Instant::now();
+    while start.elapsed() < timeout {
+        if let Some(port) = read_port_file(path) {
+            return Some(port);
+        }
+        std::thread::sleep(Duration::from_millis(200));
+    }
+    None
+}
+
 fn default_env_path(app: &tauri::AppHandle) -> anyhow::Result<PathBuf> {
     let data_dir = tauri::api::path::app_data_dir(&app.config())
         .ok_or_else(|| anyhow::anyhow!("Unable to resolve app data directory"))?;